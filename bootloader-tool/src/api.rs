@@ -0,0 +1,63 @@
+//! Flows of the command line tool as library functions, for other Rust programs to drive the tool
+//!
+//! These functions never print nor exit the process. They yield their results instead, and only log progress.
+//! Arguments are the same as on the command line, see for example [SignArguments::new](crate::SignArguments::new).
+
+use std::path::Path;
+
+use anyhow::Context;
+
+pub use crate::commands::download::DownloadOutput;
+pub use crate::commands::sign::SignOutput;
+use crate::config::Config;
+use crate::processors::certificates::Rkth;
+use crate::processors::mbi::SignedImage;
+use crate::processors::mbi::diff::{self, Difference};
+use crate::processors::mbi::headers::Headers;
+use crate::processors::mbi::rom::{self, Authenticated, Rejection};
+use crate::{RunCommands, SignBatchArguments, SignCommands};
+
+/// Sign a bootloader or application image, like the `sign` command
+pub async fn sign(config: &Config, command: SignCommands) -> anyhow::Result<SignOutput> {
+    crate::commands::sign::process(config, command).await
+}
+
+/// Sign several images at once, like the `sign batch` command, yielding the outputs in the order of the images
+pub fn sign_batch(config: &Config, args: SignBatchArguments) -> anyhow::Result<Vec<SignOutput>> {
+    crate::commands::sign::batch(config, args)
+}
+
+/// Sign an image unless nothing changed since it was last signed, and flash it, like the `download` command
+///
+/// Yields the probe session, such that the caller can configure the device further before resetting it.
+pub async fn download(config: &Config, command: RunCommands) -> anyhow::Result<DownloadOutput> {
+    crate::commands::download::process_other(config, command).await
+}
+
+/// Check that the signed image at `path` is signed for `rkth`, like the `inspect verify` command
+pub fn verify(path: impl AsRef<Path>, rkth: &Rkth) -> anyhow::Result<SignedImage> {
+    let image = SignedImage::from_file(path)?;
+    image.check(rkth)?;
+    Ok(image)
+}
+
+/// Authenticate the signed image at `path` like the ROM does, like the `inspect verify --like-rom` command
+///
+/// Only fails if the image cannot be read, yielding why the ROM would reject it otherwise.
+pub fn verify_like_rom(path: impl AsRef<Path>, rkth: &Rkth) -> anyhow::Result<Result<Authenticated, Rejection>> {
+    let path = path.as_ref();
+    let raw = std::fs::read(path).with_context(|| format!("Could not read {}", path.display()))?;
+    Ok(rom::authenticate(&raw, rkth))
+}
+
+/// Differences between the signed images at `a` and `b`, like the `inspect diff` command
+pub fn diff(a: impl AsRef<Path>, b: impl AsRef<Path>) -> anyhow::Result<Vec<Difference>> {
+    diff::diff(&SignedImage::from_file(a)?, &SignedImage::from_file(b)?)
+}
+
+/// Headers of the binary at `path`, decoded as far as they fit, like the `inspect mbi` command
+pub fn headers(path: impl AsRef<Path>) -> anyhow::Result<Headers> {
+    let path = path.as_ref();
+    let raw = std::fs::read(path).with_context(|| format!("Could not read {}", path.display()))?;
+    Headers::decode(&raw)
+}
@@ -0,0 +1,33 @@
+use crate::CleanArguments;
+use crate::config::Config;
+use crate::processors::clean;
+
+pub fn process(config: &Config, args: CleanArguments, dry_run: bool) -> anyhow::Result<()> {
+    if args.images.is_empty() && !args.keys {
+        return Err(anyhow::anyhow!("Nothing to clean, pass --images and/or --keys"));
+    }
+
+    let mut files = args
+        .images
+        .iter()
+        .flat_map(|image| clean::image_files(config, image, args.artifact_dir.as_deref()))
+        .collect::<Vec<_>>();
+    if args.keys {
+        log::warn!("Removing key material, which can not be recovered");
+        files.extend(clean::key_files(config));
+    }
+
+    let removed = clean::remove(&files, dry_run)?;
+    for file in &removed {
+        if dry_run {
+            println!("Would remove {}", file.display());
+        } else {
+            println!("Removed {}", file.display());
+        }
+    }
+    if removed.is_empty() {
+        println!("Nothing to remove");
+    }
+
+    Ok(())
+}
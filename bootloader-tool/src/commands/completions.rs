@@ -0,0 +1,10 @@
+use anyhow::Context;
+
+use crate::CompletionsArguments;
+use crate::completion;
+
+pub fn process(args: CompletionsArguments) -> anyhow::Result<()> {
+    // Call back into this very binary, such that completions match its version even when it is not on PATH.
+    let completer = std::env::current_exe().context("Could not locate the bootloader-tool binary")?;
+    completion::write_registration(&args.shell, &completer.to_string_lossy(), &mut std::io::stdout())
+}
@@ -0,0 +1,110 @@
+use anyhow::bail;
+use itertools::Itertools;
+
+use crate::config::Config;
+use crate::processors::debug::{self, DebugAccess, Feature};
+use crate::processors::fuse::{self, Word};
+use crate::processors::plan::Operation;
+use crate::processors::probe;
+use crate::{LockArguments, ProbeArgs, UnlockArguments};
+
+pub async fn lock(args: LockArguments, dry_run: bool) -> anyhow::Result<()> {
+    let access = DebugAccess::locked(&args.allow, args.uuid_check);
+    println!("Restricting {}", describe(&access.restricted()?));
+
+    if dry_run {
+        super::print_plan(&[
+            Operation::write(fuse::DCFG_CC_SOCU, "DCFG_CC_SOCU", &[access.socu, access.socu_ns]),
+            Operation::Reset,
+        ]);
+    } else {
+        log::debug!("Starting probe session...");
+        let mut session = probe::start_session(&args.probe_args.chip, args.probe_args.probe.clone()).await?;
+        let mut core = session.core(0)?;
+
+        let current = DebugAccess::read(&mut core)?;
+        if !current.is_open() && current != access {
+            log::warn!(
+                "Device already restricts debug access with DCFG_CC_SOCU {:#010x}, which burning can not undo",
+                current.socu
+            );
+        }
+
+        log::info!("Setting shadow registers on target");
+        access.write_shadow(&mut core)?;
+        core.reset()?;
+
+        println!("Device reset, debug access is restricted until the next power cycle");
+    }
+
+    println!("To restrict debug access permanently, burn:");
+    let words = [
+        (fuse::DCFG_CC_SOCU, "DCFG_CC_SOCU", access.socu),
+        (fuse::DCFG_CC_SOCU_NS, "DCFG_CC_SOCU_NS", access.socu_ns),
+    ];
+    for (index, name, value) in words {
+        println!("  {}: {value:#010x}", Word { index, name });
+    }
+
+    Ok(())
+}
+
+pub async fn unlock(config: &Config, args: UnlockArguments, dry_run: bool) -> anyhow::Result<()> {
+    if dry_run {
+        // Whether debug access is restricted can not be told without reading from the device, so assume it is.
+        let operation = args.credential.map(|credential| Operation::Authenticate { credential });
+        super::print_plan(operation.as_slice());
+        return Ok(());
+    }
+
+    // Restricted debug access may prevent the probe from reading anything at all.
+    match read(&args.probe_args).await {
+        Ok(access) => {
+            let restricted = access.restricted()?;
+            if restricted.is_empty() {
+                println!("Debug access is already open");
+                return Ok(());
+            }
+            log::info!("Device restricts {}", describe(&restricted));
+        }
+        Err(e) => log::info!("Could not read the debug access configuration, assuming it is restricted: {e:#}"),
+    }
+
+    let (Some(credential), Some(key)) = (&args.credential, &args.key) else {
+        bail!(
+            "Debug access is restricted, pass --credential and --key to authenticate, \
+             or power cycle the device if it was only locked in the shadow registers"
+        );
+    };
+
+    let serial = args.probe_args.probe.as_deref().and_then(debug::probe_serial);
+    log::info!("Authenticating with debug credential {}", credential.display());
+    debug::authenticate(
+        &args.nxpdebugmbox_path,
+        &config.mbi.family,
+        serial,
+        credential,
+        key,
+        args.beacon,
+    )?;
+
+    println!("Debug access is open until the next reset");
+    Ok(())
+}
+
+async fn read(probe_args: &ProbeArgs) -> anyhow::Result<DebugAccess> {
+    log::debug!("Starting probe session...");
+    let mut session = probe::start_session(&probe_args.chip, probe_args.probe.clone()).await?;
+    let mut core = session.core(0)?;
+
+    log::info!("Reading debug access configuration from target");
+    DebugAccess::read(&mut core)
+}
+
+fn describe(restricted: &[Feature]) -> String {
+    if restricted.is_empty() {
+        "no debug access".to_owned()
+    } else {
+        format!("{} to authenticated debuggers", restricted.iter().join(", "))
+    }
+}
@@ -1,4 +1,4 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use DownloadCommands::Other;
 use anyhow::Context;
@@ -7,20 +7,31 @@ use probe_rs::{Session, flashing};
 
 use crate::commands::sign::SignOutput;
 use crate::config::Config;
+use crate::error::{Classify, ErrorKind};
+use crate::processors::artifacts::Artifact;
 use crate::processors::certificates::Rkth;
-use crate::processors::probe;
-use crate::{DownloadCommands, ProbeArgs, RunCommands, SignCommands};
+use crate::processors::plan::{self, Operation};
+use crate::processors::{device, probe, sign_cache};
+use crate::{DownloadCommands, ProbeArgs, RunArguments, RunCommands, SignCommands};
 
-pub async fn process(config: &Config, command: DownloadCommands) -> anyhow::Result<()> {
+pub async fn process(config: &Config, command: DownloadCommands, dry_run: bool) -> anyhow::Result<()> {
     match command {
         DownloadCommands::Prelude {
             prelude_path,
             probe_args,
         } => {
-            download_prelude(&prelude_path, &probe_args).await?;
+            if dry_run {
+                super::print_plan(&plan::flash_elf(&prelude_path)?);
+            } else {
+                download_prelude(&prelude_path, &probe_args).await?;
+            }
         }
         Other(args) => {
-            process_other(config, args).await?;
+            if dry_run {
+                super::print_plan(&[prepare(config, args).await?.flash_operation()?]);
+            } else {
+                process_other(config, args).await?;
+            }
         }
     };
     Ok(())
@@ -31,7 +42,68 @@ pub struct DownloadOutput {
     pub rkth: Rkth,
 }
 
+/// Signed image ready to be flashed, see [prepare]
+pub(crate) struct Prepared {
+    pub run_args: RunArguments,
+    pub output_path: PathBuf,
+    pub rkth: Rkth,
+    pub flash_start: u64,
+}
+
+impl Prepared {
+    /// Flash write performed by [process_other]
+    pub fn flash_operation(&self) -> anyhow::Result<Operation> {
+        plan::flash_bin(&self.output_path, self.flash_start)
+    }
+}
+
 pub async fn process_other(config: &Config, command: RunCommands) -> anyhow::Result<DownloadOutput> {
+    let Prepared {
+        run_args,
+        output_path,
+        rkth,
+        flash_start,
+    } = prepare(config, command).await?;
+
+    log::debug!("Starting probe session...");
+    let mut session = probe::start_session(&run_args.probe_args.chip, run_args.probe_args.probe.clone()).await?;
+
+    // NOTE: First flash then set secure boot configuration! Doing it the other way around causes
+    // flashing to almost always fail.
+
+    log::info!(
+        "Flashing {} to target at address 0x{:02x}",
+        output_path.display(),
+        flash_start
+    );
+
+    let options = flashing::DownloadOptions::default();
+    flashing::download_file_with_options(
+        &mut session,
+        &output_path,
+        flashing::Format::Bin(flashing::BinOptions {
+            base_address: Some(flash_start),
+            skip: 0,
+        }),
+        options,
+    )
+    .context("Failed to flash binary")?;
+
+    if !run_args.no_verify {
+        log::info!("Verifying {} on target", output_path.display());
+        let image = std::fs::read(&output_path).with_context(|| format!("Could not read {}", output_path.display()))?;
+        device::verify_flashed(&mut session.core(0)?, flash_start, &image)
+            .context("Failed to verify flashed binary")
+            .classify(ErrorKind::Verification)?;
+    }
+
+    Ok(DownloadOutput { session, rkth })
+}
+
+/// Sign the image to download unless nothing changed since it was last signed, without writing to the device
+///
+/// A symbolic slot is resolved by reading the state journal from the device.
+pub(crate) async fn prepare(config: &Config, command: RunCommands) -> anyhow::Result<Prepared> {
     let (run_args, is_bootloader, flash_start) = match command {
         RunCommands::Bootloader(run_args) => {
             if let Some(bootloader) = &config.bootloader {
@@ -42,9 +114,10 @@ pub async fn process_other(config: &Config, command: RunCommands) -> anyhow::Res
         }
         RunCommands::Application { run_args, slot } => {
             if let Some(application) = &config.application {
+                let slot = super::resolve_slot(config, &run_args.probe_args, slot).await?;
                 let flash_start = *application
                     .slot_starts
-                    .get(slot as usize)
+                    .get(slot)
                     .ok_or_else(|| anyhow::anyhow!(format!("Slot {} not defined in configuration file", slot)))?;
                 (run_args, false, flash_start)
             } else {
@@ -61,40 +134,45 @@ pub async fn process_other(config: &Config, command: RunCommands) -> anyhow::Res
         SignCommands::Application(run_args.sign_args.clone())
     };
 
-    let SignOutput { output_path, rkth } = super::sign::process(config, sign_command).await?;
-
-    let Some(output_path) = output_path else {
-        return Err(anyhow::anyhow!("Image was not signed so nothing to run"));
+    let cache_path = run_args.sign_args.layout(config).path(Artifact::SignCache);
+    let key = sign_cache::key(config, is_bootloader, &run_args.sign_args);
+    let cached = if run_args.force_sign {
+        None
+    } else {
+        sign_cache::lookup(&cache_path, &key)
     };
 
-    log::debug!("Starting probe session...");
-    let mut session = probe::start_session(&run_args.probe_args.chip, run_args.probe_args.probe.clone()).await?;
+    let (output_path, rkth) = match cached {
+        Some((output_path, rkth)) => {
+            log::info!(
+                "Nothing changed since {} was signed, skipping signing",
+                output_path.display()
+            );
+            (output_path, rkth)
+        }
+        None => {
+            let SignOutput { output_path, rkth } = super::sign::process(config, sign_command).await?;
 
-    // NOTE: First flash then set secure boot configuration! Doing it the other way around causes
-    // flashing to almost always fail.
+            let Some(output_path) = output_path else {
+                return Err(anyhow::anyhow!("Image was not signed so nothing to run"));
+            };
 
-    log::info!(
-        "Flashing {} to target at address 0x{:02x}",
-        output_path.display(),
-        flash_start
-    );
+            if let Err(e) = sign_cache::store(&cache_path, &key, &output_path, &rkth) {
+                log::warn!("Could not store sign cache in {}: {e}", cache_path.display());
+            }
+            (output_path, rkth)
+        }
+    };
 
-    let options = flashing::DownloadOptions::default();
-    flashing::download_file_with_options(
-        &mut session,
+    Ok(Prepared {
+        run_args,
         output_path,
-        flashing::Format::Bin(flashing::BinOptions {
-            base_address: Some(flash_start),
-            skip: 0,
-        }),
-        options,
-    )
-    .context("Failed to flash binary")?;
-
-    Ok(DownloadOutput { session, rkth })
+        rkth,
+        flash_start,
+    })
 }
 
-async fn download_prelude(path: &Path, probe_args: &ProbeArgs) -> anyhow::Result<Session> {
+pub(crate) async fn download_prelude(path: &Path, probe_args: &ProbeArgs) -> anyhow::Result<Session> {
     log::debug!("Starting probe session...");
     let mut session = probe::start_session(&probe_args.chip, probe_args.probe.clone()).await?;
 
@@ -0,0 +1,55 @@
+use crate::config::Config;
+use crate::error::{Classify, ErrorKind};
+use crate::processors::fuse::{self, Word};
+use crate::processors::mbi::cert_block;
+use crate::processors::probe;
+use crate::{FuseCommands, ProbeArgs};
+
+pub async fn process(config: &Config, command: FuseCommands) -> anyhow::Result<()> {
+    match command {
+        FuseCommands::Read { probe_args } => {
+            let words = fuse::words();
+            let values = read(&probe_args, &words).await?;
+
+            for (word, value) in words.iter().zip(values) {
+                println!("{word}: {value:#010x}");
+            }
+
+            Ok(())
+        }
+        FuseCommands::Verify {
+            probe_args,
+            certificate,
+            nxpimage_path,
+        } => {
+            let rkth = cert_block::generate(&nxpimage_path, config, certificate)?.rkth();
+            log::info!("Expecting RKTH {} of certificate chain {}", rkth.as_hex(), certificate);
+
+            let expected = fuse::expected(config, &rkth)?;
+            let words: Vec<Word> = expected.iter().map(|expected| expected.word).collect();
+            let values = read(&probe_args, &words).await?;
+
+            let mismatches = fuse::verify(&expected, &values);
+            if mismatches.is_empty() {
+                println!("Fuses match the intended provisioning");
+                return Ok(());
+            }
+
+            println!("Fuses differ from the intended provisioning in:");
+            for mismatch in &mismatches {
+                println!("  {mismatch}");
+            }
+
+            Err(anyhow::anyhow!("{} fuse word(s) do not match", mismatches.len())).classify(ErrorKind::Verification)
+        }
+    }
+}
+
+async fn read(probe_args: &ProbeArgs, words: &[Word]) -> anyhow::Result<Vec<u32>> {
+    log::debug!("Starting probe session...");
+    let mut session = probe::start_session(&probe_args.chip, probe_args.probe.clone()).await?;
+    let mut core = session.core(0)?;
+
+    log::info!("Reading OTP shadow registers from target");
+    fuse::read(&mut core, words)
+}
@@ -1,5 +1,15 @@
+use std::path::PathBuf;
+
+use anyhow::Context;
+use object::read::elf::ElfFile32;
+
 use crate::config::Config;
-use crate::{GenerateCommands, processors};
+use crate::processors::artifacts::{Artifact, ArtifactLayout};
+use crate::processors::fcb::{self, FlashDescription};
+use crate::processors::mbi::{self, cert_block};
+use crate::processors::objcopy;
+use crate::util::temp_file;
+use crate::{GenerateCommands, GenerateFcbArguments, GenerateLayoutRsArguments, GenerateMbiArguments, processors};
 
 pub async fn process(config: &Config, command: GenerateCommands) -> anyhow::Result<()> {
     match command {
@@ -8,5 +18,95 @@ pub async fn process(config: &Config, command: GenerateCommands) -> anyhow::Resu
             let _ = processors::otp::generate(config)?;
             Ok(())
         }
+        GenerateCommands::Fcb(args) => generate_fcb(args),
+        GenerateCommands::Mbi(args) => generate_mbi(config, args),
+        GenerateCommands::LayoutRs(args) => generate_layout_rs(config, args),
+    }
+}
+
+fn generate_layout_rs(config: &Config, args: GenerateLayoutRsArguments) -> anyhow::Result<()> {
+    let output_path = args
+        .output_path
+        .unwrap_or_else(|| config.artifacts_path.join("layout.rs"));
+
+    std::fs::write(&output_path, processors::layout::to_rust(config)?)?;
+
+    log::info!("Wrote flash layout to {}", output_path.display());
+    Ok(())
+}
+
+fn generate_fcb(args: GenerateFcbArguments) -> anyhow::Result<()> {
+    let output_path = args
+        .output_path
+        .unwrap_or_else(|| args.input_path.with_extension("fcb.bin"));
+    let output_rust_path = args
+        .output_rust_path
+        .unwrap_or_else(|| args.input_path.with_extension("fcb.rs"));
+    let output_partition_path = args
+        .output_partition_path
+        .unwrap_or_else(|| args.input_path.with_extension("fcb-partition.bin"));
+
+    let fcb = FlashDescription::read(&args.input_path)?.build()?;
+    std::fs::write(&output_path, fcb)?;
+    std::fs::write(&output_rust_path, fcb::to_rust(&fcb, &args.input_path))?;
+    std::fs::write(&output_partition_path, fcb::partition_image(&fcb))?;
+
+    log::info!(
+        "Wrote FCB to {}, {} and {}",
+        output_path.display(),
+        output_rust_path.display(),
+        output_partition_path.display()
+    );
+    Ok(())
+}
+
+fn generate_mbi(config: &Config, args: GenerateMbiArguments) -> anyhow::Result<()> {
+    let output_path = match args.output_path {
+        Some(output_path) => output_path,
+        None => {
+            let layout = ArtifactLayout::new(config, &args.input_path, args.artifact_dir.as_deref());
+            layout.create_dir()?;
+            layout.path(Artifact::Nxp)
+        }
+    };
+
+    let Some(cert_chain) = config.certificates.get(args.certificate) else {
+        return Err(anyhow::anyhow!("Certificate chain {} does not exist", args.certificate));
+    };
+    let Some(cert) = cert_chain.0.last() else {
+        return Err(anyhow::anyhow!("Empty certificate chain"));
+    };
+    let Some(cert_proto) = &cert.prototype else {
+        return Err(anyhow::anyhow!(
+            "No prototype configured for leaf of chain {}",
+            args.certificate
+        ));
+    };
+
+    let input_data = std::fs::read(&args.input_path)?;
+    log::info!("Reading ELF from {}", args.input_path.display());
+    let file = ElfFile32::parse(&input_data[..]).context("Could not parse ELF file")?;
+    let (image, base_addr) = objcopy::objcopy(&file, objcopy::DEFAULT_MAX_GAP)?;
+
+    let unsigned_file = temp_file(".bin", &image)?;
+
+    let mut mbi_args = config.mbi.clone();
+    for (key, value) in args.overrides {
+        mbi_args.overrides.insert(key, toml::Value::String(value));
     }
+
+    let cert_block_config = cert_block::generate_config(config, args.certificate, None::<PathBuf>)?;
+    mbi::generate_nxp(
+        &args.nxpimage_path,
+        &unsigned_file,
+        base_addr,
+        &output_path,
+        cert_block_config,
+        &cert_proto.key_path,
+        &mbi_args,
+    )
+    .context("Could not generate MBI using nxpimage")?;
+
+    log::info!("Written MBI to {}", output_path.display());
+    Ok(())
 }
@@ -0,0 +1,77 @@
+use anyhow::Context;
+
+use x509_parser::prelude::ASN1Time;
+
+use crate::config::Config;
+use crate::error::{Classify, ErrorKind};
+use crate::processors::certificates::{Rkth, Validity};
+use crate::{InspectCommands, api};
+
+pub async fn process(config: &Config, command: InspectCommands) -> anyhow::Result<()> {
+    match command {
+        InspectCommands::Certificates { expiry_window } => {
+            let now = ASN1Time::now();
+            for (chain_i, chain) in config.certificates.iter().enumerate() {
+                println!("Certificate chain {chain_i}:");
+                for certificate in &chain.0 {
+                    let validity = Validity::from_file(&certificate.path)?;
+                    println!("  {}: {}", certificate.path.display(), validity.subject);
+                    println!(
+                        "    {} until {}, {}",
+                        validity.not_before,
+                        validity.not_after,
+                        validity.expiry(now, expiry_window)
+                    );
+                }
+            }
+
+            Ok(())
+        }
+        InspectCommands::Diff { a, b } => {
+            let differences = api::diff(&a, &b)?;
+            if differences.is_empty() {
+                println!("{} and {} are identical", a.display(), b.display());
+            } else {
+                println!("{} and {} differ in:", a.display(), b.display());
+                for difference in differences {
+                    println!("  {difference}");
+                }
+            }
+
+            Ok(())
+        }
+        InspectCommands::Mbi { image } => {
+            println!("{}", api::headers(&image)?);
+
+            Ok(())
+        }
+        InspectCommands::Verify {
+            image,
+            rkth,
+            like_rom,
+            root_key_slot,
+        } => {
+            let rkth = Rkth::from_hex(&rkth).context("Invalid RKTH")?;
+
+            if like_rom {
+                match api::verify_like_rom(&image, &rkth)? {
+                    Ok(authenticated) => println!("ROM would accept {}: {authenticated}", image.display()),
+                    Err(rejection) => {
+                        return Err(anyhow::anyhow!("ROM would reject {}: {rejection}", image.display()))
+                            .classify(ErrorKind::Verification);
+                    }
+                }
+            } else {
+                api::verify(&image, &rkth)?.cert_block.check_expiry(0, false)?;
+                println!("{} is signed for RKTH {}", image.display(), rkth.as_hex());
+            }
+
+            if let Some(slot) = root_key_slot {
+                api::verify(&image, &rkth)?.cert_block.check_root_key_slot(slot)?;
+                println!("{} is signed with the root key in slot {slot}", image.display());
+            }
+
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,13 @@
+use anyhow::Context;
+use clap::CommandFactory;
+
+use crate::{Cli, ManArguments};
+
+pub fn process(args: ManArguments) -> anyhow::Result<()> {
+    std::fs::create_dir_all(&args.output_dir)
+        .with_context(|| format!("Could not create {}", args.output_dir.display()))?;
+    clap_mangen::generate_to(Cli::command(), &args.output_dir)
+        .with_context(|| format!("Could not write manual pages to {}", args.output_dir.display()))?;
+    println!("Written manual pages to {}", args.output_dir.display());
+    Ok(())
+}
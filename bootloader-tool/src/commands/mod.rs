@@ -1,23 +1,92 @@
-mod download;
+mod clean;
+pub mod completions;
+mod debug;
+pub(crate) mod download;
+mod fuse;
 mod generate;
-mod run;
-mod sign;
+mod inspect;
+pub mod man;
+mod monitor;
+mod ota;
+mod provision;
+pub(crate) mod run;
+pub(crate) mod sign;
+mod slot;
+mod state;
+mod test;
+mod tui;
+
+use std::time::Duration;
+
+use anyhow::bail;
 
-use crate::Commands;
 use crate::config::Config;
+use crate::processors::plan::Operation;
+use crate::processors::state::SlotRef;
+use crate::processors::{device, probe};
+use crate::{Commands, ProbeArgs, SignCommands};
 
-pub async fn process(config: &Config, command: Commands) -> anyhow::Result<()> {
+/// Process `command`, only printing the changes it would make to the device when doing a `dry_run`
+pub async fn process(config: &Config, command: Commands, dry_run: bool) -> anyhow::Result<()> {
     match command {
         Commands::Generate { subcommand } => generate::process(config, subcommand).await,
+        Commands::Sign {
+            subcommand: SignCommands::Batch(args),
+        } => {
+            let _ = sign::batch(config, args)?;
+            Ok(())
+        }
         Commands::Sign { subcommand } => {
             let _ = sign::process(config, subcommand).await?;
             Ok(())
         }
         Commands::Download { subcommand } => {
-            download::process(config, subcommand).await?;
+            download::process(config, subcommand, dry_run).await?;
             Ok(())
         }
-        Commands::Run { subcommand } => run::process(config, subcommand).await,
-        Commands::Fuse => todo!(),
+        Commands::Run { subcommand } => run::process(config, subcommand, dry_run).await,
+        Commands::Ota { subcommand } => ota::process(subcommand).await,
+        Commands::Inspect { subcommand } => inspect::process(config, subcommand).await,
+        Commands::Fuse { subcommand } => fuse::process(config, subcommand).await,
+        Commands::Provision { subcommand } => provision::process(config, subcommand, dry_run).await,
+        Commands::State { subcommand } => state::process(config, subcommand, dry_run).await,
+        Commands::Slot { subcommand } => slot::process(config, subcommand, dry_run).await,
+        Commands::Test { subcommand } => test::process(subcommand),
+        Commands::Lock(args) => debug::lock(args, dry_run).await,
+        Commands::Unlock(args) => debug::unlock(config, args, dry_run).await,
+        Commands::Clean(args) => clean::process(config, args, dry_run),
+        Commands::Completions(args) => completions::process(args),
+        Commands::Man(args) => man::process(args),
+        Commands::Tui { probe_args, refresh_ms } => {
+            if dry_run {
+                bail!("The interactive monitor does not support --dry-run");
+            }
+            tui::process(config, probe_args, Duration::from_millis(refresh_ms)).await
+        }
+        Commands::Monitor { probe_args, refresh_ms } => {
+            monitor::process(config, probe_args, Duration::from_millis(refresh_ms)).await
+        }
     }
 }
+
+/// Print the `operations` a command would perform on the device, in order
+fn print_plan(operations: &[Operation]) {
+    println!("Dry run, would perform:");
+    for operation in operations {
+        println!("  {operation}");
+    }
+}
+
+/// Index of `slot` in `application.slot_starts`, reading the state journal from the device first if it is symbolic
+async fn resolve_slot(config: &Config, probe_args: &ProbeArgs, slot: SlotRef) -> anyhow::Result<usize> {
+    if !slot.is_symbolic() {
+        return slot.resolve(None);
+    }
+
+    log::debug!("Starting probe session to read the state journal...");
+    let mut session = probe::start_session(&probe_args.chip, probe_args.probe.clone()).await?;
+    let state = device::read_state(&mut session.core(0)?, config)?;
+    let index = slot.resolve(state.as_ref())?;
+    log::info!("Resolved slot {slot} to slot {index} using state {state:?}");
+    Ok(index)
+}
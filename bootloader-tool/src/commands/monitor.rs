@@ -0,0 +1,73 @@
+use std::time::{Duration, Instant};
+
+use anyhow::bail;
+use probe_rs::Session;
+
+use crate::ProbeArgs;
+use crate::config::Config;
+use crate::processors::monitor::{self, Status};
+use crate::processors::probe;
+
+pub async fn process(config: &Config, probe_args: ProbeArgs, refresh: Duration) -> anyhow::Result<()> {
+    let Some(bootloader) = &config.bootloader else {
+        bail!("Bootloader not defined in configuration file");
+    };
+    if bootloader.counters.is_none() {
+        log::warn!(
+            "Counters journal not defined in configuration file as `bootloader.counters`, only showing the state"
+        );
+    }
+
+    log::debug!("Starting probe session...");
+    let mut session = Some(probe::start_session(&probe_args.chip, probe_args.probe.clone()).await?);
+    let started = Instant::now();
+    let print = |line: &str| println!("[{:>9.1}s] {line}", started.elapsed().as_secs_f64());
+
+    let mut previous: Option<Status> = None;
+    // Whether the device went away, for example because it is power cycled, and that was printed.
+    let mut lost = false;
+    loop {
+        if session.is_none() {
+            session = probe::start_session(&probe_args.chip, probe_args.probe.clone())
+                .await
+                .ok();
+        }
+
+        match session.as_mut().map(|session| read(session, config)) {
+            Some(Ok(status)) => {
+                if lost {
+                    print("device attached");
+                    lost = false;
+                }
+                match &previous {
+                    Some(previous) if *previous == status => {}
+                    Some(previous) => {
+                        let events = monitor::events(previous, &status);
+                        if events.is_empty() {
+                            print(&status.to_string());
+                        } else {
+                            print(&format!("{status} ({})", events.join(", ")));
+                        }
+                    }
+                    None => print(&status.to_string()),
+                }
+                previous = Some(status);
+            }
+            Some(Err(e)) => {
+                if !lost {
+                    print(&format!("device lost: {e:#}"));
+                    lost = true;
+                }
+                session = None;
+            }
+            None => {}
+        }
+
+        std::thread::sleep(refresh);
+    }
+}
+
+fn read(session: &mut Session, config: &Config) -> anyhow::Result<Status> {
+    let mut core = session.core(0)?;
+    monitor::read(&mut core, config)
+}
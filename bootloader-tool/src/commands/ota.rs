@@ -0,0 +1,52 @@
+use anyhow::Context;
+
+use crate::OtaCommands;
+use crate::processors::mbi::SignedImage;
+
+pub async fn process(command: OtaCommands) -> anyhow::Result<()> {
+    match command {
+        OtaCommands::Diff { old, new, output_path } => {
+            let old_data = std::fs::read(&old).with_context(|| format!("Could not read {}", old.display()))?;
+            let new_data = std::fs::read(&new).with_context(|| format!("Could not read {}", new.display()))?;
+
+            // Only signed images are expected to be sent over the air, so refuse anything else.
+            SignedImage::parse(&old_data).with_context(|| format!("Could not parse signed image {}", old.display()))?;
+            SignedImage::parse(&new_data).with_context(|| format!("Could not parse signed image {}", new.display()))?;
+
+            log::info!("Generating patch from {} to {}", old.display(), new.display());
+            let patch = ec_slimloader_delta::diff::diff(&old_data, &new_data);
+
+            let output_path = output_path.unwrap_or_else(|| new.with_extension("patch"));
+            std::fs::write(&output_path, &patch).context("Could not write patch")?;
+
+            log::info!(
+                "Written patch of {} bytes ({:.1}% of the new image) to {}",
+                patch.len(),
+                100.0 * patch.len() as f64 / new_data.len() as f64,
+                output_path.display()
+            );
+
+            Ok(())
+        }
+        OtaCommands::Package { input, output_path } => {
+            let image = std::fs::read(&input).with_context(|| format!("Could not read {}", input.display()))?;
+            SignedImage::parse(&image).with_context(|| format!("Could not parse signed image {}", input.display()))?;
+
+            let header = ec_slimloader_ota::Header::for_image(&image);
+            let mut package = header.to_bytes().to_vec();
+            package.extend_from_slice(&image);
+
+            let output_path = output_path.unwrap_or_else(|| input.with_extension("ota"));
+            std::fs::write(&output_path, &package).context("Could not write package")?;
+
+            log::info!(
+                "Written package of {} bytes with image CRC32 {:#010x} to {}",
+                package.len(),
+                header.image_crc,
+                output_path.display()
+            );
+
+            Ok(())
+        }
+    }
+}
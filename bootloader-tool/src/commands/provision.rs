@@ -0,0 +1,360 @@
+use std::path::PathBuf;
+
+use anyhow::bail;
+use ec_slimloader_state::state::{Slot, State, Status};
+use itertools::Itertools;
+use probe_rs::MemoryInterface;
+
+use super::{download, run};
+use crate::config::Config;
+use crate::error::{Classify, ErrorKind};
+use crate::processors::manifest::{Manifest, Mismatch, SignedManifest, sha256_hex};
+use crate::processors::mbi::cert_block;
+use crate::processors::otp::get_otp;
+use crate::processors::plan::{self, Operation};
+use crate::processors::provision::{self, Checkpoint, Step};
+use crate::processors::state::SlotRef;
+use crate::processors::{certificates, fuse, otp, probe, state};
+use crate::{
+    GenerateCertificatesArguments, ProvisionCommands, ProvisionDeviceArguments, RunArguments, RunCommands,
+    SignArguments,
+};
+
+pub async fn process(config: &Config, command: ProvisionCommands, dry_run: bool) -> anyhow::Result<()> {
+    match command {
+        ProvisionCommands::Export {
+            certificate,
+            images,
+            output_path,
+            nxpimage_path,
+        } => {
+            let Some(cert_chain) = config.certificates.get(certificate) else {
+                return Err(anyhow::anyhow!("Certificate chain {} does not exist", certificate));
+            };
+
+            let Some(cert_proto) = cert_chain.0.last().and_then(|cert| cert.prototype.as_ref()) else {
+                return Err(anyhow::anyhow!(
+                    "No prototype configured for leaf of chain {}, cannot sign manifest",
+                    certificate
+                ));
+            };
+
+            let cert_block = cert_block::generate(&nxpimage_path, config, certificate)?;
+            let otp = get_otp(config)?;
+
+            let images = images
+                .iter()
+                .map(|image| {
+                    log::info!("Recording image {} at 0x{:08x}", image.path.display(), image.address);
+                    Ok((image.address, std::fs::read(&image.path)?))
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
+
+            let manifest =
+                Manifest::new(config, certificate, &cert_block, &otp.0, &images)?.sign(&cert_proto.key_path)?;
+
+            let output_path = output_path.unwrap_or_else(|| config.artifacts_path.join("provisioning-manifest.json"));
+            manifest.write(&output_path)?;
+            log::info!("Written signed provisioning manifest to {}", output_path.display());
+
+            Ok(())
+        }
+        ProvisionCommands::Verify {
+            manifest_path,
+            probe_args,
+            nxpimage_path,
+        } => {
+            let signed = SignedManifest::read(&manifest_path)?;
+            let cert_block = cert_block::generate(&nxpimage_path, config, signed.manifest.certificate)?;
+            let manifest = signed.verify(&cert_block).classify(ErrorKind::Verification)?;
+            log::info!("Manifest signature matches certificate chain {}", manifest.certificate);
+
+            let mut mismatches = vec![];
+
+            // Check that the manifest describes the key material of this configuration.
+            let certificates: Vec<String> = cert_block.certificates()?.iter().map(|cert| sha256_hex(cert)).collect();
+            if certificates != manifest.certificates {
+                mismatches.push(Mismatch::Certificates);
+            }
+
+            let rkth = cert_block.rkth().as_hex();
+            if rkth != manifest.rkth {
+                mismatches.push(Mismatch::Rkth {
+                    manifest: manifest.rkth.clone(),
+                    config: rkth,
+                });
+            }
+
+            if sha256_hex(&get_otp(config)?.0) != manifest.otp_key_sha256 {
+                mismatches.push(Mismatch::OtpKey);
+            }
+
+            // Check the device itself.
+            log::debug!("Starting probe session...");
+            let mut session = probe::start_session(&probe_args.chip, probe_args.probe.clone()).await?;
+            let mut core = session.core(0)?;
+
+            log::info!("Reading OTP shadow registers from target");
+            let expected = manifest.expected_fuses()?;
+            let words: Vec<_> = expected.iter().map(|expected| expected.word).collect();
+            let values = fuse::read(&mut core, &words)?;
+            mismatches.extend(fuse::verify(&expected, &values).into_iter().map(Mismatch::Fuse));
+
+            for image in &manifest.images {
+                log::info!("Reading image at 0x{:08x} from target", image.address);
+                let mut data = vec![0u8; image.length];
+                core.read(image.address, &mut data)?;
+
+                let actual = sha256_hex(&data);
+                if actual != image.sha256 {
+                    mismatches.push(Mismatch::Image {
+                        address: image.address,
+                        actual,
+                        expected: image.sha256.clone(),
+                    });
+                }
+            }
+
+            if mismatches.is_empty() {
+                println!("Device matches provisioning manifest {}", manifest_path.display());
+                return Ok(());
+            }
+
+            println!(
+                "Device differs from provisioning manifest {} in:",
+                manifest_path.display()
+            );
+            for mismatch in &mismatches {
+                println!("  {mismatch}");
+            }
+
+            Err(anyhow::anyhow!(
+                "{} mismatch(es) with the provisioning manifest",
+                mismatches.len()
+            ))
+            .classify(ErrorKind::Verification)
+        }
+        ProvisionCommands::Device(args) => device(config, args, dry_run).await,
+    }
+}
+
+/// Take a blank device to bootable, skipping the steps recorded as completed in the checkpoint
+async fn device(config: &Config, args: ProvisionDeviceArguments, dry_run: bool) -> anyhow::Result<()> {
+    let Some(bootloader) = &config.bootloader else {
+        bail!("Bootloader not defined in configuration file");
+    };
+    let Some(bootloader_elf) = args.bootloader.clone().or_else(|| bootloader.elf_path.clone()) else {
+        bail!("No bootloader ELF file given, pass --bootloader or configure `bootloader.elf_path`");
+    };
+
+    if dry_run {
+        log::info!("Dry run, not generating the OTP master key and certificates if missing");
+    } else {
+        generate_keys(config, &args)?;
+    }
+
+    let sign_args = |input_path: &PathBuf| {
+        let mut sign_args = SignArguments::new(input_path);
+        sign_args.certificate = args.certificate;
+        sign_args.nxpimage_path = args.nxpimage_path.clone();
+        sign_args
+    };
+    let run_args = |sign_args| {
+        let mut run_args = RunArguments::new(sign_args);
+        run_args.probe_args = args.probe_args.clone();
+        run_args
+    };
+    let bootloader_sign_args = sign_args(&bootloader_elf);
+    let application_sign_args = args.application.as_ref().map(sign_args);
+
+    let checkpoint_path = args
+        .checkpoint_path
+        .clone()
+        .unwrap_or_else(|| config.artifacts_path.join("provision-checkpoint.json"));
+    let fingerprint = provision::fingerprint(
+        config,
+        &args.probe_args.chip,
+        &bootloader_sign_args,
+        application_sign_args.as_ref(),
+    );
+
+    // Without the device, a dry run can not tell which steps were completed on it, and plans all of them.
+    let device = match read_device(&args).await {
+        Ok(device) => {
+            log::info!("Provisioning device {device}");
+            Some(device)
+        }
+        Err(e) if dry_run => {
+            log::warn!("Could not read the device UUID, planning all steps: {e:#}");
+            None
+        }
+        Err(e) => return Err(e),
+    };
+
+    let resumed = device
+        .as_deref()
+        .and_then(|device| Checkpoint::resume(&checkpoint_path, &fingerprint, device));
+    let device = device.unwrap_or_default();
+    let mut checkpoint = match resumed {
+        Some(_) if args.restart => {
+            log::info!("Starting over, ignoring checkpoint {}", checkpoint_path.display());
+            Checkpoint::new(fingerprint, device)
+        }
+        Some(checkpoint) => {
+            log::info!(
+                "Resuming from checkpoint {}, skipping completed steps: {}",
+                checkpoint_path.display(),
+                checkpoint.completed.iter().join(", ")
+            );
+            checkpoint
+        }
+        None => {
+            if checkpoint_path.exists() {
+                log::info!(
+                    "Starting over, checkpoint {} was recorded for other inputs or another device",
+                    checkpoint_path.display()
+                );
+            }
+            Checkpoint::new(fingerprint, device)
+        }
+    };
+    let steps: Vec<Step> = Step::all(application_sign_args.is_some())
+        .into_iter()
+        .filter(|step| !checkpoint.is_completed(*step))
+        .collect();
+
+    // The prelude is only extracted when signing, so the bootloader is signed again if it went missing since.
+    let mut bootloader_run_args = run_args(bootloader_sign_args);
+    let prelude_path = bootloader_run_args.sign_args.prelude_path_with_default(config);
+    bootloader_run_args.force_sign = !prelude_path.exists();
+    let prepared = download::prepare(config, RunCommands::Bootloader(bootloader_run_args.clone())).await?;
+    bootloader_run_args.force_sign = false;
+    let bootloader_command = RunCommands::Bootloader(bootloader_run_args);
+
+    let application_command = application_sign_args.map(|sign_args| RunCommands::Application {
+        run_args: run_args(sign_args),
+        slot: SlotRef::Index(0),
+    });
+    let state = State::new(Status::Initial, Slot::S0, Slot::S1);
+
+    if dry_run {
+        let mut operations = vec![];
+        for step in &steps {
+            match step {
+                Step::Prelude => operations.extend(plan::flash_elf(&prelude_path)?),
+                Step::Bootloader => operations.push(prepared.flash_operation()?),
+                Step::Application => {
+                    if let Some(command) = &application_command {
+                        operations.push(download::prepare(config, command.clone()).await?.flash_operation()?);
+                    }
+                }
+                Step::State => operations.push(Operation::Flash {
+                    address: bootloader.state.start,
+                    len: bootloader.state.size,
+                    source: format!("state journal seeded with {state:?}"),
+                }),
+                Step::Fuses => {
+                    operations.extend(plan::run_shadow_writes(
+                        &prepared.rkth.as_u32_le(),
+                        fuse::planned_boot_cfg0(config)?,
+                        fuse::planned_boot_cfg1(config)?,
+                    ));
+                    operations.push(Operation::Reset);
+                }
+            }
+        }
+        super::print_plan(&operations);
+        return Ok(());
+    }
+
+    for step in steps {
+        log::info!("Provisioning {step}");
+        match step {
+            Step::Prelude => {
+                let _ = download::download_prelude(&prelude_path, &args.probe_args).await?;
+            }
+            Step::Bootloader => {
+                let _ = download::process_other(config, bootloader_command.clone()).await?;
+            }
+            Step::Application => {
+                if let Some(command) = &application_command {
+                    let _ = download::process_other(config, command.clone()).await?;
+                }
+            }
+            Step::State => {
+                let mut session = probe::start_session(&args.probe_args.chip, args.probe_args.probe.clone()).await?;
+                state::flash_seeded(&mut session, &bootloader.state, &state)?;
+            }
+            Step::Fuses => {
+                let mut session = probe::start_session(&args.probe_args.chip, args.probe_args.probe.clone()).await?;
+                let mut core = session.core(0)?;
+                run::write_shadow_registers(&mut core, config, &prepared.rkth, &otp::get_otp(config)?)?;
+
+                let expected = fuse::expected(config, &prepared.rkth)?;
+                let words: Vec<_> = expected.iter().map(|expected| expected.word).collect();
+                let mismatches = fuse::verify(&expected, &fuse::read(&mut core, &words)?);
+                if !mismatches.is_empty() {
+                    for mismatch in &mismatches {
+                        log::error!("{mismatch}");
+                    }
+                    return Err(anyhow::anyhow!(
+                        "{} fuse word(s) do not read back as written to the shadow registers",
+                        mismatches.len()
+                    ))
+                    .classify(ErrorKind::Verification);
+                }
+
+                core.reset()?;
+            }
+        }
+        checkpoint.complete(step, &checkpoint_path)?;
+    }
+
+    // A fully provisioned device leaves nothing to resume, so the next device starts from scratch.
+    if checkpoint_path.exists() {
+        std::fs::remove_file(&checkpoint_path)?;
+    }
+
+    println!(
+        "Device provisioned with certificate chain {}. The fuse values are only written to the shadow registers, \
+         check them with `fuse verify` after burning",
+        args.certificate
+    );
+    Ok(())
+}
+
+/// UUID of the device attached to the probe, identifying it in the checkpoint
+async fn read_device(args: &ProvisionDeviceArguments) -> anyhow::Result<String> {
+    let mut session = probe::start_session(&args.probe_args.chip, args.probe_args.probe.clone()).await?;
+    let mut core = session.core(0)?;
+    fuse::read_uuid(&mut core)
+}
+
+/// Generate the OTP master key and the certificates, unless they exist
+///
+/// Certificates are only generated when none exist, as generating them replaces the private keys of all chains.
+fn generate_keys(config: &Config, args: &ProvisionDeviceArguments) -> anyhow::Result<()> {
+    if !config.otp_path.exists() {
+        let _ = otp::generate(config)?;
+    }
+
+    let all: Vec<_> = config.certificates.iter().flat_map(|chain| &chain.0).collect();
+    let existing = all.iter().filter(|certificate| certificate.path.exists()).count();
+    if existing == 0 {
+        certificates::generate(
+            GenerateCertificatesArguments {
+                nxpcrypto_path: args.nxpcrypto_path.clone(),
+                nxpimage_path: args.nxpimage_path.clone(),
+                jobs: None,
+            },
+            config,
+        )?;
+    } else if existing < all.len() {
+        bail!(
+            "Only {existing} of the {} configured certificates exist. Generating them replaces the private keys of \
+             all chains, so run `generate certificates` explicitly",
+            all.len()
+        );
+    }
+    Ok(())
+}
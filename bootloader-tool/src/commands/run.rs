@@ -1,48 +1,58 @@
-use probe_rs::MemoryInterface;
+use anyhow::Context;
+use itertools::Itertools;
+use probe_rs::{Core, MemoryInterface};
 
 use crate::RunCommands;
 use crate::commands::download::DownloadOutput;
 use crate::config::Config;
-use crate::processors::otp;
-
-pub async fn process(config: &Config, command: RunCommands) -> anyhow::Result<()> {
+use crate::error::{Classify, ErrorKind};
+use crate::processors::certificates::Rkth;
+use crate::processors::otp::Otp;
+use crate::processors::plan::{self, Operation};
+use crate::processors::rtt::{self, Stage};
+use crate::processors::{fuse, otp};
+
+pub async fn process(config: &Config, command: RunCommands, dry_run: bool) -> anyhow::Result<()> {
     let otp = otp::get_otp(config)?;
 
+    if dry_run {
+        let prepared = super::download::prepare(config, command).await?;
+        let mut operations = vec![prepared.flash_operation()?];
+        operations.extend(plan::run_shadow_writes(
+            &prepared.rkth.as_u32_le(),
+            fuse::planned_boot_cfg0(config)?,
+            fuse::planned_boot_cfg1(config)?,
+        ));
+        operations.push(Operation::Reset);
+        super::print_plan(&operations);
+        return Ok(());
+    }
+
+    let stages = stages(config, &command)?;
+
     log::debug!("Preparing for run by calling download...");
     let DownloadOutput { mut session, rkth } = super::download::process_other(config, command.clone()).await?;
 
     let mut core = session.core(0)?;
 
-    log::info!("Setting shadow registers on target");
-    core.write_32(0x401301E0, &rkth.as_u32_le())?;
-    core.write_32(0x401301C0, &otp.as_reversed_u32_be())?;
-
-    // Enable secure boot, skip DICE
-    let mut boot0 = 0u32;
-    boot0 |= 0b0101; // Use QSPI B
-    boot0 |= 0b111 << 4; // Completely disable ISP mode
-    boot0 |= 0b10 << 13; // Force Trust-Zone mode
-    boot0 |= 0b01 << 20; // Enable secure boot
-    boot0 |= 0b1 << 23; // Skip DICE
-    boot0 |= 0b101 << 24; // Configure boot_fail_pin port 5
-    boot0 |= 0b00111 << 27; // Configure boot_fail_pin pin 7
-
-    core.write_32(0x40130180, &[boot0])?;
-
-    let mut boot1 = 0u32;
-    boot1 |= 1 << 14; // Reset pin enable.
-    boot1 |= 2 << 15; // Reset pin port 2.
-    boot1 |= 12 << 18; // Reset pin number 12.
+    log::info!(
+        "Setting shadow registers on target for the {:?} board preset",
+        config.board.preset
+    );
+    write_shadow_registers(&mut core, config, &rkth, &otp)?;
 
-    core.write_32(0x40130184, &[boot1])?;
-
-    let mut buf = [0u32; 1];
-    core.read_32(0x40130194, &mut buf)?;
+    let (RunCommands::Bootloader(run_args) | RunCommands::Application { run_args, .. }) = command;
 
-    // buf[0] |= 0b1111; // Revoke root cert 2.
-    buf[0] &= !(1 << 7); // Set USE_PUF to 0
+    if let Some(stages) = stages {
+        rtt::clear_control_blocks(&mut core, &stages)?;
+        core.reset().unwrap();
 
-    core.write_32(0x40130194, &buf)?;
+        log::info!(
+            "Target configured and reset, following the logs of the {}",
+            stages.iter().map(|stage| stage.name).join(" and the ")
+        );
+        return rtt::follow(&mut core, &stages, &run_args.defmt_print_path);
+    }
 
     core.reset().unwrap();
     drop(core);
@@ -50,8 +60,6 @@ pub async fn process(config: &Config, command: RunCommands) -> anyhow::Result<()
 
     log::info!("Target configured and reset, attaching...");
 
-    let (RunCommands::Bootloader(run_args) | RunCommands::Application { run_args, .. }) = command;
-
     let mut command = std::process::Command::new(&run_args.probe_rs_path);
     command.args(["attach", "--chip", &run_args.probe_args.chip]);
 
@@ -59,7 +67,71 @@ pub async fn process(config: &Config, command: RunCommands) -> anyhow::Result<()
         command.args(["--probe", probe]);
     }
 
-    command.arg(run_args.sign_args.input_path).status().unwrap();
+    command
+        .arg(run_args.sign_args.input_path)
+        .status()
+        .with_context(|| {
+            format!(
+                "Could not execute `{}`, is it installed?",
+                run_args.probe_rs_path.display()
+            )
+        })
+        .classify(ErrorKind::ExternalTool)?;
 
     Ok(())
 }
+
+/// Write the RKTH, OTP master key and boot configuration to the shadow registers, such that the ROM boots images
+/// signed for `rkth` until the next power cycle
+pub(crate) fn write_shadow_registers(core: &mut Core, config: &Config, rkth: &Rkth, otp: &Otp) -> anyhow::Result<()> {
+    core.write_32(fuse::shadow_address(fuse::RKTH), &rkth.as_u32_le())?;
+    core.write_32(fuse::shadow_address(fuse::OTP_MASTER_KEY), &otp.as_reversed_u32_be())?;
+
+    // Enable secure boot, skip DICE
+    core.write_32(
+        fuse::shadow_address(fuse::BOOT_CFG0),
+        &[fuse::planned_boot_cfg0(config)?],
+    )?;
+    core.write_32(
+        fuse::shadow_address(fuse::BOOT_CFG1),
+        &[fuse::planned_boot_cfg1(config)?],
+    )?;
+
+    let mut buf = [0u32; 1];
+    core.read_32(fuse::shadow_address(fuse::SEC_BOOT_CFG5), &mut buf)?;
+
+    // buf[0] |= 0b1111; // Revoke root cert 2.
+    buf[0] &= !fuse::SEC_BOOT_CFG5_USE_PUF; // Set USE_PUF to 0
+
+    core.write_32(fuse::shadow_address(fuse::SEC_BOOT_CFG5), &buf)?;
+
+    Ok(())
+}
+
+/// Stages of the boot chain to follow the logs of, if both the bootloader and the application are to be decoded
+///
+/// Otherwise only the image that was run is attached to using probe-rs.
+fn stages(config: &Config, command: &RunCommands) -> anyhow::Result<Option<Vec<Stage>>> {
+    let RunCommands::Application { run_args, .. } = command else {
+        return Ok(None);
+    };
+    let bootloader_elf = run_args.bootloader_elf.as_ref().or(config
+        .bootloader
+        .as_ref()
+        .and_then(|bootloader| bootloader.elf_path.as_ref()));
+    let Some(bootloader_elf) = bootloader_elf else {
+        return Ok(None);
+    };
+
+    let bootloader = Stage::from_file("bootloader", bootloader_elf)?;
+    let application = Stage::from_file("application", &run_args.sign_args.input_path)?;
+    if bootloader.control_block == application.control_block {
+        log::warn!(
+            "The bootloader and the application both place their RTT control block at 0x{:08x}, only decoding the \
+             logs of the application",
+            application.control_block
+        );
+        return Ok(None);
+    }
+    Ok(Some(vec![bootloader, application]))
+}
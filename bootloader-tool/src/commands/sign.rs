@@ -1,27 +1,64 @@
 use std::path::PathBuf;
 
-use anyhow::Context;
+use anyhow::{Context, bail};
+use itertools::Itertools;
 use object::read::elf::ElfFile32;
 
-use crate::SignCommands;
 use crate::config::Config;
+use crate::processors::artifacts::Artifact;
+use crate::processors::audit::{self, SignatureSource};
 use crate::processors::certificates::Rkth;
 use crate::processors::mbi::cert_block;
 use crate::processors::otp::get_otp;
-use crate::processors::{mbi, objcopy};
+use crate::processors::slot::SlotLayout;
+use crate::processors::{mbi, objcopy, pipeline};
+use crate::{SignArguments, SignBatchArguments, SignCommands};
 
 pub struct SignOutput {
+    /// Image to flash at the start of the slot, which is the slot image when subregions are given
     pub output_path: Option<PathBuf>,
     pub rkth: Rkth,
 }
 
 pub async fn process(config: &Config, command: SignCommands) -> anyhow::Result<SignOutput> {
-    let (is_bootloader, args) = match command {
-        SignCommands::Bootloader(sign_arguments) => (true, sign_arguments),
-        SignCommands::Application(sign_arguments) => (false, sign_arguments),
+    match command {
+        SignCommands::Bootloader(sign_arguments) => sign(config, true, sign_arguments),
+        SignCommands::Application(sign_arguments) => sign(config, false, sign_arguments),
+        SignCommands::Batch(_) => bail!("A batch yields an output per image, sign it with `batch` instead"),
+    }
+}
+
+/// Sign all images of the batch, running at most `--jobs` signing flows at once
+///
+/// The cert blocks are generated up front, such that the flows reuse them from the cache rather than all running
+/// nxpimage for the same certificate chain. Yields the outputs in the order of the images in `args`.
+pub fn batch(config: &Config, args: SignBatchArguments) -> anyhow::Result<Vec<SignOutput>> {
+    let parallelism = pipeline::parallelism(args.jobs);
+    cert_block::generate(&args.nxpimage_path, config, args.certificate)?;
+
+    let images = args.images();
+    log::info!("Signing {} images, {parallelism} at a time", images.len());
+    let results = pipeline::run(images, parallelism, |(is_bootloader, sign_args)| {
+        let input_path = sign_args.input_path.clone();
+        sign(config, is_bootloader, sign_args).with_context(|| format!("Could not sign {}", input_path.display()))
+    });
+
+    // Report every failure, rather than only the first.
+    let (outputs, errors): (Vec<_>, Vec<_>) = results.into_iter().partition_result();
+    let mut errors = errors.into_iter();
+    let Some(first) = errors.next() else {
+        return Ok(outputs);
     };
+    for error in errors {
+        log::error!("{error:#}");
+    }
+    Err(first)
+}
 
+fn sign(config: &Config, is_bootloader: bool, args: SignArguments) -> anyhow::Result<SignOutput> {
     let input_data = std::fs::read(&args.input_path)?;
+    let artifacts = args.layout(config);
+    artifacts.create_dir()?;
 
     log::info!("Reading ELF from {}", args.input_path.display());
     let file = ElfFile32::parse(&input_data[..]).context("Could not parse ELF file")?;
@@ -29,45 +66,167 @@ pub async fn process(config: &Config, command: SignCommands) -> anyhow::Result<S
     if is_bootloader {
         log::info!("Extracting prelude");
         let out = objcopy::remove_non_prelude(&input_data)?;
-        std::fs::write(args.prelude_path_with_default(), &out).context("Could not write prelude elf file")?;
+        std::fs::write(args.prelude_path_with_default(config), &out).context("Could not write prelude elf file")?;
     }
 
     log::info!("Generating image for {}", args.input_path.display());
-    let (image, base_addr) = objcopy::objcopy(&file)?;
+    let (mut image, base_addr) = objcopy::objcopy(&file, args.max_gap)?;
+    let image_range = base_addr..base_addr + image.len() as u32;
+    let mut xip_slot = None;
 
     if is_bootloader {
-        if let Some(bootloader) = &config.bootloader
-            && bootloader.run_start != base_addr as u64
+        if let Some(bootloader) = &config.bootloader {
+            if bootloader.run_start != base_addr as u64 {
+                return Err(anyhow::anyhow!(
+                    "Bootloader image will be run from unexpected address 0x{:x}, should be 0x{:x}",
+                    base_addr,
+                    bootloader.run_start
+                ));
+            }
+            if bootloader.executes_in_place() {
+                log::info!("Bootloader image will be executed in place from flash");
+            } else if !objcopy::within_ram(image_range.clone()) {
+                return Err(anyhow::anyhow!(
+                    "Bootloader image at 0x{:x}..0x{:x} does not fit in RAM",
+                    image_range.start,
+                    image_range.end
+                ));
+            }
+        }
+    } else if let Some(application) = &config.application
+        && application.run_start == base_addr as u64
+    {
+        if !objcopy::within_ram(image_range.clone()) {
+            return Err(anyhow::anyhow!(
+                "Application image at 0x{:x}..0x{:x} does not fit in RAM",
+                image_range.start,
+                image_range.end
+            ));
+        }
+        if let Some(load_range) = &application.load_range
+            && (u64::from(image_range.start) < load_range.start
+                || u64::from(image_range.end) > load_range.start + load_range.size)
         {
             return Err(anyhow::anyhow!(
-                "Bootloader image will be run from unexpected address 0x{:x}, should be 0x{:x}",
+                "Application image at 0x{:x}..0x{:x} does not fit in application.load_range at 0x{:x}..0x{:x}",
+                image_range.start,
+                image_range.end,
+                load_range.start,
+                load_range.start + load_range.size
+            ));
+        }
+    } else if let Some(application) = &config.application {
+        // Images linked to run from the start of a slot are executed in place, if the bootloader is configured to.
+        let Some(slot) = application
+            .slot_starts
+            .iter()
+            .position(|&slot_start| slot_start == base_addr as u64)
+        else {
+            return Err(anyhow::anyhow!(
+                "Application image will be run from unexpected address 0x{:x}, should be 0x{:x} or the start of a slot",
                 base_addr,
-                bootloader.run_start
+                application.run_start
             ));
+        };
+        log::info!("Application image will be executed in place from slot {slot}");
+        xip_slot = Some(slot);
+    }
+
+    if let Some(binding) = args.bind_slot {
+        if is_bootloader {
+            bail!("Only application images can be bound to a slot");
         }
-    } else if let Some(application) = &config.application
-        && application.run_start != base_addr as u64
-    {
-        return Err(anyhow::anyhow!(
-            "Application image will be run from unexpected address 0x{:x}, should be 0x{:x}",
-            base_addr,
-            application.run_start
-        ));
+        if let (Some(bound), Some(application)) = (binding.slot, &config.application)
+            && usize::from(bound) >= application.slot_starts.len()
+        {
+            bail!(
+                "Cannot bind image to slot {bound}, only {} slots are configured",
+                application.slot_starts.len()
+            );
+        }
+        if let (Some(bound), Some(slot)) = (binding.slot, xip_slot)
+            && usize::from(bound) != slot
+        {
+            bail!("Cannot bind image to slot {bound}, as it is executed in place from slot {slot}");
+        }
+        match binding.slot {
+            Some(slot) => log::info!("Binding image to slot {slot}"),
+            None => log::info!("Binding image to any slot"),
+        }
+        mbi::append_slot_binding(&mut image, binding);
     }
 
-    let output_unsigned_path = args.output_unsigned_path_with_default();
+    let output_unsigned_path = args.output_unsigned_path_with_default(config);
     log::debug!("Wrote unsigned bare binary image to {}", output_unsigned_path.display());
     std::fs::write(&output_unsigned_path, &image)?;
 
     let otp = get_otp(config)?;
 
-    let output_prestage_path = args.output_prestage_path_with_default();
+    let output_prestage_path = args.output_prestage_path_with_default(config);
     log::info!(
         "Generating prestage MBI using pure Rust in {}",
         output_prestage_path.display()
     );
 
     let cert_block = cert_block::generate(&args.nxpimage_path, config, args.certificate)?;
+    cert_block.check_root_key_slot(config.root_key_slot(args.certificate)?)?;
+    cert_block.check_expiry(args.expiry_window, args.strict)?;
+
+    let layout = match &config.application {
+        Some(application) if !is_bootloader => Some(SlotLayout::from_config(application, &args.subregions)?),
+        _ if !args.subregions.is_empty() => {
+            return Err(anyhow::anyhow!(
+                "Subregions are only supported for configured application slots"
+            ));
+        }
+        _ => None,
+    };
+
+    let max_size = if is_bootloader {
+        config
+            .bootloader
+            .as_ref()
+            .map(|bootloader| ("bootloader.max_size", bootloader.max_size))
+    } else {
+        config.application.as_ref().map(|application| {
+            match application.subregions.iter().map(|subregion| subregion.offset).min() {
+                Some(offset) => ("the first of application.subregions", offset),
+                None => ("application.slot_size", application.slot_size),
+            }
+        })
+    };
+    let mut metadata = args.metadata.metadata();
+    if let Some(layout) = &layout {
+        metadata.subregions = layout.digests();
+    }
+    if !is_bootloader
+        && let Some(product_id) = config
+            .application
+            .as_ref()
+            .and_then(|application| application.product_id)
+    {
+        match metadata.product_id {
+            Some(id) if id != product_id => {
+                return Err(anyhow::anyhow!(
+                    "Product ID {id:#x} differs from application.product_id {product_id:#x}"
+                ));
+            }
+            _ => metadata.product_id = Some(product_id),
+        }
+    }
+    if let Some((name, max_size)) = max_size {
+        let signed_len = mbi::signed_len(&output_unsigned_path, base_addr, is_bootloader, cert_block.clone())?;
+        let signed_len = signed_len + metadata.appended_len(signed_len)?;
+        if signed_len as u64 > max_size {
+            let message = format!("Signed image of {signed_len:#x} bytes exceeds {name} of {max_size:#x} bytes");
+            if args.strict {
+                return Err(anyhow::anyhow!(message));
+            }
+            log::warn!("{message}");
+        } else {
+            log::info!("Signed image of {signed_len:#x} bytes fits {name} of {max_size:#x} bytes");
+        }
+    }
 
     mbi::prepare_to_sign(
         &output_unsigned_path,
@@ -79,6 +238,7 @@ pub async fn process(config: &Config, command: SignCommands) -> anyhow::Result<S
     .context("Could not generate prestage MBI")?;
 
     let mut signature_path = args.signature_path.clone();
+    let mut signature_source = signature_path.clone().map(SignatureSource::External);
 
     if !args.dont_sign && signature_path.is_none() {
         log::info!("Signing image {}", args.input_path.display());
@@ -98,15 +258,16 @@ pub async fn process(config: &Config, command: SignCommands) -> anyhow::Result<S
             ));
         };
 
-        let default_path = args.input_path.clone().with_extension("signature.bin");
+        let default_path = artifacts.path(Artifact::Signature);
         mbi::sign(&default_path, &output_prestage_path, &cert_proto.key_path).context("Could not sign image")?;
         signature_path = Some(default_path);
+        signature_source = Some(SignatureSource::Key(cert_proto.key_path.clone()));
     }
 
     let rkth = cert_block.rkth();
 
     if let Some(signature_path) = signature_path {
-        let output_path = args.output_path_with_default();
+        let output_path = args.output_path_with_default(config);
         log::info!("Merging signature into image");
         mbi::merge_with_signature(
             &output_unsigned_path,
@@ -115,10 +276,44 @@ pub async fn process(config: &Config, command: SignCommands) -> anyhow::Result<S
             &output_path,
             is_bootloader,
             Some(otp),
-            cert_block,
+            cert_block.clone(),
         )
         .context("Could not merge image with signature")?;
+        if metadata.image_digest.is_some() {
+            metadata.digest_image(&output_path)?;
+        }
+        if !metadata.is_empty() {
+            log::info!("Appending metadata: {metadata}");
+            metadata.append_to(&output_path)?;
+        }
         log::info!("Written merged image to {}", output_path.display());
+
+        if let Some(signature) = signature_source.filter(|_| !args.no_audit_log) {
+            let record = audit::Record::new(
+                config,
+                &args,
+                is_bootloader,
+                &input_data,
+                &output_path,
+                &cert_block,
+                signature,
+            )?;
+            let audit_path = audit::path(config);
+            audit::append(&audit_path, &record)?;
+            log::info!("Recorded signing in audit log {}", audit_path.display());
+        }
+
+        if let Some(layout) = layout.filter(|layout| !layout.is_empty()) {
+            let image = std::fs::read(&output_path)?;
+            let slot = layout.compose(&image).context("Could not compose slot image")?;
+            let output_slot_path = args.output_slot_path_with_default(config);
+            std::fs::write(&output_slot_path, slot)?;
+            log::info!("Written slot image to {}", output_slot_path.display());
+            return Ok(SignOutput {
+                output_path: Some(output_slot_path),
+                rkth,
+            });
+        }
         Ok(SignOutput {
             output_path: Some(output_path),
             rkth,
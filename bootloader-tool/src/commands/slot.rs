@@ -0,0 +1,56 @@
+use anyhow::Context;
+
+use crate::SlotCommands;
+use crate::config::Config;
+use crate::processors::plan::Operation;
+use crate::processors::{device, probe};
+
+pub async fn process(config: &Config, command: SlotCommands, dry_run: bool) -> anyhow::Result<()> {
+    match command {
+        SlotCommands::Dump { probe_args, slot, out } => {
+            let slot = super::resolve_slot(config, &probe_args, slot).await?;
+            let region = device::slot_region(config, slot)?;
+
+            log::debug!("Starting probe session...");
+            let mut session = probe::start_session(&probe_args.chip, probe_args.probe.clone()).await?;
+            let mut core = session.core(0)?;
+
+            log::info!("Reading slot {slot} at {:#x}..{:#x}", region.start, region.end);
+            let contents = device::dump_slot(&mut core, region)?;
+            std::fs::write(&out, &contents).with_context(|| format!("Could not write {}", out.display()))?;
+
+            println!("Slot {slot} dumped to {}", out.display());
+            Ok(())
+        }
+        SlotCommands::Write {
+            probe_args,
+            slot,
+            input_path,
+        } => {
+            let slot = super::resolve_slot(config, &probe_args, slot).await?;
+            let region = device::slot_region(config, slot)?;
+            let contents =
+                std::fs::read(&input_path).with_context(|| format!("Could not read {}", input_path.display()))?;
+
+            if dry_run {
+                // Fails the same as writing would, if the contents do not fit.
+                let padded = device::slot_contents(&contents, region.end - region.start)?;
+                super::print_plan(&[Operation::Flash {
+                    address: region.start,
+                    len: padded.len() as u64,
+                    source: format!("{}, padded with 0xff", input_path.display()),
+                }]);
+                return Ok(());
+            }
+
+            log::debug!("Starting probe session...");
+            let mut session = probe::start_session(&probe_args.chip, probe_args.probe.clone()).await?;
+
+            log::info!("Writing slot {slot} at {:#x}..{:#x}", region.start, region.end);
+            device::write_slot(&mut session, region, &contents)?;
+
+            println!("Slot {slot} written from {}", input_path.display());
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use anyhow::bail;
+use ec_slimloader_state::state::{Slot, State};
+
+use crate::StateCommands;
+use crate::config::Config;
+use crate::processors::plan::Operation;
+use crate::processors::{device, probe, state};
+
+pub async fn process(config: &Config, command: StateCommands, dry_run: bool) -> anyhow::Result<()> {
+    match command {
+        StateCommands::Reset {
+            probe_args,
+            status,
+            target,
+            backup,
+        } => {
+            let Some(bootloader) = &config.bootloader else {
+                bail!("Bootloader not defined in configuration file");
+            };
+
+            let slot = |slot: u8| {
+                if let Some(application) = &config.application
+                    && slot as usize >= application.slot_starts.len()
+                {
+                    bail!("Slot {slot} not defined in configuration file");
+                }
+                Slot::try_from(slot).map_err(|_| anyhow::anyhow!("Invalid slot {slot}"))
+            };
+            let state = State::new(status.into(), slot(target)?, slot(backup)?);
+
+            if dry_run {
+                super::print_plan(&[Operation::Flash {
+                    address: bootloader.state.start,
+                    len: bootloader.state.size,
+                    source: format!("state journal seeded with {state:?}"),
+                }]);
+                return Ok(());
+            }
+
+            log::debug!("Starting probe session...");
+            let mut session = probe::start_session(&probe_args.chip, probe_args.probe.clone()).await?;
+
+            log::info!("Resetting state journal at {:#x} to {state:?}", bootloader.state.start);
+            state::flash_seeded(&mut session, &bootloader.state, &state)?;
+
+            println!("State journal reset to {state:?}");
+            Ok(())
+        }
+        StateCommands::History { probe_args } => {
+            let Some(bootloader) = &config.bootloader else {
+                bail!("Bootloader not defined in configuration file");
+            };
+
+            log::debug!("Starting probe session...");
+            let mut session = probe::start_session(&probe_args.chip, probe_args.probe.clone()).await?;
+            let journal = device::read_journal(&mut session.core(0)?, config)?;
+
+            let history = state::history(&journal);
+            for (offset, record) in &history {
+                let address = bootloader.state.start + *offset as u64;
+                match record {
+                    Ok(state) => println!("{address:#010x}: {state:?}"),
+                    Err(e) => println!("{address:#010x}: {e:?} record"),
+                }
+            }
+            println!("{}", state::summarize(&history));
+            Ok(())
+        }
+    }
+}
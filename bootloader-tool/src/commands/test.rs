@@ -0,0 +1,34 @@
+use anyhow::Context;
+
+use crate::TestCommands;
+use crate::processors::mbi::vectors::Vector;
+
+pub fn process(command: TestCommands) -> anyhow::Result<()> {
+    match command {
+        TestCommands::Vectors {
+            input,
+            output_dir,
+            only,
+        } => {
+            let image = std::fs::read(&input).with_context(|| format!("Could not read {}", input.display()))?;
+            let vectors = if only.is_empty() { Vector::ALL.to_vec() } else { only };
+
+            for vector in vectors {
+                let corrupted = vector
+                    .apply(&image)
+                    .with_context(|| format!("Could not generate {vector} from {}", input.display()))?;
+
+                let mut output_path = input.with_extension(format!("{vector}.bin"));
+                if let (Some(output_dir), Some(file_name)) = (&output_dir, output_path.file_name()) {
+                    output_path = output_dir.join(file_name);
+                }
+                std::fs::write(&output_path, &corrupted)
+                    .with_context(|| format!("Could not write {}", output_path.display()))?;
+
+                println!("{}: BootError::{}", output_path.display(), vector.expected());
+            }
+
+            Ok(())
+        }
+    }
+}
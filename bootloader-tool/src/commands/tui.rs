@@ -0,0 +1,288 @@
+use std::time::{Duration, Instant};
+
+use anyhow::bail;
+use ec_slimloader_state::state::{Slot, State, Status};
+use probe_rs::Session;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Modifier, Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Paragraph, Row, Table, TableState};
+use ratatui::{DefaultTerminal, Frame};
+
+use crate::ProbeArgs;
+use crate::config::Config;
+use crate::processors::device::{self, Snapshot};
+use crate::processors::{probe, state};
+
+const HELP: &str = "↑/↓ select slot  t set target  c confirm  e erase slot  r reset core  q quit";
+
+/// Action on the device that is only performed once confirmed, as it destroys an image
+#[derive(Debug, Clone, Copy)]
+enum Pending {
+    Erase(usize),
+}
+
+struct App<'a> {
+    config: &'a Config,
+    snapshot: Option<Snapshot>,
+    /// Error of the last refresh, shown in place of the snapshot
+    error: Option<String>,
+    slots: TableState,
+    pending: Option<Pending>,
+    /// Outcome of the last action
+    message: String,
+}
+
+pub async fn process(config: &Config, probe_args: ProbeArgs, refresh: Duration) -> anyhow::Result<()> {
+    if config.bootloader.is_none() {
+        bail!("Bootloader not defined in configuration file");
+    }
+
+    log::debug!("Starting probe session...");
+    let mut session = probe::start_session(&probe_args.chip, probe_args.probe.clone()).await?;
+
+    let mut app = App {
+        config,
+        snapshot: None,
+        error: None,
+        slots: TableState::default().with_selected(0),
+        pending: None,
+        message: String::new(),
+    };
+
+    let mut terminal = ratatui::init();
+    let result = app.run(&mut terminal, &mut session, refresh);
+    ratatui::restore();
+    result
+}
+
+impl App<'_> {
+    fn run(&mut self, terminal: &mut DefaultTerminal, session: &mut Session, refresh: Duration) -> anyhow::Result<()> {
+        let mut last_refresh: Option<Instant> = None;
+        loop {
+            if last_refresh.is_none_or(|last| last.elapsed() >= refresh) {
+                self.refresh(session);
+                last_refresh = Some(Instant::now());
+            }
+            terminal.draw(|frame| self.draw(frame))?;
+
+            if !event::poll(Duration::from_millis(50))? {
+                continue;
+            }
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+
+            if let Some(pending) = self.pending.take() {
+                if key.code == KeyCode::Char('y') {
+                    self.perform(terminal, session, pending)?;
+                    last_refresh = None;
+                } else {
+                    self.message = "Cancelled".to_string();
+                }
+                continue;
+            }
+
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                KeyCode::Up => self.slots.select_previous(),
+                KeyCode::Down => self.slots.select_next(),
+                KeyCode::Char('t') => {
+                    self.set_target(session);
+                    last_refresh = None;
+                }
+                KeyCode::Char('c') => {
+                    self.confirm(session);
+                    last_refresh = None;
+                }
+                KeyCode::Char('e') => {
+                    if let Some(slot) = self.selected() {
+                        self.pending = Some(Pending::Erase(slot));
+                        self.message = format!("Erase slot {slot}? Press y to confirm, any other key to cancel");
+                    }
+                }
+                KeyCode::Char('r') => {
+                    self.message = match session.core(0).and_then(|mut core| core.reset()) {
+                        Ok(()) => "Core reset".to_string(),
+                        Err(e) => format!("Failed to reset core: {e}"),
+                    };
+                    last_refresh = None;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn refresh(&mut self, session: &mut Session) {
+        let result = session
+            .core(0)
+            .map_err(anyhow::Error::from)
+            .and_then(|mut core| device::snapshot(&mut core, self.config));
+        match result {
+            Ok(snapshot) => {
+                self.snapshot = Some(snapshot);
+                self.error = None;
+            }
+            Err(e) => self.error = Some(format!("{e:#}")),
+        }
+    }
+
+    /// Index of the selected slot, if it is configured
+    fn selected(&self) -> Option<usize> {
+        let slot_count = self.config.application.as_ref()?.slot_starts.len();
+        self.slots.selected().filter(|slot| *slot < slot_count)
+    }
+
+    fn current_state(&self) -> Option<State> {
+        self.snapshot.as_ref().and_then(|snapshot| snapshot.state)
+    }
+
+    /// Boot the selected slot next, falling back to the current target
+    fn set_target(&mut self, session: &mut Session) {
+        let Some(target) = self.selected().and_then(|slot| Slot::try_from(slot as u8).ok()) else {
+            return;
+        };
+        let backup = self.current_state().map_or(target, |state| state.target());
+        self.write_state(session, State::new(Status::Initial, target, backup));
+    }
+
+    /// Mark the current target as booted successfully, as the application would
+    fn confirm(&mut self, session: &mut Session) {
+        let Some(state) = self.current_state() else {
+            self.message = "No state to confirm".to_string();
+            return;
+        };
+        self.write_state(session, state.with_status(Status::Confirmed));
+    }
+
+    fn write_state(&mut self, session: &mut Session, state: State) {
+        let range = &self.config.bootloader.as_ref().expect("checked in process").state;
+        self.message = match state::flash_seeded(session, range, &state) {
+            Ok(()) => format!("State journal reset to {state:?}"),
+            Err(e) => format!("Failed to write state: {e:#}"),
+        };
+    }
+
+    fn perform(
+        &mut self,
+        terminal: &mut DefaultTerminal,
+        session: &mut Session,
+        pending: Pending,
+    ) -> anyhow::Result<()> {
+        match pending {
+            Pending::Erase(slot) => {
+                let application = self.config.application.as_ref().expect("slot is configured");
+                let start = application.slot_starts[slot];
+
+                // Erasing a whole slot takes a while, so show what is going on.
+                self.message = format!("Erasing slot {slot}...");
+                terminal.draw(|frame| self.draw(frame))?;
+
+                self.message = match device::erase(session, start, application.slot_size) {
+                    Ok(()) => format!("Erased slot {slot}"),
+                    Err(e) => format!("Failed to erase slot {slot}: {e:#}"),
+                };
+            }
+        }
+        Ok(())
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [state_area, slots_area, fuses_area, message_area, help_area] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Min(4),
+            Constraint::Length(self.fuses_height()),
+            Constraint::Length(1),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        if let Some(error) = &self.error {
+            let error = Paragraph::new(error.as_str())
+                .red()
+                .block(Block::bordered().title("Device"));
+            frame.render_widget(error, Rect::union(state_area, fuses_area));
+        } else if let Some(snapshot) = &self.snapshot {
+            frame.render_widget(state_paragraph(snapshot), state_area);
+            frame.render_stateful_widget(slots_table(snapshot), slots_area, &mut self.slots);
+            frame.render_widget(fuses_table(snapshot), fuses_area);
+        }
+
+        frame.render_widget(Line::from(self.message.as_str()).bold(), message_area);
+        frame.render_widget(Line::from(HELP).dim(), help_area);
+    }
+
+    fn fuses_height(&self) -> u16 {
+        let words = self.snapshot.as_ref().map_or(0, |snapshot| snapshot.fuses.len());
+        words as u16 + 2
+    }
+}
+
+fn state_paragraph(snapshot: &Snapshot) -> Paragraph<'static> {
+    let text = match snapshot.state {
+        Some(state) => format!(
+            "{:?}, target {}, backup {}, user bits {:#04x}",
+            state.status(),
+            state.target(),
+            state.backup(),
+            state.user_bits()
+        ),
+        None => "Journal is empty or corrupt".to_string(),
+    };
+    Paragraph::new(text).block(Block::bordered().title("Boot state"))
+}
+
+fn slots_table(snapshot: &Snapshot) -> Table<'static> {
+    let role = |slot: usize| match snapshot.state {
+        Some(state) if u8::from(state.target()) as usize == slot => "target",
+        Some(state) if u8::from(state.backup()) as usize == slot => "backup",
+        _ => "",
+    };
+
+    let rows = snapshot.slots.iter().enumerate().map(|(i, slot)| {
+        let mut cells = vec![i.to_string(), format!("{:#010x}", slot.start), role(i).to_string()];
+        match &slot.image {
+            Some(image) => {
+                cells.push(image.kind.map_or("unknown".to_string(), |kind| format!("{kind:?}")));
+                cells.push(format!("{:#x}", image.len));
+                cells.push(
+                    image
+                        .metadata
+                        .as_ref()
+                        .map_or(String::new(), |metadata| metadata.to_string()),
+                );
+            }
+            None => cells.push("empty".to_string()),
+        }
+        Row::new(cells)
+    });
+
+    let widths = [
+        Constraint::Length(4),
+        Constraint::Length(12),
+        Constraint::Length(7),
+        Constraint::Length(16),
+        Constraint::Length(10),
+        Constraint::Fill(1),
+    ];
+    Table::new(rows, widths)
+        .header(Row::new(["Slot", "Start", "Role", "Type", "Length", "Metadata"]).bold())
+        .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED))
+        .block(Block::bordered().title("Slots"))
+}
+
+fn fuses_table(snapshot: &Snapshot) -> Table<'static> {
+    let rows = snapshot
+        .fuses
+        .iter()
+        .map(|(word, value)| Row::new([word.name.to_string(), word.index.to_string(), format!("{value:#010x}")]));
+
+    let widths = [Constraint::Length(14), Constraint::Length(6), Constraint::Fill(1)];
+    Table::new(rows, widths)
+        .header(Row::new(["Fuse", "Word", "Value (shadow)"]).bold())
+        .block(Block::bordered().title("Fuses"))
+}
@@ -0,0 +1,108 @@
+//! Completion of command line arguments in the shell, suggesting the values configured in the configuration file
+
+use std::io::Write;
+
+use anyhow::Context;
+use clap_complete::CompletionCandidate;
+use clap_complete::env::Shells;
+
+use crate::config::Hints;
+
+/// Environment variable through which the shell requests completions, see [clap_complete::CompleteEnv]
+pub const VAR: &str = "COMPLETE";
+
+/// Name of the binary to complete
+pub const BIN: &str = "bootloader-tool";
+
+/// Shells that completions can be registered for
+pub const SHELLS: [&str; 5] = ["bash", "elvish", "fish", "powershell", "zsh"];
+
+/// Write the script registering completions of this binary for `shell`
+///
+/// The script calls back into `completer` with [VAR] set, for every argument completed.
+pub fn write_registration(shell: &str, completer: &str, out: &mut dyn Write) -> anyhow::Result<()> {
+    let shells = Shells::builtins();
+    let Some(shell) = shells.completer(shell) else {
+        anyhow::bail!(
+            "Completions are not supported for {shell}, only for {}",
+            SHELLS.join(", ")
+        );
+    };
+    shell
+        .write_registration(VAR, BIN, BIN, completer, out)
+        .context("Could not write the completion script")
+}
+
+/// Value of the option `long` or `short` in `args`, if given
+fn option_value<'a>(args: &'a [String], long: &str, short: Option<&str>) -> Option<&'a str> {
+    args.iter().enumerate().rev().find_map(|(i, arg)| {
+        if arg == long || short == Some(arg.as_str()) {
+            args.get(i + 1).map(String::as_str)
+        } else {
+            arg.strip_prefix(long)?.strip_prefix('=')
+        }
+    })
+}
+
+/// Hints of the configuration file passed by `--config` and `--profile` on the command line being completed
+///
+/// Falls back to ignoring the profile if it does not exist, and to no hints at all if the configuration can not be read.
+fn hints() -> Hints {
+    let args: Vec<String> = std::env::args_os()
+        .map(|arg| arg.to_string_lossy().into_owned())
+        .collect();
+    let config = option_value(&args, "--config", Some("-c")).unwrap_or("./config.toml");
+    let profile = option_value(&args, "--profile", None);
+    Hints::read(config, profile)
+        .or_else(|_| Hints::read(config, None))
+        .unwrap_or_default()
+}
+
+/// Profiles in the configuration, for `--profile`
+pub fn profiles() -> Vec<CompletionCandidate> {
+    hints().profiles.into_iter().map(CompletionCandidate::new).collect()
+}
+
+/// Certificate chains that can sign images, for `--certificate`
+pub fn certificates() -> Vec<CompletionCandidate> {
+    hints()
+        .certificates
+        .into_iter()
+        .enumerate()
+        .map(|(root_key_slot, (chain, source))| {
+            CompletionCandidate::new(chain.to_string())
+                .help(Some(format!("{source} (root key slot {root_key_slot})").into()))
+        })
+        .collect()
+}
+
+/// Application slots by index, for `--slot` and the like
+pub fn slots() -> Vec<CompletionCandidate> {
+    hints()
+        .slot_starts
+        .into_iter()
+        .enumerate()
+        .map(|(slot, start)| CompletionCandidate::new(slot.to_string()).help(Some(format!("at 0x{start:08x}").into())))
+        .collect()
+}
+
+/// Application slots by index or relative to the latest state on the device, see [SlotRef](crate::processors::state::SlotRef)
+pub fn slot_refs() -> Vec<CompletionCandidate> {
+    let symbolic = [
+        ("current", "The slot the bootloader boots"),
+        ("target", "The target slot of the latest state"),
+        ("backup", "The backup slot of the latest state"),
+        (
+            "other",
+            "Whichever of the target and backup slot is not the current slot",
+        ),
+    ];
+    slots()
+        .into_iter()
+        .chain(
+            symbolic
+                .into_iter()
+                .map(|(name, help)| CompletionCandidate::new(name).help(Some(help.into()))),
+        )
+        .collect()
+}
@@ -1,8 +1,16 @@
 #![allow(unused)]
 
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 
+use anyhow::{Context, bail};
 use serde::Deserialize;
+use serde_json::Value;
+
+use crate::processors::certificates;
+
+/// Number of slots in the root key table of a cert block
+pub const ROOT_KEY_SLOT_COUNT: usize = 4;
 
 #[derive(Deserialize, Debug)]
 pub struct Config {
@@ -12,19 +20,113 @@ pub struct Config {
     /// Path of the file containing the OTP Master Key, used to encrypt the bootloader image.
     pub otp_path: PathBuf,
 
-    /// Certificate chains as used by this project.
+    /// Certificate chains as configured, either listed or read from a bundle.
+    #[serde(rename = "certificates")]
+    pub certificate_sources: Vec<CertificateChainSource>,
+
+    /// Certificate chains as used by this project, ordered root to leaf, see [Config::read_profile].
+    #[serde(skip)]
     pub certificates: Vec<CertificateChain>,
 
+    /// Index of the certificate chain populating each slot of the root key table, by default the chains in order.
+    ///
+    /// Chains left out are not part of the generated cert blocks, and can thus not sign images.
+    pub root_key_slots: Option<Vec<usize>>,
+
     /// Arguments related to the setup of the bootloader.
     pub bootloader: Option<BootloaderArgs>,
 
     /// Arguments related to application images.
     pub application: Option<ApplicationArgs>,
+
+    /// Options of Master Boot Images exported using nxpimage.
+    #[serde(default)]
+    pub mbi: MbiArgs,
+
+    /// Board specific boot configuration, as set by `run` and expected in the fuses.
+    #[serde(default)]
+    pub board: BoardArgs,
+
+    /// Audit log of signed images, appended to by `sign`.
+    #[serde(default)]
+    pub audit_log: AuditLogArgs,
+
+    /// Profile merged over the configuration, see [Config::read_profile].
+    #[serde(skip)]
+    pub profile: Option<String>,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Debug)]
 pub struct CertificateChain(pub Vec<Certificate>);
 
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub enum CertificateChainSource {
+    /// Certificates listed in any order.
+    Listed(Vec<Certificate>),
+    /// Certificates read from a bundle.
+    Bundle(CertificateBundle),
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CertificateBundle {
+    /// Path of the PKCS#12 (`.p12` or `.pfx`), DER or PEM file containing the certificates of the chain.
+    pub bundle: PathBuf,
+
+    /// Name of the environment variable holding the password of a PKCS#12 bundle, which is empty if not set.
+    pub password_env: Option<String>,
+}
+
+impl CertificateChainSource {
+    /// Certificates of the chain ordered root to leaf, writing those read from a bundle to `dir` for nxpimage
+    ///
+    /// Listed certificates that cannot be read yet, as they are still to be generated, are kept in the listed order.
+    fn resolve(&self, dir: &Path, chain_i: usize) -> anyhow::Result<CertificateChain> {
+        match self {
+            CertificateChainSource::Listed(certificates) => {
+                let Ok(ders) = certificates
+                    .iter()
+                    .map(|certificate| certificates::read_der(&certificate.path))
+                    .collect::<anyhow::Result<Vec<_>>>()
+                else {
+                    return Ok(CertificateChain(certificates.clone()));
+                };
+
+                let order = certificates::chain_order(&ders)?;
+                if !order.is_sorted() {
+                    log::debug!("Ordered certificate chain {chain_i} root to leaf as {order:?}");
+                }
+                Ok(CertificateChain(
+                    order.into_iter().map(|i| certificates[i].clone()).collect(),
+                ))
+            }
+            CertificateChainSource::Bundle(bundle) => {
+                let password = match &bundle.password_env {
+                    Some(name) => std::env::var(name)
+                        .with_context(|| format!("Password of {} not set in {name}", bundle.bundle.display()))?,
+                    None => String::new(),
+                };
+                let ders = certificates::read_bundle(&bundle.bundle, &password)?;
+                let order = certificates::chain_order(&ders)
+                    .with_context(|| format!("Invalid certificate chain in {}", bundle.bundle.display()))?;
+
+                std::fs::create_dir_all(dir).with_context(|| format!("Could not create {}", dir.display()))?;
+                let mut chain = vec![];
+                for (cert_i, i) in order.into_iter().enumerate() {
+                    let path = dir.join(format!("chain{chain_i}-{cert_i}.der"));
+                    // Leave unchanged certificates untouched, as concurrent invocations may be reading them.
+                    if std::fs::read(&path).ok().as_ref() != Some(&ders[i]) {
+                        std::fs::write(&path, &ders[i])
+                            .with_context(|| format!("Could not write {}", path.display()))?;
+                    }
+                    chain.push(Certificate { path, prototype: None });
+                }
+                Ok(CertificateChain(chain))
+            }
+        }
+    }
+}
+
 #[derive(Deserialize, Debug, Clone)]
 pub struct Certificate {
     /// Path of the file containing the public facing certificate.
@@ -53,7 +155,9 @@ pub struct MemoryRange {
 pub struct BootloaderArgs {
     /// Location in external NOR flash in which the bootloader should live. (must be 0x08001000)
     pub flash_start: u64,
-    /// Location in RAM which the bootloader should run from. (can be anything in RAM)
+    /// Location which the bootloader should run from. (can be anything in RAM, or `flash_start` to execute in place)
+    ///
+    /// A bootloader executing in place must be built with the `xip` feature of `ec-slimloader-imxrt`.
     pub run_start: u64,
     /// Maximum binary size of the image. (including certificates, hashes and encryption key)
     pub max_size: u64,
@@ -61,6 +165,19 @@ pub struct BootloaderArgs {
     ///
     /// Used to set a new state when ordering to start a specific application image slot.
     pub state: MemoryRange,
+    /// Memory location of the telemetry counters journal, when the bootloader is built with the `counters` feature.
+    ///
+    /// Only read, by the `monitor` command.
+    pub counters: Option<MemoryRange>,
+    /// ELF file of the bootloader on the device, to decode its logs when running an application.
+    pub elf_path: Option<PathBuf>,
+}
+
+impl BootloaderArgs {
+    /// Whether the bootloader is executed in place from flash, rather than copied to RAM by the ROM
+    pub fn executes_in_place(&self) -> bool {
+        self.run_start == self.flash_start
+    }
 }
 
 #[derive(Deserialize, Debug)]
@@ -71,10 +188,97 @@ pub struct ApplicationArgs {
     ///
     /// This address is hard-coded and checked in the bootloader.
     pub run_start: u64,
+    /// RAM the bootloader copies images into, as configured in `ImxrtConfig::LOAD_RANGE`.
+    ///
+    /// Images run from RAM are checked to fit, when given.
+    pub load_range: Option<MemoryRange>,
     /// Exactly the slot size, which is also the maximum size of the binary image. (including certificates and hashes)
     ///
     /// This size is hard-coded and checked in the bootloader.
     pub slot_size: u64,
+    /// Product ID recorded in the metadata of application images, when not given on the command line.
+    ///
+    /// Must match the product ID configured in the bootloader, or the image is refused.
+    pub product_id: Option<u32>,
+    /// Fixed regions within each slot holding blobs next to the image, such as radio firmware.
+    ///
+    /// The image itself must end before the first subregion.
+    #[serde(default)]
+    pub subregions: Vec<SubregionArgs>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct SubregionArgs {
+    /// Name by which the blob for this subregion is passed when signing.
+    pub name: String,
+    /// Offset from the start of the slot.
+    pub offset: u64,
+    /// Maximum size of the blob.
+    pub size: u64,
+    /// Whether to record the digest of the blob in the metadata trailer of the image.
+    #[serde(default)]
+    pub digest: bool,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct MbiArgs {
+    /// Chip family as known to SPSDK.
+    pub family: String,
+    /// Silicon revision as known to SPSDK.
+    pub revision: String,
+    /// Additional options passed to `nxpimage mbi export` as `-oc KEY=VALUE`, taking precedence over the generated ones.
+    pub overrides: BTreeMap<String, toml::Value>,
+}
+
+impl Default for MbiArgs {
+    fn default() -> Self {
+        Self {
+            family: "mimxrt685s".to_owned(),
+            revision: "latest".to_owned(),
+            overrides: BTreeMap::new(),
+        }
+    }
+}
+
+impl MbiArgs {
+    /// The overrides as `KEY=VALUE` options, with strings unquoted.
+    pub fn override_options(&self) -> impl Iterator<Item = String> + '_ {
+        self.overrides.iter().map(|(key, value)| match value {
+            toml::Value::String(value) => format!("{key}={value}"),
+            value => format!("{key}={value}"),
+        })
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct BoardArgs {
+    /// Preset of boot configuration fields to start from.
+    pub preset: BoardPreset,
+    /// Fields of BOOT_CFG0 by their name in `libs/imxrt-rom/registers.json`, taking precedence over the preset.
+    pub boot_cfg0: BTreeMap<String, u32>,
+    /// Fields of BOOT_CFG1 by their name in `libs/imxrt-rom/registers.json`, taking precedence over the preset.
+    pub boot_cfg1: BTreeMap<String, u32>,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct AuditLogArgs {
+    /// Path of the audit log (JSONL), by default `signing-audit.jsonl` in the artifacts path.
+    pub path: Option<PathBuf>,
+    /// Identity of the signer to record, by default the user running the tool.
+    pub signer: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BoardPreset {
+    /// Only the fields required for secure boot, leaving all pins unconfigured.
+    #[default]
+    Production,
+    /// The MIMXRT685-EVK, booting from QSPI B with its flash reset and boot failure pins.
+    Evk,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -95,8 +299,227 @@ impl KeyType {
     }
 }
 
+/// Format of a configuration file, as determined by its extension.
+enum Format {
+    Toml,
+    Yaml,
+    Json,
+}
+
+impl Format {
+    fn of(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("yaml" | "yml") => Format::Yaml,
+            Some("json") => Format::Json,
+            _ => Format::Toml,
+        }
+    }
+}
+
+/// Merge `overrides` into `base`, recursing into tables and replacing everything else, including lists.
+fn merge(base: &mut Value, overrides: Value) {
+    match (base, overrides) {
+        (Value::Object(base), Value::Object(overrides)) => {
+            for (key, value) in overrides {
+                match base.get_mut(&key) {
+                    Some(base) => merge(base, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overrides) => *base = overrides,
+    }
+}
+
+/// Read the configuration file at `path` with the files it includes merged in, in any [Format].
+///
+/// `stack` holds the files currently being read, to refuse including a file in itself.
+fn read_value(path: &Path, stack: &mut Vec<PathBuf>) -> anyhow::Result<Value> {
+    let canonical = path
+        .canonicalize()
+        .with_context(|| format!("Could not open {}", path.display()))?;
+    if stack.contains(&canonical) {
+        bail!("{} includes itself", path.display());
+    }
+
+    let text = std::fs::read_to_string(path).with_context(|| format!("Could not read {}", path.display()))?;
+    let mut value: Value = match Format::of(path) {
+        Format::Toml => toml::from_str(&text)?,
+        Format::Yaml => serde_yaml::from_str(&text)?,
+        Format::Json => serde_json::from_str(&text)?,
+    };
+
+    let includes = match value.as_object_mut().and_then(|table| table.remove("include")) {
+        Some(includes) => serde_json::from_value::<Vec<PathBuf>>(includes)
+            .with_context(|| format!("Expected a list of paths as include in {}", path.display()))?,
+        None => Vec::new(),
+    };
+
+    stack.push(canonical);
+    let mut merged = Value::Object(Default::default());
+    for include in includes {
+        // Included files are relative to the file including them.
+        let include = path.parent().unwrap_or(Path::new("")).join(include);
+        merge(&mut merged, read_value(&include, stack)?);
+    }
+    stack.pop();
+
+    merge(&mut merged, value);
+    Ok(merged)
+}
+
+/// Read the configuration file at `path` like [read_value], with `profile` from its `profiles` table merged over it.
+///
+/// Returns the names of all profiles alongside.
+fn read_merged(path: &Path, profile: Option<&str>) -> anyhow::Result<(Value, Vec<String>)> {
+    let mut value = read_value(path, &mut Vec::new())?;
+
+    let profiles = match value.as_object_mut().and_then(|table| table.remove("profiles")) {
+        Some(Value::Object(profiles)) => profiles,
+        Some(_) => bail!("Expected a table of profiles in {}", path.display()),
+        None => Default::default(),
+    };
+
+    if let Some(profile) = profile {
+        let Some(overrides) = profiles.get(profile) else {
+            bail!(
+                "Profile {profile} is not defined in {}, available profiles: {}",
+                path.display(),
+                profiles.keys().cloned().collect::<Vec<_>>().join(", ")
+            );
+        };
+        merge(&mut value, overrides.clone());
+    }
+
+    Ok((value, profiles.keys().cloned().collect()))
+}
+
 impl Config {
     pub fn read(path: impl AsRef<Path>) -> anyhow::Result<Self> {
-        Ok(toml::from_str::<Config>(&std::fs::read_to_string(path)?)?)
+        Self::read_profile(path, None)
+    }
+
+    /// Read the configuration in TOML, YAML or JSON, with `profile` from its `profiles` table merged over it.
+    ///
+    /// Files listed in `include` are merged in first, such that the including file overrides them. Certificate chains
+    /// are ordered root to leaf, with the certificates of bundles extracted to `<ARTIFACTS_PATH>/certificates`.
+    pub fn read_profile(path: impl AsRef<Path>, profile: Option<&str>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let (value, _) = read_merged(path, profile)?;
+
+        let mut config: Self =
+            serde_json::from_value(value).with_context(|| format!("Invalid configuration in {}", path.display()))?;
+        config.profile = profile.map(str::to_owned);
+
+        let dir = config.artifacts_path.join("certificates");
+        config.certificates = config
+            .certificate_sources
+            .iter()
+            .enumerate()
+            .map(|(chain_i, source)| {
+                source
+                    .resolve(&dir, chain_i)
+                    .with_context(|| format!("Invalid certificate chain {chain_i} in {}", path.display()))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        config
+            .root_key_chains()
+            .with_context(|| format!("Invalid root key slots in {}", path.display()))?;
+        Ok(config)
+    }
+
+    /// Index of the certificate chain in each slot of the root key table
+    pub fn root_key_chains(&self) -> anyhow::Result<Vec<usize>> {
+        let chains = match &self.root_key_slots {
+            Some(chains) => chains.clone(),
+            None => (0..self.certificates.len()).collect(),
+        };
+
+        if chains.len() > ROOT_KEY_SLOT_COUNT {
+            bail!(
+                "The root key table has {ROOT_KEY_SLOT_COUNT} slots, but {} certificate chains are placed in it",
+                chains.len()
+            );
+        }
+        for (slot, &chain) in chains.iter().enumerate() {
+            if chain >= self.certificates.len() {
+                bail!("Certificate chain {chain} in root key slot {slot} does not exist");
+            }
+            if chains[..slot].contains(&chain) {
+                bail!("Certificate chain {chain} is placed in multiple root key slots");
+            }
+        }
+        Ok(chains)
+    }
+
+    /// Slot of the root key table populated by the root certificate of certificate chain `certificate_idx`
+    pub fn root_key_slot(&self, certificate_idx: usize) -> anyhow::Result<usize> {
+        self.root_key_chains()?
+            .iter()
+            .position(|&chain| chain == certificate_idx)
+            .ok_or_else(|| anyhow::anyhow!("Certificate chain {certificate_idx} is not placed in the root key table"))
+    }
+}
+
+/// Values in the configuration that command line arguments refer to, such as to complete them in the shell
+///
+/// Read without validating the rest of the configuration nor resolving certificate bundles, such that it is cheap and
+/// works on configurations that are still incomplete.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Hints {
+    /// Names of the profiles in the `profiles` table
+    pub profiles: Vec<String>,
+    /// Index of each certificate chain that can sign images, as it is placed in the root key table, with its source
+    ///
+    /// The source is the path of the leaf certificate of listed chains, or the path of the bundle.
+    pub certificates: Vec<(usize, String)>,
+    /// Starting address of each application slot
+    pub slot_starts: Vec<u64>,
+}
+
+impl Hints {
+    /// Read the hints from the configuration file at `path`, with `profile` merged over it like [Config::read_profile]
+    pub fn read(path: impl AsRef<Path>, profile: Option<&str>) -> anyhow::Result<Self> {
+        let (value, profiles) = read_merged(path.as_ref(), profile)?;
+
+        let sources: Vec<String> = match value.get("certificates") {
+            Some(Value::Array(chains)) => chains
+                .iter()
+                .map(|chain| {
+                    let path = match chain {
+                        Value::Array(certificates) => {
+                            certificates.last().and_then(|certificate| certificate.get("path"))
+                        }
+                        chain => chain.get("bundle"),
+                    };
+                    path.and_then(Value::as_str).unwrap_or_default().to_owned()
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        let chains: Vec<usize> = match value.get("root_key_slots") {
+            Some(slots) => {
+                serde_json::from_value(slots.clone()).context("Expected a list of indices as root_key_slots")?
+            }
+            None => (0..sources.len()).collect(),
+        };
+        let certificates = chains
+            .into_iter()
+            .filter_map(|chain| Some((chain, sources.get(chain)?.clone())))
+            .collect();
+
+        let slot_starts = match value.pointer("/application/slot_starts") {
+            Some(slot_starts) => serde_json::from_value(slot_starts.clone())
+                .context("Expected a list of addresses as application.slot_starts")?,
+            None => Vec::new(),
+        };
+
+        Ok(Self {
+            profiles,
+            certificates,
+            slot_starts,
+        })
     }
 }
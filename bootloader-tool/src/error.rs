@@ -0,0 +1,123 @@
+//! Classification of failures into kinds with stable exit codes, for scripts driving the tool
+//!
+//! Errors remain [anyhow::Error]s throughout the tool. Where the kind of a failure is known, it is attached using
+//! [Classify::classify] without changing the message, and retrieved again at the top level using [ErrorKind::of].
+
+use std::error::Error as StdError;
+use std::fmt;
+
+use serde::Serialize;
+
+/// Kind of failure, each with its own exit code
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ErrorKind {
+    /// Anything not classified otherwise
+    Other,
+    /// The configuration could not be read, or is invalid
+    Config,
+    /// A file could not be read or written
+    Io,
+    /// No probe was found, or the probe could not attach to or access the device
+    Probe,
+    /// An image, signature, certificate, manifest or fuse does not match what it is checked against
+    Verification,
+    /// An external tool such as nxpimage could not be executed, or failed
+    ExternalTool,
+}
+
+impl ErrorKind {
+    /// Exit code of the process when failing with this kind of error
+    ///
+    /// These are stable across versions. Code 2 is left to clap, which exits with it for invalid arguments.
+    pub fn exit_code(self) -> u8 {
+        match self {
+            ErrorKind::Other => 1,
+            ErrorKind::Config => 3,
+            ErrorKind::Io => 4,
+            ErrorKind::Probe => 5,
+            ErrorKind::Verification => 6,
+            ErrorKind::ExternalTool => 7,
+        }
+    }
+
+    /// Kind of `error`, as attached using [Classify::classify] or otherwise derived from the errors it wraps
+    ///
+    /// The outermost attached kind takes precedence.
+    pub fn of(error: &anyhow::Error) -> Self {
+        if let Some(kind) = error
+            .chain()
+            .find_map(|cause| cause.downcast_ref::<Classified>().map(|classified| classified.kind))
+        {
+            return kind;
+        }
+
+        for cause in error.chain() {
+            if cause.is::<probe_rs::Error>() || cause.is::<probe_rs::probe::DebugProbeError>() {
+                return ErrorKind::Probe;
+            }
+            if cause.is::<std::io::Error>() {
+                return ErrorKind::Io;
+            }
+        }
+
+        ErrorKind::Other
+    }
+}
+
+/// Error of which the kind is known, displayed exactly like the error it wraps
+#[derive(Debug)]
+struct Classified {
+    kind: ErrorKind,
+    error: anyhow::Error,
+}
+
+impl fmt::Display for Classified {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.error, f)
+    }
+}
+
+impl StdError for Classified {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        self.error.chain().nth(1)
+    }
+}
+
+/// Attach an [ErrorKind] to the error of a result
+pub trait Classify<T> {
+    fn classify(self, kind: ErrorKind) -> anyhow::Result<T>;
+}
+
+impl<T, E: Into<anyhow::Error>> Classify<T> for Result<T, E> {
+    fn classify(self, kind: ErrorKind) -> anyhow::Result<T> {
+        self.map_err(|error| {
+            anyhow::Error::new(Classified {
+                kind,
+                error: error.into(),
+            })
+        })
+    }
+}
+
+/// Error as printed with `--output json`
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub kind: ErrorKind,
+    pub exit_code: u8,
+    pub message: String,
+    /// Messages of the underlying errors, outermost first
+    pub causes: Vec<String>,
+}
+
+impl Report {
+    pub fn new(error: &anyhow::Error) -> Self {
+        let kind = ErrorKind::of(error);
+        Self {
+            kind,
+            exit_code: kind.exit_code(),
+            message: error.to_string(),
+            causes: error.chain().skip(1).map(|cause| cause.to_string()).collect(),
+        }
+    }
+}
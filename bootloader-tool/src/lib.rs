@@ -1,11 +1,22 @@
+use std::ffi::OsString;
+use std::num::NonZeroUsize;
 use std::path::PathBuf;
 
-use clap::{Args, Parser, Subcommand};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use clap_complete::ArgValueCandidates;
+use ec_slimloader_state::state::Status;
+use mbi_format::{ImageRole, SlotBinding};
 
-pub use crate::config::Config;
+pub use crate::config::{Config, Hints};
+use crate::processors::artifacts::{Artifact, ArtifactLayout};
+use crate::processors::mbi::vectors::Vector;
+use crate::processors::state::SlotRef;
 
+pub mod api;
 pub mod commands;
+pub mod completion;
 mod config;
+pub mod error;
 pub mod processors;
 mod util;
 
@@ -16,10 +27,35 @@ pub struct Cli {
     #[arg(short, long, value_name = "FILE", default_value = "./config.toml")]
     pub config: PathBuf,
 
+    /// Profile from the `profiles` table of the configuration file to merge over the rest of it
+    #[arg(long, value_name = "PROFILE", add = ArgValueCandidates::new(completion::profiles))]
+    pub profile: Option<String>,
+
+    /// Format in which errors are reported
+    ///
+    /// The exit code identifies the kind of error in either format, see [error::ErrorKind::exit_code]
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    pub output: OutputFormat,
+
+    /// Only print the changes that commands would make, such as flash writes, register writes and resets
+    ///
+    /// Images are still signed, but the device is not touched, nor are files removed by `clean`. Commands that only
+    /// read from the device are not affected
+    #[arg(long, global = true)]
+    pub dry_run: bool,
+
     #[command(subcommand)]
     pub commands: Option<Commands>,
 }
 
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Human readable, on stderr
+    Text,
+    /// A single JSON object on stdout, see [error::Report]
+    Json,
+}
+
 #[derive(Subcommand, Debug)]
 pub enum Commands {
     /// Generate keys and certificates
@@ -42,8 +78,148 @@ pub enum Commands {
         #[command(subcommand)]
         subcommand: RunCommands,
     },
-    /// Burn fuse registers with key material and settings
-    Fuse,
+    /// Prepare over-the-air updates
+    Ota {
+        #[command(subcommand)]
+        subcommand: OtaCommands,
+    },
+    /// Inspect signed binaries
+    Inspect {
+        #[command(subcommand)]
+        subcommand: InspectCommands,
+    },
+    /// Read and verify fuse registers containing key material and settings
+    Fuse {
+        #[command(subcommand)]
+        subcommand: FuseCommands,
+    },
+    /// Provision devices, and record and audit their provisioning on a factory line
+    Provision {
+        #[command(subcommand)]
+        subcommand: ProvisionCommands,
+    },
+    /// Manage the bootloader state journal on the device
+    State {
+        #[command(subcommand)]
+        subcommand: StateCommands,
+    },
+    /// Export and import the contents of application slots on the device
+    Slot {
+        #[command(subcommand)]
+        subcommand: SlotCommands,
+    },
+    /// Generate inputs for testing the bootloader on a device
+    Test {
+        #[command(subcommand)]
+        subcommand: TestCommands,
+    },
+    /// Restrict debug access to debuggers authenticating with a debug credential
+    ///
+    /// Writes DCFG_CC_SOCU and DCFG_CC_SOCU_NS to the shadow registers and resets the device, and prints the values to
+    /// burn into the fuses for production
+    Lock(LockArguments),
+    /// Open up debug access to a device, using the debug authentication flow if debug access is restricted
+    Unlock(UnlockArguments),
+    /// Remove the files generated by the other commands
+    Clean(CleanArguments),
+    /// Print the script registering completions for a shell, suggesting slots, certificates and profiles
+    ///
+    /// Suggestions are read from the `--config` and `--profile` on the command line being completed, so they follow the
+    /// configuration as it changes. Register on shell startup, for example with `source <(bootloader-tool completions
+    /// bash)` in `~/.bashrc`
+    Completions(CompletionsArguments),
+    /// Generate manual pages for every command
+    Man(ManArguments),
+    /// Interactively monitor and manage the boot state and slots of a device
+    Tui {
+        #[command(flatten)]
+        probe_args: ProbeArgs,
+
+        /// Interval between reads of the device in milliseconds
+        #[arg(long, default_value_t = 1000)]
+        refresh_ms: u64,
+    },
+    /// Print the boot status of a running device whenever it changes, for example during power-cycling tests
+    ///
+    /// Reads the state journal configured as `bootloader.state` and the counters journal configured as
+    /// `bootloader.counters`, and attaches again whenever the device goes away
+    Monitor {
+        #[command(flatten)]
+        probe_args: ProbeArgs,
+
+        /// Interval between reads of the device in milliseconds
+        #[arg(long, default_value_t = 200)]
+        refresh_ms: u64,
+    },
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct LockArguments {
+    #[command(flatten)]
+    probe_args: ProbeArgs,
+
+    /// Debug access to leave open to unauthenticated debuggers
+    #[arg(long, value_enum, value_name = "FEATURE", num_args = 1..)]
+    allow: Vec<processors::debug::Feature>,
+
+    /// Only accept debug credentials issued for the UUID of the device
+    #[arg(long)]
+    uuid_check: bool,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct UnlockArguments {
+    #[command(flatten)]
+    probe_args: ProbeArgs,
+
+    /// Debug credential (DC) file, as exported by `nxpdebugmbox dc export`
+    ///
+    /// Only needed when debug access is restricted
+    #[arg(long, value_name = "DC_FILE")]
+    credential: Option<PathBuf>,
+
+    /// Private key of the debug credential, signing the debug authentication response
+    #[arg(long, value_name = "KEY_FILE")]
+    key: Option<PathBuf>,
+
+    /// Beacon passed to the application through the debug authentication response
+    #[arg(long, default_value_t = 0)]
+    beacon: u16,
+
+    /// Where the nxpdebugmbox binary can be found. May be on PATH
+    #[arg(long, default_value = "nxpdebugmbox")]
+    nxpdebugmbox_path: PathBuf,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct CleanArguments {
+    /// Remove the images generated from these ELF files, such as the signed images
+    ///
+    /// Only files at the default output paths are removed
+    #[arg(long, value_name = "INPUT_FILE", num_args = 1..)]
+    images: Vec<PathBuf>,
+    /// Directory the images were generated in [default: <ARTIFACTS_PATH>/images/<PROFILE>]
+    #[arg(long, value_name = "ARTIFACT_DIR")]
+    artifact_dir: Option<PathBuf>,
+    /// Remove the generated private keys, certificates and OTP master key from the configuration
+    ///
+    /// These can not be recovered, and images signed with them can no longer be reproduced
+    #[arg(long)]
+    keys: bool,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct CompletionsArguments {
+    /// Shell to register the completions in
+    #[arg(value_parser = completion::SHELLS)]
+    shell: String,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct ManArguments {
+    /// Directory to write the manual pages to, one per command
+    #[arg(long, value_name = "DIR", default_value = "./man")]
+    output_dir: PathBuf,
 }
 
 #[derive(Args, Debug, Clone)]
@@ -55,6 +231,10 @@ pub struct GenerateCertificatesArguments {
     /// Where the nxpimage binary can be found. May be on PATH
     #[arg(long, default_value = "nxpimage")]
     nxpimage_path: PathBuf,
+
+    /// Number of keys and certificates to generate at once [default: number of CPUs]
+    #[arg(short, long, value_name = "JOBS")]
+    jobs: Option<NonZeroUsize>,
 }
 
 #[derive(Subcommand, Debug, Clone)]
@@ -63,68 +243,230 @@ pub enum GenerateCommands {
     Certificates(GenerateCertificatesArguments),
     /// Generate an OTP encryption master key (used for header integrity validation)
     Otp,
+    /// Generate a FlexSPI NOR Configuration Block (FCB) from a TOML description of the flash
+    Fcb(GenerateFcbArguments),
+    /// Generate a signed Master Boot Image (MBI) with nxpimage, templating its configuration from config.toml
+    Mbi(GenerateMbiArguments),
+    /// Generate a Rust module of constants describing the flash layout from config.toml, for the firmware to include
+    LayoutRs(GenerateLayoutRsArguments),
 }
 
 #[derive(Args, Debug, Clone)]
-pub struct SignArguments {
+pub struct GenerateLayoutRsArguments {
+    /// Output file path of the Rust module [default: <ARTIFACTS_PATH>/layout.rs]
+    #[arg(short, long, value_name = "OUTPUT_FILE")]
+    output_path: Option<PathBuf>,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct GenerateFcbArguments {
+    /// Flash description (TOML)
+    #[arg(short, long, value_name = "INPUT_FILE")]
+    input_path: PathBuf,
+    /// Output file path of the FCB (BIN) [default: <INPUT_FILE>.fcb.bin]
+    #[arg(short, long, value_name = "OUTPUT_FILE")]
+    output_path: Option<PathBuf>,
+    /// Output file path of the FCB as Rust source [default: <INPUT_FILE>.fcb.rs]
+    #[arg(long, value_name = "OUTPUT_RUST_FILE")]
+    output_rust_path: Option<PathBuf>,
+    /// Output file path of the FCB partition for the `runtime-fcb` feature (BIN) [default: <INPUT_FILE>.fcb-partition.bin]
+    #[arg(long, value_name = "OUTPUT_PARTITION_FILE")]
+    output_partition_path: Option<PathBuf>,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct GenerateMbiArguments {
     /// Input file path (ELF)
     #[arg(short, long, value_name = "INPUT_FILE")]
     input_path: PathBuf,
+    /// Output file path (BIN) [default: <ARTIFACT_DIR>/<INPUT_FILE>.nxp.bin]
+    #[arg(short, long, value_name = "OUTPUT_FILE")]
+    output_path: Option<PathBuf>,
+    /// Directory of the output file when not given explicitly [default: <ARTIFACTS_PATH>/images/<PROFILE>]
+    #[arg(long, value_name = "ARTIFACT_DIR")]
+    artifact_dir: Option<PathBuf>,
+    /// Index of the certificate intended to sign the image with
+    ///
+    /// The private key of the leaf of this chain needs to be configured
+    #[arg(long, value_name = "CERTIFICATE_IDX", default_value = "0", add = ArgValueCandidates::new(completion::certificates))]
+    certificate: usize,
+    /// Additional nxpimage configuration option, replacing the one from config.toml or the generated configuration
+    ///
+    /// May be passed multiple times
+    #[arg(long = "override", value_name = "KEY=VALUE", value_parser = util::parse_key_value)]
+    overrides: Vec<(String, String)>,
+    /// Where the nxpimage binary can be found. May be on PATH
+    #[arg(long, default_value = "nxpimage")]
+    nxpimage_path: PathBuf,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct SignArguments {
+    /// Input file path (ELF)
+    #[arg(short, long, value_name = "INPUT_FILE")]
+    pub input_path: PathBuf,
     /// Signature file
     ///
     /// If present, will be checked against image and merged into output path
     #[arg(short, long, value_name = "SIGNATURE_FILE")]
-    signature_path: Option<PathBuf>,
-    /// Output file path of unsigned application (BIN) [default: <INPUT_FILE>.unsigned.bin]
+    pub signature_path: Option<PathBuf>,
+    /// Output file path of unsigned application (BIN) [default: <ARTIFACT_DIR>/<INPUT_FILE>.unsigned.bin]
     #[arg(long, value_name = "OUTPUT_UNSIGNED_FILE")]
-    output_unsigned_path: Option<PathBuf>,
-    /// Output file path of unsigned Master Boot Image (BIN, without signature) [default: <INPUT_FILE>.mbi-proto.bin]
+    pub output_unsigned_path: Option<PathBuf>,
+    /// Output file path of unsigned Master Boot Image (BIN, without signature) [default: <ARTIFACT_DIR>/<INPUT_FILE>.mbi-proto.bin]
     #[arg(long, value_name = "OUTPUT_PRESTAGE_FILE")]
-    output_prestage_path: Option<PathBuf>,
-    /// Output file path (BIN) [default: <INPUT_FILE>.signed.bin]
+    pub output_prestage_path: Option<PathBuf>,
+    /// Output file path (BIN) [default: <ARTIFACT_DIR>/<INPUT_FILE>.signed.bin]
     #[arg(short, long, value_name = "OUTPUT_FILE")]
-    output_path: Option<PathBuf>,
+    pub output_path: Option<PathBuf>,
+    /// Directory of the output files not given explicitly [default: <ARTIFACTS_PATH>/images/<PROFILE>]
+    ///
+    /// The profile is `default` when no `--profile` is selected
+    #[arg(long, value_name = "ARTIFACT_DIR")]
+    pub artifact_dir: Option<PathBuf>,
     /// Do not actually sign the image only export the prestage for external signing by HSM
     #[arg(long)]
-    dont_sign: bool,
+    pub dont_sign: bool,
     /// Index of the certificate intended to sign the image with
     ///
     /// Used to generate the appropriate certificate block for this image
     ///
     /// When this tool is used to generate a signature, the private key also needs to be configured
-    #[arg(long, value_name = "CERTIFICATE_IDX", default_value = "0")]
-    certificate: usize,
-    /// Prelude output file path (BIN) [default: <INPUT_FILE>.prelude.bin]
+    #[arg(long, value_name = "CERTIFICATE_IDX", default_value = "0", add = ArgValueCandidates::new(completion::certificates))]
+    pub certificate: usize,
+    /// Prelude output file path (ELF) [default: <ARTIFACT_DIR>/<INPUT_FILE>.prelude.elf]
     #[arg(long)]
-    prelude_path: Option<PathBuf>,
+    pub prelude_path: Option<PathBuf>,
     /// Where the nxpimage binary can be found. May be on PATH
     #[arg(long, default_value = "nxpimage")]
-    nxpimage_path: PathBuf,
+    pub nxpimage_path: PathBuf,
+    #[command(flatten)]
+    pub metadata: MetadataArgs,
+    /// Fail instead of warn when the signed image exceeds the maximum size from the configuration, or when a
+    /// certificate in the chain expires within the `--expiry-window`
+    ///
+    /// That is `bootloader.max_size` for the bootloader, and `application.slot_size` for applications
+    #[arg(long)]
+    pub strict: bool,
+    /// Warn when a certificate in the chain is not valid for at least this many more days
+    #[arg(long, value_name = "DAYS", default_value_t = 90)]
+    pub expiry_window: u32,
+    /// Blob to place in a subregion of the slot, as configured in `application.subregions`
+    ///
+    /// Either all subregions are given, or none. May be passed multiple times
+    #[arg(long = "subregion", value_name = "NAME=PATH", value_parser = util::parse_key_value)]
+    pub subregions: Vec<(String, String)>,
+    /// Output file path of the slot image, containing the signed image and the subregions (BIN) [default: <ARTIFACT_DIR>/<INPUT_FILE>.slot.bin]
+    #[arg(long, value_name = "OUTPUT_SLOT_FILE")]
+    pub output_slot_path: Option<PathBuf>,
+    /// Do not record the signed image in the audit log configured as `audit_log`
+    #[arg(long)]
+    pub no_audit_log: bool,
+    /// Largest gap between segments of the ELF to fill with 0xFF, in bytes, decimal or hexadecimal with 0x prefix
+    ///
+    /// Larger gaps are rejected, as they likely stem from a segment linked to the wrong memory region
+    #[arg(long, value_name = "BYTES", value_parser = util::parse_u32, default_value_t = processors::objcopy::DEFAULT_MAX_GAP)]
+    pub max_gap: u32,
+    /// Bind the application image to a slot, either its index or `any`
+    ///
+    /// The binding is covered by the signature. The bootloader warns about or refuses an image read from another slot,
+    /// as configured by its `SLOT_BINDING` policy
+    #[arg(long, value_name = "SLOT", value_parser = util::parse_slot_binding)]
+    pub bind_slot: Option<SlotBinding>,
+}
+
+/// Firmware identity appended as metadata trailer after the signature
+///
+/// The trailer is only appended when any of these is given, and is not covered by the signature.
+#[derive(Args, Debug, Clone)]
+pub struct MetadataArgs {
+    /// Version of the image to record in the metadata trailer
+    #[arg(long, value_name = "VERSION")]
+    pub image_version: Option<String>,
+    /// Product ID to record in the metadata trailer, decimal or hexadecimal with 0x prefix
+    #[arg(long, value_parser = util::parse_u32)]
+    pub product_id: Option<u32>,
+    /// Git commit hash to record in the metadata trailer
+    #[arg(long)]
+    pub git_hash: Option<String>,
+    /// Build time to record in the metadata trailer, in seconds since the Unix epoch
+    #[arg(long)]
+    pub build_time: Option<u64>,
+    /// Role of the image in the boot chain to record in the metadata trailer
+    ///
+    /// Mark a secondary loader booted by the bootloader as `loader`, such that the bootloader hands its boot info
+    /// over to it. Images without a role are applications
+    #[arg(long)]
+    pub role: Option<RoleArg>,
+    /// Stage the image, such that the bootloader does not boot it as new target until the application activates it
+    ///
+    /// Allows installing an update ahead of a staged rollout, activated later on by a command of the host
+    #[arg(long)]
+    pub staged: bool,
+    /// Record the SHA-256 of the signed image in the metadata trailer
+    ///
+    /// Checked by a bootloader configured for digest verification in development mode, instead of the signature
+    #[arg(long)]
+    pub image_digest: bool,
+}
+
+impl MetadataArgs {
+    pub fn metadata(&self) -> processors::mbi::metadata::Metadata {
+        processors::mbi::metadata::Metadata {
+            version: self.image_version.clone(),
+            product_id: self.product_id,
+            git_hash: self.git_hash.clone(),
+            build_time: self.build_time,
+            role: self.role.map(Into::into),
+            staged: self.staged,
+            subregions: Vec::new(),
+            // Placeholder of the right length, filled in once the image is signed
+            image_digest: self.image_digest.then_some([0; 32]),
+        }
+    }
 }
 
 impl SignArguments {
-    pub fn output_unsigned_path_with_default(&self) -> PathBuf {
+    /// Arguments to sign the ELF file at `input_path`, with the defaults of the command line for everything else
+    pub fn new(input_path: impl Into<PathBuf>) -> Self {
+        let mut input_arg = OsString::from("--input-path=");
+        input_arg.push(input_path.into());
+        util::parse_args([input_arg])
+    }
+
+    /// Locations of the output files not given explicitly
+    pub fn layout(&self, config: &Config) -> ArtifactLayout {
+        ArtifactLayout::new(config, &self.input_path, self.artifact_dir.as_deref())
+    }
+
+    pub fn output_unsigned_path_with_default(&self, config: &Config) -> PathBuf {
         self.output_unsigned_path
             .clone()
-            .unwrap_or_else(|| self.input_path.clone().with_extension("unsigned.bin"))
+            .unwrap_or_else(|| self.layout(config).path(Artifact::Unsigned))
     }
 
-    pub fn output_prestage_path_with_default(&self) -> PathBuf {
+    pub fn output_prestage_path_with_default(&self, config: &Config) -> PathBuf {
         self.output_prestage_path
             .clone()
-            .unwrap_or_else(|| self.input_path.clone().with_extension("mbi-proto.bin"))
+            .unwrap_or_else(|| self.layout(config).path(Artifact::Prestage))
     }
 
-    pub fn output_path_with_default(&self) -> PathBuf {
+    pub fn output_path_with_default(&self, config: &Config) -> PathBuf {
         self.output_path
             .clone()
-            .unwrap_or_else(|| self.input_path.clone().with_extension("signed.bin"))
+            .unwrap_or_else(|| self.layout(config).path(Artifact::Signed))
+    }
+
+    pub fn output_slot_path_with_default(&self, config: &Config) -> PathBuf {
+        self.output_slot_path
+            .clone()
+            .unwrap_or_else(|| self.layout(config).path(Artifact::Slot))
     }
 
-    pub fn prelude_path_with_default(&self) -> PathBuf {
+    pub fn prelude_path_with_default(&self, config: &Config) -> PathBuf {
         self.prelude_path
             .clone()
-            .unwrap_or_else(|| self.input_path.clone().with_extension("prelude.elf"))
+            .unwrap_or_else(|| self.layout(config).path(Artifact::Prelude))
     }
 }
 
@@ -134,30 +476,120 @@ pub enum SignCommands {
     Bootloader(SignArguments),
     /// Sign an application image
     Application(SignArguments),
+    /// Sign several bootloader and application images at once, running the external tools concurrently
+    ///
+    /// All images are signed with the same certificate chain, and otherwise the defaults of the other commands
+    Batch(SignBatchArguments),
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct SignBatchArguments {
+    /// Bootloader input file path (ELF). May be passed multiple times
+    #[arg(long = "bootloader", value_name = "INPUT_FILE")]
+    pub bootloaders: Vec<PathBuf>,
+    /// Application input file path (ELF). May be passed multiple times
+    #[arg(long = "application", value_name = "INPUT_FILE")]
+    pub applications: Vec<PathBuf>,
+    /// Index of the certificate to sign all images with
+    #[arg(long, value_name = "CERTIFICATE_IDX", default_value = "0", add = ArgValueCandidates::new(completion::certificates))]
+    pub certificate: usize,
+    /// Where the nxpimage binary can be found. May be on PATH
+    #[arg(long, default_value = "nxpimage")]
+    pub nxpimage_path: PathBuf,
+    /// Number of images to sign at once [default: number of CPUs]
+    #[arg(short, long, value_name = "JOBS")]
+    pub jobs: Option<NonZeroUsize>,
+    /// Fail instead of warn when a signed image exceeds its maximum size, or a certificate expires soon
+    #[arg(long)]
+    pub strict: bool,
+    /// Directory of the output files [default: <ARTIFACTS_PATH>/images/<PROFILE>]
+    #[arg(long, value_name = "ARTIFACT_DIR")]
+    pub artifact_dir: Option<PathBuf>,
+}
+
+impl SignBatchArguments {
+    /// Arguments to sign each image of the batch, with whether it is a bootloader
+    pub fn images(&self) -> Vec<(bool, SignArguments)> {
+        let bootloaders = self.bootloaders.iter().map(|path| (true, path));
+        let applications = self.applications.iter().map(|path| (false, path));
+        bootloaders
+            .chain(applications)
+            .map(|(is_bootloader, path)| {
+                let mut args = SignArguments::new(path);
+                args.certificate = self.certificate;
+                args.nxpimage_path = self.nxpimage_path.clone();
+                args.strict = self.strict;
+                args.artifact_dir = self.artifact_dir.clone();
+                (is_bootloader, args)
+            })
+            .collect()
+    }
 }
 
 #[derive(Args, Debug, Clone)]
 pub struct RunArguments {
     #[command(flatten)]
-    sign_args: SignArguments,
+    pub sign_args: SignArguments,
 
     #[command(flatten)]
-    probe_args: ProbeArgs,
+    pub probe_args: ProbeArgs,
 
     /// Where the probe-rs binary can be found. May be on PATH
     #[arg(long, default_value = "probe-rs")]
-    probe_rs_path: PathBuf,
+    pub probe_rs_path: PathBuf,
+
+    /// Sign the image even if nothing changed since it was last signed
+    ///
+    /// Otherwise the signed image is reused when the input, configuration, keys and arguments are unchanged
+    #[arg(long)]
+    pub force_sign: bool,
+
+    /// Do not read back the flashed image to compare it against the signed image
+    #[arg(long)]
+    pub no_verify: bool,
+
+    /// ELF file of the bootloader on the device, to also decode its logs when running an application
+    ///
+    /// The logs of the bootloader are then printed until it hands off to the application [default:
+    /// `bootloader.elf_path` of the configuration]
+    #[arg(long, value_name = "ELF_FILE")]
+    pub bootloader_elf: Option<PathBuf>,
+
+    /// Where the defmt-print binary can be found, to decode the logs of both the bootloader and the application. May
+    /// be on PATH
+    #[arg(long, default_value = "defmt-print")]
+    pub defmt_print_path: PathBuf,
+}
+
+impl RunArguments {
+    /// Arguments to download or run the image signed with `sign_args`, with the defaults of the command line for
+    /// everything else
+    pub fn new(sign_args: SignArguments) -> Self {
+        let mut input_arg = OsString::from("--input-path=");
+        input_arg.push(&sign_args.input_path);
+        Self {
+            sign_args,
+            ..util::parse_args([input_arg])
+        }
+    }
 }
 
 #[derive(Args, Debug, Clone)]
 pub struct ProbeArgs {
     /// Which probe to use (passed to probe-rs)
     #[arg(short, long, value_name = "PROBE")]
-    probe: Option<String>,
+    pub probe: Option<String>,
 
     /// Type of chip to be programmed (passed to probe-rs)
     #[arg(short, long, value_name = "CHIP", default_value = "MIMXRT685SFVKB")]
-    chip: String,
+    pub chip: String,
+}
+
+impl Default for ProbeArgs {
+    /// Use the only connected probe, to program the chip of the command line default
+    fn default() -> Self {
+        util::parse_args::<Self>([] as [OsString; 0])
+    }
 }
 
 #[derive(Subcommand, Debug, Clone)]
@@ -172,11 +604,311 @@ pub enum RunCommands {
         run_args: RunArguments,
 
         /// Image slot to which to upload the binary to
-        #[arg(long, default_value_t = 0)]
-        slot: u8,
+        ///
+        /// Either an index, or one of `current`, `target`, `backup` or `other` to resolve against the state journal
+        /// on the device first, even on a dry run
+        #[arg(long, default_value_t = SlotRef::Index(0), add = ArgValueCandidates::new(completion::slot_refs))]
+        slot: SlotRef,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum OtaCommands {
+    /// Generate a delta patch that reconstructs the new image from the old image
+    Diff {
+        /// Signed image currently on the device (BIN)
+        old: PathBuf,
+        /// Signed image to update to (BIN)
+        new: PathBuf,
+        /// Output file path of the patch [default: <NEW>.patch]
+        #[arg(short, long, value_name = "OUTPUT_FILE")]
+        output_path: Option<PathBuf>,
+    },
+    /// Wrap a signed image in an update package for the `Writer` of the `ec-slimloader-ota` crate
+    Package {
+        /// Signed image to update to (BIN)
+        input: PathBuf,
+        /// Output file path of the package [default: <INPUT>.ota]
+        #[arg(short, long, value_name = "OUTPUT_FILE")]
+        output_path: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum InspectCommands {
+    /// Report the differences between two signed images
+    ///
+    /// Compares the headers, cert blocks, RKTH, signatures and payloads of both images
+    Diff {
+        /// Signed image to compare against (BIN)
+        a: PathBuf,
+        /// Signed image to compare (BIN)
+        b: PathBuf,
+    },
+    /// Decode the image header, cert block header and root key table of any binary
+    ///
+    /// Nothing is verified, such that images rejected on the device can be inspected. Decoding stops at the first
+    /// structure that does not fit the binary
+    Mbi {
+        /// Image to decode (BIN)
+        image: PathBuf,
+    },
+    /// List the configured certificates with their validity windows
+    ///
+    /// Marks certificates that are not valid for at least another `--expiry-window` days
+    Certificates {
+        /// Number of days within which a certificate is reported as expiring
+        #[arg(long, value_name = "DAYS", default_value_t = 90)]
+        expiry_window: u32,
+    },
+    /// Check that a signed image authenticates against an RKTH
+    ///
+    /// By default performs the same checks as done when merging a signature into an image
+    Verify {
+        /// Signed image to check (BIN)
+        image: PathBuf,
+        /// Expected RKTH (hex), as fused into the device
+        #[arg(long)]
+        rkth: String,
+        /// Emulate the authentication by the ROM instead
+        ///
+        /// Independently re-implements the checks of the ROM on the image, such that images it would reject are caught
+        /// before going to hardware
+        #[arg(long)]
+        like_rom: bool,
+        /// Expected slot of the root key table occupied by the root certificate of the image
+        #[arg(long)]
+        root_key_slot: Option<usize>,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum FuseCommands {
+    /// Read the fuse words relevant to secure boot
+    ///
+    /// Reads the OTP shadow registers, which mirror the fuses unless overwritten since power-up (e.g. by `run`)
+    Read {
+        #[command(flatten)]
+        probe_args: ProbeArgs,
+    },
+    /// Compare the fuse words relevant to secure boot against the intended provisioning
+    ///
+    /// The intended RKTH is derived from the configured certificate chains, the boot configuration is the one also
+    /// used by `run`
+    Verify {
+        #[command(flatten)]
+        probe_args: ProbeArgs,
+
+        /// Index of the certificate chain the RKTH is expected to be derived from
+        #[arg(long, value_name = "CERTIFICATE_IDX", default_value = "0", add = ArgValueCandidates::new(completion::certificates))]
+        certificate: usize,
+
+        /// Where the nxpimage binary can be found. May be on PATH
+        #[arg(long, default_value = "nxpimage")]
+        nxpimage_path: PathBuf,
+    },
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct ProvisionDeviceArguments {
+    #[command(flatten)]
+    probe_args: ProbeArgs,
+
+    /// Index of the certificate chain to sign the images with
+    #[arg(long, value_name = "CERTIFICATE_IDX", default_value = "0", add = ArgValueCandidates::new(completion::certificates))]
+    certificate: usize,
+
+    /// ELF file of the bootloader [default: `bootloader.elf_path` of the configuration]
+    #[arg(long, value_name = "ELF_FILE")]
+    bootloader: Option<PathBuf>,
+
+    /// ELF file of an application to flash into slot 0, otherwise the slots are left as they are
+    #[arg(long, value_name = "ELF_FILE")]
+    application: Option<PathBuf>,
+
+    /// File recording the completed steps [default: <ARTIFACTS_PATH>/provision-checkpoint.json]
+    #[arg(long, value_name = "CHECKPOINT_FILE")]
+    checkpoint_path: Option<PathBuf>,
+
+    /// Start over, performing the steps recorded as completed in the checkpoint again
+    #[arg(long)]
+    restart: bool,
+
+    /// Where the nxpcrypto binary can be found. May be on PATH
+    #[arg(long, default_value = "nxpcrypto")]
+    nxpcrypto_path: PathBuf,
+
+    /// Where the nxpimage binary can be found. May be on PATH
+    #[arg(long, default_value = "nxpimage")]
+    nxpimage_path: PathBuf,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum ProvisionCommands {
+    /// Export a signed manifest of the intended provisioning
+    ///
+    /// Records the certificates, RKTH, OTP master key digest, fuse plan and flash image digests.
+    /// The manifest is signed with the leaf certificate of the certificate chain.
+    Export {
+        /// Index of the certificate chain the device is provisioned with
+        #[arg(long, value_name = "CERTIFICATE_IDX", default_value = "0", add = ArgValueCandidates::new(completion::certificates))]
+        certificate: usize,
+
+        /// Signed image (BIN) and the flash address it is flashed to, e.g. `bootloader.signed.bin@0x08001000`
+        #[arg(long = "image", value_name = "PATH@ADDRESS")]
+        images: Vec<processors::manifest::ImageArg>,
+
+        /// Output file path of the manifest [default: <ARTIFACTS_PATH>/provisioning-manifest.json]
+        #[arg(short, long, value_name = "OUTPUT_FILE")]
+        output_path: Option<PathBuf>,
+
+        /// Where the nxpimage binary can be found. May be on PATH
+        #[arg(long, default_value = "nxpimage")]
+        nxpimage_path: PathBuf,
+    },
+    /// Confirm that a device matches a signed manifest
+    ///
+    /// Checks the manifest signature and key material against the configuration,
+    /// and the fuses and flash images against the device
+    Verify {
+        /// Signed provisioning manifest (JSON)
+        manifest_path: PathBuf,
+
+        #[command(flatten)]
+        probe_args: ProbeArgs,
+
+        /// Where the nxpimage binary can be found. May be on PATH
+        #[arg(long, default_value = "nxpimage")]
+        nxpimage_path: PathBuf,
+    },
+    /// Take a blank device to bootable in one go, as described by the configuration
+    ///
+    /// Generates the OTP master key and certificates when missing, signs the bootloader, flashes its prelude, the
+    /// bootloader and optionally an application into slot 0, seeds the state journal to boot slot 0, and writes the
+    /// intended fuse values to the shadow registers as `run` does. Completed steps are recorded in a checkpoint, such
+    /// that running the command again resumes after the last completed step
+    Device(ProvisionDeviceArguments),
+}
+
+/// Role of an image in the boot chain, see [ImageRole]
+#[derive(ValueEnum, Debug, Clone, Copy)]
+pub enum RoleArg {
+    Application,
+    Loader,
+}
+
+impl From<RoleArg> for ImageRole {
+    fn from(value: RoleArg) -> Self {
+        match value {
+            RoleArg::Application => ImageRole::Application,
+            RoleArg::Loader => ImageRole::Loader,
+        }
+    }
+}
+
+/// Status of a bootloader state, see [Status]
+#[derive(ValueEnum, Debug, Clone, Copy)]
+pub enum StatusArg {
+    Initial,
+    Attempting,
+    Failed,
+    Confirmed,
+}
+
+impl From<StatusArg> for Status {
+    fn from(value: StatusArg) -> Self {
+        match value {
+            StatusArg::Initial => Status::Initial,
+            StatusArg::Attempting => Status::Attempting,
+            StatusArg::Failed => Status::Failed,
+            StatusArg::Confirmed => Status::Confirmed,
+        }
+    }
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum StateCommands {
+    /// Reset the state journal to a single state, discarding its history
+    ///
+    /// Erases the state partition configured as `bootloader.state` and seeds it with the given state
+    Reset {
+        #[command(flatten)]
+        probe_args: ProbeArgs,
+
+        /// Status to seed the journal with
+        #[arg(long, value_enum, default_value_t = StatusArg::Initial)]
+        status: StatusArg,
+
+        /// Image slot to boot
+        #[arg(long, default_value_t = 0, add = ArgValueCandidates::new(completion::slots))]
+        target: u8,
+
+        /// Image slot to fall back to
+        #[arg(long, default_value_t = 1, add = ArgValueCandidates::new(completion::slots))]
+        backup: u8,
+    },
+    /// List every record in the state journal, oldest first, followed by how many there are of each status
+    ///
+    /// Reveals how the latest state came about, such as how many boot attempts failed
+    History {
+        #[command(flatten)]
+        probe_args: ProbeArgs,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum SlotCommands {
+    /// Read the entire contents of an application slot into a file
+    ///
+    /// Reads the slot through the memory mapped external flash, so the bootloader or ROM should have run
+    Dump {
+        #[command(flatten)]
+        probe_args: ProbeArgs,
+
+        /// Index of the slot in `application.slot_starts`, or one of `current`, `target`, `backup` or `other`
+        #[arg(long, add = ArgValueCandidates::new(completion::slot_refs))]
+        slot: SlotRef,
+
+        /// Output file path of the slot contents (BIN)
+        #[arg(long, value_name = "OUTPUT_FILE")]
+        out: PathBuf,
+    },
+    /// Replace the entire contents of an application slot by a file, for example one created by `slot dump`
+    ///
+    /// The remainder of the slot after the contents of the file is erased
+    Write {
+        #[command(flatten)]
+        probe_args: ProbeArgs,
+
+        /// Index of the slot in `application.slot_starts`, or one of `current`, `target`, `backup` or `other`
+        #[arg(long, add = ArgValueCandidates::new(completion::slot_refs))]
+        slot: SlotRef,
+
+        /// Slot contents (BIN)
+        input_path: PathBuf,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum TestCommands {
+    /// Corrupt a signed image in the ways the bootloader must refuse, and print the error expected for each
+    ///
+    /// Every vector is written next to the input as `<INPUT>.<VECTOR>.bin`. Write them to a slot with `slot write`
+    /// to check that the bootloader refuses them with the expected `BootError`
+    Vectors {
+        /// Signed image to corrupt (BIN)
+        input: PathBuf,
+        /// Output directory of the vectors [default: directory of <INPUT>]
+        #[arg(short, long, value_name = "OUTPUT_DIR")]
+        output_dir: Option<PathBuf>,
+        /// Only generate these vectors [default: all]
+        #[arg(long, value_enum, value_delimiter = ',')]
+        only: Vec<Vector>,
     },
 }
 
+// Parsed once from the command line, so its size does not matter.
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand, Debug, Clone)]
 pub enum DownloadCommands {
     /// Download the flash prelude containing OTFAD, FCB, etc.
@@ -1,21 +1,49 @@
 extern crate log;
 extern crate pretty_env_logger;
 
+use std::process::ExitCode;
+
 use anyhow::Context;
-use bootloader_tool::{Cli, Config, commands};
-use clap::Parser;
+use bootloader_tool::error::{Classify, ErrorKind, Report};
+use bootloader_tool::{Cli, Commands, Config, OutputFormat, commands, completion};
+use clap::{CommandFactory, Parser};
+use clap_complete::CompleteEnv;
 
 #[tokio::main]
-async fn main() -> anyhow::Result<()> {
+async fn main() -> ExitCode {
     pretty_env_logger::init();
+    CompleteEnv::with_factory(Cli::command).var(completion::VAR).complete();
 
     let cli = Cli::parse();
 
-    let config =
-        Config::read(&cli.config).with_context(|| format!("Tried to open --config {}", cli.config.display()))?;
+    let output = cli.output;
+    match run(cli).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            let report = Report::new(&e);
+            match output {
+                OutputFormat::Text => eprintln!("Error: {e:?}"),
+                OutputFormat::Json => println!("{}", serde_json::to_string(&report).expect("serializable report")),
+            }
+            ExitCode::from(report.exit_code)
+        }
+    }
+}
+
+async fn run(cli: Cli) -> anyhow::Result<()> {
+    // Neither needs the configuration, which may not exist yet.
+    match cli.commands {
+        Some(Commands::Completions(args)) => return commands::completions::process(args),
+        Some(Commands::Man(args)) => return commands::man::process(args),
+        _ => {}
+    }
+
+    let config = Config::read_profile(&cli.config, cli.profile.as_deref())
+        .with_context(|| format!("Tried to open --config {}", cli.config.display()))
+        .classify(ErrorKind::Config)?;
 
     if let Some(command) = cli.commands {
-        commands::process(&config, command).await
+        commands::process(&config, command, cli.dry_run).await
     } else {
         eprintln!("Done nothing");
         Ok(())
@@ -0,0 +1,120 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+
+use crate::config::Config;
+
+/// Name of the directory of the images signed without a profile selected
+pub const DEFAULT_PROFILE_DIR: &str = "default";
+
+/// File generated from an input ELF file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Artifact {
+    /// Bare binary image, before signing
+    Unsigned,
+    /// Master Boot Image without signature, to sign externally
+    Prestage,
+    /// Signature of the prestage, when signed with a configured key
+    Signature,
+    /// Signed image
+    Signed,
+    /// Prelude of the bootloader, holding the FCB and boot image version
+    Prelude,
+    /// Signed image composed with the subregions of its slot
+    Slot,
+    /// What `download` and `run` signed last, see [sign_cache](crate::processors::sign_cache)
+    SignCache,
+    /// Master Boot Image generated by `generate mbi` using nxpimage
+    Nxp,
+    /// Delta update from `ota diff` of the signed image
+    SignedPatch,
+}
+
+impl Artifact {
+    pub const ALL: [Artifact; 9] = [
+        Artifact::Unsigned,
+        Artifact::Prestage,
+        Artifact::Signature,
+        Artifact::Signed,
+        Artifact::Prelude,
+        Artifact::Slot,
+        Artifact::SignCache,
+        Artifact::Nxp,
+        Artifact::SignedPatch,
+    ];
+
+    /// Extension appended to the name of the target
+    pub fn extension(self) -> &'static str {
+        match self {
+            Artifact::Unsigned => "unsigned.bin",
+            Artifact::Prestage => "mbi-proto.bin",
+            Artifact::Signature => "signature.bin",
+            Artifact::Signed => "signed.bin",
+            Artifact::Prelude => "prelude.elf",
+            Artifact::Slot => "slot.bin",
+            Artifact::SignCache => "sign-cache",
+            Artifact::Nxp => "nxp.bin",
+            Artifact::SignedPatch => "signed.patch",
+        }
+    }
+}
+
+/// Locations of the files generated from an input ELF file
+///
+/// Files are named after the target, the file name of the ELF file, and put in `<ARTIFACTS_PATH>/images/<PROFILE>`,
+/// with `default` as profile when none is selected. Signing the same target with different profiles thus never
+/// overwrites the files of another profile, and the files are found at the same place wherever the ELF file was built.
+/// Pass `--artifact-dir` to keep the files of different builds of the same target apart.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArtifactLayout {
+    dir: PathBuf,
+    target: String,
+}
+
+impl ArtifactLayout {
+    /// Layout of the files generated from the ELF file at `input_path`, in `artifact_dir` if given
+    pub fn new(config: &Config, input_path: impl AsRef<Path>, artifact_dir: Option<&Path>) -> Self {
+        let dir = match artifact_dir {
+            Some(dir) => dir.to_owned(),
+            None => config
+                .artifacts_path
+                .join("images")
+                .join(config.profile.as_deref().unwrap_or(DEFAULT_PROFILE_DIR)),
+        };
+        Self::in_dir(dir, input_path)
+    }
+
+    /// Layout of the files generated from the ELF file at `input_path`, in `dir`
+    pub fn in_dir(dir: impl Into<PathBuf>, input_path: impl AsRef<Path>) -> Self {
+        let input_path = input_path.as_ref();
+        let target = input_path
+            .file_stem()
+            .unwrap_or(input_path.as_os_str())
+            .to_string_lossy()
+            .into_owned();
+        Self {
+            dir: dir.into(),
+            target,
+        }
+    }
+
+    /// Directory the files are put in
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Path of `artifact`
+    pub fn path(&self, artifact: Artifact) -> PathBuf {
+        self.dir.join(format!("{}.{}", self.target, artifact.extension()))
+    }
+
+    /// Paths of all artifacts, whether generated or not
+    pub fn files(&self) -> Vec<PathBuf> {
+        Artifact::ALL.iter().map(|artifact| self.path(*artifact)).collect()
+    }
+
+    /// Create the directory the files are put in
+    pub fn create_dir(&self) -> anyhow::Result<()> {
+        std::fs::create_dir_all(&self.dir).with_context(|| format!("Could not create {}", self.dir.display()))
+    }
+}
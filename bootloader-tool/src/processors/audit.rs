@@ -0,0 +1,125 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::SignArguments;
+use crate::config::Config;
+use crate::processors::manifest::sha256_hex;
+use crate::processors::mbi::cert_block::CertBlock;
+
+/// How the signature of an image was made
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "kebab-case", tag = "kind", content = "path")]
+pub enum SignatureSource {
+    /// By this tool, using the private key of the leaf certificate at this path
+    Key(PathBuf),
+    /// Externally, for example by an HSM, and passed as the signature file at this path
+    External(PathBuf),
+}
+
+/// Entry of the audit log, recording which image was signed with which keys, by whom and when
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Record {
+    /// Time of signing, in seconds since the Unix epoch
+    pub timestamp: u64,
+    /// Who signed the image, see [signer]
+    pub signer: String,
+    /// Version of this tool
+    pub tool_version: String,
+    /// Whether the image is a bootloader rather than an application
+    pub bootloader: bool,
+    pub input_path: PathBuf,
+    /// SHA-256 of the input ELF file
+    pub input_sha256: String,
+    pub output_path: PathBuf,
+    /// SHA-256 of the signed image
+    pub output_sha256: String,
+    /// Index of the certificate chain in the configuration
+    pub certificate: usize,
+    /// SHA-256 of each certificate in the chain (DER), starting with the root certificate
+    pub certificates: Vec<String>,
+    /// Root key table hash the image authenticates against
+    pub rkth: String,
+    pub signature: SignatureSource,
+}
+
+impl Record {
+    /// Record the signing of the ELF file with contents `input` into the image at `output_path` with `args`, now
+    pub fn new(
+        config: &Config,
+        args: &SignArguments,
+        bootloader: bool,
+        input: &[u8],
+        output_path: &Path,
+        cert_block: &CertBlock,
+        signature: SignatureSource,
+    ) -> anyhow::Result<Self> {
+        let output = std::fs::read(output_path).with_context(|| format!("Could not read {}", output_path.display()))?;
+        Ok(Self {
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+            signer: signer(config),
+            tool_version: env!("CARGO_PKG_VERSION").to_owned(),
+            bootloader,
+            input_path: args.input_path.clone(),
+            input_sha256: sha256_hex(input),
+            output_path: output_path.to_owned(),
+            output_sha256: sha256_hex(&output),
+            certificate: args.certificate,
+            certificates: cert_block.certificates()?.iter().map(|cert| sha256_hex(cert)).collect(),
+            rkth: cert_block.rkth().as_hex(),
+            signature,
+        })
+    }
+}
+
+/// Identity of who signs, which is `audit_log.signer` from the configuration or else the user running the tool
+pub fn signer(config: &Config) -> String {
+    config
+        .audit_log
+        .signer
+        .clone()
+        .or_else(|| std::env::var("USER").ok())
+        .or_else(|| std::env::var("USERNAME").ok())
+        .unwrap_or_else(|| "unknown".to_owned())
+}
+
+/// Path of the audit log, which is `audit_log.path` from the configuration [default: <ARTIFACTS_PATH>/signing-audit.jsonl]
+pub fn path(config: &Config) -> PathBuf {
+    config
+        .audit_log
+        .path
+        .clone()
+        .unwrap_or_else(|| config.artifacts_path.join("signing-audit.jsonl"))
+}
+
+/// Append `record` to the audit log at `path` as a single line of JSON, creating the log if needed
+///
+/// Existing entries are never rewritten.
+pub fn append(path: impl AsRef<Path>, record: &Record) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    let mut line = serde_json::to_string(record)?;
+    line.push('\n');
+
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(line.as_bytes()))
+        .with_context(|| format!("Could not append to audit log {}", path.display()))
+}
+
+/// All records in the audit log at `path`, oldest first
+pub fn read(path: impl AsRef<Path>) -> anyhow::Result<Vec<Record>> {
+    let path = path.as_ref();
+    let log = std::fs::read_to_string(path).with_context(|| format!("Could not read audit log {}", path.display()))?;
+    log.lines()
+        .enumerate()
+        .map(|(i, line)| {
+            serde_json::from_str(line)
+                .with_context(|| format!("Invalid record on line {} of {}", i + 1, path.display()))
+        })
+        .collect()
+}
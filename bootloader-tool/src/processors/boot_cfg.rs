@@ -0,0 +1,97 @@
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+use anyhow::{Context, bail};
+use serde::Deserialize;
+
+use crate::config::{BoardArgs, BoardPreset};
+
+/// Register definitions of `imxrt-rom`, such that fields are laid out exactly as the bootloader reads them
+const REGISTERS: &str = include_str!("../../../libs/imxrt-rom/registers.json");
+
+/// Fields set on every board, as required for secure boot
+const SECURE_BOOT: [(&str, &str, u32); 4] = [
+    ("BOOT_CFG0", "default_isp_mode", 0b111), // Completely disable ISP mode
+    ("BOOT_CFG0", "tzm_image_type", 0b10),    // Force Trust-Zone mode
+    ("BOOT_CFG0", "secure_boot_en", 0b01),    // Enable secure boot
+    ("BOOT_CFG0", "dice_skip", 1),            // Skip DICE
+];
+
+/// Fields set on the MIMXRT685-EVK in addition to [SECURE_BOOT]
+const EVK: [(&str, &str, u32); 6] = [
+    ("BOOT_CFG0", "primary_boot_src", 0b0101), // Use QSPI B
+    ("BOOT_CFG0", "boot_fail_pin_port", 5),
+    ("BOOT_CFG0", "boot_fail_pin_num", 7),
+    ("BOOT_CFG1", "qspi_reset_pin_enable", 1),
+    ("BOOT_CFG1", "qspi_reset_pin_port", 2),
+    ("BOOT_CFG1", "qspi_reset_pin_num", 12),
+];
+
+#[derive(Deserialize)]
+struct Register {
+    #[serde(default)]
+    fields: BTreeMap<String, Field>,
+}
+
+#[derive(Deserialize)]
+struct Field {
+    start: u32,
+    /// Exclusive end bit, absent for single bit fields
+    end: Option<u32>,
+}
+
+/// Bits of the field `name` in `register`
+pub fn field_bits(register: &str, name: &str) -> anyhow::Result<Range<u32>> {
+    let registers: BTreeMap<String, serde_json::Value> =
+        serde_json::from_str(REGISTERS).expect("registers.json of imxrt-rom is valid JSON");
+    let register_definition = registers
+        .get(register)
+        .cloned()
+        .with_context(|| format!("Unknown register {register}"))?;
+    let Register { fields } = serde_json::from_value(register_definition)
+        .with_context(|| format!("Invalid definition of register {register}"))?;
+
+    let Some(field) = fields.get(name) else {
+        bail!(
+            "Unknown field {name} of {register}, available fields: {}",
+            fields.keys().cloned().collect::<Vec<_>>().join(", ")
+        );
+    };
+    Ok(field.start..field.end.unwrap_or(field.start + 1))
+}
+
+/// Fields of `register` intended for the board, with the preset overridden by those configured explicitly
+pub fn fields(board: &BoardArgs, register: &str) -> BTreeMap<String, u32> {
+    let preset: &[(&str, &str, u32)] = match board.preset {
+        BoardPreset::Production => &[],
+        BoardPreset::Evk => &EVK,
+    };
+    let mut fields: BTreeMap<String, u32> = SECURE_BOOT
+        .iter()
+        .chain(preset)
+        .filter(|(preset_register, _, _)| *preset_register == register)
+        .map(|(_, name, value)| (name.to_string(), *value))
+        .collect();
+
+    let configured = match register {
+        "BOOT_CFG0" => &board.boot_cfg0,
+        "BOOT_CFG1" => &board.boot_cfg1,
+        _ => return fields,
+    };
+    fields.extend(configured.iter().map(|(name, value)| (name.clone(), *value)));
+    fields
+}
+
+/// Value of `register` with `fields` set and all other bits cleared
+pub fn encode(register: &str, fields: &BTreeMap<String, u32>) -> anyhow::Result<u32> {
+    let mut value = 0u32;
+    for (name, field_value) in fields {
+        let bits = field_bits(register, name)?;
+        let width = bits.end - bits.start;
+        if width < 32 && *field_value >> width != 0 {
+            bail!("Value {field_value:#x} of {register} field {name} does not fit in {width} bit(s)");
+        }
+        value |= field_value << bits.start;
+    }
+    Ok(value)
+}
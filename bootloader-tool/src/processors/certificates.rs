@@ -1,12 +1,20 @@
+use std::fmt;
 use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
 
-use anyhow::Context;
+use anyhow::{Context, anyhow, bail};
+use itertools::Itertools;
+use p12_keystore::{KeyStore, KeyStoreEntry};
 use serde::Serialize;
+use x509_parser::certificate::X509Certificate;
+use x509_parser::pem::Pem;
+use x509_parser::prelude::{ASN1Time, FromDer};
 
 use crate::GenerateCertificatesArguments;
 use crate::config::{Certificate, CertificatePrototype, Config};
-use crate::util::{bytes_to_u32_le, generate_hex, parse_hex};
+use crate::error::{Classify, ErrorKind};
+use crate::processors::pipeline;
+use crate::util::{bytes_to_u32_le, generate_hex, parse_hex, temp_file};
 
 #[derive(Serialize)]
 struct BasicConstraints {
@@ -46,13 +54,15 @@ fn generate_private_key(nxpcrypto: impl AsRef<Path>, prototype: &CertificateProt
         .stdout(Stdio::piped())
         .stderr(Stdio::inherit())
         .output()
-        .with_context(failed_exec(nxpcrypto))?;
+        .with_context(failed_exec(nxpcrypto))
+        .classify(ErrorKind::ExternalTool)?;
 
     if !output.status.success() {
         Err(
             anyhow::anyhow!(format!("Failed to generate private key {}", output_path.display()))
                 .context(String::from_utf8(output.stdout)?),
         )
+        .classify(ErrorKind::ExternalTool)
     } else {
         Ok(())
     }
@@ -93,8 +103,7 @@ fn generate_certificate(
         },
     };
 
-    let mut input_file = tempfile::NamedTempFile::new()?;
-    serde_json::to_writer(&mut input_file, &input)?;
+    let input_file = temp_file(".json", &serde_json::to_vec(&input)?)?;
 
     let output_path = &certificate.path;
     if std::fs::exists(output_path)? {
@@ -107,7 +116,7 @@ fn generate_certificate(
     let mut command = Command::new(nxpcrypto.as_ref());
 
     command.args(["cert", "generate", "-e", "PEM", "-c"]);
-    command.arg(input_file.path());
+    command.arg(&input_file);
     command.arg("-o");
     command.arg(output_path);
 
@@ -116,7 +125,8 @@ fn generate_certificate(
         .stdout(Stdio::piped())
         .stderr(Stdio::inherit())
         .output()
-        .with_context(failed_exec(nxpcrypto))?;
+        .with_context(failed_exec(nxpcrypto))
+        .classify(ErrorKind::ExternalTool)?;
 
     if !output.status.success() {
         Err(anyhow::anyhow!(format!(
@@ -124,30 +134,12 @@ fn generate_certificate(
             certificate, parent_certificate
         ))
         .context(String::from_utf8(output.stdout)?))
+        .classify(ErrorKind::ExternalTool)
     } else {
         Ok(())
     }
 }
 
-fn generate_single(
-    nxpcrypto: impl AsRef<Path>,
-    certificate: &Certificate,
-    parent_certificate: &Option<Certificate>,
-    is_leaf: bool,
-) -> anyhow::Result<()> {
-    let Some(prototype) = &certificate.prototype else {
-        return Err(anyhow::anyhow!(
-            "Request generation of private key for {}, but no prototype has been defined",
-            certificate.path.display()
-        ));
-    };
-
-    generate_private_key(&nxpcrypto, prototype)?;
-    generate_certificate(&nxpcrypto, certificate, parent_certificate, is_leaf)?;
-
-    Ok(())
-}
-
 #[derive(PartialEq, Clone, Debug)]
 pub struct Rkth(pub [u8; 32]);
 
@@ -169,17 +161,274 @@ impl Rkth {
     }
 }
 
+/// Read the DER encoding of the certificate in a PEM or DER file
+pub fn read_der(path: impl AsRef<Path>) -> anyhow::Result<Vec<u8>> {
+    let path = path.as_ref();
+    let data = std::fs::read(path).with_context(|| format!("Could not read {}", path.display()))?;
+    if !data.starts_with(b"-----BEGIN") {
+        return Ok(data);
+    }
+
+    let (_, pem) =
+        x509_parser::pem::parse_x509_pem(&data).map_err(|e| anyhow!("Invalid PEM file {}: {e}", path.display()))?;
+    Ok(pem.contents)
+}
+
+/// Read the DER encoded certificates in a bundle, in the order they are stored in
+///
+/// Bundles with a `.p12` or `.pfx` extension are PKCS#12 files decrypted with `password`, of which the certificates
+/// in the chains of private keys and the trusted certificates are read. Other bundles consist of concatenated DER or
+/// PEM certificates.
+pub fn read_bundle(path: impl AsRef<Path>, password: &str) -> anyhow::Result<Vec<Vec<u8>>> {
+    let path = path.as_ref();
+    let data = std::fs::read(path).with_context(|| format!("Could not read {}", path.display()))?;
+
+    let is_pkcs12 = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("p12") || extension.eq_ignore_ascii_case("pfx"));
+    let mut certificates: Vec<Vec<u8>> = vec![];
+    if is_pkcs12 {
+        let keystore = KeyStore::from_pkcs12(&data, password)
+            .map_err(|e| anyhow!("Could not read PKCS#12 bundle {}: {e}", path.display()))?;
+        for (_, entry) in keystore.entries() {
+            let chain = match entry {
+                KeyStoreEntry::PrivateKeyChain(chain) => chain.chain(),
+                KeyStoreEntry::Certificate(certificate) => std::slice::from_ref(certificate),
+            };
+            for certificate in chain {
+                // Chains of several keys may share their root and intermediate certificates.
+                if !certificates.iter().any(|der| der == certificate.as_der()) {
+                    certificates.push(certificate.as_der().to_vec());
+                }
+            }
+        }
+    } else if data.starts_with(b"-----BEGIN") {
+        for pem in Pem::iter_from_buffer(&data) {
+            let pem = pem.map_err(|e| anyhow!("Invalid PEM file {}: {e}", path.display()))?;
+            certificates.push(pem.contents);
+        }
+    } else {
+        let mut rest = &data[..];
+        while !rest.is_empty() {
+            let (next, _) = X509Certificate::from_der(rest).map_err(|e| {
+                anyhow!(
+                    "Invalid certificate at offset {:#x} of {}: {e}",
+                    data.len() - rest.len(),
+                    path.display()
+                )
+            })?;
+            certificates.push(rest[..rest.len() - next.len()].to_vec());
+            rest = next;
+        }
+    }
+
+    if certificates.is_empty() {
+        bail!("No certificates in bundle {}", path.display());
+    }
+    Ok(certificates)
+}
+
+/// Order of the DER encoded `certificates` from root to leaf, as indices into `certificates`
+///
+/// Every certificate must be issued by the one before it, which is checked by its signature, such that the
+/// certificates form a single chain.
+pub fn chain_order(certificates: &[impl AsRef<[u8]>]) -> anyhow::Result<Vec<usize>> {
+    let parsed = certificates
+        .iter()
+        .enumerate()
+        .map(|(i, der)| {
+            X509Certificate::from_der(der.as_ref())
+                .map(|(_, certificate)| certificate)
+                .map_err(|e| anyhow!("Invalid certificate {i}: {e}"))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let issued_by = |child: usize, parent: usize| {
+        child != parent && parsed[child].issuer().as_raw() == parsed[parent].subject().as_raw()
+    };
+    let subjects =
+        |indices: &mut dyn Iterator<Item = usize>| indices.map(|i| parsed[i].subject().to_string()).join(", ");
+
+    // The root is the only certificate not issued by another one of the chain.
+    let roots: Vec<usize> = (0..parsed.len())
+        .filter(|&i| !(0..parsed.len()).any(|parent| issued_by(i, parent)))
+        .collect();
+    let root = match roots[..] {
+        [root] => root,
+        [] => bail!("Certificates do not form a chain, as all of them are issued by one another"),
+        _ => bail!(
+            "Certificates do not form a single chain, as several are not issued by another one: {}",
+            subjects(&mut roots.iter().copied())
+        ),
+    };
+
+    let mut order = vec![root];
+    while order.len() < parsed.len() {
+        let parent = order[order.len() - 1];
+        let children: Vec<usize> = (0..parsed.len())
+            .filter(|&i| !order.contains(&i) && issued_by(i, parent))
+            .collect();
+        let child = match children[..] {
+            [child] => child,
+            [] => bail!(
+                "Certificate chain does not link: nothing is issued by {}, leaving {} unlinked",
+                parsed[parent].subject(),
+                subjects(&mut (0..parsed.len()).filter(|i| !order.contains(i)))
+            ),
+            _ => bail!(
+                "Certificate chain branches: {} issued {}",
+                parsed[parent].subject(),
+                subjects(&mut children.iter().copied())
+            ),
+        };
+        parsed[child]
+            .verify_signature(Some(parsed[parent].public_key()))
+            .map_err(|e| {
+                anyhow!(
+                    "Certificate {} is not signed by its issuer {}: {e}",
+                    parsed[child].subject(),
+                    parsed[parent].subject()
+                )
+            })?;
+        order.push(child);
+    }
+    Ok(order)
+}
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// Validity window of a certificate
+#[derive(Debug, Clone)]
+pub struct Validity {
+    pub subject: String,
+    pub not_before: ASN1Time,
+    pub not_after: ASN1Time,
+}
+
+impl Validity {
+    pub fn from_certificate(certificate: &X509Certificate) -> Self {
+        let validity = certificate.validity();
+        Self {
+            subject: certificate.subject().to_string(),
+            not_before: validity.not_before,
+            not_after: validity.not_after,
+        }
+    }
+
+    /// Read the validity of a DER encoded certificate
+    pub fn from_der(der: &[u8]) -> anyhow::Result<Self> {
+        let (_, certificate) = X509Certificate::from_der(der).context("Could not parse certificate")?;
+        Ok(Self::from_certificate(&certificate))
+    }
+
+    /// Read the validity of the certificate in a PEM or DER file, such as those in the configuration
+    pub fn from_file(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        Self::from_der(&read_der(path)?).with_context(|| format!("Invalid certificate {}", path.display()))
+    }
+
+    /// Whether the certificate is valid at `now`, and if so, whether it expires within `window_days` of it
+    pub fn expiry(&self, now: ASN1Time, window_days: u32) -> Expiry {
+        if now < self.not_before {
+            Expiry::NotYetValid
+        } else if now > self.not_after {
+            Expiry::Expired
+        } else {
+            let days = (self.not_after.timestamp() - now.timestamp()) / SECONDS_PER_DAY;
+            if days < window_days as i64 {
+                Expiry::Expiring { days }
+            } else {
+                Expiry::Valid { days }
+            }
+        }
+    }
+}
+
+/// Validity of a certificate at some point in time, see [Validity::expiry]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Expiry {
+    NotYetValid,
+    Valid {
+        days: i64,
+    },
+    /// Valid, but expiring within the window
+    Expiring {
+        days: i64,
+    },
+    Expired,
+}
+
+impl fmt::Display for Expiry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expiry::NotYetValid => write!(f, "not yet valid"),
+            Expiry::Valid { days } => write!(f, "valid for {days} more days"),
+            Expiry::Expiring { days } => write!(f, "expires within {days} days"),
+            Expiry::Expired => write!(f, "expired"),
+        }
+    }
+}
+
+/// Warn about each certificate in `validities` that is not valid for at least another `window_days`
+///
+/// The ROM does not check the validity of certificates, but signing with a certificate that is about to expire is
+/// likely a mistake. Fails instead of warning when `strict`.
+pub fn check_expiry(validities: &[Validity], window_days: u32, strict: bool) -> anyhow::Result<()> {
+    let now = ASN1Time::now();
+    for validity in validities {
+        let expiry = validity.expiry(now, window_days);
+        if matches!(expiry, Expiry::Valid { .. }) {
+            continue;
+        }
+
+        let message = format!(
+            "Certificate {} {expiry} (valid from {} until {})",
+            validity.subject, validity.not_before, validity.not_after
+        );
+        if strict {
+            return Err(anyhow::anyhow!(message)).classify(ErrorKind::Verification);
+        }
+        log::warn!("{message}");
+    }
+    Ok(())
+}
+
+/// Generate the private keys, and then the certificates, of all chains with at most `--jobs` invocations of nxpcrypto
+/// at once
+///
+/// Certificates are only signed with the private key of their parent, so once all keys exist they are independent.
 pub fn generate(args: GenerateCertificatesArguments, config: &Config) -> anyhow::Result<()> {
+    let mut jobs = vec![];
     for chain in &config.certificates {
         let mut parent = None;
         let mut iter = chain.0.iter().peekable();
         while let Some(certificate) = iter.next() {
             let is_leaf = iter.peek().is_none();
-            generate_single(&args.nxpcrypto_path, certificate, &parent, is_leaf)?;
+            let Some(prototype) = &certificate.prototype else {
+                return Err(anyhow::anyhow!(
+                    "Request generation of private key for {}, but no prototype has been defined",
+                    certificate.path.display()
+                ));
+            };
+            jobs.push((certificate, prototype, parent.clone(), is_leaf));
             parent = Some(certificate.clone());
         }
     }
 
+    let parallelism = pipeline::parallelism(args.jobs);
+    let nxpcrypto = &args.nxpcrypto_path;
+    pipeline::run(jobs.iter().collect(), parallelism, |(_, prototype, _, _)| {
+        generate_private_key(nxpcrypto, prototype)
+    })
+    .into_iter()
+    .collect::<anyhow::Result<()>>()?;
+    pipeline::run(
+        jobs.iter().collect(),
+        parallelism,
+        |(certificate, _, parent, is_leaf)| generate_certificate(nxpcrypto, certificate, parent, *is_leaf),
+    )
+    .into_iter()
+    .collect::<anyhow::Result<()>>()?;
+
     Ok(())
 }
 
@@ -0,0 +1,67 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+
+use crate::config::Config;
+use crate::processors::artifacts::ArtifactLayout;
+
+/// Files generated from the ELF file at `input_path` at the default output paths, in `artifact_dir` if given
+///
+/// By `sign` and thus `download` and `run`, by `generate mbi` and by `ota diff` of the signed image, see [Artifact].
+///
+/// [Artifact]: crate::processors::artifacts::Artifact
+pub fn image_files(config: &Config, input_path: impl AsRef<Path>, artifact_dir: Option<&Path>) -> Vec<PathBuf> {
+    let input_path = input_path.as_ref();
+    ArtifactLayout::new(config, input_path, artifact_dir)
+        .files()
+        .into_iter()
+        .filter(|path| path != input_path)
+        .collect()
+}
+
+/// Key material generated by `generate certificates` and `generate otp`
+///
+/// Only includes the certificates and private keys with a prototype, as others are not generated by this tool.
+pub fn key_files(config: &Config) -> Vec<PathBuf> {
+    let mut files = vec![];
+    for chain in &config.certificates {
+        for certificate in &chain.0 {
+            if let Some(prototype) = &certificate.prototype {
+                files.push(certificate.path.clone());
+                files.push(prototype.key_path.clone());
+            }
+        }
+    }
+    files.push(config.otp_path.clone());
+    files
+}
+
+/// Remove those of `files` that exist, yielding the removed files
+///
+/// Refuses to remove anything if any of them is not a regular file. Only lists the files when doing a `dry_run`.
+pub fn remove(files: &[PathBuf], dry_run: bool) -> anyhow::Result<Vec<PathBuf>> {
+    let mut existing = vec![];
+    for file in files {
+        let metadata = match std::fs::symlink_metadata(file) {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e).with_context(|| format!("Could not inspect {}", file.display())),
+        };
+        if !metadata.is_file() {
+            return Err(anyhow::anyhow!(
+                "Refusing to remove {}, as it is not a file",
+                file.display()
+            ));
+        }
+        if !existing.contains(file) {
+            existing.push(file.clone());
+        }
+    }
+
+    if !dry_run {
+        for file in &existing {
+            std::fs::remove_file(file).with_context(|| format!("Could not remove {}", file.display()))?;
+        }
+    }
+    Ok(existing)
+}
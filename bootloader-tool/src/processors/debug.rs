@@ -0,0 +1,198 @@
+use std::fmt;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use anyhow::{Context, bail};
+use clap::ValueEnum;
+use probe_rs::{Core, MemoryInterface};
+
+use crate::error::{Classify, ErrorKind};
+use crate::processors::fuse;
+
+/// UUID_CHECK bit in DCFG_CC_SOCU, restricting debug credentials to the UUID of a single device
+pub const UUID_CHECK: u32 = 1 << 15;
+
+/// Debug access that DCFG_CC_SOCU can restrict to authenticated debuggers
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Feature {
+    /// Non-secure non-invasive debug (NIDEN)
+    Niden,
+    /// Non-secure invasive debug (DBGEN)
+    Dbgen,
+    /// Secure non-invasive debug (SPNIDEN)
+    Spniden,
+    /// Secure invasive debug (SPIDEN)
+    Spiden,
+    /// JTAG test access port (TAPEN)
+    Tapen,
+    /// Debug of the DSP (DSP_DBGEN)
+    DspDbgen,
+    /// ISP boot command of the debug mailbox (ISP_CMD_EN)
+    IspCmd,
+    /// Fault analysis command of the debug mailbox (FA_CMD_EN)
+    FaCmd,
+    /// Mass erase command of the debug mailbox (ME_CMD_EN)
+    MeCmd,
+}
+
+impl Feature {
+    pub const ALL: [Feature; 9] = [
+        Feature::Niden,
+        Feature::Dbgen,
+        Feature::Spniden,
+        Feature::Spiden,
+        Feature::Tapen,
+        Feature::DspDbgen,
+        Feature::IspCmd,
+        Feature::FaCmd,
+        Feature::MeCmd,
+    ];
+
+    /// Bit of the feature in DCFG_CC_SOCU
+    pub fn bit(self) -> u32 {
+        1 << Self::ALL.iter().position(|feature| *feature == self).unwrap()
+    }
+}
+
+impl fmt::Display for Feature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Feature::Niden => "NIDEN",
+            Feature::Dbgen => "DBGEN",
+            Feature::Spniden => "SPNIDEN",
+            Feature::Spiden => "SPIDEN",
+            Feature::Tapen => "TAPEN",
+            Feature::DspDbgen => "DSP_DBGEN",
+            Feature::IspCmd => "ISP_CMD_EN",
+            Feature::FaCmd => "FA_CMD_EN",
+            Feature::MeCmd => "ME_CMD_EN",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Debug access configuration held by DCFG_CC_SOCU and DCFG_CC_SOCU_NS
+///
+/// A set bit in DCFG_CC_SOCU restricts the feature to debuggers that authenticated using a debug credential signed
+/// by one of the root keys. DCFG_CC_SOCU_NS holds the inverse as a redundant copy. Unprogrammed fuses, with both words
+/// zero, leave all debug access open.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DebugAccess {
+    pub socu: u32,
+    pub socu_ns: u32,
+}
+
+impl DebugAccess {
+    /// Restrict all debug access to authenticated debuggers, except for the features in `allow`
+    ///
+    /// With `uuid_check`, a debug credential only authenticates the device with the UUID it was issued for.
+    pub fn locked(allow: &[Feature], uuid_check: bool) -> Self {
+        let mut socu = Feature::ALL
+            .iter()
+            .filter(|feature| !allow.contains(feature))
+            .fold(0, |socu, feature| socu | feature.bit());
+        if uuid_check {
+            socu |= UUID_CHECK;
+        }
+        Self { socu, socu_ns: !socu }
+    }
+
+    /// Whether the words are unprogrammed, leaving all debug access open
+    pub fn is_open(&self) -> bool {
+        self.socu == 0 && self.socu_ns == 0
+    }
+
+    /// Features that require debug authentication
+    ///
+    /// Fails if DCFG_CC_SOCU_NS is not the inverse of DCFG_CC_SOCU, which the ROM treats as all access restricted.
+    pub fn restricted(&self) -> anyhow::Result<Vec<Feature>> {
+        if self.is_open() {
+            return Ok(vec![]);
+        }
+        if self.socu_ns != !self.socu {
+            bail!(
+                "DCFG_CC_SOCU_NS {:#010x} is not the inverse of DCFG_CC_SOCU {:#010x}",
+                self.socu_ns,
+                self.socu
+            );
+        }
+
+        Ok(Feature::ALL
+            .into_iter()
+            .filter(|feature| self.socu & feature.bit() != 0)
+            .collect())
+    }
+
+    /// Read the debug access configuration from the shadow registers
+    pub fn read(core: &mut Core) -> anyhow::Result<Self> {
+        Ok(Self {
+            socu: core.read_word_32(fuse::shadow_address(fuse::DCFG_CC_SOCU))?,
+            socu_ns: core.read_word_32(fuse::shadow_address(fuse::DCFG_CC_SOCU_NS))?,
+        })
+    }
+
+    /// Write the debug access configuration to the shadow registers, until the next power cycle
+    ///
+    /// The ROM applies it when booting, so it only takes effect after a reset.
+    pub fn write_shadow(&self, core: &mut Core) -> anyhow::Result<()> {
+        core.write_32(fuse::shadow_address(fuse::DCFG_CC_SOCU), &[self.socu, self.socu_ns])?;
+        Ok(())
+    }
+}
+
+/// Authenticate as debugger using the debug credential at `credential_path`, signed for the key at `key_path`
+///
+/// Runs the debug authentication protocol over the debug mailbox using nxpdebugmbox, which requests the ROM to open
+/// up debug access as permitted by the credential until the next reset. `serial` selects the probe.
+pub fn authenticate(
+    nxpdebugmbox: impl AsRef<Path>,
+    family: &str,
+    serial: Option<&str>,
+    credential_path: &Path,
+    key_path: &Path,
+    beacon: u16,
+) -> anyhow::Result<()> {
+    // nxpdebugmbox -s SERIAL dat auth -f FAMILY -c DC -k KEY -b BEACON
+    let mut command = Command::new(nxpdebugmbox.as_ref());
+
+    if let Some(serial) = serial {
+        command.args(["-s", serial]);
+    }
+    command.args(["dat", "auth", "-f", family, "-c"]);
+    command.arg(credential_path);
+    command.arg("-k");
+    command.arg(key_path);
+    command.args(["-b", &beacon.to_string()]);
+
+    let output = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .output()
+        .with_context(|| {
+            format!(
+                "Could not execute `{}`, is it installed?",
+                nxpdebugmbox.as_ref().display()
+            )
+        })
+        .classify(ErrorKind::ExternalTool)?;
+
+    if !output.status.success() {
+        return Err(
+            anyhow::anyhow!("Debug authentication with {} failed", credential_path.display())
+                .context(String::from_utf8(output.stdout)?),
+        )
+        .classify(ErrorKind::Verification);
+    }
+
+    Ok(())
+}
+
+/// Serial number of the probe in a probe-rs selector of the form `VID:PID:SERIAL`, as expected by nxpdebugmbox
+pub fn probe_serial(selector: &str) -> Option<&str> {
+    let mut parts = selector.splitn(3, ':');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(_), Some(_), Some(serial)) => Some(serial),
+        _ => None,
+    }
+}
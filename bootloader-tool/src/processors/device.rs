@@ -0,0 +1,214 @@
+use std::ops::Range;
+
+use anyhow::{Context, bail};
+use ec_slimloader_state::state::State;
+use mbi_format::{ImageKind, Ivt, Trailer};
+use probe_rs::flashing::DownloadOptions;
+use probe_rs::{Core, MemoryInterface, Session};
+
+use crate::config::Config;
+use crate::processors::fuse::{self, Word};
+use crate::processors::mbi::metadata::Metadata;
+use crate::processors::state;
+
+/// Image found at the start of a slot, as far as can be told from its header and metadata trailer
+#[derive(Debug, Clone, PartialEq)]
+pub struct SlotImage {
+    pub kind: Option<ImageKind>,
+    pub len: u32,
+    pub load_addr: u32,
+    pub metadata: Option<Metadata>,
+}
+
+/// Application image slot as configured in `application.slot_starts`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SlotInfo {
+    pub start: u64,
+    /// [None] if the slot does not start with a plausible image header, for example because it is erased
+    pub image: Option<SlotImage>,
+}
+
+/// Everything the bootloader bases its decisions on, as read from the device at one moment
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    /// Latest state in the state journal, [None] if the journal is empty or corrupt
+    pub state: Option<State>,
+    pub slots: Vec<SlotInfo>,
+    pub fuses: Vec<(Word, u32)>,
+}
+
+/// Read the contents of the state journal configured as `bootloader.state`
+pub fn read_journal(core: &mut Core, config: &Config) -> anyhow::Result<Vec<u8>> {
+    let Some(bootloader) = &config.bootloader else {
+        bail!("Bootloader not defined in configuration file");
+    };
+
+    let mut journal = vec![0u8; bootloader.state.size as usize];
+    core.read(bootloader.state.start, &mut journal)?;
+    Ok(journal)
+}
+
+/// Read the latest state from the state journal configured as `bootloader.state`
+pub fn read_state(core: &mut Core, config: &Config) -> anyhow::Result<Option<State>> {
+    Ok(state::latest(&read_journal(core, config)?))
+}
+
+/// Read the image header and metadata trailer of the slot starting at `start`, no larger than `slot_size`
+///
+/// Relies on the external flash being memory mapped, which it is once the ROM or bootloader has run.
+pub fn read_slot(core: &mut Core, start: u64, slot_size: u64) -> anyhow::Result<SlotInfo> {
+    let mut header = [0u8; Ivt::LEN];
+    core.read(start, &mut header)?;
+    let ivt = Ivt::parse(&header).expect("buffer of header length");
+
+    let kind = ivt.image_kind();
+    if (ivt.image_len as usize) < Ivt::LEN || ivt.image_len as u64 > slot_size {
+        return Ok(SlotInfo { start, image: None });
+    }
+
+    let offset = Trailer::offset(ivt.image_len as usize) as u64;
+    let mut trailer = vec![0u8; Trailer::HEADER_LEN];
+    let metadata = if offset + Trailer::HEADER_LEN as u64 <= slot_size {
+        core.read(start + offset, &mut trailer)?;
+        match Trailer::entries_len(&trailer) {
+            Ok(entries_len) if offset + (Trailer::HEADER_LEN + entries_len) as u64 <= slot_size => {
+                trailer.resize(Trailer::HEADER_LEN + entries_len, 0);
+                core.read(start + offset, &mut trailer)?;
+                Trailer::parse(&trailer).ok().map(|trailer| Metadata::parse(&trailer))
+            }
+            _ => None,
+        }
+    } else {
+        None
+    };
+
+    Ok(SlotInfo {
+        start,
+        image: Some(SlotImage {
+            kind,
+            len: ivt.image_len,
+            load_addr: ivt.load_addr,
+            metadata,
+        }),
+    })
+}
+
+/// Read the state journal, all application slots and the fuse words relevant to secure boot
+pub fn snapshot(core: &mut Core, config: &Config) -> anyhow::Result<Snapshot> {
+    let state = read_state(core, config)?;
+
+    let slots = match &config.application {
+        Some(application) => application
+            .slot_starts
+            .iter()
+            .map(|start| read_slot(core, *start, application.slot_size))
+            .collect::<anyhow::Result<_>>()?,
+        None => vec![],
+    };
+
+    let words = fuse::words();
+    let values = fuse::read(core, &words)?;
+    let fuses = words.into_iter().zip(values).collect();
+
+    Ok(Snapshot { state, slots, fuses })
+}
+
+/// Erase sector size of the external flash, the granularity at which [mismatched_sectors] reports differences
+pub const SECTOR_SIZE: u64 = 0x1000;
+
+/// Sectors of flash in which `actual` differs from `expected`, both starting at `start`, merged into address ranges
+pub fn mismatched_sectors(start: u64, expected: &[u8], actual: &[u8]) -> Vec<Range<u64>> {
+    let mut mismatched: Vec<Range<u64>> = vec![];
+    for (offset, _) in expected
+        .iter()
+        .zip(actual)
+        .enumerate()
+        .filter(|(_, (expected, actual))| expected != actual)
+    {
+        let sector_start = (start + offset as u64) / SECTOR_SIZE * SECTOR_SIZE;
+        match mismatched.last_mut() {
+            Some(last) if last.end >= sector_start => last.end = last.end.max(sector_start + SECTOR_SIZE),
+            _ => mismatched.push(sector_start..sector_start + SECTOR_SIZE),
+        }
+    }
+    mismatched
+}
+
+/// Read back the flash starting at `start` and compare it against `expected`, as just programmed
+///
+/// Relies on the external flash being memory mapped, like [read_slot], which it is after programming it.
+pub fn verify_flashed(core: &mut Core, start: u64, expected: &[u8]) -> anyhow::Result<()> {
+    let mut actual = vec![0u8; expected.len()];
+    core.read(start, &mut actual)?;
+
+    let mismatched = mismatched_sectors(start, expected, &actual);
+    if mismatched.is_empty() {
+        return Ok(());
+    }
+    let sectors = mismatched
+        .iter()
+        .map(|range| format!("{:#010x}..{:#010x}", range.start, range.end))
+        .collect::<Vec<_>>()
+        .join(", ");
+    bail!("Flash contents differ from the image in {sectors}")
+}
+
+/// Erase `len` bytes of external flash starting at `start`, for example a whole slot
+pub fn erase(session: &mut Session, start: u64, len: u64) -> anyhow::Result<()> {
+    let mut loader = session.target().flash_loader();
+    loader.add_data(start, &vec![0xff; len as usize])?;
+    loader
+        .commit(session, DownloadOptions::default())
+        .context("Failed to erase flash")
+}
+
+/// Flash region of the application slot `slot`, as configured in `application.slot_starts` and `application.slot_size`
+pub fn slot_region(config: &Config, slot: usize) -> anyhow::Result<Range<u64>> {
+    let Some(application) = &config.application else {
+        bail!("Application not defined in configuration file");
+    };
+    let Some(start) = application.slot_starts.get(slot) else {
+        bail!(
+            "Slot {slot} not defined in configuration file, which has {} slots",
+            application.slot_starts.len()
+        );
+    };
+
+    Ok(*start..*start + application.slot_size)
+}
+
+/// Read the entire contents of the slot in `region`, including anything after the image
+///
+/// Relies on the external flash being memory mapped, like [read_slot].
+pub fn dump_slot(core: &mut Core, region: Range<u64>) -> anyhow::Result<Vec<u8>> {
+    let mut contents = vec![0u8; (region.end - region.start) as usize];
+    core.read(region.start, &mut contents)?;
+    Ok(contents)
+}
+
+/// Contents to write to a slot of `slot_size` bytes, such that nothing of the previous contents remains
+///
+/// Pads `contents` as in erased flash.
+pub fn slot_contents(contents: &[u8], slot_size: u64) -> anyhow::Result<Vec<u8>> {
+    if contents.len() as u64 > slot_size {
+        bail!(
+            "Contents of {:#x} bytes exceed the slot of {slot_size:#x} bytes",
+            contents.len()
+        );
+    }
+
+    let mut padded = contents.to_vec();
+    padded.resize(slot_size as usize, 0xff);
+    Ok(padded)
+}
+
+/// Replace the contents of the slot in `region` by `contents`, see [slot_contents]
+pub fn write_slot(session: &mut Session, region: Range<u64>, contents: &[u8]) -> anyhow::Result<()> {
+    let contents = slot_contents(contents, region.end - region.start)?;
+
+    let mut loader = session.target().flash_loader();
+    loader.add_data(region.start, &contents)?;
+    loader
+        .commit(session, DownloadOptions::default())
+        .context("Failed to write slot")
+}
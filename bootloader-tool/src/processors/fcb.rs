@@ -0,0 +1,479 @@
+//! FlexSPI NOR Configuration Block (FCB), which the ROM reads at offset 0x400 of the boot flash to configure the FlexSPI controller.
+//!
+//! Generated from a TOML description of the flash, see `fcb/1spi-a1-nor.toml` for an example.
+//! The layout follows `flexspi_nor_config_t` in the reference manual (UM11147).
+
+use std::fmt::Write;
+use std::path::Path;
+
+use anyhow::{Context, bail};
+use crc::{CRC_32_ISO_HDLC, Crc};
+use serde::Deserialize;
+
+/// Size of the FCB in bytes.
+pub const FCB_LEN: usize = 0x200;
+
+/// "FCFB" in little endian.
+const TAG: u32 = 0x4246_4346;
+/// Version 1.4.0 of the FCB layout.
+const VERSION: u32 = 0x5601_0400;
+/// Device type of a serial NOR flash.
+const DEVICE_TYPE_SERIAL_NOR: u8 = 1;
+
+/// Number of LUT sequences, each consisting of four words of two instructions.
+const SEQUENCE_COUNT: usize = 16;
+const INSTRUCTIONS_PER_SEQUENCE: usize = 8;
+
+// Offsets of the fields in the FCB.
+const OFFSET_TAG: usize = 0x000;
+const OFFSET_VERSION: usize = 0x004;
+const OFFSET_READ_SAMPLE_CLK_SRC: usize = 0x00C;
+const OFFSET_CS_HOLD_TIME: usize = 0x00D;
+const OFFSET_CS_SETUP_TIME: usize = 0x00E;
+const OFFSET_COLUMN_ADDRESS_WIDTH: usize = 0x00F;
+const OFFSET_DEVICE_MODE_CFG_ENABLE: usize = 0x010;
+const OFFSET_DEVICE_MODE_TYPE: usize = 0x011;
+const OFFSET_WAIT_TIME_CFG_COMMANDS: usize = 0x012;
+const OFFSET_DEVICE_MODE_SEQ: usize = 0x014;
+const OFFSET_DEVICE_MODE_ARG: usize = 0x018;
+const OFFSET_CONTROLLER_MISC_OPTION: usize = 0x040;
+const OFFSET_DEVICE_TYPE: usize = 0x044;
+const OFFSET_SFLASH_PAD_TYPE: usize = 0x045;
+const OFFSET_SERIAL_CLK_FREQ: usize = 0x046;
+const OFFSET_SFLASH_A1_SIZE: usize = 0x050;
+const OFFSET_SFLASH_A2_SIZE: usize = 0x054;
+const OFFSET_SFLASH_B1_SIZE: usize = 0x058;
+const OFFSET_SFLASH_B2_SIZE: usize = 0x05C;
+const OFFSET_LOOKUP_TABLE: usize = 0x080;
+const OFFSET_PAGE_SIZE: usize = 0x1C0;
+const OFFSET_SECTOR_SIZE: usize = 0x1C4;
+const OFFSET_IS_UNIFORM_BLOCK_SIZE: usize = 0x1C9;
+const OFFSET_SERIAL_NOR_TYPE: usize = 0x1CC;
+const OFFSET_BLOCK_SIZE: usize = 0x1D0;
+
+/// Description of a NOR flash and how the ROM should access it.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct FlashDescription {
+    /// Number of data pads used once the flash is configured: 1, 2, 4 or 8.
+    pub pads: Pads,
+    /// Serial clock frequency in MHz, one of the frequencies supported by the ROM.
+    pub frequency_mhz: u32,
+    /// Size in bytes of the flash connected to each chip select, 0 if there is none.
+    #[serde(default)]
+    pub a1_size: u32,
+    #[serde(default)]
+    pub a2_size: u32,
+    #[serde(default)]
+    pub b1_size: u32,
+    #[serde(default)]
+    pub b2_size: u32,
+    #[serde(default = "default_page_size")]
+    pub page_size: u32,
+    #[serde(default = "default_sector_size")]
+    pub sector_size: u32,
+    #[serde(default = "default_block_size")]
+    pub block_size: u32,
+    /// Whether the flash only supports erasing whole blocks.
+    #[serde(default)]
+    pub uniform_block_size: bool,
+    #[serde(default)]
+    pub serial_nor_type: SerialNorType,
+    #[serde(default)]
+    pub read_sample_clock: ReadSampleClock,
+    /// Chip select hold and setup time in serial clock cycles.
+    #[serde(default = "default_cs_time")]
+    pub cs_hold_time: u8,
+    #[serde(default = "default_cs_time")]
+    pub cs_setup_time: u8,
+    /// Number of column address bits, for flashes addressed by row and column.
+    #[serde(default)]
+    pub column_address_width: u8,
+    /// Raw value of the controller misc option bitfield.
+    #[serde(default)]
+    pub controller_misc_option: u32,
+    /// Command to bring the flash into the mode described here, such as enabling quad mode.
+    pub device_mode: Option<DeviceMode>,
+    /// Command sets, as sequences of instructions in the lookup table.
+    #[serde(default, rename = "sequence")]
+    pub sequences: Vec<Sequence>,
+}
+
+fn default_page_size() -> u32 {
+    256
+}
+
+fn default_sector_size() -> u32 {
+    4 * 1024
+}
+
+fn default_block_size() -> u32 {
+    64 * 1024
+}
+
+fn default_cs_time() -> u8 {
+    3
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(try_from = "u8")]
+pub enum Pads {
+    Single,
+    Dual,
+    Quad,
+    Octal,
+}
+
+impl TryFrom<u8> for Pads {
+    type Error = String;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(Self::Single),
+            2 => Ok(Self::Dual),
+            4 => Ok(Self::Quad),
+            8 => Ok(Self::Octal),
+            _ => Err(format!("invalid number of pads {value}, expected 1, 2, 4 or 8")),
+        }
+    }
+}
+
+impl Pads {
+    /// Number of pads, as used for the pad type of the flash.
+    fn count(self) -> u8 {
+        match self {
+            Self::Single => 1,
+            Self::Dual => 2,
+            Self::Quad => 4,
+            Self::Octal => 8,
+        }
+    }
+
+    /// Encoding of the number of pads in a LUT instruction.
+    fn lut_code(self) -> u16 {
+        match self {
+            Self::Single => 0,
+            Self::Dual => 1,
+            Self::Quad => 2,
+            Self::Octal => 3,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum SerialNorType {
+    #[default]
+    StandardSpi,
+    Hyperbus,
+    Xpi,
+    NoCmd,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ReadSampleClock {
+    /// Dummy read strobe looped back internally.
+    #[default]
+    InternalLoopback,
+    /// Dummy read strobe looped back from the DQS pad.
+    DqsLoopback,
+    /// Read strobe provided by the flash on the DQS pad.
+    FlashDqs,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct DeviceMode {
+    pub kind: DeviceModeKind,
+    /// Sequence sending the command, and the number of consecutive sequences it consists of.
+    pub sequence: SequenceRef,
+    #[serde(default = "default_sequence_count")]
+    pub sequence_count: u8,
+    /// Argument of the command, such as the value of a status register.
+    pub argument: u32,
+    /// Time to wait after the command in units of 100 µs.
+    #[serde(default)]
+    pub wait_time: u16,
+}
+
+fn default_sequence_count() -> u8 {
+    1
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum DeviceModeKind {
+    Generic,
+    QuadEnable,
+    Spi2Xpi,
+    Xpi2Spi,
+    Spi2NoCmd,
+    Reset,
+}
+
+/// Sequence in the lookup table, either by the purpose the ROM uses it for or by index.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum SequenceRef {
+    Named(SequenceName),
+    Index(u8),
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum SequenceName {
+    Read,
+    ReadStatus,
+    ReadStatusXpi,
+    WriteEnable,
+    WriteEnableXpi,
+    EraseSector,
+    EraseBlock,
+    PageProgram,
+    ChipErase,
+    ReadSfdp,
+    RestoreNoCmd,
+    ExitNoCmd,
+}
+
+impl SequenceRef {
+    /// Index of the sequence in the lookup table, as fixed by the ROM for named sequences.
+    fn index(self) -> anyhow::Result<usize> {
+        let index = match self {
+            Self::Named(SequenceName::Read) => 0,
+            Self::Named(SequenceName::ReadStatus) => 1,
+            Self::Named(SequenceName::ReadStatusXpi) => 2,
+            Self::Named(SequenceName::WriteEnable) => 3,
+            Self::Named(SequenceName::WriteEnableXpi) => 4,
+            Self::Named(SequenceName::EraseSector) => 5,
+            Self::Named(SequenceName::EraseBlock) => 8,
+            Self::Named(SequenceName::PageProgram) => 9,
+            Self::Named(SequenceName::ChipErase) => 11,
+            Self::Named(SequenceName::ReadSfdp) => 13,
+            Self::Named(SequenceName::RestoreNoCmd) => 14,
+            Self::Named(SequenceName::ExitNoCmd) => 15,
+            Self::Index(index) => index as usize,
+        };
+        if index >= SEQUENCE_COUNT {
+            bail!("Sequence index {index} out of range, the lookup table has {SEQUENCE_COUNT} sequences");
+        }
+        Ok(index)
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Sequence {
+    pub id: SequenceRef,
+    /// At most 8 instructions, the remainder is filled with `STOP`.
+    pub instructions: Vec<Instruction>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(deny_unknown_fields)]
+pub struct Instruction {
+    pub opcode: Opcode,
+    pub pads: Pads,
+    #[serde(default)]
+    pub operand: u8,
+}
+
+/// Opcodes of LUT instructions, named as in the reference manual.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(non_camel_case_types)]
+pub enum Opcode {
+    STOP,
+    CMD_SDR,
+    CMD_DDR,
+    RADDR_SDR,
+    RADDR_DDR,
+    CADDR_SDR,
+    CADDR_DDR,
+    MODE1_SDR,
+    MODE1_DDR,
+    MODE2_SDR,
+    MODE2_DDR,
+    MODE4_SDR,
+    MODE4_DDR,
+    MODE8_SDR,
+    MODE8_DDR,
+    WRITE_SDR,
+    WRITE_DDR,
+    READ_SDR,
+    READ_DDR,
+    LEARN_SDR,
+    LEARN_DDR,
+    DATSZ_SDR,
+    DATSZ_DDR,
+    DUMMY_SDR,
+    DUMMY_DDR,
+    DUMMY_RWDS_SDR,
+    DUMMY_RWDS_DDR,
+    JMP_ON_CS,
+}
+
+impl Opcode {
+    fn code(self) -> u16 {
+        match self {
+            Self::STOP => 0x00,
+            Self::CMD_SDR => 0x01,
+            Self::CMD_DDR => 0x21,
+            Self::RADDR_SDR => 0x02,
+            Self::RADDR_DDR => 0x22,
+            Self::CADDR_SDR => 0x03,
+            Self::CADDR_DDR => 0x23,
+            Self::MODE1_SDR => 0x04,
+            Self::MODE1_DDR => 0x24,
+            Self::MODE2_SDR => 0x05,
+            Self::MODE2_DDR => 0x25,
+            Self::MODE4_SDR => 0x06,
+            Self::MODE4_DDR => 0x26,
+            Self::MODE8_SDR => 0x07,
+            Self::MODE8_DDR => 0x27,
+            Self::WRITE_SDR => 0x08,
+            Self::WRITE_DDR => 0x28,
+            Self::READ_SDR => 0x09,
+            Self::READ_DDR => 0x29,
+            Self::LEARN_SDR => 0x0A,
+            Self::LEARN_DDR => 0x2A,
+            Self::DATSZ_SDR => 0x0B,
+            Self::DATSZ_DDR => 0x2B,
+            Self::DUMMY_SDR => 0x0C,
+            Self::DUMMY_DDR => 0x2C,
+            Self::DUMMY_RWDS_SDR => 0x0D,
+            Self::DUMMY_RWDS_DDR => 0x2D,
+            Self::JMP_ON_CS => 0x1F,
+        }
+    }
+}
+
+impl Instruction {
+    /// Encode as a half word of a LUT sequence.
+    pub fn encode(&self) -> u16 {
+        self.operand as u16 | (self.pads.lut_code() << 8) | (self.opcode.code() << 10)
+    }
+}
+
+/// Serial clock frequency setting of the ROM for `mhz`.
+fn serial_clk_freq(mhz: u32) -> anyhow::Result<u8> {
+    const FREQUENCIES: [u32; 9] = [30, 50, 60, 80, 100, 120, 133, 166, 200];
+    match FREQUENCIES.iter().position(|f| *f == mhz) {
+        Some(i) => Ok(i as u8 + 1),
+        None => bail!("Unsupported serial clock frequency {mhz} MHz, expected one of {FREQUENCIES:?}"),
+    }
+}
+
+impl FlashDescription {
+    pub fn read(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        toml::from_str(&contents).with_context(|| format!("Failed to parse {}", path.display()))
+    }
+
+    /// Lookup table of 16 sequences of 4 words each.
+    pub fn lookup_table(&self) -> anyhow::Result<[u32; SEQUENCE_COUNT * 4]> {
+        let mut lut = [0u32; SEQUENCE_COUNT * 4];
+        let mut defined = [false; SEQUENCE_COUNT];
+
+        for sequence in &self.sequences {
+            let index = sequence.id.index()?;
+            if std::mem::replace(&mut defined[index], true) {
+                bail!("Sequence {:?} (index {index}) is defined more than once", sequence.id);
+            }
+            if sequence.instructions.len() > INSTRUCTIONS_PER_SEQUENCE {
+                bail!(
+                    "Sequence {:?} has {} instructions, at most {INSTRUCTIONS_PER_SEQUENCE} fit",
+                    sequence.id,
+                    sequence.instructions.len()
+                );
+            }
+
+            for (i, instruction) in sequence.instructions.iter().enumerate() {
+                lut[index * 4 + i / 2] |= (instruction.encode() as u32) << (16 * (i % 2));
+            }
+        }
+
+        if !defined[0] {
+            bail!("The read sequence is required, as the ROM reads the boot image using it");
+        }
+        Ok(lut)
+    }
+
+    /// Serialize into the FCB as read by the ROM.
+    pub fn build(&self) -> anyhow::Result<[u8; FCB_LEN]> {
+        if [self.a1_size, self.a2_size, self.b1_size, self.b2_size] == [0; 4] {
+            bail!("No flash size given, set at least one of a1_size, a2_size, b1_size and b2_size");
+        }
+
+        let mut fcb = [0u8; FCB_LEN];
+        let mut put_u32 = |offset: usize, value: u32| fcb[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+
+        put_u32(OFFSET_TAG, TAG);
+        put_u32(OFFSET_VERSION, VERSION);
+        put_u32(OFFSET_CONTROLLER_MISC_OPTION, self.controller_misc_option);
+        put_u32(OFFSET_SFLASH_A1_SIZE, self.a1_size);
+        put_u32(OFFSET_SFLASH_A2_SIZE, self.a2_size);
+        put_u32(OFFSET_SFLASH_B1_SIZE, self.b1_size);
+        put_u32(OFFSET_SFLASH_B2_SIZE, self.b2_size);
+        put_u32(OFFSET_PAGE_SIZE, self.page_size);
+        put_u32(OFFSET_SECTOR_SIZE, self.sector_size);
+        put_u32(OFFSET_BLOCK_SIZE, self.block_size);
+        for (i, word) in self.lookup_table()?.into_iter().enumerate() {
+            put_u32(OFFSET_LOOKUP_TABLE + i * 4, word);
+        }
+
+        if let Some(device_mode) = &self.device_mode {
+            put_u32(OFFSET_DEVICE_MODE_ARG, device_mode.argument);
+            fcb[OFFSET_DEVICE_MODE_CFG_ENABLE] = 1;
+            fcb[OFFSET_DEVICE_MODE_TYPE] = device_mode.kind as u8;
+            fcb[OFFSET_WAIT_TIME_CFG_COMMANDS..OFFSET_WAIT_TIME_CFG_COMMANDS + 2]
+                .copy_from_slice(&device_mode.wait_time.to_le_bytes());
+            // Number of sequences followed by the index of the first one.
+            fcb[OFFSET_DEVICE_MODE_SEQ] = device_mode.sequence_count;
+            fcb[OFFSET_DEVICE_MODE_SEQ + 1] = device_mode.sequence.index()? as u8;
+        }
+
+        fcb[OFFSET_READ_SAMPLE_CLK_SRC] = match self.read_sample_clock {
+            ReadSampleClock::InternalLoopback => 0,
+            ReadSampleClock::DqsLoopback => 1,
+            ReadSampleClock::FlashDqs => 3,
+        };
+        fcb[OFFSET_CS_HOLD_TIME] = self.cs_hold_time;
+        fcb[OFFSET_CS_SETUP_TIME] = self.cs_setup_time;
+        fcb[OFFSET_COLUMN_ADDRESS_WIDTH] = self.column_address_width;
+        fcb[OFFSET_DEVICE_TYPE] = DEVICE_TYPE_SERIAL_NOR;
+        fcb[OFFSET_SFLASH_PAD_TYPE] = self.pads.count();
+        fcb[OFFSET_SERIAL_CLK_FREQ] = serial_clk_freq(self.frequency_mhz)?;
+        fcb[OFFSET_IS_UNIFORM_BLOCK_SIZE] = self.uniform_block_size as u8;
+        fcb[OFFSET_SERIAL_NOR_TYPE] = self.serial_nor_type as u8;
+
+        Ok(fcb)
+    }
+}
+
+/// Contents of the FCB partition of a bootloader with the `runtime-fcb` feature: the FCB followed by its CRC-32.
+pub fn partition_image(fcb: &[u8; FCB_LEN]) -> Vec<u8> {
+    let crc = Crc::<u32>::new(&CRC_32_ISO_HDLC).checksum(fcb);
+    [fcb.as_slice(), &crc.to_le_bytes()].concat()
+}
+
+/// Rust source placing `fcb` in the `.fcb` section, for a bootloader built without any of the `imxrt-fcb-*` features.
+pub fn to_rust(fcb: &[u8; FCB_LEN], source: &Path) -> String {
+    let mut rust = String::new();
+    writeln!(
+        rust,
+        "// FlexSPI NOR Configuration Block generated by bootloader-tool from {}",
+        source.display()
+    )
+    .unwrap();
+    writeln!(rust, "#[link_section = \".fcb\"]").unwrap();
+    writeln!(rust, "#[used]").unwrap();
+    writeln!(rust, "static FCB: [u8; {FCB_LEN:#x}] = [").unwrap();
+    for line in fcb.chunks(16) {
+        let bytes = line.iter().map(|b| format!("{b:#04x},")).collect::<Vec<_>>();
+        writeln!(rust, "    {}", bytes.join(" ")).unwrap();
+    }
+    writeln!(rust, "];").unwrap();
+    rust
+}
@@ -0,0 +1,191 @@
+use std::fmt;
+
+use probe_rs::{Core, MemoryInterface};
+
+use crate::config::Config;
+use crate::error::{Classify, ErrorKind};
+use crate::processors::boot_cfg;
+use crate::processors::certificates::Rkth;
+use crate::util::generate_hex;
+
+/// Start of the OTP shadow registers, which hold one 32-bit word for each fuse word
+///
+/// The ROM loads these from the fuses on power-up. They can be overwritten over the probe,
+/// which is what `run` does, so they only reflect the fuses until then.
+const SHADOW_BASE: u64 = 0x40130000;
+
+/// Fuse word index of BOOT_CFG0
+pub const BOOT_CFG0: u32 = 96;
+/// Fuse word index of BOOT_CFG1
+pub const BOOT_CFG1: u32 = 97;
+/// Fuse word index of SEC_BOOT_CFG5
+pub const SEC_BOOT_CFG5: u32 = 101;
+/// Fuse word index of DCFG_CC_SOCU, restricting debug access to authenticated debuggers
+pub const DCFG_CC_SOCU: u32 = 104;
+/// Fuse word index of DCFG_CC_SOCU_NS, the inverse of DCFG_CC_SOCU
+pub const DCFG_CC_SOCU_NS: u32 = 105;
+/// Fuse word index of the OTP master key, spanning 8 words
+pub const OTP_MASTER_KEY: u32 = 112;
+/// Fuse word index of the root key table hash, spanning 8 words
+pub const RKTH: u32 = 120;
+/// Fuse word index of the device UUID, spanning 4 words
+pub const UUID: u32 = 184;
+
+/// USE_PUF bit in SEC_BOOT_CFG5, which must be cleared for the OTP master key to be used
+pub const SEC_BOOT_CFG5_USE_PUF: u32 = 1 << 7;
+
+/// Address of the shadow register of a fuse word
+pub fn shadow_address(index: u32) -> u64 {
+    SHADOW_BASE + index as u64 * 4
+}
+
+/// Read the current value of each fuse word from its shadow register
+pub fn read(core: &mut Core, words: &[Word]) -> anyhow::Result<Vec<u32>> {
+    words
+        .iter()
+        .map(|word| Ok(core.read_word_32(shadow_address(word.index))?))
+        .collect()
+}
+
+/// Read the UUID of the device from its shadow registers, as hex
+///
+/// The UUID is fused at manufacturing and never written by this tool, so it identifies the device.
+pub fn read_uuid(core: &mut Core) -> anyhow::Result<String> {
+    let mut uuid = vec![];
+    for index in UUID..UUID + 4 {
+        uuid.extend_from_slice(&core.read_word_32(shadow_address(index))?.to_le_bytes());
+    }
+    Ok(generate_hex(&uuid))
+}
+
+/// BOOT_CFG0 as intended for secure boot on the board of the configuration
+pub fn planned_boot_cfg0(config: &Config) -> anyhow::Result<u32> {
+    boot_cfg::encode("BOOT_CFG0", &boot_cfg::fields(&config.board, "BOOT_CFG0")).classify(ErrorKind::Config)
+}
+
+/// BOOT_CFG1 as intended for secure boot on the board of the configuration
+pub fn planned_boot_cfg1(config: &Config) -> anyhow::Result<u32> {
+    boot_cfg::encode("BOOT_CFG1", &boot_cfg::fields(&config.board, "BOOT_CFG1")).classify(ErrorKind::Config)
+}
+
+/// A fuse word relevant to secure boot
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Word {
+    pub index: u32,
+    pub name: &'static str,
+}
+
+impl fmt::Display for Word {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} (word {}, {:#010x})",
+            self.name,
+            self.index,
+            shadow_address(self.index)
+        )
+    }
+}
+
+/// All fuse words read by `fuse read`
+///
+/// The OTP master key is left out, as it is not meant to leave the device.
+pub fn words() -> Vec<Word> {
+    const RKTH_NAMES: [&str; 8] = [
+        "RKTH[0]", "RKTH[1]", "RKTH[2]", "RKTH[3]", "RKTH[4]", "RKTH[5]", "RKTH[6]", "RKTH[7]",
+    ];
+
+    let mut words = vec![
+        Word {
+            index: BOOT_CFG0,
+            name: "BOOT_CFG0",
+        },
+        Word {
+            index: BOOT_CFG1,
+            name: "BOOT_CFG1",
+        },
+        Word {
+            index: SEC_BOOT_CFG5,
+            name: "SEC_BOOT_CFG5",
+        },
+    ];
+    words.extend(RKTH_NAMES.iter().zip(RKTH..).map(|(name, index)| Word { index, name }));
+    words
+}
+
+/// Value a fuse word is intended to have, limited to the bits set in `mask`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Expected {
+    pub word: Word,
+    pub value: u32,
+    pub mask: u32,
+}
+
+/// The provisioning intended by the configuration, for each word in [words]
+pub fn expected(config: &Config, rkth: &Rkth) -> anyhow::Result<Vec<Expected>> {
+    let rkth = rkth.as_u32_le();
+    let (boot_cfg0, boot_cfg1) = (planned_boot_cfg0(config)?, planned_boot_cfg1(config)?);
+    Ok(words()
+        .into_iter()
+        .map(|word| {
+            let (value, mask) = match word.index {
+                BOOT_CFG0 => (boot_cfg0, u32::MAX),
+                BOOT_CFG1 => (boot_cfg1, u32::MAX),
+                SEC_BOOT_CFG5 => (0, SEC_BOOT_CFG5_USE_PUF),
+                index => (rkth[(index - RKTH) as usize], u32::MAX),
+            };
+            Expected { word, value, mask }
+        })
+        .collect())
+}
+
+/// A fuse word that does not match its intended value
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mismatch {
+    pub expected: Expected,
+    pub actual: u32,
+}
+
+impl Mismatch {
+    /// Bits that still need to be burned
+    pub fn missing(&self) -> u32 {
+        self.expected.value & !self.actual & self.expected.mask
+    }
+
+    /// Bits that are set but should not be, which burning can not undo
+    pub fn excess(&self) -> u32 {
+        !self.expected.value & self.actual & self.expected.mask
+    }
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Expected { word, value, mask } = self.expected;
+        write!(f, "{word}: {:#010x}, expected {value:#010x}", self.actual)?;
+        if mask != u32::MAX {
+            write!(f, " under mask {mask:#010x}")?;
+        }
+        if self.missing() != 0 {
+            write!(f, ", to burn {:#010x}", self.missing())?;
+        }
+        if self.excess() != 0 {
+            write!(f, ", irreversibly set {:#010x}", self.excess())?;
+        }
+        Ok(())
+    }
+}
+
+/// Compare the read fuse words against their intended values
+///
+/// `read` yields the value for each of the expected words, in order.
+pub fn verify(expected: &[Expected], read: &[u32]) -> Vec<Mismatch> {
+    expected
+        .iter()
+        .zip(read)
+        .filter(|(expected, actual)| (*actual ^ expected.value) & expected.mask != 0)
+        .map(|(expected, actual)| Mismatch {
+            expected: *expected,
+            actual: *actual,
+        })
+        .collect()
+}
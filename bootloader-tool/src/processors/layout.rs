@@ -0,0 +1,137 @@
+use std::fmt::Write;
+
+use anyhow::{Context, bail};
+
+use crate::config::{Config, MemoryRange};
+
+/// Memory mapped address of the start of the external NOR flash, from which partition offsets are counted
+pub const FLASH_BASE: u64 = 0x0800_0000;
+
+/// Offset of `address` within the external NOR flash
+fn flash_offset(address: u64, name: &str) -> anyhow::Result<u64> {
+    address
+        .checked_sub(FLASH_BASE)
+        .with_context(|| format!("{name} at {address:#010x} lies before the external flash at {FLASH_BASE:#010x}"))
+}
+
+fn constant(rust: &mut String, doc: &str, name: &str, value: u64) {
+    writeln!(rust, "/// {doc}").unwrap();
+    writeln!(rust, "pub const {name}: u32 = {value:#010x};").unwrap();
+}
+
+fn memory_range(rust: &mut String, doc: &str, name: &str, range: &MemoryRange) -> anyhow::Result<()> {
+    constant(
+        rust,
+        &format!("Address of the {doc}"),
+        &format!("{name}_START"),
+        range.start,
+    );
+    constant(
+        rust,
+        &format!("Offset of the {doc} in the external flash"),
+        &format!("{name}_OFFSET"),
+        flash_offset(range.start, name)?,
+    );
+    constant(rust, &format!("Size of the {doc}"), &format!("{name}_SIZE"), range.size);
+    Ok(())
+}
+
+fn array(rust: &mut String, doc: &str, name: &str, values: &[u64]) {
+    let values = values.iter().map(|value| format!("{value:#010x}")).collect::<Vec<_>>();
+    writeln!(rust, "/// {doc}").unwrap();
+    writeln!(
+        rust,
+        "pub const {name}: [u32; {}] = [{}];",
+        values.len(),
+        values.join(", ")
+    )
+    .unwrap();
+}
+
+/// Render the flash layout of `config` as a Rust module of constants, to be included by the bootloader and application
+///
+/// Addresses are memory mapped, offsets are relative to [FLASH_BASE] as used for partitions of the external flash.
+/// Sections of the configuration that are absent are left out, but at least one of them has to be present.
+pub fn to_rust(config: &Config) -> anyhow::Result<String> {
+    if config.bootloader.is_none() && config.application.is_none() {
+        bail!("Neither bootloader nor application defined in configuration file");
+    }
+
+    let mut rust = String::new();
+    writeln!(
+        rust,
+        "// Flash layout generated by bootloader-tool from its configuration, do not edit"
+    )
+    .unwrap();
+    writeln!(rust).unwrap();
+    constant(
+        &mut rust,
+        "Address of the start of the external flash",
+        "FLASH_BASE",
+        FLASH_BASE,
+    );
+
+    if let Some(bootloader) = &config.bootloader {
+        writeln!(rust).unwrap();
+        constant(
+            &mut rust,
+            "Address of the bootloader in the external flash",
+            "BOOTLOADER_START",
+            bootloader.flash_start,
+        );
+        constant(
+            &mut rust,
+            "Address the bootloader runs from",
+            "BOOTLOADER_RUN_START",
+            bootloader.run_start,
+        );
+        constant(
+            &mut rust,
+            "Maximum size of the signed bootloader image",
+            "BOOTLOADER_MAX_SIZE",
+            bootloader.max_size,
+        );
+        memory_range(&mut rust, "state journal", "STATE", &bootloader.state)?;
+        if let Some(counters) = &bootloader.counters {
+            memory_range(&mut rust, "counters journal", "COUNTERS", counters)?;
+        }
+    }
+
+    if let Some(application) = &config.application {
+        let offsets = application
+            .slot_starts
+            .iter()
+            .map(|start| flash_offset(*start, "slot"))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        writeln!(rust).unwrap();
+        array(
+            &mut rust,
+            "Address of each application slot",
+            "SLOT_STARTS",
+            &application.slot_starts,
+        );
+        array(
+            &mut rust,
+            "Offset of each application slot in the external flash",
+            "SLOT_OFFSETS",
+            &offsets,
+        );
+        constant(
+            &mut rust,
+            "Size of each application slot, which is also the maximum size of a signed image",
+            "SLOT_SIZE",
+            application.slot_size,
+        );
+        writeln!(rust, "/// Range of RAM into which application images are loaded").unwrap();
+        writeln!(
+            rust,
+            "pub const LOAD_RANGE: core::ops::Range<u32> = {:#010x}..{:#010x};",
+            application.run_start,
+            application.run_start + application.slot_size
+        )
+        .unwrap();
+    }
+
+    Ok(rust)
+}
@@ -0,0 +1,213 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::Context;
+use rsa::RsaPrivateKey;
+use rsa::pkcs1v15::{Signature, SigningKey};
+use rsa::pkcs8::DecodePrivateKey;
+use rsa::signature::{SignatureEncoding, SignerMut, Verifier};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::config::Config;
+use crate::processors::fuse::{self, Expected};
+use crate::processors::mbi::cert_block::CertBlock;
+use crate::util::{generate_hex, parse_hex};
+
+/// Image to be flashed at a specific address, as passed on the command line as `PATH@ADDRESS`
+#[derive(Debug, Clone)]
+pub struct ImageArg {
+    pub path: PathBuf,
+    pub address: u64,
+}
+
+impl FromStr for ImageArg {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (path, address) = s
+            .rsplit_once('@')
+            .ok_or_else(|| anyhow::anyhow!("Expected PATH@ADDRESS, got {s}"))?;
+        let address = match address.strip_prefix("0x") {
+            Some(hex) => u64::from_str_radix(hex, 16),
+            None => address.parse(),
+        }
+        .with_context(|| format!("Invalid address {address}"))?;
+
+        Ok(Self {
+            path: PathBuf::from(path),
+            address,
+        })
+    }
+}
+
+/// Intended value of a fuse word, see [Expected]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FusePlan {
+    pub name: String,
+    pub index: u32,
+    pub value: u32,
+    pub mask: u32,
+}
+
+/// Image as flashed to the device
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FlashImage {
+    pub address: u64,
+    pub length: usize,
+    pub sha256: String,
+}
+
+/// Record of the intended provisioning of a device
+///
+/// Contains only public information: the OTP master key is recorded as its digest.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Manifest {
+    /// Index of the certificate chain in the configuration
+    pub certificate: usize,
+    /// SHA-256 of each certificate in the chain (DER), starting with the root certificate
+    pub certificates: Vec<String>,
+    /// Root key table hash derived from the certificate chain
+    pub rkth: String,
+    /// SHA-256 of the OTP master key
+    pub otp_key_sha256: String,
+    /// Intended values of the fuse words relevant to secure boot
+    pub fuses: Vec<FusePlan>,
+    /// Images flashed to the device
+    pub images: Vec<FlashImage>,
+}
+
+/// [Manifest] signed by the leaf certificate of its certificate chain
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SignedManifest {
+    pub manifest: Manifest,
+    pub signature: String,
+}
+
+pub fn sha256_hex(data: &[u8]) -> String {
+    generate_hex(&Sha256::digest(data))
+}
+
+impl Manifest {
+    pub fn new(
+        config: &Config,
+        certificate: usize,
+        cert_block: &CertBlock,
+        otp_key: &[u8],
+        images: &[(u64, Vec<u8>)],
+    ) -> anyhow::Result<Self> {
+        let rkth = cert_block.rkth();
+        Ok(Self {
+            certificate,
+            certificates: cert_block.certificates()?.iter().map(|cert| sha256_hex(cert)).collect(),
+            rkth: rkth.as_hex(),
+            otp_key_sha256: sha256_hex(otp_key),
+            fuses: fuse::expected(config, &rkth)?
+                .into_iter()
+                .map(|expected| FusePlan {
+                    name: expected.word.name.to_string(),
+                    index: expected.word.index,
+                    value: expected.value,
+                    mask: expected.mask,
+                })
+                .collect(),
+            images: images
+                .iter()
+                .map(|(address, data)| FlashImage {
+                    address: *address,
+                    length: data.len(),
+                    sha256: sha256_hex(data),
+                })
+                .collect(),
+        })
+    }
+
+    /// The fuse plan as [Expected] values, for comparison with the fuses of a device
+    pub fn expected_fuses(&self) -> anyhow::Result<Vec<Expected>> {
+        let words = fuse::words();
+        self.fuses
+            .iter()
+            .map(|plan| {
+                let word = words
+                    .iter()
+                    .find(|word| word.index == plan.index)
+                    .ok_or_else(|| anyhow::anyhow!("Unknown fuse word {} ({})", plan.index, plan.name))?;
+                Ok(Expected {
+                    word: *word,
+                    value: plan.value,
+                    mask: plan.mask,
+                })
+            })
+            .collect()
+    }
+
+    /// Sign the manifest with the private key of the leaf certificate
+    pub fn sign(self, private_key_path: impl AsRef<Path>) -> anyhow::Result<SignedManifest> {
+        let private_key = std::fs::read_to_string(private_key_path)?;
+        let private_key = RsaPrivateKey::from_pkcs8_pem(&private_key)?;
+        let mut signing_key = SigningKey::<Sha256>::new(private_key);
+
+        let signature = signing_key.sign(&serde_json::to_vec(&self)?);
+        Ok(SignedManifest {
+            manifest: self,
+            signature: generate_hex(&signature.to_bytes()),
+        })
+    }
+}
+
+impl SignedManifest {
+    pub fn read(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let file = std::fs::read(path).with_context(|| format!("Failed to open manifest {}", path.display()))?;
+        Ok(serde_json::from_slice(&file)?)
+    }
+
+    pub fn write(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        Ok(std::fs::write(path, serde_json::to_string_pretty(self)?)?)
+    }
+
+    /// Check that the manifest was signed by the leaf certificate of `cert_block`
+    pub fn verify(&self, cert_block: &CertBlock) -> anyhow::Result<&Manifest> {
+        let signature = Signature::try_from(parse_hex(&self.signature)?.as_slice())?;
+        cert_block
+            .verifying_key()
+            .verify(&serde_json::to_vec(&self.manifest)?, &signature)
+            .context("Manifest signature does not match its certificate chain")?;
+        Ok(&self.manifest)
+    }
+}
+
+/// A difference between a device or configuration and its provisioning manifest
+pub enum Mismatch {
+    /// The certificate chain in the manifest is not the one in the configuration
+    Certificates,
+    /// The RKTH in the manifest is not derived from the configured certificate chain
+    Rkth { manifest: String, config: String },
+    /// The OTP master key in the configuration is not the one recorded in the manifest
+    OtpKey,
+    /// A fuse word of the device does not match the fuse plan
+    Fuse(fuse::Mismatch),
+    /// An image in the flash of the device does not match the recorded digest
+    Image {
+        address: u64,
+        actual: String,
+        expected: String,
+    },
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Mismatch::Certificates => write!(f, "certificate chain differs from the configuration"),
+            Mismatch::Rkth { manifest, config } => write!(f, "RKTH: {manifest} in manifest, {config} in configuration"),
+            Mismatch::OtpKey => write!(f, "OTP master key differs from the configuration"),
+            Mismatch::Fuse(mismatch) => write!(f, "fuse {mismatch}"),
+            Mismatch::Image {
+                address,
+                actual,
+                expected,
+            } => write!(f, "image at {address:#010x}: SHA-256 {actual}, expected {expected}"),
+        }
+    }
+}
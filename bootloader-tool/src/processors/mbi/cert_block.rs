@@ -1,8 +1,10 @@
 use std::collections::BTreeMap;
+use std::io::Write;
 use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
 
 use anyhow::Context;
+use mbi_format::CertBlockHeader;
 use rsa::RsaPublicKey;
 use rsa::pkcs1v15::VerifyingKey;
 use rsa::pkcs8::DecodePublicKey;
@@ -12,8 +14,10 @@ use sha2::{Digest, Sha256};
 use x509_parser::public_key::PublicKey;
 
 use crate::Config;
-use crate::processors::certificates::Rkth;
+use crate::error::{Classify, ErrorKind};
+use crate::processors::certificates::{self, Rkth, Validity};
 use crate::processors::mbi::parse_x509_cert;
+use crate::util::{generate_hex, temp_file, temp_path};
 
 #[derive(Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -28,68 +32,134 @@ pub struct CertBlockConfig {
     pub container_output_file: Option<PathBuf>,
 }
 
-/// Best-effort attempt to canonicalize the path, if it exists.
-fn canonicalize_or_leave(path: impl AsRef<Path>) -> PathBuf {
-    match path.as_ref().canonicalize() {
-        Ok(path) => path,
-        Err(_) => path.as_ref().to_owned(),
-    }
+/// Best-effort attempt to make the path absolute.
+///
+/// Unlike canonicalizing, this does not yield the `\\?\` verbatim paths of Windows, which nxpimage does not support.
+fn absolute_or_leave(path: impl AsRef<Path>) -> PathBuf {
+    std::path::absolute(path.as_ref()).unwrap_or_else(|_| path.as_ref().to_owned())
 }
 
 pub fn generate_config(
     config: &Config,
     certificate_idx: usize,
     output_file: Option<impl AsRef<Path>>,
-) -> CertBlockConfig {
+) -> anyhow::Result<CertBlockConfig> {
     let mut certificates = BTreeMap::default();
-    for (chain_i, chain) in config.certificates.iter().enumerate() {
-        for (cert_i, cert) in chain.0.iter().enumerate() {
+    for (slot, &chain_i) in config.root_key_chains()?.iter().enumerate() {
+        for (cert_i, cert) in config.certificates[chain_i].0.iter().enumerate() {
             let name = if cert_i == 0 {
-                format!("rootCertificate{}File", chain_i)
+                format!("rootCertificate{}File", slot)
             } else {
-                format!("chainCertificate{}File{}", chain_i, cert_i - 1)
+                format!("chainCertificate{}File{}", slot, cert_i - 1)
             };
-            certificates.insert(name, canonicalize_or_leave(&cert.path));
+            certificates.insert(name, absolute_or_leave(&cert.path));
         }
     }
 
-    CertBlockConfig {
-        family: "mimxrt685s".to_owned(),
-        revision: "latest".to_owned(),
+    Ok(CertBlockConfig {
+        family: config.mbi.family.clone(),
+        revision: config.mbi.revision.clone(),
         certificates,
-        main_root_cert_id: certificate_idx,
+        main_root_cert_id: config.root_key_slot(certificate_idx)?,
         container_output_file: output_file.map(|output_file| output_file.as_ref().to_owned()),
+    })
+}
+
+/// Digest of the inputs of generating the cert block for certificate chain `certificate_idx`
+///
+/// Covers the version of this tool, the family and revision, the root key slots, and the names and contents of the
+/// certificates.
+pub fn cache_key(config: &Config, certificate_idx: usize) -> anyhow::Result<String> {
+    let mut hasher = Sha256::new();
+    let mut update = |data: &[u8]| {
+        hasher.update((data.len() as u64).to_le_bytes());
+        hasher.update(data);
+    };
+
+    let cert_block_config = generate_config(config, certificate_idx, None::<PathBuf>)?;
+    update(env!("CARGO_PKG_VERSION").as_bytes());
+    update(cert_block_config.family.as_bytes());
+    update(cert_block_config.revision.as_bytes());
+    update(&(cert_block_config.main_root_cert_id as u64).to_le_bytes());
+    for (name, path) in &cert_block_config.certificates {
+        update(name.as_bytes());
+        // Certificates that cannot be read make generation fail, and are thus never part of a cached cert block.
+        update(&std::fs::read(path).unwrap_or_default());
     }
+
+    Ok(generate_hex(&hasher.finalize()))
+}
+
+/// Path of the cert block cached for `key` in the artifacts directory
+pub fn cache_path(config: &Config, key: &str) -> PathBuf {
+    config.artifacts_path.join("cert-blocks").join(format!("{key}.bin"))
 }
 
+fn store(path: &Path, cert_block: &CertBlock) -> anyhow::Result<()> {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    std::fs::create_dir_all(dir)?;
+
+    // Concurrent signing may store the same cert block at once, so never leave a partially written one behind.
+    let mut file = tempfile::NamedTempFile::new_in(dir)?;
+    file.write_all(cert_block.raw())?;
+    file.persist(path)?;
+    Ok(())
+}
+
+/// Cert block for certificate chain `certificate_idx`, generated with nxpimage unless cached since
+///
+/// Cached cert blocks are verified like generated ones, and replaced when the certificates change.
 pub fn generate(nxpimage: impl AsRef<Path>, config: &Config, certificate_idx: usize) -> anyhow::Result<CertBlock> {
+    let path = cache_path(config, &cache_key(config, certificate_idx)?);
+    if path.exists() {
+        match CertBlock::from_file(&path, None) {
+            Ok(cert_block) => {
+                log::info!("Reusing certificate block {}", path.display());
+                return Ok(cert_block);
+            }
+            Err(e) => log::warn!(
+                "Regenerating invalid cached certificate block {}: {e:#}",
+                path.display()
+            ),
+        }
+    }
+
+    let cert_block = generate_uncached(nxpimage, config, certificate_idx)?;
+    if let Err(e) = store(&path, &cert_block) {
+        log::warn!("Could not cache certificate block in {}: {e:#}", path.display());
+    }
+    Ok(cert_block)
+}
+
+fn generate_uncached(nxpimage: impl AsRef<Path>, config: &Config, certificate_idx: usize) -> anyhow::Result<CertBlock> {
     // nxpimage cert-block export -c ./cert-block.yaml
 
-    let mut input_file = tempfile::NamedTempFile::new()?;
-    let output_file = tempfile::NamedTempFile::new()?;
-    serde_json::to_writer(
-        &mut input_file,
-        &generate_config(config, certificate_idx, Some(output_file.path())),
+    let output_file = temp_path(".bin")?;
+    let input_file = temp_file(
+        ".json",
+        &serde_json::to_vec(&generate_config(config, certificate_idx, Some(&output_file))?)?,
     )?;
 
     let mut command = Command::new(nxpimage.as_ref());
 
     command.args(["cert-block", "export", "-c"]);
-    command.arg(input_file.path());
+    command.arg(&input_file);
 
     let output = command
         .stdin(Stdio::null())
         .stdout(Stdio::piped())
         .stderr(Stdio::inherit())
         .output()
-        .with_context(move || format!("Could not execute `{}`, is it installed?", nxpimage.as_ref().display()))?;
+        .with_context(move || format!("Could not execute `{}`, is it installed?", nxpimage.as_ref().display()))
+        .classify(ErrorKind::ExternalTool)?;
 
     if !output.status.success() {
         return Err(anyhow::anyhow!(format!(
             "Failed to build certificate block from {}",
-            input_file.as_ref().display()
+            input_file.display()
         ))
-        .context(String::from_utf8(output.stdout)?));
+        .context(String::from_utf8(output.stdout)?))
+        .classify(ErrorKind::ExternalTool);
     }
 
     let rkth_str = String::from_utf8(output.stdout)?
@@ -103,7 +173,7 @@ pub fn generate(nxpimage: impl AsRef<Path>, config: &Config, certificate_idx: us
 
     log::info!("RKTH: {rkth_str}");
 
-    CertBlock::from_file(output_file.path(), Some(&rkth))
+    CertBlock::from_file(&output_file, Some(&rkth))
 }
 
 /// Cert block as described in UM11147 Fig 239
@@ -118,50 +188,70 @@ impl CertBlock {
     /// That file contains padding data, so we read out the lengths from the header in that file to determine the correct length.
     pub fn from_file(filename: impl AsRef<Path>, rkth: Option<&Rkth>) -> anyhow::Result<Self> {
         let data = std::fs::read(filename)?;
-        let mut me = Self { data };
-
-        // Strip padding
-        let cert_block_len = me.header_len() + me.cert_table_len() + 4 * Sha256::output_size() as u32;
-        assert!(me.data.len() >= cert_block_len as usize);
-        me.data.truncate(cert_block_len as usize);
+        let me = Self::from_bytes(&data)?;
 
         // Ensure the cert block is valid
-        me.verify(rkth)?;
+        me.verify(rkth).classify(ErrorKind::Verification)?;
 
         Ok(me)
     }
 
-    /// Get the raw bytes of the cert block
-    pub fn raw(&self) -> &[u8] {
-        &self.data
-    }
+    /// Read cert block from the start of `data`, ignoring any trailing bytes
+    ///
+    /// Note: the cert block is not verified, such that the caller can inspect blocks that do not verify.
+    pub fn from_bytes(data: &[u8]) -> anyhow::Result<Self> {
+        let Ok(header) = CertBlockHeader::parse(data) else {
+            return Err(anyhow::anyhow!("Certificate block header is truncated"));
+        };
 
-    fn cert_count(&self) -> u32 {
-        u32::from_le_bytes(self.data[0x18..0x1c].try_into().unwrap())
+        // Strip padding or whatever follows the cert block
+        let cert_block_len = header.root_key_hashes_offset() + 4 * Sha256::output_size();
+        if data.len() < cert_block_len {
+            return Err(anyhow::anyhow!(
+                "Certificate block is truncated, expected {cert_block_len} bytes, got {}",
+                data.len()
+            ));
+        }
+
+        Ok(Self {
+            data: data[..cert_block_len].to_vec(),
+        })
     }
 
-    fn cert_table_len(&self) -> u32 {
-        u32::from_le_bytes(self.data[0x1c..0x20].try_into().unwrap())
+    /// Get the raw bytes of the cert block
+    pub fn raw(&self) -> &[u8] {
+        &self.data
     }
 
-    fn header_len(&self) -> u32 {
-        let len = u32::from_le_bytes(self.data[0x08..0x0c].try_into().unwrap());
+    /// Get the parsed header of the cert block
+    pub fn header(&self) -> CertBlockHeader {
+        let header = CertBlockHeader::parse(&self.data).expect("length checked in from_bytes");
 
-        if len != 0x20 {
-            log::warn!("Header length mismatch, expected {:x?}, got {:x?}", 0x20, len);
+        if header.header_length as usize != CertBlockHeader::LEN {
+            log::warn!(
+                "Header length mismatch, expected {:x?}, got {:x?}",
+                CertBlockHeader::LEN,
+                header.header_length
+            );
         }
 
-        len
+        header
+    }
+
+    /// Get the build number, which the ROM uses for anti-rollback
+    pub fn build_number(&self) -> u32 {
+        self.header().build_number
     }
 
     /// Sets the total length of signed bytes, this needs to be updated before signing
     pub fn set_total_image_length_in_bytes(&mut self, total_image_length_in_bytes: usize) {
-        let total_image_length_in_bytes = total_image_length_in_bytes.try_into().unwrap();
-        self.data[0x14..0x18].copy_from_slice(&u32::to_le_bytes(total_image_length_in_bytes));
+        let mut header = self.header();
+        header.total_image_length = total_image_length_in_bytes.try_into().unwrap();
+        header.write(&mut self.data).expect("length checked in from_bytes");
     }
 
     fn root_key_hashes(&self) -> [[u8; 256 / 8]; 4] {
-        let rkh_start = (self.header_len() + self.cert_table_len()) as usize;
+        let rkh_start = self.header().root_key_hashes_offset();
         let data = &self.data[rkh_start..];
         assert_eq!(data.len(), 4 * Sha256::output_size());
 
@@ -181,46 +271,42 @@ impl CertBlock {
         Rkth(hash.finalize().into())
     }
 
-    fn certificates(&self) -> anyhow::Result<Vec<Vec<u8>>> {
+    /// Get the DER encoded certificates, starting with the root certificate
+    pub fn certificates(&self) -> anyhow::Result<Vec<Vec<u8>>> {
         let mut out = vec![];
-        let mut next_cert = self.header_len() as usize;
-
-        for i in 0..self.cert_count() {
-            let cert_len = u32::from_le_bytes(self.data[next_cert..next_cert + 4].try_into().unwrap()) as usize;
+        let header = self.header();
+        let mut next_cert = header.header_length as usize;
+        let cert_table_end = header.root_key_hashes_offset();
+        let cert_table = &self.data[..cert_table_end];
+
+        for i in 0..header.certificate_count {
+            let Some(cert_len) = cert_table.get(next_cert..next_cert + 4) else {
+                return Err(anyhow::anyhow!("Length of cert {i} exceeds the certificate table"));
+            };
+            let cert_len = u32::from_le_bytes(cert_len.try_into().unwrap()) as usize;
             if !cert_len.is_multiple_of(4) {
                 return Err(anyhow::anyhow!("Certificate of cert {i} length is not divisible by 4"));
             }
             next_cert += 4;
-            out.push(self.data[next_cert..next_cert + cert_len].to_vec());
+            let Some(cert) = cert_table.get(next_cert..next_cert + cert_len) else {
+                return Err(anyhow::anyhow!("Cert {i} exceeds the certificate table"));
+            };
+            out.push(cert.to_vec());
             next_cert += cert_len;
         }
 
-        assert_eq!(next_cert, (self.header_len() + self.cert_table_len()) as usize);
+        if next_cert != cert_table_end {
+            return Err(anyhow::anyhow!(
+                "Certificates do not fill the certificate table, {next_cert} of {cert_table_end} bytes used"
+            ));
+        }
 
         Ok(out)
     }
 
-    /// Check if this CertBlock is consistent with itself and the Rkth
-    fn verify(&self, rkth: Option<&Rkth>) -> anyhow::Result<()> {
-        log::info!("Checking certificate block is consistent");
-
-        // Ensure the rkth is correct if the user has one
-        match rkth {
-            Some(rkth) if &self.rkth() != rkth => {
-                return Err(anyhow::anyhow!(
-                    "CertBlock RKTH does not match provided Rkth, in block: {:x?}, to check: {rkth:x?}",
-                    self.rkth()
-                ));
-            }
-            _ => {}
-        }
-
-        // Unpack the certificates
+    /// Slot of the root key table occupied by the root certificate of the chain
+    pub fn root_key_slot(&self) -> anyhow::Result<usize> {
         let certs = self.certificates().context("Could not parse certificate list")?;
-
-        log::info!("Got {} certificates in certificate block", certs.len());
-
-        // Check root cert is in root key hashes
         let Some(raw_root_cert) = certs.first() else {
             return Err(anyhow::anyhow!("Certificate block contains no certificates"));
         };
@@ -249,16 +335,58 @@ impl CertBlock {
         let rkh: [u8; 32] = rkh.finalize().into();
 
         // Walk the root key hashes to find the right slot
-        match self.root_key_hashes().iter().position(|&slot| slot == rkh) {
-            Some(slot) => log::info!("Found key hash in RKT slot {slot}"),
-            None => {
-                return Err(anyhow::anyhow!(
+        self.root_key_hashes()
+            .iter()
+            .position(|&slot| slot == rkh)
+            .ok_or_else(|| {
+                anyhow::anyhow!(
                     "Root cert is not in root key hashes! Cert hash: {rkh:x?}, Hash table: {:x?}",
                     self.root_key_hashes()
+                )
+            })
+    }
+
+    /// Ensure the root certificate of the chain occupies `slot` of the root key table
+    pub fn check_root_key_slot(&self, slot: usize) -> anyhow::Result<()> {
+        let actual = self.root_key_slot()?;
+        if actual != slot {
+            return Err(anyhow::anyhow!(
+                "Root certificate occupies root key slot {actual}, but is intended for slot {slot}"
+            ))
+            .classify(ErrorKind::Verification);
+        }
+        Ok(())
+    }
+
+    /// Check if this CertBlock is consistent with itself and the Rkth
+    pub(crate) fn verify(&self, rkth: Option<&Rkth>) -> anyhow::Result<()> {
+        log::info!("Checking certificate block is consistent");
+
+        // Ensure the rkth is correct if the user has one
+        match rkth {
+            Some(rkth) if &self.rkth() != rkth => {
+                return Err(anyhow::anyhow!(
+                    "CertBlock RKTH does not match provided Rkth, in block: {:x?}, to check: {rkth:x?}",
+                    self.rkth()
                 ));
             }
+            _ => {}
         }
 
+        // Unpack the certificates
+        let certs = self.certificates().context("Could not parse certificate list")?;
+
+        log::info!("Got {} certificates in certificate block", certs.len());
+
+        // Check root cert is in root key hashes
+        let Some(raw_root_cert) = certs.first() else {
+            return Err(anyhow::anyhow!("Certificate block contains no certificates"));
+        };
+
+        let root_cert = parse_x509_cert(raw_root_cert)?;
+        let slot = self.root_key_slot()?;
+        log::info!("Found key hash in RKT slot {slot}");
+
         // Check root_cert is a CA cert
         if !root_cert.is_ca() {
             return Err(anyhow::anyhow!("Root cert is not marked as CA"));
@@ -291,6 +419,24 @@ impl CertBlock {
         Ok(())
     }
 
+    /// Validity of each certificate in the chain, starting from the root certificate
+    pub fn validities(&self) -> anyhow::Result<Vec<Validity>> {
+        self.certificates()?
+            .iter()
+            .enumerate()
+            .map(|(i, cert)| {
+                Ok(Validity::from_certificate(
+                    &parse_x509_cert(cert).with_context(|| format!("Could not parse cert number {i}"))?,
+                ))
+            })
+            .collect()
+    }
+
+    /// Check that no certificate in the chain expires within `window_days`, see [certificates::check_expiry]
+    pub fn check_expiry(&self, window_days: u32, strict: bool) -> anyhow::Result<()> {
+        certificates::check_expiry(&self.validities()?, window_days, strict)
+    }
+
     /// Get the public key of the certificates that is used to sign the image
     pub fn verifying_key(&self) -> VerifyingKey<Sha256> {
         let certs = self.certificates().expect("Ensured in verify");
@@ -0,0 +1,176 @@
+use std::fmt;
+use std::ops::Range;
+
+use mbi_format::Ivt;
+
+use crate::processors::certificates::Rkth;
+use crate::processors::mbi::SignedImage;
+use crate::processors::mbi::metadata::Metadata;
+
+/// Names of the header words that have a meaning beyond being a vector table entry
+const HEADER_FIELDS: [(usize, &str); 6] = [
+    (0x00, "initial stack pointer"),
+    (0x04, "reset vector"),
+    (0x20, "image length"),
+    (0x24, "image type"),
+    (0x28, "cert block offset"),
+    (0x34, "load address"),
+];
+
+/// A single difference between two signed images
+#[derive(Debug, Clone, PartialEq)]
+pub enum Difference {
+    /// A word in the image header differs
+    Header { offset: usize, a: u32, b: u32 },
+    /// The header HMAC differs, or is only present in one of the images
+    Hmac,
+    /// The cert block build number differs
+    BuildNumber { a: u32, b: u32 },
+    /// The number of certificates in the cert block differs
+    CertificateCount { a: usize, b: usize },
+    /// A certificate in the cert block differs
+    Certificate { index: usize },
+    /// The root key table hash differs
+    Rkth { a: Rkth, b: Rkth },
+    /// The length of the payload differs
+    PayloadLength { a: usize, b: usize },
+    /// The payload differs in the given range of image offsets
+    Payload { range: Range<usize>, load_addr: u32 },
+    /// The signature differs
+    Signature,
+    /// The metadata trailer differs, or is only present in one of the images
+    Metadata {
+        a: Option<Box<Metadata>>,
+        b: Option<Box<Metadata>>,
+    },
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Difference::Header { offset, a, b } => {
+                match HEADER_FIELDS.iter().find(|(field_offset, _)| field_offset == offset) {
+                    Some((_, name)) => write!(f, "header {name}")?,
+                    None => write!(f, "header vector table entry at {offset:#04x}")?,
+                }
+                write!(f, ": {a:#010x} -> {b:#010x}")
+            }
+            Difference::Hmac => write!(f, "header HMAC"),
+            Difference::BuildNumber { a, b } => write!(f, "cert block build number: {a} -> {b}"),
+            Difference::CertificateCount { a, b } => write!(f, "cert block certificate count: {a} -> {b}"),
+            Difference::Certificate { index } => write!(f, "cert block certificate {index}"),
+            Difference::Rkth { a, b } => write!(f, "RKTH: {} -> {}", a.as_hex(), b.as_hex()),
+            Difference::PayloadLength { a, b } => write!(f, "payload length: {a:#x} -> {b:#x}"),
+            Difference::Payload { range, load_addr } => write!(
+                f,
+                "payload {:#010x}..{:#010x} (image offset {:#x}..{:#x}, {} bytes)",
+                *load_addr as usize + range.start,
+                *load_addr as usize + range.end,
+                range.start,
+                range.end,
+                range.len()
+            ),
+            Difference::Signature => write!(f, "signature"),
+            Difference::Metadata { a, b } => {
+                let describe = |metadata: &Option<Box<Metadata>>| match metadata {
+                    Some(metadata) => format!("[{metadata}]"),
+                    None => "none".to_owned(),
+                };
+                write!(f, "metadata: {} -> {}", describe(a), describe(b))
+            }
+        }
+    }
+}
+
+/// Ranges of offsets at which the two slices differ, limited to their common length
+fn differing_ranges(a: &[u8], b: &[u8]) -> Vec<Range<usize>> {
+    let mut out: Vec<Range<usize>> = vec![];
+    for (i, _) in a.iter().zip(b).enumerate().filter(|(_, (a, b))| a != b) {
+        match out.last_mut() {
+            Some(range) if range.end == i => range.end = i + 1,
+            _ => out.push(i..i + 1),
+        }
+    }
+    out
+}
+
+/// Compare two signed images and list their differences, in order of appearance in the image
+pub fn diff(a: &SignedImage, b: &SignedImage) -> anyhow::Result<Vec<Difference>> {
+    let mut out = vec![];
+
+    for index in 0..Ivt::LEN / 4 {
+        let (word_a, word_b) = (a.header.word(index), b.header.word(index));
+        if word_a != word_b {
+            out.push(Difference::Header {
+                offset: index * 4,
+                a: word_a,
+                b: word_b,
+            });
+        }
+    }
+
+    if a.hmac != b.hmac {
+        out.push(Difference::Hmac);
+    }
+
+    // Payload offsets are relative to the start of the image as loaded, and thus exclude the HMAC
+    if a.data.len() != b.data.len() {
+        out.push(Difference::PayloadLength {
+            a: a.data.len(),
+            b: b.data.len(),
+        });
+    }
+    out.extend(
+        differing_ranges(&a.data, &b.data)
+            .into_iter()
+            .map(|range| Difference::Payload {
+                range: range.start + Ivt::LEN..range.end + Ivt::LEN,
+                load_addr: a.header.load_addr(),
+            }),
+    );
+
+    let (cert_block_a, cert_block_b) = (&a.cert_block, &b.cert_block);
+    if cert_block_a.build_number() != cert_block_b.build_number() {
+        out.push(Difference::BuildNumber {
+            a: cert_block_a.build_number(),
+            b: cert_block_b.build_number(),
+        });
+    }
+
+    let certs_a = cert_block_a.certificates()?;
+    let certs_b = cert_block_b.certificates()?;
+    if certs_a.len() != certs_b.len() {
+        out.push(Difference::CertificateCount {
+            a: certs_a.len(),
+            b: certs_b.len(),
+        });
+    }
+    out.extend(
+        certs_a
+            .iter()
+            .zip(&certs_b)
+            .enumerate()
+            .filter(|(_, (cert_a, cert_b))| cert_a != cert_b)
+            .map(|(index, _)| Difference::Certificate { index }),
+    );
+
+    if cert_block_a.rkth() != cert_block_b.rkth() {
+        out.push(Difference::Rkth {
+            a: cert_block_a.rkth(),
+            b: cert_block_b.rkth(),
+        });
+    }
+
+    if a.signature != b.signature {
+        out.push(Difference::Signature);
+    }
+
+    if a.metadata != b.metadata {
+        out.push(Difference::Metadata {
+            a: a.metadata.clone().map(Box::new),
+            b: b.metadata.clone().map(Box::new),
+        });
+    }
+
+    Ok(out)
+}
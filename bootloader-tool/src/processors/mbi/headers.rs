@@ -0,0 +1,149 @@
+use std::fmt;
+
+use mbi_format::{CertBlockHeader, ImageType, Ivt};
+use sha2::{Digest, Sha256};
+
+use crate::processors::certificates::Rkth;
+use crate::util::generate_hex;
+
+/// Number of root key hashes in the root key table
+const ROOT_KEY_COUNT: usize = 4;
+
+/// Headers decoded from an arbitrary binary, without checking that they are consistent
+///
+/// Decoding stops at the first structure that does not fit the binary, such that truncated or corrupt images can be
+/// inspected up to that point. Unlike [SignedImage](super::SignedImage), nothing is verified.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Headers {
+    /// Length of the binary
+    pub len: usize,
+    pub ivt: Ivt,
+    /// Offset of the cert block, following the header HMAC if the image kind has one
+    pub cert_block_offset: usize,
+    /// Cert block header, if it fits the binary
+    pub cert_block: Option<CertBlockHeader>,
+    /// Root key table, if it fits the binary
+    pub root_key_hashes: Option<[[u8; 32]; ROOT_KEY_COUNT]>,
+    /// Length of what follows the signed part up to the image length, if the image length covers the signed part
+    pub signature_len: Option<usize>,
+}
+
+impl Headers {
+    /// Decode the headers of `image`, failing only if it is too small to hold an image header
+    pub fn decode(image: &[u8]) -> anyhow::Result<Self> {
+        let ivt = Ivt::parse(image).map_err(|_| anyhow::anyhow!("Image of {} bytes has no header", image.len()))?;
+
+        let hmac_len = match ivt.image_kind() {
+            Some(kind) if kind.has_hmac() => Sha256::output_size(),
+            _ => 0,
+        };
+        let cert_block_offset = ivt.header_offset as usize + hmac_len;
+        let cert_block = image
+            .get(cert_block_offset..)
+            .and_then(|cert_block| CertBlockHeader::parse(cert_block).ok());
+
+        let root_key_hashes = cert_block.and_then(|header| {
+            let start = cert_block_offset + header.root_key_hashes_offset();
+            let table = image.get(start..start + ROOT_KEY_COUNT * Sha256::output_size())?;
+            Some(
+                table
+                    .chunks_exact(Sha256::output_size())
+                    .map(|chunk| chunk.try_into().unwrap())
+                    .collect::<Vec<_>>()
+                    .try_into()
+                    .unwrap(),
+            )
+        });
+
+        // The signed part ends with the cert block, after which the signature follows up to the image length
+        let signature_len = root_key_hashes.and(cert_block).and_then(|header| {
+            let signed_len =
+                cert_block_offset + header.root_key_hashes_offset() + ROOT_KEY_COUNT * Sha256::output_size();
+            (ivt.image_len as usize).checked_sub(signed_len)
+        });
+
+        Ok(Self {
+            len: image.len(),
+            ivt,
+            cert_block_offset,
+            cert_block,
+            root_key_hashes,
+            signature_len,
+        })
+    }
+
+    /// Calculate the RKTH of the root key table, if it fits the binary
+    pub fn rkth(&self) -> Option<Rkth> {
+        let root_key_hashes = self.root_key_hashes?;
+        Some(Rkth(Sha256::digest(root_key_hashes.as_flattened()).into()))
+    }
+}
+
+impl fmt::Display for Headers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ivt = &self.ivt;
+        writeln!(f, "Image header:")?;
+        writeln!(
+            f,
+            "  image length:  {:#x} (binary is {:#x} bytes)",
+            ivt.image_len, self.len
+        )?;
+        writeln!(f, "  image type:    {:#x}", ivt.image_type)?;
+        match ImageType::from_u32(ivt.image_type) {
+            Some(image_type) => {
+                writeln!(f, "    image kind:              {:?}", image_type.image_kind)?;
+                writeln!(f, "    TrustZone:               {:?}", image_type.tz_m_image_type)?;
+                writeln!(f, "    TrustZone preset:        {:?}", image_type.tz_m_preset)?;
+                writeln!(f, "    key store included:      {}", image_type.key_store_included)?;
+                writeln!(
+                    f,
+                    "    HW user mode keys:       {}",
+                    image_type.enable_hw_user_mode_keys
+                )?;
+            }
+            None => writeln!(f, "    image kind:              unknown ({:#x})", ivt.image_type as u8)?,
+        }
+        writeln!(f, "  header offset: {:#x}", ivt.header_offset)?;
+        writeln!(f, "  load address:  {:#010x}", ivt.load_addr)?;
+
+        let Some(cert_block) = self.cert_block else {
+            return write!(
+                f,
+                "Cert block header at {:#x}: beyond the end of the binary",
+                self.cert_block_offset
+            );
+        };
+        writeln!(f, "Cert block header at {:#x}:", self.cert_block_offset)?;
+        let signature = cert_block.signature.to_le_bytes();
+        writeln!(f, "  signature:          {:?}", String::from_utf8_lossy(&signature))?;
+        writeln!(
+            f,
+            "  version:            {}.{}",
+            cert_block.header_major_version, cert_block.header_minor_version
+        )?;
+        writeln!(f, "  header length:      {:#x}", cert_block.header_length)?;
+        writeln!(f, "  flags:              {:#x}", cert_block.flags)?;
+        writeln!(f, "  build number:       {}", cert_block.build_number)?;
+        writeln!(f, "  total image length: {:#x}", cert_block.total_image_length)?;
+        writeln!(f, "  certificate count:  {}", cert_block.certificate_count)?;
+        writeln!(
+            f,
+            "  certificate table:  {:#x} bytes",
+            cert_block.certificate_table_length
+        )?;
+
+        let (Some(root_key_hashes), Some(rkth)) = (self.root_key_hashes, self.rkth()) else {
+            return write!(f, "Root key table: beyond the end of the binary");
+        };
+        writeln!(f, "Root key table:")?;
+        for (slot, hash) in root_key_hashes.iter().enumerate() {
+            writeln!(f, "  {slot}: {}", generate_hex(hash))?;
+        }
+        writeln!(f, "RKTH: {}", rkth.as_hex())?;
+
+        match self.signature_len {
+            Some(len) => write!(f, "Signature: {len} bytes"),
+            None => write!(f, "Signature: image length ends within the signed part"),
+        }
+    }
+}
@@ -0,0 +1,169 @@
+use std::fmt;
+use std::path::Path;
+
+use anyhow::Context;
+use mbi_format::{ImageRole, Ivt, SubregionDigest, Tag, Trailer, TrailerWriter};
+use sha2::{Digest, Sha256};
+
+use crate::util::generate_hex;
+
+/// Padding between the signature and the metadata trailer, as in erased flash
+const PADDING: u8 = 0xff;
+
+/// Identity of the firmware, stored in the metadata trailer following the signature of an image
+///
+/// See [Trailer]. Note that the trailer is not covered by the signature.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Metadata {
+    pub version: Option<String>,
+    pub product_id: Option<u32>,
+    pub git_hash: Option<String>,
+    pub build_time: Option<u64>,
+    /// Role in the boot chain, an application if not given
+    pub role: Option<ImageRole>,
+    /// Whether the image awaits activation by the application before the bootloader boots it as new target
+    pub staged: bool,
+    /// Digests of the blobs placed in subregions of the slot, see [crate::processors::slot]
+    pub subregions: Vec<SubregionDigest>,
+    /// SHA-256 of the signed image, checked by a bootloader in development mode instead of the signature
+    pub image_digest: Option<[u8; 32]>,
+}
+
+impl Metadata {
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// Encode as trailer
+    pub fn to_bytes(&self) -> anyhow::Result<Vec<u8>> {
+        let mut buf =
+            vec![
+                0u8;
+                Trailer::HEADER_LEN + 7 * (2 + u8::MAX as usize) + self.subregions.len() * (2 + SubregionDigest::LEN)
+            ];
+        let mut writer = TrailerWriter::new(&mut buf).map_err(|e| anyhow::anyhow!("{e:?}"))?;
+
+        let entries: [(Tag, Option<Vec<u8>>); 7] = [
+            (
+                Tag::Version,
+                self.version.as_ref().map(|version| version.as_bytes().to_vec()),
+            ),
+            (Tag::ProductId, self.product_id.map(|id| id.to_le_bytes().to_vec())),
+            (
+                Tag::GitHash,
+                self.git_hash.as_ref().map(|hash| hash.as_bytes().to_vec()),
+            ),
+            (Tag::BuildTime, self.build_time.map(|time| time.to_le_bytes().to_vec())),
+            (Tag::Role, self.role.map(|role| vec![role as u8])),
+            (Tag::Staged, self.staged.then(|| vec![Trailer::STAGED])),
+            (Tag::ImageDigest, self.image_digest.map(|digest| digest.to_vec())),
+        ];
+        for (tag, value) in entries {
+            if let Some(value) = value {
+                writer
+                    .push(tag, &value)
+                    .map_err(|e| anyhow::anyhow!("Could not encode {tag:?} metadata: {e:?}"))?;
+            }
+        }
+        for digest in &self.subregions {
+            writer
+                .push(Tag::SubregionDigest, &digest.to_bytes())
+                .map_err(|e| anyhow::anyhow!("Could not encode subregion digest metadata: {e:?}"))?;
+        }
+
+        let len = writer.finish();
+        buf.truncate(len);
+        Ok(buf)
+    }
+
+    /// Decode from a trailer
+    pub fn parse(trailer: &Trailer) -> Self {
+        Self {
+            version: trailer.version().map(str::to_owned),
+            product_id: trailer.product_id(),
+            git_hash: trailer.git_hash().map(str::to_owned),
+            build_time: trailer.build_time(),
+            role: trailer.role(),
+            staged: trailer.staged(),
+            subregions: trailer.subregion_digests().collect(),
+            image_digest: trailer.image_digest(),
+        }
+    }
+
+    /// Length the signed image of `signed_len` bytes grows with when appending this metadata
+    pub fn appended_len(&self, signed_len: usize) -> anyhow::Result<usize> {
+        if self.is_empty() {
+            return Ok(0);
+        }
+        Ok(Trailer::offset(signed_len) - signed_len + self.to_bytes()?.len())
+    }
+
+    /// Record the digest of the signed image at `path` as [Metadata::image_digest]
+    ///
+    /// Covers the image over the length from its header, as hashed by the bootloader after loading it.
+    pub fn digest_image(&mut self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        let image = std::fs::read(path).with_context(|| format!("Could not read {}", path.display()))?;
+        let ivt = Ivt::parse(&image).map_err(|_| anyhow::anyhow!("Image of {} bytes has no header", image.len()))?;
+        let Some(image) = image.get(..ivt.image_len as usize) else {
+            return Err(anyhow::anyhow!(
+                "Image of {} bytes is shorter than the {} bytes in its header",
+                image.len(),
+                ivt.image_len
+            ));
+        };
+        self.image_digest = Some(Sha256::digest(image).into());
+        Ok(())
+    }
+
+    /// Append the trailer to the signed image at `path`, if there is any metadata
+    pub fn append_to(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        if self.is_empty() {
+            return Ok(());
+        }
+
+        let path = path.as_ref();
+        let mut image = std::fs::read(path).with_context(|| format!("Could not read {}", path.display()))?;
+        image.resize(Trailer::offset(image.len()), PADDING);
+        image.extend(self.to_bytes()?);
+        std::fs::write(path, image).with_context(|| format!("Could not write {}", path.display()))
+    }
+}
+
+impl fmt::Display for Metadata {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut fields = vec![];
+        if let Some(version) = &self.version {
+            fields.push(format!("version {version}"));
+        }
+        if let Some(product_id) = self.product_id {
+            fields.push(format!("product ID {product_id:#x}"));
+        }
+        if let Some(git_hash) = &self.git_hash {
+            fields.push(format!("git hash {git_hash}"));
+        }
+        if let Some(build_time) = self.build_time {
+            fields.push(format!("built at {build_time} (Unix time)"));
+        }
+        match self.role {
+            Some(ImageRole::Loader) => fields.push("secondary loader".to_owned()),
+            Some(ImageRole::Application) => fields.push("application".to_owned()),
+            None => {}
+        }
+        if self.staged {
+            fields.push("staged".to_owned());
+        }
+        for digest in &self.subregions {
+            fields.push(format!(
+                "subregion at {:#x} of {:#x} bytes with SHA-256 {}",
+                digest.offset,
+                digest.len,
+                generate_hex(&digest.sha256)
+            ));
+        }
+        if let Some(digest) = &self.image_digest {
+            fields.push(format!("image SHA-256 {}", generate_hex(digest)));
+        }
+        write!(f, "{}", fields.join(", "))
+    }
+}
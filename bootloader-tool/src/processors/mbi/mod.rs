@@ -1,26 +1,36 @@
 #![allow(clippy::len_without_is_empty)]
 
 pub mod cert_block;
+pub mod diff;
+pub mod headers;
+pub mod metadata;
+pub mod rom;
+pub mod vectors;
 
-use std::collections::BTreeMap;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
 
 use anyhow::{Context, anyhow, bail};
 use hmac::{Hmac, Mac};
+pub use mbi_format::{ImageKind, ImageType, SlotBinding, TrustZone, TrustZonePreset};
+use mbi_format::{Ivt, Trailer};
 use rsa::RsaPrivateKey;
 use rsa::pkcs1v15::{Signature, SigningKey};
 use rsa::pkcs8::DecodePrivateKey;
 use rsa::signature::{SignatureEncoding, SignerMut, Verifier};
+use serde::Serialize;
 use sha2::{Digest, Sha256};
-use tempfile::NamedTempFile;
 use x509_parser::asn1_rs::FromDer;
 use x509_parser::certificate::X509Certificate;
 use x509_parser::oid_registry::Oid;
 
+use crate::config::MbiArgs;
+use crate::error::{Classify, ErrorKind};
 use crate::processors::certificates::Rkth;
 use crate::processors::mbi::cert_block::{CertBlock, CertBlockConfig};
+use crate::processors::mbi::metadata::Metadata;
 use crate::processors::otp::Otp;
+use crate::util::temp_file;
 
 type HmacSha256 = Hmac<Sha256>;
 
@@ -33,19 +43,26 @@ pub struct ImageHeader {
 impl ImageHeader {
     /// Take a vector table and merge in the header information
     pub fn new(mut ivt: Vec<u8>, image_type: ImageType, header_offset: u32, load_addr: u32) -> Self {
-        assert_eq!(ivt.len(), 0x40);
+        assert_eq!(ivt.len(), Ivt::LEN);
 
-        ivt[0x24..0x28].copy_from_slice(&u32::to_le_bytes(image_type.as_u32()));
-        ivt[0x28..0x2C].copy_from_slice(&u32::to_le_bytes(header_offset));
-        ivt[0x34..0x38].copy_from_slice(&u32::to_le_bytes(load_addr));
+        let mut fields = Ivt::parse(&ivt).expect("length asserted");
+        fields.image_type = image_type.as_u32();
+        fields.header_offset = header_offset;
+        fields.load_addr = load_addr;
+        fields.write(&mut ivt).expect("length asserted");
 
         Self { ivt }
     }
 
+    fn fields(&self) -> Ivt {
+        Ivt::parse(&self.ivt).expect("length asserted in new")
+    }
+
     /// Set the image length, this includes the header, data and cert block
     pub fn set_image_length(&mut self, image_length: usize) {
-        let image_length = image_length.try_into().unwrap();
-        self.ivt[0x20..0x24].copy_from_slice(&u32::to_le_bytes(image_length));
+        let mut fields = self.fields();
+        fields.image_len = image_length.try_into().unwrap();
+        fields.write(&mut self.ivt).expect("length asserted in new");
     }
 
     /// Get the raw data
@@ -55,99 +72,30 @@ impl ImageHeader {
 
     /// Get the [ImageKind]
     pub fn image_kind(&self) -> ImageKind {
-        ImageKind::from_u8(self.ivt[0x24]).expect("image kind was set in new")
+        self.fields().image_kind().expect("image kind was set in new")
     }
-}
 
-/// Bitset for the image type header field
-#[derive(Debug, Copy, Clone)]
-pub struct ImageType {
-    pub key_store_included: bool,
-    pub tz_m_image_type: TrustZone,
-    pub tz_m_preset: TrustZonePreset,
-    pub enable_hw_user_mode_keys: bool,
-    pub image_kind: ImageKind,
-}
-
-impl ImageType {
-    pub fn new(image_kind: ImageKind) -> Self {
-        Self {
-            key_store_included: false,
-            tz_m_image_type: TrustZone::Enabled,
-            tz_m_preset: TrustZonePreset::NotIncluded,
-            enable_hw_user_mode_keys: false,
-            image_kind,
-        }
+    /// Get a 32-bit word of the header by its index
+    pub fn word(&self, index: usize) -> u32 {
+        u32::from_le_bytes(self.ivt[index * 4..index * 4 + 4].try_into().unwrap())
     }
 
-    fn as_u32(&self) -> u32 {
-        let mut out = 0;
-        let Self {
-            key_store_included,
-            tz_m_image_type,
-            tz_m_preset,
-            enable_hw_user_mode_keys,
-            image_kind: image_type,
-        } = *self;
-
-        out |= (key_store_included as u32 & 1) << 15;
-        out |= (tz_m_image_type as u32 & 1) << 14;
-        out |= (tz_m_preset as u32 & 1) << 13;
-        out |= (enable_hw_user_mode_keys as u32 & 1) << 12;
-        out |= image_type as u32;
-
-        out
+    /// Get the image length, this includes the header, data and cert block
+    pub fn image_length(&self) -> usize {
+        self.fields().image_len as usize
     }
-}
 
-#[derive(Debug, Copy, Clone)]
-pub enum ImageKind {
-    Plain = 0,
-    /// Note: This uses the Encrypted image layout, but omits the Enc. Image Header?
-    PlainSigned = 1,
-    PlainWithCrc = 2,
-    EncryptedSigned = 3,
-    XipPlainSigned = 4,
-    XipPlainWithCrc = 5,
-}
-
-impl ImageKind {
-    fn from_u8(value: u8) -> Option<Self> {
-        Some(match value {
-            0 => Self::Plain,
-            1 => Self::PlainSigned,
-            2 => Self::PlainWithCrc,
-            3 => Self::EncryptedSigned,
-            4 => Self::XipPlainSigned,
-            5 => Self::XipPlainWithCrc,
-            _ => return None,
-        })
+    /// Get the offset of the cert block, which is also the length of the header and data
+    pub fn header_offset(&self) -> usize {
+        self.fields().header_offset as usize
     }
-}
 
-impl ImageKind {
-    pub fn has_hmac(&self) -> bool {
-        match self {
-            ImageKind::Plain | ImageKind::PlainWithCrc | ImageKind::XipPlainSigned | ImageKind::XipPlainWithCrc => {
-                false
-            }
-            ImageKind::PlainSigned | ImageKind::EncryptedSigned => true,
-        }
+    /// Get the address the image is loaded at
+    pub fn load_addr(&self) -> u32 {
+        self.fields().load_addr
     }
 }
 
-#[derive(Debug, Copy, Clone)]
-pub enum TrustZone {
-    Enabled = 0,
-    Disabled = 1,
-}
-
-#[derive(Debug, Copy, Clone)]
-pub enum TrustZonePreset {
-    NotIncluded = 0,
-    Included = 1,
-}
-
 fn parse_x509_cert(bytes: &[u8]) -> anyhow::Result<X509Certificate<'_>> {
     let (rem, cert) = X509Certificate::from_der(bytes)?;
     if rem.len() >= 4 {
@@ -181,7 +129,7 @@ impl Image {
     const DATA_ALIGN: usize = 4;
 
     pub fn new(image: Vec<u8>, base_addr: u32, image_type: ImageType, mut cert_block: CertBlock) -> Self {
-        let (ivt, data) = image.split_at(0x40);
+        let (ivt, data) = image.split_at(Ivt::LEN);
 
         // Make sure we do not use the original image without padding by accident
         let ivt = ivt.to_vec();
@@ -244,25 +192,28 @@ impl Image {
         log::info!("Checking if signature matches image");
 
         if signature.len() != self.cert_block.signature_len() {
-            bail!(
+            return Err(anyhow!(
                 "signature length mismatch, expected {} got {}",
                 self.cert_block.signature_len(),
                 signature.len()
-            );
+            ))
+            .classify(ErrorKind::Verification);
         }
 
         if &self.cert_block.rkth() != rkth {
-            bail!(
+            return Err(anyhow!(
                 "CertBlock RKTH does not match expected. CertBlock: {:x?}, Expected: {rkth:x?}",
                 self.cert_block.rkth()
-            );
+            ))
+            .classify(ErrorKind::Verification);
         }
 
-        let signature = Signature::try_from(signature)?;
+        let signature = Signature::try_from(signature).classify(ErrorKind::Verification)?;
         self.cert_block
             .verifying_key()
             .verify(&self.sign_me(), &signature)
-            .context("Could not verify signature with the current image")?;
+            .context("Could not verify signature with the current image")
+            .classify(ErrorKind::Verification)?;
 
         log::info!("OK - Signature matches image");
 
@@ -292,6 +243,118 @@ impl Image {
     }
 }
 
+/// A signed MBI as read back from its binary form
+#[derive(Debug, Clone)]
+pub struct SignedImage {
+    pub header: ImageHeader,
+    pub hmac: Option<Vec<u8>>,
+    pub data: Vec<u8>,
+    pub cert_block: CertBlock,
+    pub signature: Vec<u8>,
+    /// Metadata trailer following the signature, if any
+    pub metadata: Option<Metadata>,
+}
+
+impl SignedImage {
+    /// Split a signed image as produced by [Image::merge] into its parts, optionally followed by a metadata trailer
+    ///
+    /// Note: neither the cert block nor the signature is verified.
+    pub fn parse(raw: &[u8]) -> anyhow::Result<Self> {
+        let Ok(fields) = Ivt::parse(raw) else {
+            bail!("image of {} bytes is too small to contain a header", raw.len());
+        };
+
+        if fields.image_kind().is_none() {
+            bail!("unknown image type {:#x}", fields.image_type);
+        }
+        let header = ImageHeader {
+            ivt: raw[..Ivt::LEN].to_vec(),
+        };
+
+        let metadata = if header.image_length() < raw.len() {
+            let offset = Trailer::offset(header.image_length());
+            let trailer = raw
+                .get(offset..)
+                .and_then(|trailer| Trailer::parse(trailer).ok())
+                .filter(|trailer| offset + trailer.total_len() == raw.len());
+            let Some(trailer) = trailer else {
+                bail!(
+                    "image length in header {:#x} does not match file length {:#x}, nor is it followed by metadata",
+                    header.image_length(),
+                    raw.len()
+                );
+            };
+            Some(Metadata::parse(&trailer))
+        } else if header.image_length() > raw.len() {
+            bail!(
+                "image length in header {:#x} exceeds file length {:#x}",
+                header.image_length(),
+                raw.len()
+            );
+        } else {
+            None
+        };
+        let raw = &raw[..header.image_length()];
+
+        let hmac_len = if header.image_kind().has_hmac() {
+            Sha256::output_size()
+        } else {
+            0
+        };
+        let cert_block_offset = header.header_offset() + hmac_len;
+        if cert_block_offset < Ivt::LEN + hmac_len || cert_block_offset > raw.len() {
+            bail!("cert block offset {:#x} out of range", header.header_offset());
+        }
+
+        let hmac = (hmac_len > 0).then(|| raw[Ivt::LEN..Ivt::LEN + hmac_len].to_vec());
+        let data = raw[Ivt::LEN + hmac_len..cert_block_offset].to_vec();
+        let cert_block = CertBlock::from_bytes(&raw[cert_block_offset..]).context("Could not parse cert block")?;
+        let signature = raw[cert_block_offset + cert_block.raw().len()..].to_vec();
+
+        Ok(Self {
+            header,
+            hmac,
+            data,
+            cert_block,
+            signature,
+            metadata,
+        })
+    }
+
+    /// Check the cert block and signature against `rkth`, as done when merging a signature into an image
+    pub fn check(&self, rkth: &Rkth) -> anyhow::Result<()> {
+        self.cert_block.verify(Some(rkth)).classify(ErrorKind::Verification)?;
+
+        let mut signed = self.header.raw().to_vec();
+        signed.extend(&self.data);
+        signed.extend(self.cert_block.raw());
+
+        let signature = Signature::try_from(self.signature.as_slice()).classify(ErrorKind::Verification)?;
+        self.cert_block
+            .verifying_key()
+            .verify(&signed, &signature)
+            .context("Could not verify signature with the image")
+            .classify(ErrorKind::Verification)?;
+
+        Ok(())
+    }
+
+    /// Read and parse a signed image from a file
+    pub fn from_file(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let raw =
+            std::fs::read(path.as_ref()).with_context(|| format!("Could not read {}", path.as_ref().display()))?;
+        Self::parse(&raw).with_context(|| format!("Could not parse signed image {}", path.as_ref().display()))
+    }
+}
+
+/// Append `binding` to the plain `image`, such that it ends right in front of the cert block and is thus signed
+///
+/// The image is first padded to the alignment of the cert block with 0xFF.
+pub fn append_slot_binding(image: &mut Vec<u8>, binding: SlotBinding) {
+    image.resize(image.len().next_multiple_of(Image::DATA_ALIGN), 0xFF);
+    image.extend_from_slice(&binding.to_bytes());
+}
+
 fn load_image(
     input_path: &impl AsRef<Path>,
     base_addr: u32,
@@ -302,6 +365,7 @@ fn load_image(
 
     let image_type = if is_bootloader {
         // Note: ROM bootloader only accepts xip plain signed images when secure_boot_en bit is unset.
+        // It executes the image in place if linked to run from its flash address, and copies it to RAM otherwise.
         ImageKind::XipPlainSigned
     } else {
         // Note: ec-slimloader loads the application to RAM, but skboot_authenticate requires the image to be marked for XIP.
@@ -312,6 +376,20 @@ fn load_image(
     Ok(image)
 }
 
+/// Length of the image once signed, including the HMAC, cert block and signature
+///
+/// The signature length follows from the key of the leaf certificate in the cert block, such that this is known
+/// before the image is signed.
+pub fn signed_len(
+    input_path: impl AsRef<Path>,
+    base_addr: u32,
+    is_bootloader: bool,
+    cert_block: CertBlock,
+) -> anyhow::Result<usize> {
+    let image = load_image(&input_path, base_addr, is_bootloader, cert_block)?;
+    Ok(image.header.image_length())
+}
+
 /// Produce an image that can be signed by an external source
 pub fn prepare_to_sign(
     input_path: impl AsRef<Path>,
@@ -400,80 +478,87 @@ pub fn generate_pure(
     Ok(())
 }
 
+/// Configuration of `nxpimage mbi export`, templated from the [Config](crate::Config) rather than kept as YAML file.
+///
+/// Written as JSON, which SPSDK reads as it is a subset of YAML. Paths are absolute,
+/// as SPSDK resolves relative paths against the directory of the configuration file.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct NxpMbiConfig {
+    family: String,
+    revision: String,
+    output_image_execution_target: &'static str,
+    output_image_authentication_type: &'static str,
+    master_boot_output_file: PathBuf,
+    input_image_file: PathBuf,
+    output_image_execution_address: u32,
+    enable_hw_user_mode_keys: bool,
+    enable_trust_zone: bool,
+    cert_block: PathBuf,
+    signer: String,
+}
+
 /// Generate a MBI using the original NXP SPSDK tooling.
+///
+/// Options not set from the arguments can be added or replaced using [MbiArgs::overrides].
 pub fn generate_nxp(
     nxpimage: impl AsRef<Path>,
     input_path: impl AsRef<Path>,
     base_addr: u32,
     output_path: impl AsRef<Path>,
-    is_bootloader: bool,
     cert_block: CertBlockConfig,
+    private_key_path: impl AsRef<Path>,
+    mbi: &MbiArgs,
 ) -> anyhow::Result<()> {
-    let mut config: BTreeMap<String, String> = BTreeMap::default();
-
-    let mut cert_block_file = NamedTempFile::new()?;
-    serde_json::to_writer(&mut cert_block_file, &cert_block)?;
-
-    config.insert(
-        "certBlock".to_owned(),
-        cert_block_file
-            .path()
-            .to_str()
-            .ok_or_else(|| anyhow!("Path not a string"))?
-            .to_owned(),
-    );
-
-    config.insert("outputImageExecutionAddress".to_owned(), format!("{base_addr:#x}"));
-
-    config.insert(
-        "inputImageFile".to_owned(),
-        input_path
-            .as_ref()
-            .to_str()
-            .ok_or_else(|| anyhow!("Path not a string"))?
-            .into(),
-    );
-
-    let output_path_abs = std::env::current_dir()?.join(output_path.as_ref());
-    config.insert(
-        "masterBootOutputFile".to_owned(),
-        output_path_abs
-            .to_str()
-            .ok_or_else(|| anyhow!("Path not a string"))?
-            .into(),
-    );
+    let cert_block_file = temp_file(".json", &serde_json::to_vec(&cert_block)?)?;
+
+    let signer = std::path::absolute(private_key_path.as_ref())?;
+    let config = NxpMbiConfig {
+        family: mbi.family.clone(),
+        revision: mbi.revision.clone(),
+        output_image_execution_target: "xip",
+        output_image_authentication_type: "signed",
+        master_boot_output_file: std::path::absolute(output_path.as_ref())?,
+        input_image_file: std::path::absolute(input_path.as_ref())?,
+        output_image_execution_address: base_addr,
+        enable_hw_user_mode_keys: false,
+        enable_trust_zone: true,
+        cert_block: cert_block_file.to_path_buf(),
+        signer: format!(
+            "type=file;file_path={}",
+            signer.to_str().ok_or_else(|| anyhow!("Path not a string"))?
+        ),
+    };
 
     log::debug!("Config: {config:#?}");
 
-    let mut command = Command::new(nxpimage.as_ref());
-    command.current_dir("./artifacts");
-
-    let mbi_conf_path = if is_bootloader {
-        "mbi-bootloader.yaml"
-    } else {
-        "mbi-application.yaml"
-    };
+    let mbi_config_file = temp_file(".yaml", &serde_json::to_vec_pretty(&config)?)?;
 
-    command.args(["mbi", "export", "-c", mbi_conf_path]);
+    let mut command = Command::new(nxpimage.as_ref());
+    command.args(["mbi", "export", "-c"]);
+    command.arg(&mbi_config_file);
 
-    for (k, v) in config {
-        command.args(["-oc", &format!("{k}={v}")]);
+    for option in mbi.override_options() {
+        command.args(["-oc", &option]);
     }
 
-    eprintln!("{:?}", command);
+    log::debug!("Executing {command:?}");
 
     let output = command
         .stdin(Stdio::inherit())
         .stdout(Stdio::piped())
         .stderr(Stdio::inherit())
         .output()
-        .with_context(|| format!("Could not execute command {command:?}"))?;
+        .with_context(|| format!("Could not execute command {command:?}"))
+        .classify(ErrorKind::ExternalTool)?;
 
     if !output.status.success() {
-        return Err(
-            anyhow::anyhow!(format!("Failed to build MBI image from {}", mbi_conf_path))
-                .context(String::from_utf8(output.stdout)?),
-        );
+        return Err(anyhow::anyhow!(format!(
+            "Failed to build MBI image from {}",
+            input_path.as_ref().display()
+        ))
+        .context(String::from_utf8(output.stdout)?))
+        .classify(ErrorKind::ExternalTool);
     }
 
     let input = std::fs::read(&input_path)?;
@@ -490,10 +575,11 @@ pub fn generate_nxp(
     }
 
     // Performing checks on output image
-    #[allow(clippy::if_same_then_else)]
-    let expected_image_type = if is_bootloader { 0x0004u32 } else { 0x0004u32 };
+    let expected_image_type = 0x0004u32;
 
-    let image_type = u32::from_le_bytes((&output[0x24..0x28]).try_into().unwrap());
+    let image_type = Ivt::parse(&output)
+        .map_err(|_| anyhow::anyhow!("Output image too small"))?
+        .image_type;
     if image_type != expected_image_type {
         return Err(anyhow::anyhow!(
             "Failed to generate expected image type 0x{:x}, got 0x{:x}",
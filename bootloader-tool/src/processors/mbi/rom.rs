@@ -0,0 +1,264 @@
+use std::fmt;
+
+use mbi_format::{CertBlockHeader, ImageKind, Ivt};
+use rsa::RsaPublicKey;
+use rsa::pkcs1v15::{Signature, VerifyingKey};
+use rsa::pkcs8::DecodePublicKey;
+use rsa::signature::Verifier;
+use rsa::traits::PublicKeyParts;
+use sha2::{Digest, Sha256};
+use x509_parser::asn1_rs::FromDer;
+use x509_parser::certificate::X509Certificate;
+use x509_parser::oid_registry::OID_PKCS1_SHA256WITHRSA;
+
+use crate::processors::certificates::Rkth;
+
+/// Magic at the start of the cert block
+const CERT_BLOCK_SIGNATURE: u32 = u32::from_le_bytes(*b"cert");
+/// Number of root key hashes in the root key table
+const ROOT_KEY_COUNT: usize = 4;
+/// RSA key sizes in bits supported by the ROM
+const KEY_SIZES: [usize; 2] = [2048, 4096];
+
+/// Reason for which the ROM would reject an image
+#[derive(Debug, Clone, PartialEq)]
+pub enum Rejection {
+    /// The image is too small to contain a header
+    TooSmall,
+    /// The image type is not one of the signed image types
+    NotSigned(u32),
+    /// The image length in the header does not fit the image
+    ImageLength { header: usize, actual: usize },
+    /// The cert block offset in the header does not point into the image
+    CertBlockOffset(usize),
+    /// The cert block header is malformed
+    CertBlockHeader(&'static str),
+    /// The signed length in the cert block does not end at the end of the cert block
+    SignedLength { cert_block: usize, expected: usize },
+    /// A certificate can not be parsed or is not signed with RSA and SHA-256
+    Certificate { index: usize, reason: String },
+    /// A certificate holds a key of an unsupported size
+    KeySize { index: usize, bits: usize },
+    /// The root key table does not hash to the expected RKTH
+    Rkth(Rkth),
+    /// The key of the root certificate is not in the root key table
+    RootKeyHash,
+    /// A certificate is not signed by its predecessor in the chain
+    ChainSignature { index: usize },
+    /// The signature does not have the length of the key of the leaf certificate
+    SignatureLength { expected: usize, actual: usize },
+    /// The signature does not match the signed part of the image
+    Signature,
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rejection::TooSmall => write!(f, "image is too small to contain a header"),
+            Rejection::NotSigned(image_type) => write!(f, "image type {image_type:#x} is not a signed image type"),
+            Rejection::ImageLength { header, actual } => {
+                write!(
+                    f,
+                    "image length in header {header:#x} does not fit image of {actual:#x} bytes"
+                )
+            }
+            Rejection::CertBlockOffset(offset) => write!(f, "cert block offset {offset:#x} out of range"),
+            Rejection::CertBlockHeader(reason) => write!(f, "cert block header {reason}"),
+            Rejection::SignedLength { cert_block, expected } => write!(
+                f,
+                "signed length in cert block {cert_block:#x} does not end at the cert block ({expected:#x})"
+            ),
+            Rejection::Certificate { index, reason } => write!(f, "certificate {index}: {reason}"),
+            Rejection::KeySize { index, bits } => write!(f, "certificate {index}: unsupported RSA-{bits} key"),
+            Rejection::Rkth(rkth) => write!(f, "root key table hashes to RKTH {}", rkth.as_hex()),
+            Rejection::RootKeyHash => write!(f, "root certificate key is not in the root key table"),
+            Rejection::ChainSignature { index } => {
+                write!(f, "certificate {index} is not signed by certificate {}", index - 1)
+            }
+            Rejection::SignatureLength { expected, actual } => {
+                write!(f, "signature of {actual} bytes, expected {expected} bytes")
+            }
+            Rejection::Signature => write!(f, "signature does not match image"),
+        }
+    }
+}
+
+/// Properties of an image that the ROM would accept
+#[derive(Debug, Clone, PartialEq)]
+pub struct Authenticated {
+    pub image_kind: ImageKind,
+    /// Slot of the root key table holding the hash of the root certificate key
+    pub root_key_slot: usize,
+    pub certificate_count: usize,
+    pub build_number: u32,
+    /// Size of the key of the leaf certificate in bits
+    pub key_bits: usize,
+}
+
+impl fmt::Display for Authenticated {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} image, build number {}, {} certificate(s) from root key slot {}, signed with RSA-{}",
+            self.image_kind, self.build_number, self.certificate_count, self.root_key_slot, self.key_bits
+        )
+    }
+}
+
+fn certificate_error(index: usize, reason: impl ToString) -> Rejection {
+    Rejection::Certificate {
+        index,
+        reason: reason.to_string(),
+    }
+}
+
+/// Authenticate a signed image like the ROM does, against the RKTH fused into the device
+///
+/// Walks the image in the order of the ROM: header, cert block header, root key table, certificate chain and finally
+/// the signature. This deliberately does not reuse [SignedImage](super::SignedImage) nor
+/// [CertBlock](super::cert_block::CertBlock), such that it is an independent check of the images they produce.
+pub fn authenticate(image: &[u8], rkth: &Rkth) -> Result<Authenticated, Rejection> {
+    let header = Ivt::parse(image).map_err(|_| Rejection::TooSmall)?;
+    let image_kind = match header.image_kind() {
+        Some(kind @ (ImageKind::PlainSigned | ImageKind::EncryptedSigned | ImageKind::XipPlainSigned)) => kind,
+        _ => return Err(Rejection::NotSigned(header.image_type)),
+    };
+
+    // Anything following the image, like padding up to the end of the slot, is ignored
+    let image_len = header.image_len as usize;
+    if image_len < Ivt::LEN || image_len > image.len() {
+        return Err(Rejection::ImageLength {
+            header: image_len,
+            actual: image.len(),
+        });
+    }
+    let image = &image[..image_len];
+
+    // The header HMAC directly follows the header, but is not part of the signed data nor the header offset
+    let hmac_len = if image_kind.has_hmac() {
+        Sha256::output_size()
+    } else {
+        0
+    };
+    let header_offset = header.header_offset as usize;
+    let cert_block_offset = header_offset + hmac_len;
+    if header_offset < Ivt::LEN
+        || !header_offset.is_multiple_of(4)
+        || cert_block_offset + CertBlockHeader::LEN > image_len
+    {
+        return Err(Rejection::CertBlockOffset(header_offset));
+    }
+
+    let cert_block = &image[cert_block_offset..];
+    let cert_header = CertBlockHeader::parse(cert_block).expect("length checked above");
+    if cert_header.signature != CERT_BLOCK_SIGNATURE {
+        return Err(Rejection::CertBlockHeader("does not start with \"cert\""));
+    }
+    if cert_header.header_major_version != 1 {
+        return Err(Rejection::CertBlockHeader("has an unsupported major version"));
+    }
+    if cert_header.header_length as usize != CertBlockHeader::LEN {
+        return Err(Rejection::CertBlockHeader("has an unexpected length"));
+    }
+    if cert_header.certificate_count == 0 {
+        return Err(Rejection::CertBlockHeader("lists no certificates"));
+    }
+
+    let root_key_hashes_offset = cert_header.root_key_hashes_offset();
+    let cert_block_len = root_key_hashes_offset + ROOT_KEY_COUNT * Sha256::output_size();
+    if cert_block_len > cert_block.len() {
+        return Err(Rejection::CertBlockHeader("exceeds the image"));
+    }
+    let signed_len = header_offset + cert_block_len;
+    if cert_header.total_image_length as usize != signed_len {
+        return Err(Rejection::SignedLength {
+            cert_block: cert_header.total_image_length as usize,
+            expected: signed_len,
+        });
+    }
+
+    let root_key_table = &cert_block[root_key_hashes_offset..cert_block_len];
+    let actual_rkth = Rkth(Sha256::digest(root_key_table).into());
+    if &actual_rkth != rkth {
+        return Err(Rejection::Rkth(actual_rkth));
+    }
+
+    let mut table = &cert_block[CertBlockHeader::LEN..root_key_hashes_offset];
+    let mut certificates = vec![];
+    for index in 0..cert_header.certificate_count as usize {
+        let Some((len, rest)) = table.split_at_checked(4) else {
+            return Err(certificate_error(index, "length exceeds the certificate table"));
+        };
+        let len = u32::from_le_bytes(len.try_into().unwrap()) as usize;
+        let Some((der, rest)) = rest.split_at_checked(len) else {
+            return Err(certificate_error(index, "exceeds the certificate table"));
+        };
+        table = rest;
+
+        let (_, certificate) = X509Certificate::from_der(der).map_err(|e| certificate_error(index, e))?;
+        if certificate.signature_algorithm.algorithm != OID_PKCS1_SHA256WITHRSA {
+            return Err(certificate_error(index, "not signed with RSA and SHA-256"));
+        }
+        certificates.push(certificate);
+    }
+    if !table.is_empty() {
+        return Err(Rejection::CertBlockHeader(
+            "certificate table length does not match its certificates",
+        ));
+    }
+
+    let keys = certificates
+        .iter()
+        .enumerate()
+        .map(|(index, certificate)| {
+            let key = RsaPublicKey::from_public_key_der(certificate.public_key().raw)
+                .map_err(|e| certificate_error(index, e))?;
+            let bits = key.size() * 8;
+            if !KEY_SIZES.contains(&bits) {
+                return Err(Rejection::KeySize { index, bits });
+            }
+            Ok(key)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // The root key hash covers the big-endian modulus and exponent without leading zeroes
+    let root_key = &keys[0];
+    let root_key_hash = Sha256::new()
+        .chain_update(root_key.n().to_bytes_be())
+        .chain_update(root_key.e().to_bytes_be())
+        .finalize();
+    let root_key_slot = root_key_table
+        .chunks_exact(Sha256::output_size())
+        .position(|hash| hash == root_key_hash.as_slice())
+        .ok_or(Rejection::RootKeyHash)?;
+
+    for (index, pair) in certificates.windows(2).enumerate() {
+        pair[1]
+            .verify_signature(Some(pair[0].public_key()))
+            .map_err(|_| Rejection::ChainSignature { index: index + 1 })?;
+    }
+
+    let leaf_key = keys.last().expect("at least one certificate");
+    let signature = &image[cert_block_offset + cert_block_len..];
+    if signature.len() != leaf_key.size() {
+        return Err(Rejection::SignatureLength {
+            expected: leaf_key.size(),
+            actual: signature.len(),
+        });
+    }
+
+    let mut signed = image[..Ivt::LEN].to_vec();
+    signed.extend(&image[Ivt::LEN + hmac_len..cert_block_offset + cert_block_len]);
+    let signature = Signature::try_from(signature).map_err(|_| Rejection::Signature)?;
+    VerifyingKey::<Sha256>::new(leaf_key.clone())
+        .verify(&signed, &signature)
+        .map_err(|_| Rejection::Signature)?;
+
+    Ok(Authenticated {
+        image_kind,
+        root_key_slot,
+        certificate_count: certificates.len(),
+        build_number: cert_header.build_number,
+        key_bits: leaf_key.size() * 8,
+    })
+}
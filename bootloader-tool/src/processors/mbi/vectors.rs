@@ -0,0 +1,100 @@
+use std::fmt;
+
+use anyhow::bail;
+use clap::ValueEnum;
+use mbi_format::{CertBlockHeader, Ivt};
+
+use crate::processors::mbi::headers::Headers;
+
+/// Bit of the image type disabling TrustZone, see [ImageType::as_u32](mbi_format::ImageType::as_u32)
+const TRUSTZONE_DISABLED_BIT: u32 = 1 << 14;
+
+/// Deliberate corruption of a signed image, which the bootloader must refuse with [Vector::expected]
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Vector {
+    /// Flip a bit in the signature
+    BadSignature,
+    /// Cut the image off right after the cert block header, omitting the root key table and signature
+    TruncatedCertBlock,
+    /// Replace the root key table, such that neither the RKTH nor the root key match
+    WrongRkth,
+    /// Claim an image length far exceeding any slot
+    OversizedLength,
+    /// Flip the TrustZone bit of the image type
+    FlippedImageType,
+}
+
+impl Vector {
+    /// Every vector, in the order they are generated
+    pub const ALL: [Vector; 5] = [
+        Vector::BadSignature,
+        Vector::TruncatedCertBlock,
+        Vector::WrongRkth,
+        Vector::OversizedLength,
+        Vector::FlippedImageType,
+    ];
+
+    /// Variant of `BootError` of `ec-slimloader` the bootloader refuses the image with
+    pub fn expected(&self) -> &'static str {
+        match self {
+            Vector::BadSignature | Vector::WrongRkth => "Authenticate",
+            Vector::TruncatedCertBlock | Vector::OversizedLength => "TooLarge",
+            Vector::FlippedImageType => "Markers",
+        }
+    }
+
+    /// Corrupt the signed `image`
+    ///
+    /// Only the parts of the image involved in the corruption are checked to be present, the image is not verified.
+    pub fn apply(&self, image: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let headers = Headers::decode(image)?;
+        let image_len = headers.ivt.image_len as usize;
+        if image_len > image.len() {
+            bail!(
+                "Image length in header {image_len:#x} exceeds file length {:#x}",
+                image.len()
+            );
+        }
+        let (Some(cert_block), Some(_), Some(signature_len @ 1..)) =
+            (headers.cert_block, headers.root_key_hashes, headers.signature_len)
+        else {
+            bail!("Image is not signed, or its cert block does not fit\n{headers}");
+        };
+        if headers.cert_block_offset < Ivt::LEN {
+            bail!(
+                "Cert block at {:#x} overlaps the image header",
+                headers.cert_block_offset
+            );
+        }
+
+        let mut corrupted = image[..image_len].to_vec();
+        let mut ivt = headers.ivt;
+        match self {
+            Vector::BadSignature => {
+                let signature = image_len - signature_len;
+                corrupted[signature] ^= 1;
+            }
+            Vector::TruncatedCertBlock => {
+                let truncated_len = headers.cert_block_offset + CertBlockHeader::LEN;
+                corrupted.truncate(truncated_len);
+                ivt.image_len = truncated_len as u32;
+            }
+            Vector::WrongRkth => {
+                let root_key_hashes = headers.cert_block_offset + cert_block.root_key_hashes_offset();
+                corrupted[root_key_hashes..image_len - signature_len].fill(0);
+            }
+            Vector::OversizedLength => ivt.image_len = u32::MAX,
+            Vector::FlippedImageType => ivt.image_type ^= TRUSTZONE_DISABLED_BIT,
+        }
+        ivt.write(&mut corrupted).expect("cert block follows the header");
+
+        Ok(corrupted)
+    }
+}
+
+impl fmt::Display for Vector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = self.to_possible_value().expect("no skipped variants");
+        write!(f, "{}", name.get_name())
+    }
+}
@@ -1,5 +1,23 @@
+pub mod artifacts;
+pub mod audit;
+pub mod boot_cfg;
 pub mod certificates;
+pub mod clean;
+pub mod debug;
+pub mod device;
+pub mod fcb;
+pub mod fuse;
+pub mod layout;
+pub mod manifest;
 pub mod mbi;
+pub mod monitor;
 pub mod objcopy;
 pub mod otp;
+pub mod pipeline;
+pub mod plan;
 pub mod probe;
+pub mod provision;
+pub mod rtt;
+pub mod sign_cache;
+pub mod slot;
+pub mod state;
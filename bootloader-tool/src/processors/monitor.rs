@@ -0,0 +1,114 @@
+use std::fmt;
+
+use anyhow::bail;
+use ec_slimloader_state::counters::{Counters, SLOT_COUNT};
+use ec_slimloader_state::state::{Slot, State};
+use probe_rs::{Core, MemoryInterface};
+
+use crate::config::Config;
+use crate::processors::{device, state};
+
+/// Boot status of a device, as decoded from its state and counters journals
+#[derive(Debug, Clone, PartialEq)]
+pub struct Status {
+    /// Latest state in the state journal, [None] if the journal is empty or corrupt
+    pub state: Option<State>,
+    /// Latest counters in the counters journal, [None] if it is not configured, empty or corrupt
+    pub counters: Option<Counters>,
+}
+
+/// Read the state journal configured as `bootloader.state`, and the counters journal if configured as `bootloader.counters`
+pub fn read(core: &mut Core, config: &Config) -> anyhow::Result<Status> {
+    let Some(bootloader) = &config.bootloader else {
+        bail!("Bootloader not defined in configuration file");
+    };
+
+    let state = device::read_state(core, config)?;
+    let counters = match &bootloader.counters {
+        Some(range) => {
+            let mut journal = vec![0u8; range.size as usize];
+            core.read(range.start, &mut journal)?;
+            state::latest_record(&journal)
+        }
+        None => None,
+    };
+
+    Ok(Status { state, counters })
+}
+
+/// Authentication failures counted for each slot that had any
+fn auth_failures(counters: &Counters) -> impl Iterator<Item = (Slot, u16)> + '_ {
+    (0..SLOT_COUNT as u8)
+        .filter_map(|slot| Slot::try_from(slot).ok())
+        .map(|slot| (slot, counters.auth_failures(slot)))
+        .filter(|(_, count)| *count > 0)
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.state {
+            Some(state) => write!(
+                f,
+                "{:?}, target {}, backup {}",
+                state.status(),
+                state.target(),
+                state.backup()
+            )?,
+            None => write!(f, "journal empty or corrupt")?,
+        }
+
+        if let Some(counters) = &self.counters {
+            write!(f, "; boots {}, fallbacks {}", counters.boots(), counters.fallbacks())?;
+            for (slot, count) in auth_failures(counters) {
+                write!(f, ", auth failures {slot} {count}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// What happened on the device between reading `previous` and `current`, as far as can be told from the journals
+///
+/// Counters that went down mean that the counters journal was reset, which is reported rather than a negative count.
+pub fn events(previous: &Status, current: &Status) -> Vec<String> {
+    let mut events = vec![];
+
+    match (previous.state, current.state) {
+        (Some(previous), Some(current)) if previous != current => {
+            if previous.target() != current.target() || previous.backup() != current.backup() {
+                events.push(format!(
+                    "new target {} with backup {}",
+                    current.target(),
+                    current.backup()
+                ));
+            }
+            if previous.status() != current.status() {
+                events.push(format!("{:?} -> {:?}", previous.status(), current.status()));
+            }
+        }
+        (Some(_), None) => events.push("state journal erased".to_string()),
+        (None, Some(_)) => events.push("state journal written".to_string()),
+        _ => {}
+    }
+
+    if let (Some(previous), Some(current)) = (&previous.counters, &current.counters) {
+        let counted = |name: &str, previous: u16, current: u16| match current.checked_sub(previous) {
+            Some(0) => None,
+            Some(1) => Some(name.to_string()),
+            Some(count) => Some(format!("{count}x {name}")),
+            None => Some(format!("{name} counter reset")),
+        };
+
+        events.extend(counted("boot", previous.boots(), current.boots()));
+        events.extend(counted("fallback", previous.fallbacks(), current.fallbacks()));
+        for slot in (0..SLOT_COUNT as u8).filter_map(|slot| Slot::try_from(slot).ok()) {
+            events.extend(counted(
+                &format!("auth failure in {slot}"),
+                previous.auth_failures(slot),
+                current.auth_failures(slot),
+            ));
+        }
+    }
+
+    events
+}
@@ -3,13 +3,34 @@ use std::ops::Range;
 use anyhow::Context;
 use object::elf::{SHT_NOBITS, SHT_PROGBITS};
 use object::read::elf::{ElfFile32, ProgramHeader};
-use object::{Object, ObjectSegment};
+use object::{Object, ObjectSection, ObjectSegment, ObjectSymbol, SectionKind};
 
 const PRELUDE_ADDRESS_RANGE: Range<u32> = 0x08000000..0x08001000;
 
-pub fn objcopy(file: &ElfFile32) -> anyhow::Result<(Vec<u8>, u32)> {
+/// The 4.5 MiB of SRAM, through its non-secure and secure aliases on the code and the data bus
+const RAM_RANGES: [Range<u32>; 4] = [
+    0x00000000..0x00480000,
+    0x10000000..0x10480000,
+    0x20000000..0x20480000,
+    0x30000000..0x30480000,
+];
+
+/// VTOR ignores the lowest 7 bits, so the vector table must be aligned to at least 128 bytes
+const VECTOR_TABLE_ALIGNMENT: u32 = 128;
+
+/// Default for the largest gap between segments that is filled when assembling an image, see [objcopy]
+pub const DEFAULT_MAX_GAP: u32 = 0x10000;
+
+/// Value gaps between segments are filled with, such that they are left as erased flash
+const GAP_FILL: u8 = 0xFF;
+
+/// Assemble the BIN image of all loadable segments, yielding the image and its base address
+///
+/// Segments need not be contiguous: gaps between them, such as in front of a config block at a fixed offset, are
+/// filled with 0xFF. Gaps larger than `max_gap` are rejected, as those likely stem from a segment linked to the wrong
+/// memory region and would silently blow up the image.
+pub fn objcopy(file: &ElfFile32, max_gap: u32) -> anyhow::Result<(Vec<u8>, u32)> {
     // Sanity checks
-    let mut last_paddr = 0;
     let mut segments = vec![];
     for segment in file.segments() {
         let filesz = segment.elf_program_header().p_filesz(file.endianness());
@@ -31,16 +52,40 @@ pub fn objcopy(file: &ElfFile32) -> anyhow::Result<(Vec<u8>, u32)> {
         if PRELUDE_ADDRESS_RANGE.contains(&paddr) {
             continue;
         }
-        if paddr < last_paddr {
-            return Err(anyhow::anyhow!(
-                "Segments not in order of physical address or overlapping segments"
-            ));
-        }
-        last_paddr = paddr + segment.elf_program_header().p_memsz(file.endianness());
 
         segments.push(segment);
     }
 
+    if segments.is_empty() {
+        return Err(anyhow::anyhow!("No loadable segments outside of the prelude"));
+    }
+
+    // Program headers need not be in order of physical address.
+    segments.sort_by_key(|segment| segment.elf_program_header().p_paddr(file.endianness()));
+
+    let mut last_paddr = None;
+    for segment in &segments {
+        let paddr = segment.elf_program_header().p_paddr(file.endianness());
+        let filesz = segment.elf_program_header().p_filesz(file.endianness());
+        if let Some(last_paddr) = last_paddr {
+            if paddr < last_paddr {
+                return Err(anyhow::anyhow!(
+                    "Segment at 0x{paddr:x} overlaps with the previous segment ending at 0x{last_paddr:x}"
+                ));
+            }
+            let gap = paddr - last_paddr;
+            if gap > max_gap {
+                return Err(anyhow::anyhow!(
+                    "Gap of 0x{gap:x} bytes between 0x{last_paddr:x} and the segment at 0x{paddr:x} exceeds the maximum gap of 0x{max_gap:x} bytes"
+                ));
+            }
+            if gap > 0 {
+                log::debug!("Filling gap of 0x{gap:x} bytes between 0x{last_paddr:x} and 0x{paddr:x}");
+            }
+        }
+        last_paddr = Some(paddr + filesz);
+    }
+
     let base_addr = segments
         .iter()
         .map(|segment| segment.elf_program_header().p_paddr.get(file.endianness()))
@@ -69,13 +114,14 @@ pub fn objcopy(file: &ElfFile32) -> anyhow::Result<(Vec<u8>, u32)> {
             expected_entry
         )));
     }
-
-    // TODO check VTOR
-    // TODO check image size
-    // TODO check execution address
+    if expected_entry >= top_addr as u64 {
+        return Err(anyhow::anyhow!(
+            "Image entrypoint 0x{expected_entry:x} not within the image at 0x{base_addr:x}..0x{top_addr:x}"
+        ));
+    }
 
     // Assemble BIN image by copying all segments directly
-    let mut image = vec![0; output_size as usize];
+    let mut image = vec![GAP_FILL; output_size as usize];
     for segment in segments {
         let paddr = segment.elf_program_header().p_paddr(file.endianness());
 
@@ -83,9 +129,65 @@ pub fn objcopy(file: &ElfFile32) -> anyhow::Result<(Vec<u8>, u32)> {
             .copy_from_slice(segment.data().unwrap());
     }
 
+    check_vector_table(&image, base_addr)?;
+
     Ok((image, base_addr))
 }
 
+/// Check the vector table at the start of the image, as the image would otherwise fault before it can log anything
+///
+/// The table must be aligned for VTOR, the initial stack pointer must lie within RAM, and the reset vector must point
+/// to Thumb code within the image.
+fn check_vector_table(image: &[u8], base_addr: u32) -> anyhow::Result<()> {
+    if !base_addr.is_multiple_of(VECTOR_TABLE_ALIGNMENT) {
+        return Err(anyhow::anyhow!(
+            "Vector table at 0x{base_addr:x} not aligned to {VECTOR_TABLE_ALIGNMENT} bytes, as required by VTOR"
+        ));
+    }
+
+    let word = |i: usize| u32::from_le_bytes(image[i * 4..i * 4 + 4].try_into().unwrap());
+    let top_addr = base_addr + image.len() as u32;
+
+    // The stack grows down from the initial stack pointer, which may thus be the end of RAM.
+    let stack_pointer = word(0);
+    if !RAM_RANGES
+        .iter()
+        .any(|ram| ram.start < stack_pointer && stack_pointer <= ram.end)
+    {
+        return Err(anyhow::anyhow!(
+            "Initial stack pointer 0x{stack_pointer:x} not within RAM"
+        ));
+    }
+    if !stack_pointer.is_multiple_of(8) {
+        return Err(anyhow::anyhow!(
+            "Initial stack pointer 0x{stack_pointer:x} not aligned to 8 bytes"
+        ));
+    }
+
+    let reset_vector = word(1);
+    if reset_vector & 1 == 0 {
+        return Err(anyhow::anyhow!(
+            "Reset vector 0x{reset_vector:x} does not point to Thumb code"
+        ));
+    }
+    if !(base_addr..top_addr).contains(&(reset_vector & !1)) {
+        return Err(anyhow::anyhow!(
+            "Reset vector 0x{reset_vector:x} not within the image at 0x{base_addr:x}..0x{top_addr:x}"
+        ));
+    }
+
+    log::debug!("Initial stack pointer: 0x{stack_pointer:x}");
+    log::debug!("Reset vector: 0x{reset_vector:x}");
+    Ok(())
+}
+
+/// Whether `range` lies entirely within one of the aliases of RAM
+pub fn within_ram(range: Range<u32>) -> bool {
+    RAM_RANGES
+        .iter()
+        .any(|ram| ram.start <= range.start && range.end <= ram.end)
+}
+
 pub fn remove_non_prelude(data: &[u8]) -> anyhow::Result<Vec<u8>> {
     let mut builder = object::build::elf::Builder::read32(data).context("Could not parse ELF")?;
 
@@ -109,3 +211,32 @@ pub fn remove_non_prelude(data: &[u8]) -> anyhow::Result<Vec<u8>> {
     builder.write(&mut out)?;
     Ok(out)
 }
+
+/// The `count` largest symbols that end up in the image, largest first, like `cargo bloat` reports
+///
+/// Names are demangled. Symbols in the prelude and in bss are left out, as they do not count towards the image size.
+pub fn largest_symbols(file: &ElfFile32, count: usize) -> Vec<(String, u64)> {
+    let mut symbols: Vec<(String, u64)> = file
+        .symbols()
+        .filter(|symbol| symbol.size() > 0 && !PRELUDE_ADDRESS_RANGE.contains(&(symbol.address() as u32)))
+        .filter(|symbol| {
+            symbol
+                .section_index()
+                .and_then(|index| file.section_by_index(index).ok())
+                .is_some_and(|section| {
+                    matches!(
+                        section.kind(),
+                        SectionKind::Text | SectionKind::Data | SectionKind::ReadOnlyData | SectionKind::ReadOnlyString
+                    )
+                })
+        })
+        .filter_map(|symbol| {
+            let name = symbol.name().ok()?;
+            Some((rustc_demangle::demangle(name).to_string(), symbol.size()))
+        })
+        .collect();
+
+    symbols.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    symbols.truncate(count);
+    symbols
+}
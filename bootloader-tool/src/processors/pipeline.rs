@@ -0,0 +1,38 @@
+use std::num::NonZeroUsize;
+use std::sync::Mutex;
+
+/// Number of jobs to run at once, which is `jobs` if given or else the number of CPUs
+pub fn parallelism(jobs: Option<NonZeroUsize>) -> NonZeroUsize {
+    jobs.or_else(|| std::thread::available_parallelism().ok())
+        .unwrap_or(NonZeroUsize::MIN)
+}
+
+/// Run `f` on each of `inputs`, on at most `parallelism` threads at once, yielding the results in the order of `inputs`
+///
+/// Meant for jobs invoking external tools such as nxpimage, which mostly wait on those tools to finish.
+pub fn run<T: Send, R: Send>(inputs: Vec<T>, parallelism: NonZeroUsize, f: impl Fn(T) -> R + Sync) -> Vec<R> {
+    let count = inputs.len();
+    let queue = Mutex::new(inputs.into_iter().enumerate());
+    let results = Mutex::new((0..count).map(|_| None).collect::<Vec<Option<R>>>());
+
+    std::thread::scope(|scope| {
+        for _ in 0..parallelism.get().min(count) {
+            scope.spawn(|| {
+                loop {
+                    let Some((i, input)) = queue.lock().unwrap().next() else {
+                        break;
+                    };
+                    let result = f(input);
+                    results.lock().unwrap()[i] = Some(result);
+                }
+            });
+        }
+    });
+
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|result| result.expect("every job has run"))
+        .collect()
+}
@@ -0,0 +1,112 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use object::Object;
+use object::read::elf::{ElfFile32, ProgramHeader};
+
+use crate::processors::fuse::{self, Word};
+
+/// Change to the device that a command performs, as printed instead with `--dry-run`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operation {
+    /// Program `len` bytes of flash starting at `address`, erasing the sectors they cover
+    Flash { address: u64, len: u64, source: String },
+    /// Write `values` to consecutive shadow registers, starting at the one of `word`
+    Write { word: Word, values: Vec<u32> },
+    /// Write `len` consecutive shadow registers starting at the one of `word`, with values not to be shown
+    WriteSecret { word: Word, len: usize },
+    /// Clear the bits in `mask` in the shadow register of `word`, leaving the others as read from the device
+    Clear { word: Word, mask: u32 },
+    /// Authenticate as debugger using the debug credential at `credential`
+    Authenticate { credential: PathBuf },
+    /// Reset the core
+    Reset,
+}
+
+impl Operation {
+    /// Write of `values` to the shadow registers starting at the one of fuse word `index`, named `name`
+    pub fn write(index: u32, name: &'static str, values: &[u32]) -> Self {
+        Operation::Write {
+            word: Word { index, name },
+            values: values.to_vec(),
+        }
+    }
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operation::Flash { address, len, source } => write!(
+                f,
+                "flash {address:#010x}..{:#010x} ({len:#x} bytes) from {source}",
+                address + len
+            ),
+            Operation::Write { word, values } => {
+                write!(f, "write {word}:")?;
+                for value in values {
+                    write!(f, " {value:#010x}")?;
+                }
+                Ok(())
+            }
+            Operation::WriteSecret { word, len } => write!(f, "write {word}: {len} words, not shown"),
+            Operation::Clear { word, mask } => write!(f, "clear {mask:#010x} in {word}"),
+            Operation::Authenticate { credential } => {
+                write!(f, "authenticate with debug credential {}", credential.display())
+            }
+            Operation::Reset => write!(f, "reset"),
+        }
+    }
+}
+
+/// Flash writes of the loadable segments of the ELF file at `path`, as done when downloading it
+pub fn flash_elf(path: &Path) -> anyhow::Result<Vec<Operation>> {
+    let data = std::fs::read(path).with_context(|| format!("Could not read {}", path.display()))?;
+    let file = ElfFile32::parse(&*data).with_context(|| format!("Could not parse {}", path.display()))?;
+
+    Ok(file
+        .segments()
+        .map(|segment| segment.elf_program_header())
+        .filter(|header| header.p_filesz(file.endianness()) > 0)
+        .map(|header| Operation::Flash {
+            address: header.p_paddr(file.endianness()) as u64,
+            len: header.p_filesz(file.endianness()) as u64,
+            source: path.display().to_string(),
+        })
+        .collect())
+}
+
+/// Flash write of the file at `path` to `address`, as done when downloading a binary
+pub fn flash_bin(path: &Path, address: u64) -> anyhow::Result<Operation> {
+    let len = std::fs::metadata(path)
+        .with_context(|| format!("Could not read {}", path.display()))?
+        .len();
+    Ok(Operation::Flash {
+        address,
+        len,
+        source: path.display().to_string(),
+    })
+}
+
+/// Shadow register writes that `run` performs to boot the image signed for `rkth`
+pub fn run_shadow_writes(rkth: &[u32], boot_cfg0: u32, boot_cfg1: u32) -> Vec<Operation> {
+    vec![
+        Operation::write(fuse::RKTH, "RKTH", rkth),
+        Operation::WriteSecret {
+            word: Word {
+                index: fuse::OTP_MASTER_KEY,
+                name: "OTP_MASTER_KEY",
+            },
+            len: 8,
+        },
+        Operation::write(fuse::BOOT_CFG0, "BOOT_CFG0", &[boot_cfg0]),
+        Operation::write(fuse::BOOT_CFG1, "BOOT_CFG1", &[boot_cfg1]),
+        Operation::Clear {
+            word: Word {
+                index: fuse::SEC_BOOT_CFG5,
+                name: "SEC_BOOT_CFG5",
+            },
+            mask: fuse::SEC_BOOT_CFG5_USE_PUF,
+        },
+    ]
+}
@@ -2,7 +2,13 @@ use probe_rs::probe::DebugProbeSelector;
 use probe_rs::probe::list::Lister;
 use probe_rs::{Permissions, Session};
 
+use crate::error::{Classify, ErrorKind};
+
 pub async fn start_session(chip: &str, probe_selector: Option<String>) -> anyhow::Result<Session> {
+    attach(chip, probe_selector).classify(ErrorKind::Probe)
+}
+
+fn attach(chip: &str, probe_selector: Option<String>) -> anyhow::Result<Session> {
     let session = if let Some(ref probe) = probe_selector {
         Lister::new().open(DebugProbeSelector::try_from(&**probe)?)?
     } else {
@@ -11,16 +17,15 @@ pub async fn start_session(chip: &str, probe_selector: Option<String>) -> anyhow
             0 => return Err(anyhow::anyhow!("No probe found")),
             1 => probes.first().unwrap(),
             _ => {
-                eprintln!("Use --probe to select one of the following available Probes:");
+                let mut message = String::from("Use --probe to select one of the following available probes:");
                 for (i, probe_info) in probes.iter().enumerate() {
-                    eprintln!("{i}: {probe_info}");
+                    message.push_str(&format!("\n{i}: {probe_info}"));
                 }
-
-                std::process::exit(1);
+                return Err(anyhow::anyhow!(message));
             }
         };
 
-        probe.open().unwrap()
+        probe.open()?
     }
     .attach(chip, Permissions::default())?;
 
@@ -0,0 +1,122 @@
+use std::fmt;
+use std::path::Path;
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::SignArguments;
+use crate::config::Config;
+use crate::processors::sign_cache;
+use crate::util::generate_hex;
+
+/// Change to the device made by `provision device`, recorded in the [Checkpoint] once completed
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Step {
+    /// Flash the prelude holding the FCB and boot image version
+    Prelude,
+    /// Flash the signed bootloader
+    Bootloader,
+    /// Flash the signed application into slot 0
+    Application,
+    /// Seed the state journal to boot slot 0
+    State,
+    /// Write the intended fuse values to the shadow registers and verify them
+    Fuses,
+}
+
+impl Step {
+    /// Steps to provision a device with, in order, flashing an application or not
+    pub fn all(with_application: bool) -> Vec<Step> {
+        [
+            Step::Prelude,
+            Step::Bootloader,
+            Step::Application,
+            Step::State,
+            Step::Fuses,
+        ]
+        .into_iter()
+        .filter(|step| with_application || *step != Step::Application)
+        .collect()
+    }
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Step::Prelude => "prelude",
+            Step::Bootloader => "bootloader",
+            Step::Application => "application",
+            Step::State => "state",
+            Step::Fuses => "fuses",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// Steps of `provision device` completed so far, to resume after the last one when run again
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Checkpoint {
+    /// Digest of the inputs of the provisioning, see [fingerprint]
+    pub fingerprint: String,
+    /// UUID of the device the steps were completed on
+    pub device: String,
+    /// Completed steps, in order
+    pub completed: Vec<Step>,
+}
+
+impl Checkpoint {
+    /// Checkpoint of `device` without completed steps
+    pub fn new(fingerprint: impl Into<String>, device: impl Into<String>) -> Self {
+        Self {
+            fingerprint: fingerprint.into(),
+            device: device.into(),
+            completed: vec![],
+        }
+    }
+
+    /// Checkpoint stored at `path` to resume from, if it was recorded for the same `fingerprint` and `device`
+    ///
+    /// A checkpoint recorded for other inputs is not resumed from, as the steps it completed are then outdated.
+    /// Neither is one recorded for another device, for example when swapping boards after a failure, as its steps
+    /// were never done on this one.
+    pub fn resume(path: impl AsRef<Path>, fingerprint: &str, device: &str) -> Option<Self> {
+        let checkpoint: Self = serde_json::from_slice(&std::fs::read(path).ok()?).ok()?;
+        (checkpoint.fingerprint == fingerprint && checkpoint.device == device).then_some(checkpoint)
+    }
+
+    /// Whether `step` was completed
+    pub fn is_completed(&self, step: Step) -> bool {
+        self.completed.contains(&step)
+    }
+
+    /// Record `step` as completed, and store the checkpoint at `path`
+    pub fn complete(&mut self, step: Step, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        if !self.is_completed(step) {
+            self.completed.push(step);
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Could not write checkpoint {}", path.display()))
+    }
+}
+
+/// Digest of the inputs of provisioning `chip` with the bootloader and application signed with `bootloader` and
+/// `application`
+///
+/// Covers everything that goes into signing the images, see [sign_cache::key].
+pub fn fingerprint(
+    config: &Config,
+    chip: &str,
+    bootloader: &SignArguments,
+    application: Option<&SignArguments>,
+) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(chip.as_bytes());
+    hasher.update(sign_cache::key(config, true, bootloader).as_bytes());
+    if let Some(application) = application {
+        hasher.update(sign_cache::key(config, false, application).as_bytes());
+    }
+    generate_hex(&hasher.finalize())
+}
@@ -0,0 +1,193 @@
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use anyhow::Context;
+use object::read::elf::ElfFile32;
+use object::{Object, ObjectSymbol};
+use probe_rs::rtt::Rtt;
+use probe_rs::{Core, MemoryInterface};
+
+use crate::error::{Classify, ErrorKind};
+
+/// Symbol of the RTT control block, as placed by `defmt-rtt` and `rtt-target`
+const CONTROL_BLOCK_SYMBOL: &str = "_SEGGER_RTT";
+
+/// Interval between reads of the RTT buffer when it was empty
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Stage of the boot chain logging over RTT, such as the bootloader or the application it hands off to
+#[derive(Debug, Clone)]
+pub struct Stage {
+    /// Name the logs of this stage are prefixed with
+    pub name: &'static str,
+    /// ELF file the stage was built as
+    pub elf_path: PathBuf,
+    /// Address of the RTT control block in RAM
+    pub control_block: u64,
+    /// Whether the stage logs using defmt, and thus needs its ELF to decode the logs, rather than plain text
+    pub defmt: bool,
+}
+
+impl Stage {
+    pub fn from_file(name: &'static str, elf_path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let elf_path = elf_path.as_ref();
+        let data = std::fs::read(elf_path).with_context(|| format!("Could not read {}", elf_path.display()))?;
+        Self::from_elf(name, elf_path, &data).with_context(|| format!("Invalid ELF file {}", elf_path.display()))
+    }
+
+    /// Locate the RTT control block and the defmt table in the ELF `data`
+    pub fn from_elf(name: &'static str, elf_path: impl AsRef<Path>, data: &[u8]) -> anyhow::Result<Self> {
+        let file: ElfFile32 = ElfFile32::parse(data).context("Could not parse ELF file")?;
+        let control_block = file
+            .symbols()
+            .find(|symbol| symbol.name() == Ok(CONTROL_BLOCK_SYMBOL))
+            .map(|symbol| symbol.address())
+            .ok_or_else(|| {
+                anyhow::anyhow!("The {name} does not log over RTT, as it has no `{CONTROL_BLOCK_SYMBOL}`")
+            })?;
+
+        Ok(Self {
+            name,
+            elf_path: elf_path.as_ref().to_owned(),
+            control_block,
+            defmt: file.section_by_name(".defmt").is_some(),
+        })
+    }
+}
+
+/// Invalidate the RTT control blocks of `stages` left in RAM by an earlier run
+///
+/// Otherwise a stage would be considered to have started before it initialized its control block again.
+pub fn clear_control_blocks(core: &mut Core, stages: &[Stage]) -> anyhow::Result<()> {
+    for stage in stages {
+        core.write_8(stage.control_block, &Rtt::RTT_ID.map(|_| 0))?;
+    }
+    Ok(())
+}
+
+/// Print the logs of every stage in turn, decoding each with its own ELF
+///
+/// Reads the first up channel of each stage until the control block of the next stage appears, which happens when
+/// the bootloader hands off to the application. Only returns on errors, as the logs of the last stage are followed
+/// indefinitely. Two stages with their control block at the same address can not be told apart.
+pub fn follow(core: &mut Core, stages: &[Stage], defmt_print: &Path) -> anyhow::Result<()> {
+    let mut buf = [0u8; 1024];
+    for (stage_i, stage) in stages.iter().enumerate() {
+        let next = stages.get(stage_i + 1);
+        let next_started = |core: &mut Core| next.is_some_and(|next| Rtt::attach_at(core, next.control_block).is_ok());
+
+        log::debug!(
+            "Waiting for the RTT control block of the {} at 0x{:08x}",
+            stage.name,
+            stage.control_block
+        );
+        let mut rtt = loop {
+            match Rtt::attach_at(core, stage.control_block) {
+                Ok(rtt) => break Some(rtt),
+                Err(_) if next_started(core) => break None,
+                Err(_) => std::thread::sleep(POLL_INTERVAL),
+            }
+        };
+        let Some(rtt) = &mut rtt else {
+            log::warn!("The {} started without logging over RTT", stage.name);
+            continue;
+        };
+
+        let mut decoder = Decoder::spawn(stage, defmt_print)?;
+        loop {
+            let Some(channel) = rtt.up_channel(0) else {
+                anyhow::bail!("The {} has no RTT up channel", stage.name);
+            };
+            match channel.read(core, &mut buf) {
+                Ok(0) if next_started(core) => break,
+                Ok(0) => std::thread::sleep(POLL_INTERVAL),
+                Ok(len) => decoder.write(&buf[..len])?,
+                // The RAM of the previous stage may be reused once the next one runs.
+                Err(_) if next_started(core) => break,
+                Err(e) => return Err(e).context("Could not read RTT").classify(ErrorKind::Probe),
+            }
+        }
+        decoder.finish()?;
+    }
+    Ok(())
+}
+
+/// Sink of the raw RTT output of a [Stage], printing it prefixed with the name of the stage
+enum Decoder {
+    /// Frames decoded by a `defmt-print` process, of which the output is forwarded
+    Defmt {
+        child: Child,
+        stdin: ChildStdin,
+        forward: JoinHandle<()>,
+    },
+    /// Plain text printed as is
+    Text { prefix: String, line_start: bool },
+}
+
+impl Decoder {
+    fn spawn(stage: &Stage, defmt_print: &Path) -> anyhow::Result<Self> {
+        let prefix = format!("[{}] ", stage.name);
+        if !stage.defmt {
+            return Ok(Decoder::Text {
+                prefix,
+                line_start: true,
+            });
+        }
+
+        let mut child = Command::new(defmt_print)
+            .arg("-e")
+            .arg(&stage.elf_path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .with_context(|| format!("Could not execute `{}`, is it installed?", defmt_print.display()))
+            .classify(ErrorKind::ExternalTool)?;
+
+        let stdin = child.stdin.take().expect("Piped");
+        let stdout = child.stdout.take().expect("Piped");
+        let forward = std::thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                println!("{prefix}{line}");
+            }
+        });
+
+        Ok(Decoder::Defmt { child, stdin, forward })
+    }
+
+    fn write(&mut self, data: &[u8]) -> anyhow::Result<()> {
+        match self {
+            Decoder::Defmt { stdin, .. } => stdin.write_all(data).context("defmt-print exited")?,
+            Decoder::Text { prefix, line_start } => {
+                let mut stdout = std::io::stdout().lock();
+                for line in data.split_inclusive(|b| *b == b'\n') {
+                    if *line_start {
+                        stdout.write_all(prefix.as_bytes())?;
+                    }
+                    stdout.write_all(line)?;
+                    *line_start = line.ends_with(b"\n");
+                }
+                stdout.flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Wait for the remaining output to be printed
+    fn finish(self) -> anyhow::Result<()> {
+        if let Decoder::Defmt {
+            mut child,
+            stdin,
+            forward,
+        } = self
+        {
+            drop(stdin);
+            child.wait()?;
+            let _ = forward.join();
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,83 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::SignArguments;
+use crate::config::Config;
+use crate::processors::certificates::Rkth;
+use crate::util::generate_hex;
+
+/// Result of signing an image, as stored next to the input ELF file
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Entry {
+    /// Digest of everything that went into signing the image, see [key]
+    key: String,
+    output_path: PathBuf,
+    /// Digest of the output file, to notice when it has been changed or replaced since
+    output_sha256: String,
+    rkth: String,
+}
+
+/// Digest of the inputs of signing an image with `args`
+///
+/// Covers the arguments, the configuration, and the contents of the input ELF file, the OTP master key,
+/// the certificates and keys, a given signature and the subregion blobs.
+pub fn key(config: &Config, is_bootloader: bool, args: &SignArguments) -> String {
+    let mut hasher = Sha256::new();
+    let mut update = |data: &[u8]| {
+        hasher.update((data.len() as u64).to_le_bytes());
+        hasher.update(data);
+    };
+
+    update(env!("CARGO_PKG_VERSION").as_bytes());
+    update(&[is_bootloader as u8]);
+    update(format!("{config:?}").as_bytes());
+    update(format!("{args:?}").as_bytes());
+
+    let mut files = vec![args.input_path.clone(), config.otp_path.clone()];
+    for chain in &config.certificates {
+        for certificate in &chain.0 {
+            files.push(certificate.path.clone());
+            if let Some(prototype) = &certificate.prototype {
+                files.push(prototype.key_path.clone());
+            }
+        }
+    }
+    files.extend(args.signature_path.clone());
+    files.extend(args.subregions.iter().map(|(_, path)| PathBuf::from(path)));
+
+    for file in files {
+        // Files that cannot be read make signing fail, and are thus never part of a stored entry.
+        update(&std::fs::read(file).unwrap_or_default());
+    }
+
+    generate_hex(&hasher.finalize())
+}
+
+fn file_sha256(path: impl AsRef<Path>) -> Option<String> {
+    std::fs::read(path).ok().map(|data| generate_hex(&Sha256::digest(data)))
+}
+
+/// Signed image and RKTH stored at `cache_path` for `key`, if its output file is unchanged
+pub fn lookup(cache_path: impl AsRef<Path>, key: &str) -> Option<(PathBuf, Rkth)> {
+    let entry: Entry = serde_json::from_slice(&std::fs::read(cache_path).ok()?).ok()?;
+    if entry.key != key || file_sha256(&entry.output_path)? != entry.output_sha256 {
+        return None;
+    }
+
+    Some((entry.output_path, Rkth::from_hex(&entry.rkth).ok()?))
+}
+
+/// Store the signed image at `output_path` and its RKTH at `cache_path` for `key`
+pub fn store(cache_path: impl AsRef<Path>, key: &str, output_path: &Path, rkth: &Rkth) -> anyhow::Result<()> {
+    let entry = Entry {
+        key: key.to_owned(),
+        output_path: output_path.to_owned(),
+        output_sha256: file_sha256(output_path)
+            .ok_or_else(|| anyhow::anyhow!("Could not read {}", output_path.display()))?,
+        rkth: rkth.as_hex(),
+    };
+    std::fs::write(cache_path, serde_json::to_vec_pretty(&entry)?)?;
+    Ok(())
+}
@@ -0,0 +1,143 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, bail};
+use mbi_format::SubregionDigest;
+use sha2::{Digest, Sha256};
+
+use crate::config::ApplicationArgs;
+
+/// Fill of the gaps between the image and the blobs, as in erased flash
+const GAP_FILL: u8 = 0xff;
+
+/// Blob placed at a fixed offset in a slot, next to the image
+#[derive(Debug, Clone)]
+pub struct Blob {
+    pub name: String,
+    /// Offset from the start of the slot
+    pub offset: u64,
+    /// Maximum size of the blob
+    pub size: u64,
+    pub data: Vec<u8>,
+    /// Whether to record the digest of the blob in the metadata trailer of the image
+    pub digest: bool,
+}
+
+/// Layout of a slot consisting of the image at its start, followed by blobs in fixed subregions
+#[derive(Debug, Clone)]
+pub struct SlotLayout {
+    slot_size: u64,
+    blobs: Vec<Blob>,
+}
+
+impl SlotLayout {
+    /// Check that the blobs fit their subregions, and that the subregions fit the slot without overlapping
+    pub fn new(slot_size: u64, mut blobs: Vec<Blob>) -> anyhow::Result<Self> {
+        blobs.sort_by_key(|blob| blob.offset);
+
+        let mut end = 0;
+        for blob in &blobs {
+            if blob.data.len() as u64 > blob.size {
+                bail!(
+                    "Blob of {:#x} bytes exceeds subregion {} of {:#x} bytes",
+                    blob.data.len(),
+                    blob.name,
+                    blob.size
+                );
+            }
+            if blob.offset < end {
+                bail!(
+                    "Subregion {} at {:#x} overlaps the previous subregion",
+                    blob.name,
+                    blob.offset
+                );
+            }
+            end = blob.offset + blob.size;
+            if end > slot_size {
+                bail!(
+                    "Subregion {} ends at {end:#x}, beyond the slot of {slot_size:#x} bytes",
+                    blob.name
+                );
+            }
+        }
+
+        Ok(Self { slot_size, blobs })
+    }
+
+    /// Read the blob of every subregion in `application` from the `NAME=PATH` pairs in `inputs`
+    ///
+    /// Either all subregions are given, or none, in which case the layout is empty.
+    pub fn from_config(application: &ApplicationArgs, inputs: &[(String, String)]) -> anyhow::Result<Self> {
+        for (name, _) in inputs {
+            if !application.subregions.iter().any(|subregion| &subregion.name == name) {
+                bail!("Subregion {name} is not configured in application.subregions");
+            }
+        }
+        if inputs.is_empty() {
+            return Self::new(application.slot_size, Vec::new());
+        }
+
+        let blobs = application
+            .subregions
+            .iter()
+            .map(|subregion| {
+                let mut paths = inputs.iter().filter(|(name, _)| name == &subregion.name);
+                let (Some((_, path)), None) = (paths.next(), paths.next()) else {
+                    bail!("Expected exactly one blob for subregion {}", subregion.name);
+                };
+                let path = PathBuf::from(path);
+                let data = std::fs::read(&path).with_context(|| format!("Could not read {}", path.display()))?;
+                Ok(Blob {
+                    name: subregion.name.clone(),
+                    offset: subregion.offset,
+                    size: subregion.size,
+                    data,
+                    digest: subregion.digest,
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        Self::new(application.slot_size, blobs)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blobs.is_empty()
+    }
+
+    /// Space available to the image, which is up to the first subregion
+    pub fn image_limit(&self) -> u64 {
+        self.blobs.first().map_or(self.slot_size, |blob| blob.offset)
+    }
+
+    /// Digests of the blobs to record in the metadata trailer
+    pub fn digests(&self) -> Vec<SubregionDigest> {
+        self.blobs
+            .iter()
+            .filter(|blob| blob.digest)
+            .map(|blob| SubregionDigest {
+                offset: blob.offset as u32,
+                len: blob.data.len() as u32,
+                sha256: Sha256::digest(&blob.data).into(),
+            })
+            .collect()
+    }
+
+    /// Compose the contents of the slot from the signed `image` and the blobs, filling the gaps in between
+    ///
+    /// The result ends with the last blob rather than being padded to the size of the slot.
+    pub fn compose(&self, image: &[u8]) -> anyhow::Result<Vec<u8>> {
+        if image.len() as u64 > self.image_limit() {
+            bail!(
+                "Image of {:#x} bytes overlaps the first subregion at {:#x}",
+                image.len(),
+                self.image_limit()
+            );
+        }
+
+        let mut slot = image.to_vec();
+        for blob in &self.blobs {
+            slot.resize(blob.offset as usize, GAP_FILL);
+            slot.extend(&blob.data);
+        }
+        Ok(slot)
+    }
+}
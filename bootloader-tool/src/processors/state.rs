@@ -0,0 +1,154 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use anyhow::{Context, bail};
+use ec_slimloader_state::record::{self, Record};
+use ec_slimloader_state::state::{ParseResult, Slot, State, Status};
+use itertools::Itertools;
+use probe_rs::Session;
+use probe_rs::flashing::DownloadOptions;
+
+use crate::config::MemoryRange;
+
+/// Contents of a state journal of `size` bytes containing only `state`, as left by `FlashJournal::reset`
+pub fn seeded_journal(state: &State, size: usize) -> Vec<u8> {
+    let mut journal = vec![0xff; size];
+    state.to_bytes(&mut journal[..State::SIZE]);
+    journal
+}
+
+/// Latest valid state in the contents of a state journal, like `FlashJournal::get`
+pub fn latest(journal: &[u8]) -> Option<State> {
+    latest_record(journal)
+}
+
+/// Latest valid record in the contents of a journal of `R`, like `FlashJournal::get`
+///
+/// Records are written in ascending address order, so this is the last one that parses.
+pub fn latest_record<R: Record>(journal: &[u8]) -> Option<R> {
+    journal
+        .chunks_exact(R::SIZE)
+        .rev()
+        .find_map(|chunk| R::try_from_bytes(chunk).ok())
+}
+
+/// Records written to the contents of a state journal with their offsets, oldest first, skipping erased slots
+///
+/// Unlike [latest], this includes states that have been superseded and records that do not parse.
+pub fn history(journal: &[u8]) -> Vec<(usize, Result<State, ParseResult>)> {
+    record::parse_records(journal)
+        .filter(|(_, record)| *record != Err(ParseResult::Unset))
+        .collect()
+}
+
+/// Summary of a [history], counting the states per status and the records that do not parse
+pub fn summarize(history: &[(usize, Result<State, ParseResult>)]) -> String {
+    let mut counts: Vec<(String, usize)> = vec![];
+    for (_, record) in history {
+        let name = match record {
+            Ok(state) => format!("{:?}", state.status()),
+            Err(ParseResult::Outdated) => "outdated".to_owned(),
+            Err(_) => "invalid".to_owned(),
+        };
+        match counts.iter_mut().find(|(counted, _)| *counted == name) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((name, 1)),
+        }
+    }
+
+    let counts = counts.iter().map(|(name, count)| format!("{count} {name}")).join(", ");
+    format!("{} records: {counts}", history.len())
+}
+
+/// Reset the state journal in `range` on the device to only contain `state`, see [seeded_journal]
+pub fn flash_seeded(session: &mut Session, range: &MemoryRange, state: &State) -> anyhow::Result<()> {
+    let journal = seeded_journal(state, range.size as usize);
+
+    let mut loader = session.target().flash_loader();
+    loader.add_data(range.start, &journal)?;
+    loader
+        .commit(session, DownloadOptions::default())
+        .context("Failed to flash state journal")
+}
+
+/// Application slot given on the command line, either by index or relative to the latest state on the device
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SlotRef {
+    Index(u8),
+    /// The slot the bootloader boots, see [current_slot]
+    Current,
+    /// The target slot of the latest state
+    Target,
+    /// The backup slot of the latest state
+    Backup,
+    /// Whichever of the target and backup slot is not the current slot
+    Other,
+}
+
+impl SlotRef {
+    /// Whether the state journal on the device needs to be read to [resolve](SlotRef::resolve) this slot
+    pub fn is_symbolic(&self) -> bool {
+        !matches!(self, SlotRef::Index(_))
+    }
+
+    /// Index of the slot in `application.slot_starts`, relative to `state` if symbolic
+    pub fn resolve(self, state: Option<&State>) -> anyhow::Result<usize> {
+        let slot = match (self, state) {
+            (SlotRef::Index(index), _) => return Ok(index as usize),
+            (_, None) => bail!("No valid state in the state journal to resolve slot {self} against"),
+            (SlotRef::Current, Some(state)) => current_slot(state),
+            (SlotRef::Target, Some(state)) => state.target(),
+            (SlotRef::Backup, Some(state)) => state.backup(),
+            (SlotRef::Other, Some(state)) => {
+                if state.target() == state.backup() {
+                    bail!(
+                        "Target and backup are both slot {}, so there is no other slot",
+                        u8::from(state.target())
+                    );
+                }
+                if current_slot(state) == state.target() {
+                    state.backup()
+                } else {
+                    state.target()
+                }
+            }
+        };
+        Ok(u8::from(slot) as usize)
+    }
+}
+
+impl FromStr for SlotRef {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "current" => Ok(SlotRef::Current),
+            "target" => Ok(SlotRef::Target),
+            "backup" => Ok(SlotRef::Backup),
+            "other" => Ok(SlotRef::Other),
+            _ => s.parse().map(SlotRef::Index).map_err(|_| {
+                anyhow::anyhow!("Invalid slot {s}, expected an index or one of current, target, backup or other")
+            }),
+        }
+    }
+}
+
+impl Display for SlotRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SlotRef::Index(index) => write!(f, "{index}"),
+            SlotRef::Current => write!(f, "current"),
+            SlotRef::Target => write!(f, "target"),
+            SlotRef::Backup => write!(f, "backup"),
+            SlotRef::Other => write!(f, "other"),
+        }
+    }
+}
+
+/// Slot the bootloader boots in `state`: the backup once booting the target failed, the target otherwise
+pub fn current_slot(state: &State) -> Slot {
+    match state.status() {
+        Status::Failed => state.backup(),
+        Status::Initial | Status::Attempting | Status::Confirmed => state.target(),
+    }
+}
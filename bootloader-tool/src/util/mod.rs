@@ -1,5 +1,36 @@
+use std::ffi::OsString;
+
 use anyhow::Context;
+use clap::{Args, Command};
 use itertools::Itertools;
+use mbi_format::SlotBinding;
+use tempfile::{NamedTempFile, TempPath};
+
+/// Temporary file with `contents` and `suffix`, to be passed by path to an external tool
+///
+/// The file is closed before returning, as Windows refuses other processes to open a file that is still open here.
+/// It is removed when the returned path is dropped.
+pub fn temp_file(suffix: &str, contents: &[u8]) -> anyhow::Result<TempPath> {
+    let mut file = NamedTempFile::with_suffix(suffix).context("Could not create temporary file")?;
+    std::io::Write::write_all(&mut file, contents).context("Could not write temporary file")?;
+    Ok(file.into_temp_path())
+}
+
+/// Path of a new empty temporary file with `suffix`, for an external tool to write to, see [temp_file]
+pub fn temp_path(suffix: &str) -> anyhow::Result<TempPath> {
+    temp_file(suffix, &[])
+}
+
+/// Parse arguments `T` from `args`, taking the defaults from their definition like the command line does
+///
+/// Panics if `args` are not valid, so only use this with arguments built by the tool itself.
+pub fn parse_args<T: Args>(args: impl IntoIterator<Item = OsString>) -> T {
+    let command = T::augment_args(Command::new("bootloader-tool").no_binary_name(true));
+    let matches = command
+        .try_get_matches_from(args)
+        .expect("arguments built by the tool are valid");
+    T::from_arg_matches(&matches).expect("arguments built by the tool are valid")
+}
 
 pub fn parse_hex(s: &str) -> anyhow::Result<Vec<u8>> {
     s.as_bytes()
@@ -9,6 +40,34 @@ pub fn parse_hex(s: &str) -> anyhow::Result<Vec<u8>> {
         .context("Input not hexidecimal")
 }
 
+/// Parse a decimal number, or a hexadecimal one when prefixed with 0x
+pub fn parse_u32(s: &str) -> anyhow::Result<u32> {
+    match s.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => s.parse(),
+    }
+    .with_context(|| format!("Invalid number {s}"))
+}
+
+/// Parse the slot an image is bound to, either its index or `any`
+pub fn parse_slot_binding(s: &str) -> anyhow::Result<SlotBinding> {
+    if s == "any" {
+        return Ok(SlotBinding { slot: None });
+    }
+    match s.parse::<u8>() {
+        Ok(slot) if slot != SlotBinding::ANY => Ok(SlotBinding { slot: Some(slot) }),
+        _ => Err(anyhow::anyhow!("Expected a slot index or `any`, got {s}")),
+    }
+}
+
+/// Parse a `KEY=VALUE` pair
+pub fn parse_key_value(s: &str) -> anyhow::Result<(String, String)> {
+    match s.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_owned(), value.to_owned())),
+        _ => Err(anyhow::anyhow!("Expected KEY=VALUE, got {s}")),
+    }
+}
+
 pub fn generate_hex(buf: &[u8]) -> String {
     let mut result = String::new();
     for b in buf {
@@ -0,0 +1,43 @@
+//! Arguments for the library functions in `api`, built with the defaults of the command line.
+
+use std::path::PathBuf;
+
+use bootloader_tool::processors::certificates::Rkth;
+use bootloader_tool::{Config, ProbeArgs, RunArguments, SignArguments, api};
+
+#[test]
+fn sign_arguments_with_defaults() {
+    let mut args = SignArguments::new("build/example-application");
+    assert_eq!(args.input_path, PathBuf::from("build/example-application"));
+    assert_eq!(args.certificate, 0);
+    assert_eq!(args.nxpimage_path, PathBuf::from("nxpimage"));
+    assert!(!args.dont_sign);
+    let config = Config::read("config.toml").unwrap();
+    assert_eq!(
+        args.output_path_with_default(&config),
+        PathBuf::from("./artifacts/images/default/example-application.signed.bin")
+    );
+
+    args.certificate = 1;
+    let run_args = RunArguments::new(args);
+    assert_eq!(run_args.sign_args.certificate, 1);
+    assert_eq!(run_args.probe_rs_path, PathBuf::from("probe-rs"));
+    assert!(!run_args.force_sign);
+    assert_eq!(run_args.probe_args.chip, ProbeArgs::default().chip);
+    assert_eq!(run_args.probe_args.probe, None);
+}
+
+#[test]
+fn input_paths_are_not_options() {
+    let args = SignArguments::new("--dont-sign");
+    assert_eq!(args.input_path, PathBuf::from("--dont-sign"));
+    assert!(!args.dont_sign);
+}
+
+#[test]
+fn verify_fails_without_image() {
+    let dir = tempfile::tempdir().unwrap();
+    let missing = dir.path().join("example-application.signed.bin");
+    assert!(api::verify(&missing, &Rkth([0; 32])).is_err());
+    assert!(api::verify_like_rom(&missing, &Rkth([0; 32])).is_err());
+}
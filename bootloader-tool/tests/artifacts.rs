@@ -0,0 +1,43 @@
+//! Locations of the files generated from input ELF files.
+
+use std::path::{Path, PathBuf};
+
+use bootloader_tool::Config;
+use bootloader_tool::processors::artifacts::{Artifact, ArtifactLayout};
+
+const CONFIG: &str = r#"
+artifacts_path = "./artifacts"
+otp_path = "./artifacts/otp_master_key.txt"
+certificates = [[{ path = "./artifacts/cert-rot1.pem" }]]
+
+[profiles.prod]
+"#;
+
+#[test]
+fn layout_per_profile() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.toml");
+    std::fs::write(&path, CONFIG).unwrap();
+
+    let default = Config::read(&path).unwrap();
+    let prod = Config::read_profile(&path, Some("prod")).unwrap();
+
+    // Builds of different profiles no longer end up next to each other, nor next to the ELF file.
+    let input = "target/release/example-application.elf";
+    assert_eq!(
+        ArtifactLayout::new(&default, input, None).path(Artifact::Signed),
+        PathBuf::from("./artifacts/images/default/example-application.signed.bin")
+    );
+    assert_eq!(
+        ArtifactLayout::new(&prod, input, None).path(Artifact::Signed),
+        PathBuf::from("./artifacts/images/prod/example-application.signed.bin")
+    );
+
+    let overridden = ArtifactLayout::new(&prod, input, Some(Path::new("out")));
+    assert_eq!(overridden.dir(), Path::new("out"));
+    assert_eq!(
+        overridden.path(Artifact::SignCache),
+        PathBuf::from("out/example-application.sign-cache")
+    );
+    assert_eq!(overridden.files().len(), Artifact::ALL.len());
+}
@@ -0,0 +1,59 @@
+//! Records appended to the signing audit log by `sign`.
+
+use std::path::PathBuf;
+
+use bootloader_tool::Config;
+use bootloader_tool::processors::audit::{self, Record, SignatureSource};
+
+fn record(output_sha256: &str, signature: SignatureSource) -> Record {
+    Record {
+        timestamp: 1_700_000_000,
+        signer: "release-bot".to_owned(),
+        tool_version: "0.1.0".to_owned(),
+        bootloader: false,
+        input_path: PathBuf::from("example-application"),
+        input_sha256: "00".repeat(32),
+        output_path: PathBuf::from("example-application.signed.bin"),
+        output_sha256: output_sha256.to_owned(),
+        certificate: 0,
+        certificates: vec!["11".repeat(32), "22".repeat(32)],
+        rkth: "33".repeat(32),
+        signature,
+    }
+}
+
+#[test]
+fn append_keeps_earlier_records() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("signing-audit.jsonl");
+
+    let first = record(
+        &"44".repeat(32),
+        SignatureSource::Key(PathBuf::from("cert-img1-user-key.pem")),
+    );
+    let second = record(
+        &"55".repeat(32),
+        SignatureSource::External(PathBuf::from("signature.bin")),
+    );
+    audit::append(&path, &first).unwrap();
+    audit::append(&path, &second).unwrap();
+
+    // One JSON object per line.
+    let log = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(log.lines().count(), 2);
+    assert!(
+        log.lines()
+            .nth(1)
+            .unwrap()
+            .contains(r#""signature":{"kind":"external","path":"signature.bin"}"#)
+    );
+
+    assert_eq!(audit::read(&path).unwrap(), [first, second]);
+}
+
+#[test]
+fn defaults_to_artifacts_path() {
+    let config = Config::read("config.toml").unwrap();
+    assert_eq!(audit::path(&config), config.artifacts_path.join("signing-audit.jsonl"));
+    assert!(!audit::signer(&config).is_empty());
+}
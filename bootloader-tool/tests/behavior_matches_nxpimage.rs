@@ -2,7 +2,7 @@ use std::path::PathBuf;
 
 use anyhow::Context;
 use bootloader_tool::Config;
-use bootloader_tool::processors::mbi::{self, cert_block};
+use bootloader_tool::processors::mbi::{self, cert_block, rom};
 use bootloader_tool::processors::objcopy;
 use bootloader_tool::processors::otp::Otp;
 use object::read::elf::ElfFile32;
@@ -80,8 +80,9 @@ fn assert_same(
     let pure_out = output_dir.path().join("pure.bin");
     let nxp_out = output_dir.path().join("nxp.bin");
 
-    let cert_block = cert_block::generate("nxpimage", &config, certificate_idx).unwrap();
-    let private_key_path = get_private_key(&config, certificate_idx);
+    let cert_block = cert_block::generate("nxpimage", config, certificate_idx).unwrap();
+    let rkth = cert_block.rkth();
+    let private_key_path = get_private_key(config, certificate_idx);
     mbi::generate_pure(
         &input_path,
         base_addr,
@@ -89,18 +90,19 @@ fn assert_same(
         is_bootloader,
         otp,
         cert_block,
-        private_key_path,
+        &private_key_path,
     )
     .unwrap();
 
-    let cert_block_config = cert_block::generate_config(&config, certificate_idx, None as Option<PathBuf>);
+    let cert_block_config = cert_block::generate_config(config, certificate_idx, None as Option<PathBuf>).unwrap();
     mbi::generate_nxp(
         "nxpimage",
         &input_path,
         base_addr,
         &nxp_out,
-        is_bootloader,
         cert_block_config,
+        &private_key_path,
+        &config.mbi,
     )
     .unwrap();
 
@@ -111,6 +113,11 @@ fn assert_same(
         let evidence = output_dir.keep();
         panic!("Outputs differ, see {} for generated files.", evidence.display());
     }
+
+    if let Err(rejection) = rom::authenticate(&nxp, &rkth) {
+        let evidence = output_dir.keep();
+        panic!("ROM would reject {}: {rejection}", evidence.join("nxp.bin").display());
+    }
 }
 
 fn read_example(app_or_boot: &str) -> (Vec<u8>, u32) {
@@ -126,5 +133,5 @@ fn read_example(app_or_boot: &str) -> (Vec<u8>, u32) {
     let file = ElfFile32::parse(&input[..])
         .context("Could not parse ELF file")
         .unwrap();
-    objcopy::objcopy(&file).unwrap()
+    objcopy::objcopy(&file, objcopy::DEFAULT_MAX_GAP).unwrap()
 }
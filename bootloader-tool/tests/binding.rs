@@ -0,0 +1,18 @@
+//! Binding of application images to a slot, appended in front of the cert block such that it is signed.
+
+use bootloader_tool::processors::mbi::{self, SlotBinding};
+
+#[test]
+fn binding_ends_at_cert_block() {
+    let mut image = vec![0x11; 0x102];
+    mbi::append_slot_binding(&mut image, SlotBinding { slot: Some(1) });
+
+    // The cert block follows the image, which is now aligned.
+    assert_eq!(image.len(), 0x104 + SlotBinding::LEN);
+    assert_eq!(image[0x102..0x104], [0xFF, 0xFF]);
+    let offset = SlotBinding::offset(image.len()).unwrap();
+    assert_eq!(
+        SlotBinding::parse(&image[offset..]),
+        Some(SlotBinding { slot: Some(1) })
+    );
+}
@@ -0,0 +1,71 @@
+//! Boot configuration fields of the board presets and their overrides, as set by `run` and expected in the fuses.
+
+use std::path::Path;
+
+use bootloader_tool::Config;
+use bootloader_tool::processors::boot_cfg;
+use bootloader_tool::processors::fuse::{planned_boot_cfg0, planned_boot_cfg1};
+
+fn read_config(dir: &Path, board: &str) -> Config {
+    let path = dir.join("config.toml");
+    let contents = format!(
+        r#"
+artifacts_path = "./artifacts"
+otp_path = "./artifacts/otp_master_key.txt"
+certificates = [[{{ path = "./artifacts/cert-rot1.pem" }}]]
+{board}
+"#
+    );
+    std::fs::write(&path, contents).unwrap();
+    Config::read(&path).unwrap()
+}
+
+#[test]
+fn evk_preset() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = read_config(dir.path(), "[board]\npreset = \"evk\"");
+
+    // QSPI B, ISP disabled, TrustZone, secure boot, DICE skipped, boot failure pin 5.7.
+    assert_eq!(planned_boot_cfg0(&config).unwrap(), 0x3d90_4075);
+    // QSPI reset pin 2.12 enabled.
+    assert_eq!(planned_boot_cfg1(&config).unwrap(), 0x0031_4000);
+}
+
+#[test]
+fn production_preset_leaves_pins_unconfigured() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = read_config(dir.path(), "");
+
+    assert_eq!(planned_boot_cfg0(&config).unwrap(), 0x0090_4070);
+    assert_eq!(planned_boot_cfg1(&config).unwrap(), 0);
+}
+
+#[test]
+fn overrides() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = read_config(
+        dir.path(),
+        "[board]\npreset = \"evk\"\nboot_cfg0 = { primary_boot_src = 1 }\nboot_cfg1 = { qspi_reset_pin_num = 3 }",
+    );
+
+    assert_eq!(planned_boot_cfg0(&config).unwrap(), 0x3d90_4071);
+    assert_eq!(planned_boot_cfg1(&config).unwrap(), 0x000d_4000);
+}
+
+#[test]
+fn invalid_fields() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let config = read_config(dir.path(), "[board]\nboot_cfg1 = { qspi_reset_pin = 1 }");
+    assert!(planned_boot_cfg1(&config).is_err());
+
+    // The port is 3 bits wide.
+    let config = read_config(dir.path(), "[board]\nboot_cfg1 = { qspi_reset_pin_port = 8 }");
+    assert!(planned_boot_cfg1(&config).is_err());
+
+    assert_eq!(boot_cfg::field_bits("BOOT_CFG0", "dice_skip").unwrap(), 23..24);
+    assert_eq!(
+        boot_cfg::field_bits("BOOT_CFG1", "qspi_reset_pin_port").unwrap(),
+        15..18
+    );
+}
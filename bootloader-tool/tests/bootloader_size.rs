@@ -0,0 +1,50 @@
+//! Size regression test of the example bootloader, which must fit `bootloader.max_size` once signed.
+//!
+//! Build the smallest configuration of the bootloader first:
+//! `cargo build --profile minimal --features minimal -p example-bootloader` in `examples/rt685s`.
+
+use bootloader_tool::Config;
+use bootloader_tool::processors::mbi::{self, cert_block};
+use bootloader_tool::processors::objcopy;
+use object::read::elf::ElfFile32;
+
+const BOOTLOADER_PATH: &str = "../examples/rt685s/target/thumbv8m.main-none-eabihf/minimal/example-bootloader";
+
+/// Number of symbols to report, to point at what to shrink when the budget is exceeded
+const REPORTED_SYMBOLS: usize = 20;
+
+#[test]
+fn bootloader_fits_budget() {
+    const CERTIFICATE_IDX: usize = 0;
+
+    let config = Config::read("config.toml").unwrap();
+    let max_size = config.bootloader.as_ref().unwrap().max_size as usize;
+
+    let input = match std::fs::read(BOOTLOADER_PATH) {
+        Ok(input) => input,
+        Err(e) => {
+            panic!(
+                "Could not load example binary at '{BOOTLOADER_PATH}'!\n -> Go to examples/rt685s and run cargo build --profile minimal --features minimal -p example-bootloader.\nError: {e}",
+            );
+        }
+    };
+    let file = ElfFile32::parse(&input[..]).unwrap();
+    let (image, base_addr) = objcopy::objcopy(&file, objcopy::DEFAULT_MAX_GAP).unwrap();
+
+    let output_dir = tempfile::tempdir().unwrap();
+    let input_path = output_dir.path().join("input.bin");
+    std::fs::write(&input_path, &image).unwrap();
+
+    let cert_block = cert_block::generate("nxpimage", &config, CERTIFICATE_IDX).unwrap();
+    let signed_len = mbi::signed_len(&input_path, base_addr, true, cert_block).unwrap();
+
+    println!("Signed bootloader is {signed_len:#x} bytes, budget is {max_size:#x} bytes. Largest symbols:");
+    for (name, size) in objcopy::largest_symbols(&file, REPORTED_SYMBOLS) {
+        println!("{size:>8} {name}");
+    }
+
+    assert!(
+        signed_len <= max_size,
+        "Signed bootloader of {signed_len:#x} bytes exceeds bootloader.max_size of {max_size:#x} bytes, run with --nocapture to see the largest symbols"
+    );
+}
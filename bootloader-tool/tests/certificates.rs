@@ -0,0 +1,36 @@
+//! Expiry checks of certificates before signing.
+
+use bootloader_tool::processors::certificates::{self, Expiry, Validity};
+use x509_parser::prelude::ASN1Time;
+
+const DAY: i64 = 24 * 60 * 60;
+
+fn validity(not_before: i64, not_after: i64) -> Validity {
+    Validity {
+        subject: "CN=test".to_owned(),
+        not_before: ASN1Time::from_timestamp(not_before).unwrap(),
+        not_after: ASN1Time::from_timestamp(not_after).unwrap(),
+    }
+}
+
+#[test]
+fn expiry_window() {
+    let validity = validity(100 * DAY, 200 * DAY);
+    let at = |days: i64| ASN1Time::from_timestamp(days * DAY).unwrap();
+
+    assert_eq!(validity.expiry(at(50), 30), Expiry::NotYetValid);
+    assert_eq!(validity.expiry(at(150), 30), Expiry::Valid { days: 50 });
+    assert_eq!(validity.expiry(at(150), 60), Expiry::Expiring { days: 50 });
+    assert_eq!(validity.expiry(at(200), 0), Expiry::Valid { days: 0 });
+    assert_eq!(validity.expiry(at(201), 30), Expiry::Expired);
+}
+
+#[test]
+fn strict_fails_on_expiring() {
+    let now = ASN1Time::now().timestamp();
+    let expiring = [validity(now - DAY, now + 10 * DAY)];
+
+    assert!(certificates::check_expiry(&expiring, 30, false).is_ok());
+    assert!(certificates::check_expiry(&expiring, 30, true).is_err());
+    assert!(certificates::check_expiry(&expiring, 5, true).is_ok());
+}
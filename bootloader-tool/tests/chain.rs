@@ -0,0 +1,165 @@
+//! Certificate chains ordered root to leaf, whether listed in the configuration or read from a bundle.
+
+use std::path::Path;
+
+use bootloader_tool::Config;
+use bootloader_tool::processors::certificates;
+use p12_keystore::{KeyStore, KeyStoreEntry, PrivateKeyChain};
+use rcgen::{BasicConstraints, CertificateParams, CertifiedIssuer, DnType, IsCa, KeyPair};
+
+struct Chain {
+    /// DER encoded certificates, root to leaf
+    ders: Vec<Vec<u8>>,
+    leaf_key: KeyPair,
+}
+
+fn params(name: &str, ca: bool) -> CertificateParams {
+    let mut params = CertificateParams::new(vec![]).unwrap();
+    params.distinguished_name.push(DnType::CommonName, name);
+    if ca {
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    }
+    params
+}
+
+fn chain(prefix: &str) -> Chain {
+    let root =
+        CertifiedIssuer::self_signed(params(&format!("{prefix}-root"), true), KeyPair::generate().unwrap()).unwrap();
+    let intermediate = CertifiedIssuer::signed_by(
+        params(&format!("{prefix}-intermediate"), true),
+        KeyPair::generate().unwrap(),
+        &root,
+    )
+    .unwrap();
+    let leaf_key = KeyPair::generate().unwrap();
+    let leaf = params(&format!("{prefix}-leaf"), false)
+        .signed_by(&leaf_key, &intermediate)
+        .unwrap();
+
+    Chain {
+        ders: vec![root.der().to_vec(), intermediate.der().to_vec(), leaf.der().to_vec()],
+        leaf_key,
+    }
+}
+
+fn pkcs12(chain: &Chain, password: &str) -> Vec<u8> {
+    let certificates = chain
+        .ders
+        .iter()
+        .rev()
+        .map(|der| p12_keystore::Certificate::from_der(der).unwrap());
+    let key_chain = PrivateKeyChain::new(chain.leaf_key.serialize_der(), [1u8; 20], certificates);
+
+    let mut keystore = KeyStore::new();
+    keystore.add_entry("leaf", KeyStoreEntry::PrivateKeyChain(key_chain));
+    keystore.writer(password).write().unwrap()
+}
+
+#[test]
+fn order_root_to_leaf() {
+    let chain = chain("a");
+    assert_eq!(certificates::chain_order(&chain.ders).unwrap(), [0, 1, 2]);
+
+    let shuffled = [&chain.ders[2], &chain.ders[0], &chain.ders[1]];
+    assert_eq!(certificates::chain_order(&shuffled).unwrap(), [1, 2, 0]);
+}
+
+#[test]
+fn chains_that_do_not_link() {
+    let a = chain("a");
+    let b = chain("b");
+
+    let error = certificates::chain_order(&[&a.ders[0], &b.ders[0]]).unwrap_err();
+    assert!(error.to_string().contains("single chain"), "{error}");
+
+    // The intermediate is missing, such that the leaf is taken for a second root.
+    let error = certificates::chain_order(&[&a.ders[0], &a.ders[2]]).unwrap_err();
+    assert!(error.to_string().contains("single chain"), "{error}");
+
+    // Issued by the subject of the root, but not signed by its key.
+    let forged = chain("a");
+    let error = certificates::chain_order(&[&a.ders[0], &forged.ders[1]]).unwrap_err();
+    assert!(error.to_string().contains("not signed by its issuer"), "{error}");
+}
+
+#[test]
+fn bundles() {
+    let dir = tempfile::tempdir().unwrap();
+    let chain = chain("a");
+
+    let der = dir.path().join("chain.der");
+    std::fs::write(&der, chain.ders.iter().rev().flatten().copied().collect::<Vec<u8>>()).unwrap();
+    let ders = certificates::read_bundle(&der, "").unwrap();
+    assert_eq!(ders, chain.ders.iter().rev().cloned().collect::<Vec<_>>());
+
+    let p12 = dir.path().join("chain.p12");
+    std::fs::write(&p12, pkcs12(&chain, "secret")).unwrap();
+    let ders = certificates::read_bundle(&p12, "secret").unwrap();
+    let order = certificates::chain_order(&ders).unwrap();
+    assert_eq!(
+        order.iter().map(|&i| &ders[i]).collect::<Vec<_>>(),
+        chain.ders.iter().collect::<Vec<_>>()
+    );
+    assert!(certificates::read_bundle(&p12, "wrong").is_err());
+}
+
+fn write_config(dir: &Path, certificates: &str) -> std::path::PathBuf {
+    let path = dir.join("config.toml");
+    std::fs::write(
+        &path,
+        format!(
+            "artifacts_path = {:?}\notp_path = \"otp.txt\"\ncertificates = {certificates}\n",
+            dir.join("artifacts")
+        ),
+    )
+    .unwrap();
+    path
+}
+
+#[test]
+fn config_orders_chains() {
+    let dir = tempfile::tempdir().unwrap();
+    let chain = chain("a");
+    for (name, der) in ["root", "intermediate", "leaf"].iter().zip(&chain.ders) {
+        std::fs::write(dir.path().join(format!("{name}.der")), der).unwrap();
+    }
+    let p12 = dir.path().join("chain.p12");
+    std::fs::write(&p12, pkcs12(&chain, "secret")).unwrap();
+
+    // Listed leaf first, and read from a bundle.
+    let listed = ["leaf", "root", "intermediate"]
+        .map(|name| format!("{{ path = {:?} }}", dir.path().join(format!("{name}.der"))));
+    let path = write_config(
+        dir.path(),
+        &format!(
+            "[[{}], {{ bundle = {p12:?}, password_env = \"CHAIN_TEST_PASSWORD\" }}]",
+            listed.join(", ")
+        ),
+    );
+    // SAFETY: no other test reads this variable.
+    unsafe { std::env::set_var("CHAIN_TEST_PASSWORD", "secret") };
+    let config = Config::read(&path).unwrap();
+
+    let names: Vec<_> = config.certificates[0]
+        .0
+        .iter()
+        .map(|certificate| certificate.path.file_name().unwrap().to_owned())
+        .collect();
+    assert_eq!(names, ["root.der", "intermediate.der", "leaf.der"]);
+    for (certificate, der) in config.certificates[1].0.iter().zip(&chain.ders) {
+        assert!(certificate.path.starts_with(dir.path().join("artifacts")));
+        assert_eq!(&std::fs::read(&certificate.path).unwrap(), der);
+    }
+
+    // Certificates still to be generated keep their listed order.
+    let path = write_config(
+        dir.path(),
+        "[[{ path = \"missing-leaf.pem\" }, { path = \"missing-root.pem\" }]]",
+    );
+    let config = Config::read(&path).unwrap();
+    assert_eq!(config.certificates[0].0[0].path, Path::new("missing-leaf.pem"));
+
+    let path = write_config(dir.path(), &format!("[[{}, {}]]", listed[0], listed[1]));
+    let error = Config::read(&path).unwrap_err();
+    assert!(format!("{error:#}").contains("single chain"), "{error:#}");
+}
@@ -0,0 +1,55 @@
+//! Selection and removal of generated files by the `clean` command.
+
+use std::path::{Path, PathBuf};
+
+use bootloader_tool::Config;
+use bootloader_tool::processors::clean;
+
+#[test]
+fn image_files_follow_default_outputs() {
+    let config = Config::read("config.toml").unwrap();
+    let files = clean::image_files(&config, "build/example-application", None);
+    let dir = PathBuf::from("./artifacts/images/default");
+    assert!(files.contains(&dir.join("example-application.signed.bin")));
+    assert!(files.contains(&dir.join("example-application.mbi-proto.bin")));
+    assert!(files.contains(&dir.join("example-application.signed.patch")));
+
+    let files = clean::image_files(&config, "build/example-application", Some(Path::new("build")));
+    assert!(files.contains(&PathBuf::from("build/example-application.signed.bin")));
+    assert!(!files.contains(&PathBuf::from("build/example-application")));
+}
+
+#[test]
+fn key_files_only_for_prototypes() {
+    let config = Config::read("config.toml").unwrap();
+    let files = clean::key_files(&config);
+    assert!(files.contains(&PathBuf::from("./artifacts/cert-rot1.pem")));
+    assert!(files.contains(&PathBuf::from("./artifacts/cert-img2-user-key.pem")));
+    assert!(files.contains(&PathBuf::from("./artifacts/otp_master_key.txt")));
+    assert_eq!(files.len(), 2 * 4 + 1);
+}
+
+#[test]
+fn remove_only_existing_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("example-bootloader");
+    std::fs::write(&input, b"elf").unwrap();
+    std::fs::write(input.with_extension("signed.bin"), b"signed").unwrap();
+    std::fs::write(input.with_extension("prelude.elf"), b"prelude").unwrap();
+
+    let config = Config::read("config.toml").unwrap();
+    let files = clean::image_files(&config, &input, Some(dir.path()));
+    let removed = clean::remove(&files, true).unwrap();
+    assert_eq!(removed.len(), 2);
+    assert!(removed.iter().all(|file| file.exists()));
+
+    let removed = clean::remove(&files, false).unwrap();
+    assert_eq!(removed.len(), 2);
+    assert!(removed.iter().all(|file| !file.exists()));
+    assert!(input.exists());
+
+    // Directories are never removed.
+    std::fs::create_dir(input.with_extension("slot.bin")).unwrap();
+    assert!(clean::remove(&files, false).is_err());
+    assert!(input.with_extension("slot.bin").exists());
+}
@@ -0,0 +1,23 @@
+//! Registration of shell completions and the command line they complete.
+
+use bootloader_tool::Cli;
+use bootloader_tool::completion::{self, SHELLS};
+use clap::CommandFactory;
+
+#[test]
+fn command_line_is_consistent() {
+    Cli::command().debug_assert();
+}
+
+#[test]
+fn registration_calls_back() {
+    for shell in SHELLS {
+        let mut script = Vec::new();
+        completion::write_registration(shell, "/opt/bootloader-tool", &mut script).unwrap();
+        let script = String::from_utf8(script).unwrap();
+        assert!(script.contains("/opt/bootloader-tool"), "{shell}");
+        assert!(script.contains(completion::VAR), "{shell}");
+    }
+
+    assert!(completion::write_registration("tcsh", "bootloader-tool", &mut Vec::new()).is_err());
+}
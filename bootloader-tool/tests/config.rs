@@ -0,0 +1,177 @@
+//! Reading the configuration in any format, with includes and profiles.
+
+use std::path::Path;
+
+use bootloader_tool::{Config, Hints};
+
+const TOML: &str = r#"
+artifacts_path = "./artifacts"
+otp_path = "./artifacts/otp_master_key.txt"
+certificates = [[{ path = "./artifacts/cert-rot1.pem" }]]
+
+[application]
+slot_starts = [0x08020000, 0x08120000]
+run_start = 0x10000000
+slot_size = 0x100000
+
+[profiles.prod]
+certificates = [[{ path = "./prod/cert-rot1.pem" }]]
+application = { slot_starts = [0x08040000] }
+"#;
+
+const YAML: &str = r#"
+artifacts_path: ./artifacts
+otp_path: ./artifacts/otp_master_key.txt
+certificates:
+  - - path: ./artifacts/cert-rot1.pem
+application:
+  slot_starts: [0x08020000, 0x08120000]
+  run_start: 0x10000000
+  slot_size: 0x100000
+profiles:
+  prod:
+    certificates:
+      - - path: ./prod/cert-rot1.pem
+    application:
+      slot_starts: [0x08040000]
+"#;
+
+fn write(dir: &Path, name: &str, contents: &str) -> std::path::PathBuf {
+    let path = dir.join(name);
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+fn read(path: &Path, profile: Option<&str>) -> String {
+    format!("{:?}", Config::read_profile(path, profile).unwrap())
+}
+
+#[test]
+fn formats_are_equivalent() {
+    let dir = tempfile::tempdir().unwrap();
+    let toml = write(dir.path(), "config.toml", TOML);
+    let yaml = write(dir.path(), "config.yaml", YAML);
+    let value: serde_json::Value = serde_yaml::from_str(YAML).unwrap();
+    let json = write(dir.path(), "config.json", &value.to_string());
+
+    for profile in [None, Some("prod")] {
+        assert_eq!(read(&toml, profile), read(&yaml, profile));
+        assert_eq!(read(&toml, profile), read(&json, profile));
+    }
+}
+
+#[test]
+fn profile_overrides_base() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write(dir.path(), "config.toml", TOML);
+
+    let base = Config::read(&path).unwrap();
+    assert_eq!(base.application.as_ref().unwrap().slot_starts, [0x08020000, 0x08120000]);
+
+    let prod = Config::read_profile(&path, Some("prod")).unwrap();
+    assert_eq!(prod.certificates[0].0[0].path, Path::new("./prod/cert-rot1.pem"));
+    let application = prod.application.unwrap();
+    // Lists are replaced, whilst tables are merged.
+    assert_eq!(application.slot_starts, [0x08040000]);
+    assert_eq!(application.slot_size, 0x100000);
+
+    assert!(Config::read_profile(&path, Some("staging")).is_err());
+}
+
+#[test]
+fn includes_are_overridden() {
+    let dir = tempfile::tempdir().unwrap();
+    write(dir.path(), "base.toml", TOML);
+    let path = write(
+        dir.path(),
+        "device.yaml",
+        "include: [base.toml]\nartifacts_path: ./device\napplication:\n  slot_size: 0x80000\n",
+    );
+
+    let config = Config::read_profile(&path, Some("prod")).unwrap();
+    assert_eq!(config.artifacts_path, Path::new("./device"));
+    let application = config.application.unwrap();
+    assert_eq!(application.slot_size, 0x80000);
+    assert_eq!(application.slot_starts, [0x08040000]);
+
+    let cycle = write(dir.path(), "cycle.toml", "include = [\"cycle.toml\"]\n");
+    assert!(Config::read(&cycle).is_err());
+}
+
+#[test]
+fn bootloader_in_place() {
+    let dir = tempfile::tempdir().unwrap();
+    let bootloader = |run_start: u32| {
+        let path = write(
+            dir.path(),
+            "bootloader.toml",
+            &format!(
+                "include = [\"base.toml\"]\n[bootloader]\nflash_start = 0x08001000\nrun_start = {run_start:#x}\nmax_size = 0x8000\nstate = {{ start = 0x0800B000, size = 0x2000 }}\n"
+            ),
+        );
+        Config::read(&path).unwrap().bootloader.unwrap()
+    };
+    write(dir.path(), "base.toml", TOML);
+
+    assert!(!bootloader(0x10170000).executes_in_place());
+    assert!(bootloader(0x08001000).executes_in_place());
+}
+
+#[test]
+fn root_key_slots() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = |slots: &str| {
+        let path = write(
+            dir.path(),
+            "slots.toml",
+            &format!(
+                "artifacts_path = \"./artifacts\"\notp_path = \"./otp.txt\"\ncertificates = [[{{ path = \"./a.pem\" }}], [{{ path = \"./b.pem\" }}], [{{ path = \"./c.pem\" }}]]\n{slots}"
+            ),
+        );
+        Config::read(&path)
+    };
+
+    let in_order = config("").unwrap();
+    assert_eq!(in_order.root_key_chains().unwrap(), [0, 1, 2]);
+    assert_eq!(in_order.root_key_slot(2).unwrap(), 2);
+
+    // A new root key staged in the first slot, whilst the one it replaces is no longer used.
+    let rotated = config("root_key_slots = [2, 1]").unwrap();
+    assert_eq!(rotated.root_key_slot(2).unwrap(), 0);
+    assert_eq!(rotated.root_key_slot(1).unwrap(), 1);
+    assert!(rotated.root_key_slot(0).is_err());
+
+    assert!(config("root_key_slots = [0, 0]").is_err());
+    assert!(config("root_key_slots = [3]").is_err());
+    assert!(config("root_key_slots = [0, 1, 2, 0, 1]").is_err());
+}
+
+#[test]
+fn hints_of_incomplete_config() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write(
+        dir.path(),
+        "config.toml",
+        &format!(
+            "{TOML}\n[profiles.dev]\n\n[profiles.rotated]\nroot_key_slots = [1, 0]\ncertificates = [[{{ path = \"./a.pem\" }}], {{ bundle = \"./b.p12\" }}]\n"
+        ),
+    );
+
+    let hints = Hints::read(&path, None).unwrap();
+    assert_eq!(hints.profiles, ["dev", "prod", "rotated"]);
+    assert_eq!(hints.certificates, [(0, "./artifacts/cert-rot1.pem".to_owned())]);
+    assert_eq!(hints.slot_starts, [0x08020000, 0x08120000]);
+
+    let prod = Hints::read(&path, Some("prod")).unwrap();
+    assert_eq!(prod.certificates, [(0, "./prod/cert-rot1.pem".to_owned())]);
+    assert_eq!(prod.slot_starts, [0x08040000]);
+
+    // Chains in the order of the root key table, which the configuration could not be read with as it lacks keys.
+    let rotated = Hints::read(&path, Some("rotated")).unwrap();
+    assert_eq!(
+        rotated.certificates,
+        [(1, "./b.p12".to_owned()), (0, "./a.pem".to_owned())]
+    );
+
+    assert!(Hints::read(&path, Some("missing")).is_err());
+}
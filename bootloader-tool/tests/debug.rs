@@ -0,0 +1,33 @@
+//! Debug access configuration written by `lock` and interpreted by `unlock`.
+
+use bootloader_tool::processors::debug::{self, DebugAccess, Feature, UUID_CHECK};
+
+#[test]
+fn locked_restricts_all_but_allowed() {
+    let access = DebugAccess::locked(&[], false);
+    assert_eq!(access.socu, 0x1ff);
+    assert_eq!(access.socu_ns, !0x1ff);
+    assert!(!access.is_open());
+    assert_eq!(access.restricted().unwrap(), Feature::ALL);
+
+    let access = DebugAccess::locked(&[Feature::Niden, Feature::MeCmd], true);
+    assert_eq!(access.socu, 0x0fe | UUID_CHECK);
+    assert_eq!(access.restricted().unwrap(), &Feature::ALL[1..8]);
+}
+
+#[test]
+fn unprogrammed_is_open() {
+    let access = DebugAccess { socu: 0, socu_ns: 0 };
+    assert!(access.is_open());
+    assert!(access.restricted().unwrap().is_empty());
+
+    // A redundant copy that does not match is not trusted.
+    let access = DebugAccess { socu: 0x2, socu_ns: 0 };
+    assert!(access.restricted().is_err());
+}
+
+#[test]
+fn probe_serial() {
+    assert_eq!(debug::probe_serial("1366:0101:000123456"), Some("000123456"));
+    assert_eq!(debug::probe_serial("1366:0101"), None);
+}
@@ -0,0 +1,55 @@
+//! Slot regions and contents for `slot dump` and `slot write`.
+
+use bootloader_tool::Config;
+use bootloader_tool::processors::device;
+
+const CONFIG: &str = r#"
+artifacts_path = "./artifacts"
+otp_path = "./artifacts/otp_master_key.txt"
+certificates = []
+
+[application]
+slot_starts = [0x0800D000, 0x080F9000]
+run_start = 0x10020000
+slot_size = 0xEC000
+"#;
+
+#[test]
+fn slot_regions() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.toml");
+    std::fs::write(&path, CONFIG).unwrap();
+    let config = Config::read(&path).unwrap();
+
+    assert_eq!(device::slot_region(&config, 0).unwrap(), 0x0800D000..0x080F9000);
+    assert_eq!(device::slot_region(&config, 1).unwrap(), 0x080F9000..0x081E5000);
+    assert!(device::slot_region(&config, 2).is_err());
+}
+
+#[test]
+fn slot_contents_are_padded() {
+    let contents = device::slot_contents(&[0x5a; 3], 8).unwrap();
+    assert_eq!(contents, [0x5a, 0x5a, 0x5a, 0xff, 0xff, 0xff, 0xff, 0xff]);
+
+    assert_eq!(device::slot_contents(&[0; 8], 8).unwrap(), [0; 8]);
+    assert!(device::slot_contents(&[0; 9], 8).is_err());
+}
+
+#[test]
+fn mismatched_sectors_are_merged() {
+    let start = 0x0800_1800;
+    let expected = vec![0x5a; 0x3000];
+    assert!(device::mismatched_sectors(start, &expected, &expected).is_empty());
+
+    let mut actual = expected.clone();
+    // First sector, only partially covered by the image.
+    actual[0x10] = 0;
+    // Two bytes in the same sector, and one in the next.
+    actual[0x1800] = 0;
+    actual[0x1fff] = 0;
+    actual[0x2800] = 0;
+    assert_eq!(
+        device::mismatched_sectors(start, &expected, &actual),
+        [0x0800_1000..0x0800_2000, 0x0800_3000..0x0800_5000]
+    );
+}
@@ -0,0 +1,55 @@
+//! Classification of errors into kinds with stable exit codes.
+
+use std::process::Command;
+
+use anyhow::Context;
+use bootloader_tool::error::{Classify, ErrorKind, Report};
+
+#[test]
+fn classification_survives_context() {
+    let error = Err::<(), _>(anyhow::anyhow!("bad signature"))
+        .classify(ErrorKind::Verification)
+        .context("Could not verify image")
+        .unwrap_err();
+    assert_eq!(ErrorKind::of(&error), ErrorKind::Verification);
+
+    // Classifying does not change the messages.
+    let report = Report::new(&error);
+    assert_eq!(report.message, "Could not verify image");
+    assert_eq!(report.causes, ["bad signature"]);
+    assert_eq!(report.exit_code, 6);
+
+    // The outermost classification takes precedence.
+    let error = Err::<(), _>(error).classify(ErrorKind::Probe).unwrap_err();
+    assert_eq!(ErrorKind::of(&error), ErrorKind::Probe);
+}
+
+#[test]
+fn classification_by_type() {
+    let error = std::fs::read("/nonexistent/image.bin")
+        .context("Could not read image")
+        .unwrap_err();
+    assert_eq!(ErrorKind::of(&error), ErrorKind::Io);
+
+    assert_eq!(ErrorKind::of(&anyhow::anyhow!("anything")), ErrorKind::Other);
+}
+
+#[test]
+fn json_output_of_invalid_config() {
+    let output = Command::new(env!("CARGO_BIN_EXE_bootloader-tool"))
+        .args([
+            "--config",
+            "/nonexistent/config.toml",
+            "--output",
+            "json",
+            "clean",
+            "--keys",
+        ])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(3));
+
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["kind"], "config");
+    assert_eq!(report["exit_code"], 3);
+}
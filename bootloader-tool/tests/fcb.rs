@@ -0,0 +1,54 @@
+//! FCB generation from the example flash description, compared with the hard-coded `imxrt-fcb-1spi-a1-nor` FCB.
+
+use bootloader_tool::processors::fcb::{self, FCB_LEN, FlashDescription};
+
+fn read_u32(fcb: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(fcb[offset..offset + 4].try_into().unwrap())
+}
+
+#[test]
+fn example_matches_1spi_a1_nor() {
+    let fcb = FlashDescription::read("fcb/1spi-a1-nor.toml").unwrap().build().unwrap();
+    assert_eq!(fcb.len(), FCB_LEN);
+
+    assert_eq!(&fcb[0..4], b"FCFB");
+    assert_eq!(read_u32(&fcb, 0x040), 0x10, "controller misc option");
+    assert_eq!(fcb[0x045], 4, "pad type");
+    assert_eq!(fcb[0x046], 2, "serial clock frequency");
+    assert_eq!(read_u32(&fcb, 0x050), 0x0040_0000, "A1 size");
+    assert_eq!(read_u32(&fcb, 0x058), 0, "B1 size");
+
+    // flexspi_lut_seq(CMD_SDR, Single, 0x03, RADDR_SDR, Single, 0x18)
+    assert_eq!(read_u32(&fcb, 0x080), 0x0818_0403);
+    // flexspi_lut_seq(READ_SDR, Single, 0x80, STOP, Single, 0x00)
+    assert_eq!(read_u32(&fcb, 0x084), 0x0000_2480);
+    assert!(fcb[0x088..0x180].iter().all(|b| *b == 0));
+}
+
+#[test]
+fn partition_image() {
+    let fcb = FlashDescription::read("fcb/1spi-a1-nor.toml").unwrap().build().unwrap();
+    let image = fcb::partition_image(&fcb);
+
+    assert_eq!(&image[..FCB_LEN], fcb.as_slice());
+    let crc = crc::Crc::<u32>::new(&crc::CRC_32_ISO_HDLC).checksum(&fcb);
+    assert_eq!(read_u32(&image, FCB_LEN), crc);
+    assert_eq!(image.len(), FCB_LEN + 4);
+}
+
+#[test]
+fn invalid_descriptions() {
+    let parse = |toml: &str| toml::from_str::<FlashDescription>(toml);
+    let build = |toml: &str| parse(toml).unwrap().build();
+
+    // No read sequence.
+    assert!(build("pads = 1\nfrequency_mhz = 50\na1_size = 0x1000").is_err());
+    // No flash size.
+    assert!(build("pads = 1\nfrequency_mhz = 50\n[[sequence]]\nid = \"read\"\ninstructions = []").is_err());
+    // Frequency not supported by the ROM.
+    assert!(build("pads = 1\nfrequency_mhz = 42\na1_size = 0x1000\n[[sequence]]\nid = 0\ninstructions = []").is_err());
+    // Invalid number of pads.
+    assert!(parse("pads = 3\nfrequency_mhz = 50").is_err());
+    // Sequence outside the lookup table.
+    assert!(build("pads = 1\nfrequency_mhz = 50\na1_size = 0x1000\n[[sequence]]\nid = 16\ninstructions = []").is_err());
+}
@@ -0,0 +1,87 @@
+//! Headers decoded by `inspect mbi` from arbitrary binaries.
+
+use bootloader_tool::processors::mbi::headers::Headers;
+use bootloader_tool::processors::mbi::{ImageKind, ImageType};
+use mbi_format::{CertBlockHeader, Ivt};
+
+const HEADER_OFFSET: usize = 0x100;
+const CERT_TABLE_LEN: usize = 0x10;
+const SIGNATURE_LEN: usize = 0x100;
+
+/// Image without HMAC of which the cert block holds an empty certificate table and root key hashes filled with their slot
+fn image() -> Vec<u8> {
+    let cert_block_len = CertBlockHeader::LEN + CERT_TABLE_LEN + 4 * 32;
+    let signed_len = HEADER_OFFSET + cert_block_len;
+    let mut image = vec![0; signed_len + SIGNATURE_LEN];
+
+    Ivt {
+        image_len: image.len() as u32,
+        image_type: ImageType::new(ImageKind::XipPlainSigned).as_u32(),
+        header_offset: HEADER_OFFSET as u32,
+        load_addr: 0x0800_1000,
+    }
+    .write(&mut image)
+    .unwrap();
+    CertBlockHeader {
+        signature: u32::from_le_bytes(*b"cert"),
+        header_major_version: 1,
+        header_minor_version: 0,
+        header_length: CertBlockHeader::LEN as u32,
+        flags: 0,
+        build_number: 7,
+        total_image_length: signed_len as u32,
+        certificate_count: 0,
+        certificate_table_length: CERT_TABLE_LEN as u32,
+    }
+    .write(&mut image[HEADER_OFFSET..])
+    .unwrap();
+    let root_key_hashes = HEADER_OFFSET + CertBlockHeader::LEN + CERT_TABLE_LEN;
+    for (slot, hash) in image[root_key_hashes..signed_len].chunks_exact_mut(32).enumerate() {
+        hash.fill(slot as u8);
+    }
+
+    image
+}
+
+#[test]
+fn decodes_all_headers() {
+    let headers = Headers::decode(&image()).unwrap();
+    assert_eq!(headers.ivt.load_addr, 0x0800_1000);
+    assert_eq!(headers.cert_block_offset, HEADER_OFFSET);
+    assert_eq!(headers.cert_block.unwrap().build_number, 7);
+    assert_eq!(headers.root_key_hashes.unwrap()[3], [3; 32]);
+    assert!(headers.rkth().is_some());
+    assert_eq!(headers.signature_len, Some(SIGNATURE_LEN));
+
+    let printed = headers.to_string();
+    assert!(printed.contains("image kind:              XipPlainSigned"));
+    assert!(printed.contains("build number:       7"));
+    assert!(printed.contains(&format!("RKTH: {}", headers.rkth().unwrap().as_hex())));
+    assert!(printed.ends_with("Signature: 256 bytes"));
+}
+
+#[test]
+fn stops_at_end_of_binary() {
+    let image = image();
+    assert!(Headers::decode(&image[..Ivt::LEN - 1]).is_err());
+
+    let headers = Headers::decode(&image[..HEADER_OFFSET]).unwrap();
+    assert_eq!(headers.cert_block, None);
+    assert!(headers.to_string().ends_with("beyond the end of the binary"));
+
+    let headers = Headers::decode(&image[..HEADER_OFFSET + CertBlockHeader::LEN]).unwrap();
+    assert!(headers.cert_block.is_some());
+    assert_eq!(headers.root_key_hashes, None);
+    assert_eq!(headers.signature_len, None);
+}
+
+#[test]
+fn unknown_image_kind() {
+    let mut image = image();
+    image[0x24] = 0x06;
+
+    let headers = Headers::decode(&image).unwrap();
+    assert!(headers.to_string().contains("image kind:              unknown (0x6)"));
+    // Without a known image kind there is no HMAC to skip
+    assert_eq!(headers.cert_block_offset, HEADER_OFFSET);
+}
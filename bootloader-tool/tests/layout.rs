@@ -0,0 +1,64 @@
+//! Rust constants of the flash layout generated by `generate layout-rs`.
+
+use bootloader_tool::Config;
+use bootloader_tool::processors::layout;
+
+fn config(sections: &str) -> Config {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.toml");
+    let config = format!(
+        r#"
+artifacts_path = "./artifacts"
+otp_path = "./artifacts/otp_master_key.txt"
+certificates = [[{{ path = "./artifacts/cert-rot1.pem" }}]]
+{sections}
+"#
+    );
+    std::fs::write(&path, config).unwrap();
+    Config::read(&path).unwrap()
+}
+
+#[test]
+fn layout_constants() {
+    let rust = layout::to_rust(&config(
+        r#"
+[bootloader]
+flash_start = 0x08001000
+run_start = 0x10170000
+max_size = 0x8000
+state = { start = 0x0800B000, size = 0x2000 }
+
+[application]
+slot_starts = [0x800D000, 0x80F9000]
+run_start = 0x10020000
+slot_size = 0xEC000
+"#,
+    ))
+    .unwrap();
+
+    for line in [
+        "pub const BOOTLOADER_START: u32 = 0x08001000;",
+        "pub const STATE_START: u32 = 0x0800b000;",
+        "pub const STATE_OFFSET: u32 = 0x0000b000;",
+        "pub const STATE_SIZE: u32 = 0x00002000;",
+        "pub const SLOT_STARTS: [u32; 2] = [0x0800d000, 0x080f9000];",
+        "pub const SLOT_OFFSETS: [u32; 2] = [0x0000d000, 0x000f9000];",
+        "pub const SLOT_SIZE: u32 = 0x000ec000;",
+        "pub const LOAD_RANGE: core::ops::Range<u32> = 0x10020000..0x1010c000;",
+    ] {
+        assert!(rust.lines().any(|l| l == line), "missing {line} in\n{rust}");
+    }
+    assert!(!rust.contains("COUNTERS"));
+}
+
+#[test]
+fn layout_requires_flash_addresses() {
+    let application = r#"
+[application]
+slot_starts = [0x100000]
+run_start = 0x10020000
+slot_size = 0xEC000
+"#;
+    assert!(layout::to_rust(&config(application)).is_err());
+    assert!(layout::to_rust(&config("")).is_err());
+}
@@ -0,0 +1,73 @@
+//! Marking of images in the metadata trailer, by their role in the boot chain, whether they are staged and their digest.
+
+use bootloader_tool::processors::mbi::metadata::Metadata;
+use mbi_format::{ImageRole, Ivt, Trailer};
+use sha2::{Digest, Sha256};
+
+#[test]
+fn role_roundtrip() {
+    let metadata = Metadata {
+        version: Some("2.0.0".to_owned()),
+        role: Some(ImageRole::Loader),
+        ..Default::default()
+    };
+    let bytes = metadata.to_bytes().unwrap();
+    let trailer = Trailer::parse(&bytes).unwrap();
+    assert_eq!(trailer.role(), Some(ImageRole::Loader));
+    assert_eq!(Metadata::parse(&trailer), metadata);
+    assert_eq!(metadata.to_string(), "version 2.0.0, secondary loader");
+}
+
+#[test]
+fn role_absent_by_default() {
+    let metadata = Metadata {
+        version: Some("2.0.0".to_owned()),
+        ..Default::default()
+    };
+    let bytes = metadata.to_bytes().unwrap();
+    assert_eq!(Trailer::parse(&bytes).unwrap().role(), None);
+}
+
+#[test]
+fn staged_roundtrip() {
+    let metadata = Metadata {
+        staged: true,
+        ..Default::default()
+    };
+    assert!(!metadata.is_empty());
+    let bytes = metadata.to_bytes().unwrap();
+    let trailer = Trailer::parse(&bytes).unwrap();
+    assert!(trailer.staged());
+    assert_eq!(Metadata::parse(&trailer), metadata);
+    assert_eq!(metadata.to_string(), "staged");
+}
+
+#[test]
+fn image_digest_covers_image() {
+    let mut image = vec![0x5a; 0x100];
+    Ivt {
+        image_len: 0x80,
+        image_type: 0,
+        header_offset: 0x60,
+        load_addr: 0,
+    }
+    .write(&mut image)
+    .unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("image.bin");
+    std::fs::write(&path, &image).unwrap();
+
+    let mut metadata = Metadata::default();
+    metadata.digest_image(&path).unwrap();
+    let digest: [u8; 32] = Sha256::digest(&image[..0x80]).into();
+    assert_eq!(metadata.image_digest, Some(digest));
+
+    let bytes = metadata.to_bytes().unwrap();
+    let trailer = Trailer::parse(&bytes).unwrap();
+    assert_eq!(trailer.image_digest(), Some(digest));
+    assert_eq!(Metadata::parse(&trailer), metadata);
+
+    // An image shorter than its header claims has no digest.
+    std::fs::write(&path, &image[..0x7f]).unwrap();
+    metadata.digest_image(&path).unwrap_err();
+}
@@ -0,0 +1,79 @@
+//! Decoding of the boot status shown by `monitor`.
+
+use bootloader_tool::processors::monitor::{self, Status};
+use ec_slimloader_state::counters::{Counters, Event};
+use ec_slimloader_state::state::{Slot, State, Status as BootStatus};
+
+fn status(state: BootStatus, events: &[Event]) -> Status {
+    Status {
+        state: Some(State::new(state, Slot::S1, Slot::S0)),
+        counters: Some(
+            events
+                .iter()
+                .fold(Counters::default(), |counters, event| counters.with_event(*event)),
+        ),
+    }
+}
+
+#[test]
+fn status_display() {
+    let status = status(BootStatus::Attempting, &[Event::Boot, Event::AuthFailure(Slot::S1)]);
+    assert_eq!(
+        status.to_string(),
+        "Attempting, target S1, backup S0; boots 1, fallbacks 0, auth failures S1 1"
+    );
+
+    let empty = Status {
+        state: None,
+        counters: None,
+    };
+    assert_eq!(empty.to_string(), "journal empty or corrupt");
+}
+
+#[test]
+fn fallback_after_power_cycle() {
+    let attempting = status(BootStatus::Attempting, &[Event::Boot]);
+    let failed = status(
+        BootStatus::Failed,
+        &[Event::Boot, Event::AuthFailure(Slot::S1), Event::Fallback, Event::Boot],
+    );
+
+    assert_eq!(
+        monitor::events(&attempting, &failed),
+        ["Attempting -> Failed", "boot", "fallback", "auth failure in S1"]
+    );
+    assert!(monitor::events(&failed, &failed).is_empty());
+
+    // Counters going down were reset rather than counted.
+    let reset = status(BootStatus::Failed, &[]);
+    assert_eq!(
+        monitor::events(&failed, &reset),
+        [
+            "boot counter reset",
+            "fallback counter reset",
+            "auth failure in S1 counter reset"
+        ]
+    );
+}
+
+#[test]
+fn new_target() {
+    let confirmed = status(BootStatus::Confirmed, &[]);
+    let mut initial = status(BootStatus::Initial, &[]);
+    initial.state = Some(State::new(BootStatus::Initial, Slot::S0, Slot::S1));
+
+    assert_eq!(
+        monitor::events(&confirmed, &initial),
+        ["new target S0 with backup S1", "Confirmed -> Initial"]
+    );
+    assert_eq!(
+        monitor::events(
+            &Status {
+                state: None,
+                ..confirmed.clone()
+            },
+            &confirmed
+        ),
+        ["state journal written"]
+    );
+}
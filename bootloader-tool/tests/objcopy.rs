@@ -0,0 +1,155 @@
+//! Assembly of BIN images from ELF files, with segments that are not contiguous, and checks of their vector table.
+
+use bootloader_tool::processors::objcopy;
+use object::Endianness;
+use object::elf::{EM_ARM, ET_EXEC, PF_R, PT_LOAD};
+use object::read::elf::ElfFile32;
+use object::write::elf::{FileHeader, ProgramHeader, Writer};
+
+const BASE_ADDR: u32 = 0x08001000;
+const STACK_POINTER: u32 = 0x30128000;
+const RESET_VECTOR: u32 = BASE_ADDR + 0x131;
+
+/// Code of `len` bytes starting with a vector table with the given initial stack pointer and reset vector
+fn code_with_vectors(len: usize, stack_pointer: u32, reset_vector: u32) -> Vec<u8> {
+    let mut code = vec![0x11; len];
+    code[0..4].copy_from_slice(&stack_pointer.to_le_bytes());
+    code[4..8].copy_from_slice(&reset_vector.to_le_bytes());
+    code
+}
+
+fn code(len: usize) -> Vec<u8> {
+    code_with_vectors(len, STACK_POINTER, RESET_VECTOR)
+}
+
+/// ELF with a loadable segment for each `(paddr, data)`, in the given order
+fn elf_with_entry(entry: u32, segments: &[(u32, &[u8])]) -> Vec<u8> {
+    let mut out = vec![];
+    let mut writer = Writer::new(Endianness::Little, false, &mut out);
+    writer.reserve_file_header();
+    writer.reserve_program_headers(segments.len() as u32);
+    let offsets: Vec<usize> = segments.iter().map(|(_, data)| writer.reserve(data.len(), 4)).collect();
+
+    writer
+        .write_file_header(&FileHeader {
+            os_abi: 0,
+            abi_version: 0,
+            e_type: ET_EXEC,
+            e_machine: EM_ARM,
+            e_entry: entry as u64,
+            e_flags: 0,
+        })
+        .unwrap();
+    writer.write_align_program_headers();
+    for ((paddr, data), offset) in segments.iter().zip(&offsets) {
+        writer.write_program_header(&ProgramHeader {
+            p_type: PT_LOAD,
+            p_flags: PF_R,
+            p_offset: *offset as u64,
+            p_vaddr: *paddr as u64,
+            p_paddr: *paddr as u64,
+            p_filesz: data.len() as u64,
+            p_memsz: data.len() as u64,
+            p_align: 4,
+        });
+    }
+    for ((_, data), offset) in segments.iter().zip(&offsets) {
+        writer.pad_until(*offset);
+        writer.write(data);
+    }
+    out
+}
+
+fn elf(segments: &[(u32, &[u8])]) -> Vec<u8> {
+    elf_with_entry(RESET_VECTOR, segments)
+}
+
+#[test]
+fn gaps_are_filled_with_erased_flash() {
+    let data = elf(&[(BASE_ADDR, &code(0x200)), (BASE_ADDR + 0x400, &[0x22; 0x10])]);
+    let file = ElfFile32::parse(&data[..]).unwrap();
+
+    let (image, base_addr) = objcopy::objcopy(&file, objcopy::DEFAULT_MAX_GAP).unwrap();
+    assert_eq!(base_addr, BASE_ADDR);
+    assert_eq!(image.len(), 0x410);
+    assert_eq!(image[..0x200], code(0x200));
+    assert!(image[0x200..0x400].iter().all(|&b| b == 0xFF));
+    assert!(image[0x400..].iter().all(|&b| b == 0x22));
+}
+
+#[test]
+fn segments_out_of_order() {
+    let data = elf(&[(BASE_ADDR + 0x200, &[0x22; 0x10]), (BASE_ADDR, &code(0x200))]);
+    let file = ElfFile32::parse(&data[..]).unwrap();
+
+    let (image, base_addr) = objcopy::objcopy(&file, objcopy::DEFAULT_MAX_GAP).unwrap();
+    assert_eq!(base_addr, BASE_ADDR);
+    assert_eq!(image.len(), 0x210);
+    assert_eq!(image[..0x200], code(0x200));
+    assert!(image[0x200..].iter().all(|&b| b == 0x22));
+}
+
+#[test]
+fn gap_exceeding_limit() {
+    let data = elf(&[(BASE_ADDR, &code(0x200)), (BASE_ADDR + 0x1200, &[0x22; 0x10])]);
+    let file = ElfFile32::parse(&data[..]).unwrap();
+
+    assert!(objcopy::objcopy(&file, 0x1000).is_ok());
+    let error = objcopy::objcopy(&file, 0xfff).unwrap_err();
+    assert!(error.to_string().contains("exceeds the maximum gap"), "{error}");
+}
+
+#[test]
+fn overlapping_segments() {
+    let data = elf(&[(BASE_ADDR, &code(0x200)), (BASE_ADDR + 0x100, &[0x22; 0x10])]);
+    let file = ElfFile32::parse(&data[..]).unwrap();
+
+    let error = objcopy::objcopy(&file, objcopy::DEFAULT_MAX_GAP).unwrap_err();
+    assert!(error.to_string().contains("overlaps"), "{error}");
+}
+
+#[test]
+fn entry_beyond_image() {
+    let data = elf(&[(BASE_ADDR, &code(0x100))]);
+    let file = ElfFile32::parse(&data[..]).unwrap();
+
+    let error = objcopy::objcopy(&file, objcopy::DEFAULT_MAX_GAP).unwrap_err();
+    assert!(error.to_string().contains("not within the image"), "{error}");
+}
+
+#[test]
+fn misaligned_vector_table() {
+    let base_addr = BASE_ADDR + 0x40;
+    let code = code_with_vectors(0x200, STACK_POINTER, base_addr + 0x131);
+    let data = elf_with_entry(base_addr + 0x131, &[(base_addr, &code)]);
+    let file = ElfFile32::parse(&data[..]).unwrap();
+
+    let error = objcopy::objcopy(&file, objcopy::DEFAULT_MAX_GAP).unwrap_err();
+    assert!(error.to_string().contains("not aligned to 128 bytes"), "{error}");
+}
+
+#[test]
+fn invalid_vectors() {
+    let check = |stack_pointer, reset_vector| {
+        let data = elf(&[(BASE_ADDR, &code_with_vectors(0x200, stack_pointer, reset_vector))]);
+        let file = ElfFile32::parse(&data[..]).unwrap();
+        objcopy::objcopy(&file, objcopy::DEFAULT_MAX_GAP)
+            .map(|_| ())
+            .unwrap_err()
+            .to_string()
+    };
+
+    assert!(check(0x08100000, RESET_VECTOR).contains("not within RAM"));
+    assert!(check(0x30000000, RESET_VECTOR).contains("not within RAM"));
+    assert!(check(0x30128004, RESET_VECTOR).contains("not aligned"));
+    assert!(check(STACK_POINTER, RESET_VECTOR - 1).contains("Thumb"));
+    assert!(check(STACK_POINTER, BASE_ADDR + 0x201).contains("not within the image"));
+}
+
+#[test]
+fn within_ram() {
+    assert!(objcopy::within_ram(0x10020000..0x10180000));
+    assert!(objcopy::within_ram(0x30000000..0x30480000));
+    assert!(!objcopy::within_ram(0x08001000..0x08009000));
+    assert!(!objcopy::within_ram(0x10470000..0x10490000));
+}
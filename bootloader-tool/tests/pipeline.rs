@@ -0,0 +1,57 @@
+//! Concurrent invocations of external tools, and reuse of generated cert blocks.
+
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use bootloader_tool::Config;
+use bootloader_tool::processors::mbi::cert_block;
+use bootloader_tool::processors::pipeline;
+
+#[test]
+fn run_keeps_order_and_bound() {
+    let running = AtomicUsize::new(0);
+    let most = AtomicUsize::new(0);
+
+    let results = pipeline::run((0..16).collect(), NonZeroUsize::new(3).unwrap(), |i: u32| {
+        most.fetch_max(running.fetch_add(1, Ordering::SeqCst) + 1, Ordering::SeqCst);
+        std::thread::sleep(Duration::from_millis(5));
+        running.fetch_sub(1, Ordering::SeqCst);
+        i * 2
+    });
+
+    assert_eq!(results, (0..16).map(|i| i * 2).collect::<Vec<_>>());
+    assert!(most.load(Ordering::SeqCst) <= 3);
+    assert!(pipeline::run(Vec::<u32>::new(), NonZeroUsize::MIN, |i| i).is_empty());
+}
+
+#[test]
+fn cert_block_cache_follows_certificates() {
+    let dir = tempfile::tempdir().unwrap();
+    let config_path = dir.path().join("config.toml");
+    let config = format!(
+        r#"
+artifacts_path = "{artifacts}"
+otp_path = "{artifacts}/otp_master_key.txt"
+certificates = [[{{ path = "{artifacts}/cert-rot1.pem" }}], [{{ path = "{artifacts}/cert-rot2.pem" }}]]
+"#,
+        artifacts = dir.path().display().to_string().replace('\\', "/")
+    );
+    std::fs::write(&config_path, config).unwrap();
+    let config = Config::read(&config_path).unwrap();
+
+    std::fs::write(dir.path().join("cert-rot1.pem"), "first").unwrap();
+    let key = cert_block::cache_key(&config, 0).unwrap();
+    assert_eq!(
+        cert_block::cache_path(&config, &key),
+        dir.path().join("cert-blocks").join(format!("{key}.bin"))
+    );
+    assert_eq!(cert_block::cache_key(&config, 0).unwrap(), key);
+
+    // Each chain has its own cert block.
+    assert_ne!(cert_block::cache_key(&config, 1).unwrap(), key);
+
+    // Any certificate in the block invalidates the cache, including those of other chains.
+    std::fs::write(dir.path().join("cert-rot2.pem"), "second").unwrap();
+    assert_ne!(cert_block::cache_key(&config, 0).unwrap(), key);
+}
@@ -0,0 +1,52 @@
+//! Operations printed instead of performed with `--dry-run`.
+
+use bootloader_tool::processors::fuse;
+use bootloader_tool::processors::plan::{self, Operation};
+
+#[test]
+fn flash_shows_range_and_source() {
+    let operation = Operation::Flash {
+        address: 0x0800_1000,
+        len: 0x2000,
+        source: "example-bootloader.signed.bin".to_owned(),
+    };
+    assert_eq!(
+        operation.to_string(),
+        "flash 0x08001000..0x08003000 (0x2000 bytes) from example-bootloader.signed.bin"
+    );
+}
+
+#[test]
+fn flash_bin_takes_file_length() {
+    let file = tempfile::NamedTempFile::new().unwrap();
+    std::fs::write(file.path(), [0u8; 100]).unwrap();
+
+    let Operation::Flash { address, len, .. } = plan::flash_bin(file.path(), 0x0810_0000).unwrap() else {
+        panic!("not a flash write");
+    };
+    assert_eq!((address, len), (0x0810_0000, 100));
+}
+
+#[test]
+fn run_keeps_master_key_hidden() {
+    let rkth = [0x1111_1111; 8];
+    let operations = plan::run_shadow_writes(&rkth, 0x3d90_4075, 0x0031_4000);
+
+    let lines: Vec<String> = operations.iter().map(Operation::to_string).collect();
+    assert!(lines[0].starts_with("write RKTH (word 120, 0x401301e0): 0x11111111"));
+    assert_eq!(
+        lines[1],
+        "write OTP_MASTER_KEY (word 112, 0x401301c0): 8 words, not shown"
+    );
+    assert_eq!(lines[2], "write BOOT_CFG0 (word 96, 0x40130180): 0x3d904075");
+    assert_eq!(
+        operations[4],
+        Operation::Clear {
+            word: fuse::Word {
+                index: fuse::SEC_BOOT_CFG5,
+                name: "SEC_BOOT_CFG5",
+            },
+            mask: fuse::SEC_BOOT_CFG5_USE_PUF,
+        }
+    );
+}
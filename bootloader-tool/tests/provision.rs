@@ -0,0 +1,77 @@
+//! Checkpoints of `provision device`, to resume after the last completed step.
+
+use bootloader_tool::processors::provision::{Checkpoint, Step};
+
+#[test]
+fn steps_in_order() {
+    assert_eq!(
+        Step::all(true),
+        [Step::Prelude, Step::Bootloader, Step::Application, Step::State, Step::Fuses]
+    );
+    assert_eq!(
+        Step::all(false),
+        [Step::Prelude, Step::Bootloader, Step::State, Step::Fuses]
+    );
+}
+
+#[test]
+fn resume_completed_steps() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("provision-checkpoint.json");
+    assert!(Checkpoint::resume(&path, "inputs", "device-a").is_none());
+
+    let mut checkpoint = Checkpoint::new("inputs", "device-a");
+    checkpoint.complete(Step::Prelude, &path).unwrap();
+    checkpoint.complete(Step::Bootloader, &path).unwrap();
+    checkpoint.complete(Step::Bootloader, &path).unwrap();
+
+    let resumed = Checkpoint::resume(&path, "inputs", "device-a").unwrap();
+    assert_eq!(resumed.completed, [Step::Prelude, Step::Bootloader]);
+    assert!(resumed.is_completed(Step::Bootloader));
+    assert!(!resumed.is_completed(Step::State));
+
+    // Steps completed for other inputs are outdated.
+    assert!(Checkpoint::resume(&path, "changed", "device-a").is_none());
+}
+
+#[test]
+fn start_over_on_another_device() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("provision-checkpoint.json");
+
+    // Provisioning failed on one device after flashing the prelude and the bootloader.
+    let mut checkpoint = Checkpoint::new("inputs", "device-a");
+    checkpoint.complete(Step::Prelude, &path).unwrap();
+    checkpoint.complete(Step::Bootloader, &path).unwrap();
+
+    // None of these steps were done on the device swapped in for it.
+    assert!(Checkpoint::resume(&path, "inputs", "device-b").is_none());
+    assert_eq!(
+        Checkpoint::resume(&path, "inputs", "device-a").unwrap().completed,
+        [Step::Prelude, Step::Bootloader]
+    );
+}
+
+#[test]
+fn start_over_without_device() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("provision-checkpoint.json");
+
+    // Checkpoints recorded before the device was identified are not resumed from.
+    std::fs::write(&path, r#"{"fingerprint": "inputs", "completed": ["prelude"]}"#).unwrap();
+    assert!(Checkpoint::resume(&path, "inputs", "device-a").is_none());
+}
+
+#[test]
+fn checkpoint_names_steps() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("provision-checkpoint.json");
+    Checkpoint::new("inputs", "device-a")
+        .complete(Step::Fuses, &path)
+        .unwrap();
+
+    let json: serde_json::Value = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+    assert_eq!(json["completed"], serde_json::json!(["fuses"]));
+    assert_eq!(json["device"], "device-a");
+    assert_eq!(Step::Fuses.to_string(), "fuses");
+}
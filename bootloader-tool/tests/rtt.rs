@@ -0,0 +1,45 @@
+//! Locating the RTT control block and defmt table of the stages of the boot chain in their ELF files.
+
+use bootloader_tool::processors::rtt::Stage;
+use object::write::{Object, Symbol, SymbolSection};
+use object::{Architecture, BinaryFormat, Endianness, SectionKind, SymbolFlags, SymbolKind, SymbolScope};
+
+/// ELF with a `.bss` section at `bss_addr`, optionally holding the RTT control block and a `.defmt` section
+fn elf(bss_addr: u64, control_block: bool, defmt: bool) -> Vec<u8> {
+    let mut object = Object::new(BinaryFormat::Elf, Architecture::Arm, Endianness::Little);
+    let bss = object.add_section(vec![], b".bss".to_vec(), SectionKind::UninitializedData);
+    object.append_section_bss(bss, 0x100, 4);
+
+    if control_block {
+        object.add_symbol(Symbol {
+            name: b"_SEGGER_RTT".to_vec(),
+            value: bss_addr + 0x20,
+            size: 0x30,
+            kind: SymbolKind::Data,
+            scope: SymbolScope::Linkage,
+            weak: false,
+            section: SymbolSection::Absolute,
+            flags: SymbolFlags::None,
+        });
+    }
+    if defmt {
+        let section = object.add_section(vec![], b".defmt".to_vec(), SectionKind::Other);
+        object.append_section_data(section, &[0], 1);
+    }
+    object.write().unwrap()
+}
+
+#[test]
+fn stage_from_elf() {
+    let bootloader = Stage::from_elf("bootloader", "bootloader.elf", &elf(0x10170000, true, true)).unwrap();
+    assert_eq!(bootloader.control_block, 0x10170020);
+    assert!(bootloader.defmt);
+
+    let application = Stage::from_elf("application", "application.elf", &elf(0x10000000, true, false)).unwrap();
+    assert_eq!(application.control_block, 0x10000020);
+    assert!(!application.defmt);
+
+    let error = Stage::from_elf("application", "application.elf", &elf(0x10000000, false, true)).unwrap_err();
+    assert!(format!("{error:#}").contains("_SEGGER_RTT"));
+    assert!(Stage::from_elf("application", "application.elf", b"not an ELF").is_err());
+}
@@ -0,0 +1,37 @@
+//! Reuse of signed images by `download` and `run` when nothing changed since signing.
+
+use bootloader_tool::processors::artifacts::{Artifact, ArtifactLayout};
+use bootloader_tool::processors::certificates::Rkth;
+use bootloader_tool::processors::sign_cache;
+
+#[test]
+fn lookup_stored_entry() {
+    let dir = tempfile::tempdir().unwrap();
+    let input = dir.path().join("example-application");
+    let output = input.with_extension("signed.bin");
+    let cache = ArtifactLayout::in_dir(dir.path(), &input).path(Artifact::SignCache);
+    assert_eq!(cache, dir.path().join("example-application.sign-cache"));
+
+    let rkth = Rkth([0x5a; 32]);
+    std::fs::write(&output, [0xaa; 64]).unwrap();
+    assert!(sign_cache::lookup(&cache, "key").is_none());
+
+    sign_cache::store(&cache, "key", &output, &rkth).unwrap();
+    assert_eq!(sign_cache::lookup(&cache, "key"), Some((output.clone(), rkth)));
+
+    // Other inputs miss.
+    assert!(sign_cache::lookup(&cache, "other").is_none());
+
+    // A changed or removed output file misses as well.
+    std::fs::write(&output, [0xbb; 64]).unwrap();
+    assert!(sign_cache::lookup(&cache, "key").is_none());
+    std::fs::remove_file(&output).unwrap();
+    assert!(sign_cache::lookup(&cache, "key").is_none());
+}
+
+#[test]
+fn store_requires_output() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = dir.path().join("example-application.signed.bin");
+    assert!(sign_cache::store(dir.path().join("cache"), "key", &output, &Rkth([0; 32])).is_err());
+}
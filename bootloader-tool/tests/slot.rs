@@ -0,0 +1,90 @@
+//! Composition of slot images with blobs in fixed subregions, and their digests in the metadata trailer.
+
+use bootloader_tool::processors::mbi::metadata::Metadata;
+use bootloader_tool::processors::slot::{Blob, SlotLayout};
+use mbi_format::Trailer;
+use sha2::{Digest, Sha256};
+
+fn blob(name: &str, offset: u64, size: u64, data: &[u8], digest: bool) -> Blob {
+    Blob {
+        name: name.to_owned(),
+        offset,
+        size,
+        data: data.to_vec(),
+        digest,
+    }
+}
+
+#[test]
+fn compose_with_gap_fill() {
+    let layout = SlotLayout::new(
+        0x100,
+        vec![
+            blob("config", 0xc0, 0x40, &[0x22; 4], false),
+            blob("radio", 0x40, 0x40, &[0x11; 0x20], true),
+        ],
+    )
+    .unwrap();
+    assert_eq!(layout.image_limit(), 0x40);
+
+    let slot = layout.compose(&[0xaa; 0x10]).unwrap();
+    assert_eq!(slot.len(), 0xc4);
+    assert!(slot[..0x10].iter().all(|&b| b == 0xaa));
+    assert!(slot[0x10..0x40].iter().all(|&b| b == 0xff));
+    assert!(slot[0x40..0x60].iter().all(|&b| b == 0x11));
+    assert!(slot[0x60..0xc0].iter().all(|&b| b == 0xff));
+    assert!(slot[0xc0..].iter().all(|&b| b == 0x22));
+
+    // The image may not extend into the first subregion.
+    assert!(layout.compose(&[0xaa; 0x41]).is_err());
+}
+
+#[test]
+fn invalid_layouts() {
+    // Blob exceeds its subregion.
+    assert!(SlotLayout::new(0x100, vec![blob("radio", 0x40, 0x10, &[0; 0x11], false)]).is_err());
+    // Subregion exceeds the slot.
+    assert!(SlotLayout::new(0x100, vec![blob("radio", 0xf0, 0x20, &[], false)]).is_err());
+    // Subregions overlap.
+    assert!(
+        SlotLayout::new(
+            0x100,
+            vec![
+                blob("radio", 0x40, 0x40, &[], false),
+                blob("config", 0x70, 0x10, &[], false)
+            ]
+        )
+        .is_err()
+    );
+
+    let empty = SlotLayout::new(0x100, vec![]).unwrap();
+    assert!(empty.is_empty());
+    assert_eq!(empty.image_limit(), 0x100);
+}
+
+#[test]
+fn digests_in_metadata() {
+    let data = [0x5a; 0x30];
+    let layout = SlotLayout::new(
+        0x100,
+        vec![
+            blob("radio", 0x40, 0x40, &data, true),
+            blob("config", 0xc0, 0x40, &[0x22; 4], false),
+        ],
+    )
+    .unwrap();
+
+    let digests = layout.digests();
+    assert_eq!(digests.len(), 1);
+    assert_eq!(digests[0].offset, 0x40);
+    assert_eq!(digests[0].len, 0x30);
+    assert_eq!(digests[0].sha256, <[u8; 32]>::from(Sha256::digest(data)));
+
+    let metadata = Metadata {
+        version: Some("1.0.0".to_owned()),
+        subregions: digests,
+        ..Default::default()
+    };
+    let bytes = metadata.to_bytes().unwrap();
+    assert_eq!(Metadata::parse(&Trailer::parse(&bytes).unwrap()), metadata);
+}
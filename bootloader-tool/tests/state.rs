@@ -0,0 +1,68 @@
+//! Symbolic slot names on the command line resolved against the latest state on the device, and the history of the
+//! state journal.
+
+use bootloader_tool::processors::state::{self, SlotRef};
+use ec_slimloader_state::record::Record;
+use ec_slimloader_state::state::{ParseResult, Slot, State, Status};
+
+#[test]
+fn parse_slot_refs() {
+    assert_eq!("2".parse::<SlotRef>().unwrap(), SlotRef::Index(2));
+    assert_eq!("current".parse::<SlotRef>().unwrap(), SlotRef::Current);
+    assert_eq!("target".parse::<SlotRef>().unwrap(), SlotRef::Target);
+    assert_eq!("backup".parse::<SlotRef>().unwrap(), SlotRef::Backup);
+    assert_eq!("other".parse::<SlotRef>().unwrap(), SlotRef::Other);
+    assert!("next".parse::<SlotRef>().is_err());
+    assert!("-1".parse::<SlotRef>().is_err());
+
+    for slot in [SlotRef::Index(1), SlotRef::Current, SlotRef::Other] {
+        assert_eq!(slot.to_string().parse::<SlotRef>().unwrap(), slot);
+    }
+}
+
+#[test]
+fn resolve_against_state() {
+    let confirmed = State::new(Status::Confirmed, Slot::S1, Slot::S0);
+    assert_eq!(SlotRef::Current.resolve(Some(&confirmed)).unwrap(), 1);
+    assert_eq!(SlotRef::Target.resolve(Some(&confirmed)).unwrap(), 1);
+    assert_eq!(SlotRef::Backup.resolve(Some(&confirmed)).unwrap(), 0);
+    assert_eq!(SlotRef::Other.resolve(Some(&confirmed)).unwrap(), 0);
+
+    // Once the target failed, the bootloader boots the backup.
+    let failed = confirmed.with_status(Status::Failed);
+    assert_eq!(state::current_slot(&failed), Slot::S0);
+    assert_eq!(SlotRef::Current.resolve(Some(&failed)).unwrap(), 0);
+    assert_eq!(SlotRef::Target.resolve(Some(&failed)).unwrap(), 1);
+    assert_eq!(SlotRef::Other.resolve(Some(&failed)).unwrap(), 1);
+}
+
+#[test]
+fn resolve_without_state() {
+    assert_eq!(SlotRef::Index(3).resolve(None).unwrap(), 3);
+    assert!(SlotRef::Current.resolve(None).is_err());
+    assert!(!SlotRef::Index(3).is_symbolic());
+    assert!(SlotRef::Other.is_symbolic());
+
+    let single = State::new(Status::Initial, Slot::S0, Slot::S0);
+    assert!(SlotRef::Other.resolve(Some(&single)).is_err());
+}
+
+#[test]
+fn history_of_journal() {
+    let mut journal = state::seeded_journal(&State::new(Status::Initial, Slot::S1, Slot::S0), 16);
+    State::new(Status::Attempting, Slot::S1, Slot::S0).to_bytes(&mut journal[4..8]);
+    journal[8..12].copy_from_slice(&[0x00, 0x12, 0x34, 0x56]);
+    State::new(Status::Failed, Slot::S1, Slot::S0).to_bytes(&mut journal[12..16]);
+
+    let history = state::history(&journal);
+    assert_eq!(history.len(), 4);
+    assert_eq!(history[1], (4, Ok(State::new(Status::Attempting, Slot::S1, Slot::S0))));
+    assert_eq!(history[2], (8, Err(ParseResult::Invalid)));
+    assert_eq!(
+        state::summarize(&history),
+        "4 records: 1 Initial, 1 Attempting, 1 invalid, 1 Failed"
+    );
+
+    let erased = state::seeded_journal(&State::new(Status::Confirmed, Slot::S0, Slot::S0), 16);
+    assert_eq!(state::history(&erased).len(), 1);
+}
@@ -0,0 +1,98 @@
+//! Corrupted images generated by `test vectors`, each refused by the bootloader for its own reason.
+
+use bootloader_tool::processors::mbi::headers::Headers;
+use bootloader_tool::processors::mbi::vectors::Vector;
+use bootloader_tool::processors::mbi::{ImageKind, ImageType, TrustZone};
+use mbi_format::{CertBlockHeader, Ivt};
+
+const HEADER_OFFSET: usize = 0x100;
+const SIGNATURE_LEN: usize = 0x100;
+
+/// Image with an empty certificate table, root key hashes filled with `0xaa` and a signature filled with `0x55`
+fn image() -> Vec<u8> {
+    let signed_len = HEADER_OFFSET + CertBlockHeader::LEN + 4 * 32;
+    let mut image = vec![0; signed_len + SIGNATURE_LEN];
+
+    Ivt {
+        image_len: image.len() as u32,
+        image_type: ImageType::new(ImageKind::XipPlainSigned).as_u32(),
+        header_offset: HEADER_OFFSET as u32,
+        load_addr: 0x0800_1000,
+    }
+    .write(&mut image)
+    .unwrap();
+    CertBlockHeader {
+        signature: u32::from_le_bytes(*b"cert"),
+        header_major_version: 1,
+        header_minor_version: 0,
+        header_length: CertBlockHeader::LEN as u32,
+        flags: 0,
+        build_number: 1,
+        total_image_length: signed_len as u32,
+        certificate_count: 0,
+        certificate_table_length: 0,
+    }
+    .write(&mut image[HEADER_OFFSET..])
+    .unwrap();
+    image[HEADER_OFFSET + CertBlockHeader::LEN..signed_len].fill(0xaa);
+    image[signed_len..].fill(0x55);
+
+    image
+}
+
+#[test]
+fn vectors_corrupt_one_part() {
+    let image = image();
+    let signed_len = image.len() - SIGNATURE_LEN;
+
+    for vector in Vector::ALL {
+        let corrupted = vector.apply(&image).unwrap();
+        let differing = image.iter().zip(&corrupted).filter(|(a, b)| a != b).count();
+        let headers = Headers::decode(&corrupted).unwrap();
+
+        match vector {
+            Vector::BadSignature => {
+                assert_eq!(differing, 1);
+                assert_eq!(corrupted[signed_len], 0x54);
+            }
+            Vector::TruncatedCertBlock => {
+                assert_eq!(corrupted.len(), HEADER_OFFSET + CertBlockHeader::LEN);
+                assert_eq!(headers.ivt.image_len as usize, corrupted.len());
+                assert_eq!(headers.root_key_hashes, None);
+            }
+            Vector::WrongRkth => {
+                assert_eq!(headers.root_key_hashes, Some([[0; 32]; 4]));
+                assert_eq!(corrupted[signed_len..], image[signed_len..]);
+            }
+            Vector::OversizedLength => assert_eq!(headers.ivt.image_len, u32::MAX),
+            Vector::FlippedImageType => {
+                let image_type = ImageType::from_u32(headers.ivt.image_type).unwrap();
+                assert_eq!(image_type.tz_m_image_type, TrustZone::Disabled);
+                assert_eq!(image_type.image_kind, ImageKind::XipPlainSigned);
+            }
+        }
+        if vector != Vector::TruncatedCertBlock {
+            assert_eq!(corrupted.len(), image.len());
+        }
+    }
+}
+
+#[test]
+fn vectors_name_expected_error() {
+    assert_eq!(Vector::BadSignature.to_string(), "bad-signature");
+    assert_eq!(Vector::BadSignature.expected(), "Authenticate");
+    assert_eq!(Vector::OversizedLength.expected(), "TooLarge");
+    assert_eq!(Vector::FlippedImageType.expected(), "Markers");
+}
+
+#[test]
+fn vectors_need_signed_image() {
+    let image = image();
+    assert!(Vector::BadSignature.apply(&image[..HEADER_OFFSET]).is_err());
+
+    // Without signature
+    let mut unsigned = image[..image.len() - SIGNATURE_LEN].to_vec();
+    let len = unsigned.len() as u32;
+    unsigned[0x20..0x24].copy_from_slice(&len.to_le_bytes());
+    assert!(Vector::BadSignature.apply(&unsigned).is_err());
+}
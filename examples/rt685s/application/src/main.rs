@@ -49,7 +49,8 @@ async fn main(_spawner: Spawner) {
 
     let ExternalStorageMap { bl_state, .. } = ext_flash_manager.map(ExternalStorageConfig::new());
 
-    let mut journal = match FlashJournal::new::<{ crate::JOURNAL_BUFFER_SIZE }>(bl_state).await {
+    let mut journal_buffer = [0; JOURNAL_BUFFER_SIZE];
+    let mut journal = match FlashJournal::new(bl_state, &mut journal_buffer).await {
         Ok(journal) => journal,
         Err(e) => defmt_or_log::panic!("Failed to initialize the flash state journal: {:?}", e),
     };
@@ -200,7 +201,7 @@ async fn main(_spawner: Spawner) {
                 }
             } else {
                 defmt_or_log::info!("Writing new state: {}", new_state);
-                defmt_or_log::unwrap!(journal.set::<JOURNAL_BUFFER_SIZE>(&new_state).await);
+                defmt_or_log::unwrap!(journal.set(&new_state).await);
             }
         }
     };
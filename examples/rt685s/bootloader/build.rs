@@ -2,15 +2,18 @@ use std::env;
 use std::fs::File;
 use std::io::Write;
 use std::path::PathBuf;
+use std::process::Command;
 
 fn main() {
     // Put corresponding linker script in our output directory and ensure it's
     // on the linker search path.
     let out = &PathBuf::from(env::var_os("OUT_DIR").unwrap());
-    File::create(out.join("memory.x"))
-        .unwrap()
-        .write_all(include_bytes!("memory.x"))
-        .unwrap();
+    let memory: &[u8] = if env::var_os("CARGO_FEATURE_XIP").is_some() {
+        include_bytes!("memory-xip.x")
+    } else {
+        include_bytes!("memory.x")
+    };
+    File::create(out.join("memory.x")).unwrap().write_all(memory).unwrap();
     println!("cargo:rustc-link-search={}", out.display());
     println!("cargo:rustc-link-arg=-Tlink.x");
     println!("cargo:rustc-link-arg=--nmagic");
@@ -19,8 +22,35 @@ fn main() {
     println!("cargo:rustc-link-arg=-Tdefmt.x");
 
     println!("cargo:rerun-if-changed=memory.x");
+    println!("cargo:rerun-if-changed=memory-xip.x");
+
+    let version = u32::from_be_bytes([
+        env!("CARGO_PKG_VERSION_MAJOR")
+            .parse::<u8>()
+            .expect("should have major version"),
+        env!("CARGO_PKG_VERSION_MINOR")
+            .parse::<u8>()
+            .expect("should have minor version"),
+        env!("CARGO_PKG_VERSION_PATCH")
+            .parse::<u8>()
+            .expect("should have patch version"),
+        0,
+    ]);
 
-    // Inject crate version into the .biv section.
+    // Leading bytes of the revision built from, or zeroes when not built from a git checkout.
+    let mut build_hash = [0u8; 8];
+    if let Ok(output) = Command::new("git").args(["rev-parse", "HEAD"]).output() {
+        let revision = String::from_utf8_lossy(&output.stdout);
+        for (byte, hex) in build_hash.iter_mut().zip(revision.trim().as_bytes().chunks_exact(2)) {
+            *byte = std::str::from_utf8(hex)
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .unwrap_or(0);
+        }
+    }
+    println!("cargo:rerun-if-changed=../../../.git/HEAD");
+
+    // Inject crate version into the .biv section, and hand it with the revision to the application.
     File::create(out.join("biv.rs"))
         .unwrap()
         .write_all(
@@ -28,17 +58,13 @@ fn main() {
                 r##"
 #[link_section = ".biv"]
 #[used]
-static BOOT_IMAGE_VERSION: u32 = 0x{:02x}{:02x}{:02x}00;
-"##,
-                env!("CARGO_PKG_VERSION_MAJOR")
-                    .parse::<u8>()
-                    .expect("should have major version"),
-                env!("CARGO_PKG_VERSION_MINOR")
-                    .parse::<u8>()
-                    .expect("should have minor version"),
-                env!("CARGO_PKG_VERSION_PATCH")
-                    .parse::<u8>()
-                    .expect("should have patch version"),
+static BOOT_IMAGE_VERSION: u32 = 0x{version:08x};
+
+const BUILD_INFO: ec_slimloader_state::boot_info::BuildInfo = ec_slimloader_state::boot_info::BuildInfo {{
+    version: 0x{version:08x},
+    build_hash: {build_hash:?},
+}};
+"##
             )
             .as_bytes(),
         )
@@ -3,26 +3,28 @@
 
 #[cfg(feature = "defmt")]
 use defmt_rtt as _;
-use ec_slimloader_imxrt::{ExternalStorage, Partitions};
+use ec_slimloader::BootOverride;
+use ec_slimloader_imxrt::{ExternalStorage, Partitions, StatePartition};
 use embassy_executor::Spawner;
+use embassy_imxrt::gpio;
+use embassy_imxrt::peripherals::PIO1_1;
 use example_bsp::bootloader::{ExternalStorageConfig, ExternalStorageMap};
-use heapless::Vec;
 use panic_probe as _;
+use static_cell::ConstStaticCell;
 
 // auto-generated version information from Cargo.toml
 include!(concat!(env!("OUT_DIR"), "/biv.rs"));
 
 struct Config;
 
-#[derive(Debug)]
-#[cfg_attr(feature = "defmt", derive(defmt::Format))]
-struct TooManySlots;
-
 const JOURNAL_BUFFER_SIZE: usize = 4096;
 
 impl ec_slimloader_imxrt::ImxrtConfig for Config {
     const SLOT_SIZE_RANGE: core::ops::Range<usize> = 64..1024 * 1024;
     const LOAD_RANGE: core::ops::Range<*mut u32> = (0x1002_0000 as *mut u32)..0x1018_0000 as *mut u32;
+    const BUILD_INFO: ec_slimloader_state::boot_info::BuildInfo = BUILD_INFO;
+
+    type StateStorage = StatePartition;
 
     fn partitions(
         &self,
@@ -37,11 +39,18 @@ impl ec_slimloader_imxrt::ImxrtConfig for Config {
             bl_state,
         } = flash.map(ExternalStorageConfig::new());
 
-        let mut slots = Vec::new();
-        defmt_or_log::unwrap!(slots.push(app_slot0).map_err(|_| TooManySlots));
-        defmt_or_log::unwrap!(slots.push(app_slot1).map_err(|_| TooManySlots));
+        Partitions {
+            state: bl_state,
+            slots: Partitions::collect_slots([app_slot0, app_slot1]),
+        }
+    }
+
+    fn boot_override(&mut self) -> Option<BootOverride> {
+        // Maps to the user1 button on the EVK, which is pulled low when pressed.
+        // Note(unsafe): the pin is not used anywhere else in the bootloader.
+        let strap = gpio::Input::new(unsafe { PIO1_1::steal() }, gpio::Pull::None, gpio::Inverter::Disabled);
 
-        Partitions { state: bl_state, slots }
+        strap.is_low().then_some(BootOverride::Backup)
     }
 }
 
@@ -49,5 +58,6 @@ impl ec_slimloader::BootStatePolicy for Config {}
 
 #[embassy_executor::main]
 async fn main(_spawner: Spawner) -> ! {
-    ec_slimloader::start::<ec_slimloader_imxrt::Imxrt<Config>, JOURNAL_BUFFER_SIZE>(Config).await
+    static JOURNAL_BUFFER: ConstStaticCell<[u8; JOURNAL_BUFFER_SIZE]> = ConstStaticCell::new([0; JOURNAL_BUFFER_SIZE]);
+    ec_slimloader::start::<ec_slimloader_imxrt::Imxrt<Config>>(Config, JOURNAL_BUFFER.take()).await
 }
@@ -22,10 +22,11 @@ BUILD_EXTRA=""
 
 FEATURE_COMBINATIONS=(
   "defmt"
-  "non-secure"
 )
 cargo batch \
       $(for features in "${FEATURE_COMBINATIONS[@]}"; do
     echo "--- build --release --manifest-path Cargo.toml --target thumbv8m.main-none-eabihf "
 	  echo "--- build --release --manifest-path Cargo.toml --target thumbv8m.main-none-eabihf --features $features "
-	done) $BUILD_EXTRA
+	done) \
+      --- build --profile minimal --manifest-path bootloader/Cargo.toml --target thumbv8m.main-none-eabihf --features minimal \
+      $BUILD_EXTRA
@@ -24,11 +24,9 @@ FEATURE_COMBINATIONS=(
   "mimxrt633s"
   "mimxrt633s,defmt"
   "mimxrt633s,log"
-  "mimxrt633s,non-secure"
   "mimxrt685s"
   "mimxrt685s,defmt"
   "mimxrt685s,log"
-  "mimxrt685s,non-secure"
 )
 cargo batch \
       $(for features in "${FEATURE_COMBINATIONS[@]}"; do
@@ -37,3 +35,4 @@ cargo batch \
 
 cargo test --locked  --manifest-path Cargo.toml --target x86_64-unknown-linux-gnu --features "mimxrt633s"
 cargo test --locked  --manifest-path Cargo.toml --target x86_64-unknown-linux-gnu --features "mimxrt685s"
+cargo test --locked  --manifest-path Cargo.toml --target x86_64-unknown-linux-gnu --features "mimxrt685s,factory-reset,self-test,self-update,auth-cache"
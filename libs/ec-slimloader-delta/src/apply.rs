@@ -0,0 +1,476 @@
+use embedded_storage_async::nor_flash::{NorFlash, ReadNorFlash};
+
+use crate::format::{Header, Op, CRC};
+
+/// Error yielded whilst applying a patch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// The patch is malformed.
+    InvalidPatch,
+    /// The old image is not the image the patch was created against.
+    BaseMismatch,
+    /// The new image does not fit in the target.
+    TooLarge,
+    /// The patch ended before describing the entire new image.
+    Incomplete,
+    /// The new image as written to the target does not match the checksum in the patch.
+    ReadbackFailed,
+    /// The patch was already applied completely.
+    Finished,
+    /// The underlying storage medium yielded an error.
+    IO,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Receiving the [Header].
+    Header,
+    /// Receiving the next [Op].
+    Op,
+    /// Receiving the bytes of an [Op::Insert].
+    Insert { remaining: u32 },
+    /// The new image was written and verified.
+    Done,
+}
+
+/// Streaming applier of a patch, reconstructing the new image into a target.
+///
+/// The patch can be fed in chunks of any size as it is being received. The target is erased as soon as the header
+/// has been received, and is written to in blocks of `N` bytes, which must be a multiple of the read and write sizes
+/// of both the old image and the target, as checked at compile time.
+pub struct Patcher<const N: usize = 256> {
+    state: State,
+    header: Option<Header>,
+    /// Partially received header or op.
+    pending: [u8; Header::LEN],
+    pending_len: usize,
+    /// Bytes of the new image not yet written to the target.
+    buf: [u8; N],
+    buf_len: usize,
+    /// Number of bytes of the new image produced so far.
+    position: u32,
+    /// Number of bytes written to the target so far.
+    flushed: u32,
+}
+
+impl<const N: usize> Default for Patcher<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> Patcher<N> {
+    pub const fn new() -> Self {
+        Self {
+            state: State::Header,
+            header: None,
+            pending: [0u8; Header::LEN],
+            pending_len: 0,
+            buf: [0u8; N],
+            buf_len: 0,
+            position: 0,
+            flushed: 0,
+        }
+    }
+
+    /// The header of the patch, once received.
+    pub fn header(&self) -> Option<&Header> {
+        self.header.as_ref()
+    }
+
+    /// Apply the next chunk of the patch.
+    pub async fn feed<O: ReadNorFlash, T: NorFlash>(
+        &mut self,
+        old: &mut O,
+        target: &mut T,
+        mut data: &[u8],
+    ) -> Result<(), Error> {
+        const {
+            assert!(
+                N > 0 && N.is_multiple_of(O::READ_SIZE),
+                "block size must be a multiple of the old read size"
+            );
+            assert!(
+                N.is_multiple_of(T::READ_SIZE),
+                "block size must be a multiple of the target read size"
+            );
+            assert!(
+                N.is_multiple_of(T::WRITE_SIZE),
+                "block size must be a multiple of the target write size"
+            );
+        }
+
+        while !data.is_empty() {
+            match self.state {
+                State::Header => {
+                    if !self.take_pending(&mut data, Header::LEN) {
+                        continue;
+                    }
+
+                    let Some(header) = Header::parse(&self.pending) else {
+                        return Err(Error::InvalidPatch);
+                    };
+                    self.pending_len = 0;
+                    self.start(old, target, header).await?;
+                    self.state = State::Op;
+                }
+                State::Op => {
+                    let tag = if self.pending_len == 0 {
+                        data[0]
+                    } else {
+                        self.pending[0]
+                    };
+                    let Some(op_len) = Op::encoded_len(tag) else {
+                        return Err(Error::InvalidPatch);
+                    };
+                    if !self.take_pending(&mut data, op_len) {
+                        continue;
+                    }
+
+                    let Some(op) = Op::parse(&self.pending[..op_len]) else {
+                        return Err(Error::InvalidPatch);
+                    };
+                    self.pending_len = 0;
+
+                    match op {
+                        Op::Copy { offset, len } => self.copy(old, target, offset, len).await?,
+                        Op::Insert { len: 0 } => {}
+                        Op::Insert { len } => self.state = State::Insert { remaining: len },
+                    }
+                }
+                State::Insert { remaining } => {
+                    let len = data.len().min(remaining as usize);
+                    self.output(target, &data[..len]).await?;
+                    data = &data[len..];
+
+                    let remaining = remaining - len as u32;
+                    self.state = if remaining == 0 {
+                        State::Op
+                    } else {
+                        State::Insert { remaining }
+                    };
+                }
+                State::Done => return Err(Error::Finished),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Write out the remainder of the new image, and verify the target against the checksum in the patch.
+    pub async fn finish<T: NorFlash>(&mut self, target: &mut T) -> Result<(), Error> {
+        const {
+            assert!(
+                N > 0 && N.is_multiple_of(T::READ_SIZE),
+                "block size must be a multiple of the target read size"
+            );
+            assert!(
+                N.is_multiple_of(T::WRITE_SIZE),
+                "block size must be a multiple of the target write size"
+            );
+        }
+
+        let Some(header) = self.header else {
+            return Err(Error::Incomplete);
+        };
+
+        if self.state == State::Done {
+            return Err(Error::Finished);
+        }
+        if self.state != State::Op || self.pending_len != 0 || self.position != header.new_len {
+            return Err(Error::Incomplete);
+        }
+
+        // Pad the last block with erased bytes.
+        let flush_len = self.buf_len.next_multiple_of(T::WRITE_SIZE);
+        self.buf[self.buf_len..flush_len].fill(0xFF);
+        self.buf_len = flush_len;
+        self.flush(target).await?;
+
+        if checksum(target, header.new_len, &mut self.buf).await? != header.new_crc {
+            return Err(Error::ReadbackFailed);
+        }
+
+        self.state = State::Done;
+        Ok(())
+    }
+
+    /// Move bytes from `data` into the pending buffer until it contains `len` bytes, returning whether it does.
+    fn take_pending(&mut self, data: &mut &[u8], len: usize) -> bool {
+        let take = (len - self.pending_len).min(data.len());
+        self.pending[self.pending_len..self.pending_len + take].copy_from_slice(&data[..take]);
+        self.pending_len += take;
+        *data = &data[take..];
+        self.pending_len == len
+    }
+
+    async fn start<O: ReadNorFlash, T: NorFlash>(
+        &mut self,
+        old: &mut O,
+        target: &mut T,
+        header: Header,
+    ) -> Result<(), Error> {
+        if header.old_len as usize > old.capacity() {
+            return Err(Error::BaseMismatch);
+        }
+        if header.new_len as usize > target.capacity() {
+            return Err(Error::TooLarge);
+        }
+
+        if checksum(old, header.old_len, &mut self.buf).await? != header.old_crc {
+            return Err(Error::BaseMismatch);
+        }
+
+        let erase_len = (header.new_len as usize).next_multiple_of(T::ERASE_SIZE);
+        target.erase(0, erase_len as u32).await.map_err(|_| Error::IO)?;
+
+        self.header = Some(header);
+        Ok(())
+    }
+
+    async fn copy<O: ReadNorFlash, T: NorFlash>(
+        &mut self,
+        old: &mut O,
+        target: &mut T,
+        offset: u32,
+        len: u32,
+    ) -> Result<(), Error> {
+        let Some(header) = self.header else {
+            return Err(Error::InvalidPatch);
+        };
+        if offset.checked_add(len).is_none_or(|end| end > header.old_len) {
+            return Err(Error::InvalidPatch);
+        }
+
+        // Reads must be aligned, hence read whole words and skip the leading bytes.
+        // Skipping less than a word, every read makes progress even if the block holds just a single word.
+        let mut scratch = [0u8; N];
+        let mut offset = offset as usize;
+        let mut remaining = len as usize;
+        while remaining > 0 {
+            let start = offset - offset % O::READ_SIZE;
+            let skip = offset - start;
+            let chunk_len = remaining.min(N - skip);
+            let end = (offset + chunk_len).next_multiple_of(O::READ_SIZE);
+
+            old.read(start as u32, &mut scratch[..end - start])
+                .await
+                .map_err(|_| Error::IO)?;
+            self.output(target, &scratch[skip..skip + chunk_len]).await?;
+
+            offset += chunk_len;
+            remaining -= chunk_len;
+        }
+
+        Ok(())
+    }
+
+    /// Append bytes to the new image.
+    async fn output<T: NorFlash>(&mut self, target: &mut T, mut data: &[u8]) -> Result<(), Error> {
+        let Some(header) = self.header else {
+            return Err(Error::InvalidPatch);
+        };
+        if (self.position as usize + data.len()) > header.new_len as usize {
+            return Err(Error::InvalidPatch);
+        }
+        self.position += data.len() as u32;
+
+        while !data.is_empty() {
+            let len = data.len().min(N - self.buf_len);
+            self.buf[self.buf_len..self.buf_len + len].copy_from_slice(&data[..len]);
+            self.buf_len += len;
+            data = &data[len..];
+
+            if self.buf_len == N {
+                self.flush(target).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn flush<T: NorFlash>(&mut self, target: &mut T) -> Result<(), Error> {
+        if self.buf_len == 0 {
+            return Ok(());
+        }
+
+        target
+            .write(self.flushed, &self.buf[..self.buf_len])
+            .await
+            .map_err(|_| Error::IO)?;
+        self.flushed += self.buf_len as u32;
+        self.buf_len = 0;
+        Ok(())
+    }
+}
+
+/// Compute the CRC32 over the first `len` bytes of `flash`.
+async fn checksum<F: ReadNorFlash, const N: usize>(flash: &mut F, len: u32, buf: &mut [u8; N]) -> Result<u32, Error> {
+    let mut digest = CRC.digest();
+    let mut offset = 0;
+    while offset < len as usize {
+        let chunk_len = (len as usize - offset).min(N);
+        let read_len = chunk_len.next_multiple_of(F::READ_SIZE);
+        flash
+            .read(offset as u32, &mut buf[..read_len])
+            .await
+            .map_err(|_| Error::IO)?;
+        digest.update(&buf[..chunk_len]);
+        offset += chunk_len;
+    }
+    Ok(digest.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use ec_slimloader_state::flash::mock::MockFlashBase;
+    use embassy_futures::block_on;
+
+    use super::*;
+
+    type MockFlash = MockFlashBase<8, 4, 256>;
+
+    fn flash_with(data: &[u8]) -> MockFlash {
+        let mut flash = MockFlash::default();
+        flash.as_bytes_mut()[..data.len()].copy_from_slice(data);
+        flash
+    }
+
+    fn old_image() -> Vec<u8> {
+        (0..3000u32).map(|i| (i * 7 + i / 13) as u8).collect()
+    }
+
+    fn make_patch(old: &[u8], new: &[u8], ops: &[(Op, &[u8])]) -> Vec<u8> {
+        let header = Header {
+            old_len: old.len() as u32,
+            old_crc: CRC.checksum(old),
+            new_len: new.len() as u32,
+            new_crc: CRC.checksum(new),
+        };
+
+        let mut out = header.to_bytes().to_vec();
+        for (op, data) in ops {
+            let mut buf = [0u8; Op::MAX_LEN];
+            let len = op.encode(&mut buf);
+            out.extend_from_slice(&buf[..len]);
+            out.extend_from_slice(data);
+        }
+        out
+    }
+
+    fn apply(old: &[u8], patch: &[u8], chunk_size: usize) -> (Result<(), Error>, MockFlash) {
+        apply_with(Patcher::<256>::new(), old, patch, chunk_size)
+    }
+
+    fn apply_with<const N: usize>(
+        mut patcher: Patcher<N>,
+        old: &[u8],
+        patch: &[u8],
+        chunk_size: usize,
+    ) -> (Result<(), Error>, MockFlash) {
+        let mut old = flash_with(old);
+        let mut target = MockFlash::default();
+
+        let result = block_on(async {
+            for chunk in patch.chunks(chunk_size) {
+                patcher.feed(&mut old, &mut target, chunk).await?;
+            }
+            patcher.finish(&mut target).await
+        });
+        (result, target)
+    }
+
+    #[test]
+    fn apply_patch() {
+        let old = old_image();
+        let inserted = b"a freshly inserted sequence";
+
+        let mut new = old[101..1101].to_vec();
+        new.extend_from_slice(inserted);
+        new.extend_from_slice(&old[5..40]);
+
+        let patch = make_patch(
+            &old,
+            &new,
+            &[
+                (Op::Copy { offset: 101, len: 1000 }, &[]),
+                (
+                    Op::Insert {
+                        len: inserted.len() as u32,
+                    },
+                    inserted,
+                ),
+                (Op::Copy { offset: 5, len: 35 }, &[]),
+            ],
+        );
+
+        for chunk_size in [1, 7, 256, patch.len()] {
+            let (result, target) = apply(&old, &patch, chunk_size);
+            assert_eq!(result, Ok(()));
+            assert_eq!(&target.as_bytes()[..new.len()], &new[..]);
+            assert!(target.as_bytes()[new.len()..].iter().all(|b| *b == 0xFF));
+        }
+    }
+
+    #[test]
+    fn apply_patch_single_word_blocks() {
+        let old = old_image();
+        let mut new = old[1..11].to_vec();
+        new.extend_from_slice(b"xyz");
+        new.extend_from_slice(&old[2003..2010]);
+        new.extend_from_slice(&old[8..12]);
+
+        let patch = make_patch(
+            &old,
+            &new,
+            &[
+                (Op::Copy { offset: 1, len: 10 }, &[]),
+                (Op::Insert { len: 3 }, b"xyz"),
+                (Op::Copy { offset: 2003, len: 7 }, &[]),
+                (Op::Copy { offset: 8, len: 4 }, &[]),
+            ],
+        );
+
+        // Blocks as large as a single read and write of the flash.
+        let (result, target) = apply_with(Patcher::<4>::new(), &old, &patch, 5);
+        assert_eq!(result, Ok(()));
+        assert_eq!(&target.as_bytes()[..new.len()], &new[..]);
+        assert!(target.as_bytes()[new.len()..].iter().all(|b| *b == 0xFF));
+    }
+
+    #[test]
+    fn apply_patch_errors() {
+        let old = old_image();
+        let new = old[..100].to_vec();
+        let ops: &[(Op, &[u8])] = &[(Op::Copy { offset: 0, len: 100 }, &[])];
+
+        // Patch against another image.
+        let mut other = old.clone();
+        other[2000] ^= 1;
+        let patch = make_patch(&other, &new, ops);
+        assert_eq!(apply(&old, &patch, 16).0, Err(Error::BaseMismatch));
+
+        // Copy beyond the old image.
+        let patch = make_patch(&old, &new, &[(Op::Copy { offset: 2950, len: 100 }, &[])]);
+        assert_eq!(apply(&old, &patch, 16).0, Err(Error::InvalidPatch));
+
+        // Produce more than the new image.
+        let patch = make_patch(&old, &new, &[(Op::Copy { offset: 0, len: 101 }, &[])]);
+        assert_eq!(apply(&old, &patch, 16).0, Err(Error::InvalidPatch));
+
+        // Truncated patch.
+        let patch = make_patch(&old, &new, ops);
+        assert_eq!(apply(&old, &patch[..patch.len() - 1], 16).0, Err(Error::Incomplete));
+
+        // Unknown op.
+        let mut patch = make_patch(&old, &new, ops);
+        patch[Header::LEN] = 0xAA;
+        assert_eq!(apply(&old, &patch, 16).0, Err(Error::InvalidPatch));
+
+        // Wrong checksum of the new image.
+        let mut patch = make_patch(&old, &new, ops);
+        patch[16] ^= 1;
+        assert_eq!(apply(&old, &patch, 16).0, Err(Error::ReadbackFailed));
+    }
+}
@@ -0,0 +1,120 @@
+//! Generation of patches, see [crate] for the patch format.
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+use crate::format::{Header, Op, CRC};
+
+/// Length of the windows of the old image that are indexed to find matches.
+const WINDOW_LEN: usize = 8;
+/// Shortest match in the old image for which a [Op::Copy] is emitted, instead of inserting the bytes.
+const MIN_MATCH_LEN: usize = 16;
+/// Maximum number of positions in the old image remembered per window.
+///
+/// Bounds the run time on repetitive data such as padding.
+const MAX_CANDIDATES: usize = 16;
+
+fn push_op(out: &mut Vec<u8>, op: Op) {
+    let mut buf = [0u8; Op::MAX_LEN];
+    let len = op.encode(&mut buf);
+    out.extend_from_slice(&buf[..len]);
+}
+
+fn push_insert(out: &mut Vec<u8>, data: &[u8]) {
+    if !data.is_empty() {
+        push_op(out, Op::Insert { len: data.len() as u32 });
+        out.extend_from_slice(data);
+    }
+}
+
+fn match_len(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b).take_while(|(a, b)| a == b).count()
+}
+
+/// Generate a patch that reconstructs `new` from `old`.
+pub fn diff(old: &[u8], new: &[u8]) -> Vec<u8> {
+    let mut index: BTreeMap<&[u8], Vec<u32>> = BTreeMap::new();
+    for (offset, window) in old.windows(WINDOW_LEN).enumerate() {
+        let candidates = index.entry(window).or_default();
+        if candidates.len() < MAX_CANDIDATES {
+            candidates.push(offset as u32);
+        }
+    }
+
+    let header = Header {
+        old_len: old.len() as u32,
+        old_crc: CRC.checksum(old),
+        new_len: new.len() as u32,
+        new_crc: CRC.checksum(new),
+    };
+
+    let mut out = Vec::from(header.to_bytes());
+    let mut insert_start = 0;
+    let mut position = 0;
+    while position < new.len() {
+        let best = new
+            .get(position..position + WINDOW_LEN)
+            .and_then(|window| index.get(window))
+            .into_iter()
+            .flatten()
+            .map(|&offset| (offset, match_len(&old[offset as usize..], &new[position..])))
+            .max_by_key(|(_, len)| *len);
+
+        match best {
+            Some((offset, len)) if len >= MIN_MATCH_LEN => {
+                push_insert(&mut out, &new[insert_start..position]);
+                push_op(
+                    &mut out,
+                    Op::Copy {
+                        offset,
+                        len: len as u32,
+                    },
+                );
+                position += len;
+                insert_start = position;
+            }
+            _ => position += 1,
+        }
+    }
+    push_insert(&mut out, &new[insert_start..]);
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use ec_slimloader_state::flash::mock::MockFlashBase;
+    use embassy_futures::block_on;
+
+    use super::*;
+    use crate::Patcher;
+
+    #[test]
+    fn diff_roundtrip() {
+        let old: Vec<u8> = (0..6000u32).map(|i| (i.wrapping_mul(2654435761) >> 13) as u8).collect();
+
+        // Shift the image, patch a few bytes and append some new data.
+        let mut new = Vec::from(b"prefix");
+        new.extend_from_slice(&old[..3000]);
+        new[1234] ^= 0xFF;
+        new.extend_from_slice(&old[3100..]);
+        new.extend_from_slice(&[0u8; 100]);
+
+        let patch = diff(&old, &new);
+        assert!(patch.len() < new.len() / 10);
+
+        let mut old_flash = MockFlashBase::<8, 4, 1024>::default();
+        old_flash.as_bytes_mut()[..old.len()].copy_from_slice(&old);
+        let mut target = MockFlashBase::<8, 4, 1024>::default();
+
+        let mut patcher = Patcher::<256>::new();
+        block_on(async {
+            for chunk in patch.chunks(100) {
+                patcher.feed(&mut old_flash, &mut target, chunk).await.unwrap();
+            }
+            patcher.finish(&mut target).await.unwrap();
+        });
+
+        assert_eq!(&target.as_bytes()[..new.len()], &new[..]);
+    }
+}
@@ -0,0 +1,154 @@
+use crc::{Crc, CRC_32_ISO_HDLC};
+
+pub(crate) static CRC: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+}
+
+/// Header at the start of each patch, describing both the old and new image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Header {
+    /// Length of the image the patch was created against.
+    pub old_len: u32,
+    /// CRC32 of the image the patch was created against.
+    pub old_crc: u32,
+    /// Length of the image the patch produces.
+    pub new_len: u32,
+    /// CRC32 of the image the patch produces.
+    pub new_crc: u32,
+}
+
+impl Header {
+    /// Length of the encoded header.
+    pub const LEN: usize = 20;
+
+    const MAGIC: [u8; 4] = *b"ECDP";
+
+    /// Parse the header, yielding [None] if the magic does not match.
+    pub fn parse(data: &[u8; Self::LEN]) -> Option<Self> {
+        if data[..4] != Self::MAGIC {
+            return None;
+        }
+
+        Some(Self {
+            old_len: read_u32(data, 4),
+            old_crc: read_u32(data, 8),
+            new_len: read_u32(data, 12),
+            new_crc: read_u32(data, 16),
+        })
+    }
+
+    pub fn to_bytes(&self) -> [u8; Self::LEN] {
+        let mut out = [0u8; Self::LEN];
+        out[..4].copy_from_slice(&Self::MAGIC);
+        out[4..8].copy_from_slice(&self.old_len.to_le_bytes());
+        out[8..12].copy_from_slice(&self.old_crc.to_le_bytes());
+        out[12..16].copy_from_slice(&self.new_len.to_le_bytes());
+        out[16..20].copy_from_slice(&self.new_crc.to_le_bytes());
+        out
+    }
+}
+
+/// Single operation producing the next part of the new image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Op {
+    /// Copy `len` bytes from `offset` in the old image.
+    Copy { offset: u32, len: u32 },
+    /// Insert the `len` bytes directly following this op in the patch.
+    Insert { len: u32 },
+}
+
+impl Op {
+    /// Maximum length of an encoded op.
+    pub const MAX_LEN: usize = 9;
+
+    const TAG_COPY: u8 = 0;
+    const TAG_INSERT: u8 = 1;
+
+    /// Length of the encoded op given its first byte, yielding [None] for unknown ops.
+    pub fn encoded_len(tag: u8) -> Option<usize> {
+        match tag {
+            Self::TAG_COPY => Some(9),
+            Self::TAG_INSERT => Some(5),
+            _ => None,
+        }
+    }
+
+    /// Parse an op, where `data` has the length indicated by [Op::encoded_len].
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let tag = *data.first()?;
+        if Self::encoded_len(tag)? != data.len() {
+            return None;
+        }
+
+        Some(match tag {
+            Self::TAG_COPY => Op::Copy {
+                offset: read_u32(data, 1),
+                len: read_u32(data, 5),
+            },
+            _ => Op::Insert { len: read_u32(data, 1) },
+        })
+    }
+
+    /// Encode the op into `out`, returning the encoded length.
+    pub fn encode(&self, out: &mut [u8; Self::MAX_LEN]) -> usize {
+        match *self {
+            Op::Copy { offset, len } => {
+                out[0] = Self::TAG_COPY;
+                out[1..5].copy_from_slice(&offset.to_le_bytes());
+                out[5..9].copy_from_slice(&len.to_le_bytes());
+                9
+            }
+            Op::Insert { len } => {
+                out[0] = Self::TAG_INSERT;
+                out[1..5].copy_from_slice(&len.to_le_bytes());
+                5
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_roundtrip() {
+        let header = Header {
+            old_len: 1,
+            old_crc: 2,
+            new_len: 3,
+            new_crc: 4,
+        };
+
+        let bytes = header.to_bytes();
+        assert_eq!(&bytes[..4], b"ECDP");
+        assert_eq!(Header::parse(&bytes), Some(header));
+
+        let mut corrupt = bytes;
+        corrupt[0] = b'X';
+        assert_eq!(Header::parse(&corrupt), None);
+    }
+
+    #[test]
+    fn op_roundtrip() {
+        for op in [
+            Op::Copy {
+                offset: 0x1234,
+                len: 0x20,
+            },
+            Op::Insert { len: 0x5678 },
+        ] {
+            let mut buf = [0u8; Op::MAX_LEN];
+            let len = op.encode(&mut buf);
+            assert_eq!(Op::encoded_len(buf[0]), Some(len));
+            assert_eq!(Op::parse(&buf[..len]), Some(op));
+            assert_eq!(Op::parse(&buf[..len - 1]), None);
+        }
+
+        assert_eq!(Op::encoded_len(2), None);
+    }
+}
@@ -0,0 +1,17 @@
+//! Delta updates: a compact patch describing a new image in terms of an old image.
+//!
+//! A patch consists of a [Header], followed by a sequence of [Op]s that each either copy a range of the old image,
+//! or insert the bytes directly following the op. Typically the old image is the active slot,
+//! and the new image is reconstructed into the inactive slot using a [Patcher] whilst the patch is being received.
+#![cfg_attr(not(test), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+mod apply;
+#[cfg(feature = "alloc")]
+pub mod diff;
+mod format;
+
+pub use apply::{Error, Patcher};
+pub use format::{Header, Op};
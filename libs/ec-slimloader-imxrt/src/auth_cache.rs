@@ -0,0 +1,177 @@
+//! Skipping authentication on warm boots of images that have been authenticated before, see [ec_slimloader_state::auth].
+//!
+//! After a successful authentication, the image digest is tagged alongside its slot with a device-unique key from
+//! [ImxrtConfig::auth_cache_key], and stored in a separate journal. When the target slot of a [Status::Confirmed]
+//! state is booted and its image still yields the same tag, the expensive authentication by the ROM is skipped.
+//! Its root keys are still checked against the revocation bits and the RKTH, as the ROM would do.
+//!
+//! The cache is not used without a key, nor on a device enforcing secure boot unless
+//! [ImxrtConfig::AUTH_CACHE_WITH_SECURE_BOOT] opts in.
+
+use defmt_or_log::{info, warn};
+use ec_slimloader::BootError;
+use ec_slimloader_state::auth::AuthCache;
+use ec_slimloader_state::state::{Slot, Status};
+use imxrt_rom::registers::field_sets::Rkth;
+use static_cell::ConstStaticCell;
+
+use crate::mbi::Ivt;
+use crate::verification::{reload_shadow, rkth_concurs, root_key_revoked, secure_boot_enabled};
+use crate::{CheckImage, Imxrt, ImxrtConfig};
+
+/// Number of bytes read in a single batch when scanning the cache journal.
+const JOURNAL_BUFFER_SIZE: usize = 256;
+
+/// Buffer to scan the cache journal with, taken once when initializing the board.
+pub(crate) fn journal_buffer() -> &'static mut [u8] {
+    static BUFFER: ConstStaticCell<[u8; JOURNAL_BUFFER_SIZE]> = ConstStaticCell::new([0; JOURNAL_BUFFER_SIZE]);
+    BUFFER.take()
+}
+
+/// Outcome of checking the root keys of a cached image, see [Imxrt::check_root_keys].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct RootKeyCheck {
+    /// Whether the image is rooted in a revoked root key.
+    revoked: bool,
+    /// Whether the RKTH of the image matches the RKTH it is authenticated against.
+    rkth_matches: bool,
+}
+
+/// Whether authentication of an image can be skipped, given whether it is `cached` and the `check` of its root keys.
+///
+/// The root keys are only checked for cached images, as authentication checks them otherwise. Cached images rooted in
+/// a revoked key are refused, and those whose RKTH no longer matches are authenticated after all.
+fn skip_authentication(
+    cached: bool,
+    check: impl FnOnce() -> Result<RootKeyCheck, BootError>,
+) -> Result<bool, BootError> {
+    if !cached {
+        return Ok(false);
+    }
+
+    let check = check()?;
+    if check.revoked {
+        return Err(BootError::Revoked);
+    }
+    Ok(check.rkth_matches)
+}
+
+impl<C: ImxrtConfig> Imxrt<C> {
+    /// Key to tag the cache records with, or [None] if the cache is not to be used.
+    fn cache_key(&mut self) -> Option<[u8; 32]> {
+        if !C::AUTH_CACHE_WITH_SECURE_BOOT && secure_boot_enabled() {
+            info!("Secure boot is enforced, not using the authentication cache");
+            return None;
+        }
+        self.config.auth_cache_key()
+    }
+
+    /// Whether the image with `tag` in `slot` has been authenticated before and can be trusted as is.
+    ///
+    /// Only holds for the target of a [Status::Confirmed] state, so new images and backups are always authenticated.
+    fn is_authenticated(&self, slot: Slot, tag: &[u8; 32]) -> bool {
+        let Some(state) = self.journal.get() else {
+            return false;
+        };
+
+        state.status() == Status::Confirmed
+            && state.target() == slot
+            && self.auth_cache.get().is_some_and(|cache| cache.matches(slot, tag))
+    }
+
+    /// Check the root keys of the image at `ram_ivt` against the revocation bits, and its RKTH against `rkth`.
+    ///
+    /// Like [CheckImage::check_image], the shadow registers are reloaded from the fuses first.
+    fn check_root_keys(&mut self, ram_ivt: &Ivt, rkth: Option<[u8; 32]>) -> Result<RootKeyCheck, BootError> {
+        let keys = self.root_keys(ram_ivt)?;
+        let slot_rkth = rkth.map(Rkth::from);
+
+        let mut shadow = reload_shadow();
+
+        Ok(RootKeyCheck {
+            revoked: root_key_revoked(&mut shadow, &keys, slot_rkth.as_ref()),
+            rkth_matches: rkth_concurs(&mut shadow, &keys.rkth, slot_rkth.as_ref())?,
+        })
+    }
+
+    /// Check the image like [CheckImage::check_image], unless it is unchanged since its last authentication.
+    pub(crate) async fn check_image_cached(&mut self, slot: Slot, ram_ivt: &Ivt) -> Result<(), BootError> {
+        let rkth = self.config.slot_rkth(slot);
+        let Some(key) = self.cache_key() else {
+            return self.check_image(ram_ivt, rkth);
+        };
+
+        let digest = self.image_digest(ram_ivt);
+        let tag = AuthCache::tag(&key, slot, &digest, |data| self.sha256(data));
+        let cached = self.is_authenticated(slot, &tag);
+        if skip_authentication(cached, || self.check_root_keys(ram_ivt, rkth))? {
+            info!(
+                "Image in {:?} is unchanged since its last authentication, skipping authentication",
+                slot
+            );
+            return Ok(());
+        }
+        if cached {
+            warn!(
+                "RKTH of the cached image in {:?} no longer matches, authenticating it",
+                slot
+            );
+        }
+
+        self.check_image(ram_ivt, rkth)?;
+
+        if let Err(e) = self.auth_cache.set(&AuthCache::new(slot, &tag)).await {
+            warn!("Failed to store the authenticated image tag: {:?}", e);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::verification::is_revoked;
+
+    fn check(revoke_rootkey: u32, root_key: usize, rkth_matches: bool) -> Result<RootKeyCheck, BootError> {
+        Ok(RootKeyCheck {
+            revoked: is_revoked(revoke_rootkey, root_key),
+            rkth_matches,
+        })
+    }
+
+    #[test]
+    fn cached_image_skips_authentication() {
+        assert!(matches!(skip_authentication(true, || check(0b0000, 1, true)), Ok(true)));
+        // Revoking another root key does not affect the image.
+        assert!(matches!(skip_authentication(true, || check(0b1101, 1, true)), Ok(true)));
+    }
+
+    #[test]
+    fn cached_image_with_revoked_root_key_is_refused() {
+        assert!(matches!(
+            skip_authentication(true, || check(0b0010, 1, true)),
+            Err(BootError::Revoked)
+        ));
+        assert!(matches!(
+            skip_authentication(true, || check(0b1111, 3, true)),
+            Err(BootError::Revoked)
+        ));
+    }
+
+    #[test]
+    fn cached_image_with_other_rkth_is_authenticated() {
+        assert!(matches!(
+            skip_authentication(true, || check(0b0000, 0, false)),
+            Ok(false)
+        ));
+    }
+
+    #[test]
+    fn uncached_image_is_authenticated() {
+        assert!(matches!(
+            skip_authentication(false, || panic!("root keys are checked by authentication")),
+            Ok(false)
+        ));
+    }
+}
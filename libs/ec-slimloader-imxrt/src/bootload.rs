@@ -1,14 +1,64 @@
 use defmt_or_log::info;
+#[cfg(not(any(feature = "xip", feature = "runtime-fcb")))]
+use defmt_or_log::warn;
 
-/// Boot an application from memory.
+/// Reset control registers of a peripheral, see chapter 4 of UM11147.
+struct PeripheralReset {
+    /// Address of the `PRSTCTLn_SET` register holding the reset bit of the peripheral.
+    set: usize,
+    /// Address of the `PRSTCTLn_CLR` register holding the reset bit of the peripheral.
+    clear: usize,
+    /// Address of the `PRSTCTLn` register reflecting the reset bit of the peripheral.
+    status: usize,
+    bit: u32,
+}
+
+impl PeripheralReset {
+    const fn new(rstctl: usize, n: usize, bit: u32) -> Self {
+        Self {
+            set: rstctl + 0x40 + 4 * n,
+            clear: rstctl + 0x70 + 4 * n,
+            status: rstctl + 0x10 + 4 * n,
+            bit,
+        }
+    }
+
+    /// Pulse the reset of the peripheral, returning it to its state after a power-on reset.
+    ///
+    /// # Safety
+    /// No driver may use the peripheral anymore.
+    unsafe fn pulse(&self) {
+        let mask = 1 << self.bit;
+        unsafe {
+            core::ptr::write_volatile(self.set as *mut u32, mask);
+            while core::ptr::read_volatile(self.status as *const u32) & mask == 0 {}
+            core::ptr::write_volatile(self.clear as *mut u32, mask);
+            while core::ptr::read_volatile(self.status as *const u32) & mask != 0 {}
+        }
+    }
+}
+
+const RSTCTL0: usize = 0x4000_0000;
+const RSTCTL1: usize = 0x4002_0000;
+
+/// Peripherals used by the bootloader that are reset before jumping, as the application expects them after reset.
+const RESETS: [(&str, PeripheralReset); 3] = [
+    ("HASHCRYPT", PeripheralReset::new(RSTCTL0, 0, 10)),
+    ("DMA0", PeripheralReset::new(RSTCTL1, 1, 23)),
+    ("DMA1", PeripheralReset::new(RSTCTL1, 1, 24)),
+];
+
+/// Hand the peripherals used by the bootloader over in the state the application expects them in after the ROM.
 ///
-/// It should follow the standard ARM Cortex M image format:
-/// initial stack pointer, vector table, program data.
+/// Masks and clears all interrupts and stops the SysTick, resets HASHCRYPT and both DMA controllers, and returns the
+/// FlexSPI controller to the configuration of the boot FCB, discarding the configuration probed by the bootloader.
+/// With `runtime-fcb`, the FCB of the FCB partition is kept instead, as the boot FCB need not suit the flash part.
+/// When executing in place, the FlexSPI controller is left as is, as it can not be reconfigured underneath the
+/// bootloader.
 ///
-/// # SAFETY
-/// The loaded application must be a valid firmware image for the platform,
-/// and it must not return control to the caller.
-pub unsafe fn boot_application(boot_address: *const u32) -> ! {
+/// # Safety
+/// No driver of the bootloader may be used afterwards, as their peripherals are reset underneath them.
+unsafe fn prepare_jump() {
     unsafe {
         // Disable interrupts globally while we reset the NVIC.
         cortex_m::interrupt::disable();
@@ -30,6 +80,40 @@ pub unsafe fn boot_application(boot_address: *const u32) -> ! {
             priority.write(0);
         }
 
+        let mut p = cortex_m::Peripherals::steal();
+        p.SYST.disable_interrupt();
+        p.SYST.disable_counter();
+        cortex_m::peripheral::SCB::clear_pendst();
+        cortex_m::peripheral::SCB::clear_pendsv();
+
+        for (_name, reset) in &RESETS {
+            reset.pulse();
+            info!("Reset {} for the application", _name);
+        }
+
+        // Note(unsafe): the bootloader runs from RAM, and nothing accesses the flash anymore.
+        #[cfg(not(any(feature = "xip", feature = "runtime-fcb")))]
+        match imxrt_rom::flexspi::restore_boot_config() {
+            Ok(()) => info!("Restored the boot FCB configuration of FlexSPI for the application"),
+            Err(_e) => warn!("Failed to restore the boot FCB configuration of FlexSPI: {:?}", _e),
+        }
+    }
+}
+
+/// Boot an application from memory.
+///
+/// It should follow the standard ARM Cortex M image format:
+/// initial stack pointer, vector table, program data.
+///
+/// Hands the peripherals used by the bootloader over first, see [prepare_jump].
+///
+/// # SAFETY
+/// The loaded application must be a valid firmware image for the platform,
+/// and it must not return control to the caller.
+pub unsafe fn boot_application(boot_address: *const u32) -> ! {
+    unsafe {
+        prepare_jump();
+
         // Re-enable interrupts globally to match boot-up environment.
         cortex_m::interrupt::enable();
 
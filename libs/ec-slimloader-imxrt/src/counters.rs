@@ -0,0 +1,30 @@
+//! Telemetry counters persisted across boots, see [ec_slimloader_state::counters].
+//!
+//! Every counted event is a write to a separate journal, such that the application can report boots,
+//! fallbacks and authentication failures for the fleet. Failing to store the counters never aborts a boot.
+
+use defmt_or_log::{debug, warn};
+use ec_slimloader_state::counters::Event;
+use static_cell::ConstStaticCell;
+
+use crate::{Imxrt, ImxrtConfig};
+
+/// Number of bytes read in a single batch when scanning the counters journal.
+const JOURNAL_BUFFER_SIZE: usize = 256;
+
+/// Buffer to scan the counters journal with, taken once when initializing the board.
+pub(crate) fn journal_buffer() -> &'static mut [u8] {
+    static BUFFER: ConstStaticCell<[u8; JOURNAL_BUFFER_SIZE]> = ConstStaticCell::new([0; JOURNAL_BUFFER_SIZE]);
+    BUFFER.take()
+}
+
+impl<C: ImxrtConfig> Imxrt<C> {
+    /// Count `event` in the counters journal, starting from zero if it is empty.
+    pub(crate) async fn count_event(&mut self, event: Event) {
+        let counters = self.counters.get().copied().unwrap_or_default().with_event(event);
+        match self.counters.set(&counters).await {
+            Ok(()) => debug!("Counted {:?}: {:?}", event, counters),
+            Err(e) => warn!("Failed to count {:?}: {:?}", event, e),
+        }
+    }
+}
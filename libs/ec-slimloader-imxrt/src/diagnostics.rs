@@ -0,0 +1,152 @@
+//! One-shot report of the full boot configuration, logged at startup when the `diagnostics` feature is enabled.
+//!
+//! Intended to be requested from devices in the field, and thus logged at the `info` level.
+
+use defmt_or_log::{info, warn};
+use embedded_storage_async::nor_flash::ReadNorFlash;
+use imxrt_rom::registers::ShadowRegisters;
+
+use crate::partitions::{bounds, StateStorage};
+use crate::{mbi, Imxrt, ImxrtConfig, Partitions, IMAGE_TYPE_TZ_XIP_SIGNED};
+
+/// Name of the FCB built into the prelude, if any.
+const FCB: &str = if cfg!(feature = "imxrt-fcb-rt685evk") {
+    "rt685evk"
+} else if cfg!(feature = "imxrt-fcb-1spi-a1-nor") {
+    "1spi-a1-nor"
+} else if cfg!(feature = "imxrt-fcb-1spi-b1-nor") {
+    "1spi-b1-nor"
+} else {
+    "none, provided externally"
+};
+
+/// Log the FCB in use and the layout of all partitions, before they are handed out.
+pub(crate) fn log_layout(partitions: &Partitions<impl StateStorage>) {
+    info!("Diagnostics: FCB {}", FCB);
+
+    match partitions.state.external_bounds() {
+        Some(state) => info!("Diagnostics: state partition {:#x}..{:#x}", state.start, state.end),
+        None => info!("Diagnostics: state kept outside the external flash"),
+    }
+
+    for (slot_i, slot) in partitions.slots.iter().enumerate() {
+        let slot = slot.bounds();
+        info!(
+            "Diagnostics: slot {} partition {:#x}..{:#x}",
+            slot_i, slot.start, slot.end
+        );
+    }
+
+    #[cfg(feature = "self-update")]
+    {
+        let journal = bounds(&partitions.self_update.journal);
+        info!(
+            "Diagnostics: self-update journal partition {:#x}..{:#x}",
+            journal.start, journal.end
+        );
+
+        for (bank_i, bank) in partitions.self_update.banks.iter().enumerate() {
+            let bank = bounds(bank);
+            info!(
+                "Diagnostics: bootloader bank {} partition {:#x}..{:#x}",
+                bank_i, bank.start, bank.end
+            );
+        }
+    }
+
+    #[cfg(feature = "auth-cache")]
+    {
+        let auth_cache = bounds(&partitions.auth_cache);
+        info!(
+            "Diagnostics: authentication cache partition {:#x}..{:#x}",
+            auth_cache.start, auth_cache.end
+        );
+    }
+
+    #[cfg(feature = "counters")]
+    {
+        let counters = bounds(&partitions.counters);
+        info!(
+            "Diagnostics: counters partition {:#x}..{:#x}",
+            counters.start, counters.end
+        );
+    }
+
+    #[cfg(feature = "runtime-fcb")]
+    {
+        let fcb = bounds(&partitions.fcb);
+        info!("Diagnostics: FCB partition {:#x}..{:#x}", fcb.start, fcb.end);
+    }
+
+    #[cfg(feature = "factory-reset")]
+    for (data_i, data) in partitions.data.iter().enumerate() {
+        let data = bounds(data);
+        info!(
+            "Diagnostics: data partition {} {:#x}..{:#x}",
+            data_i, data.start, data.end
+        );
+    }
+
+    #[cfg(feature = "self-test")]
+    {
+        let scratch = bounds(&partitions.scratch);
+        info!(
+            "Diagnostics: scratch partition {:#x}..{:#x}",
+            scratch.start, scratch.end
+        );
+    }
+}
+
+impl<C: ImxrtConfig> Imxrt<C> {
+    /// Log the shadow registers, the journal contents and a summary of the image in each slot.
+    pub(crate) async fn log_diagnostics(&mut self) {
+        let mut shadow = ShadowRegisters::new();
+        match shadow.boot_cfg_0().read() {
+            Ok(boot_cfg_0) => info!("Diagnostics: BOOT_CFG0 (shadow) {:?}", boot_cfg_0),
+            Err(e) => warn!("Diagnostics: failed to read BOOT_CFG0 (shadow): {:?}", e),
+        }
+        match shadow.boot_cfg_1().read() {
+            Ok(boot_cfg_1) => info!("Diagnostics: BOOT_CFG1 (shadow) {:?}", boot_cfg_1),
+            Err(e) => warn!("Diagnostics: failed to read BOOT_CFG1 (shadow): {:?}", e),
+        }
+        match shadow.sec_boot_cfg_5().read() {
+            Ok(sec_boot_cfg_5) => info!("Diagnostics: SEC_BOOT_CFG5 (shadow) {:?}", sec_boot_cfg_5),
+            Err(e) => warn!("Diagnostics: failed to read SEC_BOOT_CFG5 (shadow): {:?}", e),
+        }
+        match shadow.rkth().read() {
+            Ok(rkth) => info!("Diagnostics: RKTH (shadow) {}", rkth),
+            Err(e) => warn!("Diagnostics: failed to read RKTH (shadow): {:?}", e),
+        }
+
+        match self.journal.get() {
+            Some(state) => info!("Diagnostics: journal state {:?}", state),
+            None => info!("Diagnostics: journal is empty"),
+        }
+
+        #[cfg(feature = "auth-cache")]
+        match self.auth_cache.get() {
+            Some(cache) => info!("Diagnostics: authentication cache {:?}", cache),
+            None => info!("Diagnostics: authentication cache is empty"),
+        }
+
+        #[cfg(feature = "counters")]
+        match self.counters.get() {
+            Some(counters) => info!("Diagnostics: counters {:?}", counters),
+            None => info!("Diagnostics: counters are empty"),
+        }
+
+        for (slot_i, slot) in self.slots.iter_mut().enumerate() {
+            match mbi::Ivt::read(slot).await {
+                Ok(ivt) if ivt.image_type == IMAGE_TYPE_TZ_XIP_SIGNED => info!(
+                    "Diagnostics: slot {} contains a signed image of {} bytes loaded at {:?}",
+                    slot_i, ivt.image_len, ivt.target_ptr
+                ),
+                Ok(ivt) => info!(
+                    "Diagnostics: slot {} contains no signed image (image type {:#x})",
+                    slot_i, ivt.image_type
+                ),
+                Err(_e) => warn!("Diagnostics: failed to read IVT of slot {}", slot_i),
+            }
+        }
+    }
+}
@@ -0,0 +1,49 @@
+//! Erasure of the application data partitions on request, see [ImxrtConfig::FACTORY_RESET_USER_BITS].
+//!
+//! Performed by the bootloader rather than the application, such that a device can be reset to factory defaults
+//! even if the application is broken. The request in the journal is only cleared once every data partition is erased,
+//! so an interrupted factory reset is retried on the next boot.
+
+use defmt_or_log::{error, info};
+use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+use embedded_storage_async::nor_flash::{NorFlash, ReadNorFlash};
+use heapless::Vec;
+use partition_manager::{Partition, RW};
+
+use crate::{ExternalStorage, Imxrt, ImxrtConfig, MAX_DATA_PARTITIONS};
+
+/// Partition of application data that is erased by a factory reset.
+pub type DataPartition = Partition<'static, ExternalStorage, RW, NoopRawMutex>;
+
+impl<C: ImxrtConfig> Imxrt<C> {
+    /// Erase all `data` partitions if a factory reset is requested in the journal or by the board.
+    pub(crate) async fn factory_reset(&mut self, mut data: Vec<DataPartition, MAX_DATA_PARTITIONS>) {
+        let user_bits = self.journal.user_bits();
+        let by_journal = user_bits & C::FACTORY_RESET_USER_BITS != 0;
+        let by_board = self.config.factory_reset_requested();
+        if !by_journal && !by_board {
+            return;
+        }
+
+        info!("Factory reset requested (journal: {}, board: {})", by_journal, by_board);
+
+        for (partition_i, partition) in data.iter_mut().enumerate() {
+            let capacity = partition.capacity() as u32;
+            if partition.erase(0, capacity).await.is_err() {
+                error!(
+                    "Failed to erase data partition {}, retrying on the next boot",
+                    partition_i
+                );
+                return;
+            }
+            info!("Erased data partition {}", partition_i);
+        }
+
+        if by_journal {
+            let user_bits = user_bits & !C::FACTORY_RESET_USER_BITS;
+            if let Err(e) = self.journal.set_user_bits(user_bits).await {
+                error!("Failed to clear the factory reset request: {:?}", e);
+            }
+        }
+    }
+}
@@ -0,0 +1,45 @@
+//! Countermeasures against fault injection, enabled by the `hardened` feature.
+//!
+//! A glitch timed to corrupt a single read of a security-critical field could otherwise make the bootloader act on a
+//! value that was never stored. Such fields are therefore read twice, separated by a random delay such that both reads
+//! can not be hit by the same precisely timed glitch, and only used when both reads agree.
+
+use core::sync::atomic::{compiler_fence, Ordering};
+
+use ec_slimloader::BootError;
+use ec_slimloader_state::boot_info::BootProfile;
+
+use crate::ImxrtConfig;
+
+/// Upper bound of the random delay between two reads, in CPU cycles.
+const MAX_DELAY_CYCLES: u32 = 1024;
+
+/// Busy-wait for a random number of cycles, see [ImxrtConfig::random].
+pub(crate) fn random_delay<C: ImxrtConfig>(config: &mut C) {
+    cortex_m::asm::delay(config.random() % MAX_DELAY_CYCLES);
+
+    // Forces reading memory again after the delay, which would otherwise be assumed to be unchanged.
+    compiler_fence(Ordering::SeqCst);
+}
+
+/// Read a value using `read` twice with a random delay in between, failing with [BootError::ChangeAfterRead] if the
+/// reads differ.
+///
+/// Reads only once with [BootProfile::Fast].
+pub(crate) fn read_twice<C: ImxrtConfig, T: PartialEq>(
+    config: &mut C,
+    mut read: impl FnMut() -> Result<T, BootError>,
+) -> Result<T, BootError> {
+    if C::BOOT_PROFILE == BootProfile::Fast {
+        return read();
+    }
+
+    let first = read()?;
+    random_delay(config);
+    let second = read()?;
+
+    if first != second {
+        return Err(BootError::ChangeAfterRead);
+    }
+    Ok(second)
+}
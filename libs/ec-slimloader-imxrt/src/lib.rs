@@ -1,207 +1,1060 @@
 #![no_std]
 
+#[cfg(all(
+    feature = "minimal",
+    any(feature = "defmt", feature = "log", feature = "diagnostics")
+))]
+compile_error!(
+    "The `minimal` feature strips all log messages, and can not be combined with `defmt`, `log` or `diagnostics`."
+);
+
+#[cfg(all(feature = "xip", any(feature = "self-update", feature = "runtime-fcb")))]
+compile_error!(
+    "The `xip` feature runs the bootloader from the external flash, and can not be combined with `self-update` or `runtime-fcb`."
+);
+
+/// Unwrap the result of an operation that can only fail through a programming error, like a register access.
+///
+/// With the `minimal` feature this traps instead of panicking, leaving out the message and its formatting.
+macro_rules! unwrap_or_trap {
+    ($result:expr) => {{
+        #[cfg(feature = "minimal")]
+        let value = match $result {
+            Ok(value) => value,
+            Err(_) => cortex_m::asm::udf(),
+        };
+        #[cfg(not(feature = "minimal"))]
+        let value = defmt_or_log::unwrap!($result);
+        value
+    }};
+}
+
 #[cfg(feature = "fcb")]
 mod fcb;
 
-#[cfg(not(feature = "non-secure"))]
 mod verification;
 
+#[cfg(feature = "auth-cache")]
+mod auth_cache;
+#[cfg(feature = "counters")]
+mod counters;
+#[cfg(feature = "diagnostics")]
+mod diagnostics;
+#[cfg(feature = "factory-reset")]
+mod factory_reset;
+#[cfg(feature = "hardened")]
+mod hardening;
+#[cfg(feature = "runtime-fcb")]
+mod runtime_fcb;
+#[cfg(feature = "self-test")]
+mod self_test;
+
 #[cfg(feature = "empty-otfad")]
 #[link_section = ".otfad"]
 #[used]
 static OTFAD: [u8; 256] = [0x00; 256];
 
 mod bootload;
+mod load_region;
 mod mbi;
+pub mod metadata;
+mod partitions;
+#[cfg(feature = "self-update")]
+mod self_update;
+#[cfg(feature = "xip")]
+pub mod xip;
 
 use core::ops::Range;
+use core::sync::atomic::{AtomicU8, Ordering};
 
-use defmt_or_log::{error, info, panic};
-use ec_slimloader::{Board, BootError, BootStatePolicy};
+use defmt_or_log::{error, info, warn};
+#[cfg(feature = "timing")]
+use ec_slimloader::timing::{self, Stopwatch};
+#[cfg(feature = "timing")]
+use ec_slimloader::TimingPoint;
+use ec_slimloader::{Board, BootError, BootOverride, BootProgress, BootStage, BootStatePolicy};
+use ec_slimloader_state::boot_info::{BootInfo, BootProfile, BuildInfo, ImageRole};
+use ec_slimloader_state::counters::Event;
+use ec_slimloader_state::error_log::{ErrorKind, LogEntry, LogSink};
 use ec_slimloader_state::flash::FlashJournal;
+use ec_slimloader_state::shadow::Shadow;
 use ec_slimloader_state::state::Slot;
 use embassy_embedded_hal::adapter::BlockingAsync;
 use embassy_imxrt::clocks::MainClkSrc;
 use embassy_imxrt::flexspi::embedded_storage::FlexSpiNorStorage;
 use embassy_imxrt::flexspi::nor_flash::FlexSpiNorFlash;
-use embassy_imxrt::peripherals::HASHCRYPT;
+use embassy_imxrt::peripherals::{FLEXSPI, HASHCRYPT};
 use embassy_imxrt::Peri;
 use embassy_sync::blocking_mutex::raw::NoopRawMutex;
 use embedded_storage_async::nor_flash::{NorFlash, ReadNorFlash};
 use heapless::Vec;
-use partition_manager::{Partition, PartitionManager, RO, RW};
+use imxrt_rom::otp::Otp;
+pub use imxrt_rom::skboot::HashcryptIrq;
+use mbi_format::{ImageKind, ImageType, SlotBinding};
+use partition_manager::PartitionManager;
+#[cfg(any(feature = "auth-cache", feature = "counters"))]
+use partition_manager::{Partition, RW};
 use static_cell::StaticCell;
 
+#[cfg(feature = "factory-reset")]
+pub use crate::factory_reset::DataPartition;
+pub use crate::load_region::LoadRegion;
 use crate::mbi::Ivt;
+pub use crate::partitions::{
+    PartitionError, Partitions, RawPartitionMap, RawPartitions, SlotPartition, StatePartition, StateStorage,
+};
+#[cfg(feature = "self-test")]
+pub use crate::self_test::{self_test_report, ScratchPartition, SelfTest, SelfTestReport};
+#[cfg(feature = "self-update")]
+pub use crate::self_update::SelfUpdatePartitions;
+use crate::verification::SYSTEM_CORE_CLOCK_HZ;
 
-const IMAGE_TYPE_TZ_XIP_SIGNED: u32 = 0x0004;
+const IMAGE_TYPE_TZ_XIP_SIGNED: u32 = ImageType::new(ImageKind::XipPlainSigned).as_u32();
 const READ_ALIGNMENT: u32 = 2;
 const WRITE_ALIGNMENT: u32 = 2;
 const ERASE_SIZE: u32 = 4096;
 const MAX_SLOT_COUNT: usize = 7;
+/// Maximum number of partitions erased by a factory reset, see [Partitions::data].
+#[cfg(feature = "factory-reset")]
+pub const MAX_DATA_PARTITIONS: usize = 8;
+/// Number of bytes copied from a slot to RAM before reporting progress.
+const COPY_CHUNK_SIZE: usize = 16 * 1024;
 
+#[cfg(not(feature = "xip"))]
 pub type ExternalStorage = BlockingAsync<FlexSpiNorStorage<'static, READ_ALIGNMENT, WRITE_ALIGNMENT, ERASE_SIZE>>;
+#[cfg(feature = "xip")]
+pub type ExternalStorage =
+    BlockingAsync<xip::Uninterrupted<FlexSpiNorStorage<'static, READ_ALIGNMENT, WRITE_ALIGNMENT, ERASE_SIZE>>>;
 
-pub struct Partitions {
-    pub state: Partition<'static, ExternalStorage, RW, NoopRawMutex>,
-    pub slots: Vec<Partition<'static, ExternalStorage, RO, NoopRawMutex>, MAX_SLOT_COUNT>,
+/// Peripherals of an initialized HAL used by the bootloader, see [ImxrtConfig::peripherals].
+pub struct ImxrtPeripherals {
+    pub flexspi: Peri<'static, FLEXSPI>,
+    pub hashcrypt: Peri<'static, HASHCRYPT>,
 }
 
+#[allow(async_fn_in_trait)]
 pub trait ImxrtConfig {
     /// Minimum and maximum image size contained within a slot.
     const SLOT_SIZE_RANGE: Range<usize>;
 
-    /// The memory range an image is allowed to be copied to.
+    /// The memory range an image is allowed to be copied to, unless [ImxrtConfig::LOAD_REGIONS] is overridden.
+    ///
+    /// With the `self-update` feature, staged bootloader images are copied here before being installed.
+    /// Must not overlap the RAM the bootloader runs from. With the `xip` feature the bootloader code stays in flash,
+    /// such that only its data and stack need to be excluded.
     const LOAD_RANGE: Range<*mut u32>;
 
-    fn partitions(&self, flash: &'static mut PartitionManager<ExternalStorage, NoopRawMutex>) -> Partitions;
+    /// The memory regions an image is allowed to be copied to, consisting of [ImxrtConfig::LOAD_RANGE] by default.
+    ///
+    /// An image may span adjacent regions, but must start in an [executable](LoadRegion::executable) one. Allows
+    /// layouts that are not contiguous, for example with SRAM reserved for a DSP in between. None of the regions may
+    /// overlap the RAM the bootloader runs from.
+    const LOAD_REGIONS: &'static [LoadRegion] = &[LoadRegion::executable(Self::LOAD_RANGE)];
+
+    /// The memory range auxiliary images are allowed to be copied to, see [ImxrtConfig::auxiliary_slots].
+    ///
+    /// Must not overlap with [ImxrtConfig::LOAD_REGIONS], or initialization fails with [InitError::LoadRegions].
+    /// Empty by default.
+    const AUXILIARY_LOAD_RANGE: Range<*mut u32> = core::ptr::null_mut()..core::ptr::null_mut();
+
+    /// The memory regions auxiliary images are allowed to be copied to, consisting of
+    /// [ImxrtConfig::AUXILIARY_LOAD_RANGE] by default.
+    ///
+    /// Unlike application images, auxiliary images may start in a [data](LoadRegion::data) region. Like
+    /// [ImxrtConfig::AUXILIARY_LOAD_RANGE], none of the regions may overlap with [ImxrtConfig::LOAD_REGIONS].
+    const AUXILIARY_LOAD_REGIONS: &'static [LoadRegion] = &[LoadRegion::data(Self::AUXILIARY_LOAD_RANGE)];
+
+    /// Number of attempts at probing the external flash before giving up, see [ImxrtConfig::init_failed].
+    const FLASH_PROBE_ATTEMPTS: u32 = 5;
+
+    /// Delay in CPU cycles before the first retry of probing the external flash, doubled after every failed attempt.
+    const FLASH_PROBE_BACKOFF_CYCLES: u32 = 1_000_000;
+
+    /// How images are verified whilst the device is in development mode.
+    ///
+    /// Has no effect when `secure_boot_en` is fused, in which case images are always authenticated by the ROM.
+    const DEV_MODE_VERIFICATION: DevModeVerification = DevModeVerification::Authenticate;
+
+    /// How the HASHCRYPT interrupt reaches the ROM whilst authenticating images, see [imxrt_rom::skboot::HashcryptIrq].
+    ///
+    /// Use [HashcryptIrq::Isolated] when the HASHCRYPT interrupt is managed elsewhere. Uses the vector table by default.
+    const HASHCRYPT_IRQ: HashcryptIrq = HashcryptIrq::Vector;
+
+    /// Product ID of the device, which application images must carry in their metadata to be booted.
+    ///
+    /// Prevents booting firmware for a different product that happens to be signed by the same root keys.
+    /// Accepts any image by default.
+    const PRODUCT_ID: ProductId = ProductId::Any;
+
+    /// What to do with application images bound to a different slot than they are read from, see [SlotBinding].
+    ///
+    /// Images without a binding, or bound to any slot, are always accepted.
+    const SLOT_BINDING: SlotBindingPolicy = SlotBindingPolicy::Refuse;
+
+    /// Which integrity checks beyond the authentication by the ROM are performed on every boot, see [BootProfile].
+    ///
+    /// [BootProfile::Fast] skips reading the IVT of an image again after copying or mapping it, and the double reads of
+    /// the `hardened` feature, saving flash traffic. Decisions on security-critical registers are still made twice.
+    /// The profile is recorded in the [ImxrtConfig::boot_info]. Uses [BootProfile::Secure] by default.
+    const BOOT_PROFILE: BootProfile = BootProfile::Secure;
+
+    /// Version and build hash of the bootloader, recorded in the [ImxrtConfig::boot_info] for the application.
+    ///
+    /// Typically the boot image version and the revision the bootloader was built from. Unknown by default.
+    const BUILD_INFO: BuildInfo = BuildInfo::UNKNOWN;
+
+    /// Whether this bootloader is a secondary loader, booted by a first stage from one of its slots.
+    ///
+    /// A secondary loader picks up the [ImxrtConfig::boot_info] recorded by the first stage for an image marked as
+    /// [ImageRole::Loader], and forwards it to the image it boots, keeping the build of the first stage and whether the
+    /// ROM enforces secure boot. Without valid boot info of the first stage it invalidates the boot info instead, as the
+    /// chain can then not be attested. Both stages must agree on the address of the boot info. Disabled by default.
+    const SECONDARY_LOADER: bool = false;
+
+    /// Storage of the state journal, typically a [StatePartition] of the [ExternalStorage].
+    type StateStorage: StateStorage;
+
+    /// Partitions of the [ExternalStorage], mapped from a `partition_manager` manifest or from raw address ranges using
+    /// [RawPartitions].
+    fn partitions(
+        &self,
+        flash: &'static mut PartitionManager<ExternalStorage, NoopRawMutex>,
+    ) -> Partitions<Self::StateStorage>;
+
+    /// Configuration of the HAL, including the clock tree, when the bootloader initializes it.
+    ///
+    /// Not used when the board hands over [ImxrtConfig::peripherals] instead. By default the main clock runs from the
+    /// main PLL with a larger divider, as the ROM API behaves nondeterministically at higher clock speeds.
+    fn hal_config(&self) -> embassy_imxrt::config::Config {
+        let mut hal_config = embassy_imxrt::config::Config::default();
+        hal_config.clocks.main_clk.src = MainClkSrc::PllMain;
+        hal_config.clocks.main_clk.div_int = 4.into();
+        hal_config.clocks.main_pll_clk.pfd0 = 20;
+        hal_config
+    }
+
+    /// Peripherals used by the bootloader, for boards that initialize the HAL themselves.
+    ///
+    /// Called once at the start of [Board::init]. Returns [None] by default, in which case the bootloader initializes
+    /// the HAL using [ImxrtConfig::hal_config]. Boards with a different clock tree may call `embassy_imxrt::init`
+    /// before [ec_slimloader::start] instead, and hand over the peripherals here.
+    fn peripherals(&mut self) -> Option<ImxrtPeripherals> {
+        None
+    }
+
+    /// Memory mapped address of `slot` in the FlexSPI address space, if images in it can be executed in place.
+    ///
+    /// Images linked to run from this address, for example as they are larger than [ImxrtConfig::LOAD_REGIONS],
+    /// are authenticated and executed in place rather than copied to RAM. Note that the flash contents could then be
+    /// altered after authentication. Returns [None] by default, such that every image is copied.
+    fn xip_address(&self, _slot: Slot) -> Option<*const u32> {
+        None
+    }
+
+    /// Slots containing the auxiliary images to load alongside the application image in `slot`.
+    ///
+    /// Auxiliary images, for example DSP firmware, are signed like application images and copied to their load address
+    /// within [ImxrtConfig::AUXILIARY_LOAD_REGIONS]. Returns no slots by default.
+    fn auxiliary_slots(&self, _slot: Slot) -> &'static [Slot] {
+        &[]
+    }
+
+    /// Work buffer for the ROM whilst authenticating images, see [imxrt_rom::skboot::skboot_authenticate_with_buffer].
+    ///
+    /// Returns [None] by default, in which case a buffer of [imxrt_rom::skboot::DEFAULT_BUFFER_WORDS] words is placed
+    /// on the stack. Return a buffer kept in a `static`, for example using a `ConstStaticCell`, to make the stack usage
+    /// of the bootloader predictable.
+    fn auth_buffer(&mut self) -> Option<&mut [u32]> {
+        None
+    }
+
+    /// Root key table hash to authenticate the images in `slot` against, instead of the RKTH fused in the device.
+    ///
+    /// Allows booting images signed with other root keys from specific slots, for example a diagnostic image signed by
+    /// a partner. Images in such a slot must carry exactly this RKTH, which is passed to the ROM as `user_rhk`. The
+    /// revocation bits in SEC_BOOT_CFG5 only concern the fused root keys, so do not apply to these images.
+    /// Returns [None] by default, for every slot.
+    fn slot_rkth(&self, _slot: Slot) -> Option<[u8; 32]> {
+        None
+    }
+
+    /// Shadow of the state journal in RAM retained across warm resets, to skip scanning the journal, see [Shadow].
+    ///
+    /// Returns [None] by default, in which case the journal is always scanned. Return a [Shadow] placed in RAM that is
+    /// not initialized at startup, for example the `.uninit` section of `cortex-m-rt`. It is refreshed just before
+    /// jumping to the application, which may refresh it in turn after changing the journal.
+    fn state_shadow(&mut self) -> Option<&mut Shadow> {
+        None
+    }
+
+    /// Sink of the errors recorded by the bootloader, for the application to retrieve, see [ErrorLog](ec_slimloader_state::error_log::ErrorLog).
+    ///
+    /// Returns [None] by default, in which case errors are only logged. Return an `ErrorLog` placed in RAM that is not
+    /// initialized at startup, like [ImxrtConfig::state_shadow], or a sink persisting into a reserved flash partition.
+    fn error_log(&mut self) -> Option<&mut dyn LogSink> {
+        None
+    }
+
+    /// Information on the boot chain handed to the application, see [BootInfo].
+    ///
+    /// Returns [None] by default, in which case nothing is recorded. Return a [BootInfo] placed in RAM that is not
+    /// initialized at startup, at an address known to the application. It is written just before jumping to the
+    /// application, with [ImxrtConfig::BUILD_INFO], the [ImageRole] of the image from its metadata, and whether the
+    /// ROM enforces secure boot.
+    fn boot_info(&mut self) -> Option<&mut BootInfo> {
+        None
+    }
+
+    /// User bits of the state through which the application requests a factory reset, see the `factory-reset` feature.
+    ///
+    /// The bootloader clears these bits once all [Partitions::data] are erased. None by default, in which case only
+    /// [ImxrtConfig::factory_reset_requested] can request a factory reset.
+    #[cfg(feature = "factory-reset")]
+    const FACTORY_RESET_USER_BITS: u8 = 0;
+
+    /// Query whether a factory reset is requested regardless of the journal, for example by reading a strap pin.
+    ///
+    /// Called after the HAL has been initialized. Returns `false` by default.
+    #[cfg(feature = "factory-reset")]
+    fn factory_reset_requested(&mut self) -> bool {
+        false
+    }
+
+    /// User bits of the state through which a self-test is requested, see the `self-test` feature.
+    ///
+    /// The bootloader clears these bits once the self-test has run. None by default, in which case only
+    /// [ImxrtConfig::self_test_requested] can request a self-test.
+    #[cfg(feature = "self-test")]
+    const SELF_TEST_USER_BITS: u8 = 0;
+
+    /// Query whether a self-test is requested regardless of the journal, for example by reading a strap pin.
+    ///
+    /// Called after the HAL has been initialized. Returns `false` by default.
+    #[cfg(feature = "self-test")]
+    fn self_test_requested(&mut self) -> bool {
+        false
+    }
+
+    /// Report the outcome of the self-test, for example on a status register or pin read by a test fixture.
+    ///
+    /// The outcome is also retained for later retrieval through [self_test_report]. Halts the core by default.
+    #[cfg(feature = "self-test")]
+    fn self_test_done(&mut self, _report: SelfTestReport) -> ! {
+        loop {
+            cortex_m::asm::wfi();
+        }
+    }
+
+    /// Random number used to randomize the timing of security-critical reads, see the `hardened` feature.
+    ///
+    /// Should be unpredictable to an attacker, for example taken from the TRNG. Called several times per boot.
+    #[cfg(feature = "hardened")]
+    fn random(&mut self) -> u32;
+
+    /// Device-unique secret the records of the authentication cache are tagged with, see the `auth-cache` feature.
+    ///
+    /// Returns [None] by default, in which case every image is authenticated. Derive the key from a secret only the
+    /// bootloader can read, for example a PUF key or OTP fuse words that are read-locked before jumping to the
+    /// application, as anyone knowing the key can tag a tampered image.
+    #[cfg(feature = "auth-cache")]
+    fn auth_cache_key(&mut self) -> Option<[u8; 32]> {
+        None
+    }
+
+    /// Whether to use the authentication cache on a device enforcing secure boot, see the `auth-cache` feature.
+    ///
+    /// `false` by default, in which case the ROM authenticates every image once `secure_boot_en` is fused. Images
+    /// skipping authentication are still checked for revoked root keys and their RKTH, but not for rollback of their
+    /// build number.
+    #[cfg(feature = "auth-cache")]
+    const AUTH_CACHE_WITH_SECURE_BOOT: bool = false;
+
+    /// Query whether the journal state should be overridden, for example by reading a strap pin.
+    ///
+    /// Called after the HAL has been initialized. Returns [None] by default.
+    fn boot_override(&mut self) -> Option<BootOverride> {
+        None
+    }
+
+    /// Report progress of the boot process, for example to a display or host-visible status register.
+    ///
+    /// Does nothing by default.
+    async fn report(&mut self, _progress: BootProgress) {}
+
+    /// Bring up whatever is needed to read the image in `slot`, before it is first loaded.
+    ///
+    /// Called for application and auxiliary slots alike, only when they are attempted or checked for being staged. Allows deferring expensive
+    /// bring-up, such as powering a flash device holding only backup slots, off the path of a confirmed boot.
+    /// Does nothing by default.
+    async fn prepare_slot(&mut self, _slot: Slot) -> Result<(), BootError> {
+        Ok(())
+    }
+
+    /// Enter recovery instead of booting, as requested by the application, see [BootStatePolicy::RECOVERY_USER_BITS].
+    ///
+    /// For example hand over to the ROM for ISP using [imxrt_rom::isp::run_bootloader], or run a firmware update
+    /// transport of the product. Halts the core by default, like [Board::abort].
+    fn recover(&mut self) -> ! {
+        loop {
+            cortex_m::asm::wfi();
+        }
+    }
+
+    /// Handle a failure to initialize the board that prevents booting any image, for example by entering a recovery mode.
+    ///
+    /// The error is also retained for later retrieval through [init_error].
+    /// Halts the core by default, like [Board::abort].
+    fn init_failed(&mut self, _error: InitError) -> ! {
+        loop {
+            cortex_m::asm::wfi();
+        }
+    }
+}
+
+/// Failure to initialize the board, as passed to [ImxrtConfig::init_failed].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum InitError {
+    /// The external flash could not be probed, even after retrying.
+    FlashProbe = 1,
+    /// The external flash could not be wrapped in an embedded-storage adaptor.
+    FlashStorage = 2,
+    /// The partitions returned by [ImxrtConfig::partitions] are misconfigured, see [Partitions::validate].
+    Partitions = 3,
+    /// The state journal could not be read.
+    StateJournal = 4,
+    /// The authentication cache journal could not be read, see the `auth-cache` feature.
+    AuthCache = 5,
+    /// The ROM refused the FCB in the FCB partition, see the `runtime-fcb` feature.
+    RuntimeFcb = 6,
+    /// The counters journal could not be read, see the `counters` feature.
+    Counters = 7,
+    /// The [ImxrtConfig::AUXILIARY_LOAD_REGIONS] overlap with the [ImxrtConfig::LOAD_REGIONS].
+    LoadRegions = 8,
+}
+
+/// Last [InitError], if any, as a raw value such that a debugger can read it.
+static INIT_ERROR: AtomicU8 = AtomicU8::new(0);
+
+/// Retrieve the error that prevented the board from initializing, if any.
+pub fn init_error() -> Option<InitError> {
+    match INIT_ERROR.load(Ordering::Relaxed) {
+        1 => Some(InitError::FlashProbe),
+        2 => Some(InitError::FlashStorage),
+        3 => Some(InitError::Partitions),
+        4 => Some(InitError::StateJournal),
+        5 => Some(InitError::AuthCache),
+        6 => Some(InitError::RuntimeFcb),
+        7 => Some(InitError::Counters),
+        8 => Some(InitError::LoadRegions),
+        _ => None,
+    }
+}
+
+/// Record `error` and hand over to [ImxrtConfig::init_failed].
+fn init_failed<C: ImxrtConfig>(mut config: C, error: InitError) -> ! {
+    INIT_ERROR.store(error as u8, Ordering::Relaxed);
+    if let Some(error_log) = config.error_log() {
+        error_log.record(LogEntry {
+            kind: ErrorKind::Init,
+            slot: None,
+            code: error as u8,
+        });
+    }
+    config.init_failed(error)
+}
+
+/// Verification of images whilst the device is in development mode, i.e. `secure_boot_en` is not fused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DevModeVerification {
+    /// Authenticate images using the ROM, identical to a device in which `secure_boot_en` is fused.
+    Authenticate,
+    /// Check the integrity of the image against the SHA-256 digest in its metadata trailer, instead of its signature.
+    ///
+    /// The image is hashed over its full length and refused if it does not match, as is an image whose root keys (RKTH)
+    /// do not match the shadow registers. Skips the signature verification by the ROM, which is slow and sensitive to
+    /// clock jitter. As the trailer is not signed, this guards against corrupted images but not against forged ones.
+    /// Images without a digest in their trailer are authenticated by the ROM instead. Sign images with
+    /// `--image-digest` to record it.
+    Digest,
+}
+
+/// Source of the product ID of the device, see [ImxrtConfig::PRODUCT_ID].
+///
+/// Images carry their product ID in the unsigned metadata trailer, see [metadata]. This check thus guards against
+/// accidentally flashing firmware for another product, not against a malicious image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ProductId {
+    /// Boot images regardless of their product ID.
+    Any,
+    /// Only boot images carrying this product ID.
+    Fixed(u32),
+    /// Only boot images carrying the product ID programmed in the OTP fuse word with this index.
+    ///
+    /// Images are booted regardless of their product ID as long as the fuse word is not programmed, i.e. zero.
+    Otp(u32),
+}
+
+/// Handling of images bound to another slot, see [ImxrtConfig::SLOT_BINDING].
+///
+/// Unlike the product ID, the [SlotBinding] of an image is covered by its signature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SlotBindingPolicy {
+    /// Do not check the binding of images.
+    Ignore,
+    /// Log a warning, but boot the image regardless.
+    Warn,
+    /// Refuse to boot the image.
+    Refuse,
 }
 
 #[allow(dead_code)]
-pub struct Imxrt<C> {
-    journal: FlashJournal<Partition<'static, ExternalStorage, RW>>,
-    slots: Vec<Partition<'static, ExternalStorage, RO, NoopRawMutex>, MAX_SLOT_COUNT>,
+pub struct Imxrt<C: ImxrtConfig> {
+    journal: FlashJournal<'static, C::StateStorage>,
+    #[cfg(feature = "auth-cache")]
+    auth_cache: FlashJournal<'static, Partition<'static, ExternalStorage, RW>, ec_slimloader_state::auth::AuthCache>,
+    #[cfg(feature = "counters")]
+    counters: FlashJournal<'static, Partition<'static, ExternalStorage, RW>, ec_slimloader_state::counters::Counters>,
+    slots: Vec<SlotPartition, MAX_SLOT_COUNT>,
     hashcrypt: Peri<'static, HASHCRYPT>,
-    _config: C,
+    previous_stage: Option<BootInfo>,
+    /// Memory range the application image being booted was copied to, which no auxiliary image may overwrite.
+    application_range: Range<usize>,
+    /// Digest from the metadata trailer of the image last loaded, see [DevModeVerification::Digest].
+    expected_digest: Option<[u8; 32]>,
+    config: C,
 }
 
 trait CheckImage {
-    fn check_image(&mut self, _ram_ivt: &Ivt) -> Result<(), BootError>;
+    /// Authenticate the image at `ram_ivt` against `rkth`, or against the fused RKTH if [None].
+    fn check_image(&mut self, _ram_ivt: &Ivt, _rkth: Option<[u8; 32]>) -> Result<(), BootError>;
 }
 
-#[cfg(feature = "non-secure")]
-impl<C: ImxrtConfig> CheckImage for Imxrt<C> {
-    fn check_image(&mut self, _ram_ivt: &Ivt) -> Result<(), BootError> {
-        defmt_or_log::warn!("Skipped authentication because non-secure mode is set");
-        Ok(())
+impl<C: ImxrtConfig + BootStatePolicy> Imxrt<C> {
+    /// Copy the image in `slot` to its load address within `load_regions`, starting in an executable one if `execute`.
+    ///
+    /// Ensures that everything from flash is no longer used after the copy, and yields the IVT of the copy.
+    /// Images linked to run from the [ImxrtConfig::xip_address] of `slot` are not copied, yielding the IVT as
+    /// memory mapped instead. Refuses to copy over `occupied`, holding an image loaded before.
+    async fn load(
+        &mut self,
+        slot: &Slot,
+        load_regions: &[LoadRegion],
+        execute: bool,
+        occupied: &Range<usize>,
+    ) -> Result<Ivt, BootError> {
+        let slot_i = u8::from(*slot) as usize;
+        let Some(slot_size) = self.slots.get(slot_i).map(|slot_partition| slot_partition.capacity()) else {
+            return Err(BootError::SlotUnknown);
+        };
+
+        self.report(BootProgress::Stage(BootStage::Verify)).await;
+        let slot_partition = &mut self.slots[slot_i];
+
+        // Check if the image_len fits within the slot.
+        if slot_size >= C::SLOT_SIZE_RANGE.end {
+            return Err(BootError::TooLarge);
+        }
+
+        // Verify IVT fields.
+        let Ok(ivt) = mbi::Ivt::read(slot_partition).await else {
+            return Err(BootError::IO);
+        };
+
+        // Read the IVT again, such that a glitch corrupting the first read does not go unnoticed.
+        #[cfg(feature = "hardened")]
+        if C::BOOT_PROFILE == BootProfile::Secure {
+            hardening::random_delay(&mut self.config);
+            let Ok(reread_ivt) = mbi::Ivt::read(slot_partition).await else {
+                return Err(BootError::IO);
+            };
+            if ivt != reread_ivt {
+                return Err(BootError::ChangeAfterRead);
+            }
+        }
+
+        // Note: skboot_authenticate only supports checking XIP_SIGNED, even though we are loading it to RAM here.
+        if ivt.image_type != IMAGE_TYPE_TZ_XIP_SIGNED {
+            return Err(BootError::Markers);
+        }
+        if ivt.image_len > slot_size {
+            return Err(BootError::TooLarge);
+        }
+        if ivt.image_len < C::SLOT_SIZE_RANGE.start {
+            return Err(BootError::TooSmall);
+        }
+
+        self.expected_digest = None;
+        if C::DEV_MODE_VERIFICATION == DevModeVerification::Digest {
+            self.expected_digest = self.read_expected_digest(slot).await;
+        }
+
+        if self.config.xip_address(*slot) == Some(ivt.target_ptr as *const u32) {
+            info!("Executing image in place");
+
+            // Note(unsafe): the ROM clears the cache, whilst interrupts that could be fetched from the flash are masked.
+            cortex_m::interrupt::free(|_| unsafe { imxrt_rom::flexspi::clear_cache() });
+
+            if C::BOOT_PROFILE == BootProfile::Fast {
+                return Ok(ivt);
+            }
+
+            // Note(unsafe): the slot is memory mapped at its XIP address, for the length checked above.
+            let mapped_slice = unsafe { core::slice::from_raw_parts(ivt.target_ptr as *const u8, ivt.image_len) };
+            let Ok(mapped_ivt) = mbi::Ivt::read_from_slice(mapped_slice) else {
+                return Err(BootError::TooSmall);
+            };
+
+            if ivt != mapped_ivt {
+                return Err(BootError::ChangeAfterRead);
+            }
+
+            return Ok(mapped_ivt);
+        }
+
+        // Check if the target_ptr is within the allowed range.
+        // In MBI this is called the 'load_addr', which is located in 0x34 of IVT.
+        let Some(image_target_end_ptr) = ivt.target_end_ptr() else {
+            return Err(BootError::TooLarge);
+        };
+
+        let image_range = ivt.target_ptr as usize..image_target_end_ptr as usize;
+        if !load_region::check(load_regions, image_range.clone(), execute) {
+            return Err(BootError::MemoryRegion);
+        }
+
+        if load_region::ranges_overlap(&image_range, occupied) {
+            return Err(BootError::MemoryRegion);
+        }
+
+        info!("Starting copy");
+        self.report(BootProgress::Stage(BootStage::Copy)).await;
+        #[cfg(feature = "timing")]
+        let stopwatch = Stopwatch::start();
+        let target_slice = unsafe { core::slice::from_raw_parts_mut(ivt.target_ptr as *mut u8, ivt.image_len) };
+        for (chunk_i, chunk) in target_slice.chunks_mut(COPY_CHUNK_SIZE).enumerate() {
+            let offset = chunk_i * COPY_CHUNK_SIZE;
+            if let Err(_e) = self.slots[slot_i].read(offset as u32, chunk).await {
+                return Err(BootError::IO);
+            }
+
+            let done = offset + chunk.len();
+            self.report(BootProgress::Copy {
+                done,
+                total: ivt.image_len,
+            })
+            .await;
+        }
+
+        // Invalidate icache as we are writing to Code RAM, which is cached.
+        unsafe {
+            let mut p = cortex_m::Peripherals::steal();
+            p.SCB.invalidate_icache();
+        }
+        info!("Copy done");
+        #[cfg(feature = "timing")]
+        self.report(stopwatch.lap(TimingPoint::Copy)).await;
+
+        if C::BOOT_PROFILE == BootProfile::Fast {
+            return Ok(ivt);
+        }
+
+        let Ok(ram_ivt) = mbi::Ivt::read_from_slice(target_slice) else {
+            return Err(BootError::TooSmall);
+        };
+
+        if ivt != ram_ivt {
+            return Err(BootError::ChangeAfterRead);
+        }
+
+        Ok(ram_ivt)
+    }
+
+    /// Record the [ImxrtConfig::boot_info] of booting into the image with `role` in `slot`.
+    fn record_boot_info(&mut self, slot: Slot, role: ImageRole) {
+        let info = if C::SECONDARY_LOADER {
+            match self
+                .previous_stage
+                .and_then(|previous_stage| previous_stage.forward(C::BUILD_INFO, slot, role))
+                .map(|info| info.with_profile(C::BOOT_PROFILE))
+            {
+                Some(info) => info,
+                None => {
+                    warn!("No boot info of the previous stage to forward, invalidating the boot info");
+                    BootInfo::INVALID
+                }
+            }
+        } else {
+            BootInfo::new(C::BUILD_INFO, slot, role, verification::secure_boot_enabled()).with_profile(C::BOOT_PROFILE)
+        };
+        if let Some(boot_info) = self.config.boot_info() {
+            *boot_info = info;
+        }
+    }
+
+    /// Refresh the [ImxrtConfig::state_shadow] to the state journal, or invalidate it if the journal has no shadow.
+    fn refresh_shadow(&mut self) {
+        let journal_shadow = self.journal.shadow();
+        if let Some(shadow) = self.config.state_shadow() {
+            match journal_shadow {
+                Some(journal_shadow) => *shadow = journal_shadow,
+                None => shadow.invalidate(),
+            }
+        }
     }
 }
 
 impl<C: ImxrtConfig + BootStatePolicy> Board for Imxrt<C> {
     type Config = C;
 
-    async fn init<const JOURNAL_BUFFER_SIZE: usize>(config: Self::Config) -> Self {
-        // Set clock to Pll but with a larger divider, otherwise
-        // we get nondeterministic behaviour from the ROM API.
-        let mut hal_config = embassy_imxrt::config::Config::default();
-        hal_config.clocks.main_clk.src = MainClkSrc::PllMain;
-        hal_config.clocks.main_clk.div_int = 4.into();
-        hal_config.clocks.main_pll_clk.pfd0 = 20;
-        let p = embassy_imxrt::init(hal_config);
+    async fn init(mut config: Self::Config, journal_buffer: &'static mut [u8]) -> Self {
+        let ImxrtPeripherals { flexspi, hashcrypt } = match config.peripherals() {
+            Some(peripherals) => peripherals,
+            None => {
+                let p = embassy_imxrt::init(config.hal_config());
+                ImxrtPeripherals {
+                    flexspi: p.FLEXSPI,
+                    hashcrypt: p.HASHCRYPT,
+                }
+            }
+        };
+
+        info!("ROM {} ({})", imxrt_rom::info::version(), imxrt_rom::info::copyright());
 
-        let ext_flash = match unsafe { FlexSpiNorFlash::with_probed_config(p.FLEXSPI, READ_ALIGNMENT, WRITE_ALIGNMENT) }
-        {
-            Ok(ext_flash) => ext_flash,
-            Err(e) => panic!("Failed to initialize FlexSPI peripheral: {:?}", e),
+        // Retry probing with an exponential backoff, as marginal flash power-up timing can fail the first attempts.
+        let mut flexspi = Some(flexspi);
+        let mut backoff = C::FLASH_PROBE_BACKOFF_CYCLES;
+        let mut attempt = 1;
+        let ext_flash = loop {
+            // Note(unsafe): a failed probe consumes the peripheral, which is not used anywhere else in the bootloader.
+            let peripheral = flexspi.take().unwrap_or_else(|| unsafe { FLEXSPI::steal() });
+            let probe = || unsafe { FlexSpiNorFlash::with_probed_config(peripheral, READ_ALIGNMENT, WRITE_ALIGNMENT) };
+            // Executing in place, nothing may be fetched from the flash whilst it is being reconfigured.
+            #[cfg(feature = "xip")]
+            let probed = cortex_m::interrupt::free(|_| probe());
+            #[cfg(not(feature = "xip"))]
+            let probed = probe();
+            match probed {
+                Ok(ext_flash) => break ext_flash,
+                Err(e) if attempt < C::FLASH_PROBE_ATTEMPTS => {
+                    warn!(
+                        "Failed to initialize FlexSPI peripheral (attempt {}/{}): {:?}",
+                        attempt,
+                        C::FLASH_PROBE_ATTEMPTS,
+                        e
+                    );
+                    cortex_m::asm::delay(backoff);
+                    backoff = backoff.saturating_mul(2);
+                    attempt += 1;
+                }
+                Err(e) => {
+                    error!("Failed to initialize FlexSPI peripheral: {:?}", e);
+                    init_failed(config, InitError::FlashProbe)
+                }
+            }
         };
 
         let ext_flash =
             match unsafe { FlexSpiNorStorage::<READ_ALIGNMENT, WRITE_ALIGNMENT, ERASE_SIZE>::new(ext_flash) } {
                 Ok(ext_flash) => ext_flash,
-                Err(e) => panic!("Failed to wrap FlexSPI flash in embedded_storage adaptor: {:?}", e),
+                Err(e) => {
+                    error!("Failed to wrap FlexSPI flash in embedded_storage adaptor: {:?}", e);
+                    init_failed(config, InitError::FlashStorage)
+                }
             };
 
         static EXT_FLASH: StaticCell<PartitionManager<ExternalStorage, NoopRawMutex>> = StaticCell::new();
+        #[cfg(feature = "xip")]
+        let ext_flash = xip::Uninterrupted::new(ext_flash);
         let ext_flash_manager =
             EXT_FLASH.init_with(|| PartitionManager::<_, NoopRawMutex>::new(BlockingAsync::new(ext_flash)));
 
-        let Partitions { state, slots } = config.partitions(ext_flash_manager);
+        let partitions = config.partitions(ext_flash_manager);
+        if let Err(e) = partitions.validate() {
+            error!("Misconfigured partitions: {:?}", e);
+            init_failed(config, InitError::Partitions)
+        }
+
+        if load_region::overlaps(C::LOAD_REGIONS, C::AUXILIARY_LOAD_REGIONS) {
+            error!("Auxiliary load regions overlap the load regions of application images");
+            init_failed(config, InitError::LoadRegions)
+        }
 
-        let journal = match FlashJournal::new::<JOURNAL_BUFFER_SIZE>(state).await {
+        #[cfg(feature = "diagnostics")]
+        diagnostics::log_layout(&partitions);
+
+        let Partitions {
+            state,
+            slots,
+            #[cfg(feature = "self-update")]
+            self_update,
+            #[cfg(feature = "auth-cache")]
+            auth_cache,
+            #[cfg(feature = "counters")]
+            counters,
+            #[cfg(feature = "runtime-fcb")]
+            mut fcb,
+            #[cfg(feature = "factory-reset")]
+            data,
+            #[cfg(feature = "self-test")]
+            scratch,
+        } = partitions;
+
+        // Apply the FCB before anything else is read from the flash.
+        #[cfg(feature = "runtime-fcb")]
+        if let Err(e) = runtime_fcb::apply(&mut fcb).await {
+            error!("Failed to apply the FCB in the FCB partition: {:?}", e);
+            init_failed(config, InitError::RuntimeFcb)
+        }
+
+        #[cfg(feature = "timing")]
+        let stopwatch = Stopwatch::start();
+        let mut shadowed = None;
+        let journal = match config.state_shadow() {
+            Some(shadow) => {
+                shadowed = Some(*shadow);
+                FlashJournal::with_shadow(state, journal_buffer, shadow).await
+            }
+            None => FlashJournal::new(state, journal_buffer).await,
+        };
+        let mut journal = match journal {
             Ok(journal) => journal,
-            Err(e) => panic!("Failed to initialize the flash state journal: {:?}", e),
+            Err(e) => {
+                error!("Failed to initialize the flash state journal: {:?}", e);
+                init_failed(config, InitError::StateJournal)
+            }
+        };
+
+        // Reclaim pages garbled by an erase interrupted by power loss. This reads the entire journal, hence it is skipped
+        // when the shadow still matched, as the journal was then repaired on an earlier boot.
+        if shadowed.is_none() || shadowed != journal.shadow() {
+            match journal.repair().await {
+                Ok(0) => {}
+                Ok(pages) => warn!("Erased {} garbled pages of the flash state journal", pages),
+                Err(e) => warn!("Failed to repair the flash state journal: {:?}", e),
+            }
+        }
+        #[cfg(feature = "timing")]
+        let journal_scan = stopwatch.lap(TimingPoint::JournalScan);
+
+        #[cfg(feature = "auth-cache")]
+        let auth_cache = match FlashJournal::new(auth_cache, auth_cache::journal_buffer()).await {
+            Ok(auth_cache) => auth_cache,
+            Err(e) => {
+                error!("Failed to initialize the authentication cache journal: {:?}", e);
+                init_failed(config, InitError::AuthCache)
+            }
         };
 
-        Self {
+        #[cfg(feature = "counters")]
+        let counters = match FlashJournal::new(counters, counters::journal_buffer()).await {
+            Ok(counters) => counters,
+            Err(e) => {
+                error!("Failed to initialize the counters journal: {:?}", e);
+                init_failed(config, InitError::Counters)
+            }
+        };
+
+        // Read before anything is booted, as the boot info is overwritten right before jumping to the next image.
+        let previous_stage = match config.boot_info() {
+            Some(info) if C::SECONDARY_LOADER && info.role() == Some(ImageRole::Loader) => Some(*info),
+            _ => None,
+        };
+
+        #[allow(unused_mut)]
+        let mut board = Self {
             journal,
+            #[cfg(feature = "auth-cache")]
+            auth_cache,
+            #[cfg(feature = "counters")]
+            counters,
             slots,
-            hashcrypt: p.HASHCRYPT,
-            _config: config,
-        }
+            hashcrypt,
+            previous_stage,
+            application_range: 0..0,
+            expected_digest: None,
+            config,
+        };
+
+        #[cfg(feature = "timing")]
+        board.report(journal_scan).await;
+
+        #[cfg(feature = "diagnostics")]
+        board.log_diagnostics().await;
+
+        #[cfg(feature = "self-update")]
+        board.self_update(self_update).await;
+
+        #[cfg(feature = "factory-reset")]
+        board.factory_reset(data).await;
+
+        #[cfg(feature = "self-test")]
+        board.self_test(scratch).await;
+
+        board
     }
 
-    fn journal(&mut self) -> &mut FlashJournal<impl NorFlash> {
+    fn journal(&mut self) -> &mut FlashJournal<'static, impl NorFlash> {
         &mut self.journal
     }
 
-    async fn check_and_boot(&mut self, slot: &Slot) -> BootError {
-        let Some(slot_partition) = self.slots.get_mut(u8::from(*slot) as usize) else {
-            return BootError::SlotUnknown;
-        };
+    fn boot_override(&mut self) -> Option<BootOverride> {
+        self.config.boot_override()
+    }
 
-        // Copy the image to RAM from flash, and ensure that everything from flash is no longer available.
-        let ram_ivt = {
-            let slot_size = slot_partition.capacity();
+    fn previous_stage(&mut self) -> Option<BootInfo> {
+        self.previous_stage
+    }
 
-            // Check if the image_len fits within the slot.
-            if slot_size >= C::SLOT_SIZE_RANGE.end {
-                return BootError::TooLarge;
+    fn device_id(&mut self) -> [u8; 16] {
+        let mut otp = Otp::init(SYSTEM_CORE_CLOCK_HZ);
+        match imxrt_rom::info::uuid(&mut otp) {
+            Ok(uuid) => uuid,
+            Err(e) => {
+                warn!("Failed to read the device UUID from OTP: {:?}", e);
+                [0; 16]
             }
+        }
+    }
 
-            // Verify IVT fields.
-            let Ok(ivt) = mbi::Ivt::read(slot_partition).await else {
-                return BootError::IO;
-            };
+    async fn report(&mut self, progress: BootProgress) {
+        self.config.report(progress).await
+    }
 
-            // Note: skboot_authenticate only supports checking XIP_SIGNED, even though we are loading it to RAM here.
-            if ivt.image_type != IMAGE_TYPE_TZ_XIP_SIGNED {
-                return BootError::Markers;
-            }
-            if ivt.image_len > slot_size {
-                return BootError::TooLarge;
-            }
-            if ivt.image_len < C::SLOT_SIZE_RANGE.start {
-                return BootError::TooSmall;
-            }
+    async fn is_staged(&mut self, slot: &Slot) -> bool {
+        if self.config.prepare_slot(*slot).await.is_err() {
+            // The attempt to boot the slot fails likewise.
+            return false;
+        }
+        match self.slots.get_mut(u8::from(*slot) as usize) {
+            Some(slot_partition) => metadata::is_staged(slot_partition).await,
+            None => false,
+        }
+    }
 
-            // Check if the target_ptr is within the allowed range.
-            // In MBI this is called the 'load_addr', which is located in 0x34 of IVT.
-            let Some(image_target_end_ptr) = ivt.target_end_ptr() else {
-                return BootError::TooLarge;
-            };
+    async fn prepare(&mut self, slot: &Slot) -> Result<(), BootError> {
+        self.config.prepare_slot(*slot).await
+    }
 
-            if !C::LOAD_RANGE.contains(&ivt.target_ptr) || !C::LOAD_RANGE.contains(&image_target_end_ptr) {
-                return BootError::MemoryRegion;
-            }
+    #[cfg(feature = "counters")]
+    async fn count(&mut self, event: Event) {
+        self.count_event(event).await
+    }
 
-            info!("Starting copy");
-            let target_slice = unsafe { core::slice::from_raw_parts_mut(ivt.target_ptr as *mut u8, ivt.image_len) };
-            if let Err(_e) = slot_partition.read(0, target_slice).await {
-                return BootError::IO;
-            }
+    fn log(&mut self, entry: LogEntry) {
+        if let Some(error_log) = self.config.error_log() {
+            error_log.record(entry);
+        }
+    }
 
-            // Invalidate icache as we are writing to Code RAM, which is cached.
-            unsafe {
-                let mut p = cortex_m::Peripherals::steal();
-                p.SCB.invalidate_icache();
-            }
-            info!("Copy done");
+    async fn check_and_boot(&mut self, slot: &Slot) -> BootError {
+        let ram_ivt = match self.load(slot, C::LOAD_REGIONS, true, &(0..0)).await {
+            Ok(ram_ivt) => ram_ivt,
+            Err(e) => return e,
+        };
+        let application_end = ram_ivt.target_end_ptr().unwrap_or(ram_ivt.target_ptr);
+        self.application_range = ram_ivt.target_ptr as usize..application_end as usize;
 
-            let Ok(ram_ivt) = mbi::Ivt::read_from_slice(target_slice) else {
-                return BootError::TooSmall;
-            };
+        self.report(BootProgress::Stage(BootStage::Authenticate)).await;
+        #[cfg(feature = "timing")]
+        let stopwatch = Stopwatch::start();
+        #[cfg(feature = "auth-cache")]
+        let result = self.check_image_cached(*slot, &ram_ivt).await;
+        #[cfg(not(feature = "auth-cache"))]
+        let result = self.check_image(&ram_ivt, self.config.slot_rkth(*slot));
+        #[cfg(feature = "timing")]
+        self.report(stopwatch.lap(TimingPoint::Authenticate)).await;
+        if let Err(e) = result {
+            error!("Failed to boot image @ {}", slot);
+            return e;
+        }
 
-            if ivt != ram_ivt {
-                return BootError::ChangeAfterRead;
-            }
+        if let Err(e) = self.check_slot_binding(slot, &ram_ivt) {
+            error!("Refusing to boot image @ {} bound to another slot", slot);
+            return e;
+        }
 
-            ram_ivt
-        };
+        if let Err(e) = self.check_product_id(slot).await {
+            error!("Refusing to boot image @ {} built for another product", slot);
+            return e;
+        }
 
-        if let Err(e) = self.check_image(&ram_ivt) {
-            error!("Failed to boot image @ {}", slot);
+        if let Err(e) = self.load_auxiliary(slot).await {
+            error!("Failed to load auxiliary images for image @ {}", slot);
             return e;
         }
 
-        info!("Booting into application @ {:?}...", ram_ivt.target_ptr);
+        let role = match self.slots.get_mut(u8::from(*slot) as usize) {
+            Some(slot_partition) => {
+                metadata::log(slot_partition).await;
+                metadata::role(slot_partition).await
+            }
+            None => ImageRole::Application,
+        };
+
+        self.count(Event::Boot).await;
+        self.refresh_shadow();
+        self.record_boot_info(*slot, role);
+        self.report(BootProgress::Stage(BootStage::Jump)).await;
+        #[cfg(feature = "timing")]
+        self.report(timing::time_to_jump()).await;
+        match role {
+            ImageRole::Application => info!("Booting into application @ {:?}...", ram_ivt.target_ptr),
+            ImageRole::Loader => info!("Booting into secondary loader @ {:?}...", ram_ivt.target_ptr),
+        }
 
         // Boot to application, and we do not return from this function.
         unsafe { bootload::boot_application(ram_ivt.target_ptr) }
     }
 
+    async fn load_auxiliary(&mut self, slot: &Slot) -> Result<(), BootError> {
+        for aux_slot in self.config.auxiliary_slots(*slot) {
+            info!("Loading auxiliary image @ {}", aux_slot);
+            self.report(BootProgress::Auxiliary(*aux_slot)).await;
+
+            if let Err(e) = self.config.prepare_slot(*aux_slot).await {
+                warn!("Failed to prepare auxiliary slot @ {}: {:?}", aux_slot, e);
+                return Err(BootError::AuxiliaryLoad(*aux_slot));
+            }
+
+            let application_range = self.application_range.clone();
+            let ram_ivt = match self
+                .load(aux_slot, C::AUXILIARY_LOAD_REGIONS, false, &application_range)
+                .await
+            {
+                Ok(ram_ivt) => ram_ivt,
+                Err(e) => {
+                    warn!("Failed to load auxiliary image @ {}: {:?}", aux_slot, e);
+                    return Err(BootError::AuxiliaryLoad(*aux_slot));
+                }
+            };
+
+            self.report(BootProgress::Stage(BootStage::Authenticate)).await;
+            #[cfg(feature = "timing")]
+            let stopwatch = Stopwatch::start();
+            let result = self.check_image(&ram_ivt, self.config.slot_rkth(*aux_slot));
+            #[cfg(feature = "timing")]
+            self.report(stopwatch.lap(TimingPoint::Authenticate)).await;
+            if let Err(e) = result {
+                warn!("Failed to authenticate auxiliary image @ {}: {:?}", aux_slot, e);
+                return Err(BootError::AuxiliaryAuthenticate(*aux_slot));
+            }
+        }
+
+        Ok(())
+    }
+
     fn abort(&mut self) -> ! {
         loop {
             cortex_m::asm::wfi();
         }
     }
+
+    fn recover(&mut self) -> ! {
+        self.config.recover()
+    }
 }
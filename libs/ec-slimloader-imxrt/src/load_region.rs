@@ -0,0 +1,171 @@
+//! Memory regions images are allowed to be copied to, see [ImxrtConfig::LOAD_REGIONS](crate::ImxrtConfig::LOAD_REGIONS).
+
+use core::ops::Range;
+
+/// Memory region an image is allowed to be copied to, and whether code may be executed from it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadRegion {
+    /// Addresses of the region, of which the end is excluded.
+    pub range: Range<*mut u32>,
+    /// Whether the Cortex-M33 may execute from the region, as required for the entry of an application image.
+    pub execute: bool,
+}
+
+impl LoadRegion {
+    /// Region holding code, which an application image may start in.
+    pub const fn executable(range: Range<*mut u32>) -> Self {
+        Self { range, execute: true }
+    }
+
+    /// Region only holding data as seen by the Cortex-M33, for example SRAM reserved for a DSP.
+    pub const fn data(range: Range<*mut u32>) -> Self {
+        Self { range, execute: false }
+    }
+
+    fn contains(&self, address: usize) -> bool {
+        self.bounds().contains(&address)
+    }
+
+    fn bounds(&self) -> Range<usize> {
+        self.range.start as usize..self.range.end as usize
+    }
+}
+
+/// Whether the ranges `a` and `b` share any address, which empty ranges never do.
+pub(crate) fn ranges_overlap(a: &Range<usize>, b: &Range<usize>) -> bool {
+    !a.is_empty() && !b.is_empty() && a.start < b.end && b.start < a.end
+}
+
+/// Whether any of `regions` overlaps with any of `others`.
+pub(crate) fn overlaps(regions: &[LoadRegion], others: &[LoadRegion]) -> bool {
+    regions.iter().any(|region| {
+        others
+            .iter()
+            .any(|other| ranges_overlap(&region.bounds(), &other.bounds()))
+    })
+}
+
+/// Whether `image` lies within the union of `regions`, and starts in an executable region if `execute` is required.
+///
+/// The image may span adjacent regions, as long as every byte of it is within one of them. Empty images, including
+/// those of which the end wrapped around the address space, are never within the regions.
+pub(crate) fn check(regions: &[LoadRegion], image: Range<usize>, execute: bool) -> bool {
+    if image.is_empty() {
+        return false;
+    }
+
+    let Some(first) = regions.iter().find(|region| region.contains(image.start)) else {
+        return false;
+    };
+    if execute && !first.execute {
+        return false;
+    }
+
+    let mut covered = image.start;
+    while covered < image.end {
+        match regions.iter().find(|region| region.contains(covered)) {
+            Some(region) => covered = region.range.end as usize,
+            None => return false,
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn executable(range: Range<usize>) -> LoadRegion {
+        LoadRegion::executable(range.start as *mut u32..range.end as *mut u32)
+    }
+
+    fn data(range: Range<usize>) -> LoadRegion {
+        LoadRegion::data(range.start as *mut u32..range.end as *mut u32)
+    }
+
+    #[test]
+    fn single_region() {
+        let regions = [executable(0x1000..0x2000)];
+        assert!(check(&regions, 0x1000..0x2000, true));
+        assert!(check(&regions, 0x1800..0x1900, true));
+        assert!(!check(&regions, 0x0800..0x1800, true));
+        assert!(!check(&regions, 0x1800..0x2001, true));
+        assert!(!check(&regions, 0x2000..0x2100, true));
+    }
+
+    #[test]
+    fn adjacent_regions_merge() {
+        // Listed out of order, to check that the regions are not assumed sorted.
+        let regions = [
+            executable(0x2000..0x3000),
+            executable(0x1000..0x2000),
+            executable(0x3000..0x4000),
+        ];
+        assert!(check(&regions, 0x1000..0x4000, true));
+        assert!(check(&regions, 0x1ffc..0x2004, true));
+        assert!(!check(&regions, 0x1000..0x4004, true));
+    }
+
+    #[test]
+    fn gap_rejected() {
+        let regions = [executable(0x1000..0x2000), executable(0x2004..0x3000)];
+        assert!(check(&regions, 0x1000..0x2000, true));
+        assert!(!check(&regions, 0x1000..0x3000, true));
+        assert!(!check(&regions, 0x1ffc..0x2008, false));
+    }
+
+    #[test]
+    fn spanning_executable_and_data() {
+        let regions = [executable(0x1000..0x2000), data(0x2000..0x3000)];
+        assert!(check(&regions, 0x1000..0x3000, true));
+        assert!(check(&regions, 0x1000..0x3000, false));
+    }
+
+    #[test]
+    fn execute_start_in_data() {
+        let regions = [data(0x1000..0x2000), executable(0x2000..0x3000)];
+        assert!(!check(&regions, 0x1000..0x3000, true));
+        assert!(!check(&regions, 0x1ffc..0x2100, true));
+        assert!(check(&regions, 0x1000..0x3000, false));
+        assert!(check(&regions, 0x2000..0x3000, true));
+    }
+
+    #[test]
+    fn empty_image() {
+        let regions = [executable(0x1000..0x2000)];
+        assert!(!check(&regions, 0x1800..0x1800, true));
+        assert!(!check(&regions, 0x1800..0x1800, false));
+    }
+
+    #[test]
+    fn wrapped_image() {
+        let regions = [executable(0x1000..0x2000), executable(usize::MAX - 0xfff..usize::MAX)];
+        // An image of which the end wrapped past the top of the address space ends before it starts.
+        let start = usize::MAX - 0xff;
+        assert!(!check(&regions, start..start.wrapping_add(0x1800), true));
+        assert!(!check(&regions, 0x1800..0x1000, true));
+        assert!(check(&regions, start..usize::MAX, true));
+    }
+
+    #[test]
+    fn regions_overlap() {
+        let application = [executable(0x1000..0x2000), data(0x2000..0x3000)];
+        assert!(!overlaps(&application, &[data(0x3000..0x4000)]));
+        assert!(!overlaps(&application, &[data(0x0800..0x1000), data(0x4000..0x5000)]));
+        assert!(overlaps(&application, &[data(0x2ffc..0x4000)]));
+        assert!(overlaps(&application, &[data(0x0800..0x1004)]));
+        assert!(overlaps(&application, &[data(0x4000..0x5000), data(0x1800..0x1900)]));
+        assert!(overlaps(&application, &[data(0x0800..0x4000)]));
+
+        // Empty regions, such as the default auxiliary load region, overlap nothing.
+        assert!(!overlaps(&application, &[data(0..0)]));
+        assert!(!overlaps(&application, &[data(0x1800..0x1800)]));
+        assert!(!overlaps(&[], &application));
+    }
+
+    #[test]
+    fn no_regions() {
+        assert!(!check(&[], 0x1000..0x2000, false));
+        assert!(!check(&[data(0..0)], 0..4, false));
+    }
+}
@@ -0,0 +1,125 @@
+//! Metadata trailer following signed images, describing the identity of the firmware.
+//!
+//! Shared between the bootloader and applications, such that both can log which firmware is present in each slot.
+//! The trailer is not covered by the signature of the image, and is thus only informational.
+//!
+//! Images signed as staged are not booted as new target until the application [activates](activate) them, allowing
+//! an update to be installed well before it is rolled out.
+
+use defmt_or_log::info;
+use embedded_storage_async::nor_flash::{NorFlash, ReadNorFlash};
+pub use mbi_format::{ImageRole, Tag, Trailer, TrailerError};
+
+use crate::mbi::Ivt;
+
+/// Size of the buffer used by [log] and [role], sufficient for the version, product ID, git hash, build time and role.
+const LOG_BUFFER_SIZE: usize = 128;
+
+/// Largest write size of the flash supported by [activate].
+const MAX_WRITE_SIZE: usize = 16;
+
+/// Failure to read the metadata trailer of an image.
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum MetadataError<E> {
+    /// The trailer does not fit the buffer.
+    BufferTooSmall,
+    /// The trailer is missing or malformed.
+    Trailer(TrailerError),
+    /// The underlying storage medium yielded an error.
+    Other(E),
+}
+
+impl<E> From<TrailerError> for MetadataError<E> {
+    fn from(value: TrailerError) -> Self {
+        MetadataError::Trailer(value)
+    }
+}
+
+/// Read the metadata trailer following the image in `slot` into `buf`.
+///
+/// Yields [TrailerError::Missing] for images signed without metadata.
+pub async fn read<'a, F: ReadNorFlash>(
+    slot: &mut F,
+    buf: &'a mut [u8],
+) -> Result<Trailer<'a>, MetadataError<F::Error>> {
+    let ivt = Ivt::read(slot).await.map_err(MetadataError::Other)?;
+    let offset = Trailer::offset(ivt.image_len) as u32;
+
+    let header = buf
+        .get_mut(..Trailer::HEADER_LEN)
+        .ok_or(MetadataError::BufferTooSmall)?;
+    slot.read(offset, header).await.map_err(MetadataError::Other)?;
+
+    let len = (Trailer::HEADER_LEN + Trailer::entries_len(header)?).next_multiple_of(F::READ_SIZE);
+    let data = buf.get_mut(..len).ok_or(MetadataError::BufferTooSmall)?;
+    slot.read(offset, data).await.map_err(MetadataError::Other)?;
+
+    let data: &'a [u8] = data;
+    Ok(Trailer::parse(data)?)
+}
+
+/// Log the identity of the firmware in `slot`, as far as known from its metadata.
+pub async fn log<F: ReadNorFlash>(slot: &mut F) {
+    let mut buf = [0u8; LOG_BUFFER_SIZE];
+    match read(slot, &mut buf).await {
+        Ok(trailer) => {
+            if let Some(version) = trailer.version() {
+                info!("Firmware version {}", version);
+            }
+            if let Some(product_id) = trailer.product_id() {
+                info!("Firmware product ID {:#x}", product_id);
+            }
+            if let Some(git_hash) = trailer.git_hash() {
+                info!("Firmware git hash {}", git_hash);
+            }
+            if let Some(build_time) = trailer.build_time() {
+                info!("Firmware built at {} (Unix time)", build_time);
+            }
+        }
+        Err(MetadataError::Trailer(TrailerError::Missing)) => info!("Firmware has no metadata"),
+        Err(_e) => info!("Firmware metadata is unreadable"),
+    }
+}
+
+/// Role of the image in `slot` in the boot chain, as marked in its metadata.
+///
+/// Images without metadata, or without a known role, are applications. As the metadata is not signed, the role only
+/// steers the boot info handed over, never which images are authenticated.
+pub async fn role<F: ReadNorFlash>(slot: &mut F) -> ImageRole {
+    let mut buf = [0u8; LOG_BUFFER_SIZE];
+    match read(slot, &mut buf).await {
+        Ok(trailer) => trailer.role().unwrap_or(ImageRole::Application),
+        Err(_) => ImageRole::Application,
+    }
+}
+
+/// Whether the image in `slot` is staged, awaiting [activate] before the bootloader boots it as new target.
+///
+/// Images without metadata are never staged.
+pub async fn is_staged<F: ReadNorFlash>(slot: &mut F) -> bool {
+    let mut buf = [0u8; LOG_BUFFER_SIZE];
+    matches!(read(slot, &mut buf).await, Ok(trailer) if trailer.staged())
+}
+
+/// Activate the staged image in `slot`, such that the bootloader boots it once it is requested as new target.
+///
+/// Clears the [Tag::Staged] value in place by programming the write block holding it, without erasing the slot.
+/// Yields whether the image was staged.
+pub async fn activate<F: NorFlash>(slot: &mut F) -> Result<bool, MetadataError<F::Error>> {
+    let ivt = Ivt::read(slot).await.map_err(MetadataError::Other)?;
+    let mut buf = [0u8; LOG_BUFFER_SIZE];
+    let trailer = read(slot, &mut buf).await?;
+    let Some(value_offset) = trailer.value_offset(Tag::Staged).filter(|_| trailer.staged()) else {
+        return Ok(false);
+    };
+
+    let offset = (Trailer::offset(ivt.image_len) + value_offset) as u32;
+    let start = offset - offset % F::WRITE_SIZE as u32;
+    let mut block = [0u8; MAX_WRITE_SIZE];
+    let block = block.get_mut(..F::WRITE_SIZE).ok_or(MetadataError::BufferTooSmall)?;
+    slot.read(start, block).await.map_err(MetadataError::Other)?;
+    block[(offset - start) as usize] = Trailer::ACTIVATED;
+    slot.write(start, block).await.map_err(MetadataError::Other)?;
+    Ok(true)
+}
@@ -0,0 +1,416 @@
+use core::ops::Range;
+
+use defmt_or_log::FormatOrDebug;
+use embassy_sync::blocking_mutex::raw::{NoopRawMutex, RawMutex};
+use embassy_sync::mutex::Mutex;
+use embedded_storage_async::nor_flash::{ErrorType, NorFlash, ReadNorFlash};
+use heapless::Vec;
+use partition_manager::{Partition, PartitionConfig, RO, RW};
+
+use crate::{ExternalStorage, ERASE_SIZE, MAX_SLOT_COUNT};
+
+/// Partition of the [ExternalStorage] holding the state journal, the default [StateStorage].
+pub type StatePartition = Partition<'static, ExternalStorage, RW, NoopRawMutex>;
+
+/// Storage backing the state journal, see [ImxrtConfig::StateStorage](crate::ImxrtConfig::StateStorage).
+///
+/// Typically a [StatePartition], sharing the [ExternalStorage] with the slots. Boards keeping the state elsewhere, for
+/// example in internal flash or on a second NOR device, implement this for their storage, wrapping it if needed.
+pub trait StateStorage: NorFlash<Error: FormatOrDebug> {
+    /// Address range within the [ExternalStorage], if the state is kept there.
+    ///
+    /// Only then [Partitions::validate] checks that it is aligned to erase blocks and does not overlap with others.
+    fn external_bounds(&self) -> Option<Range<usize>> {
+        None
+    }
+}
+
+impl StateStorage for StatePartition {
+    fn external_bounds(&self) -> Option<Range<usize>> {
+        Some(bounds(self))
+    }
+}
+
+/// Partition of the [ExternalStorage] holding a slot, read-only unless it must be writable by the bootloader.
+///
+/// Only implements [ReadNorFlash], such that booting can not write to a slot by accident. Features writing to a slot,
+/// for example recovering an image into it, take its writable partition explicitly using [SlotPartition::writable].
+pub enum SlotPartition {
+    /// Slot that is never written by the bootloader.
+    ReadOnly(Partition<'static, ExternalStorage, RO, NoopRawMutex>),
+    /// Slot that may be written by the bootloader.
+    Writable(Partition<'static, ExternalStorage, RW, NoopRawMutex>),
+}
+
+impl SlotPartition {
+    /// Writable partition of the slot, or [None] if the slot is read-only.
+    pub fn writable(&mut self) -> Option<&mut Partition<'static, ExternalStorage, RW, NoopRawMutex>> {
+        match self {
+            Self::ReadOnly(_) => None,
+            Self::Writable(partition) => Some(partition),
+        }
+    }
+
+    /// Address range of the slot within the [ExternalStorage].
+    pub(crate) fn bounds(&self) -> Range<usize> {
+        match self {
+            Self::ReadOnly(partition) => bounds(partition),
+            Self::Writable(partition) => bounds(partition),
+        }
+    }
+}
+
+impl From<Partition<'static, ExternalStorage, RO, NoopRawMutex>> for SlotPartition {
+    fn from(partition: Partition<'static, ExternalStorage, RO, NoopRawMutex>) -> Self {
+        Self::ReadOnly(partition)
+    }
+}
+
+impl From<Partition<'static, ExternalStorage, RW, NoopRawMutex>> for SlotPartition {
+    fn from(partition: Partition<'static, ExternalStorage, RW, NoopRawMutex>) -> Self {
+        Self::Writable(partition)
+    }
+}
+
+impl ErrorType for SlotPartition {
+    type Error = <Partition<'static, ExternalStorage, RO, NoopRawMutex> as ErrorType>::Error;
+}
+
+impl ReadNorFlash for SlotPartition {
+    const READ_SIZE: usize = <Partition<'static, ExternalStorage, RO, NoopRawMutex> as ReadNorFlash>::READ_SIZE;
+
+    async fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        match self {
+            Self::ReadOnly(partition) => partition.read(offset, bytes).await,
+            Self::Writable(partition) => partition.read(offset, bytes).await,
+        }
+    }
+
+    fn capacity(&self) -> usize {
+        match self {
+            Self::ReadOnly(partition) => partition.capacity(),
+            Self::Writable(partition) => partition.capacity(),
+        }
+    }
+}
+
+/// Partitions of the [ExternalStorage] as used by the bootloader, and the storage of the state journal.
+pub struct Partitions<S = StatePartition> {
+    pub state: S,
+    /// Slots holding the images, read-only unless [SlotPartition::Writable].
+    pub slots: Vec<SlotPartition, MAX_SLOT_COUNT>,
+    /// Partitions used to install a bootloader update staged by the application.
+    #[cfg(feature = "self-update")]
+    pub self_update: crate::self_update::SelfUpdatePartitions,
+    /// Journal caching the tagged digest of the last authenticated image, see the `auth-cache` feature.
+    #[cfg(feature = "auth-cache")]
+    pub auth_cache: Partition<'static, ExternalStorage, RW, NoopRawMutex>,
+    /// Journal of telemetry counters, see the `counters` feature.
+    #[cfg(feature = "counters")]
+    pub counters: Partition<'static, ExternalStorage, RW, NoopRawMutex>,
+    /// FCB applied after booting, see the `runtime-fcb` feature.
+    #[cfg(feature = "runtime-fcb")]
+    pub fcb: Partition<'static, ExternalStorage, RO, NoopRawMutex>,
+    /// Application data partitions erased by a factory reset, see the `factory-reset` feature.
+    #[cfg(feature = "factory-reset")]
+    pub data: Vec<crate::DataPartition, { crate::MAX_DATA_PARTITIONS }>,
+    /// Partition erased and written by the self-test, see the `self-test` feature.
+    ///
+    /// Must span at least two erase blocks, and any data in it is lost when the self-test runs.
+    #[cfg(feature = "self-test")]
+    pub scratch: crate::ScratchPartition,
+}
+
+/// Misconfiguration of [Partitions] as detected by [Partitions::validate].
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PartitionError {
+    /// No slots have been defined.
+    NoSlots,
+    /// The state partition does not start or end on an erase block boundary.
+    StateNotAligned,
+    /// The slot with this index does not start or end on an erase block boundary.
+    SlotNotAligned(usize),
+    /// The state partition overlaps with the slot with this index.
+    StateOverlapsSlot(usize),
+    /// The slots with these indices overlap.
+    SlotsOverlap(usize, usize),
+    /// The FCB partition is too small to hold an FCB and its CRC.
+    #[cfg(feature = "runtime-fcb")]
+    FcbTooSmall,
+    /// The data partition with this index does not start or end on an erase block boundary.
+    #[cfg(feature = "factory-reset")]
+    DataNotAligned(usize),
+    /// The data partition with this index overlaps with the state partition.
+    #[cfg(feature = "factory-reset")]
+    DataOverlapsState(usize),
+    /// The data partition with the first index overlaps with the slot with the second index.
+    #[cfg(feature = "factory-reset")]
+    DataOverlapsSlot(usize, usize),
+    /// The scratch partition does not start or end on an erase block boundary, or spans less than two erase blocks.
+    #[cfg(feature = "self-test")]
+    ScratchNotAligned,
+    /// The scratch partition overlaps with the state partition.
+    #[cfg(feature = "self-test")]
+    ScratchOverlapsState,
+    /// The scratch partition overlaps with the slot with this index.
+    #[cfg(feature = "self-test")]
+    ScratchOverlapsSlot(usize),
+}
+
+/// Configuration for [PartitionManager::map](partition_manager::PartitionManager::map) defining partitions on raw
+/// address ranges, for boards without a `partition_manager` manifest.
+///
+/// The resulting [RawPartitionMap] hands out partitions for any `(offset, length)` range of the storage. Ranges are
+/// not checked when handed out, the layout as a whole is audited by [Partitions::validate] instead.
+pub struct RawPartitions;
+
+/// Partitions on raw address ranges of a storage, as mapped using [RawPartitions].
+pub struct RawPartitionMap<'a, F, M: RawMutex> {
+    storage: &'a Mutex<M, F>,
+}
+
+impl PartitionConfig for RawPartitions {
+    type Map<'a, F, M: RawMutex>
+        = RawPartitionMap<'a, F, M>
+    where
+        F: 'a,
+        M: 'a;
+
+    fn map<F, M: RawMutex>(self, storage: &Mutex<M, F>) -> Self::Map<'_, F, M> {
+        RawPartitionMap { storage }
+    }
+}
+
+impl<'a, F, M: RawMutex> RawPartitionMap<'a, F, M> {
+    /// Read-only partition of `length` bytes at `offset`, for example a slot.
+    pub fn ro(&self, offset: u32, length: u32) -> Partition<'a, F, RO, M> {
+        Partition::new(self.storage, offset, length)
+    }
+
+    /// Writable partition of `length` bytes at `offset`, for example the state journal.
+    pub fn rw(&self, offset: u32, length: u32) -> Partition<'a, F, RW, M> {
+        Partition::new(self.storage, offset, length)
+    }
+}
+
+/// Address range of a partition within the [ExternalStorage].
+pub(crate) fn bounds<MARKER>(partition: &Partition<'static, ExternalStorage, MARKER, NoopRawMutex>) -> Range<usize>
+where
+    Partition<'static, ExternalStorage, MARKER, NoopRawMutex>: ReadNorFlash,
+{
+    let start = partition.offset() as usize;
+    start..start + partition.capacity()
+}
+
+fn is_erase_aligned(range: &Range<usize>) -> bool {
+    range.start.is_multiple_of(ERASE_SIZE as usize) && range.end.is_multiple_of(ERASE_SIZE as usize)
+}
+
+fn overlaps(a: &Range<usize>, b: &Range<usize>) -> bool {
+    a.start < b.end && b.start < a.end
+}
+
+impl Partitions {
+    /// Collect a fixed number of slots for [Partitions::slots], checked against the maximum at compile time.
+    ///
+    /// Unlike pushing the slots one by one, this has no failure path to handle. Slots of either access mode can be
+    /// collected together by wrapping them in a [SlotPartition] first.
+    pub fn collect_slots<P: Into<SlotPartition>, const N: usize>(slots: [P; N]) -> Vec<SlotPartition, MAX_SLOT_COUNT> {
+        const { assert!(N <= MAX_SLOT_COUNT, "too many slots") };
+
+        let mut collected = Vec::new();
+        for slot in slots {
+            // Cannot fail, as asserted above.
+            let _ = collected.push(slot.into());
+        }
+        collected
+    }
+}
+
+impl<S: StateStorage> Partitions<S> {
+    /// Audit the partition layout before any of it is used.
+    ///
+    /// Checks that all partitions are aligned to erase blocks, and that neither the state nor the slots overlap.
+    /// Data partitions may not overlap either, as a factory reset would erase the state or the slots otherwise.
+    /// Neither may the scratch partition, which is overwritten by the self-test.
+    pub fn validate(&self) -> Result<(), PartitionError> {
+        #[cfg(feature = "runtime-fcb")]
+        if self.fcb.capacity() < crate::runtime_fcb::LEN {
+            return Err(PartitionError::FcbTooSmall);
+        }
+
+        Layout {
+            state: self.state.external_bounds(),
+            slots: self.slots.iter().map(SlotPartition::bounds).collect(),
+            #[cfg(feature = "factory-reset")]
+            data: self.data.iter().map(bounds).collect(),
+            #[cfg(feature = "self-test")]
+            scratch: bounds(&self.scratch),
+        }
+        .validate()
+    }
+}
+
+/// Address ranges of [Partitions] within the [ExternalStorage], as audited by [Partitions::validate].
+struct Layout {
+    state: Option<Range<usize>>,
+    slots: Vec<Range<usize>, MAX_SLOT_COUNT>,
+    #[cfg(feature = "factory-reset")]
+    data: Vec<Range<usize>, { crate::MAX_DATA_PARTITIONS }>,
+    #[cfg(feature = "self-test")]
+    scratch: Range<usize>,
+}
+
+impl Layout {
+    fn validate(&self) -> Result<(), PartitionError> {
+        if self.slots.is_empty() {
+            return Err(PartitionError::NoSlots);
+        }
+
+        let state = self.state.as_ref();
+        if state.is_some_and(|state| !is_erase_aligned(state)) {
+            return Err(PartitionError::StateNotAligned);
+        }
+
+        for (slot_i, slot) in self.slots.iter().enumerate() {
+            if !is_erase_aligned(slot) {
+                return Err(PartitionError::SlotNotAligned(slot_i));
+            }
+
+            if state.is_some_and(|state| overlaps(state, slot)) {
+                return Err(PartitionError::StateOverlapsSlot(slot_i));
+            }
+
+            for (other_i, other) in self.slots.iter().enumerate().skip(slot_i + 1) {
+                if overlaps(slot, other) {
+                    return Err(PartitionError::SlotsOverlap(slot_i, other_i));
+                }
+            }
+        }
+
+        #[cfg(feature = "factory-reset")]
+        for (data_i, data) in self.data.iter().enumerate() {
+            if !is_erase_aligned(data) {
+                return Err(PartitionError::DataNotAligned(data_i));
+            }
+
+            if state.is_some_and(|state| overlaps(state, data)) {
+                return Err(PartitionError::DataOverlapsState(data_i));
+            }
+
+            if let Some(slot_i) = self.slots.iter().position(|slot| overlaps(data, slot)) {
+                return Err(PartitionError::DataOverlapsSlot(data_i, slot_i));
+            }
+        }
+
+        #[cfg(feature = "self-test")]
+        {
+            let scratch = &self.scratch;
+            if !is_erase_aligned(scratch) || scratch.len() < 2 * ERASE_SIZE as usize {
+                return Err(PartitionError::ScratchNotAligned);
+            }
+
+            if state.is_some_and(|state| overlaps(state, scratch)) {
+                return Err(PartitionError::ScratchOverlapsState);
+            }
+
+            if let Some(slot_i) = self.slots.iter().position(|slot| overlaps(scratch, slot)) {
+                return Err(PartitionError::ScratchOverlapsSlot(slot_i));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Range of erase blocks `blocks` in bytes.
+    fn blocks(blocks: Range<usize>) -> Range<usize> {
+        blocks.start * ERASE_SIZE as usize..blocks.end * ERASE_SIZE as usize
+    }
+
+    /// Layout of `slots`, with the state in the first two erase blocks and no other partitions in the way.
+    fn layout(slots: &[Range<usize>]) -> Layout {
+        Layout {
+            state: Some(blocks(0..2)),
+            slots: slots.iter().cloned().collect(),
+            #[cfg(feature = "factory-reset")]
+            data: Vec::new(),
+            #[cfg(feature = "self-test")]
+            scratch: blocks(64..66),
+        }
+    }
+
+    #[test]
+    fn valid() {
+        assert_eq!(layout(&[blocks(2..18), blocks(18..34)]).validate(), Ok(()));
+
+        let mut external_state = layout(&[blocks(2..18)]);
+        external_state.state = None;
+        assert_eq!(external_state.validate(), Ok(()));
+    }
+
+    #[test]
+    fn no_slots() {
+        assert_eq!(layout(&[]).validate(), Err(PartitionError::NoSlots));
+    }
+
+    #[test]
+    fn slots_overlap() {
+        let sharing_block = layout(&[blocks(2..18), blocks(18..34), blocks(33..49)]);
+        assert_eq!(sharing_block.validate(), Err(PartitionError::SlotsOverlap(1, 2)));
+
+        let nested = layout(&[blocks(2..34), blocks(10..18)]);
+        assert_eq!(nested.validate(), Err(PartitionError::SlotsOverlap(0, 1)));
+    }
+
+    #[test]
+    fn slot_not_aligned() {
+        let slot = blocks(2..18);
+        let misaligned = layout(&[slot.start..slot.end - 1]);
+        assert_eq!(misaligned.validate(), Err(PartitionError::SlotNotAligned(0)));
+    }
+
+    #[test]
+    fn state_not_aligned() {
+        let mut misaligned = layout(&[blocks(2..18)]);
+        misaligned.state = Some(0..ERASE_SIZE as usize + 16);
+        assert_eq!(misaligned.validate(), Err(PartitionError::StateNotAligned));
+
+        misaligned.state = Some(512..blocks(0..2).end);
+        assert_eq!(misaligned.validate(), Err(PartitionError::StateNotAligned));
+    }
+
+    #[test]
+    fn state_overlaps_slot() {
+        let overlapping = layout(&[blocks(2..18), blocks(1..2)]);
+        assert_eq!(overlapping.validate(), Err(PartitionError::StateOverlapsSlot(1)));
+    }
+
+    #[cfg(feature = "factory-reset")]
+    #[test]
+    fn data_overlaps_slot() {
+        let mut overlapping = layout(&[blocks(2..18), blocks(18..34)]);
+        overlapping.data.push(blocks(34..36)).unwrap();
+        overlapping.data.push(blocks(17..19)).unwrap();
+        assert_eq!(overlapping.validate(), Err(PartitionError::DataOverlapsSlot(1, 0)));
+    }
+
+    #[cfg(feature = "self-test")]
+    #[test]
+    fn scratch_in_slot() {
+        let mut scratch = layout(&[blocks(2..18), blocks(18..34)]);
+        scratch.scratch = blocks(20..22);
+        assert_eq!(scratch.validate(), Err(PartitionError::ScratchOverlapsSlot(1)));
+
+        scratch.scratch = blocks(0..2);
+        assert_eq!(scratch.validate(), Err(PartitionError::ScratchOverlapsState));
+
+        scratch.scratch = blocks(40..41);
+        assert_eq!(scratch.validate(), Err(PartitionError::ScratchNotAligned));
+    }
+}
@@ -0,0 +1,57 @@
+//! Reconfiguring the external flash with an FCB stored in a partition, see the `runtime-fcb` feature.
+//!
+//! The ROM boots the bootloader using the FCB at offset 0x400 of the external flash, which then has to suit every
+//! flash part that can be fitted, for example by reading in single SPI mode at a low frequency. Once booted, the FCB
+//! in the FCB partition is applied instead, such that a single bootloader binary supports multiple flash parts.
+//!
+//! The partition holds the FCB followed by the CRC-32 of the FCB in little endian, as generated by
+//! `bootloader-tool generate fcb`. A partition that is erased or fails the CRC check is ignored,
+//! in which case the flash is used as configured by the boot FCB.
+
+use crc::{Crc, CRC_32_ISO_HDLC};
+use defmt_or_log::{info, warn};
+use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+use embedded_storage_async::nor_flash::ReadNorFlash;
+use imxrt_rom::flexspi::{self, FlexSpiNorConfig, FCB_LEN};
+use partition_manager::{Partition, RO};
+
+use crate::ExternalStorage;
+
+/// Size of the FCB and its CRC, the minimum size of the FCB partition.
+pub(crate) const LEN: usize = FCB_LEN + 4;
+
+/// Tag at the start of every FCB, "FCFB".
+const TAG: &[u8; 4] = b"FCFB";
+
+static CRC: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+
+/// Apply the FCB in `partition`, if it holds a valid one.
+///
+/// Only fails when the ROM refuses the FCB, after which the flash may no longer be accessible.
+pub(crate) async fn apply(
+    partition: &mut Partition<'static, ExternalStorage, RO, NoopRawMutex>,
+) -> Result<(), flexspi::Error> {
+    let mut buf = [0u8; LEN];
+    if let Err(e) = partition.read(0, &mut buf).await {
+        warn!("Failed to read the FCB partition, keeping the boot FCB: {:?}", e);
+        return Ok(());
+    }
+
+    let (fcb, crc) = buf.split_at(FCB_LEN);
+    if !fcb.starts_with(TAG) {
+        info!("No FCB in the FCB partition, keeping the boot FCB");
+        return Ok(());
+    }
+    if CRC.checksum(fcb) != u32::from_le_bytes([crc[0], crc[1], crc[2], crc[3]]) {
+        warn!("FCB in the FCB partition is corrupt, keeping the boot FCB");
+        return Ok(());
+    }
+
+    let mut config = FlexSpiNorConfig([0; FCB_LEN]);
+    config.0.copy_from_slice(fcb);
+
+    // Safety: the bootloader runs from RAM, and the flash is not in use until this returns.
+    unsafe { flexspi::init(&mut config) }?;
+    info!("Applied the FCB in the FCB partition");
+    Ok(())
+}
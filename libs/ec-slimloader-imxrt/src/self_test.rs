@@ -0,0 +1,214 @@
+//! Self-test of the board on request, see [ImxrtConfig::SELF_TEST_USER_BITS].
+//!
+//! Meant for manufacturing, to validate a board before keys are provisioned. Exercises the external flash on the
+//! scratch partition, the HASHCRYPT SHA-256 engine against known answers, and a journal round trip on the scratch
+//! partition, leaving the state journal and the slots untouched. The outcome is logged, retained for retrieval through
+//! [self_test_report], and handed to [ImxrtConfig::self_test_done] instead of booting.
+
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use defmt_or_log::{error, info};
+use ec_slimloader_state::flash::FlashJournal;
+use ec_slimloader_state::state::{Slot, State, Status};
+use embassy_imxrt::hashcrypt::Hashcrypt;
+use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+use embedded_storage_async::nor_flash::{NorFlash, ReadNorFlash};
+use partition_manager::{Partition, RW};
+
+use crate::{ExternalStorage, Imxrt, ImxrtConfig};
+
+/// Partition of the [ExternalStorage] that is erased and written by the self-test.
+pub type ScratchPartition = Partition<'static, ExternalStorage, RW, NoopRawMutex>;
+
+/// Number of bytes read or written at once by the self-test.
+const BLOCK_SIZE: usize = 256;
+
+/// Known answers of SHA-256, for the empty message and for `abc`.
+const SHA256_KATS: [(&[u8], [u8; 32]); 2] = [
+    (
+        b"",
+        [
+            0xe3, 0xb0, 0xc4, 0x42, 0x98, 0xfc, 0x1c, 0x14, 0x9a, 0xfb, 0xf4, 0xc8, 0x99, 0x6f, 0xb9, 0x24, 0x27, 0xae,
+            0x41, 0xe4, 0x64, 0x9b, 0x93, 0x4c, 0xa4, 0x95, 0x99, 0x1b, 0x78, 0x52, 0xb8, 0x55,
+        ],
+    ),
+    (
+        b"abc",
+        [
+            0xba, 0x78, 0x16, 0xbf, 0x8f, 0x01, 0xcf, 0xea, 0x41, 0x41, 0x40, 0xde, 0x5d, 0xae, 0x22, 0x23, 0xb0, 0x03,
+            0x61, 0xa3, 0x96, 0x17, 0x7a, 0x9c, 0xb4, 0x10, 0xff, 0x61, 0xf2, 0x00, 0x15, 0xad,
+        ],
+    ),
+];
+
+/// Test performed by the self-test, as a bit in [SelfTestReport::failures].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum SelfTest {
+    /// Erasing, writing and reading back the scratch partition.
+    Flash = 1 << 0,
+    /// SHA-256 known-answer tests on the HASHCRYPT engine.
+    Sha256 = 1 << 1,
+    /// Writing states to a journal on the scratch partition, and reading them back after reopening it.
+    Journal = 1 << 2,
+}
+
+/// Outcome of the self-test.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SelfTestReport {
+    failures: u8,
+}
+
+impl SelfTestReport {
+    /// Bit set in the raw value retained for [self_test_report], such that a report without failures is not zero.
+    const DONE: u8 = 1 << 7;
+
+    /// Whether every test passed.
+    pub fn passed(&self) -> bool {
+        self.failures == 0
+    }
+
+    /// Whether `test` failed.
+    pub fn failed(&self, test: SelfTest) -> bool {
+        self.failures & test as u8 != 0
+    }
+
+    /// Failed tests as a bitmask of [SelfTest]s, for example for a status register.
+    pub fn failures(&self) -> u8 {
+        self.failures
+    }
+}
+
+/// Last [SelfTestReport], if any, as a raw value such that a debugger or test fixture can read it.
+static SELF_TEST_REPORT: AtomicU8 = AtomicU8::new(0);
+
+/// Retrieve the outcome of the self-test, if it has run.
+pub fn self_test_report() -> Option<SelfTestReport> {
+    let raw = SELF_TEST_REPORT.load(Ordering::Relaxed);
+    (raw & SelfTestReport::DONE != 0).then_some(SelfTestReport {
+        failures: raw & !SelfTestReport::DONE,
+    })
+}
+
+/// Byte written at `address` of the scratch partition, differing between neighbouring bytes and pages.
+fn pattern(address: usize) -> u8 {
+    (address ^ (address >> 8) ^ 0xa5) as u8
+}
+
+/// Erase `scratch`, check that it reads as erased, write the [pattern] and read it back, and erase it again.
+async fn test_flash(scratch: &mut ScratchPartition) -> bool {
+    let capacity = scratch.capacity();
+    if scratch.erase(0, capacity as u32).await.is_err() {
+        return false;
+    }
+
+    let mut buf = [0u8; BLOCK_SIZE];
+    for block_start in (0..capacity).step_by(BLOCK_SIZE) {
+        let block = &mut buf[..BLOCK_SIZE.min(capacity - block_start)];
+        if scratch.read(block_start as u32, block).await.is_err() || block.iter().any(|b| *b != 0xff) {
+            return false;
+        }
+
+        block
+            .iter_mut()
+            .enumerate()
+            .for_each(|(i, b)| *b = pattern(block_start + i));
+        if scratch.write(block_start as u32, block).await.is_err() {
+            return false;
+        }
+    }
+
+    for block_start in (0..capacity).step_by(BLOCK_SIZE) {
+        let block = &mut buf[..BLOCK_SIZE.min(capacity - block_start)];
+        if scratch.read(block_start as u32, block).await.is_err()
+            || block.iter().enumerate().any(|(i, b)| *b != pattern(block_start + i))
+        {
+            return false;
+        }
+    }
+
+    scratch.erase(0, capacity as u32).await.is_ok()
+}
+
+/// Write a sequence of states to a journal on `scratch`, and check that reopening it yields the last one.
+async fn test_journal(scratch: &mut ScratchPartition) -> bool {
+    let mut buffer = [0; BLOCK_SIZE];
+    let Ok(mut journal) = FlashJournal::<_>::new(&mut *scratch, &mut buffer).await else {
+        return false;
+    };
+
+    let mut last = None;
+    for (i, status) in [Status::Initial, Status::Attempting, Status::Confirmed, Status::Failed]
+        .into_iter()
+        .enumerate()
+    {
+        let (Ok(target), Ok(backup)) = (Slot::try_from(i as u8), Slot::try_from(i as u8 + 1)) else {
+            return false;
+        };
+        let state = State::new(status, target, backup);
+        if journal.set(&state).await.is_err() {
+            return false;
+        }
+        last = Some(state);
+    }
+
+    let reopened = match FlashJournal::<_>::new(&mut *scratch, &mut buffer).await {
+        Ok(journal) => journal.get().copied(),
+        Err(_) => None,
+    };
+    let capacity = scratch.capacity() as u32;
+    reopened.is_some() && reopened == last && scratch.erase(0, capacity).await.is_ok()
+}
+
+impl<C: ImxrtConfig> Imxrt<C> {
+    /// Run the self-test instead of booting if requested in the journal or by the board.
+    pub(crate) async fn self_test(&mut self, mut scratch: ScratchPartition) {
+        let user_bits = self.journal.user_bits();
+        let by_journal = user_bits & C::SELF_TEST_USER_BITS != 0;
+        let by_board = self.config.self_test_requested();
+        if !by_journal && !by_board {
+            return;
+        }
+
+        info!("Self-test requested (journal: {}, board: {})", by_journal, by_board);
+
+        let mut failures = 0;
+        let mut check = |test: SelfTest, passed: bool| {
+            if passed {
+                info!("Self-test {:?} passed", test);
+            } else {
+                error!("Self-test {:?} failed", test);
+                failures |= test as u8;
+            }
+        };
+
+        check(SelfTest::Flash, test_flash(&mut scratch).await);
+
+        let mut hashcrypt = Hashcrypt::new_blocking(self.hashcrypt.reborrow());
+        check(
+            SelfTest::Sha256,
+            SHA256_KATS.iter().all(|(message, expected)| {
+                let mut digest = [0u8; 32];
+                hashcrypt.new_sha256().hash(*message, &mut digest);
+                digest == *expected
+            }),
+        );
+
+        check(SelfTest::Journal, test_journal(&mut scratch).await);
+
+        // Clear the request, such that the board boots normally once it is power cycled.
+        if by_journal {
+            let user_bits = user_bits & !C::SELF_TEST_USER_BITS;
+            if let Err(e) = self.journal.set_user_bits(user_bits).await {
+                error!("Failed to clear the self-test request: {:?}", e);
+            }
+        }
+
+        let report = SelfTestReport { failures };
+        SELF_TEST_REPORT.store(SelfTestReport::DONE | failures, Ordering::Relaxed);
+        info!("Self-test {}", if report.passed() { "passed" } else { "failed" });
+        self.config.self_test_done(report)
+    }
+}
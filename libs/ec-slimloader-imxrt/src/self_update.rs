@@ -0,0 +1,357 @@
+//! Installation of a bootloader image staged by the application, see [ec_slimloader_state::update].
+//!
+//! Relies on the ROM dual image boot: each bank contains a full prelude (OTFAD, FCB, BIV) followed by the bootloader,
+//! and the ROM boots the bank with the highest Boot Image Version that authenticates.
+//! The staged image is written to the inactive bank with its version word left erased,
+//! and the version word is programmed last as the commit point.
+//! An interrupted installation thus leaves the active bank untouched, and is retried on the next boot.
+//!
+//! Only the image is covered by its signature, so the staged prelude is never written. The prelude of the active bank
+//! is written instead, with the build number from the cert block of the image as its version.
+
+use defmt_or_log::{error, info, warn};
+use ec_slimloader::BootError;
+use ec_slimloader_state::flash::FlashJournal;
+use ec_slimloader_state::update::{BootloaderUpdate, UpdatePhase};
+use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+use embedded_storage_async::nor_flash::{NorFlash, ReadNorFlash};
+use mbi_format::CertBlockHeader;
+use partition_manager::{Partition, RW};
+
+use crate::mbi::Ivt;
+use crate::{CheckImage, ExternalStorage, Imxrt, ImxrtConfig};
+
+/// Size of the prelude at the start of each bank.
+const PRELUDE_SIZE: usize = 0x1000;
+/// Offset of the Boot Image Version word within a bank.
+const BIV_OFFSET: usize = 0x600;
+const BIV_SIZE: usize = 4;
+/// Number of bytes read in a single batch when scanning the bootloader update journal.
+const JOURNAL_BUFFER_SIZE: usize = 256;
+
+type BankPartition = Partition<'static, ExternalStorage, RW, NoopRawMutex>;
+
+/// Partitions required to install a staged bootloader update.
+pub struct SelfUpdatePartitions {
+    /// Journal containing the [BootloaderUpdate] record, shared with the application.
+    pub journal: Partition<'static, ExternalStorage, RW, NoopRawMutex>,
+    /// Both bootloader banks as used by the ROM dual image boot, each starting with a prelude.
+    pub banks: [BankPartition; 2],
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+enum UpdateError {
+    /// Staged image is invalid.
+    Image(BootError),
+    /// Staged image is not newer than the active bootloader, so the ROM would never boot it.
+    NotNewer,
+    /// Build number of the staged image does not fit the 16 bits of a Boot Image Version.
+    VersionTooLarge,
+    /// What we wrote to the bank does not read back identically.
+    ReadbackFailed,
+    /// The underlying NVM threw an error.
+    IO,
+}
+
+impl From<BootError> for UpdateError {
+    fn from(value: BootError) -> Self {
+        UpdateError::Image(value)
+    }
+}
+
+/// Boot Image Version word of `version`, of which the upper half is the complement of the lower half.
+const fn biv(version: u16) -> u32 {
+    version as u32 | ((!version as u32) << 16)
+}
+
+/// Version of a Boot Image Version word, or [None] if erased or malformed, which the ROM never boots.
+fn biv_version(biv: u32) -> Option<u16> {
+    let version = biv as u16;
+    ((biv >> 16) as u16 == !version).then_some(version)
+}
+
+async fn read_version(bank: &mut BankPartition) -> Result<Option<u16>, UpdateError> {
+    let mut buf = [0u8; BIV_SIZE];
+    bank.read(BIV_OFFSET as u32, &mut buf)
+        .await
+        .map_err(|_| UpdateError::IO)?;
+    Ok(biv_version(u32::from_le_bytes(buf)))
+}
+
+/// Set the Boot Image Version of `prelude` to the version of the staged `image`, and yield it.
+///
+/// The version is the build number in the cert block of the image, as covered by its signature.
+fn version_prelude(prelude: &mut [u8], image: &[u8]) -> Result<u16, UpdateError> {
+    let ivt = Ivt::read_from_slice(image).map_err(|_| BootError::TooSmall)?;
+    let header = image
+        .get(ivt.header_offset as usize..)
+        .and_then(|cert_block| CertBlockHeader::parse(cert_block).ok())
+        .ok_or(BootError::TooLarge)?;
+    let version = u16::try_from(header.build_number).map_err(|_| UpdateError::VersionTooLarge)?;
+
+    prelude[BIV_OFFSET..BIV_OFFSET + BIV_SIZE].copy_from_slice(&biv(version).to_le_bytes());
+    Ok(version)
+}
+
+/// Check whether the bank contains exactly `expected` at `offset`.
+async fn verify(bank: &mut BankPartition, offset: usize, expected: &[u8]) -> Result<(), UpdateError> {
+    let mut buf = [0u8; 256];
+    for (chunk_i, expected) in expected.chunks(buf.len()).enumerate() {
+        let actual = &mut buf[..expected.len()];
+        bank.read((offset + chunk_i * 256) as u32, actual)
+            .await
+            .map_err(|_| UpdateError::IO)?;
+
+        if actual != expected {
+            return Err(UpdateError::ReadbackFailed);
+        }
+    }
+    Ok(())
+}
+
+impl<C: ImxrtConfig> Imxrt<C> {
+    /// Install a staged bootloader update if requested in the journal, and reset into it when successful.
+    pub(crate) async fn self_update(&mut self, partitions: SelfUpdatePartitions) {
+        let SelfUpdatePartitions { journal, mut banks } = partitions;
+
+        let mut buffer = [0; JOURNAL_BUFFER_SIZE];
+        let mut journal = match FlashJournal::<_, BootloaderUpdate>::new(journal, &mut buffer).await {
+            Ok(journal) => journal,
+            Err(e) => {
+                error!("Failed to initialize the bootloader update journal: {:?}", e);
+                return;
+            }
+        };
+
+        let Some(update) = journal.get().copied() else {
+            return;
+        };
+
+        if !matches!(update.phase(), UpdatePhase::Staged | UpdatePhase::Installing) {
+            return;
+        }
+
+        info!("Installing bootloader update {:?}", update);
+        let phase = match self.install(&update, &mut banks, &mut journal).await {
+            Ok(()) => UpdatePhase::Installed,
+            Err(e) => {
+                error!("Failed to install bootloader update: {:?}", e);
+                UpdatePhase::Rejected
+            }
+        };
+
+        if let Err(e) = journal.set(&update.with_phase(phase)).await {
+            error!("Failed to store bootloader update phase: {:?}", e);
+        }
+
+        if phase == UpdatePhase::Installed {
+            info!("Bootloader update installed, resetting");
+            cortex_m::peripheral::SCB::sys_reset();
+        }
+    }
+
+    async fn install(
+        &mut self,
+        update: &BootloaderUpdate,
+        banks: &mut [BankPartition; 2],
+        journal: &mut FlashJournal<'_, Partition<'static, ExternalStorage, RW, NoopRawMutex>, BootloaderUpdate>,
+    ) -> Result<(), UpdateError> {
+        // Copy the staged image to the application load range, which is unused whilst in the bootloader.
+        // All further checks and writes use this copy, such that what is written is exactly what was authenticated.
+        let scratch = {
+            let Some(slot) = self.slots.get_mut(u8::from(update.slot()) as usize) else {
+                return Err(BootError::SlotUnknown.into());
+            };
+
+            let ivt = {
+                let mut buf = [0u8; 64];
+                slot.read(PRELUDE_SIZE as u32, &mut buf)
+                    .await
+                    .map_err(|_| UpdateError::IO)?;
+                Ivt::read_from_slice(&buf).map_err(|_| BootError::TooSmall)?
+            };
+
+            let len = PRELUDE_SIZE + ivt.image_len;
+            let scratch_capacity = C::LOAD_RANGE.end as usize - C::LOAD_RANGE.start as usize;
+            if len > slot.capacity() || len > scratch_capacity || banks.iter().any(|bank| len > bank.capacity()) {
+                return Err(BootError::TooLarge.into());
+            }
+
+            let scratch = unsafe { core::slice::from_raw_parts_mut(C::LOAD_RANGE.start as *mut u8, len) };
+            slot.read(0, scratch).await.map_err(|_| UpdateError::IO)?;
+
+            let Ok(ram_ivt) = Ivt::read_from_slice(&scratch[PRELUDE_SIZE..]) else {
+                return Err(BootError::TooSmall.into());
+            };
+
+            if ivt != ram_ivt {
+                return Err(BootError::ChangeAfterRead.into());
+            }
+
+            scratch
+        };
+
+        // Note: the image is authenticated at the scratch location, and not its own load address.
+        let image = &mut scratch[PRELUDE_SIZE..];
+        let ram_ivt = Ivt::read_from_slice(image).map_err(|_| BootError::TooSmall)?;
+        // The bootloader itself is always rooted in the fused keys, as the ROM boots it.
+        self.check_image(
+            &Ivt {
+                target_ptr: image.as_mut_ptr() as *mut u32,
+                ..ram_ivt
+            },
+            None,
+        )?;
+
+        let mut versions = [None; 2];
+        for (bank, version) in banks.iter_mut().zip(versions.iter_mut()) {
+            *version = read_version(bank).await?;
+        }
+
+        // The active bank is the one with the highest valid version, so target the other one.
+        // Without any valid version, the ROM boots the first bank.
+        let active_i = match versions {
+            [Some(a), Some(b)] if b > a => 1,
+            [None, Some(_)] => 1,
+            _ => 0,
+        };
+
+        // Replace the unsigned staged prelude by the one the ROM booted from, versioned from the signed image.
+        let (prelude, image) = scratch.split_at_mut(PRELUDE_SIZE);
+        banks[active_i].read(0, prelude).await.map_err(|_| UpdateError::IO)?;
+        let staged_version = version_prelude(prelude, image)?;
+
+        // If the update was committed but we were interrupted before updating the journal, we are done.
+        for (bank, version) in banks.iter_mut().zip(versions) {
+            if version == Some(staged_version) && verify(bank, 0, scratch).await.is_ok() {
+                warn!("Bootloader update was already installed");
+                return Ok(());
+            }
+        }
+
+        if versions[active_i].is_some_and(|active_version| staged_version <= active_version) {
+            return Err(UpdateError::NotNewer);
+        }
+
+        journal
+            .set(&update.with_phase(UpdatePhase::Installing))
+            .await
+            .map_err(|_| UpdateError::IO)?;
+
+        let bank = &mut banks[1 - active_i];
+        info!("Writing bootloader update to bank {}", 1 - active_i);
+        bank.erase(0, bank.capacity() as u32)
+            .await
+            .map_err(|_| UpdateError::IO)?;
+
+        // Write everything except the version word, which is written last to commit the update.
+        let (head, rest) = scratch.split_at(BIV_OFFSET);
+        let (version, tail) = rest.split_at(BIV_SIZE);
+        bank.write(0, head).await.map_err(|_| UpdateError::IO)?;
+        bank.write((BIV_OFFSET + BIV_SIZE) as u32, tail)
+            .await
+            .map_err(|_| UpdateError::IO)?;
+        verify(bank, 0, head).await?;
+        verify(bank, BIV_OFFSET + BIV_SIZE, tail).await?;
+
+        bank.write(BIV_OFFSET as u32, version)
+            .await
+            .map_err(|_| UpdateError::IO)?;
+        verify(bank, BIV_OFFSET, version).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IMAGE_LEN: usize = 0x200;
+    const HEADER_OFFSET: usize = 0x100;
+
+    /// Staged bootloader with build number `build_number`, of which the prelude claims the highest version.
+    fn staged(build_number: u32) -> [u8; PRELUDE_SIZE + IMAGE_LEN] {
+        let mut staged = [0x5a; PRELUDE_SIZE + IMAGE_LEN];
+        staged[BIV_OFFSET..BIV_OFFSET + BIV_SIZE].copy_from_slice(&biv(u16::MAX).to_le_bytes());
+
+        let image = &mut staged[PRELUDE_SIZE..];
+        mbi_format::Ivt {
+            image_len: IMAGE_LEN as u32,
+            image_type: 0,
+            header_offset: HEADER_OFFSET as u32,
+            load_addr: 0,
+        }
+        .write(image)
+        .unwrap();
+        CertBlockHeader {
+            signature: u32::from_le_bytes(*b"cert"),
+            header_major_version: 1,
+            header_minor_version: 0,
+            header_length: CertBlockHeader::LEN as u32,
+            flags: 0,
+            build_number,
+            total_image_length: IMAGE_LEN as u32,
+            certificate_count: 0,
+            certificate_table_length: 0,
+        }
+        .write(&mut image[HEADER_OFFSET..])
+        .unwrap();
+        staged
+    }
+
+    #[test]
+    fn boot_image_version() {
+        assert_eq!(biv(0), 0xffff_0000);
+        assert_eq!(biv(3), 0xfffc_0003);
+        for version in [0, 1, 0x1234, u16::MAX] {
+            assert_eq!(biv_version(biv(version)), Some(version));
+        }
+        assert_eq!(biv_version(0xffff_ffff), None);
+        assert_eq!(biv_version(0x0000_0003), None);
+        assert_eq!(biv_version(0xfffc_0004), None);
+    }
+
+    #[test]
+    fn tampered_prelude() {
+        // Only the prelude is tampered with, such that the image still carries a valid signature.
+        let mut staged = staged(3);
+        let image_before: [u8; IMAGE_LEN] = staged[PRELUDE_SIZE..].try_into().unwrap();
+        let active_prelude = [0xc3; PRELUDE_SIZE];
+
+        // The installation reads the prelude of the active bank over the staged one.
+        let (prelude, image) = staged.split_at_mut(PRELUDE_SIZE);
+        prelude.copy_from_slice(&active_prelude);
+        assert_eq!(version_prelude(prelude, image).unwrap(), 3);
+
+        assert_eq!(&prelude[..BIV_OFFSET], &active_prelude[..BIV_OFFSET]);
+        assert_eq!(&prelude[BIV_OFFSET..BIV_OFFSET + BIV_SIZE], &biv(3).to_le_bytes());
+        assert_eq!(
+            &prelude[BIV_OFFSET + BIV_SIZE..],
+            &active_prelude[BIV_OFFSET + BIV_SIZE..]
+        );
+        assert_eq!(image, image_before);
+    }
+
+    #[test]
+    fn version_out_of_range() {
+        let mut too_large = staged(0x1_0000);
+        let (prelude, image) = too_large.split_at_mut(PRELUDE_SIZE);
+        assert!(matches!(
+            version_prelude(prelude, image),
+            Err(UpdateError::VersionTooLarge)
+        ));
+
+        let mut truncated = staged(7);
+        let (prelude, image) = truncated.split_at_mut(PRELUDE_SIZE);
+        assert!(matches!(
+            version_prelude(prelude, &image[..HEADER_OFFSET]),
+            Err(UpdateError::Image(BootError::TooLarge))
+        ));
+        assert_eq!(
+            biv_version(u32::from_le_bytes(
+                prelude[BIV_OFFSET..BIV_OFFSET + BIV_SIZE].try_into().unwrap()
+            )),
+            Some(u16::MAX)
+        );
+    }
+}
@@ -1,17 +1,30 @@
-use defmt_or_log::{error, info, unwrap, warn};
+use defmt_or_log::{error, info, warn};
 use ec_slimloader::BootError;
+use ec_slimloader_state::state::Slot;
 use embassy_imxrt::hashcrypt::Hashcrypt;
 use embassy_imxrt::peripherals::HASHCRYPT;
 use embassy_imxrt::Peri;
 use imxrt_rom::otp::Otp;
 use imxrt_rom::registers::field_sets::Rkth;
-use imxrt_rom::registers::{OtpFuses, SecureBoot, ShadowRegisters};
+use imxrt_rom::registers::{SecureBoot, ShadowRegisters};
+use imxrt_rom::skboot;
+#[cfg(any(feature = "revocation-check", feature = "auth-cache"))]
+use mbi_format::RsaPublicKey;
+use mbi_format::{CertBlockHeader, SlotBinding};
 
 use crate::mbi::Ivt;
-use crate::{CheckImage, Imxrt, ImxrtConfig};
+use crate::metadata::{self, MetadataError};
+use crate::{CheckImage, DevModeVerification, Imxrt, ImxrtConfig, ProductId, SlotBindingPolicy};
 
 // TODO determine clock frequency from HAL.
-const SYSTEM_CORE_CLOCK_HZ: u32 = (5 * 1000 * 1000) / 2;
+pub(crate) const SYSTEM_CORE_CLOCK_HZ: u32 = (5 * 1000 * 1000) / 2;
+
+/// Size of the buffer the metadata trailer is read into, sufficient for any trailer with a reasonable version string.
+const METADATA_BUFFER_SIZE: usize = 512;
+
+/// Size of the buffer a root public key is hashed from, sufficient for an RSA-4096 modulus and a 32-bit exponent.
+#[cfg(any(feature = "revocation-check", feature = "auth-cache"))]
+const ROOT_KEY_BUFFER_SIZE: usize = 512 + 4;
 
 /// A Root Key Hash as lives in the Certificate Block at the end.
 #[derive(PartialEq, Debug)]
@@ -39,90 +52,286 @@ impl Rkh {
 
         Some(unsafe { (data.as_ptr() as *const [Rkh; 4]).read_unaligned() })
     }
-}
 
-impl<C: ImxrtConfig> CheckImage for Imxrt<C> {
-    fn check_image(&mut self, ram_ivt: &Ivt) -> Result<(), BootError> {
-        // Compute RKTH from image.
-        let image_rkth = {
-            // Safety: whilst we do not know if the image is valid by itself,
-            // this slice at least is what we just copied. (should be identical to target_slice)
-
-            use crate::mbi::CertificateBlockHeader;
-            let ram_image_slice =
-                unsafe { core::slice::from_raw_parts(ram_ivt.target_ptr as *const u8, ram_ivt.image_len) };
-            let cert_block_header_offset = ram_ivt.header_offset as usize;
-
-            // Fetch certificate block
-            let Some(cert_block_header) =
-                CertificateBlockHeader::read_from_slice(&ram_image_slice[cert_block_header_offset..])
-            else {
-                return Err(BootError::TooLarge);
-            };
-
-            if cert_block_header.header_length != 0x20 {
-                warn!("Certificate block header is not expected length");
+    /// Index in `rkhs` of the root key of the certificate chain in `cert_block`, which starts with `header`.
+    ///
+    /// The hash of a root key is computed like the ROM does, over its modulus followed by its exponent.
+    #[cfg(any(feature = "revocation-check", feature = "auth-cache"))]
+    pub fn root_key_index(
+        header: &CertBlockHeader,
+        cert_block: &[u8],
+        rkhs: &[Rkh; 4],
+        hashcrypt: Peri<HASHCRYPT>,
+    ) -> Result<usize, BootError> {
+        let key = match header.certificates(cert_block).next() {
+            Some(Ok(root)) => RsaPublicKey::from_certificate(root),
+            Some(Err(e)) => Err(e),
+            None => {
+                error!("Certificate block does not contain a root certificate");
+                return Err(BootError::Authenticate);
             }
+        };
+        let key = key.map_err(|e| {
+            error!("Failed to parse root certificate: {:?}", e);
+            BootError::Authenticate
+        })?;
 
-            let rkhs_offset = cert_block_header_offset
-                + cert_block_header.header_length as usize
-                + cert_block_header.certificate_table_length as usize;
+        let mut buf = [0u8; ROOT_KEY_BUFFER_SIZE];
+        let len = key.modulus.len() + key.exponent.len();
+        let Some(buf) = buf.get_mut(..len) else {
+            error!("Root key is larger than supported");
+            return Err(BootError::Authenticate);
+        };
+        let (modulus, exponent) = buf.split_at_mut(key.modulus.len());
+        modulus.copy_from_slice(key.modulus);
+        exponent.copy_from_slice(key.exponent);
 
-            let Some(rkhs) = Rkh::read_all_from_slice(&ram_image_slice[rkhs_offset..]) else {
-                return Err(BootError::TooLarge);
-            };
+        let mut hash = [0u8; 32];
+        Hashcrypt::new_blocking(hashcrypt).new_sha256().hash(buf, &mut hash);
 
-            Rkh::to_rkth(&rkhs, self.hashcrypt.reborrow())
-        };
+        rkhs.iter().position(|rkh| rkh.0 == hash).ok_or_else(|| {
+            error!("Root certificate key is not in the root key table");
+            BootError::Authenticate
+        })
+    }
+}
 
-        info!("RKTH (image) {:?}", image_rkth);
+/// Compare two digests in constant time, such that the timing does not reveal the length of the matching prefix.
+fn digest_eq(a: &[u8; 32], b: &[u8; 32]) -> bool {
+    let diff = a.iter().zip(b).fold(0u8, |diff, (a, b)| diff | (a ^ b));
+    core::hint::black_box(diff) == 0
+}
 
-        let mut shadow = ShadowRegisters::new();
+/// Whether two digests differ, computed independently of [digest_eq] in constant time.
+fn digest_ne(a: &[u8; 32], b: &[u8; 32]) -> bool {
+    let diff = a
+        .iter()
+        .rev()
+        .zip(b.iter().rev())
+        .fold(0u32, |diff, (a, b)| diff | u32::from(a.wrapping_sub(*b)));
+    core::hint::black_box(diff) != 0
+}
 
-        {
-            info!("Boot0 (shadow) {:?}", unwrap!(shadow.boot_cfg_0().read()));
-            info!("Boot1 (shadow) {:?}", unwrap!(shadow.boot_cfg_1().read()));
-            info!("RKTH (shadow) {:?}", unwrap!(shadow.rkth().read()));
-        }
+/// Compare two RKTHs like [digest_eq].
+fn rkth_eq(a: &Rkth, b: &Rkth) -> bool {
+    digest_eq(&<[u8; 32]>::from(*a), &<[u8; 32]>::from(*b))
+}
 
-        // Reload shadow registers.
-        {
-            let mut otp = Otp::init(SYSTEM_CORE_CLOCK_HZ);
-            {
-                let mut fuses = OtpFuses::readonly(&mut otp);
-                info!("Boot0 (fuse): {:?}", unwrap!(fuses.boot_cfg_0().read()));
-                info!("Boot1 (fuse): {:?}", unwrap!(fuses.boot_cfg_1().read()));
-                info!("RKTH (fuse): {:?}", unwrap!(fuses.rkth().read()));
-            }
-            unwrap!(otp.reload_shadow());
-            info!("Shadow registers reloaded from fuses");
-        }
+/// Whether two RKTHs differ, like [digest_ne].
+fn rkth_ne(a: &Rkth, b: &Rkth) -> bool {
+    digest_ne(&<[u8; 32]>::from(*a), &<[u8; 32]>::from(*b))
+}
+
+/// Whether `digest` of an image equals the `expected` digest from its metadata trailer.
+///
+/// Like [rkth_decide], the comparison is done twice and independently. Fails if the outcomes are inconsistent.
+fn digest_decide(expected: &[u8; 32], digest: &[u8; 32]) -> Result<bool, BootError> {
+    let eq = digest_eq(expected, digest);
+    let ne = digest_ne(expected, digest);
+    if eq == ne {
+        error!("Inconsistent image digest comparison, possibly due to fault injection");
+        return Err(BootError::Authenticate);
+    }
+    Ok(eq)
+}
+
+/// Whether `image_rkth` equals the RKTH yielded by `read`.
+///
+/// The comparison is done twice, independently and each with its own `read`, such that a single glitch can not flip
+/// the outcome. Fails if the outcomes are inconsistent.
+fn rkth_decide(image_rkth: &Rkth, mut read: impl FnMut() -> Rkth) -> Result<bool, BootError> {
+    let eq = rkth_eq(image_rkth, &read());
+    let ne = rkth_ne(image_rkth, &read());
+    if eq == ne {
+        error!("Inconsistent RKTH comparison, possibly due to fault injection");
+        return Err(BootError::Authenticate);
+    }
+    Ok(eq)
+}
+
+/// Whether `image_rkth` equals the RKTH in the shadow registers, decided like [rkth_decide].
+fn rkth_matches(shadow: &mut ShadowRegisters, image_rkth: &Rkth) -> Result<bool, BootError> {
+    rkth_decide(image_rkth, || unwrap_or_trap!(shadow.rkth().read()))
+}
 
-        // Fix for EVK without fuses.
-        #[cfg(feature = "mimxrt685s-evk")]
+/// Whether the device is in development mode, as `secure_boot_en` is not asserted.
+///
+/// Like [rkth_matches], the register is read and decided on twice. Fails if the outcomes are inconsistent.
+fn is_dev_mode(shadow: &mut ShadowRegisters) -> Result<bool, BootError> {
+    let dev_mode = unwrap_or_trap!(shadow.boot_cfg_0().read()).secure_boot_en() == SecureBoot::Disabled;
+    let secure = unwrap_or_trap!(shadow.boot_cfg_0().read()).secure_boot_en() != SecureBoot::Disabled;
+    if core::hint::black_box(dev_mode) == core::hint::black_box(secure) {
+        error!("Inconsistent secure boot configuration, possibly due to fault injection");
+        return Err(BootError::Authenticate);
+    }
+    Ok(dev_mode)
+}
+
+/// Whether the ROM enforces secure boot, as recorded in the [BootInfo](ec_slimloader_state::boot_info::BootInfo).
+///
+/// Inconsistent reads of the register report secure boot as disabled, such that the application never trusts the
+/// chain more than it should.
+pub(crate) fn secure_boot_enabled() -> bool {
+    matches!(is_dev_mode(&mut ShadowRegisters::new()), Ok(false))
+}
+
+/// Root keys of an image, as checked before authenticating it.
+pub(crate) struct RootKeys {
+    /// Hash of the root key table of the image.
+    pub(crate) rkth: Rkth,
+    /// Index of the root key of the image in the root key table, checked against the revocation bits.
+    #[cfg(any(feature = "revocation-check", feature = "auth-cache"))]
+    pub(crate) index: usize,
+    /// Build number of the image, logged when refused by the rollback protection of the ROM.
+    pub(crate) build_number: u32,
+}
+
+/// Whether `root_key` is revoked by the `revoke_rootkey` bits of SEC_BOOT_CFG5.
+#[cfg(any(feature = "revocation-check", feature = "auth-cache"))]
+pub(crate) fn is_revoked(revoke_rootkey: u32, root_key: usize) -> bool {
+    revoke_rootkey & (1 << root_key) != 0
+}
+
+/// Whether the root key of `keys` is revoked in the shadow registers.
+///
+/// The revocation bits only concern the fused root keys, and not those of a slot with its own RKTH.
+#[cfg(any(feature = "revocation-check", feature = "auth-cache"))]
+pub(crate) fn root_key_revoked(shadow: &mut ShadowRegisters, keys: &RootKeys, slot_rkth: Option<&Rkth>) -> bool {
+    if slot_rkth.is_some() {
+        return false;
+    }
+    let revoked = unwrap_or_trap!(shadow.sec_boot_cfg_5().read()).revoke_rootkey();
+    if is_revoked(u32::from(revoked), keys.index) {
+        error!("Image is rooted in root key {}, which is revoked", keys.index);
+        return true;
+    }
+    false
+}
+
+/// Whether `image_rkth` equals `slot_rkth` if given, or the RKTH in the shadow registers otherwise.
+pub(crate) fn rkth_concurs(
+    shadow: &mut ShadowRegisters,
+    image_rkth: &Rkth,
+    slot_rkth: Option<&Rkth>,
+) -> Result<bool, BootError> {
+    match slot_rkth {
+        Some(slot_rkth) => rkth_decide(image_rkth, || *slot_rkth),
+        None => rkth_matches(shadow, image_rkth),
+    }
+}
+
+/// Reload the shadow registers from the fuses, undoing any change to them before a warm reset.
+pub(crate) fn reload_shadow() -> ShadowRegisters {
+    // Only modified for the EVK.
+    #[allow(unused_mut)]
+    let mut shadow = ShadowRegisters::new();
+
+    // Only read the registers when they are logged.
+    #[cfg(any(feature = "defmt", feature = "log"))]
+    {
+        info!("Boot0 (shadow) {:?}", unwrap_or_trap!(shadow.boot_cfg_0().read()));
+        info!("Boot1 (shadow) {:?}", unwrap_or_trap!(shadow.boot_cfg_1().read()));
+        info!("RKTH (shadow) {:?}", unwrap_or_trap!(shadow.rkth().read()));
+    }
+
+    {
+        let mut otp = Otp::init(SYSTEM_CORE_CLOCK_HZ);
+        #[cfg(any(feature = "defmt", feature = "log"))]
         {
-            // Configure the EVK NOR flash @ port 2, pin 12 to be reset on a system reset.
-            unwrap!(shadow.boot_cfg_1().modify(|w| {
-                w.set_qspi_reset_pin_enable(true);
-                w.set_qspi_reset_pin_port(2);
-                w.set_qspi_reset_pin_num(12);
-            }));
+            let mut fuses = imxrt_rom::registers::OtpFuses::readonly(&mut otp);
+            info!("Boot0 (fuse): {:?}", unwrap_or_trap!(fuses.boot_cfg_0().read()));
+            info!("Boot1 (fuse): {:?}", unwrap_or_trap!(fuses.boot_cfg_1().read()));
+            info!("RKTH (fuse): {:?}", unwrap_or_trap!(fuses.rkth().read()));
         }
+        unwrap_or_trap!(otp.reload_shadow());
+        info!("Shadow registers reloaded from fuses");
+    }
 
-        {
-            info!("Boot0 (shadow reloaded) {:?}", unwrap!(shadow.boot_cfg_0().read()));
-            info!("Boot1 (shadow reloaded) {:?}", unwrap!(shadow.boot_cfg_1().read()));
-            info!("RKTH (shadow reloaded) {}", unwrap!(shadow.rkth().read()));
+    // Fix for EVK without fuses.
+    #[cfg(feature = "mimxrt685s-evk")]
+    {
+        // Configure the EVK NOR flash @ port 2, pin 12 to be reset on a system reset.
+        unwrap_or_trap!(shadow.boot_cfg_1().modify(|w| {
+            w.set_qspi_reset_pin_enable(true);
+            w.set_qspi_reset_pin_port(2);
+            w.set_qspi_reset_pin_num(12);
+        }));
+    }
+
+    #[cfg(any(feature = "defmt", feature = "log"))]
+    {
+        info!(
+            "Boot0 (shadow reloaded) {:?}",
+            unwrap_or_trap!(shadow.boot_cfg_0().read())
+        );
+        info!(
+            "Boot1 (shadow reloaded) {:?}",
+            unwrap_or_trap!(shadow.boot_cfg_1().read())
+        );
+        info!("RKTH (shadow reloaded) {}", unwrap_or_trap!(shadow.rkth().read()));
+    }
+
+    shadow
+}
+
+impl<C: ImxrtConfig> CheckImage for Imxrt<C> {
+    fn check_image(&mut self, ram_ivt: &Ivt, rkth: Option<[u8; 32]>) -> Result<(), BootError> {
+        let keys = self.root_keys(ram_ivt)?;
+        let image_rkth = keys.rkth;
+        info!("RKTH (image) {:?}", image_rkth);
+
+        // Images in slots with their own RKTH are checked against that instead of the shadow registers.
+        let slot_rkth = rkth.map(Rkth::from);
+        let matches = |shadow: &mut ShadowRegisters| rkth_concurs(shadow, &image_rkth, slot_rkth.as_ref());
+
+        let mut shadow = reload_shadow();
+
+        // The ROM would refuse an image rooted in a revoked key as well, but without telling why.
+        #[cfg(feature = "revocation-check")]
+        if root_key_revoked(&mut shadow, &keys, slot_rkth.as_ref()) {
+            return Err(BootError::Revoked);
         }
 
         // Whether the hardware is in 'development mode' is dependent on the secure_boot_en bit being asserted.
-        let dev_mode = unwrap!(shadow.boot_cfg_0().read()).secure_boot_en() == SecureBoot::Disabled;
+        let dev_mode = is_dev_mode(&mut shadow)?;
+
+        // The digest is taken, such that it never applies to an image checked without being loaded first.
+        let expected_digest = self.expected_digest.take();
+        if dev_mode && C::DEV_MODE_VERIFICATION == DevModeVerification::Digest {
+            match expected_digest {
+                Some(expected_digest) => {
+                    if !matches(&mut shadow)? {
+                        error!("Shadow and image RKTH do not concur");
+                        return Err(BootError::Authenticate);
+                    }
+                    let digest = self.image_digest(ram_ivt);
+                    if !digest_decide(&expected_digest, &digest)? {
+                        error!("Image digest does not match the digest in its metadata");
+                        return Err(BootError::Authenticate);
+                    }
+                    // Decide again before skipping authentication, as a single glitched branch would suffice otherwise.
+                    if !is_dev_mode(&mut shadow)? || !matches(&mut shadow)? || digest_ne(&expected_digest, &digest) {
+                        error!("Inconsistent decision to skip authentication, possibly due to fault injection");
+                        return Err(BootError::Authenticate);
+                    }
+                    warn!("Development mode detected, skipping authentication as the image digest matches");
+                    return Ok(());
+                }
+                None => warn!("Image carries no digest in its metadata, authenticating it instead"),
+            }
+        }
 
-        if image_rkth != unwrap!(shadow.rkth().read()) {
-            if dev_mode {
+        if slot_rkth.is_some() {
+            if !matches(&mut shadow)? {
+                error!("Image RKTH does not match the RKTH of its slot");
+                return Err(BootError::Authenticate);
+            }
+            info!("Slot and image RKTH concur!")
+        } else if !rkth_matches(&mut shadow, &image_rkth)? {
+            if dev_mode && is_dev_mode(&mut shadow)? {
                 // If no SECURE_BOOT fuse set => overwrite shadow RKTH with image RKTH
                 warn!("Development mode detected, using new image RKTH {}", image_rkth);
-                unwrap!(shadow.rkth().write(|w| *w = image_rkth));
+                unwrap_or_trap!(shadow.rkth().write(|w| *w = image_rkth));
             } else {
                 // If SECURE_BOOT fuse set => do nothing as skboot_authenticate should be annoyed (perhaps assert afterwards)
                 error!("Shadow and image RKTH do not concur, but we call skboot_authenticate in any case");
@@ -135,11 +344,24 @@ impl<C: ImxrtConfig> CheckImage for Imxrt<C> {
         // Call the ROM API to ensure that the image is signed and not broken or tampered with.
         // Note: skboot_authenticate will show false-negatives if your clock jitter is too high.
         // We noticed this with FFROdiv2 and MainClk > 475MHz.
-        match imxrt_rom::skboot::skboot_authenticate(ram_ivt.target_ptr, ram_ivt.image_len as u32, None) {
-            Ok(()) => {
-                info!("Authenticate succeeded!");
+        let (start, len) = (ram_ivt.target_ptr, ram_ivt.image_len as u32);
+        let result = match self.config.auth_buffer() {
+            Some(buffer) => skboot::skboot_authenticate_with_buffer(start, len, rkth, C::HASHCRYPT_IRQ, buffer),
+            None => skboot::skboot_authenticate(start, len, rkth, C::HASHCRYPT_IRQ),
+        };
+        match result {
+            Ok(timing) => {
+                info!(
+                    "Authenticate succeeded in {} cycles ({} of which authenticating)",
+                    timing.total_cycles(),
+                    timing.authenticate_cycles
+                );
                 Ok(())
             }
+            Err(skboot::AuthenticateError::Rollback) => {
+                warn!("Refused rollback of image with build number {}", keys.build_number);
+                Err(BootError::Rollback)
+            }
             Err(e) => {
                 warn!("Failed to authenticate {:?}", e);
                 Err(BootError::Authenticate)
@@ -147,3 +369,154 @@ impl<C: ImxrtConfig> CheckImage for Imxrt<C> {
         }
     }
 }
+
+impl<C: ImxrtConfig> Imxrt<C> {
+    /// Root keys of the image at `ram_ivt`, read from its certificate block.
+    pub(crate) fn root_keys(&mut self, ram_ivt: &Ivt) -> Result<RootKeys, BootError> {
+        // Safety: whilst we do not know if the image is valid by itself,
+        // this slice at least is what we just copied. (should be identical to target_slice)
+        let ram_image_slice =
+            unsafe { core::slice::from_raw_parts(ram_ivt.target_ptr as *const u8, ram_ivt.image_len) };
+        let cert_block_header_offset = ram_ivt.header_offset as usize;
+
+        // Fetch certificate block
+        let Some(cert_block) = ram_image_slice.get(cert_block_header_offset..) else {
+            return Err(BootError::TooLarge);
+        };
+        let read_header = || CertBlockHeader::parse(cert_block).map_err(|_| BootError::TooLarge);
+        #[cfg(feature = "hardened")]
+        let cert_block_header = crate::hardening::read_twice(&mut self.config, read_header)?;
+        #[cfg(not(feature = "hardened"))]
+        let cert_block_header = read_header()?;
+
+        if cert_block_header.header_length as usize != CertBlockHeader::LEN {
+            warn!("Certificate block header is not expected length");
+        }
+
+        let rkhs_offset = cert_block_header_offset + cert_block_header.root_key_hashes_offset();
+
+        let read_rkhs = || {
+            ram_image_slice
+                .get(rkhs_offset..)
+                .and_then(Rkh::read_all_from_slice)
+                .ok_or(BootError::TooLarge)
+        };
+        #[cfg(feature = "hardened")]
+        let rkhs = crate::hardening::read_twice(&mut self.config, read_rkhs)?;
+        #[cfg(not(feature = "hardened"))]
+        let rkhs = read_rkhs()?;
+
+        Ok(RootKeys {
+            #[cfg(any(feature = "revocation-check", feature = "auth-cache"))]
+            index: Rkh::root_key_index(&cert_block_header, cert_block, &rkhs, self.hashcrypt.reborrow())?,
+            rkth: Rkh::to_rkth(&rkhs, self.hashcrypt.reborrow()),
+            build_number: cert_block_header.build_number,
+        })
+    }
+
+    /// SHA-256 digest of `data`, computed by the HASHCRYPT engine.
+    pub(crate) fn sha256(&mut self, data: &[u8]) -> [u8; 32] {
+        let mut hashcrypt = Hashcrypt::new_blocking(self.hashcrypt.reborrow());
+
+        let mut digest = [0u8; 32];
+        hashcrypt.new_sha256().hash(data, &mut digest);
+        digest
+    }
+
+    /// SHA-256 digest of the image as copied to RAM, or as memory mapped when executed in place.
+    pub(crate) fn image_digest(&mut self, ram_ivt: &Ivt) -> [u8; 32] {
+        // Safety: the image has been copied to this location, and its length has been checked against the load range.
+        let image = unsafe { core::slice::from_raw_parts(ram_ivt.target_ptr as *const u8, ram_ivt.image_len) };
+        self.sha256(image)
+    }
+
+    /// Digest of the image in `slot` as carried in its metadata trailer, see [DevModeVerification::Digest].
+    pub(crate) async fn read_expected_digest(&mut self, slot: &Slot) -> Option<[u8; 32]> {
+        let slot_partition = self.slots.get_mut(u8::from(*slot) as usize)?;
+        let mut buf = [0u8; METADATA_BUFFER_SIZE];
+        metadata::read(slot_partition, &mut buf).await.ok()?.image_digest()
+    }
+
+    /// Check that the image in `slot` carries the product ID of the device in its metadata, see [ProductId].
+    ///
+    /// Images without metadata or without product ID are refused unless any image is accepted.
+    pub(crate) async fn check_product_id(&mut self, slot: &Slot) -> Result<(), BootError> {
+        let device_id = match C::PRODUCT_ID {
+            ProductId::Any => return Ok(()),
+            ProductId::Fixed(id) => id,
+            ProductId::Otp(word) => {
+                let mut otp = Otp::init(SYSTEM_CORE_CLOCK_HZ);
+                match otp.read_fuse(word) {
+                    Ok(0) => {
+                        warn!("Product ID fuse is not programmed, accepting image regardless of its product ID");
+                        return Ok(());
+                    }
+                    Ok(id) => id,
+                    Err(e) => {
+                        error!("Failed to read product ID fuse: {:?}", e);
+                        return Err(BootError::IO);
+                    }
+                }
+            }
+        };
+
+        let Some(slot_partition) = self.slots.get_mut(u8::from(*slot) as usize) else {
+            return Err(BootError::SlotUnknown);
+        };
+
+        let mut buf = [0u8; METADATA_BUFFER_SIZE];
+        let image_id = match metadata::read(slot_partition, &mut buf).await {
+            Ok(trailer) => trailer.product_id(),
+            Err(MetadataError::Other(_)) => return Err(BootError::IO),
+            Err(_) => None,
+        };
+
+        if image_id == Some(device_id) {
+            info!("Image product ID matches device product ID {:#x}", device_id);
+            Ok(())
+        } else {
+            error!(
+                "Image product ID {:?} does not match device product ID {:#x}",
+                image_id, device_id
+            );
+            Err(BootError::ProductMismatch)
+        }
+    }
+
+    /// Check that the authenticated image at `ram_ivt` is not bound to a slot other than `slot`.
+    pub(crate) fn check_slot_binding(&self, slot: &Slot, ram_ivt: &Ivt) -> Result<(), BootError> {
+        if C::SLOT_BINDING == SlotBindingPolicy::Ignore {
+            return Ok(());
+        }
+
+        let header_offset = ram_ivt.header_offset as usize;
+        let Some(offset) = SlotBinding::offset(header_offset).filter(|_| header_offset <= ram_ivt.image_len) else {
+            return Ok(());
+        };
+
+        // Note(unsafe): the binding lies within the image, which has been loaded and authenticated at `target_ptr`.
+        let data =
+            unsafe { core::slice::from_raw_parts((ram_ivt.target_ptr as *const u8).add(offset), SlotBinding::LEN) };
+        let Some(binding) = SlotBinding::parse(data) else {
+            return Ok(());
+        };
+
+        if binding.allows(u8::from(*slot)) {
+            return Ok(());
+        }
+
+        match C::SLOT_BINDING {
+            SlotBindingPolicy::Warn => {
+                warn!(
+                    "Image @ {} is bound to slot {:?}, booting regardless",
+                    slot, binding.slot
+                );
+                Ok(())
+            }
+            _ => {
+                error!("Image @ {} is bound to slot {:?}", slot, binding.slot);
+                Err(BootError::SlotMismatch)
+            }
+        }
+    }
+}
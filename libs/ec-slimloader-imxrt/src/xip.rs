@@ -0,0 +1,45 @@
+//! Access to the external flash whilst the bootloader executes in place from it, see the `xip` feature.
+//!
+//! The flash can not be read whilst it is being erased or programmed, so nothing may be fetched from it meanwhile.
+//! The FlexSPI NOR driver performs these operations through the ROM, but an interrupt handler in the bootloader would
+//! still be fetched from the flash. Every operation is thus performed with interrupts masked.
+
+use embedded_storage::nor_flash::{ErrorType, NorFlash, ReadNorFlash};
+
+/// Storage performing every operation with interrupts masked.
+pub struct Uninterrupted<T>(T);
+
+impl<T> Uninterrupted<T> {
+    pub(crate) fn new(storage: T) -> Self {
+        Self(storage)
+    }
+}
+
+impl<T: ErrorType> ErrorType for Uninterrupted<T> {
+    type Error = T::Error;
+}
+
+impl<T: ReadNorFlash> ReadNorFlash for Uninterrupted<T> {
+    const READ_SIZE: usize = T::READ_SIZE;
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        cortex_m::interrupt::free(|_| self.0.read(offset, bytes))
+    }
+
+    fn capacity(&self) -> usize {
+        self.0.capacity()
+    }
+}
+
+impl<T: NorFlash> NorFlash for Uninterrupted<T> {
+    const WRITE_SIZE: usize = T::WRITE_SIZE;
+    const ERASE_SIZE: usize = T::ERASE_SIZE;
+
+    fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+        cortex_m::interrupt::free(|_| self.0.erase(from, to))
+    }
+
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        cortex_m::interrupt::free(|_| self.0.write(offset, bytes))
+    }
+}
@@ -0,0 +1,52 @@
+use crc::{Crc, CRC_32_ISO_HDLC};
+
+pub(crate) static CRC: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+}
+
+/// Header at the start of each update package, describing the image following it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Header {
+    /// Length of the image.
+    pub image_len: u32,
+    /// CRC32 of the image.
+    pub image_crc: u32,
+}
+
+impl Header {
+    /// Length of the encoded header.
+    pub const LEN: usize = 12;
+
+    const MAGIC: [u8; 4] = *b"ECOT";
+
+    /// Header describing `image`.
+    pub fn for_image(image: &[u8]) -> Self {
+        Self {
+            image_len: image.len() as u32,
+            image_crc: CRC.checksum(image),
+        }
+    }
+
+    /// Parse the header, yielding [None] if the magic does not match.
+    pub fn parse(data: &[u8; Self::LEN]) -> Option<Self> {
+        if data[..4] != Self::MAGIC {
+            return None;
+        }
+
+        Some(Self {
+            image_len: read_u32(data, 4),
+            image_crc: read_u32(data, 8),
+        })
+    }
+
+    pub fn to_bytes(&self) -> [u8; Self::LEN] {
+        let mut out = [0u8; Self::LEN];
+        out[..4].copy_from_slice(&Self::MAGIC);
+        out[4..8].copy_from_slice(&self.image_len.to_le_bytes());
+        out[8..12].copy_from_slice(&self.image_crc.to_le_bytes());
+        out
+    }
+}
@@ -0,0 +1,16 @@
+//! Over-the-air updates: streaming a downloaded image into the inactive slot, and requesting the bootloader to boot it.
+//!
+//! An update package consists of a [Header] followed by the signed image. Typically the application feeds the package
+//! into a [Writer] as it is being received, which erases and programs the inactive slot. Only once the image is
+//! complete and its checksum matches the header, [Writer::commit] stores [Status::Initial] in the journal, with the
+//! slot currently booted as backup. An update interrupted by a power failure thus leaves the journal untouched, and
+//! the device keeps booting the current image.
+//!
+//! [Status::Initial]: ec_slimloader_state::state::Status::Initial
+#![cfg_attr(not(test), no_std)]
+
+mod format;
+mod write;
+
+pub use format::Header;
+pub use write::{Error, Writer};
@@ -0,0 +1,379 @@
+use ec_slimloader_state::flash::FlashJournal;
+use ec_slimloader_state::state::{Slot, State, Status};
+use embedded_storage_async::nor_flash::{NorFlash, ReadNorFlash};
+
+use crate::format::{Header, CRC};
+
+/// Error yielded whilst writing an update.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// The package does not start with a valid [Header].
+    InvalidHeader,
+    /// The image does not fit in the slot.
+    TooLarge,
+    /// The package continues after the image described by the header.
+    TrailingData,
+    /// The package ended before the entire image was received.
+    Incomplete,
+    /// The image as written to the slot does not match the checksum in the header.
+    ReadbackFailed,
+    /// The update was already committed.
+    Finished,
+    /// The slot is the one currently booted, which must not be overwritten.
+    BootedSlot,
+    /// The journal does not contain a [State], so the slot currently booted is not known.
+    NoState,
+    /// The journal could not be updated.
+    Journal,
+    /// The underlying storage medium yielded an error.
+    IO,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    /// Receiving the [Header].
+    Header,
+    /// Receiving the image.
+    Image,
+    /// The image was written and verified.
+    Verified,
+    /// The journal requests the bootloader to boot the image.
+    Committed,
+}
+
+/// Slot the application is running from, as far as can be told from `state`.
+///
+/// With [Status::Initial] the new target has not been attempted yet, and with [Status::Failed] the bootloader has
+/// fallen back, so in both cases the backup is running.
+fn booted(state: &State) -> Slot {
+    match state.status() {
+        Status::Initial | Status::Failed => state.backup(),
+        Status::Attempting | Status::Confirmed => state.target(),
+    }
+}
+
+/// Streaming writer of an update package into the inactive slot.
+///
+/// The package can be fed in chunks of any size as it is being received. The slot is erased one erase block at a time
+/// just ahead of being written, in blocks of `N` bytes, which must be a multiple of the read and write sizes of the
+/// slot. Nothing is changed in the journal until [Writer::commit].
+pub struct Writer<const N: usize = 256> {
+    slot: Slot,
+    phase: Phase,
+    header: Option<Header>,
+    /// Partially received header.
+    pending: [u8; Header::LEN],
+    pending_len: usize,
+    /// Bytes of the image not yet written to the slot.
+    buf: [u8; N],
+    buf_len: usize,
+    /// Number of bytes of the image received so far.
+    received: u32,
+    /// Number of bytes written to the slot so far.
+    flushed: u32,
+    /// Number of bytes at the start of the slot erased so far.
+    erased: u32,
+}
+
+impl<const N: usize> Writer<N> {
+    /// Start writing an update into `slot`, refusing the slot currently booted according to `journal`.
+    pub fn new<F: NorFlash>(journal: &FlashJournal<'_, F>, slot: Slot) -> Result<Self, Error> {
+        let state = journal.get().ok_or(Error::NoState)?;
+        if booted(state) == slot {
+            return Err(Error::BootedSlot);
+        }
+
+        Ok(Self {
+            slot,
+            phase: Phase::Header,
+            header: None,
+            pending: [0u8; Header::LEN],
+            pending_len: 0,
+            buf: [0u8; N],
+            buf_len: 0,
+            received: 0,
+            flushed: 0,
+            erased: 0,
+        })
+    }
+
+    /// The header of the package, once received.
+    pub fn header(&self) -> Option<&Header> {
+        self.header.as_ref()
+    }
+
+    /// Number of bytes of the image received so far, to report progress against [Header::image_len].
+    pub fn received(&self) -> u32 {
+        self.received
+    }
+
+    /// Write the next chunk of the package to `target`, the partition of the slot passed to [Writer::new].
+    pub async fn feed<T: NorFlash>(&mut self, target: &mut T, mut data: &[u8]) -> Result<(), Error> {
+        while !data.is_empty() {
+            match self.phase {
+                Phase::Header => {
+                    let take = (Header::LEN - self.pending_len).min(data.len());
+                    self.pending[self.pending_len..self.pending_len + take].copy_from_slice(&data[..take]);
+                    self.pending_len += take;
+                    data = &data[take..];
+                    if self.pending_len < Header::LEN {
+                        continue;
+                    }
+
+                    let header = Header::parse(&self.pending).ok_or(Error::InvalidHeader)?;
+                    if header.image_len as usize > target.capacity() {
+                        return Err(Error::TooLarge);
+                    }
+                    self.header = Some(header);
+                    self.phase = Phase::Image;
+                }
+                Phase::Image => {
+                    self.output(target, data).await?;
+                    data = &[];
+                }
+                Phase::Verified | Phase::Committed => return Err(Error::TrailingData),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Write out the remainder of the image, and verify the slot against the checksum in the header.
+    pub async fn finish<T: NorFlash>(&mut self, target: &mut T) -> Result<(), Error> {
+        let header = match self.phase {
+            Phase::Image => self.header.ok_or(Error::Incomplete)?,
+            Phase::Header => return Err(Error::Incomplete),
+            Phase::Verified => return Ok(()),
+            Phase::Committed => return Err(Error::Finished),
+        };
+        if self.received != header.image_len {
+            return Err(Error::Incomplete);
+        }
+
+        // Pad the last block with erased bytes.
+        let flush_len = self.buf_len.next_multiple_of(T::WRITE_SIZE);
+        self.buf[self.buf_len..flush_len].fill(0xFF);
+        self.buf_len = flush_len;
+        self.flush(target).await?;
+
+        if checksum(target, header.image_len, &mut self.buf).await? != header.image_crc {
+            return Err(Error::ReadbackFailed);
+        }
+
+        self.phase = Phase::Verified;
+        Ok(())
+    }
+
+    /// Request the bootloader to boot the verified image, falling back to the slot currently booted.
+    ///
+    /// Retains the user bits of the latest [State].
+    pub async fn commit<F: NorFlash>(&mut self, journal: &mut FlashJournal<'_, F>) -> Result<(), Error> {
+        match self.phase {
+            Phase::Header | Phase::Image => return Err(Error::Incomplete),
+            Phase::Verified => {}
+            Phase::Committed => return Err(Error::Finished),
+        }
+
+        let current = journal.get().ok_or(Error::NoState)?;
+        let backup = booted(current);
+        if backup == self.slot {
+            return Err(Error::BootedSlot);
+        }
+
+        let state = State::new(Status::Initial, self.slot, backup).with_user_bits(current.user_bits());
+        journal.set(&state).await.map_err(|_| Error::Journal)?;
+
+        self.phase = Phase::Committed;
+        Ok(())
+    }
+
+    /// Append bytes to the image.
+    async fn output<T: NorFlash>(&mut self, target: &mut T, mut data: &[u8]) -> Result<(), Error> {
+        let Some(header) = self.header else {
+            return Err(Error::InvalidHeader);
+        };
+        if (self.received as usize + data.len()) > header.image_len as usize {
+            return Err(Error::TrailingData);
+        }
+        self.received += data.len() as u32;
+
+        while !data.is_empty() {
+            let len = data.len().min(N - self.buf_len);
+            self.buf[self.buf_len..self.buf_len + len].copy_from_slice(&data[..len]);
+            self.buf_len += len;
+            data = &data[len..];
+
+            if self.buf_len == N {
+                self.flush(target).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn flush<T: NorFlash>(&mut self, target: &mut T) -> Result<(), Error> {
+        if self.buf_len == 0 {
+            return Ok(());
+        }
+
+        // Erase the blocks this write extends into.
+        let end = self.flushed + self.buf_len as u32;
+        if end > self.erased {
+            let erase_end = (end as usize).next_multiple_of(T::ERASE_SIZE).min(target.capacity()) as u32;
+            target.erase(self.erased, erase_end).await.map_err(|_| Error::IO)?;
+            self.erased = erase_end;
+        }
+
+        target
+            .write(self.flushed, &self.buf[..self.buf_len])
+            .await
+            .map_err(|_| Error::IO)?;
+        self.flushed = end;
+        self.buf_len = 0;
+        Ok(())
+    }
+}
+
+/// Compute the CRC32 over the first `len` bytes of `flash`.
+async fn checksum<F: ReadNorFlash, const N: usize>(flash: &mut F, len: u32, buf: &mut [u8; N]) -> Result<u32, Error> {
+    let mut digest = CRC.digest();
+    let mut offset = 0;
+    while offset < len as usize {
+        let chunk_len = (len as usize - offset).min(N);
+        let read_len = chunk_len.next_multiple_of(F::READ_SIZE);
+        flash
+            .read(offset as u32, &mut buf[..read_len])
+            .await
+            .map_err(|_| Error::IO)?;
+        digest.update(&buf[..chunk_len]);
+        offset += chunk_len;
+    }
+    Ok(digest.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use ec_slimloader_state::flash::mock::MockFlashBase;
+    use embassy_futures::block_on;
+
+    use super::*;
+
+    type MockFlash = MockFlashBase<8, 4, 256>;
+    type MockJournal = MockFlashBase<2, 4, 64>;
+
+    const JOURNAL_BUFFER_SIZE: usize = 16;
+
+    fn image() -> Vec<u8> {
+        (0..5000u32).map(|i| (i * 7 + i / 13) as u8).collect()
+    }
+
+    fn package(image: &[u8]) -> Vec<u8> {
+        let mut out = Header::for_image(image).to_bytes().to_vec();
+        out.extend_from_slice(image);
+        out
+    }
+
+    fn journal(state: State) -> FlashJournal<'static, MockJournal> {
+        block_on(async {
+            let buffer = Box::leak(Box::new([0; JOURNAL_BUFFER_SIZE]));
+            let mut journal = FlashJournal::new(MockJournal::default(), buffer).await.unwrap();
+            journal.set(&state).await.unwrap();
+            journal
+        })
+    }
+
+    fn write(
+        journal: &FlashJournal<'_, MockJournal>,
+        package: &[u8],
+        chunk_size: usize,
+    ) -> (Result<Writer, Error>, MockFlash) {
+        let mut target = MockFlash::default();
+        let result = block_on(async {
+            let mut writer = Writer::new(journal, Slot::S1)?;
+            for chunk in package.chunks(chunk_size) {
+                writer.feed(&mut target, chunk).await?;
+            }
+            writer.finish(&mut target).await?;
+            Ok(writer)
+        });
+        (result, target)
+    }
+
+    #[test]
+    fn write_and_commit() {
+        let image = image();
+        let package = package(&image);
+        let confirmed = State::new(Status::Confirmed, Slot::S0, Slot::S1).with_user_bits(0x5a);
+
+        for chunk_size in [1, 7, 256, package.len()] {
+            let mut journal = journal(confirmed);
+            let (writer, target) = write(&journal, &package, chunk_size);
+            let mut writer = writer.unwrap();
+            assert_eq!(writer.received(), image.len() as u32);
+            assert_eq!(&target.as_bytes()[..image.len()], &image[..]);
+
+            // The journal is only changed by the commit.
+            assert_eq!(journal.get(), Some(&confirmed));
+            block_on(writer.commit(&mut journal)).unwrap();
+            assert_eq!(
+                journal.get(),
+                Some(&State::new(Status::Initial, Slot::S1, Slot::S0).with_user_bits(0x5a))
+            );
+            assert_eq!(
+                block_on(writer.commit(&mut journal)),
+                Err(Error::Finished)
+            );
+        }
+    }
+
+    #[test]
+    fn refuses_booted_slot() {
+        // The backup is running when the target failed, or has not been attempted yet.
+        for state in [
+            State::new(Status::Confirmed, Slot::S1, Slot::S0),
+            State::new(Status::Failed, Slot::S0, Slot::S1),
+            State::new(Status::Initial, Slot::S0, Slot::S1),
+        ] {
+            assert!(matches!(
+                Writer::<256>::new(&journal(state), Slot::S1),
+                Err(Error::BootedSlot)
+            ));
+        }
+
+        let mut buffer = [0; JOURNAL_BUFFER_SIZE];
+        let empty = block_on(FlashJournal::new(MockJournal::default(), &mut buffer)).unwrap();
+        assert!(matches!(Writer::<256>::new(&empty, Slot::S1), Err(Error::NoState)));
+    }
+
+    #[test]
+    fn write_errors() {
+        let image = image();
+        let journal = journal(State::new(Status::Confirmed, Slot::S0, Slot::S1));
+
+        // Corrupted in transit.
+        let mut corrupted = package(&image);
+        corrupted[3000] ^= 1;
+        assert!(matches!(write(&journal, &corrupted, 64).0, Err(Error::ReadbackFailed)));
+
+        // Cut short, or followed by more data.
+        let package = package(&image);
+        assert!(matches!(
+            write(&journal, &package[..4000], 64).0,
+            Err(Error::Incomplete)
+        ));
+        let mut trailing = package.clone();
+        trailing.push(0);
+        assert!(matches!(write(&journal, &trailing, 64).0, Err(Error::TrailingData)));
+
+        // Not a package at all.
+        assert!(matches!(write(&journal, &image, 64).0, Err(Error::InvalidHeader)));
+
+        // Larger than the slot.
+        let large = vec![0u8; 8 * 4 * 256 + 1];
+        assert!(matches!(
+            write(&journal, &self::package(&large), 64).0,
+            Err(Error::TooLarge)
+        ));
+    }
+}
@@ -10,6 +10,7 @@ use ec_slimloader_state::{
         mock::{MockFlashBase, MockFlashError::EarlyShutoff},
         FlashJournal,
     },
+    record::Record,
     state::State,
 };
 use libfuzzer_sys::fuzz_target;
@@ -28,7 +29,7 @@ struct Input {
 impl<'a> Arbitrary<'a> for Input {
     fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
         let states: Vec<State> = Arbitrary::arbitrary(u)?;
-        let fail_at = u.int_in_range(0..=states.len() * 2)?;
+        let fail_at = u.int_in_range(0..=states.len() * State::SIZE)?;
         Ok(Input { states, fail_at })
     }
 }
@@ -43,18 +44,19 @@ fn fuzz(states: Vec<State>, fail_at: usize) {
     let mut states = states.into_iter().peekable();
 
     futures::executor::block_on(async {
-        let mut journal = FlashJournal::new::<4>(&mut flash).await.unwrap();
+        let mut buffer = [0; 4];
+        let mut journal = FlashJournal::new(&mut flash, &mut buffer).await.unwrap();
 
         let mut prev_state = None;
         while let Some(new_state) = states.peek() {
-            match journal.set::<4>(new_state).await {
+            match journal.set(new_state).await {
                 Ok(_) => {
                     assert_eq!(journal.get(), Some(new_state));
                 }
                 Err(flash::Error::Other(EarlyShutoff(_, _))) => {
                     drop(journal);
                     flash.remove_shutoff();
-                    journal = FlashJournal::new::<4>(&mut flash).await.unwrap();
+                    journal = FlashJournal::new(&mut flash, &mut buffer).await.unwrap();
                     let old_state = journal.get();
 
                     if old_state == prev_state.as_ref() {
@@ -31,10 +31,11 @@ fn fuzz(random_data: &[u8], new_state: State) {
 
     futures::executor::block_on(async {
         // Instantiation should never crash. (error is only if there are not enough pages)
-        let mut journal = FlashJournal::new::<4>(&mut flash).await.unwrap();
+        let mut buffer = [0; 4];
+        let mut journal = FlashJournal::new(&mut flash, &mut buffer).await.unwrap();
 
         // Finally try to update the state.
-        journal.set::<4>(&new_state).await.unwrap();
+        journal.set(&new_state).await.unwrap();
         assert_eq!(journal.get(), Some(&new_state));
     });
 }
@@ -0,0 +1,179 @@
+//! Record caching the digest of the image that was last authenticated, allowing warm boots to skip authentication.
+//!
+//! The record carries a tag over the slot and the full image digest, computed as HMAC-SHA256 under a device-unique
+//! key. Without the key, no valid record can be forged for a tampered image, even by anyone able to write to the
+//! [crate::flash::FlashJournal] containing it.
+use crate::record::Record;
+use crate::state::{ParseResult, Slot};
+
+/// Number of bytes of the tag retained in an [AuthCache].
+pub const TAG_SIZE: usize = 15;
+
+/// Length of the key an [AuthCache] is tagged with, see [AuthCache::tag].
+pub const KEY_SIZE: usize = 32;
+
+/// Block size of SHA-256, over which HMAC pads its key.
+const BLOCK_SIZE: usize = 64;
+
+/// Tag of the image in a [Slot] that has been authenticated, as stored in its own journal.
+///
+/// Layout: slot, and the first [TAG_SIZE] bytes of the [AuthCache::tag] over the slot and the image digest.
+/// A record of only `0xff` bytes is never valid as Slot value 0b111 is disallowed.
+#[derive(PartialEq, Clone, Copy)]
+pub struct AuthCache([u8; 16]);
+
+impl AuthCache {
+    pub fn new(slot: Slot, tag: &[u8; 32]) -> Self {
+        let mut data = [0u8; 16];
+        data[0] = slot as u8;
+        data[1..].copy_from_slice(&tag[..TAG_SIZE]);
+        Self(data)
+    }
+
+    pub fn try_new(data: [u8; 16]) -> Result<Self, ParseResult> {
+        if data == [0xff; 16] {
+            return Err(ParseResult::Unset);
+        }
+
+        if Slot::try_from(data[0]).is_err() {
+            return Err(ParseResult::Invalid);
+        }
+
+        Ok(AuthCache(data))
+    }
+
+    /// HMAC-SHA256 under `key` over `slot` followed by `digest` of its image, using `sha256` to hash.
+    ///
+    /// The hash function is passed in, such that the hardware accelerator of the device can compute it.
+    pub fn tag(
+        key: &[u8; KEY_SIZE],
+        slot: Slot,
+        digest: &[u8; 32],
+        mut sha256: impl FnMut(&[u8]) -> [u8; 32],
+    ) -> [u8; 32] {
+        let mut inner = [0x36u8; BLOCK_SIZE + 1 + 32];
+        inner[..KEY_SIZE].iter_mut().zip(key).for_each(|(pad, key)| *pad ^= key);
+        inner[BLOCK_SIZE] = slot as u8;
+        inner[BLOCK_SIZE + 1..].copy_from_slice(digest);
+
+        let mut outer = [0x5cu8; BLOCK_SIZE + 32];
+        outer[..KEY_SIZE].iter_mut().zip(key).for_each(|(pad, key)| *pad ^= key);
+        outer[BLOCK_SIZE..].copy_from_slice(&sha256(&inner));
+        sha256(&outer)
+    }
+
+    /// Slot containing the authenticated image.
+    pub fn slot(&self) -> Slot {
+        // If Self exists, Slot must be valid.
+        unsafe { Slot::try_from(self.0[0]).unwrap_unchecked() }
+    }
+
+    /// Whether this record describes the image in `slot` with `tag`, see [AuthCache::tag].
+    ///
+    /// The tag is compared in constant time, such that the timing does not reveal the length of the matching prefix.
+    pub fn matches(&self, slot: Slot, tag: &[u8; 32]) -> bool {
+        let diff = self.0[1..].iter().zip(tag).fold(0u8, |diff, (a, b)| diff | (a ^ b));
+        self.slot() == slot && core::hint::black_box(diff) == 0
+    }
+}
+
+impl Record for AuthCache {
+    const SIZE: usize = 16;
+
+    fn try_from_bytes(data: &[u8]) -> Result<Self, ParseResult> {
+        AuthCache::try_new(data.try_into().map_err(|_| ParseResult::Invalid)?)
+    }
+
+    fn to_bytes(&self, data: &mut [u8]) {
+        data.copy_from_slice(&self.0);
+    }
+}
+
+impl core::fmt::Debug for AuthCache {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("AuthCache")
+            .field("slot", &self.slot())
+            .field("tag", &&self.0[1..])
+            .finish()
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for AuthCache {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(f, "AuthCache {{ slot: {}, tag: {=[u8]:x} }}", self.slot(), &self.0[1..])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hmac::{Hmac, Mac};
+    use sha2::{Digest, Sha256};
+
+    use super::*;
+    use crate::flash::mock::MockFlashBase;
+    use crate::flash::FlashJournal;
+
+    fn sha256(data: &[u8]) -> [u8; 32] {
+        Sha256::digest(data).into()
+    }
+
+    #[test]
+    fn auth_cache_tag_is_hmac() {
+        let key: [u8; KEY_SIZE] = core::array::from_fn(|i| 0xa0 ^ i as u8);
+        let digest: [u8; 32] = core::array::from_fn(|i| i as u8);
+
+        let mut mac = Hmac::<Sha256>::new_from_slice(&key).unwrap();
+        mac.update(&[Slot::S2 as u8]);
+        mac.update(&digest);
+        let expected: [u8; 32] = mac.finalize().into_bytes().into();
+
+        assert_eq!(AuthCache::tag(&key, Slot::S2, &digest, sha256), expected);
+    }
+
+    #[test]
+    fn auth_cache_validity() {
+        let key = [0x5a; KEY_SIZE];
+        let digest: [u8; 32] = core::array::from_fn(|i| i as u8);
+        for i in 0b0..0b111u8 {
+            let slot = Slot::try_from(i).unwrap();
+            let tag = AuthCache::tag(&key, slot, &digest, sha256);
+
+            let cache = AuthCache::new(slot, &tag);
+            assert_eq!(cache.slot(), slot);
+            assert!(cache.matches(slot, &tag));
+            assert!(AuthCache::try_new(cache.0).is_ok());
+
+            // Any other slot, digest or key does not match.
+            let other_slot = Slot::try_from((i + 1) % 0b111).unwrap();
+            assert!(!cache.matches(other_slot, &AuthCache::tag(&key, other_slot, &digest, sha256)));
+            let mut other = digest;
+            other[31] ^= 1;
+            assert!(!cache.matches(slot, &AuthCache::tag(&key, slot, &other, sha256)));
+            assert!(!cache.matches(slot, &AuthCache::tag(&[0xa5; KEY_SIZE], slot, &digest, sha256)));
+
+            // Records with an invalid slot are rejected.
+            let mut data = cache.0;
+            data[0] = 0b111;
+            assert!(matches!(AuthCache::try_new(data), Err(ParseResult::Invalid)));
+        }
+
+        assert!(matches!(AuthCache::try_new([0xff; 16]), Err(ParseResult::Unset)));
+    }
+
+    #[test]
+    fn auth_cache_journal() {
+        let mut mock: MockFlashBase<2, 2, 16> = MockFlashBase::new(None, false);
+        embassy_futures::block_on(async {
+            let mut buffer = [0; 16];
+            let mut journal: FlashJournal<_, AuthCache> = FlashJournal::new(&mut mock, &mut buffer).await.unwrap();
+            assert!(journal.get().is_none());
+
+            for i in 0..8u8 {
+                let cache = AuthCache::new(Slot::S1, &[i; 32]);
+                journal.set(&cache).await.unwrap();
+                assert_eq!(journal.get(), Some(&cache));
+            }
+        });
+    }
+}
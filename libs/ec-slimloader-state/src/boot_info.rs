@@ -0,0 +1,336 @@
+//! Information on the boot chain handed by the bootloader to the application, kept in RAM retained across warm resets.
+//!
+//! The bootloader writes a [BootInfo] just before jumping to the application, recording its own version and build
+//! hash, whether the ROM enforced secure boot whilst it ran, and the [BootProfile] it verified the image with.
+//! Applications attest the full chain with it, and may
+//! refuse to run production workloads on devices in development mode. A [BootInfo] that is not valid, for example as
+//! it was left over in RAM by an older bootloader, reports secure boot as disabled.
+//!
+//! The chain may consist of an immutable first stage booting an updatable secondary loader, which in turn boots the
+//! application. The first stage then records the [ImageRole::Loader] role of the image it boots, and the secondary
+//! loader [forwards](BootInfo::forward) the boot info to the application, keeping the build of the first stage and
+//! what it observed about secure boot.
+pub use mbi_format::ImageRole;
+
+use crate::state::Slot;
+
+/// Marks boot info as written by [BootInfo::new], rather than left over in uninitialized RAM.
+const MAGIC: u32 = 0x4f46_4e49;
+
+const CRC: crc::Crc<u32> = crc::Crc::<u32>::new(&crc::CRC_32_ISCSI);
+
+/// Version and build of the bootloader.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct BuildInfo {
+    /// Version of the bootloader, for example encoded as `0xMMmmpp00` like its boot image version.
+    pub version: u32,
+    /// Leading bytes of the hash of the revision the bootloader was built from.
+    pub build_hash: [u8; 8],
+}
+
+impl BuildInfo {
+    /// Build of a bootloader that does not know its version.
+    pub const UNKNOWN: Self = Self {
+        version: 0,
+        build_hash: [0; 8],
+    };
+}
+
+/// Trade-off between boot time and the integrity checks of a bootloader, beyond the authentication by the ROM.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum BootProfile {
+    /// Every integrity check, such as reading security-critical data again to detect fault injection.
+    Secure = 0,
+    /// Skips the checks that read data again, at the expense of the protection against fault injection.
+    Fast = 1,
+}
+
+/// Build of the bootloader and the slot, role and security of the image it booted into.
+///
+/// Typically placed in RAM that is neither initialized at startup nor cleared by a warm reset, at an address agreed
+/// upon by the bootloader and the application, for example in the `.uninit` section of `cortex-m-rt`.
+#[derive(Clone, Copy, PartialEq)]
+#[repr(C)]
+pub struct BootInfo {
+    magic: u32,
+    version: u32,
+    build_hash: [u8; 8],
+    root_version: u32,
+    root_build_hash: [u8; 8],
+    slot: u8,
+    secure_boot: u8,
+    role: u8,
+    stage: u8,
+    profile: u8,
+    crc: u32,
+}
+
+impl BootInfo {
+    /// Boot info that is never valid, for example to initialize a `static`.
+    pub const INVALID: Self = Self {
+        magic: 0,
+        version: 0,
+        build_hash: [0; 8],
+        root_version: 0,
+        root_build_hash: [0; 8],
+        slot: 0,
+        secure_boot: 0,
+        role: 0,
+        stage: 0,
+        profile: 0,
+        crc: 0,
+    };
+
+    /// Boot info of the first stage bootloader `build` booting into the image with `role` in `slot`, with the ROM
+    /// enforcing secure boot or not.
+    ///
+    /// Records [BootProfile::Secure], see [BootInfo::with_profile] otherwise.
+    pub fn new(build: BuildInfo, slot: Slot, role: ImageRole, secure_boot: bool) -> Self {
+        Self::sealed(Self {
+            magic: MAGIC,
+            version: build.version,
+            build_hash: build.build_hash,
+            root_version: build.version,
+            root_build_hash: build.build_hash,
+            slot: slot.into(),
+            secure_boot: secure_boot.into(),
+            role: role as u8,
+            stage: 1,
+            profile: BootProfile::Secure as u8,
+            crc: 0,
+        })
+    }
+
+    /// Record that the bootloader verified the image with `profile`.
+    ///
+    /// [BootProfile::Fast] is kept once recorded by an earlier stage, as the chain is only as strong as its weakest
+    /// stage. Boot info that is not valid is left as is.
+    pub fn with_profile(&self, profile: BootProfile) -> Self {
+        if !self.is_valid() || self.profile() == Some(BootProfile::Fast) {
+            return *self;
+        }
+        Self::sealed(Self {
+            profile: profile as u8,
+            ..*self
+        })
+    }
+
+    /// Boot info of the secondary loader `build`, booted by the stage that recorded this boot info, booting into the
+    /// image with `role` in `slot`.
+    ///
+    /// Keeps the build of the first stage and whether the ROM enforced secure boot. Yields [None] if this boot info
+    /// is not valid or was not recorded for a [ImageRole::Loader], as the chain can then not be attested.
+    pub fn forward(&self, build: BuildInfo, slot: Slot, role: ImageRole) -> Option<Self> {
+        if self.role() != Some(ImageRole::Loader) {
+            return None;
+        }
+        Some(Self::sealed(Self {
+            version: build.version,
+            build_hash: build.build_hash,
+            slot: slot.into(),
+            role: role as u8,
+            stage: self.stage.checked_add(1)?,
+            ..*self
+        }))
+    }
+
+    fn sealed(mut info: Self) -> Self {
+        info.crc = info.checksum();
+        info
+    }
+
+    fn checksum(&self) -> u32 {
+        let mut digest = CRC.digest();
+        digest.update(&self.magic.to_le_bytes());
+        digest.update(&self.version.to_le_bytes());
+        digest.update(&self.build_hash);
+        digest.update(&self.root_version.to_le_bytes());
+        digest.update(&self.root_build_hash);
+        digest.update(&[self.slot, self.secure_boot, self.role, self.stage, self.profile]);
+        digest.finalize()
+    }
+
+    /// Whether this boot info was written as a whole, rather than left over in uninitialized RAM or partially written.
+    pub fn is_valid(&self) -> bool {
+        self.magic == MAGIC && self.crc == self.checksum()
+    }
+
+    /// Mark this boot info as not valid, for example when booting without the bootloader recording it.
+    pub fn invalidate(&mut self) {
+        *self = Self::INVALID;
+    }
+
+    /// Build of the bootloader that booted the image, if valid.
+    pub fn build(&self) -> Option<BuildInfo> {
+        self.is_valid().then_some(BuildInfo {
+            version: self.version,
+            build_hash: self.build_hash,
+        })
+    }
+
+    /// Build of the first stage bootloader of the chain, if valid.
+    ///
+    /// Equals [BootInfo::build] when the image was booted by the first stage directly.
+    pub fn root_build(&self) -> Option<BuildInfo> {
+        self.is_valid().then_some(BuildInfo {
+            version: self.root_version,
+            build_hash: self.root_build_hash,
+        })
+    }
+
+    /// Slot of the image booted into, if valid.
+    pub fn slot(&self) -> Option<Slot> {
+        if !self.is_valid() {
+            return None;
+        }
+        Slot::try_from(self.slot).ok()
+    }
+
+    /// Role of the image booted into, if valid.
+    pub fn role(&self) -> Option<ImageRole> {
+        if !self.is_valid() {
+            return None;
+        }
+        ImageRole::from_u8(self.role)
+    }
+
+    /// Number of loaders in the chain up to the image booted into, if valid.
+    ///
+    /// `1` when the image was booted by the first stage, `2` when booted by a secondary loader, and so on.
+    pub fn stage(&self) -> Option<u8> {
+        self.is_valid().then_some(self.stage)
+    }
+
+    /// Weakest [BootProfile] any bootloader of the chain verified an image with, if valid.
+    pub fn profile(&self) -> Option<BootProfile> {
+        if !self.is_valid() {
+            return None;
+        }
+        match self.profile {
+            0 => Some(BootProfile::Secure),
+            1 => Some(BootProfile::Fast),
+            _ => None,
+        }
+    }
+
+    /// Whether the ROM enforced secure boot, such that the bootloader and the image it booted were authenticated.
+    ///
+    /// Returns `false` if not valid, as nothing can then be told about the chain.
+    pub fn secure_boot(&self) -> bool {
+        self.is_valid() && self.secure_boot == 1
+    }
+}
+
+impl core::fmt::Debug for BootInfo {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("BootInfo")
+            .field("build", &self.build())
+            .field("root_build", &self.root_build())
+            .field("slot", &self.slot())
+            .field("role", &self.role())
+            .field("stage", &self.stage())
+            .field("profile", &self.profile())
+            .field("secure_boot", &self.secure_boot())
+            .finish()
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for BootInfo {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "BootInfo {{ build: {}, root_build: {}, slot: {}, role: {}, stage: {}, profile: {}, secure_boot: {} }}",
+            self.build(),
+            self.root_build(),
+            self.slot(),
+            self.role(),
+            self.stage(),
+            self.profile(),
+            self.secure_boot()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BUILD: BuildInfo = BuildInfo {
+        version: 0x0102_0300,
+        build_hash: [0xde, 0xad, 0xbe, 0xef, 0x01, 0x23, 0x45, 0x67],
+    };
+
+    const LOADER_BUILD: BuildInfo = BuildInfo {
+        version: 0x0200_0000,
+        build_hash: [0x89, 0xab, 0xcd, 0xef, 0x01, 0x23, 0x45, 0x67],
+    };
+
+    #[test]
+    fn boot_info_validity() {
+        let info = BootInfo::INVALID;
+        assert!(!info.is_valid());
+        assert_eq!(info.build(), None);
+        assert_eq!(info.slot(), None);
+        assert_eq!(info.role(), None);
+        assert!(!info.secure_boot());
+
+        let mut info = BootInfo::new(BUILD, Slot::S2, ImageRole::Application, true);
+        assert!(info.is_valid());
+        assert_eq!(info.build(), Some(BUILD));
+        assert_eq!(info.root_build(), Some(BUILD));
+        assert_eq!(info.slot(), Some(Slot::S2));
+        assert_eq!(info.role(), Some(ImageRole::Application));
+        assert_eq!(info.stage(), Some(1));
+        assert_eq!(info.profile(), Some(BootProfile::Secure));
+        assert!(info.secure_boot());
+        assert!(!BootInfo::new(BUILD, Slot::S2, ImageRole::Application, false).secure_boot());
+
+        // Flipping secure boot on without the checksum is not trusted.
+        let mut forged = BootInfo::new(BUILD, Slot::S2, ImageRole::Application, false);
+        forged.secure_boot = 1;
+        assert!(!forged.secure_boot());
+
+        info.invalidate();
+        assert!(!info.is_valid());
+    }
+
+    #[test]
+    fn boot_info_forward() {
+        let root = BootInfo::new(BUILD, Slot::S0, ImageRole::Loader, true);
+        let info = root.forward(LOADER_BUILD, Slot::S3, ImageRole::Application).unwrap();
+        assert!(info.is_valid());
+        assert_eq!(info.build(), Some(LOADER_BUILD));
+        assert_eq!(info.root_build(), Some(BUILD));
+        assert_eq!(info.slot(), Some(Slot::S3));
+        assert_eq!(info.role(), Some(ImageRole::Application));
+        assert_eq!(info.stage(), Some(2));
+        assert!(info.secure_boot());
+
+        // Only boot info recorded for a loader is forwarded.
+        assert_eq!(info.forward(LOADER_BUILD, Slot::S3, ImageRole::Application), None);
+        assert_eq!(
+            BootInfo::INVALID.forward(LOADER_BUILD, Slot::S3, ImageRole::Application),
+            None
+        );
+    }
+
+    #[test]
+    fn boot_info_profile() {
+        let info = BootInfo::new(BUILD, Slot::S0, ImageRole::Loader, true).with_profile(BootProfile::Fast);
+        assert!(info.is_valid());
+        assert_eq!(info.profile(), Some(BootProfile::Fast));
+
+        // A later stage can not hide that an earlier stage booted fast.
+        let forwarded = info
+            .forward(LOADER_BUILD, Slot::S3, ImageRole::Application)
+            .unwrap()
+            .with_profile(BootProfile::Secure);
+        assert_eq!(forwarded.profile(), Some(BootProfile::Fast));
+
+        assert_eq!(BootInfo::INVALID.with_profile(BootProfile::Fast), BootInfo::INVALID);
+    }
+}
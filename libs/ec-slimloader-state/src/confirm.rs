@@ -0,0 +1,125 @@
+//! Fallback for applications that do not confirm the image they were booted into in time.
+//!
+//! The bootloader boots a new image with [Status::Attempting], and boots the backup on the next boot unless the
+//! application confirms the image using [SharedFlashJournal::confirm] in the meantime. An application that never gets
+//! to confirm, for example because it hangs waiting on a peripheral, is however never reset and keeps running the
+//! unconfirmed image. Running [confirm_timeout] in a task bounds how long that can last.
+//!
+//! Tasks can not be generic, hence the application declares an `#[embassy_executor::task]` for its own journal type
+//! that awaits [confirm_timeout], rebooting for example with `|| cortex_m::peripheral::SCB::sys_reset()`.
+//!
+//! [SharedFlashJournal::confirm]: crate::flash::SharedFlashJournal::confirm
+
+use embassy_sync::blocking_mutex::raw::RawMutex;
+use embassy_time::{Duration, Instant, Timer};
+use embedded_storage_async::nor_flash::NorFlash;
+
+use crate::flash::SharedFlashJournal;
+use crate::state::{State, Status};
+
+/// Outcome of waiting for the application to confirm its image, see [wait_for_confirmation].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Confirmation {
+    /// The image was not booted with [Status::Attempting], hence there is nothing to confirm.
+    NotPending,
+    /// The [State] is no longer [Status::Attempting] at the deadline, typically as the image was confirmed.
+    Confirmed,
+    /// The [State] is still [Status::Attempting] at the deadline.
+    TimedOut,
+}
+
+/// Wait until `timeout` after boot, and check whether the image booted into has been confirmed by then.
+///
+/// Returns right away if the latest [State] is not [Status::Attempting]. The deadline is measured from boot rather than
+/// from calling this, such that a task spawned late does not extend it.
+pub async fn wait_for_confirmation<M: RawMutex, T: NorFlash, const WATCHERS: usize>(
+    journal: &SharedFlashJournal<'_, M, T, State, WATCHERS>,
+    timeout: Duration,
+) -> Confirmation {
+    let attempting = |state: Option<State>| state.is_some_and(|state| state.status() == Status::Attempting);
+    if !attempting(journal.get().await) {
+        return Confirmation::NotPending;
+    }
+
+    Timer::at(Instant::from_ticks(0) + timeout).await;
+
+    if attempting(journal.get().await) {
+        Confirmation::TimedOut
+    } else {
+        Confirmation::Confirmed
+    }
+}
+
+/// Wait for the image to be confirmed like [wait_for_confirmation], and call `reboot` if it is not in time.
+///
+/// On the next boot the bootloader then finds the image still [Status::Attempting], and falls back to the backup.
+/// Without `reboot` the timeout is only logged, leaving the fallback to the next reset.
+pub async fn confirm_timeout<M: RawMutex, T: NorFlash, const WATCHERS: usize>(
+    journal: &SharedFlashJournal<'_, M, T, State, WATCHERS>,
+    timeout: Duration,
+    reboot: Option<fn()>,
+) -> Confirmation {
+    let confirmation = wait_for_confirmation(journal, timeout).await;
+    if confirmation == Confirmation::TimedOut {
+        defmt_or_log::error!(
+            "Image not confirmed within {} ms after boot, the bootloader falls back to the backup on the next boot",
+            timeout.as_millis()
+        );
+        if let Some(reboot) = reboot {
+            reboot();
+        }
+    }
+    confirmation
+}
+
+#[cfg(test)]
+mod tests {
+    use core::sync::atomic::{AtomicBool, Ordering};
+
+    use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+
+    use super::*;
+    use crate::flash::mock::MockFlashBase;
+    use crate::flash::FlashJournal;
+    use crate::state::Slot;
+
+    type Mock = MockFlashBase<3, 2, 8>;
+
+    static REBOOTED: AtomicBool = AtomicBool::new(false);
+
+    #[test]
+    fn confirm_within_timeout() {
+        embassy_futures::block_on(async {
+            let mut mock = Mock::new(None, false);
+            let mut buffer = [0; 4];
+            let mut journal = FlashJournal::new(&mut mock, &mut buffer).await.unwrap();
+            let attempting = State::new(Status::Attempting, Slot::S1, Slot::S0);
+            journal.set(&attempting).await.unwrap();
+            let shared: SharedFlashJournal<'_, CriticalSectionRawMutex, _> = SharedFlashJournal::new(journal);
+
+            // The deadline shortly after boot has passed already.
+            let timeout = Duration::from_millis(1);
+            assert_eq!(
+                confirm_timeout(&shared, timeout, Some(|| REBOOTED.store(true, Ordering::SeqCst))).await,
+                Confirmation::TimedOut
+            );
+            assert!(REBOOTED.load(Ordering::SeqCst));
+
+            assert!(shared.confirm().await.unwrap());
+            assert!(!shared.confirm().await.unwrap());
+            assert_eq!(shared.get().await, Some(attempting.with_status(Status::Confirmed)));
+            assert_eq!(wait_for_confirmation(&shared, timeout).await, Confirmation::NotPending);
+
+            // Confirming in time is seen at the deadline.
+            shared.set(&attempting).await.unwrap();
+            let deadline = Instant::now().as_millis() + 50;
+            let (confirmation, _) = embassy_futures::join::join(
+                wait_for_confirmation(&shared, Duration::from_millis(deadline)),
+                shared.confirm(),
+            )
+            .await;
+            assert_eq!(confirmation, Confirmation::Confirmed);
+        });
+    }
+}
@@ -0,0 +1,197 @@
+//! Record of telemetry counters persisted across boots, for the application to report on the health of a fleet.
+//!
+//! The bootloader counts [Event]s in a separate [crate::flash::FlashJournal], which the application
+//! can read at any time using [crate::flash::FlashJournal::get]. All counters saturate rather than wrap.
+use crate::record::Record;
+use crate::state::{ParseResult, Slot, CRC};
+
+/// Number of slots for which authentication failures are counted, one for each [Slot].
+pub const SLOT_COUNT: usize = 7;
+
+/// Offset of the first byte of the reserved bytes, which must be zero.
+const RESERVED_OFFSET: usize = 4 + 2 * SLOT_COUNT;
+
+/// Occurrence counted by the bootloader in [Counters].
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Event {
+    /// The bootloader jumped to an application.
+    Boot,
+    /// The bootloader booted the backup slot instead of the target slot.
+    Fallback,
+    /// The image in the slot did not authenticate.
+    AuthFailure(Slot),
+}
+
+/// Telemetry counters as stored in their own journal.
+///
+/// Layout: boots, fallbacks and authentication failures for each [Slot] as little-endian u16,
+/// reserved bytes that must be zero, and a CRC over the preceding bytes.
+/// A record of only `0xff` bytes is never valid as the reserved bytes are nonzero.
+#[derive(PartialEq, Clone, Copy)]
+pub struct Counters([u8; 32]);
+
+impl Default for Counters {
+    fn default() -> Self {
+        Self::with_fields(0, 0, [0; SLOT_COUNT])
+    }
+}
+
+impl Counters {
+    fn with_fields(boots: u16, fallbacks: u16, auth_failures: [u16; SLOT_COUNT]) -> Self {
+        let mut data = [0u8; 32];
+        data[0..2].copy_from_slice(&boots.to_le_bytes());
+        data[2..4].copy_from_slice(&fallbacks.to_le_bytes());
+        for (chunk, count) in data[4..RESERVED_OFFSET].chunks_exact_mut(2).zip(auth_failures) {
+            chunk.copy_from_slice(&count.to_le_bytes());
+        }
+        data[31] = CRC.checksum(&data[..31]);
+        Self(data)
+    }
+
+    pub fn try_new(data: [u8; 32]) -> Result<Self, ParseResult> {
+        if data == [0xff; 32] {
+            return Err(ParseResult::Unset);
+        }
+
+        if data[RESERVED_OFFSET..31].iter().any(|&b| b != 0) {
+            return Err(ParseResult::Invalid);
+        }
+
+        if data[31] != CRC.checksum(&data[..31]) {
+            return Err(ParseResult::Invalid);
+        }
+
+        Ok(Counters(data))
+    }
+
+    fn field(&self, offset: usize) -> u16 {
+        u16::from_le_bytes([self.0[offset], self.0[offset + 1]])
+    }
+
+    /// Number of times the bootloader jumped to an application.
+    pub fn boots(&self) -> u16 {
+        self.field(0)
+    }
+
+    /// Number of times the backup slot was booted instead of the target slot.
+    pub fn fallbacks(&self) -> u16 {
+        self.field(2)
+    }
+
+    /// Number of times the image in `slot` did not authenticate.
+    pub fn auth_failures(&self, slot: Slot) -> u16 {
+        self.field(4 + 2 * slot as usize)
+    }
+
+    /// Count `event`, saturating at [u16::MAX].
+    pub fn with_event(&self, event: Event) -> Self {
+        let mut boots = self.boots();
+        let mut fallbacks = self.fallbacks();
+        let mut auth_failures: [u16; SLOT_COUNT] = core::array::from_fn(|i| self.field(4 + 2 * i));
+
+        match event {
+            Event::Boot => boots = boots.saturating_add(1),
+            Event::Fallback => fallbacks = fallbacks.saturating_add(1),
+            Event::AuthFailure(slot) => {
+                let count = &mut auth_failures[slot as usize];
+                *count = count.saturating_add(1);
+            }
+        }
+
+        Self::with_fields(boots, fallbacks, auth_failures)
+    }
+}
+
+impl Record for Counters {
+    const SIZE: usize = 32;
+
+    fn try_from_bytes(data: &[u8]) -> Result<Self, ParseResult> {
+        Counters::try_new(data.try_into().map_err(|_| ParseResult::Invalid)?)
+    }
+
+    fn to_bytes(&self, data: &mut [u8]) {
+        data.copy_from_slice(&self.0);
+    }
+}
+
+impl core::fmt::Debug for Counters {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let auth_failures: [u16; SLOT_COUNT] = core::array::from_fn(|i| self.field(4 + 2 * i));
+        f.debug_struct("Counters")
+            .field("boots", &self.boots())
+            .field("fallbacks", &self.fallbacks())
+            .field("auth_failures", &auth_failures)
+            .finish()
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for Counters {
+    fn format(&self, f: defmt::Formatter) {
+        let auth_failures: [u16; SLOT_COUNT] = core::array::from_fn(|i| self.field(4 + 2 * i));
+        defmt::write!(
+            f,
+            "Counters {{ boots: {}, fallbacks: {}, auth_failures: {} }}",
+            self.boots(),
+            self.fallbacks(),
+            auth_failures
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flash::mock::MockFlashBase;
+    use crate::flash::FlashJournal;
+
+    #[test]
+    fn counters_validity() {
+        let counters = Counters::default()
+            .with_event(Event::Boot)
+            .with_event(Event::Boot)
+            .with_event(Event::Fallback)
+            .with_event(Event::AuthFailure(Slot::S2));
+        assert_eq!(counters.boots(), 2);
+        assert_eq!(counters.fallbacks(), 1);
+        assert_eq!(counters.auth_failures(Slot::S2), 1);
+        assert_eq!(counters.auth_failures(Slot::S0), 0);
+        assert!(Counters::try_new(counters.0).is_ok());
+
+        // Corrupted records are rejected.
+        let mut data = counters.0;
+        data[0] ^= 1;
+        assert!(matches!(Counters::try_new(data), Err(ParseResult::Invalid)));
+        let mut data = counters.0;
+        data[RESERVED_OFFSET] = 1;
+        data[31] = CRC.checksum(&data[..31]);
+        assert!(matches!(Counters::try_new(data), Err(ParseResult::Invalid)));
+
+        assert!(matches!(Counters::try_new([0xff; 32]), Err(ParseResult::Unset)));
+    }
+
+    #[test]
+    fn counters_saturate() {
+        let counters = Counters::with_fields(u16::MAX, u16::MAX, [u16::MAX; SLOT_COUNT]);
+        for event in [Event::Boot, Event::Fallback, Event::AuthFailure(Slot::S6)] {
+            assert_eq!(counters.with_event(event), counters);
+        }
+    }
+
+    #[test]
+    fn counters_journal() {
+        let mut mock: MockFlashBase<2, 2, 32> = MockFlashBase::new(None, false);
+        embassy_futures::block_on(async {
+            let mut buffer = [0; 32];
+            let mut journal: FlashJournal<_, Counters> = FlashJournal::new(&mut mock, &mut buffer).await.unwrap();
+            assert!(journal.get().is_none());
+
+            for i in 1..=8u16 {
+                let counters = journal.get().copied().unwrap_or_default().with_event(Event::Boot);
+                journal.set(&counters).await.unwrap();
+                assert_eq!(journal.get().map(Counters::boots), Some(i));
+            }
+        });
+    }
+}
@@ -0,0 +1,241 @@
+//! Trail of errors recorded by the bootloader, for the application to retrieve without a debugger attached.
+//!
+//! The bootloader records into a [LogSink] provided by the board, typically an [ErrorLog] kept in RAM that is retained
+//! across warm resets. Only errors are recorded, in a compact form, such that the trail fits in a few words.
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+
+use crate::state::Slot;
+
+/// Marks a log as written by [ErrorLog::new], rather than left over in uninitialized RAM.
+const MAGIC: u32 = 0x474f_4c45;
+
+const CRC: crc::Crc<u32> = crc::Crc::<u32>::new(&crc::CRC_32_ISCSI);
+
+/// Byte of a [LogEntry] without slot.
+const NO_SLOT: u8 = 0xff;
+
+/// Kind of error in a [LogEntry].
+#[derive(Debug, PartialEq, Clone, Copy, TryFromPrimitive, IntoPrimitive)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum ErrorKind {
+    /// The board failed to initialize, with a board specific code.
+    Init = 1,
+    /// Booting the image in the slot failed, with the code of the `BootError`.
+    Boot = 2,
+    /// No image booted, and the bootloader gave up.
+    GaveUp = 3,
+}
+
+/// Error recorded by the bootloader.
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct LogEntry {
+    pub kind: ErrorKind,
+    /// Slot being booted, if any.
+    pub slot: Option<Slot>,
+    /// Code identifying the error within its [ErrorKind].
+    pub code: u8,
+}
+
+impl LogEntry {
+    fn to_word(self) -> u32 {
+        let slot = self.slot.map_or(NO_SLOT, u8::from);
+        u32::from_le_bytes([self.kind.into(), slot, self.code, 0])
+    }
+
+    fn from_word(word: u32) -> Option<Self> {
+        let [kind, slot, code, reserved] = word.to_le_bytes();
+        if reserved != 0 {
+            return None;
+        }
+
+        let slot = match slot {
+            NO_SLOT => None,
+            slot => Some(Slot::try_from(slot).ok()?),
+        };
+        Some(Self {
+            kind: ErrorKind::try_from(kind).ok()?,
+            slot,
+            code,
+        })
+    }
+}
+
+/// Destination of the errors recorded by the bootloader.
+///
+/// Recording must not fail, nor take long, as it happens on the path to booting a fallback image.
+pub trait LogSink {
+    fn record(&mut self, entry: LogEntry);
+}
+
+/// Ring buffer of the latest `N` [LogEntry]s.
+///
+/// Typically placed in RAM that is neither initialized at startup nor cleared by a warm reset, for example the
+/// `.uninit` section of `cortex-m-rt`. As any contents are valid, it can be recorded into before it was ever written,
+/// in which case it starts out empty. The application retrieves the entries after booting, and may [ErrorLog::clear]
+/// them after reporting.
+#[derive(Clone, Copy, PartialEq)]
+#[repr(C)]
+pub struct ErrorLog<const N: usize> {
+    magic: u32,
+    /// Number of entries ever recorded, of which the latest `N` are kept.
+    recorded: u32,
+    entries: [u32; N],
+    crc: u32,
+}
+
+impl<const N: usize> ErrorLog<N> {
+    /// Log that is never valid, for example to initialize a `static`.
+    pub const INVALID: Self = Self {
+        magic: 0,
+        recorded: 0,
+        entries: [0; N],
+        crc: 0,
+    };
+
+    /// Empty log.
+    pub fn new() -> Self {
+        let mut log = Self {
+            magic: MAGIC,
+            recorded: 0,
+            entries: [0; N],
+            crc: 0,
+        };
+        log.crc = log.checksum();
+        log
+    }
+
+    fn checksum(&self) -> u32 {
+        let mut digest = CRC.digest();
+        digest.update(&self.magic.to_le_bytes());
+        digest.update(&self.recorded.to_le_bytes());
+        for entry in &self.entries {
+            digest.update(&entry.to_le_bytes());
+        }
+        digest.finalize()
+    }
+
+    /// Whether this log was written as a whole, rather than left over in uninitialized RAM or partially written.
+    pub fn is_valid(&self) -> bool {
+        self.magic == MAGIC && self.crc == self.checksum()
+    }
+
+    /// Remove all entries.
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Number of entries recorded but no longer kept, as more than `N` were recorded since the last clear.
+    pub fn lost(&self) -> u32 {
+        if !self.is_valid() {
+            return 0;
+        }
+        self.recorded.saturating_sub(N as u32)
+    }
+
+    /// Entries kept, oldest first. Yields nothing if the log is not valid.
+    pub fn entries(&self) -> impl Iterator<Item = LogEntry> + '_ {
+        let recorded = if self.is_valid() { self.recorded as usize } else { 0 };
+        let kept = recorded.min(N);
+        (recorded - kept..recorded).filter_map(move |i| LogEntry::from_word(self.entries[i % N]))
+    }
+}
+
+impl<const N: usize> Default for ErrorLog<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> LogSink for ErrorLog<N> {
+    fn record(&mut self, entry: LogEntry) {
+        if N == 0 {
+            return;
+        }
+        if !self.is_valid() {
+            self.clear();
+        }
+
+        self.entries[self.recorded as usize % N] = entry.to_word();
+        self.recorded = self.recorded.saturating_add(1);
+        self.crc = self.checksum();
+    }
+}
+
+impl<const N: usize> core::fmt::Debug for ErrorLog<N> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("ErrorLog")
+            .field("valid", &self.is_valid())
+            .field("recorded", &self.recorded)
+            .finish()
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl<const N: usize> defmt::Format for ErrorLog<N> {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "ErrorLog {{ valid: {}, recorded: {=u32} }}",
+            self.is_valid(),
+            self.recorded
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn boot(slot: Slot, code: u8) -> LogEntry {
+        LogEntry {
+            kind: ErrorKind::Boot,
+            slot: Some(slot),
+            code,
+        }
+    }
+
+    #[test]
+    fn entry_roundtrip() {
+        let gave_up = LogEntry {
+            kind: ErrorKind::GaveUp,
+            slot: None,
+            code: 0,
+        };
+        for entry in [boot(Slot::S3, 7), gave_up] {
+            assert_eq!(LogEntry::from_word(entry.to_word()), Some(entry));
+        }
+        assert_eq!(LogEntry::from_word(0), None);
+        assert_eq!(LogEntry::from_word(0xffff_ffff), None);
+    }
+
+    #[test]
+    fn ring_keeps_latest() {
+        let mut log = ErrorLog::<3>::INVALID;
+        assert!(!log.is_valid());
+        assert_eq!(log.entries().count(), 0);
+
+        // Recording into an invalid log starts out empty.
+        log.record(boot(Slot::S0, 1));
+        assert!(log.is_valid());
+        assert_eq!(log.entries().collect::<std::vec::Vec<_>>(), [boot(Slot::S0, 1)]);
+
+        for code in 2..=5 {
+            log.record(boot(Slot::S1, code));
+        }
+        assert_eq!(
+            log.entries().collect::<std::vec::Vec<_>>(),
+            [boot(Slot::S1, 3), boot(Slot::S1, 4), boot(Slot::S1, 5)]
+        );
+        assert_eq!(log.lost(), 2);
+
+        // A corrupted log yields nothing, rather than garbage.
+        log.entries[0] ^= 1;
+        assert_eq!(log.entries().count(), 0);
+
+        log.clear();
+        assert_eq!(log.entries().count(), 0);
+        assert_eq!(log.lost(), 0);
+    }
+}
@@ -1,10 +1,20 @@
 #[cfg(any(test, feature = "_test"))]
 pub mod mock;
+#[cfg(feature = "shared")]
+mod shared;
 
 use core::ops::Range;
 
+#[cfg(feature = "notify")]
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+#[cfg(feature = "notify")]
+use embassy_sync::signal::Signal;
 use embedded_storage_async::nor_flash::NorFlash;
 
+#[cfg(feature = "shared")]
+pub use self::shared::SharedFlashJournal;
+use crate::record::{Record, MAX_RECORD_SIZE};
+use crate::shadow::Shadow;
 use crate::state::{ParseResult, State};
 
 /// Error describing that the Nvm should have at least two partitions.
@@ -20,8 +30,60 @@ pub enum Error<E> {
     /// or in the storage medium itself.
     ReadbackFailed,
 
+    /// The journal does not contain a [Record] to update yet.
+    Empty,
+
     /// The underlying storage medium yielded an error.
     Other(E),
+
+    /// The storage medium does not span a whole number of erase blocks.
+    ///
+    /// Erasing its last page would also erase the data following it, such as a neighbouring partition.
+    NotEraseAligned,
+
+    /// A [Record] padded to the write size of the storage medium does not fit the journal, or an erase block does not
+    /// hold a whole number of them.
+    UnsupportedWriteSize,
+
+    /// The buffer to scan the storage medium with does not hold a single [Record] padded to the write size.
+    BufferTooSmall,
+
+    /// The [Record] can not be updated anymore, as a counter it holds would wrap.
+    Exhausted,
+}
+
+/// Signal carrying the latest [Record] after it has been changed using [FlashJournal::set].
+#[cfg(feature = "notify")]
+pub type Notifier<R = State> = Signal<CriticalSectionRawMutex, R>;
+
+impl<E> Error<E> {
+    /// Stable code of the error, for status registers and logs without `defmt`.
+    pub fn code(&self) -> u8 {
+        match self {
+            Error::NotEnoughPartitions => 1,
+            Error::ReadbackFailed => 2,
+            Error::Empty => 3,
+            Error::Other(_) => 4,
+            Error::NotEraseAligned => 5,
+            Error::UnsupportedWriteSize => 6,
+            Error::BufferTooSmall => 7,
+            Error::Exhausted => 8,
+        }
+    }
+
+    /// Short description of the error, for boards logging without `defmt`.
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Error::NotEnoughPartitions => "not enough partitions",
+            Error::ReadbackFailed => "readback failed",
+            Error::Empty => "journal empty",
+            Error::Other(_) => "storage error",
+            Error::NotEraseAligned => "storage not erase aligned",
+            Error::UnsupportedWriteSize => "unsupported write size",
+            Error::BufferTooSmall => "scan buffer too small",
+            Error::Exhausted => "record exhausted",
+        }
+    }
 }
 
 impl<E> From<E> for Error<E> {
@@ -31,43 +93,220 @@ impl<E> From<E> for Error<E> {
 }
 
 #[derive(Debug)]
-struct StateWithAddr {
-    /// Actual value of the [State].
-    state: State,
-    /// Address of the [State].
+struct StateWithAddr<R> {
+    /// Actual value of the [Record].
+    state: R,
+    /// Address of the [Record].
     address: usize,
 }
 
-#[derive(Default)]
-struct Cache {
-    /// A copy of the last valid [State] on-disk.
-    last_valid_state: Option<StateWithAddr>,
+struct Cache<R> {
+    /// A copy of the last valid [Record] on-disk.
+    last_valid_state: Option<StateWithAddr<R>>,
 
-    /// Address of last empty slot for a [State], containing only 0xff bytes.
+    /// Address of last empty slot for a [Record], containing only 0xff bytes.
     first_empty_slot: Option<usize>,
+
+    /// Whether the last valid [Record] was migrated from an older [Record::SCHEMA], and has yet to be written back.
+    migrated: bool,
 }
 
-/// Bootloader [State] journal backed by Non-Volatile Memory.
-pub struct FlashJournal<T> {
+impl<R> Default for Cache<R> {
+    fn default() -> Self {
+        Self {
+            last_valid_state: None,
+            first_empty_slot: None,
+            migrated: false,
+        }
+    }
+}
+
+/// Cursor over every slot of a [FlashJournal], as returned by [FlashJournal::iter_records].
+pub struct Records<'a, 'b, T, R: 'static> {
+    journal: &'a mut FlashJournal<'b, T, R>,
+    /// Address of the next slot to read.
+    address: usize,
+}
+
+impl<T: NorFlash, R: Record> Records<'_, '_, T, R> {
+    /// Read the next slot, yielding its address and the [Record] parsed from it, or [None] past the last slot.
+    pub async fn next(&mut self) -> Option<Result<(usize, Result<R, ParseResult>), T::Error>> {
+        let address = self.address;
+        let slot_size = FlashJournal::<T, R>::SLOT_SIZE;
+        if address + slot_size > self.journal.inner.capacity() {
+            return None;
+        }
+        self.address += slot_size;
+
+        let mut buf = [0u8; MAX_RECORD_SIZE];
+        let bytes = &mut buf[..slot_size];
+        Some(match self.journal.inner.read(address as u32, bytes).await {
+            Ok(()) => Ok((address, R::try_from_bytes(&bytes[..R::SIZE]))),
+            Err(e) => Err(e),
+        })
+    }
+}
+
+/// Journal of [Record]s backed by Non-Volatile Memory, by default containing the bootloader [State].
+///
+/// The journal borrows a buffer to scan the storage with, such that its size is chosen once when constructing it.
+pub struct FlashJournal<'a, T, R: 'static = State> {
     /// Inner flash storage.
     inner: T,
+    /// Buffer the storage is scanned with, trimmed to a whole number of padded [Record]s.
+    buffer: &'a mut [u8],
     /// A in-ram cache of the state on disk and where to write the next state to.
-    cache: Cache,
+    cache: Cache<R>,
+    /// Signalled whenever the [Record] changes.
+    #[cfg(feature = "notify")]
+    notifier: Option<&'static Notifier<R>>,
 }
 
-impl<T: NorFlash> FlashJournal<T> {
+impl<'a, T: NorFlash, R: Record> FlashJournal<'a, T, R> {
     const PAGE_SIZE: usize = T::ERASE_SIZE;
 
-    /// Construct the FlashJournal given a storage device (or a partition).
+    /// Number of bytes between consecutive [Record]s, being [Record::SIZE] padded with `0xff` to a whole number of
+    /// writes.
+    const SLOT_SIZE: usize = R::SIZE.next_multiple_of(T::WRITE_SIZE);
+
+    /// Construct the FlashJournal given a storage device (or a partition), scanning it using `buffer`.
+    ///
+    /// The storage is read in blocks of the length of `buffer`, rounded down to a whole number of [Record]s padded to
+    /// [NorFlash::WRITE_SIZE]. A larger buffer generally makes scanning faster.
+    ///
+    /// Will yield [Error::NotEnoughPartitions] if the partition is not contain at least 2 pages,
+    /// [Error::NotEraseAligned] or [Error::UnsupportedWriteSize] if the storage does not fit the journal, see
+    /// [FlashJournal::check], and [Error::BufferTooSmall] if `buffer` does not hold a single padded [Record]. A latest
+    /// [Record] of an older [Record::SCHEMA] is migrated, and written back in the current schema.
+    pub async fn new(mut inner: T, buffer: &'a mut [u8]) -> Result<Self, Error<T::Error>> {
+        const {
+            assert!(
+                R::SIZE > 0 && R::SIZE <= MAX_RECORD_SIZE,
+                "record does not fit the journal"
+            )
+        };
+
+        Self::check(&inner)?;
+        let buffer = Self::trim(buffer)?;
+
+        let cache = Self::compute_cache(&mut inner, buffer).await?;
+        Ok(Self::with_cache(inner, buffer, cache).await)
+    }
+
+    /// Construct the FlashJournal like [FlashJournal::new], but without scanning the storage if `shadow` still matches.
     ///
-    /// Will yield [Error::NotEnoughPartitions] if the partition is not contain at least 2 pages.
-    pub async fn new<const N: usize>(mut inner: T) -> Result<Self, Error<T::Error>> {
-        if Self::page_count(&inner) < 2 {
+    /// The storage is only read at the address of the [Record] in the shadow and at the slot after it. If either does
+    /// not match, for example because the journal was changed without updating the shadow, the storage is scanned.
+    pub async fn with_shadow(mut inner: T, buffer: &'a mut [u8], shadow: &Shadow) -> Result<Self, Error<T::Error>> {
+        Self::check(&inner)?;
+        let buffer = Self::trim(buffer)?;
+
+        let cache = match Self::cache_from_shadow(&mut inner, shadow).await? {
+            Some(cache) => cache,
+            None => Self::compute_cache(&mut inner, buffer).await?,
+        };
+        Ok(Self::with_cache(inner, buffer, cache).await)
+    }
+
+    /// Ensure the journal can be kept in `inner` without touching anything beyond it.
+    ///
+    /// The storage must span at least two erase blocks, and a whole number of them, such that erasing a page never
+    /// erases data next to the journal. Records are padded to [NorFlash::WRITE_SIZE], which must keep them within
+    /// [MAX_RECORD_SIZE] and must evenly divide an erase block. Only the length is known here: the offset of a
+    /// partition has to be checked to be erase aligned by whoever maps it.
+    fn check(inner: &T) -> Result<(), Error<T::Error>> {
+        if Self::SLOT_SIZE > MAX_RECORD_SIZE || !Self::PAGE_SIZE.is_multiple_of(Self::SLOT_SIZE) {
+            return Err(Error::UnsupportedWriteSize);
+        }
+        if !inner.capacity().is_multiple_of(Self::PAGE_SIZE) {
+            return Err(Error::NotEraseAligned);
+        }
+        if Self::page_count(inner) < 2 {
             return Err(Error::NotEnoughPartitions);
         }
+        Ok(())
+    }
+
+    /// Trim `buffer` to a whole number of [Record]s padded to [NorFlash::WRITE_SIZE], to scan the storage with.
+    fn trim(buffer: &'a mut [u8]) -> Result<&'a mut [u8], Error<T::Error>> {
+        let len = buffer.len() - buffer.len() % Self::SLOT_SIZE;
+        if len == 0 {
+            return Err(Error::BufferTooSmall);
+        }
+        Ok(&mut buffer[..len])
+    }
+
+    /// Construct the FlashJournal from its `cache`, writing back a migrated [Record].
+    ///
+    /// A failure to write back is not fatal, as the outdated record is migrated again the next time.
+    async fn with_cache(inner: T, buffer: &'a mut [u8], cache: Cache<R>) -> Self {
+        let mut journal = Self {
+            inner,
+            buffer,
+            cache,
+            #[cfg(feature = "notify")]
+            notifier: None,
+        };
+
+        if let (true, Some(&state)) = (journal.cache.migrated, journal.get()) {
+            if journal.write(&state).await.is_err() {
+                defmt_or_log::warn!("Failed to write back a record migrated to the current schema");
+            }
+        }
+        journal
+    }
+
+    /// Check `shadow` against the storage, yielding the cache it describes if it still matches.
+    async fn cache_from_shadow(inner: &mut T, shadow: &Shadow) -> Result<Option<Cache<R>>, T::Error> {
+        let Some((record, address, first_empty_slot)) = shadow.get::<R>() else {
+            return Ok(None);
+        };
+
+        let in_bounds =
+            |address: usize| address.is_multiple_of(Self::SLOT_SIZE) && address + Self::SLOT_SIZE <= inner.capacity();
+        if !in_bounds(address) || !in_bounds(first_empty_slot) || first_empty_slot <= address {
+            return Ok(None);
+        }
+
+        let mut buf = [0u8; MAX_RECORD_SIZE];
+        let bytes = &mut buf[..Self::SLOT_SIZE];
+        inner.read(first_empty_slot as u32, bytes).await?;
+        if bytes.iter().any(|b| *b != 0xff) {
+            return Ok(None);
+        }
+
+        inner.read(address as u32, bytes).await?;
+        let bytes = &bytes[..R::SIZE];
+        if bytes != record {
+            return Ok(None);
+        }
+        let Ok(state) = R::try_from_bytes(bytes) else {
+            return Ok(None);
+        };
 
-        let cache = Self::compute_cache::<N>(&mut inner).await?;
-        Ok(Self { inner, cache })
+        Ok(Some(Cache {
+            last_valid_state: Some(StateWithAddr { state, address }),
+            first_empty_slot: Some(first_empty_slot),
+            migrated: false,
+        }))
+    }
+
+    /// Shadow of the latest [Record], to construct the journal using [FlashJournal::with_shadow] after a warm reset.
+    ///
+    /// Yields [None] if the journal is empty, or if it contains no empty slot. In the latter case the next change
+    /// moves records around, hence the journal has to be scanned anyway.
+    pub fn shadow(&self) -> Option<Shadow> {
+        let StateWithAddr { state, address } = self.cache.last_valid_state.as_ref()?;
+        let first_empty_slot = self.cache.first_empty_slot?;
+        Some(Shadow::new(state, *address, first_empty_slot))
+    }
+
+    /// Signal `notifier` with the new [Record] whenever it is changed using [FlashJournal::set].
+    ///
+    /// Allows other tasks to learn about changes without polling. Only the latest [Record] is retained in the signal.
+    #[cfg(feature = "notify")]
+    pub fn notify(&mut self, notifier: &'static Notifier<R>) {
+        self.notifier = Some(notifier);
     }
 
     /// Number of pages in the backing storage medium.
@@ -80,44 +319,57 @@ impl<T: NorFlash> FlashJournal<T> {
         address as usize / Self::PAGE_SIZE
     }
 
-    /// Walk through the entire NVM range, and find the last valid [State] entry
-    /// and find the first empty slot of a [State] entry, if any.
+    /// Walk backwards through the NVM range to find the last valid [Record] entry
+    /// and the first empty slot of a [Record] entry after it, if any.
     ///
-    /// `BLOCK_SIZE` denotes the number of bytes that are read in a single batch
+    /// Records are written in ascending address order, hence the scan stops at the first valid entry from the end.
+    /// Blocks in the erased tail are recognized as a whole and are not parsed entry by entry.
+    /// Only if the journal contains no valid entry at all is the entire NVM range read.
+    ///
+    /// The length of `buf` denotes the number of bytes that are read in a single batch
     /// and are analysed, before reading the next block.
-    /// A larger block size generally improves performance, and needs to be a non-zero multiple of 2 bytes.
-    async fn compute_cache<const BLOCK_SIZE: usize>(inner: &mut T) -> Result<Cache, T::Error> {
-        const CHUNK_SIZE: usize = 2;
+    /// It needs to be a non-zero multiple of [Record::SIZE] bytes padded to [NorFlash::WRITE_SIZE], see
+    /// [FlashJournal::trim].
+    async fn compute_cache(inner: &mut T, buf: &mut [u8]) -> Result<Cache<R>, T::Error> {
+        let chunk_size = Self::SLOT_SIZE;
+        let block_size = buf.len();
 
-        defmt_or_log::assert!(BLOCK_SIZE >= CHUNK_SIZE);
-        defmt_or_log::assert!(BLOCK_SIZE.is_multiple_of(CHUNK_SIZE));
+        defmt_or_log::assert!(block_size >= chunk_size);
+        defmt_or_log::assert!(block_size.is_multiple_of(chunk_size));
 
-        let mut buf = [0u8; BLOCK_SIZE];
-        let block_count = inner.capacity().div_ceil(BLOCK_SIZE);
+        let block_count = inner.capacity().div_ceil(block_size);
 
         let mut result = Cache::default();
-        for block_i in 0..block_count {
-            let block_start = block_i * BLOCK_SIZE;
-            let block_end = (block_start + BLOCK_SIZE).min(inner.capacity());
+        for block_i in (0..block_count).rev() {
+            let block_start = block_i * block_size;
+            let block_end = (block_start + block_size).min(inner.capacity());
 
             let slice = &mut buf[0..block_end - block_start];
             inner.read(block_start as u32, slice).await?;
 
-            for (chunk_i, chunk) in slice.chunks_exact(CHUNK_SIZE).enumerate() {
-                // Note(unsafe): we are using chunks_exact and then cast the slice into the same size array.
-                let chunk: [u8; CHUNK_SIZE] = unsafe { chunk.try_into().unwrap_unchecked() };
-                let address = block_start + chunk_i * CHUNK_SIZE;
-                match State::try_new(chunk) {
+            // Part of the erased tail, every entry in this block is empty.
+            if slice.len() >= chunk_size && slice.iter().all(|b| *b == 0xff) {
+                result.first_empty_slot = Some(block_start);
+                continue;
+            }
+
+            for (chunk_i, chunk) in slice.chunks_exact(chunk_size).enumerate().rev() {
+                let address = block_start + chunk_i * chunk_size;
+                let chunk = &chunk[..R::SIZE];
+                match R::try_from_bytes(chunk) {
                     Ok(state) => {
-                        result = Cache {
-                            last_valid_state: Some(StateWithAddr { state, address }),
-                            first_empty_slot: None, // Reset if any.
-                        };
+                        result.last_valid_state = Some(StateWithAddr { state, address });
+                        return Ok(result);
                     }
                     Err(ParseResult::Unset) => {
-                        // If not found an empty entry yet, we can record this one as the first one free.
-                        if result.first_empty_slot.is_none() {
-                            result.first_empty_slot = Some(address);
+                        // Scanning backwards, so this empty slot comes before any found so far.
+                        result.first_empty_slot = Some(address);
+                    }
+                    Err(ParseResult::Outdated) => {
+                        if let Ok(state) = R::migrate(chunk) {
+                            result.last_valid_state = Some(StateWithAddr { state, address });
+                            result.migrated = true;
+                            return Ok(result);
                         }
                     }
                     Err(ParseResult::Invalid) => {} // Broken.
@@ -127,14 +379,25 @@ impl<T: NorFlash> FlashJournal<T> {
         Ok(result)
     }
 
-    /// Get the latest [State] contained in the [FlashJournal], if any.
-    pub fn get(&self) -> Option<&State> {
+    /// Get the latest [Record] contained in the [FlashJournal], if any.
+    pub fn get(&self) -> Option<&R> {
         self.cache
             .last_valid_state
             .as_ref()
             .map(|StateWithAddr { state, address: _ }| state)
     }
 
+    /// Iterate over every slot of the journal in the order they are written, yielding raw [Record]s for diagnostics.
+    ///
+    /// Unlike [FlashJournal::get] this reveals the history of the journal, such as how many boot attempts failed.
+    /// Erased slots yield [ParseResult::Unset], and records of an older [Record::SCHEMA] are not migrated.
+    pub fn iter_records(&mut self) -> Records<'_, 'a, T, R> {
+        Records {
+            journal: self,
+            address: 0,
+        }
+    }
+
     /// Erase a range of pages as a single erase instruction to [NorFlash].
     async fn erase_pages(&mut self, page_range: Range<usize>) -> Result<(), T::Error> {
         let start = page_range.start * Self::PAGE_SIZE;
@@ -142,17 +405,26 @@ impl<T: NorFlash> FlashJournal<T> {
         self.inner.erase(start as u32, end as u32).await
     }
 
-    /// Synchronize the latest [State] to the [FlashJournal].
-    pub async fn set<const N: usize>(&mut self, state: &State) -> Result<(), Error<T::Error>> {
+    /// Synchronize the latest [Record] to the [FlashJournal].
+    pub async fn set(&mut self, state: &R) -> Result<(), Error<T::Error>> {
         // Check if the current state is identical.
         if self.get() == Some(state) {
             return Ok(());
         }
 
+        self.write(state).await
+    }
+
+    /// Write `state` as the latest [Record], even if it equals the current one.
+    async fn write(&mut self, state: &R) -> Result<(), Error<T::Error>> {
+        let mut buf = [0xffu8; MAX_RECORD_SIZE];
+        state.to_bytes(&mut buf[..R::SIZE]);
+        let bytes = &buf[..Self::SLOT_SIZE];
+
         // Write the new state somewhere.
         if let Some(first_empty_slot) = self.cache.first_empty_slot {
             // If detected first empty slot, we can write to it as we are [NorFlash] and the empty slot is all `0xff``.
-            self.inner.write(first_empty_slot as u32, &state.as_bytes()).await?;
+            self.inner.write(first_empty_slot as u32, bytes).await?;
         } else if let Some(last_valid_state) = &self.cache.last_valid_state {
             // If detected no empty slot, we can assume that all pages have been written, or we are in a partially valid state.
 
@@ -162,7 +434,7 @@ impl<T: NorFlash> FlashJournal<T> {
                 self.erase_pages(0..1).await?;
 
                 // Write state.
-                self.inner.write(0, &state.as_bytes()).await?;
+                self.inner.write(0, bytes).await?;
 
                 // Erase rest of pages, and the erasure of the final page will validate our just written state.
                 // If this gets interrupted, the last state will remain valid.
@@ -177,34 +449,244 @@ impl<T: NorFlash> FlashJournal<T> {
 
                 // Write the state to the first address in the second page, immediately becoming the newest valid state.
                 let state_address = second_page_i * Self::PAGE_SIZE;
-                self.inner.write(state_address as u32, &state.as_bytes()).await?;
+                self.inner.write(state_address as u32, bytes).await?;
             }
         } else {
             // No state is stored anywhere, and there are no empty slots, clear everything, write.
             self.inner.erase(0, self.inner.capacity() as u32).await?;
-            self.inner.write(0, &state.as_bytes()).await?;
+            self.inner.write(0, bytes).await?;
         }
 
         // Re-compute the cache to check if the journal is valid.
-        self.cache = Self::compute_cache::<N>(&mut self.inner).await?;
+        self.cache = Self::compute_cache(&mut self.inner, self.buffer).await?;
 
         // Check if the readback is successful.
         if self.get() == Some(state) {
+            #[cfg(feature = "notify")]
+            if let Some(notifier) = self.notifier {
+                notifier.signal(*state);
+            }
+
+            Ok(())
+        } else {
+            Err(Error::ReadbackFailed)
+        }
+    }
+    /// Reset the journal to only contain `default_state`, discarding all older [Record]s.
+    ///
+    /// The journal is erased and seeded in an order such that, when interrupted, the latest [Record] is either the one
+    /// from before the reset or `default_state`. The journal is never left empty.
+    pub async fn reset(&mut self, default_state: &R) -> Result<(), Error<T::Error>> {
+        // Make `default_state` the latest record, after which only copies of it are moved around.
+        self.set(default_state).await?;
+        let Some(latest) = &self.cache.last_valid_state else {
+            return Err(Error::ReadbackFailed);
+        };
+
+        let mut buf = [0xffu8; MAX_RECORD_SIZE];
+        default_state.to_bytes(&mut buf[..R::SIZE]);
+        let bytes = &buf[..Self::SLOT_SIZE];
+        let page_count = Self::page_count(&self.inner);
+
+        if Self::address_to_page_i(latest.address as u32) == 0 {
+            // Move the latest copy out of the first page, such that the first page can be erased.
+            // The pages after it contain no valid records, as those would be newer.
+            self.erase_pages(1..page_count).await?;
+            self.inner.write(Self::PAGE_SIZE as u32, bytes).await?;
+        }
+
+        // The latest copy lives beyond the first page, so the first page can be seeded.
+        self.erase_pages(0..1).await?;
+        self.inner.write(0, bytes).await?;
+
+        // Erasing the other pages leaves the seeded copy as the only record.
+        self.erase_pages(1..page_count).await?;
+
+        self.cache = Self::compute_cache(&mut self.inner, self.buffer).await?;
+        if self.get() == Some(default_state) {
             Ok(())
         } else {
             Err(Error::ReadbackFailed)
         }
     }
+
+    /// Erase pages that contain data but no valid [Record], as left behind by an interrupted erase.
+    ///
+    /// Such pages are skipped when scanning, but their slots stay lost until the pages are rotated out. Pages are only
+    /// erased if the journal contains a valid [Record], which by definition lives in another page. Unlike constructing
+    /// the journal this reads the entire storage, hence it is meant to be run once at startup.
+    ///
+    /// Yields the number of pages that were erased.
+    pub async fn repair(&mut self) -> Result<usize, Error<T::Error>> {
+        let Some(latest) = &self.cache.last_valid_state else {
+            return Ok(0);
+        };
+        let latest_page_i = Self::address_to_page_i(latest.address as u32);
+
+        let mut erased = 0;
+        for page_i in (0..Self::page_count(&self.inner)).filter(|page_i| *page_i != latest_page_i) {
+            if self.page_is_garbage(page_i).await? {
+                self.erase_pages(page_i..page_i + 1).await?;
+                erased += 1;
+            }
+        }
+
+        if erased > 0 {
+            self.cache = Self::compute_cache(&mut self.inner, self.buffer).await?;
+        }
+        Ok(erased)
+    }
+
+    /// Whether the page contains data, but not a single [Record] that can be parsed or migrated.
+    async fn page_is_garbage(&mut self, page_i: usize) -> Result<bool, T::Error> {
+        let block_size = self.buffer.len();
+        let page_start = page_i * Self::PAGE_SIZE;
+        let page_end = (page_start + Self::PAGE_SIZE).min(self.inner.capacity());
+
+        let mut dirty = false;
+        for block_start in (page_start..page_end).step_by(block_size) {
+            let slice = &mut self.buffer[0..(page_end - block_start).min(block_size)];
+            self.inner.read(block_start as u32, slice).await?;
+
+            for chunk in slice.chunks_exact(Self::SLOT_SIZE) {
+                let chunk = &chunk[..R::SIZE];
+                match R::try_from_bytes(chunk) {
+                    Ok(_) => return Ok(false),
+                    Err(ParseResult::Outdated) if R::migrate(chunk).is_ok() => return Ok(false),
+                    Err(ParseResult::Unset) => {}
+                    Err(ParseResult::Outdated | ParseResult::Invalid) => dirty = true,
+                }
+            }
+        }
+        Ok(dirty)
+    }
+}
+
+impl<T: NorFlash> FlashJournal<'_, T, State> {
+    /// Get the user bits of the latest [State], or zero if the journal is empty.
+    ///
+    /// See [State::user_bits].
+    pub fn user_bits(&self) -> u8 {
+        self.get().map_or(0, State::user_bits)
+    }
+
+    /// Store new user bits alongside the latest [State].
+    ///
+    /// Yields [Error::Empty] if the journal does not contain a [State] yet.
+    pub async fn set_user_bits(&mut self, user_bits: u8) -> Result<(), Error<T::Error>> {
+        let Some(state) = self.get() else {
+            return Err(Error::Empty);
+        };
+
+        let state = state.with_user_bits(user_bits);
+        self.set(&state).await
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use embedded_storage_async::nor_flash::{ErrorType, ReadNorFlash};
+
     use super::*;
     use crate::flash::mock::MockFlashBase;
     use crate::state::{Slot, Status};
 
+    /// Flash wrapper counting the number of bytes read.
+    struct CountingFlash<T> {
+        inner: T,
+        bytes_read: usize,
+    }
+
+    impl<T: ErrorType> ErrorType for CountingFlash<T> {
+        type Error = T::Error;
+    }
+
+    impl<T: ReadNorFlash> ReadNorFlash for CountingFlash<T> {
+        const READ_SIZE: usize = T::READ_SIZE;
+
+        async fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+            self.bytes_read += bytes.len();
+            self.inner.read(offset, bytes).await
+        }
+
+        fn capacity(&self) -> usize {
+            self.inner.capacity()
+        }
+    }
+
+    impl<T: NorFlash> NorFlash for CountingFlash<T> {
+        const WRITE_SIZE: usize = T::WRITE_SIZE;
+        const ERASE_SIZE: usize = T::ERASE_SIZE;
+
+        async fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+            self.inner.erase(from, to).await
+        }
+
+        async fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+            self.inner.write(offset, bytes).await
+        }
+    }
+
+    /// Flash wrapper pretending to end `cut` bytes early, like a partition not spanning whole erase blocks.
+    struct TruncatedFlash<T> {
+        inner: T,
+        cut: usize,
+    }
+
+    impl<T: ErrorType> ErrorType for TruncatedFlash<T> {
+        type Error = T::Error;
+    }
+
+    impl<T: ReadNorFlash> ReadNorFlash for TruncatedFlash<T> {
+        const READ_SIZE: usize = T::READ_SIZE;
+
+        async fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+            self.inner.read(offset, bytes).await
+        }
+
+        fn capacity(&self) -> usize {
+            self.inner.capacity() - self.cut
+        }
+    }
+
+    impl<T: NorFlash> NorFlash for TruncatedFlash<T> {
+        const WRITE_SIZE: usize = T::WRITE_SIZE;
+        const ERASE_SIZE: usize = T::ERASE_SIZE;
+
+        async fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+            self.inner.erase(from, to).await
+        }
+
+        async fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+            self.inner.write(offset, bytes).await
+        }
+    }
+
+    /// Straightforward forward scan of the entire NVM range, as reference for [FlashJournal::compute_cache].
+    fn reference_cache(data: &[u8]) -> (Option<usize>, Option<usize>) {
+        let mut last_valid = None;
+        let mut first_empty = None;
+        for (chunk_i, chunk) in data.chunks_exact(State::SIZE).enumerate() {
+            let address = chunk_i * State::SIZE;
+            match State::try_from_bytes(chunk) {
+                Ok(_) => {
+                    last_valid = Some(address);
+                    first_empty = None;
+                }
+                Err(ParseResult::Unset) => {
+                    if first_empty.is_none() {
+                        first_empty = Some(address);
+                    }
+                }
+                Err(ParseResult::Invalid | ParseResult::Outdated) => {}
+            }
+        }
+        (last_valid, first_empty)
+    }
+
     async fn test_journal(nvm: impl NorFlash, assert_empty: bool) -> Option<usize> {
-        let mut journal = FlashJournal::new::<4>(nvm).await.unwrap();
+        let mut buffer = [0; 4];
+        let mut journal = FlashJournal::new(nvm, &mut buffer).await.unwrap();
 
         if assert_empty {
             assert!(journal.get().is_none());
@@ -215,11 +697,11 @@ mod tests {
             let slot_b = Slot::try_from(2).unwrap();
 
             let state = State::new(Status::Initial, slot_b, slot_a);
-            journal.set::<4>(&state).await.unwrap();
+            journal.set(&state).await.unwrap();
             assert_eq!(journal.get(), Some(&state));
 
             // Re-do the same operation.
-            journal.set::<4>(&state).await.unwrap();
+            journal.set(&state).await.unwrap();
             assert_eq!(journal.get(), Some(&state));
         }
 
@@ -232,7 +714,7 @@ mod tests {
                     let slot_b = Slot::try_from(j).unwrap();
 
                     let state = State::new(status, slot_b, slot_a);
-                    journal.set::<4>(&state).await.unwrap();
+                    journal.set(&state).await.unwrap();
                     assert_eq!(journal.get(), Some(&state));
                 }
             }
@@ -247,6 +729,166 @@ mod tests {
         embassy_futures::block_on(test_journal(&mut mock, true));
     }
 
+    #[test]
+    fn journal_iter_records() {
+        let mut mock: MockFlashBase<3, 2, 8> = MockFlashBase::new(None, false);
+        embassy_futures::block_on(async {
+            let mut buffer = [0; 4];
+            let mut journal = FlashJournal::new(&mut mock, &mut buffer).await.unwrap();
+            let states = [
+                State::new(Status::Initial, Slot::S1, Slot::S0),
+                State::new(Status::Attempting, Slot::S1, Slot::S0),
+                State::new(Status::Failed, Slot::S1, Slot::S0),
+            ];
+            for state in &states {
+                journal.set(state).await.unwrap();
+            }
+
+            let mut records = journal.iter_records();
+            let mut written = [None; 3];
+            let mut slot_count = 0;
+            while let Some(record) = records.next().await {
+                let (address, record) = record.unwrap();
+                assert_eq!(address, slot_count * State::SIZE);
+                match record {
+                    Ok(state) => written[address / State::SIZE] = Some(state),
+                    Err(e) => assert_eq!(e, ParseResult::Unset),
+                }
+                slot_count += 1;
+            }
+            assert_eq!(slot_count, 3 * 2 * 8 / State::SIZE);
+            assert_eq!(written, states.map(Some));
+        });
+    }
+
+    #[test]
+    fn journal_padded_to_write_size() {
+        // Pages of four 8-byte words, such that every 4-byte state is padded to a whole word.
+        let mut mock: MockFlashBase<3, 8, 4> = MockFlashBase::new(None, false);
+        embassy_futures::block_on(async {
+            let mut buffer = [0; 8];
+            let mut journal = FlashJournal::new(&mut mock, &mut buffer).await.unwrap();
+            let mut state = State::new(Status::Initial, Slot::S1, Slot::S0);
+            for i in 0..20 {
+                state = State::new(
+                    [Status::Attempting, Status::Failed][i % 2],
+                    Slot::S1,
+                    Slot::try_from(i as u8 % 7).unwrap(),
+                );
+                journal.set(&state).await.unwrap();
+            }
+
+            let mut buffer = [0; 8];
+            let journal = FlashJournal::<_, State>::new(&mut mock, &mut buffer).await.unwrap();
+            assert_eq!(journal.get(), Some(&state));
+            let address = journal.cache.last_valid_state.as_ref().unwrap().address;
+            assert_eq!(address % 8, 0);
+
+            let data = mock.as_bytes();
+            assert_eq!(data[address + 4..address + 8], [0xff; 4]);
+            let parsed = crate::record::parse_padded_records::<State>(data, 8)
+                .filter_map(|(address, record)| Some((address, record.ok()?)))
+                .last();
+            assert_eq!(parsed, Some((address, state)));
+        });
+    }
+
+    #[test]
+    fn journal_rejects_unsupported_storage() {
+        embassy_futures::block_on(async {
+            let mut wide: MockFlashBase<3, 64, 1> = MockFlashBase::new(None, false);
+            let mut buffer = [0; 64];
+            let result = FlashJournal::<_, State>::new(&mut wide, &mut buffer).await;
+            assert!(matches!(result, Err(Error::UnsupportedWriteSize)));
+
+            let truncated = TruncatedFlash {
+                inner: MockFlashBase::<3, 2, 8>::new(None, false),
+                cut: 2,
+            };
+            let mut buffer = [0; 4];
+            let result = FlashJournal::<_, State>::new(truncated, &mut buffer).await;
+            assert!(matches!(result, Err(Error::NotEraseAligned)));
+
+            let single: MockFlashBase<1, 2, 8> = MockFlashBase::new(None, false);
+            let mut buffer = [0; 4];
+            let result = FlashJournal::<_, State>::with_shadow(single, &mut buffer, &Shadow::INVALID).await;
+            assert!(matches!(result, Err(Error::NotEnoughPartitions)));
+
+            let mut buffer = [0; State::SIZE - 1];
+            let result = FlashJournal::<_, State>::new(MockFlashBase::<3, 2, 8>::new(None, false), &mut buffer).await;
+            assert!(matches!(result, Err(Error::BufferTooSmall)));
+
+            // A buffer not holding a whole number of records is trimmed.
+            let mut buffer = [0; State::SIZE + 2];
+            let mut journal = FlashJournal::new(MockFlashBase::<3, 2, 8>::new(None, false), &mut buffer)
+                .await
+                .unwrap();
+            journal
+                .set(&State::new(Status::Initial, Slot::S1, Slot::S0))
+                .await
+                .unwrap();
+            assert_eq!(journal.buffer.len(), State::SIZE);
+        });
+    }
+
+    #[test]
+    fn error_codes_distinct() {
+        let errors: [Error<()>; 8] = [
+            Error::NotEnoughPartitions,
+            Error::ReadbackFailed,
+            Error::Empty,
+            Error::Other(()),
+            Error::NotEraseAligned,
+            Error::UnsupportedWriteSize,
+            Error::BufferTooSmall,
+            Error::Exhausted,
+        ];
+        for (i, a) in errors.iter().enumerate() {
+            assert!(!a.describe().is_empty());
+            for b in &errors[i + 1..] {
+                assert_ne!(a.code(), b.code());
+                assert_ne!(a.describe(), b.describe());
+            }
+        }
+    }
+
+    #[cfg(feature = "notify")]
+    #[test]
+    fn journal_notify() {
+        static NOTIFIER: Notifier = Notifier::new();
+
+        let mut mock: MockFlashBase<3, 2, 8> = MockFlashBase::new(None, false);
+        embassy_futures::block_on(async {
+            let mut buffer = [0; 4];
+            let mut journal = FlashJournal::new(&mut mock, &mut buffer).await.unwrap();
+            journal.notify(&NOTIFIER);
+            assert_eq!(NOTIFIER.try_take(), None);
+
+            let state_a = State::new(Status::Initial, Slot::try_from(1).unwrap(), Slot::try_from(0).unwrap());
+            journal.set(&state_a).await.unwrap();
+            assert_eq!(NOTIFIER.try_take(), Some(state_a));
+
+            // Setting an identical state is not a change.
+            journal.set(&state_a).await.unwrap();
+            assert_eq!(NOTIFIER.try_take(), None);
+
+            // Only the latest state is retained.
+            let state_b = State::new(
+                Status::Attempting,
+                Slot::try_from(1).unwrap(),
+                Slot::try_from(0).unwrap(),
+            );
+            let state_c = State::new(
+                Status::Confirmed,
+                Slot::try_from(1).unwrap(),
+                Slot::try_from(0).unwrap(),
+            );
+            journal.set(&state_b).await.unwrap();
+            journal.set(&state_c).await.unwrap();
+            assert_eq!(NOTIFIER.try_take(), Some(state_c));
+        });
+    }
+
     #[test]
     fn journal_garbage() {
         let mut mock: MockFlashBase<3, 2, 8> = MockFlashBase::new(None, false);
@@ -263,6 +905,38 @@ mod tests {
         });
     }
 
+    #[test]
+    fn journal_repair() {
+        let state = State::new(Status::Confirmed, Slot::S1, Slot::S0);
+        let mut mock: MockFlashBase<3, 2, 8> = MockFlashBase::new(None, false);
+        embassy_futures::block_on(async {
+            // Without a valid record, garbage is left for the first write to deal with.
+            mock.write(32, &[0xaa; 8]).await.unwrap();
+            let mut buffer = [0; 4];
+            let mut journal = FlashJournal::<_>::new(&mut mock, &mut buffer).await.unwrap();
+            assert!(matches!(journal.repair().await, Ok(0)));
+            journal.set(&state).await.unwrap();
+            assert_eq!(journal.cache.last_valid_state.as_ref().unwrap().address, 0);
+
+            // Garbage of an interrupted erase in the last page, after the latest record and an empty page.
+            journal.inner.write(40, &[0x55; 8]).await.unwrap();
+            assert!(matches!(journal.repair().await, Ok(1)));
+            assert!(journal.inner.as_bytes()[32..].iter().all(|b| *b == 0xff));
+            assert_eq!(journal.get(), Some(&state));
+            assert!(matches!(journal.repair().await, Ok(0)));
+        });
+
+        // Pages with an older valid record are kept.
+        let mut buffer = [0; 4];
+        let mut journal = embassy_futures::block_on(FlashJournal::<_>::new(&mut mock, &mut buffer)).unwrap();
+        for status in [Status::Attempting, Status::Failed, Status::Initial, Status::Confirmed] {
+            embassy_futures::block_on(journal.set(&State::new(status, Slot::S0, Slot::S1))).unwrap();
+        }
+        let before = journal.inner.as_bytes().to_vec();
+        assert!(matches!(embassy_futures::block_on(journal.repair()), Ok(0)));
+        assert_eq!(journal.inner.as_bytes(), before);
+    }
+
     #[test]
     fn journal_realistic() {
         // Use a realistic page count and size.
@@ -278,12 +952,253 @@ mod tests {
                         let state = State::new(status, slot_b, slot_a);
 
                         // Practice you re-init the journal every boot and application load.
-                        let mut journal = FlashJournal::new::<256>(&mut mock).await.unwrap();
-                        journal.set::<4>(&state).await.unwrap();
+                        let mut buffer = [0; 256];
+                        let mut journal = FlashJournal::new(&mut mock, &mut buffer).await.unwrap();
+                        journal.set(&state).await.unwrap();
                         assert_eq!(journal.get(), Some(&state));
                     }
                 }
             }
         });
     }
+
+    #[test]
+    fn compute_cache_matches_forward_scan() {
+        let valid = State::new(
+            Status::Confirmed,
+            Slot::try_from(1).unwrap(),
+            Slot::try_from(0).unwrap(),
+        );
+        let mut valid_bytes = [0u8; State::SIZE];
+        valid.to_bytes(&mut valid_bytes);
+
+        // Deterministic pseudo-random layouts of valid, empty and broken entries.
+        let mut seed = 0x1234_5678u32;
+        for _ in 0..1000 {
+            let mut mock: MockFlashBase<3, 2, 8> = MockFlashBase::new(None, false);
+            for chunk in mock.as_bytes_mut().chunks_exact_mut(State::SIZE) {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+                match (seed >> 16) % 4 {
+                    0 => chunk.copy_from_slice(&valid_bytes),
+                    1 => chunk.copy_from_slice(&[0xaa; State::SIZE]),
+                    _ => {} // Bias towards empty entries.
+                }
+            }
+
+            let expected = reference_cache(mock.as_bytes());
+            for block_size in [4, 8, 12, 16] {
+                let mut buffer = [0; 16];
+                let cache =
+                    embassy_futures::block_on(FlashJournal::<_>::compute_cache(&mut mock, &mut buffer[..block_size]))
+                        .unwrap();
+
+                let actual = (
+                    cache.last_valid_state.map(|state| state.address),
+                    cache.first_empty_slot,
+                );
+                assert_eq!(actual, expected, "block size {block_size}");
+            }
+        }
+    }
+
+    #[test]
+    fn compute_cache_stops_early() {
+        let mut flash = CountingFlash {
+            inner: MockFlashBase::<2, 2, 2048>::new(None, false),
+            bytes_read: 0,
+        };
+        embassy_futures::block_on(async {
+            let mut buffer = [0; 256];
+            let mut journal = FlashJournal::new(&mut flash, &mut buffer).await.unwrap();
+            let state = State::new(Status::Initial, Slot::try_from(1).unwrap(), Slot::try_from(0).unwrap());
+            journal.set(&state).await.unwrap();
+        });
+
+        // Only valid entry at the start of the first page, the entire range needs to be scanned.
+        flash.bytes_read = 0;
+        let mut buffer = [0; 256];
+        let journal = embassy_futures::block_on(FlashJournal::<_>::new(&mut flash, &mut buffer)).unwrap();
+        assert_eq!(journal.cache.first_empty_slot, Some(State::SIZE));
+        assert_eq!(flash.bytes_read, 2 * 4096);
+
+        // Valid entry in the last block, only that block needs to be read.
+        let capacity = flash.inner.as_bytes().len();
+        let last = flash.inner.as_bytes()[..State::SIZE].to_vec();
+        flash.inner.as_bytes_mut()[capacity - State::SIZE..].copy_from_slice(&last);
+        flash.bytes_read = 0;
+        let mut buffer = [0; 256];
+        let journal = embassy_futures::block_on(FlashJournal::<_>::new(&mut flash, &mut buffer)).unwrap();
+        assert_eq!(journal.cache.first_empty_slot, None);
+        assert_eq!(journal.cache.last_valid_state.unwrap().address, capacity - State::SIZE);
+        assert_eq!(flash.bytes_read, 256);
+    }
+
+    /// Record of which schema 1 replaced schema 0, storing its schema in the second byte.
+    #[derive(Debug, PartialEq, Clone, Copy)]
+    struct Versioned(u8);
+
+    impl Record for Versioned {
+        const SIZE: usize = 2;
+        const SCHEMA: u8 = 1;
+
+        fn try_from_bytes(data: &[u8]) -> Result<Self, ParseResult> {
+            match data {
+                [0xff, 0xff] => Err(ParseResult::Unset),
+                [value, 1] => Ok(Versioned(*value)),
+                [_, 0] => Err(ParseResult::Outdated),
+                _ => Err(ParseResult::Invalid),
+            }
+        }
+
+        fn to_bytes(&self, data: &mut [u8]) {
+            data.copy_from_slice(&[self.0, Self::SCHEMA]);
+        }
+
+        /// Schema 0 stored the value off by one.
+        fn migrate(data: &[u8]) -> Result<Self, ParseResult> {
+            match data {
+                [value, 0] => Ok(Versioned(value.wrapping_add(1))),
+                _ => Err(ParseResult::Invalid),
+            }
+        }
+    }
+
+    #[test]
+    fn journal_migrates_outdated() {
+        let mut mock: MockFlashBase<3, 2, 8> = MockFlashBase::new(None, false);
+        mock.as_bytes_mut()[..4].copy_from_slice(&[5, 0, 6, 0]);
+
+        embassy_futures::block_on(async {
+            // The latest record is migrated, and written back in the current schema after the outdated ones.
+            let mut buffer = [0; 4];
+            let journal = FlashJournal::<_, Versioned>::new(&mut mock, &mut buffer).await.unwrap();
+            assert_eq!(journal.get(), Some(&Versioned(7)));
+            assert!(!journal.cache.migrated);
+        });
+        assert_eq!(mock.as_bytes()[..8], [5, 0, 6, 0, 7, 1, 0xff, 0xff]);
+
+        embassy_futures::block_on(async {
+            // Once written back, nothing is migrated anymore.
+            let mut buffer = [0; 4];
+            let journal = FlashJournal::<_, Versioned>::new(&mut mock, &mut buffer).await.unwrap();
+            assert_eq!(journal.get(), Some(&Versioned(7)));
+            assert_eq!(journal.cache.first_empty_slot, Some(6));
+        });
+    }
+
+    #[test]
+    fn journal_migrates_two_byte_states() {
+        let legacy = |state: State| {
+            let data = state.as_bytes()[0];
+            [data, crate::state::CRC.checksum(&[data])]
+        };
+        let states = [
+            State::new(Status::Initial, Slot::S1, Slot::S0),
+            State::new(Status::Attempting, Slot::S1, Slot::S0),
+            State::new(Status::Confirmed, Slot::S1, Slot::S0),
+        ];
+
+        for count in [2, 3] {
+            // Written back to back by the bootloader before records had user bits.
+            let mut mock: MockFlashBase<3, 2, 8> = MockFlashBase::new(None, false);
+            for (i, state) in states[..count].iter().enumerate() {
+                mock.as_bytes_mut()[2 * i..2 * i + 2].copy_from_slice(&legacy(*state));
+            }
+            let latest = states[count - 1];
+            // Written back in the first slot after the records of the two-byte layout.
+            let address = (2 * count).next_multiple_of(State::SIZE);
+
+            embassy_futures::block_on(async {
+                let mut buffer = [0; 4];
+                let journal = FlashJournal::<_>::new(&mut mock, &mut buffer).await.unwrap();
+                assert_eq!(journal.get(), Some(&latest));
+                assert_eq!(journal.cache.last_valid_state.as_ref().unwrap().address, address);
+            });
+            assert_eq!(mock.as_bytes()[address..address + State::SIZE], latest.as_bytes());
+
+            embassy_futures::block_on(async {
+                // Once written back, nothing is migrated anymore, and the journal continues in the current layout.
+                let mut buffer = [0; 4];
+                let mut journal = FlashJournal::<_>::new(&mut mock, &mut buffer).await.unwrap();
+                assert!(!journal.cache.migrated);
+                assert_eq!(journal.get(), Some(&latest));
+
+                let state = State::new(Status::Initial, Slot::S0, Slot::S1);
+                journal.set(&state).await.unwrap();
+                assert_eq!(journal.get(), Some(&state));
+            });
+        }
+    }
+
+    #[test]
+    fn journal_user_bits() {
+        let mut mock: MockFlashBase<3, 2, 8> = MockFlashBase::new(None, false);
+        embassy_futures::block_on(async {
+            let mut buffer = [0; 4];
+            let mut journal = FlashJournal::new(&mut mock, &mut buffer).await.unwrap();
+            assert_eq!(journal.user_bits(), 0);
+            assert!(matches!(journal.set_user_bits(0b01).await, Err(Error::Empty)));
+
+            let state = State::new(Status::Initial, Slot::S1, Slot::S0);
+            journal.set(&state).await.unwrap();
+            journal.set_user_bits(0b01).await.unwrap();
+            assert_eq!(journal.user_bits(), 0b01);
+
+            // User bits ride along with status updates.
+            let state = journal.get().unwrap().with_status(Status::Confirmed);
+            journal.set(&state).await.unwrap();
+
+            let mut buffer = [0; 4];
+            let journal = FlashJournal::<_>::new(&mut mock, &mut buffer).await.unwrap();
+            assert_eq!(
+                journal.get(),
+                Some(&State::new(Status::Confirmed, Slot::S1, Slot::S0).with_user_bits(0b01))
+            );
+            assert_eq!(journal.user_bits(), 0b01);
+        });
+    }
+
+    #[test]
+    fn journal_reset_interrupted() {
+        let old = State::new(Status::Confirmed, Slot::S1, Slot::S0);
+        let default = State::new(Status::Initial, Slot::S0, Slot::S1);
+
+        // Histories with the latest record in the second respectively the first page.
+        for history_len in [5, 13] {
+            for shutoff in 0.. {
+                let mut mock: MockFlashBase<3, 2, 8> = MockFlashBase::new(None, false);
+                let result = embassy_futures::block_on(async {
+                    let mut buffer = [0; 4];
+                    let mut journal = FlashJournal::new(&mut mock, &mut buffer).await.unwrap();
+                    for i in 0..history_len {
+                        let state = if i == history_len - 1 {
+                            old
+                        } else {
+                            State::new(Status::Attempting, Slot::try_from(i % 2).unwrap(), Slot::S2)
+                        };
+                        journal.set(&state).await.unwrap();
+                    }
+
+                    journal.inner.bytes_until_shutoff = Some(shutoff);
+                    journal.reset(&default).await
+                });
+                mock.remove_shutoff();
+
+                let mut buffer = [0; 4];
+                let journal = embassy_futures::block_on(FlashJournal::<_>::new(&mut mock, &mut buffer)).unwrap();
+                if result.is_ok() {
+                    assert_eq!(journal.get(), Some(&default));
+                    assert_eq!(journal.cache.last_valid_state.unwrap().address, 0);
+                    assert_eq!(journal.cache.first_empty_slot, Some(State::SIZE));
+                    break;
+                }
+
+                let latest = journal.get();
+                assert!(
+                    latest == Some(&old) || latest == Some(&default),
+                    "history {history_len}, shutoff {shutoff}: {latest:?}"
+                );
+            }
+        }
+    }
 }
@@ -0,0 +1,197 @@
+use embassy_sync::blocking_mutex::raw::RawMutex;
+use embassy_sync::mutex::{Mutex, MutexGuard};
+use embassy_sync::watch::Watch;
+use embedded_storage_async::nor_flash::NorFlash;
+
+use super::{Error, FlashJournal};
+use crate::record::Record;
+use crate::state::{State, Status};
+
+/// [FlashJournal] shared between tasks, guarded by a mutex.
+///
+/// Every change made through this wrapper is broadcast to the receivers of [SharedFlashJournal::get_watch],
+/// of which there can be at most `WATCHERS`. Typically placed in a `static`, for example using a `StaticCell`.
+pub struct SharedFlashJournal<'a, M: RawMutex, T, R: Record + 'static = State, const WATCHERS: usize = 4> {
+    journal: Mutex<M, FlashJournal<'a, T, R>>,
+    watch: Watch<M, R, WATCHERS>,
+}
+
+impl<'a, M: RawMutex, T: NorFlash, R: Record, const WATCHERS: usize> SharedFlashJournal<'a, M, T, R, WATCHERS> {
+    /// Share `journal`, of which the latest [Record] is immediately available to watchers.
+    pub fn new(journal: FlashJournal<'a, T, R>) -> Self {
+        let watch = match journal.get() {
+            Some(record) => Watch::new_with(*record),
+            None => Watch::new(),
+        };
+
+        Self {
+            journal: Mutex::new(journal),
+            watch,
+        }
+    }
+
+    /// Get the latest [Record] contained in the journal, if any.
+    pub async fn get(&self) -> Option<R> {
+        self.journal.lock().await.get().copied()
+    }
+
+    /// Synchronize the latest [Record] to the journal, see [FlashJournal::set].
+    pub async fn set(&self, record: &R) -> Result<(), Error<T::Error>> {
+        let mut journal = self.journal.lock().await;
+        journal.set(record).await?;
+        self.publish(&journal);
+        Ok(())
+    }
+
+    /// Replace the latest [Record] by the one `f` derives from it, without other tasks changing it in between.
+    ///
+    /// Nothing is written when `f` yields [None].
+    pub async fn update(&self, f: impl FnOnce(Option<&R>) -> Option<R>) -> Result<(), Error<T::Error>> {
+        let mut journal = self.journal.lock().await;
+        let Some(record) = f(journal.get()) else {
+            return Ok(());
+        };
+
+        journal.set(&record).await?;
+        self.publish(&journal);
+        Ok(())
+    }
+
+    /// Reset the journal to only contain `default_record`, see [FlashJournal::reset].
+    pub async fn reset(&self, default_record: &R) -> Result<(), Error<T::Error>> {
+        let mut journal = self.journal.lock().await;
+        journal.reset(default_record).await?;
+        self.publish(&journal);
+        Ok(())
+    }
+
+    /// Exclusive access to the journal itself.
+    ///
+    /// Changes made through the guard are not broadcast to watchers.
+    pub async fn lock(&self) -> MutexGuard<'_, M, FlashJournal<'a, T, R>> {
+        self.journal.lock().await
+    }
+
+    /// Watch carrying the latest [Record], for tasks to be woken when it changes.
+    ///
+    /// Receivers only see a change when the [Record] actually differs, as setting an identical one is not a change.
+    pub fn get_watch(&self) -> &Watch<M, R, WATCHERS> {
+        &self.watch
+    }
+
+    /// Broadcast the latest [Record] of `journal` if it changed.
+    fn publish(&self, journal: &FlashJournal<'a, T, R>) {
+        if let Some(record) = journal.get() {
+            self.watch.sender().send_if_modified(|current| {
+                if current.as_ref() == Some(record) {
+                    false
+                } else {
+                    *current = Some(*record);
+                    true
+                }
+            });
+        }
+    }
+}
+
+impl<M: RawMutex, T: NorFlash, const WATCHERS: usize> SharedFlashJournal<'_, M, T, State, WATCHERS> {
+    /// Get the user bits of the latest [State], or zero if the journal is empty, see [FlashJournal::user_bits].
+    pub async fn user_bits(&self) -> u8 {
+        self.journal.lock().await.user_bits()
+    }
+
+    /// Confirm the image booted into, if the latest [State] is [Status::Attempting].
+    ///
+    /// Yields whether the [State] was changed. Without confirmation, the bootloader boots the backup on the next boot.
+    pub async fn confirm(&self) -> Result<bool, Error<T::Error>> {
+        let mut confirmed = false;
+        self.update(|state| {
+            let state = state.filter(|state| state.status() == Status::Attempting)?;
+            confirmed = true;
+            Some(state.with_status(Status::Confirmed))
+        })
+        .await?;
+        Ok(confirmed)
+    }
+
+    /// Store new user bits alongside the latest [State], see [FlashJournal::set_user_bits].
+    pub async fn set_user_bits(&self, user_bits: u8) -> Result<(), Error<T::Error>> {
+        let mut journal = self.journal.lock().await;
+        journal.set_user_bits(user_bits).await?;
+        self.publish(&journal);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+
+    use super::*;
+    use crate::flash::mock::MockFlashBase;
+    use crate::state::{Slot, Status};
+
+    type Mock = MockFlashBase<3, 2, 8>;
+
+    fn state(status: Status) -> State {
+        State::new(status, Slot::try_from(1).unwrap(), Slot::try_from(0).unwrap())
+    }
+
+    #[test]
+    fn shared_set_and_watch() {
+        let mut mock = Mock::new(None, false);
+        embassy_futures::block_on(async {
+            let mut buffer = [0; 4];
+            let journal = FlashJournal::new(&mut mock, &mut buffer).await.unwrap();
+            let shared: SharedFlashJournal<'_, CriticalSectionRawMutex, _> = SharedFlashJournal::new(journal);
+            let mut receiver = shared.get_watch().receiver().unwrap();
+            assert_eq!(receiver.try_get(), None);
+
+            shared.set(&state(Status::Initial)).await.unwrap();
+            assert_eq!(shared.get().await, Some(state(Status::Initial)));
+            assert_eq!(receiver.try_changed(), Some(state(Status::Initial)));
+
+            // Setting an identical state is not a change.
+            shared.set(&state(Status::Initial)).await.unwrap();
+            assert_eq!(receiver.try_changed(), None);
+
+            shared.set_user_bits(0x05).await.unwrap();
+            assert_eq!(shared.user_bits().await, 0x05);
+            assert_eq!(
+                receiver.try_changed(),
+                Some(state(Status::Initial).with_user_bits(0x05))
+            );
+        });
+    }
+
+    #[test]
+    fn shared_update() {
+        let mut mock = Mock::new(None, false);
+        embassy_futures::block_on(async {
+            let mut buffer = [0; 4];
+            let mut journal = FlashJournal::new(&mut mock, &mut buffer).await.unwrap();
+            journal.set(&state(Status::Attempting)).await.unwrap();
+
+            let shared: SharedFlashJournal<'_, CriticalSectionRawMutex, _> = SharedFlashJournal::new(journal);
+            let mut receiver = shared.get_watch().receiver().unwrap();
+            // The state already in the journal is available right away.
+            assert_eq!(receiver.try_get(), Some(state(Status::Attempting)));
+
+            // Declining to update writes nothing.
+            shared.update(|_| None).await.unwrap();
+            assert_eq!(receiver.try_changed(), None);
+
+            shared
+                .update(|current| current.map(|state| state.with_status(Status::Confirmed)))
+                .await
+                .unwrap();
+            assert_eq!(shared.get().await, Some(state(Status::Confirmed)));
+            assert_eq!(receiver.try_changed(), Some(state(Status::Confirmed)));
+
+            // Changes through the guard are not broadcast.
+            shared.lock().await.set(&state(Status::Failed)).await.unwrap();
+            assert_eq!(shared.get().await, Some(state(Status::Failed)));
+            assert_eq!(receiver.try_changed(), None);
+        });
+    }
+}
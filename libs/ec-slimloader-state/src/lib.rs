@@ -1,9 +1,19 @@
-//! Journal for the EC Slimloader containing [state::State].
+//! Journal for the EC Slimloader containing [state::State] and other [record::Record]s.
 #![cfg_attr(not(feature = "_test"), no_std)]
 
 #[cfg(test)]
 #[macro_use]
 extern crate std;
 
+pub mod auth;
+pub mod boot_info;
+#[cfg(feature = "confirm-timeout")]
+pub mod confirm;
+pub mod counters;
+pub mod error_log;
 pub mod flash;
+pub mod record;
+pub mod settings;
+pub mod shadow;
 pub mod state;
+pub mod update;
@@ -0,0 +1,67 @@
+//! Generic entries that can be stored in a [crate::flash::FlashJournal].
+
+use crate::auth::AuthCache;
+use crate::counters::Counters;
+use crate::state::{ParseResult, State};
+use crate::update::BootloaderUpdate;
+
+/// Maximum value of [Record::SIZE] supported by [crate::flash::FlashJournal].
+pub const MAX_RECORD_SIZE: usize = 32;
+
+// Every record of this crate fits the buffers of the journal and of its shadow.
+const _: () = {
+    assert!(State::SIZE <= MAX_RECORD_SIZE);
+    assert!(AuthCache::SIZE <= MAX_RECORD_SIZE);
+    assert!(Counters::SIZE <= MAX_RECORD_SIZE);
+    assert!(BootloaderUpdate::SIZE <= MAX_RECORD_SIZE);
+};
+
+/// A fixed-size entry that can be stored in a [crate::flash::FlashJournal].
+///
+/// Care must be taken that a record consisting of only `0xff` bytes is never valid and parses as [ParseResult::Unset],
+/// as that is the typical value used by an empty NOR flash cell.
+pub trait Record: Copy + PartialEq {
+    /// Number of bytes a single record occupies in storage, at most [MAX_RECORD_SIZE].
+    const SIZE: usize;
+
+    /// Version of the layout of the records written, for records that store it.
+    ///
+    /// Records of an older version parse as [ParseResult::Outdated], and are converted using [Record::migrate].
+    const SCHEMA: u8 = 0;
+
+    /// Parse a record from exactly [Record::SIZE] bytes.
+    fn try_from_bytes(data: &[u8]) -> Result<Self, ParseResult>;
+
+    /// Serialize a record into exactly [Record::SIZE] bytes.
+    fn to_bytes(&self, data: &mut [u8]);
+
+    /// Convert a record of an older [Record::SCHEMA] from exactly [Record::SIZE] bytes.
+    ///
+    /// Called for records that parse as [ParseResult::Outdated]. Refuses by default, treating them as invalid.
+    fn migrate(_data: &[u8]) -> Result<Self, ParseResult> {
+        Err(ParseResult::Invalid)
+    }
+}
+
+/// Every slot in the contents of a journal of `R`, with its address and the record parsed from it.
+///
+/// Slots are yielded in the order they are written, the erased ones included as [ParseResult::Unset]. Allows host
+/// tools to display the history of a journal read from a device, like [crate::flash::FlashJournal::iter_records].
+/// Assumes the journal is stored with a write size dividing [Record::SIZE], see [parse_padded_records] otherwise.
+pub fn parse_records<R: Record>(journal: &[u8]) -> impl Iterator<Item = (usize, Result<R, ParseResult>)> + '_ {
+    parse_padded_records(journal, 1)
+}
+
+/// Every slot in the contents of a journal of `R` stored with `write_size`, like [parse_records].
+///
+/// The journal pads records to a whole number of writes, hence slots are [Record::SIZE] rounded up to `write_size`.
+pub fn parse_padded_records<R: Record>(
+    journal: &[u8],
+    write_size: usize,
+) -> impl Iterator<Item = (usize, Result<R, ParseResult>)> + '_ {
+    let slot_size = R::SIZE.next_multiple_of(write_size);
+    journal
+        .chunks_exact(slot_size)
+        .enumerate()
+        .map(move |(i, chunk)| (i * slot_size, R::try_from_bytes(&chunk[..R::SIZE])))
+}
@@ -0,0 +1,157 @@
+//! Record of a few boot-related settings, such as whether the console is enabled, that survive updates.
+//!
+//! The settings are an opaque blob of `N` bytes, of which the layout is defined by the product. They are stored in a
+//! [crate::flash::FlashJournal] of their own, typically in a small partition next to the state journal, and are written
+//! by the application using [FlashJournal::set_settings]. A write interrupted by a power failure leaves the previous
+//! settings in place, like for any other [Record].
+use embedded_storage_async::nor_flash::NorFlash;
+
+use crate::flash::{Error, FlashJournal};
+use crate::record::{Record, MAX_RECORD_SIZE};
+use crate::state::{ParseResult, CRC};
+
+/// Number of bytes stored alongside the blob: the revision and a CRC.
+const OVERHEAD: usize = 4 + 1;
+
+/// Maximum size of the blob of [Settings], such that they fit in a [Record].
+pub const MAX_SETTINGS_SIZE: usize = MAX_RECORD_SIZE - OVERHEAD;
+
+/// Blob of `N` bytes of settings and its revision, as stored in its own journal.
+///
+/// Layout: the blob, the revision as little-endian u32, and a CRC over the preceding bytes.
+/// A record of only `0xff` bytes is never valid, which is why the last revision is never used.
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Settings<const N: usize> {
+    revision: u32,
+    data: [u8; N],
+}
+
+impl<const N: usize> Settings<N> {
+    pub fn new(revision: u32, data: [u8; N]) -> Self {
+        const { assert!(N <= MAX_SETTINGS_SIZE, "settings do not fit a record") };
+        Self { revision, data }
+    }
+
+    /// Number of times the settings were changed, which only increases.
+    pub fn revision(&self) -> u32 {
+        self.revision
+    }
+
+    /// Blob of settings, of which the layout is defined by the product.
+    pub fn data(&self) -> &[u8; N] {
+        &self.data
+    }
+
+    /// Settings holding `data` at the next revision, or [None] if the revision is exhausted.
+    pub fn with_data(&self, data: [u8; N]) -> Option<Self> {
+        let revision = self.revision.checked_add(1).filter(|&revision| revision != u32::MAX)?;
+        Some(Self::new(revision, data))
+    }
+}
+
+impl<const N: usize> Record for Settings<N> {
+    const SIZE: usize = N + OVERHEAD;
+
+    fn try_from_bytes(data: &[u8]) -> Result<Self, ParseResult> {
+        if data.len() != Self::SIZE {
+            return Err(ParseResult::Invalid);
+        }
+
+        if data.iter().all(|&b| b == 0xff) {
+            return Err(ParseResult::Unset);
+        }
+
+        let (content, crc) = data.split_at(N + 4);
+        if crc[0] != CRC.checksum(content) {
+            return Err(ParseResult::Invalid);
+        }
+
+        let revision = u32::from_le_bytes(content[N..].try_into().unwrap());
+        Ok(Self::new(revision, content[..N].try_into().unwrap()))
+    }
+
+    fn to_bytes(&self, data: &mut [u8]) {
+        data[..N].copy_from_slice(&self.data);
+        data[N..N + 4].copy_from_slice(&self.revision.to_le_bytes());
+        data[N + 4] = CRC.checksum(&data[..N + 4]);
+    }
+}
+
+impl<T: NorFlash, const N: usize> FlashJournal<'_, T, Settings<N>> {
+    /// Get the blob of the latest [Settings], if any were written.
+    pub fn settings(&self) -> Option<&[u8; N]> {
+        self.get().map(Settings::data)
+    }
+
+    /// Store `data` as the latest [Settings], at the revision following the current one.
+    ///
+    /// Writing the same blob again does not wear the storage. Yields [Error::Exhausted] once the revision can not be
+    /// increased anymore, which takes billions of changes.
+    pub async fn set_settings(&mut self, data: [u8; N]) -> Result<(), Error<T::Error>> {
+        let settings = match self.get() {
+            Some(settings) if settings.data() == &data => return Ok(()),
+            Some(settings) => settings.with_data(data).ok_or(Error::Exhausted)?,
+            None => Settings::new(0, data),
+        };
+        self.set(&settings).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flash::mock::MockFlashBase;
+
+    #[test]
+    fn settings_validity() {
+        let settings = Settings::new(3, [1, 2, 3, 4]);
+        let mut data = [0; Settings::<4>::SIZE];
+        settings.to_bytes(&mut data);
+        assert_eq!(data[..8], [1, 2, 3, 4, 3, 0, 0, 0]);
+        assert_eq!(Settings::try_from_bytes(&data), Ok(settings));
+
+        // Corrupted records are rejected.
+        let mut corrupted = data;
+        corrupted[0] ^= 1;
+        assert!(matches!(
+            Settings::<4>::try_from_bytes(&corrupted),
+            Err(ParseResult::Invalid)
+        ));
+        assert!(matches!(
+            Settings::<4>::try_from_bytes(&data[..8]),
+            Err(ParseResult::Invalid)
+        ));
+
+        assert!(matches!(
+            Settings::<4>::try_from_bytes(&[0xff; Settings::<4>::SIZE]),
+            Err(ParseResult::Unset)
+        ));
+        assert_eq!(
+            Settings::new(u32::MAX - 2, [0]).with_data([1]).map(|s| s.revision()),
+            Some(u32::MAX - 1)
+        );
+        assert_eq!(Settings::new(u32::MAX - 1, [0]).with_data([1]), None);
+    }
+
+    #[test]
+    fn settings_journal() {
+        let mut mock: MockFlashBase<2, 2, 32> = MockFlashBase::new(None, false);
+        embassy_futures::block_on(async {
+            let mut buffer = [0; 32];
+            let mut journal: FlashJournal<_, Settings<MAX_SETTINGS_SIZE>> =
+                FlashJournal::new(&mut mock, &mut buffer).await.unwrap();
+            assert!(journal.settings().is_none());
+
+            for i in 0..8u8 {
+                journal.set_settings([i; MAX_SETTINGS_SIZE]).await.unwrap();
+                assert_eq!(journal.settings(), Some(&[i; MAX_SETTINGS_SIZE]));
+                assert_eq!(journal.get().map(Settings::revision), Some(i as u32));
+            }
+
+            // Unchanged settings keep their revision.
+            journal.set_settings([7; MAX_SETTINGS_SIZE]).await.unwrap();
+            assert_eq!(journal.get().map(Settings::revision), Some(7));
+        });
+    }
+}
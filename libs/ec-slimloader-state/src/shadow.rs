@@ -0,0 +1,195 @@
+//! Shadow of the latest [Record] in a [FlashJournal](crate::flash::FlashJournal), kept in RAM that is retained across warm resets.
+//!
+//! Constructing the journal from a valid shadow using [FlashJournal::with_shadow](crate::flash::FlashJournal::with_shadow) avoids scanning the storage.
+//! Anything may have changed the journal without updating the shadow, such as the application. The shadow is thus
+//! only trusted when the storage still contains its record at its address, and the slot the next record would be
+//! written to is still empty. Otherwise the journal is scanned as usual.
+use crate::record::{Record, MAX_RECORD_SIZE};
+
+/// Marks a shadow as written by [Shadow::new], rather than left over in uninitialized RAM.
+const MAGIC: u32 = 0x5744_4853;
+
+const CRC: crc::Crc<u32> = crc::Crc::<u32>::new(&crc::CRC_32_ISCSI);
+
+/// Latest [Record] of a [FlashJournal](crate::flash::FlashJournal) with its address, and the address of the slot for the next record.
+///
+/// Typically placed in RAM that is neither initialized at startup nor cleared by a warm reset, for example the
+/// `.uninit` section of `cortex-m-rt`. As any contents are valid, it can be read before it was ever written.
+#[derive(Clone, Copy, PartialEq)]
+#[repr(C)]
+pub struct Shadow {
+    magic: u32,
+    address: u32,
+    first_empty_slot: u32,
+    /// [Record::SIZE] of the record, such that a shadow of a different kind of record is never trusted.
+    size: u32,
+    record: [u8; MAX_RECORD_SIZE],
+    crc: u32,
+}
+
+impl Shadow {
+    /// Shadow that is never trusted, for example to initialize a `static`.
+    pub const INVALID: Shadow = Shadow {
+        magic: 0,
+        address: 0,
+        first_empty_slot: 0,
+        size: 0,
+        record: [0; MAX_RECORD_SIZE],
+        crc: 0,
+    };
+
+    pub(crate) fn new<R: Record>(record: &R, address: usize, first_empty_slot: usize) -> Self {
+        let mut shadow = Shadow {
+            magic: MAGIC,
+            address: address as u32,
+            first_empty_slot: first_empty_slot as u32,
+            size: R::SIZE as u32,
+            record: [0xff; MAX_RECORD_SIZE],
+            crc: 0,
+        };
+        record.to_bytes(&mut shadow.record[..R::SIZE]);
+        shadow.crc = shadow.checksum();
+        shadow
+    }
+
+    fn checksum(&self) -> u32 {
+        let mut digest = CRC.digest();
+        digest.update(&self.magic.to_le_bytes());
+        digest.update(&self.address.to_le_bytes());
+        digest.update(&self.first_empty_slot.to_le_bytes());
+        digest.update(&self.size.to_le_bytes());
+        digest.update(&self.record);
+        digest.finalize()
+    }
+
+    /// Whether this shadow was written as a whole, which does not imply that it still matches the journal.
+    pub fn is_valid(&self) -> bool {
+        self.magic == MAGIC && self.crc == self.checksum()
+    }
+
+    /// Make sure this shadow is never trusted, for example before handing over to code that changes the journal.
+    pub fn invalidate(&mut self) {
+        *self = Self::INVALID;
+    }
+
+    /// Bytes of the record and its address, and the address of the next slot, if this is a valid shadow of an `R`.
+    pub(crate) fn get<R: Record>(&self) -> Option<(&[u8], usize, usize)> {
+        if !self.is_valid() || self.size as usize != R::SIZE || R::SIZE > MAX_RECORD_SIZE {
+            return None;
+        }
+
+        Some((
+            &self.record[..R::SIZE],
+            self.address as usize,
+            self.first_empty_slot as usize,
+        ))
+    }
+}
+
+impl core::fmt::Debug for Shadow {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Shadow")
+            .field("valid", &self.is_valid())
+            .field("address", &self.address)
+            .field("first_empty_slot", &self.first_empty_slot)
+            .finish()
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for Shadow {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "Shadow {{ valid: {}, address: {=u32:#x}, first_empty_slot: {=u32:#x} }}",
+            self.is_valid(),
+            self.address,
+            self.first_empty_slot
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flash::mock::MockFlashBase;
+    use crate::flash::FlashJournal;
+    use crate::state::{Slot, State, Status};
+
+    type Mock = MockFlashBase<2, 4, 4>;
+
+    fn state(status: Status) -> State {
+        State::new(status, Slot::S1, Slot::S0)
+    }
+
+    #[test]
+    fn shadow_validity() {
+        let mut shadow = Shadow::new(&state(Status::Initial), 4, 8);
+        assert!(shadow.is_valid());
+        assert!(shadow.get::<State>().is_some());
+        assert!(shadow.get::<crate::auth::AuthCache>().is_none());
+
+        shadow.address = 8;
+        assert!(!shadow.is_valid());
+
+        shadow.invalidate();
+        assert!(!shadow.is_valid());
+        assert!(!Shadow::INVALID.is_valid());
+    }
+
+    #[test]
+    fn shadow_skips_scan_only_when_matching() {
+        let mut mock = Mock::new(None, false);
+        embassy_futures::block_on(async {
+            let mut buffer = [0; 4];
+            let mut journal = FlashJournal::new(&mut mock, &mut buffer).await.unwrap();
+            assert!(journal.shadow().is_none());
+            journal.set(&state(Status::Initial)).await.unwrap();
+            journal.set(&state(Status::Attempting)).await.unwrap();
+            let shadow = journal.shadow().unwrap();
+            assert!(shadow.is_valid());
+
+            // A matching shadow yields the same journal.
+            let mut buffer = [0; 4];
+            let journal = FlashJournal::<_, State>::with_shadow(&mut mock, &mut buffer, &shadow)
+                .await
+                .unwrap();
+            assert_eq!(journal.get(), Some(&state(Status::Attempting)));
+            assert_eq!(journal.shadow(), Some(shadow));
+
+            // After a change that did not update the shadow, the journal is scanned instead.
+            let mut buffer = [0; 4];
+            let mut journal = FlashJournal::<_, State>::new(&mut mock, &mut buffer).await.unwrap();
+            journal.set(&state(Status::Confirmed)).await.unwrap();
+            let mut buffer = [0; 4];
+            let journal = FlashJournal::<_, State>::with_shadow(&mut mock, &mut buffer, &shadow)
+                .await
+                .unwrap();
+            assert_eq!(journal.get(), Some(&state(Status::Confirmed)));
+
+            // As it is for an invalid shadow.
+            let mut buffer = [0; 4];
+            let journal = FlashJournal::<_, State>::with_shadow(&mut mock, &mut buffer, &Shadow::INVALID)
+                .await
+                .unwrap();
+            assert_eq!(journal.get(), Some(&state(Status::Confirmed)));
+        });
+    }
+
+    #[test]
+    fn shadow_of_full_journal() {
+        let mut mock = Mock::new(None, false);
+        embassy_futures::block_on(async {
+            let mut buffer = [0; 4];
+            let mut journal = FlashJournal::new(&mut mock, &mut buffer).await.unwrap();
+            for _ in 0..4 {
+                journal.set(&state(Status::Initial)).await.unwrap();
+                journal.set(&state(Status::Attempting)).await.unwrap();
+            }
+
+            // Every slot is written, so the next record rotates the pages and the journal has to be scanned.
+            assert!(journal.get().is_some());
+            assert!(journal.shadow().is_none());
+        });
+    }
+}
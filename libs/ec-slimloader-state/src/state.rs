@@ -1,8 +1,30 @@
+use core::cmp::Ordering;
+
 use num_enum::{IntoPrimitive, TryFromPrimitive};
 
+use crate::record::Record;
+
 pub const MAX_SLOT_COUNT: usize = 0b111;
 
-const CRC: crc::Crc<u8> = crc::Crc::<u8>::new(&crc::CRC_8_OPENSAFETY);
+pub(crate) const CRC: crc::Crc<u8> = crc::Crc::<u8>::new(&crc::CRC_8_OPENSAFETY);
+
+/// Low bits of the third byte of a [State], holding a target [Slot] that is never stored.
+///
+/// Records of the two-byte layout preceding the current one were written back to back, such that a slot of four
+/// bytes holds two of them. As none of those has this target, they are never mistaken for a record of the current
+/// layout, see [State::try_legacy].
+const LAYOUT_MARKER: u8 = 0b0111;
+
+/// Bit of the third byte of a [State] that is reserved and must be zero.
+const RESERVED: u8 = 0b1000;
+
+/// Shift of the [Record::SCHEMA] in the high nibble of the third byte of a [State].
+const SCHEMA_SHIFT: u32 = 4;
+
+const _: () = assert!(
+    <State as Record>::SCHEMA < 1 << (8 - SCHEMA_SHIFT),
+    "schema version does not fit in its nibble"
+);
 
 /// Image slot ID.
 ///
@@ -63,13 +85,15 @@ impl arbitrary::Arbitrary<'_> for Slot {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum ParseResult {
     /// Nor flash entry yet to be written.
     Unset,
     /// State is an invalid value.
     Invalid,
+    /// Record is valid, but of an older [Record::SCHEMA] that has to be migrated using [Record::migrate].
+    Outdated,
 }
 
 /// Boot process status as stored in [State] as a 2-bit field.
@@ -95,12 +119,15 @@ pub enum Status {
 
 /// State record as stored in the State boot journal.
 ///
-/// Care must be taken that `0xffff` is an invalid value,
+/// Layout: status and slots, user bits, the schema version in the high nibble of a byte of which the low nibble holds
+/// [LAYOUT_MARKER], and a CRC over the preceding bytes.
+///
+/// Care must be taken that `0xffffffff` is an invalid value,
 /// as that is the typical value used by an empty NOR flash cell.
 ///
 /// We ensure this by disallowing Slot value 0b111.
 #[derive(PartialEq, Clone, Copy)]
-pub struct State([u8; 2]);
+pub struct State([u8; 4]);
 
 #[cfg(feature = "_test")]
 impl arbitrary::Arbitrary<'_> for State {
@@ -108,44 +135,84 @@ impl arbitrary::Arbitrary<'_> for State {
         let status = Status::arbitrary(u)?;
         let slot_a = Slot::arbitrary(u)?;
         let slot_b = Slot::arbitrary(u)?;
-        Ok(State::new(status, slot_a, slot_b))
+        let user_bits = u8::arbitrary(u)?;
+        Ok(State::new(status, slot_a, slot_b).with_user_bits(user_bits))
     }
 }
 
 impl State {
     pub const fn new(status: Status, target: Slot, backup: Slot) -> Self {
+        Self::with_fields(status, target, backup, 0)
+    }
+
+    const fn with_fields(status: Status, target: Slot, backup: Slot, user_bits: u8) -> Self {
         let mut data = 0u8;
         data |= (status as u8) << 6;
         data |= (backup as u8) << 3;
         data |= target as u8;
 
-        let crc = CRC.checksum(&[data]);
+        Self::from_raw(data, user_bits)
+    }
+
+    /// Store the packed status and slots in `data` along with `user_bits`, in the current layout.
+    const fn from_raw(data: u8, user_bits: u8) -> Self {
+        let layout = (<Self as Record>::SCHEMA << SCHEMA_SHIFT) | LAYOUT_MARKER;
+        let crc = CRC.checksum(&[data, user_bits, layout]);
 
-        Self([data, crc])
+        Self([data, user_bits, layout, crc])
     }
 
-    pub fn try_new(data: [u8; 2]) -> Result<Self, ParseResult> {
-        if data == [0xff, 0xff] {
+    pub fn try_new(data: [u8; 4]) -> Result<Self, ParseResult> {
+        if data == [0xff; 4] {
             return Err(ParseResult::Unset);
         }
 
+        if data[2] & (LAYOUT_MARKER | RESERVED) != LAYOUT_MARKER {
+            return match Self::try_legacy(data) {
+                Some(_) => Err(ParseResult::Outdated),
+                None => Err(ParseResult::Invalid),
+            };
+        }
+
         if Self::try_target(data[0]).is_none() || Self::try_backup(data[0]).is_none() {
             return Err(ParseResult::Invalid);
         }
 
-        if !Self::check_crc(data[1], data[0]) {
+        if !Self::check_crc(data[3], &data[..3]) {
             return Err(ParseResult::Invalid);
         }
 
-        Ok(State(data))
+        match (data[2] >> SCHEMA_SHIFT).cmp(&<Self as Record>::SCHEMA) {
+            Ordering::Equal => Ok(State(data)),
+            Ordering::Less => Err(ParseResult::Outdated),
+            // Written by a newer version, which may have changed the meaning of any field.
+            Ordering::Greater => Err(ParseResult::Invalid),
+        }
+    }
+
+    /// Latest state of a slot holding records of the two-byte layout: the status and slots, and a CRC over them.
+    ///
+    /// These were written back to back, hence the second record in the slot is the latest, unless it is erased or was
+    /// torn by a power failure. The user bits did not exist yet, and are zero.
+    fn try_legacy(data: [u8; 4]) -> Option<Self> {
+        if data[2] & (LAYOUT_MARKER | RESERVED) == LAYOUT_MARKER {
+            return None;
+        }
+
+        let [first, second] = [[data[0], data[1]], [data[2], data[3]]].map(|[data, crc]| {
+            let valid =
+                Self::try_target(data).is_some() && Self::try_backup(data).is_some() && Self::check_crc(crc, &[data]);
+            valid.then(|| Self::from_raw(data, 0))
+        });
+        second.or(first)
     }
 
-    pub fn as_bytes(&self) -> [u8; 2] {
+    pub fn as_bytes(&self) -> [u8; 4] {
         self.0
     }
 
-    fn check_crc(crc: u8, data: u8) -> bool {
-        crc == CRC.checksum(&[data])
+    fn check_crc(crc: u8, data: &[u8]) -> bool {
+        crc == CRC.checksum(data)
     }
 
     pub fn status(&self) -> Status {
@@ -153,8 +220,9 @@ impl State {
         unsafe { Status::try_from_primitive(self.0[0] >> 6).unwrap_unchecked() }
     }
 
+    /// Change the status, retaining the slots and user bits.
     pub fn with_status(&self, status: Status) -> Self {
-        Self::new(status, self.target(), self.backup())
+        Self::with_fields(status, self.target(), self.backup(), self.user_bits())
     }
 
     fn try_target(val: u8) -> Option<Slot> {
@@ -174,6 +242,41 @@ impl State {
         // If Self exists, Slot must be valid.
         unsafe { State::try_backup(self.0[0]).unwrap_unchecked() }
     }
+
+    /// Application defined bits, passed between the application and the bootloader.
+    ///
+    /// Their meaning is up to the product, for example a request to enter diagnostics or to clear user data.
+    /// The bootloader retains them when changing the status.
+    pub fn user_bits(&self) -> u8 {
+        self.0[1]
+    }
+
+    /// Change the user bits, retaining the status and slots.
+    pub fn with_user_bits(&self, user_bits: u8) -> Self {
+        Self::with_fields(self.status(), self.target(), self.backup(), user_bits)
+    }
+}
+
+impl Record for State {
+    const SIZE: usize = 4;
+
+    /// Version 0 is the two-byte layout without user bits, which does not store its version but is recognized by its
+    /// lack of [LAYOUT_MARKER], see [State::try_legacy].
+    const SCHEMA: u8 = 1;
+
+    fn try_from_bytes(data: &[u8]) -> Result<Self, ParseResult> {
+        State::try_new(data.try_into().map_err(|_| ParseResult::Invalid)?)
+    }
+
+    fn to_bytes(&self, data: &mut [u8]) {
+        data.copy_from_slice(&self.0);
+    }
+
+    /// Convert a slot of records of the two-byte layout, see [State::try_legacy].
+    fn migrate(data: &[u8]) -> Result<Self, ParseResult> {
+        let data = data.try_into().map_err(|_| ParseResult::Invalid)?;
+        Self::try_legacy(data).ok_or(ParseResult::Invalid)
+    }
 }
 
 impl core::fmt::Debug for State {
@@ -182,6 +285,7 @@ impl core::fmt::Debug for State {
             .field("status", &self.status())
             .field("target", &self.target())
             .field("backup", &self.backup())
+            .field("user_bits", &self.user_bits())
             .finish()
     }
 }
@@ -191,10 +295,11 @@ impl defmt::Format for State {
     fn format(&self, f: defmt::Formatter) {
         defmt::write!(
             f,
-            "State {{ status: {}, target: {}, backup: {} }}",
+            "State {{ status: {}, target: {}, backup: {}, user_bits: {=u8:#04x} }}",
             self.status(),
             self.target(),
-            self.backup()
+            self.backup(),
+            self.user_bits()
         )
     }
 }
@@ -242,12 +347,91 @@ mod tests {
         let slot_b = Slot::S2;
 
         let state = State::new(Status::Initial, slot_b, slot_a);
-        assert_eq!(state.0[1], 12); // Crc
+        assert_eq!(state.0[3], 51); // Crc
         let state = State::new(Status::Attempting, slot_b, slot_a);
-        assert_eq!(state.0[1], 234); // Crc
+        assert_eq!(state.0[3], 194); // Crc
         let state = State::new(Status::Confirmed, slot_b, slot_a);
-        assert_eq!(state.0[1], 9); // Crc
+        assert_eq!(state.0[3], 15); // Crc
         let state = State::new(Status::Failed, slot_b, slot_a);
-        assert_eq!(state.0[1], 239); // Crc
+        assert_eq!(state.0[3], 254); // Crc
+    }
+
+    /// Check that user bits are retained and covered by the Crc.
+    #[test]
+    fn state_user_bits() {
+        let state = State::new(Status::Initial, Slot::S2, Slot::S1);
+        assert_eq!(state.user_bits(), 0);
+
+        let state = state.with_user_bits(0xa5);
+        assert_eq!(state.user_bits(), 0xa5);
+        assert_eq!(state.0[3], 66); // Crc
+        assert!(State::try_new(state.0).is_ok());
+
+        let state = state.with_status(Status::Confirmed);
+        assert_eq!(state.status(), Status::Confirmed);
+        assert_eq!(state.target(), Slot::S2);
+        assert_eq!(state.backup(), Slot::S1);
+        assert_eq!(state.user_bits(), 0xa5);
+
+        // Flipped user bits or a set reserved bit are rejected.
+        let mut data = state.0;
+        data[1] ^= 0x01;
+        assert!(matches!(State::try_new(data), Err(ParseResult::Invalid)));
+        let mut data = state.0;
+        data[2] |= RESERVED;
+        assert!(matches!(State::try_new(data), Err(ParseResult::Invalid)));
+    }
+
+    /// Check that records of a newer schema, or with the reserved bit set, are rejected.
+    #[test]
+    fn state_schema() {
+        let state = State::new(Status::Attempting, Slot::S0, Slot::S1);
+        assert_eq!(state.0[2], 0x10 | LAYOUT_MARKER);
+
+        for schema_byte in [0x27, 0xf7, 0x1f] {
+            let mut data = state.0;
+            data[2] = schema_byte;
+            data[3] = CRC.checksum(&data[..3]);
+            assert!(matches!(State::try_new(data), Err(ParseResult::Invalid)));
+        }
+
+        // Schema 0 never had this layout, hence there is nothing to migrate.
+        let mut data = state.0;
+        data[2] = LAYOUT_MARKER;
+        data[3] = CRC.checksum(&data[..3]);
+        assert!(matches!(State::try_new(data), Err(ParseResult::Outdated)));
+        assert!(matches!(State::migrate(&data), Err(ParseResult::Invalid)));
+    }
+
+    /// Check that slots holding records of the two-byte layout are never taken for the current layout, and that the
+    /// latest of them is migrated.
+    #[test]
+    fn state_legacy() {
+        let mut legacy = vec![];
+        for status in [Status::Initial, Status::Attempting, Status::Confirmed, Status::Failed] {
+            for i in 0b0..0b111u8 {
+                for j in 0b0..0b111u8 {
+                    let state = State::new(status, Slot::try_from(i).unwrap(), Slot::try_from(j).unwrap());
+                    legacy.push((state, [state.0[0], CRC.checksum(&[state.0[0]])]));
+                }
+            }
+        }
+
+        for &(first, [a, b]) in &legacy {
+            for &(second, [c, d]) in &legacy {
+                let data = [a, b, c, d];
+                assert!(matches!(State::try_new(data), Err(ParseResult::Outdated)));
+                assert_eq!(State::migrate(&data), Ok(second));
+            }
+
+            // The second record is erased, or was torn.
+            assert!(matches!(State::try_new([a, b, 0xff, 0xff]), Err(ParseResult::Outdated)));
+            assert_eq!(State::migrate(&[a, b, 0xff, 0xff]), Ok(first));
+            assert_eq!(State::migrate(&[a, b, 0x00, 0x12]), Ok(first));
+        }
+
+        // Records of the current layout are not migrated.
+        let state = State::new(Status::Confirmed, Slot::S0, Slot::S0);
+        assert!(matches!(State::migrate(&state.0), Err(ParseResult::Invalid)));
     }
 }
@@ -0,0 +1,154 @@
+//! Record used to request and track an on-device update of the bootloader itself.
+//!
+//! The application stages a signed bootloader in a spare [Slot] and stores [UpdatePhase::Staged]
+//! in a separate [crate::flash::FlashJournal]. The bootloader progresses the phase whilst installing.
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+
+use crate::record::Record;
+use crate::state::{ParseResult, Slot, CRC};
+
+/// Phase of a bootloader update as stored in [BootloaderUpdate] as a 2-bit field.
+#[derive(Debug, PartialEq, Clone, Copy, TryFromPrimitive, IntoPrimitive)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[cfg_attr(feature = "_test", derive(arbitrary::Arbitrary))]
+#[repr(u8)]
+pub enum UpdatePhase {
+    /// Application has staged a new bootloader image in the slot and requests it to be installed.
+    Staged = 3,
+    /// Bootloader has verified the staged image and started writing it to the inactive bootloader region.
+    ///
+    /// If the bootloader encounters this phase at startup, the installation was interrupted and is retried.
+    Installing = 2,
+    /// Bootloader has rejected the staged image, for example because it did not authenticate.
+    Rejected = 1,
+    /// Staged image has been written and committed, and will be booted by the ROM from now on.
+    Installed = 0,
+}
+
+/// Bootloader update record as stored in its own journal.
+///
+/// Care must be taken that `0xffff` is an invalid value, which we ensure by disallowing Slot value 0b111.
+#[derive(PartialEq, Clone, Copy)]
+pub struct BootloaderUpdate([u8; 2]);
+
+impl BootloaderUpdate {
+    pub const fn new(phase: UpdatePhase, slot: Slot) -> Self {
+        let mut data = 0u8;
+        data |= (phase as u8) << 6;
+        data |= slot as u8;
+
+        let crc = CRC.checksum(&[data]);
+
+        Self([data, crc])
+    }
+
+    pub fn try_new(data: [u8; 2]) -> Result<Self, ParseResult> {
+        if data == [0xff, 0xff] {
+            return Err(ParseResult::Unset);
+        }
+
+        if Slot::try_from(data[0] & 0b111).is_err() || data[0] & 0b0011_1000 != 0 {
+            return Err(ParseResult::Invalid);
+        }
+
+        if data[1] != CRC.checksum(&[data[0]]) {
+            return Err(ParseResult::Invalid);
+        }
+
+        Ok(BootloaderUpdate(data))
+    }
+
+    pub fn phase(&self) -> UpdatePhase {
+        // Note(unsafe): we are sure that any 2-bit u8 is a valid UpdatePhase.
+        unsafe { UpdatePhase::try_from_primitive(self.0[0] >> 6).unwrap_unchecked() }
+    }
+
+    pub fn with_phase(&self, phase: UpdatePhase) -> Self {
+        Self::new(phase, self.slot())
+    }
+
+    /// Slot containing the staged bootloader image.
+    pub fn slot(&self) -> Slot {
+        // If Self exists, Slot must be valid.
+        unsafe { Slot::try_from(self.0[0] & 0b111).unwrap_unchecked() }
+    }
+}
+
+impl Record for BootloaderUpdate {
+    const SIZE: usize = 2;
+
+    fn try_from_bytes(data: &[u8]) -> Result<Self, ParseResult> {
+        BootloaderUpdate::try_new(data.try_into().map_err(|_| ParseResult::Invalid)?)
+    }
+
+    fn to_bytes(&self, data: &mut [u8]) {
+        data.copy_from_slice(&self.0);
+    }
+}
+
+impl core::fmt::Debug for BootloaderUpdate {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("BootloaderUpdate")
+            .field("phase", &self.phase())
+            .field("slot", &self.slot())
+            .finish()
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for BootloaderUpdate {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "BootloaderUpdate {{ phase: {}, slot: {} }}",
+            self.phase(),
+            self.slot()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flash::mock::MockFlashBase;
+    use crate::flash::FlashJournal;
+
+    /// Construct all possible [BootloaderUpdate] values and test whether we can get fields back out again.
+    #[test]
+    fn update_validity_content() {
+        for phase in [
+            UpdatePhase::Staged,
+            UpdatePhase::Installing,
+            UpdatePhase::Rejected,
+            UpdatePhase::Installed,
+        ] {
+            for i in 0b0..0b111u8 {
+                let slot = Slot::try_from(i).unwrap();
+
+                let update = BootloaderUpdate::new(phase, slot);
+                assert_eq!(update.phase(), phase);
+                assert_eq!(update.slot(), slot);
+                assert!(BootloaderUpdate::try_new(update.0).is_ok());
+            }
+        }
+    }
+
+    /// Walk through the phases of an update using a journal.
+    #[test]
+    fn update_journal() {
+        let mut mock: MockFlashBase<2, 2, 8> = MockFlashBase::new(None, false);
+        embassy_futures::block_on(async {
+            let staged = BootloaderUpdate::new(UpdatePhase::Staged, Slot::S1);
+            let mut buffer = [0; 4];
+            let mut journal: FlashJournal<_, BootloaderUpdate> =
+                FlashJournal::new(&mut mock, &mut buffer).await.unwrap();
+            assert!(journal.get().is_none());
+
+            for phase in [UpdatePhase::Staged, UpdatePhase::Installing, UpdatePhase::Installed] {
+                let update = staged.with_phase(phase);
+                journal.set(&update).await.unwrap();
+                assert_eq!(journal.get(), Some(&update));
+            }
+        });
+    }
+}
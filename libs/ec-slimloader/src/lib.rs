@@ -1,21 +1,43 @@
 #![no_std]
 
-use defmt_or_log::{debug, error, info, unwrap, warn};
+#[cfg(test)]
+#[macro_use]
+extern crate std;
+
+#[cfg(all(feature = "minimal", any(feature = "defmt", feature = "log")))]
+compile_error!("The `minimal` feature strips all log messages, and can not be combined with `defmt` or `log`.");
+
+use defmt_or_log::{debug, error, info, warn};
+use ec_slimloader_state::boot_info::BootInfo;
+use ec_slimloader_state::counters::Event;
+use ec_slimloader_state::error_log::{ErrorKind, LogEntry};
 use ec_slimloader_state::flash::FlashJournal;
 use ec_slimloader_state::state::{Slot, State, Status};
 use embedded_storage_async::nor_flash::NorFlash;
 
+#[cfg(test)]
+mod model;
+pub mod policy;
+#[cfg(feature = "timing")]
+pub mod timing;
+
 /// A trait for application specific configurations.
 pub trait BootStatePolicy {
     /// Get the application specific default boot state.
     fn default_state() -> State {
-        State::new(Status::Initial, unwrap!(Slot::try_from(0)), unwrap!(Slot::try_from(0)))
+        State::new(Status::Initial, Slot::S0, Slot::S0)
     }
 
     /// Allows application specific validation of the boot state.
     fn is_valid_state(_state: &State) -> bool {
         true
     }
+
+    /// User bits of the state through which the application requests entering recovery on the next boot.
+    ///
+    /// When any of these bits is set, [start] clears them and calls [Board::recover] instead of booting,
+    /// such that the request only holds for a single boot. None by default.
+    const RECOVERY_USER_BITS: u8 = 0;
 }
 
 /// A board that can boot an application image.
@@ -28,10 +50,90 @@ pub trait Board {
     type Config: BootStatePolicy;
 
     /// Initialize the [Board], can only be called once.
-    async fn init<const JOURNAL_BUFFER_SIZE: usize>(config: Self::Config) -> Self;
+    ///
+    /// Only brings up what is needed to read the journal, such as the clocks and the flash containing it.
+    /// Bringing up access to the slots is deferred to [Board::prepare], such that slots that are never attempted
+    /// cost no time.
+    ///
+    /// The [FlashJournal] is constructed using `journal_buffer`, see [FlashJournal::new].
+    async fn init(config: Self::Config, journal_buffer: &'static mut [u8]) -> Self;
 
     /// Give a mutable reference to the [FlashJournal].
-    fn journal(&mut self) -> &mut FlashJournal<impl NorFlash>;
+    fn journal(&mut self) -> &mut FlashJournal<'static, impl NorFlash>;
+
+    /// Query whether the journal state should be overridden for this boot.
+    ///
+    /// Queried once at the start of [start], allowing for example a technician to hold a strap pin
+    /// to force booting the backup or a golden slot. Overrides never update the journal.
+    ///
+    /// Returns [None] by default.
+    fn boot_override(&mut self) -> Option<BootOverride> {
+        None
+    }
+
+    /// Unique identifier of the device, such as a UUID fused at manufacturing.
+    ///
+    /// Logged at the start of [start], such that boot logs collected from a fleet can be correlated to specific units.
+    ///
+    /// Returns all zeroes by default, for boards without a unique identifier.
+    fn device_id(&mut self) -> [u8; 16] {
+        [0; 16]
+    }
+
+    /// Boot info handed over by the stage that booted this bootloader, when it runs as a secondary loader.
+    ///
+    /// Logged at the start of [start], such that boot logs show the full chain. A secondary loader typically
+    /// [forwards](BootInfo::forward) it to the application it boots.
+    ///
+    /// Returns [None] by default, for a bootloader that is the first stage.
+    fn previous_stage(&mut self) -> Option<BootInfo> {
+        None
+    }
+
+    /// Report progress of the boot process.
+    ///
+    /// Called by [start] when attempting a slot, and by the board itself on stage transitions
+    /// and whilst copying an image. Allows products to show boot progress on a display or status register.
+    ///
+    /// Does nothing by default.
+    async fn report(&mut self, _progress: BootProgress) {}
+
+    /// Count a telemetry [Event], persisting it across boots for the application to report.
+    ///
+    /// Called by [start] on fallbacks and authentication failures, and by the board itself
+    /// right before jumping to an application. Must not abort the boot if the counters can not be stored.
+    ///
+    /// Does nothing by default.
+    async fn count(&mut self, _event: Event) {}
+
+    /// Record an error in the [LogSink](ec_slimloader_state::error_log::LogSink) of the board, if any.
+    ///
+    /// Called by [start] for every failed attempt and before giving up, next to logging the error.
+    /// Allows the application to retrieve the errors of the last boot without a debugger attached.
+    ///
+    /// Records nothing by default.
+    fn log(&mut self, _entry: LogEntry) {}
+
+    /// Bring up access to `slot`, as the late phase of initialization.
+    ///
+    /// Called by [start] before every [Board::check_and_boot], such that booting the target of a [Status::Confirmed]
+    /// state only brings up the target slot. A failure counts as a failed attempt to boot the slot.
+    ///
+    /// Does nothing by default.
+    async fn prepare(&mut self, _slot: &Slot) -> Result<(), BootError> {
+        Ok(())
+    }
+
+    /// Query whether the image in `slot` is staged, awaiting activation by the application before it is booted.
+    ///
+    /// Queried by [start] for the target of a [Status::Initial] state. A staged target is not promoted, and the backup
+    /// is booted without touching the journal until the application activates the image, allowing an update to be
+    /// installed ahead of a staged rollout.
+    ///
+    /// Returns `false` by default.
+    async fn is_staged(&mut self, _slot: &Slot) -> bool {
+        false
+    }
 
     /// Check the application image for integrity, and try to boot.
     ///
@@ -39,10 +141,30 @@ pub trait Board {
     /// Yields [BootError] if at any stage the boot is aborted.
     async fn check_and_boot(&mut self, slot: &Slot) -> BootError;
 
+    /// Verify and load the auxiliary images accompanying the application image in `slot`.
+    ///
+    /// Auxiliary images contain firmware for other cores, such as a DSP or coprocessor, and live in their own slots.
+    /// Called by the board from [Board::check_and_boot] after the application image has been verified,
+    /// but before jumping to it. A failing auxiliary image aborts the boot of the application image.
+    ///
+    /// Loads nothing by default.
+    async fn load_auxiliary(&mut self, _slot: &Slot) -> Result<(), BootError> {
+        Ok(())
+    }
+
     /// Give up booting into an application.
     ///
     /// Either shut down the device or go into an infinite loop.
     fn abort(&mut self) -> !;
+
+    /// Enter recovery instead of booting, as requested by the application through [BootStatePolicy::RECOVERY_USER_BITS].
+    ///
+    /// For example hand over to a firmware update transport or the ISP mode of the ROM.
+    ///
+    /// Calls [Board::abort] by default.
+    fn recover(&mut self) -> ! {
+        self.abort()
+    }
 }
 
 #[derive(Debug)]
@@ -60,12 +182,126 @@ pub enum BootError {
     MemoryRegion,
     /// What we copied from the NVM seems to have changed after initial read.
     ///
-    /// Indicates a possible Man-in-the-Middle attack on the NVM.
+    /// Indicates a possible Man-in-the-Middle attack on the NVM, or a fault injected whilst reading.
     ChangeAfterRead,
     /// Image failed to authenticate.
     Authenticate,
     /// The underlying NVM threw an error.
     IO,
+    /// Auxiliary image in [Slot] is invalid or could not be copied to its execution location.
+    AuxiliaryLoad(Slot),
+    /// Auxiliary image in [Slot] failed to authenticate.
+    AuxiliaryAuthenticate(Slot),
+    /// Image is built for a different product than the device.
+    ProductMismatch,
+    /// Image is rooted in a root key that is revoked in SEC_BOOT_CFG5.
+    Revoked,
+    /// Image is bound to a different slot than it was read from.
+    SlotMismatch,
+    /// Image has a lower build number than allowed, refused by the rollback protection of the ROM.
+    Rollback,
+}
+
+impl BootError {
+    /// Stable code of the error, as recorded in a [LogEntry] of [ErrorKind::Boot].
+    pub fn code(&self) -> u8 {
+        match self {
+            BootError::SlotUnknown => 1,
+            BootError::TooLarge => 2,
+            BootError::TooSmall => 3,
+            BootError::Markers => 4,
+            BootError::MemoryRegion => 5,
+            BootError::ChangeAfterRead => 6,
+            BootError::Authenticate => 7,
+            BootError::IO => 8,
+            BootError::AuxiliaryLoad(_) => 9,
+            BootError::AuxiliaryAuthenticate(_) => 10,
+            BootError::ProductMismatch => 11,
+            BootError::Revoked => 12,
+            BootError::SlotMismatch => 13,
+            BootError::Rollback => 14,
+        }
+    }
+
+    /// Short description of the error, for boards logging without `defmt`.
+    pub fn describe(&self) -> &'static str {
+        match self {
+            BootError::SlotUnknown => "slot unknown",
+            BootError::TooLarge => "image too large",
+            BootError::TooSmall => "image too small",
+            BootError::Markers => "image markers invalid",
+            BootError::MemoryRegion => "image in disallowed memory region",
+            BootError::ChangeAfterRead => "image changed after read",
+            BootError::Authenticate => "image failed to authenticate",
+            BootError::IO => "storage error",
+            BootError::AuxiliaryLoad(_) => "auxiliary image invalid",
+            BootError::AuxiliaryAuthenticate(_) => "auxiliary image failed to authenticate",
+            BootError::ProductMismatch => "image for another product",
+            BootError::Revoked => "image root key revoked",
+            BootError::SlotMismatch => "image bound to another slot",
+            BootError::Rollback => "image build number rolled back",
+        }
+    }
+}
+
+/// Override of the journal state, as returned by [Board::boot_override].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BootOverride {
+    /// Boot the backup slot of the current state.
+    Backup,
+    /// Boot a specific [Slot], for example a golden image.
+    Slot(Slot),
+    /// Do not boot any image and stay in the bootloader by calling [Board::abort].
+    Stay,
+}
+
+/// Stage of a single boot attempt, as reported through [BootProgress::Stage].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BootStage {
+    /// Image headers are being read and checked.
+    Verify,
+    /// Image is being copied to its execution location.
+    Copy,
+    /// Image is being authenticated.
+    Authenticate,
+    /// Image has passed all checks and is about to be jumped to.
+    Jump,
+}
+
+/// Progress event of the boot process, as passed to [Board::report].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BootProgress {
+    /// Started an attempt to boot the image in [Slot].
+    Attempt(Slot),
+    /// Started loading the auxiliary image in [Slot] for the current attempt.
+    ///
+    /// Followed by the [BootStage]s of the auxiliary image, up to [BootStage::Authenticate].
+    Auxiliary(Slot),
+    /// Transitioned to a new [BootStage] within the current attempt.
+    Stage(BootStage),
+    /// Copied `done` out of `total` bytes of the current image.
+    Copy { done: usize, total: usize },
+    /// The [TimingPoint] took `micros` microseconds. Only reported with the `timing` feature.
+    Timing { point: TimingPoint, micros: u32 },
+}
+
+/// Phase of the boot process of which the duration is reported through [BootProgress::Timing].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TimingPoint {
+    /// Scanning the state journal for the latest state, during [Board::init].
+    JournalScan,
+    /// Writing a new state to the state journal.
+    StateWrite,
+    /// Copying an image to its execution location.
+    Copy,
+    /// Authenticating an image.
+    Authenticate,
+    /// Everything up to jumping to the application, since the time driver was started during [Board::init].
+    TimeToJump,
 }
 
 /// Intent which denotes which [Slot] should be booted.
@@ -77,17 +313,74 @@ enum BootIntent {
 }
 
 /// Set a new valid [State] as the latest in the [FlashJournal].
-async fn set_status<B: Board, const JOURNAL_BUFFER_SIZE: usize>(board: &mut B, state: &mut State, status: Status) {
+async fn set_status<B: Board>(board: &mut B, state: &mut State, status: Status) {
     *state = state.with_status(status);
-    if let Err(_e) = board.journal().set::<JOURNAL_BUFFER_SIZE>(state).await {
+    #[cfg(feature = "timing")]
+    let stopwatch = timing::Stopwatch::start();
+    if let Err(_e) = board.journal().set(state).await {
+        #[cfg(feature = "minimal")]
+        board.abort();
+        #[cfg(not(feature = "minimal"))]
         panic!("Failed to update state"); // TODO print e, but requirements for defmt are in the way.
     }
+    #[cfg(feature = "timing")]
+    board.report(stopwatch.lap(TimingPoint::StateWrite)).await;
 
     debug!("Stored new state in journal: {:?}", state);
 }
 
-pub async fn start<B: Board, const JOURNAL_BUFFER_SIZE: usize>(config: B::Config) -> ! {
-    let mut board = B::init::<JOURNAL_BUFFER_SIZE>(config).await;
+/// Attempt to boot `slot`, yielding why it failed if this returns at all.
+async fn attempt<B: Board>(board: &mut B, slot: Slot) -> BootError {
+    board.report(BootProgress::Attempt(slot)).await;
+    if let Err(e) = board.prepare(&slot).await {
+        return e;
+    }
+    board.check_and_boot(&slot).await
+}
+
+/// Record the failed attempt to boot `slot` in the log, and count the authentication failure behind `error`, if any.
+async fn record_failure<B: Board>(board: &mut B, slot: Slot, error: &BootError) {
+    board.log(LogEntry {
+        kind: ErrorKind::Boot,
+        slot: Some(slot),
+        code: error.code(),
+    });
+
+    match error {
+        BootError::Authenticate => board.count(Event::AuthFailure(slot)).await,
+        BootError::AuxiliaryAuthenticate(auxiliary) => board.count(Event::AuthFailure(*auxiliary)).await,
+        _ => {}
+    }
+}
+
+/// Boot the board, never returning.
+///
+/// The state journal is scanned in blocks of the length of `journal_buffer`, so a larger buffer boots faster.
+pub async fn start<B: Board>(config: B::Config, journal_buffer: &'static mut [u8]) -> ! {
+    let mut board = B::init(config, journal_buffer).await;
+    info!("Device ID {:?}", board.device_id());
+    if let Some(_previous_stage) = board.previous_stage() {
+        info!("Running as secondary loader, booted by {:?}", _previous_stage);
+    }
+
+    let boot_override = board.boot_override();
+    if boot_override == Some(BootOverride::Stay) {
+        warn!("Board requested to stay in the bootloader");
+        board.abort()
+    }
+
+    let user_bits = board.journal().user_bits();
+    if user_bits & B::Config::RECOVERY_USER_BITS != 0 {
+        warn!("Application requested recovery");
+        let user_bits = user_bits & !B::Config::RECOVERY_USER_BITS;
+        if let Err(_e) = board.journal().set_user_bits(user_bits).await {
+            error!(
+                "Failed to clear the recovery request, recovering regardless: {}",
+                _e.describe()
+            );
+        }
+        board.recover()
+    }
 
     let state = board.journal().get();
 
@@ -116,18 +409,54 @@ pub async fn start<B: Board, const JOURNAL_BUFFER_SIZE: usize>(config: B::Config
         }
     };
 
+    // Bit mask of the slots that failed to boot so far, which are not attempted again.
+    let mut failed = 0u8;
+    let has_failed = |failed: u8, slot: Slot| failed & 1 << slot as u8 != 0;
+
+    // Attempt the slot requested by the board, leaving the journal untouched.
+    if let Some(boot_override) = boot_override {
+        let slot = match boot_override {
+            BootOverride::Slot(slot) => slot,
+            _ => state.backup(),
+        };
+
+        warn!(
+            "Board requested override {:?}, attempting to boot {:?}",
+            boot_override, slot
+        );
+        let error = attempt(&mut board, slot).await; // If this function returns, it implies that the boot has failed.
+        warn!(
+            "Failed to boot override in {:?} because {:?}, continuing with journal state",
+            slot, error
+        );
+        record_failure(&mut board, slot, &error).await;
+        failed |= 1 << slot as u8;
+    }
+
+    // A staged target awaits activation, leaving the journal as is until then.
+    let staged = state.status() == Status::Initial && board.is_staged(&state.target()).await;
+
     // Determine our intended slot to boot.
     let intent = match state.status() {
+        Status::Initial if staged => {
+            info!("Target {:?} is staged, awaiting its activation", state.target());
+            BootIntent::Backup
+        }
+        Status::Initial if has_failed(failed, state.target()) => {
+            // The new target already failed to boot by override, so skip straight to the backup.
+            set_status(&mut board, &mut state, Status::Failed).await;
+            BootIntent::Backup
+        }
         Status::Initial => {
             // Mark the status to [Attempting], so that the app can mark the status to [Confirmed].
-            set_status::<_, JOURNAL_BUFFER_SIZE>(&mut board, &mut state, Status::Attempting).await;
+            set_status(&mut board, &mut state, Status::Attempting).await;
             BootIntent::Target
         }
         Status::Attempting => {
             // When the bootloader starts with the state [Attempting],
             // it implies that an attempt was made to start the application in the slot,
             // but the application failed to mark the slot as [Confirmed].
-            set_status::<_, JOURNAL_BUFFER_SIZE>(&mut board, &mut state, Status::Failed).await;
+            set_status(&mut board, &mut state, Status::Failed).await;
             BootIntent::Backup
         }
         Status::Failed => BootIntent::Backup,
@@ -140,25 +469,42 @@ pub async fn start<B: Board, const JOURNAL_BUFFER_SIZE: usize>(config: B::Config
         BootIntent::Backup => state.backup(),
     };
 
-    info!("Attempting to boot {:?} in {:?}", intent, slot);
-    let error = board.check_and_boot(&slot).await; // If this function returns, it implies that the boot has failed.
-    warn!("Failed to boot {:?} in {:?} because {:?}", intent, slot, error);
+    if has_failed(failed, slot) {
+        info!("Skipping {:?} in {:?}, which already failed to boot", intent, slot);
+    } else {
+        info!("Attempting to boot {:?} in {:?}", intent, slot);
+        if intent == BootIntent::Backup && !staged {
+            board.count(Event::Fallback).await;
+        }
+        let error = attempt(&mut board, slot).await; // If this function returns, it implies that the boot has failed.
+        warn!("Failed to boot {:?} in {:?} because {:?}", intent, slot, error);
+        record_failure(&mut board, slot, &error).await;
+        failed |= 1 << slot as u8;
+    }
 
-    // Mark our state as [Failed] if it was not set to be so already.
-    if state.status() != Status::Failed {
-        set_status::<_, JOURNAL_BUFFER_SIZE>(&mut board, &mut state, Status::Failed).await;
+    // Mark our state as [Failed] if it was not set to be so already, unless the target has not been attempted yet.
+    if state.status() != Status::Failed && !staged {
+        set_status(&mut board, &mut state, Status::Failed).await;
     }
 
-    if slot != state.backup() {
-        // There exists a separate backup slot.
+    let backup = state.backup();
+    if !has_failed(failed, backup) {
+        // There exists a separate backup slot that has not been attempted yet.
         // That implies that we were in either [Initial] or [Confirmed], and now are in [Failed].
         // So attempt to boot the backup for now.
 
-        info!("Attempting to boot backup in {:?}", slot);
-        let error = board.check_and_boot(&state.backup()).await; // If this function returns, it implies that the boot has failed.
-        warn!("Failed to boot backup in {:?} because {:?}", slot, error);
+        info!("Attempting to boot backup in {:?}", backup);
+        board.count(Event::Fallback).await;
+        let error = attempt(&mut board, backup).await; // If this function returns, it implies that the boot has failed.
+        warn!("Failed to boot backup in {:?} because {:?}", backup, error);
+        record_failure(&mut board, backup, &error).await;
     }
 
     error!("No candidates booted successfully, giving up...");
+    board.log(LogEntry {
+        kind: ErrorKind::GaveUp,
+        slot: None,
+        code: 0,
+    });
     board.abort()
 }
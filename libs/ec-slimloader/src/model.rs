@@ -0,0 +1,582 @@
+//! Model of the boot flow of [start] together with an application, exhaustively explored on the host.
+//!
+//! For every combination of images in the slots, every sequence of [DEPTH] boots is explored, in which both the
+//! bootloader and the application may lose power at any byte written to the journal. Unlike fuzzing the journal in
+//! isolation, this checks invariants of the state machine as a whole, such as that a confirmed slot is never
+//! replaced unless the application requests so.
+
+use core::cell::Cell;
+use core::future::Future;
+use core::marker::PhantomData;
+use core::pin::pin;
+use core::task::{Context, Poll, Waker};
+use std::boxed::Box;
+use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::vec::Vec;
+
+use ec_slimloader_state::flash::mock::MockFlashBase;
+use ec_slimloader_state::flash::FlashJournal;
+use ec_slimloader_state::state::{Slot, State, Status};
+use embedded_storage_async::nor_flash::NorFlash;
+
+use crate::policy::AbTesting;
+use crate::{start, Board, BootError, BootOverride, BootStatePolicy};
+
+/// Journal of 2 pages holding 2 states each, such that pages are rotated within a few boots.
+type Flash = MockFlashBase<2, 4, 2>;
+
+const JOURNAL_BUFFER_SIZE: usize = 4;
+
+/// Number of consecutive boots explored from an empty journal.
+const DEPTH: usize = 4;
+
+const SLOTS: [Slot; 3] = [Slot::S0, Slot::S1, Slot::S2];
+
+/// User bit by which the application requests recovery, which applications in the model never set.
+const RECOVERY: u8 = 0b100;
+
+/// Behaviour of the image in a slot.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Image {
+    /// Authenticates, and runs an application that may confirm itself and request updates.
+    Good,
+    /// Authenticates, but the application hangs or crashes before confirming itself.
+    Hangs,
+    /// Fails to authenticate.
+    Bad,
+    /// Authenticates, but awaits activation before it may be booted as new target.
+    Staged,
+}
+
+impl Image {
+    fn boots(self) -> bool {
+        self != Image::Bad
+    }
+}
+
+/// Result of a single run of the bootloader.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Outcome {
+    Booted(Slot),
+    Aborted,
+    PowerLost,
+}
+
+/// Change of the journal by the application after it has been booted.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Action {
+    /// Mark the boot successful, if it is the attempt of the target.
+    Confirm,
+    /// Request the image in [Slot] to be booted, keeping the running image as backup.
+    Update(Slot),
+}
+
+/// Policy of the model, accepting every state.
+struct AnyState;
+
+impl BootStatePolicy for AnyState {
+    const RECOVERY_USER_BITS: u8 = RECOVERY;
+}
+
+struct SimConfig<'a, P> {
+    flash: &'a mut Flash,
+    images: [Image; 3],
+    boot_override: Option<BootOverride>,
+    attempts: &'a Cell<[u8; 3]>,
+    booted: &'a Cell<Option<Slot>>,
+    aborted: &'a Cell<bool>,
+    policy: PhantomData<P>,
+}
+
+impl<P: BootStatePolicy> BootStatePolicy for SimConfig<'_, P> {
+    const RECOVERY_USER_BITS: u8 = P::RECOVERY_USER_BITS;
+
+    fn default_state() -> State {
+        P::default_state()
+    }
+
+    fn is_valid_state(state: &State) -> bool {
+        P::is_valid_state(state)
+    }
+}
+
+struct SimBoard<'a, P> {
+    journal: FlashJournal<'static, &'a mut Flash>,
+    images: [Image; 3],
+    boot_override: Option<BootOverride>,
+    /// Number of times each slot was brought up by [Board::prepare], once for every attempt.
+    attempts: &'a Cell<[u8; 3]>,
+    booted: &'a Cell<Option<Slot>>,
+    aborted: &'a Cell<bool>,
+    policy: PhantomData<P>,
+}
+
+impl<'a, P: BootStatePolicy> Board for SimBoard<'a, P> {
+    type Config = SimConfig<'a, P>;
+
+    async fn init(config: Self::Config, journal_buffer: &'static mut [u8]) -> Self {
+        Self {
+            journal: FlashJournal::new(config.flash, journal_buffer).await.unwrap(),
+            images: config.images,
+            boot_override: config.boot_override,
+            attempts: config.attempts,
+            booted: config.booted,
+            aborted: config.aborted,
+            policy: PhantomData,
+        }
+    }
+
+    fn journal(&mut self) -> &mut FlashJournal<'static, impl NorFlash> {
+        &mut self.journal
+    }
+
+    fn boot_override(&mut self) -> Option<BootOverride> {
+        self.boot_override
+    }
+
+    async fn is_staged(&mut self, slot: &Slot) -> bool {
+        self.images[*slot as usize] == Image::Staged
+    }
+
+    async fn prepare(&mut self, slot: &Slot) -> Result<(), BootError> {
+        let mut attempts = self.attempts.get();
+        attempts[*slot as usize] += 1;
+        self.attempts.set(attempts);
+        Ok(())
+    }
+
+    async fn check_and_boot(&mut self, slot: &Slot) -> BootError {
+        assert!(
+            self.attempts.get()[*slot as usize] > 0,
+            "checked a slot before preparing it"
+        );
+        if !self.images[*slot as usize].boots() {
+            return BootError::Authenticate;
+        }
+
+        // The application takes over, so the bootloader never continues.
+        self.booted.set(Some(*slot));
+        core::future::pending().await
+    }
+
+    fn abort(&mut self) -> ! {
+        self.aborted.set(true);
+        panic!("bootloader aborted");
+    }
+}
+
+/// Latest state in the journal on `flash`.
+fn read_state(flash: &mut Flash) -> Option<State> {
+    embassy_futures::block_on(async {
+        let mut buffer = [0; JOURNAL_BUFFER_SIZE];
+        let journal = FlashJournal::<_, State>::new(flash, &mut buffer).await.unwrap();
+        journal.get().copied()
+    })
+}
+
+/// Run the bootloader with policy `P` until it jumps to an application, aborts or loses power.
+///
+/// Yields the outcome and the number of attempts of each slot.
+fn run_bootloader<P: BootStatePolicy>(
+    flash: &mut Flash,
+    images: [Image; 3],
+    boot_override: Option<BootOverride>,
+) -> (Outcome, [u8; 3]) {
+    let attempts = Cell::new([0; 3]);
+    let booted = Cell::new(None);
+    let aborted = Cell::new(false);
+    let config = SimConfig {
+        flash,
+        images,
+        boot_override,
+        attempts: &attempts,
+        booted: &booted,
+        aborted: &aborted,
+        policy: PhantomData,
+    };
+
+    // All flash operations complete immediately, so the boot flow only pends once it has jumped to an application.
+    // Both aborting and failing to write the journal unwind instead.
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        // The bootloader never returns the buffer, like it is in a `static` on the device.
+        let journal_buffer = Box::leak(Box::new([0; JOURNAL_BUFFER_SIZE]));
+        let mut future = pin!(start::<SimBoard<P>>(config, journal_buffer));
+        match future.as_mut().poll(&mut Context::from_waker(Waker::noop())) {
+            Poll::Pending => (),
+            Poll::Ready(never) => never,
+        }
+    }));
+
+    let outcome = match (result, booted.get()) {
+        (Ok(()), Some(slot)) => Outcome::Booted(slot),
+        (Ok(()), None) => panic!("bootloader pended without booting"),
+        (Err(_), _) if aborted.get() => Outcome::Aborted,
+        (Err(_), _) => Outcome::PowerLost,
+    };
+    (outcome, attempts.get())
+}
+
+/// Run the application in `slot` performing `action`, yielding the state it writes if any.
+fn run_application(flash: &mut Flash, slot: Slot, action: Action) -> Option<State> {
+    embassy_futures::block_on(async {
+        let mut buffer = [0; JOURNAL_BUFFER_SIZE];
+        let mut journal = FlashJournal::<_, State>::new(flash, &mut buffer).await.unwrap();
+        let state = *journal.get().expect("booted without a state");
+        let next = match action {
+            Action::Confirm if state.status() == Status::Attempting && state.target() == slot => {
+                state.with_status(Status::Confirmed)
+            }
+            Action::Confirm => return None,
+            Action::Update(target) => State::new(Status::Initial, target, slot),
+        };
+
+        // Losing power is checked against the journal afterwards.
+        let _ = journal.set(&next).await;
+        Some(next)
+    })
+}
+
+/// Run `phase` on copies of `flash`, once to completion and once for every byte it writes or erases,
+/// at which power is lost instead.
+///
+/// Yields the resulting flash, the result of the phase, and whether power was lost.
+fn with_power_failures<R>(flash: &Flash, mut phase: impl FnMut(&mut Flash) -> R) -> Vec<(Flash, R, bool)> {
+    let mut complete = flash.clone();
+    complete.bytes_until_shutoff = Some(u32::MAX);
+    let result = phase(&mut complete);
+    let written = u32::MAX - complete.bytes_until_shutoff.take().unwrap();
+
+    let mut runs = vec![(complete, result, false)];
+    for shutoff in 0..written {
+        let mut interrupted = flash.clone();
+        interrupted.bytes_until_shutoff = Some(shutoff);
+        let result = phase(&mut interrupted);
+        assert!(interrupted.bytes_until_shutoff.is_none(), "phase is not deterministic");
+        runs.push((interrupted, result, true));
+    }
+    runs
+}
+
+/// Whether the bootloader may move the status from `from` to `to`.
+fn may_progress(from: Status, to: Status) -> bool {
+    from == to
+        || matches!(
+            (from, to),
+            (Status::Initial, Status::Attempting)
+                | (Status::Initial | Status::Attempting | Status::Confirmed, Status::Failed)
+        )
+}
+
+/// Check a single run of the bootloader, which started from `before` and left `after` in the journal.
+fn check_boot(
+    images: [Image; 3],
+    before: Option<State>,
+    (outcome, attempts): (Outcome, [u8; 3]),
+    lost: bool,
+    after: Option<State>,
+) {
+    let context = || format!("{images:?}: {before:?} -> {outcome:?} (power lost: {lost}) -> {after:?}");
+    let state = before.unwrap_or_else(AnyState::default_state);
+
+    assert!(
+        lost || outcome != Outcome::PowerLost,
+        "lost power by itself, {}",
+        context()
+    );
+    assert!(
+        before.is_none() || after.is_some(),
+        "emptied the journal, {}",
+        context()
+    );
+    assert!(
+        attempts.iter().all(|&attempts| attempts <= 1),
+        "attempted a slot twice, {}",
+        context()
+    );
+
+    // The bootloader only ever changes the status, and only forward.
+    if let Some(after) = after {
+        assert_eq!(after.target(), state.target(), "changed the target, {}", context());
+        assert_eq!(after.backup(), state.backup(), "changed the backup, {}", context());
+        assert_eq!(
+            after.user_bits(),
+            state.user_bits(),
+            "changed the user bits, {}",
+            context()
+        );
+        assert!(
+            may_progress(state.status(), after.status()),
+            "moved the status backwards, {}",
+            context()
+        );
+    }
+
+    if let Outcome::Booted(slot) = outcome {
+        assert!(
+            slot == state.target() || slot == state.backup(),
+            "booted neither target nor backup, {}",
+            context()
+        );
+
+        // A target that did not confirm itself is not attempted again.
+        if state.status() == Status::Attempting || state.status() == Status::Failed {
+            assert_eq!(slot, state.backup(), "retried an unconfirmed target, {}", context());
+        }
+
+        // Each first attempt of a target is recorded before jumping to it, such that a crash falls back.
+        if slot == state.target() && state.status() == Status::Initial {
+            assert_eq!(
+                after.map(|after| after.status()),
+                Some(Status::Attempting),
+                "attempted the target without recording so, {}",
+                context()
+            );
+        }
+    }
+
+    // A confirmed slot keeps being booted, without touching the journal.
+    if state.status() == Status::Confirmed && images[state.target() as usize].boots() {
+        assert!(
+            matches!(outcome, Outcome::Booted(slot) if slot == state.target()),
+            "replaced a confirmed slot, {}",
+            context()
+        );
+        assert_eq!(after, before, "changed a confirmed state, {}", context());
+        let mut target_only = [0; 3];
+        target_only[state.target() as usize] = 1;
+        assert_eq!(attempts, target_only, "prepared other slots, {}", context());
+    }
+
+    if state.status() == Status::Initial && images[state.target() as usize].boots() && !lost {
+        assert_eq!(
+            outcome,
+            Outcome::Booted(state.target()),
+            "did not boot a new target, {}",
+            context()
+        );
+    }
+
+    if images[state.backup() as usize].boots() {
+        assert_ne!(
+            outcome,
+            Outcome::Aborted,
+            "aborted with a bootable backup, {}",
+            context()
+        );
+    }
+}
+
+/// Actions of the application in `slot` containing `image`.
+fn actions(image: Image, slot: Slot) -> Vec<Action> {
+    match image {
+        Image::Good | Image::Staged => core::iter::once(Action::Confirm)
+            .chain(SLOTS.into_iter().filter(|&other| other != slot).map(Action::Update))
+            .collect(),
+        Image::Hangs | Image::Bad => Vec::new(),
+    }
+}
+
+/// Explore `depth` further boots from `flash`, skipping journals that have been explored as deep before.
+fn explore(flash: Flash, images: [Image; 3], depth: usize, visited: &mut HashMap<Vec<u8>, usize>) {
+    if depth == 0 || visited.get(flash.as_bytes()).is_some_and(|&explored| explored >= depth) {
+        return;
+    }
+    visited.insert(flash.as_bytes().to_vec(), depth);
+
+    let before = read_state(&mut flash.clone());
+    for (mut flash, (outcome, attempts), lost) in
+        with_power_failures(&flash, |flash| run_bootloader::<AnyState>(flash, images, None))
+    {
+        let after = read_state(&mut flash);
+        check_boot(images, before, (outcome, attempts), lost, after);
+
+        let Outcome::Booted(slot) = outcome else {
+            // Power cycle after losing power or giving up.
+            explore(flash, images, depth - 1, visited);
+            continue;
+        };
+
+        // An application that neither confirms nor updates leaves the journal as is until the next power cycle.
+        explore(flash.clone(), images, depth - 1, visited);
+
+        for action in actions(images[slot as usize], slot) {
+            for (mut flash, next, lost) in with_power_failures(&flash, |flash| run_application(flash, slot, action)) {
+                let written = read_state(&mut flash);
+                let expected = next.or(after);
+                assert!(
+                    written == expected || (lost && written == after),
+                    "{images:?}: {action:?} from {after:?} left {written:?} (power lost: {lost})"
+                );
+                explore(flash, images, depth - 1, visited);
+            }
+        }
+    }
+}
+
+/// Silence the panics by which aborting and losing power unwind, which happen many times over.
+fn quiet_panics() {
+    let report = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let message = info.payload_as_str().unwrap_or_default();
+        if message != "bootloader aborted" && message != "Failed to update state" {
+            report(info)
+        }
+    }));
+}
+
+/// Run the bootloader once from a journal holding `state`, yielding the outcome, the number of attempts of each slot
+/// and the state left in the journal.
+fn boot_from(
+    state: State,
+    images: [Image; 3],
+    boot_override: Option<BootOverride>,
+) -> (Outcome, [u8; 3], Option<State>) {
+    let mut flash = Flash::new(None, false);
+    embassy_futures::block_on(async {
+        let mut buffer = [0; JOURNAL_BUFFER_SIZE];
+        let mut journal = FlashJournal::<_, State>::new(&mut flash, &mut buffer).await.unwrap();
+        journal.set(&state).await.unwrap();
+    });
+    let (outcome, attempts) = run_bootloader::<AnyState>(&mut flash, images, boot_override);
+    (outcome, attempts, read_state(&mut flash))
+}
+
+#[test]
+fn exhaustive() {
+    const IMAGES: [Image; 3] = [Image::Good, Image::Hangs, Image::Bad];
+
+    quiet_panics();
+    for a in IMAGES {
+        for b in IMAGES {
+            for c in IMAGES {
+                let mut visited = HashMap::new();
+                explore(Flash::new(None, false), [a, b, c], DEPTH, &mut visited);
+            }
+        }
+    }
+}
+
+/// Check a single run of the bootloader from `state` with `boot_override`, which never attempts a slot twice.
+fn check_attempts(images: [Image; 3], state: State, boot_override: Option<BootOverride>) {
+    let (outcome, attempts, after) = boot_from(state, images, boot_override);
+    let context = || format!("{images:?}: {state:?} with {boot_override:?} -> {outcome:?}");
+
+    assert!(
+        attempts.iter().all(|&attempts| attempts <= 1),
+        "attempted a slot twice ({attempts:?}), {}",
+        context()
+    );
+
+    // A target that did not confirm itself is no longer a candidate.
+    let overridden = match boot_override {
+        Some(BootOverride::Slot(slot)) => Some(slot),
+        _ => None,
+    };
+    let unconfirmed = matches!(state.status(), Status::Attempting | Status::Failed);
+    let candidates = [
+        overridden,
+        (!unconfirmed).then_some(state.target()),
+        Some(state.backup()),
+    ];
+    if candidates
+        .into_iter()
+        .flatten()
+        .any(|slot| images[slot as usize].boots())
+    {
+        assert_ne!(
+            outcome,
+            Outcome::Aborted,
+            "aborted with a bootable candidate, {}",
+            context()
+        );
+    }
+
+    if outcome == Outcome::Aborted {
+        assert_eq!(
+            after.map(|after| after.status()),
+            Some(Status::Failed),
+            "gave up without recording so, {}",
+            context()
+        );
+    }
+}
+
+#[test]
+fn no_slot_attempted_twice() {
+    const IMAGES: [Image; 3] = [Image::Good, Image::Hangs, Image::Bad];
+    const OVERRIDES: [Option<BootOverride>; 4] = [
+        None,
+        Some(BootOverride::Backup),
+        Some(BootOverride::Slot(Slot::S0)),
+        Some(BootOverride::Slot(Slot::S1)),
+    ];
+
+    quiet_panics();
+    for status in [Status::Initial, Status::Attempting, Status::Failed, Status::Confirmed] {
+        for (target, backup) in [(Slot::S0, Slot::S0), (Slot::S1, Slot::S0)] {
+            for images in IMAGES.into_iter().flat_map(|a| IMAGES.map(|b| [a, b, Image::Bad])) {
+                for boot_override in OVERRIDES {
+                    check_attempts(images, State::new(status, target, backup), boot_override);
+                }
+            }
+        }
+
+        // With identical target and backup, the bootloader gives up after a single attempt.
+        let (outcome, attempts, _) = boot_from(State::new(status, Slot::S0, Slot::S0), [Image::Bad; 3], None);
+        assert_eq!((outcome, attempts), (Outcome::Aborted, [1, 0, 0]), "{status:?}");
+    }
+}
+
+#[test]
+fn recovery_requested_once() {
+    quiet_panics();
+    for status in [Status::Initial, Status::Attempting, Status::Failed, Status::Confirmed] {
+        let state = State::new(status, Slot::S1, Slot::S0).with_user_bits(RECOVERY | 0b1);
+
+        // Recovery takes precedence over booting, and is only entered once.
+        let (outcome, attempts, after) = boot_from(state, [Image::Good; 3], None);
+        assert_eq!((outcome, attempts), (Outcome::Aborted, [0; 3]), "{status:?}");
+        assert_eq!(after, Some(state.with_user_bits(0b1)), "{status:?}");
+    }
+}
+
+#[test]
+fn staged_target_awaits_activation() {
+    quiet_panics();
+    let state = State::new(Status::Initial, Slot::S1, Slot::S0);
+
+    // A staged target is not attempted, and the journal is left as is.
+    let (outcome, attempts, after) = boot_from(state, [Image::Good, Image::Staged, Image::Bad], None);
+    assert_eq!((outcome, attempts), (Outcome::Booted(Slot::S0), [1, 0, 0]));
+    assert_eq!(after, Some(state));
+
+    // Not even when the backup fails to boot.
+    let (outcome, attempts, after) = boot_from(state, [Image::Bad, Image::Staged, Image::Bad], None);
+    assert_eq!((outcome, attempts), (Outcome::Aborted, [1, 0, 0]));
+    assert_eq!(after, Some(state));
+
+    // Once activated, the target is attempted as usual.
+    let (outcome, attempts, after) = boot_from(state, [Image::Good; 3], None);
+    assert_eq!((outcome, attempts), (Outcome::Booted(Slot::S1), [0, 1, 0]));
+    assert_eq!(after, Some(state.with_status(Status::Attempting)));
+}
+
+#[test]
+fn ab_testing_default_target_progresses() {
+    // The default target is both target and backup until an update, and is confirmed or fails like any other target.
+    for (image, status) in [(Image::Good, Status::Confirmed), (Image::Hangs, Status::Failed)] {
+        let images = [Image::Bad, image, Image::Bad];
+        let mut flash = Flash::new(None, false);
+        for boot in 0..3 {
+            let (outcome, _) = run_bootloader::<AbTesting<1>>(&mut flash, images, None);
+            assert_eq!(outcome, Outcome::Booted(Slot::S1));
+
+            let expected = if boot == 0 { Status::Attempting } else { status };
+            assert_eq!(read_state(&mut flash), Some(State::new(expected, Slot::S1, Slot::S1)));
+            if image == Image::Good {
+                run_application(&mut flash, Slot::S1, Action::Confirm);
+            }
+        }
+    }
+}
@@ -0,0 +1,171 @@
+//! Ready-made [BootStatePolicy] implementations for common slot layouts.
+//!
+//! A policy only sees the [State] of the journal, and refuses states that its layout can never lead to, for example
+//! left behind by an application writing the wrong slot. The bootloader then falls back to the default state of the
+//! policy. Slots are given by their index, as [Slot] can not be a const generic parameter, and are checked at compile
+//! time.
+//!
+//! The [Board::Config](crate::Board::Config) implements [BootStatePolicy] itself, so delegate to a policy:
+//!
+//! ```ignore
+//! impl BootStatePolicy for Config {
+//!     fn default_state() -> State {
+//!         GoldenFallback::<2>::default_state()
+//!     }
+//!
+//!     fn is_valid_state(state: &State) -> bool {
+//!         GoldenFallback::<2>::is_valid_state(state)
+//!     }
+//! }
+//! ```
+use ec_slimloader_state::state::{Slot, State, Status};
+
+use crate::BootStatePolicy;
+
+/// [Slot] with `index`, failing to compile when used in a const context with an index out of range.
+const fn slot(index: u8) -> Slot {
+    match index {
+        0 => Slot::S0,
+        1 => Slot::S1,
+        2 => Slot::S2,
+        3 => Slot::S3,
+        4 => Slot::S4,
+        5 => Slot::S5,
+        6 => Slot::S6,
+        _ => panic!("slot index out of range"),
+    }
+}
+
+/// A single image in [Slot::S0], without fallback.
+///
+/// Only the state targeting and backing up slot 0 is valid.
+pub struct AlwaysSlot0;
+
+impl BootStatePolicy for AlwaysSlot0 {
+    fn default_state() -> State {
+        State::new(Status::Initial, Slot::S0, Slot::S0)
+    }
+
+    fn is_valid_state(state: &State) -> bool {
+        state.target() == Slot::S0 && state.backup() == Slot::S0
+    }
+}
+
+/// Two images in [Slot::S0] and [Slot::S1], of which an update is written to the one not booted.
+///
+/// The target and backup are always different slots, except for `DEFAULT_TARGET`, which is both until the other slot
+/// holds an image. It then goes through every status like any other target, hence the bootloader records it being
+/// attempted, and the application confirms it. A state with any other slot as both target and backup would leave an
+/// update without an image to fall back to.
+pub struct AbTesting<const DEFAULT_TARGET: u8>;
+
+impl<const DEFAULT_TARGET: u8> BootStatePolicy for AbTesting<DEFAULT_TARGET> {
+    fn default_state() -> State {
+        let default_target = const {
+            assert!(DEFAULT_TARGET < 2, "A/B testing uses slots 0 and 1");
+            slot(DEFAULT_TARGET)
+        };
+        State::new(Status::Initial, default_target, default_target)
+    }
+
+    fn is_valid_state(state: &State) -> bool {
+        let (target, backup) = (state.target(), state.backup());
+        let in_layout = |slot| slot == Slot::S0 || slot == Slot::S1;
+        if !in_layout(target) || !in_layout(backup) {
+            return false;
+        }
+
+        target != backup || target == Self::default_state().target()
+    }
+}
+
+/// A golden image in slot `GOLDEN`, which is never updated and always the backup.
+///
+/// Updates target any other slot, and the golden image is booted whenever they fail. By default, the golden image is
+/// booted as both target and backup.
+pub struct GoldenFallback<const GOLDEN: u8>;
+
+impl<const GOLDEN: u8> BootStatePolicy for GoldenFallback<GOLDEN> {
+    fn default_state() -> State {
+        let golden = const { slot(GOLDEN) };
+        State::new(Status::Initial, golden, golden)
+    }
+
+    fn is_valid_state(state: &State) -> bool {
+        state.backup() == const { slot(GOLDEN) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn always_slot0() {
+        assert!(AlwaysSlot0::is_valid_state(&AlwaysSlot0::default_state()));
+        assert!(AlwaysSlot0::is_valid_state(&State::new(
+            Status::Confirmed,
+            Slot::S0,
+            Slot::S0
+        )));
+        assert!(!AlwaysSlot0::is_valid_state(&State::new(
+            Status::Initial,
+            Slot::S1,
+            Slot::S0
+        )));
+    }
+
+    #[test]
+    fn ab_testing() {
+        type Policy = AbTesting<1>;
+        let default_state = Policy::default_state();
+        assert_eq!(default_state, State::new(Status::Initial, Slot::S1, Slot::S1));
+        assert!(Policy::is_valid_state(&default_state));
+        assert!(Policy::is_valid_state(&default_state.with_user_bits(0x3)));
+
+        assert!(Policy::is_valid_state(&State::new(Status::Initial, Slot::S0, Slot::S1)));
+        assert!(Policy::is_valid_state(&State::new(
+            Status::Confirmed,
+            Slot::S1,
+            Slot::S0
+        )));
+
+        // The same target and backup only for the default target, in any status.
+        for status in [Status::Initial, Status::Attempting, Status::Confirmed, Status::Failed] {
+            assert!(Policy::is_valid_state(&State::new(status, Slot::S1, Slot::S1)));
+            assert!(!Policy::is_valid_state(&State::new(status, Slot::S0, Slot::S0)));
+        }
+        assert!(!Policy::is_valid_state(&State::new(
+            Status::Initial,
+            Slot::S2,
+            Slot::S0
+        )));
+        assert!(!Policy::is_valid_state(&State::new(
+            Status::Initial,
+            Slot::S0,
+            Slot::S3
+        )));
+    }
+
+    #[test]
+    fn golden_fallback() {
+        type Policy = GoldenFallback<6>;
+        assert_eq!(Policy::default_state(), State::new(Status::Initial, Slot::S6, Slot::S6));
+        assert!(Policy::is_valid_state(&Policy::default_state()));
+        assert!(Policy::is_valid_state(&State::new(
+            Status::Attempting,
+            Slot::S2,
+            Slot::S6
+        )));
+        assert!(!Policy::is_valid_state(&State::new(
+            Status::Confirmed,
+            Slot::S6,
+            Slot::S2
+        )));
+        assert!(!Policy::is_valid_state(&State::new(
+            Status::Confirmed,
+            Slot::S1,
+            Slot::S2
+        )));
+    }
+}
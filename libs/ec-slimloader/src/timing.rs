@@ -0,0 +1,34 @@
+//! Durations of boot phases, as reported through [BootProgress::Timing].
+//!
+//! Relies on an embassy-time driver, which is typically started by [Board::init](crate::Board::init) when it brings
+//! up the HAL. Phases can thus only be measured once the HAL is up.
+
+use defmt_or_log::debug;
+use embassy_time::Instant;
+
+use crate::{BootProgress, TimingPoint};
+
+/// Measures the duration of a single phase, from [Stopwatch::start].
+pub struct Stopwatch(Instant);
+
+impl Stopwatch {
+    pub fn start() -> Self {
+        Self(Instant::now())
+    }
+
+    /// Progress event reporting the time since [Stopwatch::start] as the duration of `point`.
+    pub fn lap(&self, point: TimingPoint) -> BootProgress {
+        progress(point, self.0.elapsed().as_micros())
+    }
+}
+
+/// Progress event reporting the time since the time driver was started as the duration of [TimingPoint::TimeToJump].
+pub fn time_to_jump() -> BootProgress {
+    progress(TimingPoint::TimeToJump, Instant::now().as_micros())
+}
+
+fn progress(point: TimingPoint, micros: u64) -> BootProgress {
+    let micros = u32::try_from(micros).unwrap_or(u32::MAX);
+    debug!("{:?} took {} us", point, micros);
+    BootProgress::Timing { point, micros }
+}
@@ -1,11 +1,12 @@
 #[repr(C)]
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct Version {
-    bugfix: u8,
-    minor: u8,
-    major: u8,
-    name: u8,
+    pub bugfix: u8,
+    pub minor: u8,
+    pub major: u8,
+    /// Single ASCII character identifying the ROM.
+    pub name: u8,
 }
 
 #[repr(C)]
@@ -113,29 +114,49 @@ pub struct OTPDriver {
     pub crc_check: unsafe extern "C" fn(start_addr: u32, end_addr: u32, crc_addr: u32) -> u32,
 }
 
+/// Prefix of the FlexSPI NOR flash driver, of which only the functions in use are declared.
+#[repr(C)]
+pub struct FlexSpiNorDriver {
+    pub version: u32,
+    pub init: unsafe extern "C" fn(instance: u32, config: *mut u8) -> u32,
+    pub page_program: unsafe extern "C" fn(instance: u32, config: *mut u8, dst_addr: u32, src: *const u32) -> u32,
+    pub erase_all: unsafe extern "C" fn(instance: u32, config: *mut u8) -> u32,
+    pub erase: unsafe extern "C" fn(instance: u32, config: *mut u8, start: u32, length: u32) -> u32,
+    pub read: unsafe extern "C" fn(instance: u32, config: *mut u8, dst: *mut u32, addr: u32, length: u32) -> u32,
+    pub clear_cache: unsafe extern "C" fn(instance: u32),
+}
+
 /// ROM API layout 42.9.3.1, RT6xx user manual UM11147.
 #[repr(C)]
 pub struct ApiTable {
-    bootloader_fn: unsafe extern "C" fn(*const u8),
+    pub bootloader_fn: unsafe extern "C" fn(*const u8),
     pub version: Version,
     pub copyright: &'static [u8; 0],
-    reserved: u32,
+    pub(crate) reserved: u32,
     pub iap_driver: &'static IAPDriver,
-    reserved1: u32,
-    reserved2: u32,
-    flash_driver: &'static [u8; 0], // stubbed
+    pub(crate) reserved1: u32,
+    pub(crate) reserved2: u32,
+    pub flexspi_nor_driver: &'static FlexSpiNorDriver,
     pub otp_driver: &'static OTPDriver,
     pub skboot: &'static SKBoot,
 }
 
+#[cfg(not(any(test, feature = "_test")))]
 extern "C" {
     static API_TABLE: ApiTable;
 }
 
+#[cfg(not(any(test, feature = "_test")))]
 pub fn api_table() -> &'static ApiTable {
     unsafe { &API_TABLE }
 }
 
+/// The table of the [fake](crate::fake) ROM in tests.
+#[cfg(any(test, feature = "_test"))]
+pub fn api_table() -> &'static ApiTable {
+    &crate::fake::API_TABLE
+}
+
 #[derive(Debug)]
 #[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum BootStatus {
@@ -0,0 +1,290 @@
+//! In-process fake of the ROM API, replacing the table provided by the linker in tests and with the `_test` feature.
+//!
+//! Every function of the fake ROM is backed by a [Rom] test double, which is installed for the duration of a test
+//! using [install]. This allows testing the wrappers of this crate on the host, including their error paths.
+//! The core peripherals used around the ROM calls, such as the DWT cycle counter, are left untouched.
+
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::vec::Vec;
+
+pub use crate::api::KbStatus;
+use crate::api::{
+    ApiTable, FlexSpiNorDriver, IAPDriver, KbOperation, KbOptions, KbSessionRef, OTPDriver, SKBoot, Version,
+};
+
+/// Number of OTP fuse words of the fake ROM.
+pub const FUSE_WORDS: usize = 512;
+
+/// Number of words in the shadow register block of the fake ROM.
+pub const SHADOW_WORDS: usize = 0x800 / 4;
+
+/// Version reported by the fake ROM.
+pub const VERSION: Version = Version {
+    bugfix: 0,
+    minor: 0,
+    major: 1,
+    name: b'F',
+};
+
+const COPYRIGHT: &[u8] = b"Fake ROM\0";
+
+/// Test double of the ROM, programmed with the outcome of each function and recording how it was called.
+///
+/// Statuses are raw values as returned by the ROM, for example `KbStatus::RollbackBlocked as u32`.
+pub struct Rom {
+    /// Value of every OTP fuse word.
+    pub fuses: [u32; FUSE_WORDS],
+    /// Shadow register block, indexed by offset in words, reloaded from [Rom::fuses] by the OTP driver.
+    pub shadows: [u32; SHADOW_WORDS],
+    /// Status returned by the OTP driver.
+    pub otp_status: u32,
+    /// Whether the OTP driver is initialized.
+    pub otp_initialized: bool,
+    /// Fuse words programmed as `(index, value, lock)`, in order.
+    pub programmed: Vec<(u32, u32, bool)>,
+    /// Status returned when initializing and deinitializing a session of the IAP driver.
+    pub iap_status: u32,
+    /// Raw boot status returned when authenticating an image, `0x5ac3c35a` for success.
+    pub boot_status: u32,
+    /// Raw secure boolean reported as whether the signature of an image is verified, `0x55aacc33` for verified.
+    pub is_verified: u32,
+    /// Root key table hash passed to authenticate the last image against, if any.
+    pub user_rhk: Option<[u32; 8]>,
+    /// Status returned by the FlexSPI NOR driver.
+    pub flexspi_status: u32,
+    /// Number of times the AHB cache of the FlexSPI controller was cleared.
+    pub cache_clears: usize,
+}
+
+impl Default for Rom {
+    /// ROM with blank fuses, on which every call succeeds and every image is authenticated.
+    fn default() -> Self {
+        Self {
+            fuses: [0; FUSE_WORDS],
+            shadows: [0; SHADOW_WORDS],
+            otp_status: KbStatus::Success as u32,
+            otp_initialized: false,
+            programmed: Vec::new(),
+            iap_status: KbStatus::Success as u32,
+            boot_status: 0x5ac3c35a,
+            is_verified: 0x55aacc33,
+            user_rhk: None,
+            flexspi_status: KbStatus::Success as u32,
+            cache_clears: 0,
+        }
+    }
+}
+
+static ROM: Mutex<Option<Rom>> = Mutex::new(None);
+
+/// Held whilst a [Rom] is installed, as the ROM is shared by all tests.
+static INSTALLED: Mutex<()> = Mutex::new(());
+
+/// [Rom] installed as the ROM until dropped, see [install].
+pub struct Installed {
+    _serial: MutexGuard<'static, ()>,
+}
+
+impl Installed {
+    /// Inspect or reprogram the installed [Rom].
+    pub fn with<R>(&self, f: impl FnOnce(&mut Rom) -> R) -> R {
+        with_rom(f)
+    }
+}
+
+impl Drop for Installed {
+    fn drop(&mut self) {
+        *ROM.lock().unwrap_or_else(PoisonError::into_inner) = None;
+    }
+}
+
+/// Install `rom` as the ROM until the returned guard is dropped.
+///
+/// Waits for the [Rom] installed by another test to be dropped first, such that tests using the ROM do not interfere.
+pub fn install(rom: Rom) -> Installed {
+    let serial = INSTALLED.lock().unwrap_or_else(PoisonError::into_inner);
+    *ROM.lock().unwrap_or_else(PoisonError::into_inner) = Some(rom);
+    Installed { _serial: serial }
+}
+
+fn with_rom<R>(f: impl FnOnce(&mut Rom) -> R) -> R {
+    let mut rom = ROM.lock().unwrap_or_else(PoisonError::into_inner);
+    f(rom.as_mut().expect("the fake ROM is called without being installed"))
+}
+
+/// Read the shadow register at `offset` in the shadow register block.
+pub(crate) fn read_shadow(offset: usize) -> u32 {
+    with_rom(|rom| rom.shadows[offset / 4])
+}
+
+/// Write the shadow register at `offset` in the shadow register block.
+pub(crate) fn write_shadow(offset: usize, word: u32) {
+    with_rom(|rom| rom.shadows[offset / 4] = word)
+}
+
+unsafe extern "C" fn run_bootloader(_arg: *const u8) {}
+
+unsafe extern "C" fn iap_init(session: *mut *mut KbSessionRef, options: *const KbOptions) -> u32 {
+    let options = unsafe { &*options };
+    let user_rhk = match options.op {
+        // Safety: the settings of an authentication are those for authenticating.
+        KbOperation::AuthenticateImage => unsafe { options.settings.authenticate.user_rhk },
+        KbOperation::LoadImage => core::ptr::null(),
+    };
+    // Safety: a root key table hash is passed as 8 words.
+    let user_rhk = (!user_rhk.is_null()).then(|| unsafe { *(user_rhk as *const [u32; 8]) });
+    unsafe { *session = core::ptr::null_mut() };
+
+    with_rom(|rom| {
+        rom.user_rhk = user_rhk;
+        rom.iap_status
+    })
+}
+
+unsafe extern "C" fn iap_deinit(_session: *mut KbSessionRef) -> u32 {
+    with_rom(|rom| rom.iap_status)
+}
+
+unsafe extern "C" fn iap_execute(_session: *mut KbSessionRef, _data: *const u8, _len: u32) -> u32 {
+    KbStatus::Fail as u32
+}
+
+unsafe extern "C" fn otp_init(_src_clk_freq: u32) -> u32 {
+    with_rom(|rom| {
+        rom.otp_initialized = true;
+        rom.otp_status
+    })
+}
+
+unsafe extern "C" fn otp_deinit() -> u32 {
+    with_rom(|rom| {
+        rom.otp_initialized = false;
+        rom.otp_status
+    })
+}
+
+unsafe extern "C" fn otp_fuse_read(addr: u32, data: *mut u8) -> u32 {
+    let (status, word) = with_rom(|rom| match rom.fuses.get(addr as usize) {
+        Some(_) if rom.otp_status != KbStatus::Success as u32 => (rom.otp_status, None),
+        Some(word) => (rom.otp_status, Some(*word)),
+        None => (KbStatus::OutOfRange as u32, None),
+    });
+    if let Some(word) = word {
+        unsafe { core::ptr::copy_nonoverlapping(word.to_le_bytes().as_ptr(), data, 4) };
+    }
+    status
+}
+
+unsafe extern "C" fn otp_fuse_program(addr: u32, data: u32, lock: bool) -> u32 {
+    with_rom(|rom| {
+        if rom.otp_status != KbStatus::Success as u32 {
+            return rom.otp_status;
+        }
+        let Some(word) = rom.fuses.get_mut(addr as usize) else {
+            return KbStatus::OutOfRange as u32;
+        };
+        // Fuses can only be blown, not restored.
+        *word |= data;
+        rom.programmed.push((addr, data, lock));
+        KbStatus::Success as u32
+    })
+}
+
+unsafe extern "C" fn otp_crc_calc(_src: *const u32, _number_of_words: u32, _crc_checksum: *const u32) -> u32 {
+    KbStatus::Fail as u32
+}
+
+unsafe extern "C" fn otp_reload() -> u32 {
+    with_rom(|rom| {
+        if rom.otp_status == KbStatus::Success as u32 {
+            for (otp_word_i, word) in rom.fuses.iter().enumerate() {
+                if let Ok(offset) = crate::registers::otp_to_shadow_offset(otp_word_i as u32) {
+                    rom.shadows[offset / 4] = *word;
+                }
+            }
+        }
+        rom.otp_status
+    })
+}
+
+unsafe extern "C" fn otp_crc_check(_start_addr: u32, _end_addr: u32, _crc_addr: u32) -> u32 {
+    KbStatus::Fail as u32
+}
+
+unsafe extern "C" fn flexspi_init(_instance: u32, _config: *mut u8) -> u32 {
+    with_rom(|rom| rom.flexspi_status)
+}
+
+unsafe extern "C" fn flexspi_page_program(_instance: u32, _config: *mut u8, _dst_addr: u32, _src: *const u32) -> u32 {
+    with_rom(|rom| rom.flexspi_status)
+}
+
+unsafe extern "C" fn flexspi_erase_all(_instance: u32, _config: *mut u8) -> u32 {
+    with_rom(|rom| rom.flexspi_status)
+}
+
+unsafe extern "C" fn flexspi_erase(_instance: u32, _config: *mut u8, _start: u32, _length: u32) -> u32 {
+    with_rom(|rom| rom.flexspi_status)
+}
+
+unsafe extern "C" fn flexspi_read(_instance: u32, _config: *mut u8, _dst: *mut u32, _addr: u32, _length: u32) -> u32 {
+    with_rom(|rom| rom.flexspi_status)
+}
+
+unsafe extern "C" fn flexspi_clear_cache(_instance: u32) {
+    with_rom(|rom| rom.cache_clears += 1)
+}
+
+unsafe extern "C" fn skboot_authenticate(_start_addr: *const u32, is_verified: *mut u32) -> u32 {
+    let (status, verified) = with_rom(|rom| (rom.boot_status, rom.is_verified));
+    unsafe { *is_verified = verified };
+    status
+}
+
+unsafe extern "C" fn hashcrypt_irq_handler() {}
+
+static IAP_DRIVER: IAPDriver = IAPDriver {
+    init: iap_init,
+    deinit: iap_deinit,
+    execute: iap_execute,
+};
+
+static OTP_DRIVER: OTPDriver = OTPDriver {
+    init: otp_init,
+    deinit: otp_deinit,
+    fuse_read: otp_fuse_read,
+    fuse_program: otp_fuse_program,
+    crc_calc: otp_crc_calc,
+    reload: otp_reload,
+    crc_check: otp_crc_check,
+};
+
+static FLEXSPI_NOR_DRIVER: FlexSpiNorDriver = FlexSpiNorDriver {
+    version: 0,
+    init: flexspi_init,
+    page_program: flexspi_page_program,
+    erase_all: flexspi_erase_all,
+    erase: flexspi_erase,
+    read: flexspi_read,
+    clear_cache: flexspi_clear_cache,
+};
+
+static SKBOOT: SKBoot = SKBoot {
+    authenticate: skboot_authenticate,
+    hashcrypt_irq_handler,
+};
+
+/// Table of the fake ROM, taking the place of the one provided by the linker.
+pub(crate) static API_TABLE: ApiTable = ApiTable {
+    bootloader_fn: run_bootloader,
+    version: VERSION,
+    // Safety: the copyright notice is read as a NUL-terminated string from the start of the pointed to array.
+    copyright: unsafe { &*(COPYRIGHT.as_ptr() as *const [u8; 0]) },
+    reserved: 0,
+    iap_driver: &IAP_DRIVER,
+    reserved1: 0,
+    reserved2: 0,
+    flexspi_nor_driver: &FLEXSPI_NOR_DRIVER,
+    otp_driver: &OTP_DRIVER,
+    skboot: &SKBOOT,
+};
@@ -0,0 +1,66 @@
+//! API to reconfigure the FlexSPI controller and the NOR flash attached to it.
+
+use crate::api::{api_table, KbStatus};
+
+/// Size of a FlexSPI NOR Configuration Block.
+pub const FCB_LEN: usize = 0x200;
+
+/// The RT6xx only has a single FlexSPI controller.
+const INSTANCE: u32 = 0;
+
+/// FlexSPI NOR Configuration Block (FCB), `flexspi_nor_config_t` in UM11147.
+///
+/// The same block the ROM reads at offset 0x400 of the external flash at boot.
+#[repr(C, align(4))]
+pub struct FlexSpiNorConfig(pub [u8; FCB_LEN]);
+
+#[derive(Debug)]
+#[allow(dead_code)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Error(u32);
+
+/// Initialize the FlexSPI controller and the flash with `config`, replacing the configuration the ROM booted with.
+///
+/// The ROM may update `config` with what it probed from the flash. Clears the AHB cache afterwards,
+/// such that memory mapped reads go through the new configuration.
+///
+/// # Safety
+/// Nothing may execute from or otherwise access the external flash whilst it is being reconfigured.
+pub unsafe fn init(config: &mut FlexSpiNorConfig) -> Result<(), Error> {
+    let driver = api_table().flexspi_nor_driver;
+    let status = (driver.init)(INSTANCE, config.0.as_mut_ptr());
+    (driver.clear_cache)(INSTANCE);
+
+    if status == KbStatus::Success as u32 {
+        Ok(())
+    } else {
+        Err(Error(status))
+    }
+}
+
+/// Clear the AHB cache, such that memory mapped reads reflect what was programmed into the flash since.
+///
+/// # Safety
+/// Nothing may execute from the external flash whilst the cache is being cleared.
+pub unsafe fn clear_cache() {
+    (api_table().flexspi_nor_driver.clear_cache)(INSTANCE);
+}
+
+/// Address of the FCB the ROM booted with, at offset 0x400 of the external flash as mapped by the FlexSPI controller.
+pub const BOOT_FCB_ADDRESS: usize = 0x0800_0400;
+
+/// Reconfigure the FlexSPI controller and the flash with the FCB the ROM booted with, at [BOOT_FCB_ADDRESS].
+///
+/// Returns the controller to the state the ROM left it in, for example before handing it over to an application.
+///
+/// # Safety
+/// Nothing may execute from or otherwise access the external flash whilst it is being reconfigured, and the flash must
+/// still be memory mapped such that the FCB can be read.
+pub unsafe fn restore_boot_config() -> Result<(), Error> {
+    let mut config = FlexSpiNorConfig([0; FCB_LEN]);
+    unsafe {
+        clear_cache();
+        core::ptr::copy_nonoverlapping(BOOT_FCB_ADDRESS as *const u8, config.0.as_mut_ptr(), FCB_LEN);
+        init(&mut config)
+    }
+}
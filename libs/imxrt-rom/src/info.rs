@@ -0,0 +1,65 @@
+//! Identification of the ROM and the device, useful to record when debugging ROM-dependent behavior.
+
+use core::ffi::{c_char, CStr};
+
+use crate::api::api_table;
+pub use crate::api::Version;
+use crate::otp::{Error, Otp};
+
+/// The OTP word index of the first of the 4 words containing the device UUID, as defined in the OTP Fuse Map -- 220315.
+const UUID_OTP_WORD_I: u32 = 184;
+
+/// Version of the ROM.
+pub fn version() -> Version {
+    api_table().version
+}
+
+/// Copyright notice embedded in the ROM.
+///
+/// Yields an empty string if the notice is not valid UTF-8.
+pub fn copyright() -> &'static str {
+    // Safety: the ROM API table points to a NUL-terminated string that lives in ROM.
+    let copyright = unsafe { CStr::from_ptr(api_table().copyright.as_ptr() as *const c_char) };
+    copyright.to_str().unwrap_or("")
+}
+
+/// Unique identifier of the device, as read from the OTP fuses.
+pub fn uuid(otp: &mut Otp) -> Result<[u8; 16], Error> {
+    let mut uuid = [0u8; 16];
+    for (word_i, chunk) in (UUID_OTP_WORD_I..).zip(uuid.chunks_exact_mut(4)) {
+        chunk.copy_from_slice(&otp.read_fuse(word_i)?.to_le_bytes());
+    }
+    Ok(uuid)
+}
+
+impl core::fmt::Display for Version {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        if self.name.is_ascii_alphanumeric() {
+            write!(f, "{}", self.name as char)?;
+        } else {
+            write!(f, "{:02x}-", self.name)?;
+        }
+        write!(f, "{}.{}.{}", self.major, self.minor, self.bugfix)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::string::ToString;
+
+    use super::*;
+
+    #[test]
+    fn version_display() {
+        let version = Version {
+            bugfix: 3,
+            minor: 2,
+            major: 1,
+            name: b'T',
+        };
+        assert_eq!(version.to_string(), "T1.2.3");
+
+        let version = Version { name: 0, ..version };
+        assert_eq!(version.to_string(), "00-1.2.3");
+    }
+}
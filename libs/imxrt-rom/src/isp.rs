@@ -0,0 +1,51 @@
+//! Hand-over to the bootloader in ROM, for example to update the firmware over In-System Programming (ISP).
+
+use crate::api::api_table;
+
+/// Tag marking the argument of `runBootloader` as valid, in its most significant byte.
+const TAG: u32 = 0xeb;
+
+/// Boot mode of the ROM bootloader entering ISP, rather than booting from the primary boot source.
+const MODE_ISP: u32 = 1;
+
+/// Argument of `runBootloader`, as laid out in the RT6xx user manual UM11147.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct BootloaderArg(u32);
+
+impl BootloaderArg {
+    /// Enter ISP on `interface`, numbered as the boot interface field in UM11147.
+    pub const fn isp(interface: u8) -> Self {
+        Self((TAG << 24) | (MODE_ISP << 20) | ((interface as u32 & 0xf) << 16))
+    }
+
+    /// Raw value as passed to the ROM.
+    pub const fn raw(&self) -> u32 {
+        self.0
+    }
+}
+
+/// Hand over to the bootloader in ROM with `arg`, which never returns.
+///
+/// # Safety
+/// The ROM expects the peripherals it uses in their reset state, and interrupts disabled.
+pub unsafe fn run_bootloader(arg: BootloaderArg) -> ! {
+    let arg = arg.raw();
+    (api_table().bootloader_fn)(&arg as *const u32 as *const u8);
+
+    // The ROM does not return, but can not be declared as such.
+    loop {
+        cortex_m::asm::wfi();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn isp_arg() {
+        assert_eq!(BootloaderArg::isp(3).raw(), 0xeb13_0000);
+        assert_eq!(BootloaderArg::isp(0x13).raw(), 0xeb13_0000);
+    }
+}
@@ -1,11 +1,20 @@
 #![no_std]
 
-#[cfg(test)]
+#[cfg(all(feature = "minimal", any(feature = "defmt", feature = "log")))]
+compile_error!("The `minimal` feature strips all log messages, and can not be combined with `defmt` or `log`.");
+
+#[cfg(any(test, feature = "_test"))]
 #[macro_use]
 extern crate std;
 
 pub(crate) mod api;
 
+#[cfg(any(test, feature = "_test"))]
+pub mod fake;
+
+pub mod flexspi;
+pub mod info;
+pub mod isp;
 pub mod otp;
 pub mod registers;
 pub mod skboot;
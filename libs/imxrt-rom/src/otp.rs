@@ -24,6 +24,9 @@ impl Otp {
             .compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire)
             .is_err()
         {
+            #[cfg(feature = "minimal")]
+            cortex_m::asm::udf();
+            #[cfg(not(feature = "minimal"))]
             defmt_or_log::panic!("Using OTP whilst it has already been initialized");
         };
 
@@ -77,3 +80,45 @@ impl Drop for Otp {
         INITIALIZED.store(false, Ordering::Release);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake::{self, Rom, FUSE_WORDS};
+
+    #[test]
+    fn fuses() {
+        let mut rom = Rom::default();
+        rom.fuses[100] = 0x1234;
+        let installed = fake::install(rom);
+
+        let mut otp = Otp::init(0);
+        assert!(installed.with(|rom| rom.otp_initialized));
+        assert_eq!(otp.read_fuse(100).unwrap(), 0x1234);
+
+        otp.write_fuse(100, 0x1_0000, true).unwrap();
+        assert_eq!(otp.read_fuse(100).unwrap(), 0x1_1234);
+        assert_eq!(installed.with(|rom| rom.programmed.clone()), [(100, 0x1_0000, true)]);
+
+        assert!(
+            matches!(otp.read_fuse(FUSE_WORDS as u32), Err(Error(status)) if status == KbStatus::OutOfRange as u32)
+        );
+
+        drop(otp);
+        assert!(!installed.with(|rom| rom.otp_initialized));
+    }
+
+    #[test]
+    fn fuses_fail() {
+        let installed = fake::install(Rom {
+            otp_status: KbStatus::Timeout as u32,
+            ..Rom::default()
+        });
+
+        let mut otp = Otp::init(0);
+        assert!(matches!(otp.read_fuse(100), Err(Error(status)) if status == KbStatus::Timeout as u32));
+        assert!(otp.write_fuse(100, 1, false).is_err());
+        assert!(otp.reload_shadow().is_err());
+        assert!(installed.with(|rom| rom.programmed.is_empty()));
+    }
+}
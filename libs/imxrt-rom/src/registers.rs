@@ -30,7 +30,7 @@ type OtpWordIndex = u32;
 type OtpWord = u32;
 
 /// Convert an OTP word index to offset in the shadow register block.
-const fn otp_to_shadow_offset(otp_word_i: OtpWordIndex) -> Result<usize, NotShadowRegister> {
+pub(crate) const fn otp_to_shadow_offset(otp_word_i: OtpWordIndex) -> Result<usize, NotShadowRegister> {
     let shadow_offset = match otp_word_i {
         8..=9 => (otp_word_i - 8) * 4 + 0x020,
         95..=104 => (otp_word_i - 95) * 4 + 0x17C,
@@ -43,11 +43,42 @@ const fn otp_to_shadow_offset(otp_word_i: OtpWordIndex) -> Result<usize, NotShad
 }
 
 /// Convert an OTP word index to address in the shadow register block.
+#[cfg(not(any(test, feature = "_test")))]
 fn otp_to_shadow_addr(otp_word_i: OtpWordIndex) -> Result<*mut u32, NotShadowRegister> {
     const OTP_SHADOW_BASE_ADDR: usize = 0x40130000;
     Ok((OTP_SHADOW_BASE_ADDR + otp_to_shadow_offset(otp_word_i)?) as *mut u32)
 }
 
+/// Read the shadow register of an OTP word.
+#[cfg(not(any(test, feature = "_test")))]
+fn read_shadow(otp_word_i: OtpWordIndex) -> Result<OtpWord, NotShadowRegister> {
+    let shadow_addr = otp_to_shadow_addr(otp_word_i)? as *const u32;
+    // Safety: we assume that the register yaml definition is correct, and that each register is aligned.
+    Ok(unsafe { shadow_addr.read_volatile() })
+}
+
+/// Write the shadow register of an OTP word.
+#[cfg(not(any(test, feature = "_test")))]
+fn write_shadow(otp_word_i: OtpWordIndex, word: OtpWord) -> Result<(), NotShadowRegister> {
+    let shadow_addr = otp_to_shadow_addr(otp_word_i)?;
+    // Safety: we assume that the register yaml definition is correct, and that each register is aligned.
+    unsafe { shadow_addr.write_volatile(word) };
+    Ok(())
+}
+
+/// Read the shadow register of an OTP word from the [fake](crate::fake) ROM.
+#[cfg(any(test, feature = "_test"))]
+fn read_shadow(otp_word_i: OtpWordIndex) -> Result<OtpWord, NotShadowRegister> {
+    Ok(crate::fake::read_shadow(otp_to_shadow_offset(otp_word_i)?))
+}
+
+/// Write the shadow register of an OTP word of the [fake](crate::fake) ROM.
+#[cfg(any(test, feature = "_test"))]
+fn write_shadow(otp_word_i: OtpWordIndex, word: OtpWord) -> Result<(), NotShadowRegister> {
+    crate::fake::write_shadow(otp_to_shadow_offset(otp_word_i)?, word);
+    Ok(())
+}
+
 /// Converts a slice into a sequence of words to be written to either shadow registers or OTP fuses.
 fn data_to_otp_words(otp_word_i: OtpWordIndex, data: &[u8]) -> impl Iterator<Item = (OtpWordIndex, OtpWord)> + '_ {
     data.chunks(core::mem::size_of::<OtpWord>())
@@ -95,10 +126,7 @@ impl RegisterInterface for ShadowInterface {
         data: &[u8],
     ) -> Result<(), Self::Error> {
         for (otp_word_i, word) in data_to_otp_words(otp_word_i, data) {
-            let shadow_addr = otp_to_shadow_addr(otp_word_i)?;
-
-            // Safety: we assume that the register yaml definition is correct, and that each register is aligned.
-            unsafe { shadow_addr.write_volatile(word) };
+            write_shadow(otp_word_i, word)?;
         }
         Ok(())
     }
@@ -109,11 +137,7 @@ impl RegisterInterface for ShadowInterface {
         _size_bits: u32,
         data: &mut [u8],
     ) -> Result<(), Self::Error> {
-        otp_words_to_data(otp_word_i, data, |otp_word_i| {
-            let shadow_addr = otp_to_shadow_addr(otp_word_i)? as *const u32;
-            // Safety: we assume that the register yaml definition is correct, and that each register is aligned.
-            Ok(unsafe { shadow_addr.read_volatile() })
-        })?;
+        otp_words_to_data(otp_word_i, data, read_shadow)?;
         Ok(())
     }
 }
@@ -169,6 +193,35 @@ impl RegisterInterface for OtpInterface<'_> {
     }
 }
 
+/// The OTP word indices of all registers defined in `registers.json`, as captured by [ShadowSnapshot].
+const SNAPSHOT_WORDS: [OtpWordIndex; 11] = [
+    96,  // BOOT_CFG[0]
+    97,  // BOOT_CFG[1]
+    101, // SEC_BOOT_CFG[5]
+    120, // RKTH[0]
+    121, // RKTH[1]
+    122, // RKTH[2]
+    123, // RKTH[3]
+    124, // RKTH[4]
+    125, // RKTH[5]
+    126, // RKTH[6]
+    127, // RKTH[7]
+];
+
+/// Values of all shadow registers defined in `registers.json`, as taken by [ShadowRegisters::snapshot].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ShadowSnapshot {
+    words: [OtpWord; SNAPSHOT_WORDS.len()],
+}
+
+impl ShadowSnapshot {
+    /// The OTP word index and value of each captured shadow register, in ascending order of index.
+    pub fn words(&self) -> impl Iterator<Item = (OtpWordIndex, OtpWord)> + '_ {
+        SNAPSHOT_WORDS.into_iter().zip(self.words)
+    }
+}
+
 pub struct ShadowRegisters {
     device: Device<ShadowInterface>,
 }
@@ -179,6 +232,33 @@ impl ShadowRegisters {
             device: Device::new(ShadowInterface { _private: () }),
         }
     }
+
+    /// Capture all defined shadow registers, such that they can be put back with [ShadowRegisters::restore]
+    /// after an experiment that changes several of them.
+    pub fn snapshot(&mut self) -> ShadowSnapshot {
+        let mut interface = ShadowInterface { _private: () };
+        let mut words = [0; SNAPSHOT_WORDS.len()];
+        for (word, otp_word_i) in words.iter_mut().zip(SNAPSHOT_WORDS) {
+            let mut buf = [0u8; core::mem::size_of::<OtpWord>()];
+            // Every word in SNAPSHOT_WORDS has a shadow register, as checked by the tests.
+            if interface.read_register(otp_word_i, 32, &mut buf).is_ok() {
+                *word = OtpWord::from_le_bytes(buf);
+            }
+        }
+        ShadowSnapshot { words }
+    }
+
+    /// Write back all shadow registers captured in `snapshot`.
+    ///
+    /// Like writing any shadow register, this only affects the device until the shadow registers are reloaded from
+    /// the OTP fuses, such as on reset.
+    pub fn restore(&mut self, snapshot: &ShadowSnapshot) {
+        let mut interface = ShadowInterface { _private: () };
+        for (otp_word_i, word) in snapshot.words() {
+            // Every word in SNAPSHOT_WORDS has a shadow register, as checked by the tests.
+            let _ = interface.write_register(otp_word_i, 32, &word.to_le_bytes());
+        }
+    }
 }
 
 impl Default for ShadowRegisters {
@@ -252,7 +332,10 @@ impl core::fmt::Display for field_sets::Rkth {
 
 #[cfg(test)]
 mod tests {
+    use std::string::ToString;
+
     use super::*;
+    use crate::fake::{self, Rom};
 
     #[test]
     fn mapping() {
@@ -263,6 +346,40 @@ mod tests {
         assert_eq!(otp_to_shadow_offset(127), Ok(0x1FC)); // RKTH[7]
     }
 
+    #[test]
+    fn snapshot_words_are_shadowed() {
+        for otp_word_i in SNAPSHOT_WORDS {
+            assert!(otp_to_shadow_offset(otp_word_i).is_ok(), "{otp_word_i}");
+        }
+        assert!(SNAPSHOT_WORDS.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    /// Test that the snapshot covers exactly the words of the registers defined in `registers.json`.
+    #[test]
+    fn snapshot_words_match_manifest() {
+        let value = |line: &str| {
+            line.split(':')
+                .nth(1)
+                .unwrap()
+                .trim()
+                .trim_end_matches(',')
+                .parse::<u32>()
+                .unwrap()
+        };
+
+        let mut words = std::vec::Vec::new();
+        let mut address = None;
+        for line in include_str!("../registers.json").lines().map(str::trim) {
+            if line.starts_with("\"address\"") {
+                address = Some(value(line));
+            } else if line.starts_with("\"size_bits\"") {
+                let address = address.take().unwrap();
+                words.extend(address..address + value(line).div_ceil(32));
+            }
+        }
+        assert_eq!(words, SNAPSHOT_WORDS);
+    }
+
     /// Test reading registers that are smaller than the OTP fuse word.
     #[test]
     fn read_words_partial() {
@@ -331,4 +448,37 @@ mod tests {
         );
         assert_eq!(buf, generate_multiple_bytebuf());
     }
+
+    /// Test that the shadow registers reflect the fuses once reloaded, and that read-only fuses are not written.
+    #[test]
+    fn fuses_and_shadows() {
+        let mut rom = Rom::default();
+        for (word, value) in rom.fuses[120..128].iter_mut().zip(1..) {
+            *word = value;
+        }
+        let _installed = fake::install(rom);
+        let rkth = "0100000002000000030000000400000005000000060000000700000008000000";
+
+        let mut otp = Otp::init(0);
+        assert_eq!(OtpFuses::readonly(&mut otp).rkth().read().unwrap().to_string(), rkth);
+
+        let mut shadow = ShadowRegisters::new();
+        assert_eq!(shadow.rkth().read().unwrap().to_string(), "0".repeat(64));
+        otp.reload_shadow().unwrap();
+        assert_eq!(shadow.rkth().read().unwrap().to_string(), rkth);
+        assert_eq!(
+            shadow.snapshot().words().find(|(otp_word_i, _)| *otp_word_i == 127),
+            Some((127, 8))
+        );
+
+        let mut interface = OtpInterface {
+            otp: &mut otp,
+            allow_write: false,
+            mode_locked: false,
+        };
+        assert!(matches!(
+            interface.write_register(96, 32, &[1, 0, 0, 0]),
+            Err(OtpError::WriteNotAllowed)
+        ));
+    }
 }
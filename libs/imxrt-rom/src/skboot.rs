@@ -1,10 +1,18 @@
 //! Interface to the skboot ROM function 'skboot_authenticate'.
 
+#[cfg(not(any(test, feature = "_test")))]
+use core::ptr::addr_of_mut;
 use core::ptr::{null, null_mut};
 
+#[cfg(not(any(test, feature = "_test")))]
+use cortex_m::interrupt::InterruptNumber;
+#[cfg(not(any(test, feature = "_test")))]
+use cortex_m::peripheral::{DWT, NVIC};
 use defmt_or_log::error;
 #[cfg(feature = "rt")]
 use embassy_imxrt::pac::interrupt;
+#[cfg(not(any(test, feature = "_test")))]
+use embassy_imxrt::pac::Interrupt;
 
 use crate::api::{api_table, BootStatus, KbAuthenticate, KbOperation, KbOptions, KbSettings, KbStatus, SecureBool};
 
@@ -25,33 +33,122 @@ pub enum AuthenticateError {
     BootStatusUnknown,
     /// The function passed an undefined value as `is_sign_verified`` value.
     IsSignVerifiedUnknown,
+    /// The build number of the image is lower than allowed, as reported with [KbStatus::RollbackBlocked].
+    Rollback,
 }
 
-/// Perform ROM authentication of an image.
+/// Size of the work buffer in words used by [skboot_authenticate], which is known to suffice for the ROM.
+pub const DEFAULT_BUFFER_WORDS: usize = 1024;
+
+/// Number of exception and interrupt vectors in the temporary vector table of [HashcryptIrq::Isolated].
+#[cfg(not(any(test, feature = "_test")))]
+const ISOLATED_VECTORS: usize = 16 + 128;
+
+/// Vector table in RAM used during [HashcryptIrq::Isolated] authentication.
+#[cfg(not(any(test, feature = "_test")))]
+#[repr(C, align(1024))]
+struct VectorTable([u32; ISOLATED_VECTORS]);
+
+#[cfg(not(any(test, feature = "_test")))]
+static mut ISOLATED_VECTOR_TABLE: VectorTable = VectorTable([0; ISOLATED_VECTORS]);
+
+/// How the HASHCRYPT interrupt raised by the ROM whilst authenticating reaches the ROM interrupt handler.
+///
+/// The ROM only completes hashing from its interrupt handler, and unmasks the HASHCRYPT interrupt to that end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum HashcryptIrq {
+    /// Through the HASHCRYPT vector of the application, as defined by this crate with the `rt` feature.
+    ///
+    /// The interrupt is masked again afterwards.
+    #[default]
+    Vector,
+    /// Through a temporary vector table in RAM pointing straight at the ROM interrupt handler.
+    ///
+    /// Does not require a HASHCRYPT vector, and leaves the vector table and the HASHCRYPT interrupt mask of the
+    /// application as they were, for applications that manage the HASHCRYPT interrupt themselves.
+    /// Uses a static vector table of 1 KiB, and can thus not be used from multiple contexts at once.
+    Isolated,
+}
+
+/// Duration of the ROM calls performed to authenticate an image, in core clock cycles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Timing {
+    /// Initialization of the ROM API session.
+    pub init_cycles: u32,
+    /// Authentication of the image itself.
+    pub authenticate_cycles: u32,
+    /// Deinitialization of the ROM API session.
+    pub deinit_cycles: u32,
+}
+
+impl Timing {
+    pub fn total_cycles(&self) -> u32 {
+        self.init_cycles + self.authenticate_cycles + self.deinit_cycles
+    }
+
+    /// Total duration in microseconds at a core clock of `core_clock_hz`.
+    pub fn total_micros(&self, core_clock_hz: u32) -> u64 {
+        self.total_cycles() as u64 * 1_000_000 / core_clock_hz as u64
+    }
+}
+
+/// Perform ROM authentication of an image, using a work buffer of [DEFAULT_BUFFER_WORDS] words on the stack.
 ///
 /// If RHK is provided it will use that hash to verify the certificate chain instead.
+/// Use [skboot_authenticate_with_buffer] to keep the work buffer off the stack.
 #[allow(dead_code)]
 #[allow(clippy::not_unsafe_ptr_arg_deref)]
+#[inline(never)]
 pub fn skboot_authenticate(
     start: *const u32,
     max_image_length: u32,
     rhk: Option<[u8; 32]>,
-) -> Result<(), AuthenticateError> {
+    irq: HashcryptIrq,
+) -> Result<Timing, AuthenticateError> {
+    let mut user_buf = [0u32; DEFAULT_BUFFER_WORDS];
+    skboot_authenticate_with_buffer(start, max_image_length, rhk, irq, &mut user_buf)
+}
+
+/// Perform ROM authentication of an image, using `buffer` as work buffer for the ROM.
+///
+/// If RHK is provided it will use that hash to verify the certificate chain instead.
+/// Enables the DWT cycle counter to report the [Timing] of the ROM calls.
+///
+/// The ROM keeps its session context in `buffer`, of which at most `u32::MAX` bytes are used.
+/// [DEFAULT_BUFFER_WORDS] words are known to suffice, initialization of the ROM API fails with
+/// [AuthenticateError::Fail] when the buffer is too small. The buffer must not overlap with the
+/// RAM reserved by the ROM for its global variables, from 0x1000_A000 to 0x1001_2000.
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
+pub fn skboot_authenticate_with_buffer(
+    start: *const u32,
+    max_image_length: u32,
+    rhk: Option<[u8; 32]>,
+    irq: HashcryptIrq,
+    buffer: &mut [u32],
+) -> Result<Timing, AuthenticateError> {
     // Note:
     // The ROM reserved space for global variables in RAM on this device is:
     // 0x1001_2000 to 0x1000_A000
 
     // 43.9 Secure ROM API page 1282 of RT6xx User manual
 
+    enable_cycle_counter();
+
+    let mut timing = Timing::default();
     let mut session_ref = null_mut();
-    let mut user_buf = [0u32; 1024];
 
-    let user_rhk = rhk.map(|rhk| rhk.as_ptr() as *const u32).unwrap_or(null());
+    // The ROM reads the hash as words, so pass an aligned copy that lives until the ROM is done with it.
+    let rhk_words: Option<[u32; 8]> = rhk.map(|rhk| {
+        core::array::from_fn(|i| u32::from_ne_bytes([rhk[4 * i], rhk[4 * i + 1], rhk[4 * i + 2], rhk[4 * i + 3]]))
+    });
+    let user_rhk = rhk_words.as_ref().map(|words| words.as_ptr()).unwrap_or(null());
 
     let options = KbOptions {
         version: 1,
-        buffer: user_buf.as_mut_ptr() as *mut u8,
-        buffer_length: core::mem::size_of_val(&user_buf) as u32,
+        buffer: buffer.as_mut_ptr() as *mut u8,
+        buffer_length: u32::try_from(core::mem::size_of_val(buffer)).unwrap_or(u32::MAX),
         op: KbOperation::AuthenticateImage,
         settings: KbSettings {
             authenticate: KbAuthenticate {
@@ -63,23 +160,26 @@ pub fn skboot_authenticate(
         },
     };
 
+    let cycles = cycle_count();
     let status = unsafe { (api_table().iap_driver.init)(&mut session_ref, &options) };
+    timing.init_cycles = cycle_count().wrapping_sub(cycles);
     if status != KbStatus::Success as u32 {
         error!("kinit failed with {:?}", status);
-        return Err(AuthenticateError::Fail);
+        return Err(session_error(status));
     }
 
     // Placeholder value that will be mutated by skboot_authenticate.
     let mut is_sign_verified: u32 = 0xffffffff;
-    let result = unsafe { (api_table().skboot.authenticate)(start, &mut is_sign_verified) };
-
-    // ROM API keeps HASHCRYPT unmasked
-    cortex_m::peripheral::NVIC::mask(embassy_imxrt::pac::Interrupt::HASHCRYPT);
+    let cycles = cycle_count();
+    let result = authenticate(start, &mut is_sign_verified, irq);
+    timing.authenticate_cycles = cycle_count().wrapping_sub(cycles);
 
+    let cycles = cycle_count();
     let status = unsafe { (api_table().iap_driver.deinit)(session_ref) };
+    timing.deinit_cycles = cycle_count().wrapping_sub(cycles);
     if status != KbStatus::Success as u32 {
         error!("kdeinit failed with {:?}", status);
-        return Err(AuthenticateError::Fail);
+        return Err(session_error(status));
     }
 
     let status = BootStatus::try_from(result).map_err(|()| AuthenticateError::BootStatusUnknown)?;
@@ -88,7 +188,7 @@ pub fn skboot_authenticate(
 
     match status {
         BootStatus::Success => match is_sign_verified {
-            Ok(SecureBool::TrackerVerified) => Ok(()),
+            Ok(SecureBool::TrackerVerified) => Ok(timing),
             Ok(SecureBool::False) => Err(AuthenticateError::SignUnverified),
             _ => Err(AuthenticateError::SignUnknown),
         },
@@ -99,9 +199,152 @@ pub fn skboot_authenticate(
     }
 }
 
+/// Error for a failing `status` of the ROM API session, distinguishing a rollback from other failures.
+fn session_error(status: u32) -> AuthenticateError {
+    if status == KbStatus::RollbackBlocked as u32 {
+        AuthenticateError::Rollback
+    } else {
+        AuthenticateError::Fail
+    }
+}
+
+/// Enable the DWT cycle counter read by [cycle_count].
+#[cfg(not(any(test, feature = "_test")))]
+fn enable_cycle_counter() {
+    // Note(unsafe): only the cycle counter is enabled, which is not used otherwise.
+    let mut cp = unsafe { cortex_m::Peripherals::steal() };
+    cp.DCB.enable_trace();
+    cp.DWT.enable_cycle_counter();
+}
+
+#[cfg(not(any(test, feature = "_test")))]
+fn cycle_count() -> u32 {
+    DWT::cycle_count()
+}
+
+/// Call the ROM to authenticate the image at `start`, routing the HASHCRYPT interrupt it raises as per `irq`.
+#[cfg(not(any(test, feature = "_test")))]
+fn authenticate(start: *const u32, is_sign_verified: &mut u32, irq: HashcryptIrq) -> u32 {
+    match irq {
+        HashcryptIrq::Vector => {
+            let result = unsafe { (api_table().skboot.authenticate)(start, is_sign_verified) };
+
+            // ROM API keeps HASHCRYPT unmasked
+            NVIC::mask(Interrupt::HASHCRYPT);
+            result
+        }
+        HashcryptIrq::Isolated => {
+            // Note(unsafe): only the vector table is replaced, and restored before returning.
+            let mut cp = unsafe { cortex_m::Peripherals::steal() };
+            with_isolated_vectors(&mut cp, || unsafe {
+                (api_table().skboot.authenticate)(start, is_sign_verified)
+            })
+        }
+    }
+}
+
+/// The [fake](crate::fake) ROM has no cycle counter to enable.
+#[cfg(any(test, feature = "_test"))]
+fn enable_cycle_counter() {}
+
+/// The [fake](crate::fake) ROM takes no time.
+#[cfg(any(test, feature = "_test"))]
+fn cycle_count() -> u32 {
+    0
+}
+
+/// Call the [fake](crate::fake) ROM to authenticate the image at `start`, which raises no interrupts.
+#[cfg(any(test, feature = "_test"))]
+fn authenticate(start: *const u32, is_sign_verified: &mut u32, _irq: HashcryptIrq) -> u32 {
+    unsafe { (api_table().skboot.authenticate)(start, is_sign_verified) }
+}
+
+/// Run `f` with the HASHCRYPT interrupt routed to the ROM interrupt handler through a temporary vector table.
+///
+/// Restores the vector table and HASHCRYPT interrupt mask afterwards, discarding any interrupt left pending by the ROM.
+#[cfg(not(any(test, feature = "_test")))]
+fn with_isolated_vectors<R>(cp: &mut cortex_m::Peripherals, f: impl FnOnce() -> R) -> R {
+    let was_enabled = NVIC::is_enabled(Interrupt::HASHCRYPT);
+    let vtor = cp.SCB.vtor.read();
+
+    // Note(unsafe): the table is only used for the duration of this function, which is not reentered
+    // as the ROM authentication does not return before completion.
+    let table = unsafe { &mut *addr_of_mut!(ISOLATED_VECTOR_TABLE) };
+    unsafe { core::ptr::copy_nonoverlapping(vtor as *const u32, table.0.as_mut_ptr(), ISOLATED_VECTORS) };
+    table.0[16 + Interrupt::HASHCRYPT.number() as usize] = api_table().skboot.hashcrypt_irq_handler as usize as u32;
+
+    cortex_m::asm::dsb();
+    unsafe { cp.SCB.vtor.write(table.0.as_ptr() as u32) };
+    cortex_m::asm::isb();
+
+    let result = f();
+
+    NVIC::mask(Interrupt::HASHCRYPT);
+    NVIC::unpend(Interrupt::HASHCRYPT);
+    cortex_m::asm::dsb();
+    unsafe { cp.SCB.vtor.write(vtor) };
+    cortex_m::asm::isb();
+    if was_enabled {
+        // Note(unsafe): restores the mask as configured by the application.
+        unsafe { NVIC::unmask(Interrupt::HASHCRYPT) };
+    }
+
+    result
+}
+
 #[cfg(feature = "rt")]
 #[interrupt]
 #[allow(non_snake_case)]
 fn HASHCRYPT() {
     unsafe { (api_table().skboot.hashcrypt_irq_handler)() }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake::{self, Rom};
+
+    fn authenticate(rhk: Option<[u8; 32]>) -> Result<Timing, AuthenticateError> {
+        let image = [0u32; 16];
+        let mut buffer = [0u32; 16];
+        skboot_authenticate_with_buffer(image.as_ptr(), 64, rhk, HashcryptIrq::Vector, &mut buffer)
+    }
+
+    #[test]
+    fn authenticate_outcomes() {
+        let installed = fake::install(Rom::default());
+        assert!(authenticate(None).is_ok());
+        assert_eq!(installed.with(|rom| rom.user_rhk), None);
+
+        assert!(authenticate(Some([0xab; 32])).is_ok());
+        assert_eq!(installed.with(|rom| rom.user_rhk), Some([0xabab_abab; 8]));
+
+        installed.with(|rom| rom.is_verified = 0x5aa55aa5);
+        assert!(matches!(authenticate(None), Err(AuthenticateError::SignUnverified)));
+        installed.with(|rom| rom.is_verified = 0);
+        assert!(matches!(
+            authenticate(None),
+            Err(AuthenticateError::IsSignVerifiedUnknown)
+        ));
+
+        installed.with(|rom| rom.boot_status = 0xc3c35a5a);
+        assert!(matches!(
+            authenticate(None),
+            Err(AuthenticateError::KeyStoreMarkerInvalid)
+        ));
+        installed.with(|rom| rom.boot_status = 0);
+        assert!(matches!(authenticate(None), Err(AuthenticateError::BootStatusUnknown)));
+    }
+
+    #[test]
+    fn authenticate_session_fails() {
+        let installed = fake::install(Rom {
+            iap_status: KbStatus::Fail as u32,
+            ..Rom::default()
+        });
+        assert!(matches!(authenticate(None), Err(AuthenticateError::Fail)));
+
+        installed.with(|rom| rom.iap_status = KbStatus::RollbackBlocked as u32);
+        assert!(matches!(authenticate(None), Err(AuthenticateError::Rollback)));
+    }
+}
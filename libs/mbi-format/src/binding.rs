@@ -0,0 +1,81 @@
+/// Slot an image is intended for, appended to the data of the image before signing.
+///
+/// Unlike the metadata [Trailer](crate::Trailer), the binding is covered by the signature. It ends right in front of
+/// the cert block, at the header offset from the [Ivt](crate::Ivt).
+/// Layout: [SlotBinding::MAGIC], the slot or [SlotBinding::ANY], and 3 reserved bytes of 0xFF.
+///
+/// Mostly useful for images executed in place, which only run from the slot they are linked for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SlotBinding {
+    /// Index of the slot the image is intended for, or [None] if it may be placed in any slot.
+    pub slot: Option<u8>,
+}
+
+impl SlotBinding {
+    /// Magic at the start of the binding.
+    pub const MAGIC: [u8; 4] = *b"SLOT";
+    /// Length of the encoded binding.
+    pub const LEN: usize = 8;
+    /// Encoded slot of an image that may be placed in any slot.
+    pub const ANY: u8 = 0xff;
+
+    /// Offset of the binding from the start of an image with the cert block at `header_offset`.
+    pub const fn offset(header_offset: usize) -> Option<usize> {
+        header_offset.checked_sub(Self::LEN)
+    }
+
+    /// Decode the binding from `data`, [None] if the image has no binding.
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        if data.len() != Self::LEN || data[..4] != Self::MAGIC {
+            return None;
+        }
+        Some(Self {
+            slot: match data[4] {
+                Self::ANY => None,
+                slot => Some(slot),
+            },
+        })
+    }
+
+    /// Encode to append to the data of an image.
+    pub fn to_bytes(&self) -> [u8; Self::LEN] {
+        let mut buf = [0xff; Self::LEN];
+        buf[..4].copy_from_slice(&Self::MAGIC);
+        buf[4] = self.slot.unwrap_or(Self::ANY);
+        buf
+    }
+
+    /// Whether an image with this binding may be placed in `slot`.
+    pub fn allows(&self, slot: u8) -> bool {
+        self.slot.is_none_or(|bound| bound == slot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binding_roundtrip() {
+        for binding in [SlotBinding { slot: Some(1) }, SlotBinding { slot: None }] {
+            assert_eq!(SlotBinding::parse(&binding.to_bytes()), Some(binding));
+        }
+        assert_eq!(SlotBinding { slot: Some(2) }.to_bytes(), *b"SLOT\x02\xff\xff\xff");
+    }
+
+    #[test]
+    fn binding_missing() {
+        assert_eq!(SlotBinding::parse(&[0xff; SlotBinding::LEN]), None);
+        assert_eq!(SlotBinding::parse(&SlotBinding::MAGIC), None);
+        assert_eq!(SlotBinding::offset(4), None);
+        assert_eq!(SlotBinding::offset(0x1000), Some(0xff8));
+    }
+
+    #[test]
+    fn binding_allows() {
+        assert!(SlotBinding { slot: Some(1) }.allows(1));
+        assert!(!SlotBinding { slot: Some(1) }.allows(0));
+        assert!(SlotBinding { slot: None }.allows(0));
+    }
+}
@@ -0,0 +1,116 @@
+use crate::{read_u16, read_u32, write_u16, write_u32, BufferTooSmall};
+
+/// Header of the certificate block, see UM11147 Fig 239
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct CertBlockHeader {
+    pub signature: u32,
+    pub header_major_version: u16,
+    pub header_minor_version: u16,
+    pub header_length: u32,
+    pub flags: u32,
+    pub build_number: u32,
+    /// Length of the signed part of the image, which ends with the cert block.
+    pub total_image_length: u32,
+    pub certificate_count: u32,
+    pub certificate_table_length: u32,
+}
+
+impl CertBlockHeader {
+    /// Length of the header.
+    pub const LEN: usize = 0x20;
+
+    /// Parse the header from the start of `data`.
+    pub fn parse(data: &[u8]) -> Result<Self, BufferTooSmall> {
+        if data.len() < Self::LEN {
+            return Err(BufferTooSmall);
+        }
+
+        Ok(Self {
+            signature: read_u32(data, 0x00),
+            header_major_version: read_u16(data, 0x04),
+            header_minor_version: read_u16(data, 0x06),
+            header_length: read_u32(data, 0x08),
+            flags: read_u32(data, 0x0C),
+            build_number: read_u32(data, 0x10),
+            total_image_length: read_u32(data, 0x14),
+            certificate_count: read_u32(data, 0x18),
+            certificate_table_length: read_u32(data, 0x1C),
+        })
+    }
+
+    /// Write the header to the start of `data`.
+    pub fn write(&self, data: &mut [u8]) -> Result<(), BufferTooSmall> {
+        if data.len() < Self::LEN {
+            return Err(BufferTooSmall);
+        }
+
+        write_u32(data, 0x00, self.signature);
+        write_u16(data, 0x04, self.header_major_version);
+        write_u16(data, 0x06, self.header_minor_version);
+        write_u32(data, 0x08, self.header_length);
+        write_u32(data, 0x0C, self.flags);
+        write_u32(data, 0x10, self.build_number);
+        write_u32(data, 0x14, self.total_image_length);
+        write_u32(data, 0x18, self.certificate_count);
+        write_u32(data, 0x1C, self.certificate_table_length);
+        Ok(())
+    }
+
+    /// Offset of the root key hashes relative to the start of the cert block, which follow the certificate table.
+    pub fn root_key_hashes_offset(&self) -> usize {
+        self.header_length as usize + self.certificate_table_length as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cert_block_header_offsets() {
+        let mut data = [0u8; CertBlockHeader::LEN];
+        data[0x00..0x04].copy_from_slice(b"cert");
+        data[0x04..0x06].copy_from_slice(&1u16.to_le_bytes());
+        data[0x06..0x08].copy_from_slice(&2u16.to_le_bytes());
+        data[0x08..0x0C].copy_from_slice(&0x20u32.to_le_bytes());
+        data[0x0C..0x10].copy_from_slice(&0u32.to_le_bytes());
+        data[0x10..0x14].copy_from_slice(&7u32.to_le_bytes());
+        data[0x14..0x18].copy_from_slice(&0x8000u32.to_le_bytes());
+        data[0x18..0x1C].copy_from_slice(&1u32.to_le_bytes());
+        data[0x1C..0x20].copy_from_slice(&0x400u32.to_le_bytes());
+
+        let header = CertBlockHeader::parse(&data).unwrap();
+        assert_eq!(
+            header,
+            CertBlockHeader {
+                signature: u32::from_le_bytes(*b"cert"),
+                header_major_version: 1,
+                header_minor_version: 2,
+                header_length: 0x20,
+                flags: 0,
+                build_number: 7,
+                total_image_length: 0x8000,
+                certificate_count: 1,
+                certificate_table_length: 0x400,
+            }
+        );
+        assert_eq!(header.root_key_hashes_offset(), 0x420);
+
+        let mut written = [0xFFu8; CertBlockHeader::LEN];
+        header.write(&mut written).unwrap();
+        assert_eq!(written, data);
+    }
+
+    #[test]
+    fn cert_block_header_too_small() {
+        let data = [0u8; CertBlockHeader::LEN];
+        let header = CertBlockHeader::parse(&data).unwrap();
+
+        assert_eq!(
+            CertBlockHeader::parse(&data[..CertBlockHeader::LEN - 1]),
+            Err(BufferTooSmall)
+        );
+        assert_eq!(header.write(&mut [0u8; CertBlockHeader::LEN - 1]), Err(BufferTooSmall));
+    }
+}
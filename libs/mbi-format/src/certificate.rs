@@ -0,0 +1,230 @@
+use crate::{read_u32, CertBlockHeader};
+
+/// DER tag of a SEQUENCE.
+const TAG_SEQUENCE: u8 = 0x30;
+/// DER tag of an INTEGER.
+const TAG_INTEGER: u8 = 0x02;
+/// DER tag of a BIT STRING.
+const TAG_BIT_STRING: u8 = 0x03;
+/// DER tag of an OBJECT IDENTIFIER.
+const TAG_OID: u8 = 0x06;
+/// DER tag of the explicit version field of a certificate.
+const TAG_VERSION: u8 = 0xA0;
+/// Encoded object identifier of rsaEncryption, 1.2.840.113549.1.1.1.
+const OID_RSA_ENCRYPTION: &[u8] = &[0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x01, 0x01];
+
+/// Failure to parse a certificate from the certificate table of a cert block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CertificateError {
+    /// A certificate or one of its fields exceeds the data.
+    Truncated,
+    /// A certificate is not DER encoded as expected, or does not hold an RSA key.
+    Malformed,
+}
+
+/// Iterator over the DER encoded X.509 certificates in the certificate table, root certificate first.
+///
+/// See [CertBlockHeader::certificates].
+pub struct Certificates<'a> {
+    table: &'a [u8],
+    remaining: u32,
+}
+
+impl CertBlockHeader {
+    /// Certificates in the certificate table of `cert_block`, which starts with this header.
+    pub fn certificates<'a>(&self, cert_block: &'a [u8]) -> Certificates<'a> {
+        let table = cert_block
+            .get(self.header_length as usize..self.root_key_hashes_offset())
+            .unwrap_or(&[]);
+        Certificates {
+            table,
+            remaining: self.certificate_count,
+        }
+    }
+}
+
+impl<'a> Iterator for Certificates<'a> {
+    type Item = Result<&'a [u8], CertificateError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+
+        // Each certificate is preceded by its length.
+        let Some((len, rest)) = self.table.split_at_checked(4) else {
+            self.remaining = 0;
+            return Some(Err(CertificateError::Truncated));
+        };
+        let Some((certificate, rest)) = rest.split_at_checked(read_u32(len, 0) as usize) else {
+            self.remaining = 0;
+            return Some(Err(CertificateError::Truncated));
+        };
+        self.table = rest;
+        Some(Ok(certificate))
+    }
+}
+
+/// Split the DER element at the start of `data` into its tag, its value and the data following it.
+fn element(data: &[u8]) -> Result<(u8, &[u8], &[u8]), CertificateError> {
+    let [tag, len, rest @ ..] = data else {
+        return Err(CertificateError::Truncated);
+    };
+
+    let (len, rest) = if len & 0x80 == 0 {
+        (*len as usize, rest)
+    } else {
+        // Long form, in which the low bits denote the number of bytes holding the length.
+        let len_bytes = (len & 0x7F) as usize;
+        if len_bytes == 0 || len_bytes > 4 {
+            return Err(CertificateError::Malformed);
+        }
+        let (len, rest) = rest.split_at_checked(len_bytes).ok_or(CertificateError::Truncated)?;
+        (len.iter().fold(0usize, |acc, b| (acc << 8) | *b as usize), rest)
+    };
+
+    let (value, rest) = rest.split_at_checked(len).ok_or(CertificateError::Truncated)?;
+    Ok((*tag, value, rest))
+}
+
+/// Like [element], but requires the element to have `tag`.
+fn expect(data: &[u8], tag: u8) -> Result<(&[u8], &[u8]), CertificateError> {
+    match element(data)? {
+        (actual, value, rest) if actual == tag => Ok((value, rest)),
+        _ => Err(CertificateError::Malformed),
+    }
+}
+
+/// Strip the leading zeroes from an unsigned big-endian integer.
+fn strip_leading_zeroes(integer: &[u8]) -> &[u8] {
+    let start = integer.iter().position(|b| *b != 0).unwrap_or(integer.len());
+    &integer[start..]
+}
+
+/// RSA public key of a certificate, as big-endian integers without leading zeroes.
+///
+/// This is the form in which the ROM hashes a root certificate key for the root key table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RsaPublicKey<'a> {
+    pub modulus: &'a [u8],
+    pub exponent: &'a [u8],
+}
+
+impl<'a> RsaPublicKey<'a> {
+    /// Extract the subject public key of a DER encoded X.509 certificate.
+    ///
+    /// Only walks the structure up to the key, and does not verify anything else of the certificate.
+    pub fn from_certificate(der: &'a [u8]) -> Result<Self, CertificateError> {
+        let (certificate, _) = expect(der, TAG_SEQUENCE)?;
+        let (tbs, _) = expect(certificate, TAG_SEQUENCE)?;
+
+        // Skip the optional version, and the serial number, signature algorithm, issuer, validity and subject.
+        let fields = match element(tbs)? {
+            (TAG_VERSION, _, rest) => rest,
+            _ => tbs,
+        };
+        let (_, mut fields) = expect(fields, TAG_INTEGER)?;
+        for _ in 0..4 {
+            let (_, rest) = expect(fields, TAG_SEQUENCE)?;
+            fields = rest;
+        }
+
+        let (public_key_info, _) = expect(fields, TAG_SEQUENCE)?;
+        let (algorithm, public_key_info) = expect(public_key_info, TAG_SEQUENCE)?;
+        let (oid, _) = expect(algorithm, TAG_OID)?;
+        if oid != OID_RSA_ENCRYPTION {
+            return Err(CertificateError::Malformed);
+        }
+
+        // The key is wrapped in a bit string without unused bits.
+        let (bits, _) = expect(public_key_info, TAG_BIT_STRING)?;
+        let [0, key @ ..] = bits else {
+            return Err(CertificateError::Malformed);
+        };
+        let (key, _) = expect(key, TAG_SEQUENCE)?;
+        let (modulus, key) = expect(key, TAG_INTEGER)?;
+        let (exponent, _) = expect(key, TAG_INTEGER)?;
+
+        Ok(Self {
+            modulus: strip_leading_zeroes(modulus),
+            exponent: strip_leading_zeroes(exponent),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Self-signed RSA-2048 certificate with exponent 65537.
+    const ROOT_CERTIFICATE: &[u8] = include_bytes!("../testdata/root-rsa2048.der");
+
+    #[test]
+    fn rsa_public_key() {
+        let key = RsaPublicKey::from_certificate(ROOT_CERTIFICATE).unwrap();
+        assert_eq!(key.modulus.len(), 256);
+        assert_ne!(key.modulus[0], 0);
+        assert_eq!(key.exponent, [0x01, 0x00, 0x01]);
+
+        // The modulus is encoded with a leading zero, as its top bit is set.
+        let position = ROOT_CERTIFICATE
+            .windows(key.modulus.len())
+            .position(|window| window == key.modulus)
+            .unwrap();
+        assert_eq!(ROOT_CERTIFICATE[position - 1], 0);
+    }
+
+    #[test]
+    fn rsa_public_key_malformed() {
+        for len in [0, 1, 4, 100, ROOT_CERTIFICATE.len() - 300] {
+            assert_eq!(
+                RsaPublicKey::from_certificate(&ROOT_CERTIFICATE[..len]),
+                Err(CertificateError::Truncated)
+            );
+        }
+
+        let mut not_a_sequence = ROOT_CERTIFICATE.to_vec();
+        not_a_sequence[0] = TAG_INTEGER;
+        assert_eq!(
+            RsaPublicKey::from_certificate(&not_a_sequence),
+            Err(CertificateError::Malformed)
+        );
+    }
+
+    #[test]
+    fn certificate_table() {
+        let mut cert_block = std::vec![0u8; CertBlockHeader::LEN];
+        for certificate in [&[0xAA; 3][..], &[0xBB; 5][..]] {
+            cert_block.extend((certificate.len() as u32).to_le_bytes());
+            cert_block.extend(certificate);
+        }
+        let header = CertBlockHeader {
+            signature: u32::from_le_bytes(*b"cert"),
+            header_major_version: 1,
+            header_minor_version: 0,
+            header_length: CertBlockHeader::LEN as u32,
+            flags: 0,
+            build_number: 0,
+            total_image_length: 0,
+            certificate_count: 2,
+            certificate_table_length: (cert_block.len() - CertBlockHeader::LEN) as u32,
+        };
+
+        let mut certificates = header.certificates(&cert_block);
+        assert_eq!(certificates.next(), Some(Ok(&[0xAA; 3][..])));
+        assert_eq!(certificates.next(), Some(Ok(&[0xBB; 5][..])));
+        assert_eq!(certificates.next(), None);
+
+        // A table that is shorter than its certificates claim.
+        let header = CertBlockHeader {
+            certificate_table_length: header.certificate_table_length - 1,
+            ..header
+        };
+        let mut certificates = header.certificates(&cert_block);
+        assert_eq!(certificates.next(), Some(Ok(&[0xAA; 3][..])));
+        assert_eq!(certificates.next(), Some(Err(CertificateError::Truncated)));
+        assert_eq!(certificates.next(), None);
+    }
+}
@@ -0,0 +1,247 @@
+use crate::{read_u32, write_u32, BufferTooSmall};
+
+/// Image header, which is the vector table of the image with some reserved entries repurposed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Ivt {
+    /// Length of the entire image, including the header, data, cert block and signature.
+    pub image_len: u32,
+    /// Image type bitset, see [ImageType].
+    pub image_type: u32,
+    /// Offset of the cert block, which equals the length of the header and data.
+    pub header_offset: u32,
+    /// Address the image is loaded to.
+    pub load_addr: u32,
+}
+
+impl Ivt {
+    /// Length of the header.
+    pub const LEN: usize = 0x40;
+
+    const IMAGE_LEN_OFFSET: usize = 0x20;
+    const IMAGE_TYPE_OFFSET: usize = 0x24;
+    const HEADER_OFFSET_OFFSET: usize = 0x28;
+    const LOAD_ADDR_OFFSET: usize = 0x34;
+
+    /// Parse the header from the start of `data`.
+    pub fn parse(data: &[u8]) -> Result<Self, BufferTooSmall> {
+        if data.len() < Self::LEN {
+            return Err(BufferTooSmall);
+        }
+
+        Ok(Self {
+            image_len: read_u32(data, Self::IMAGE_LEN_OFFSET),
+            image_type: read_u32(data, Self::IMAGE_TYPE_OFFSET),
+            header_offset: read_u32(data, Self::HEADER_OFFSET_OFFSET),
+            load_addr: read_u32(data, Self::LOAD_ADDR_OFFSET),
+        })
+    }
+
+    /// Write the header fields into the vector table at the start of `data`, leaving all other entries untouched.
+    pub fn write(&self, data: &mut [u8]) -> Result<(), BufferTooSmall> {
+        if data.len() < Self::LEN {
+            return Err(BufferTooSmall);
+        }
+
+        write_u32(data, Self::IMAGE_LEN_OFFSET, self.image_len);
+        write_u32(data, Self::IMAGE_TYPE_OFFSET, self.image_type);
+        write_u32(data, Self::HEADER_OFFSET_OFFSET, self.header_offset);
+        write_u32(data, Self::LOAD_ADDR_OFFSET, self.load_addr);
+        Ok(())
+    }
+
+    /// Get the [ImageKind] encoded in the image type, if known.
+    pub fn image_kind(&self) -> Option<ImageKind> {
+        ImageKind::from_u8(self.image_type as u8)
+    }
+}
+
+/// Bitset for the image type header field
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ImageType {
+    pub key_store_included: bool,
+    pub tz_m_image_type: TrustZone,
+    pub tz_m_preset: TrustZonePreset,
+    pub enable_hw_user_mode_keys: bool,
+    pub image_kind: ImageKind,
+}
+
+impl ImageType {
+    pub const fn new(image_kind: ImageKind) -> Self {
+        Self {
+            key_store_included: false,
+            tz_m_image_type: TrustZone::Enabled,
+            tz_m_preset: TrustZonePreset::NotIncluded,
+            enable_hw_user_mode_keys: false,
+            image_kind,
+        }
+    }
+
+    pub const fn as_u32(&self) -> u32 {
+        let mut out = 0;
+        let Self {
+            key_store_included,
+            tz_m_image_type,
+            tz_m_preset,
+            enable_hw_user_mode_keys,
+            image_kind,
+        } = *self;
+
+        out |= (key_store_included as u32 & 1) << 15;
+        out |= (tz_m_image_type as u32 & 1) << 14;
+        out |= (tz_m_preset as u32 & 1) << 13;
+        out |= (enable_hw_user_mode_keys as u32 & 1) << 12;
+        out |= image_kind as u32;
+
+        out
+    }
+
+    /// Break the image type header field into its flags, if the image kind is known.
+    pub fn from_u32(value: u32) -> Option<Self> {
+        let bit = |n: u32| value & (1 << n) != 0;
+
+        Some(Self {
+            key_store_included: bit(15),
+            tz_m_image_type: if bit(14) {
+                TrustZone::Disabled
+            } else {
+                TrustZone::Enabled
+            },
+            tz_m_preset: if bit(13) {
+                TrustZonePreset::Included
+            } else {
+                TrustZonePreset::NotIncluded
+            },
+            enable_hw_user_mode_keys: bit(12),
+            image_kind: ImageKind::from_u8(value as u8)?,
+        })
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ImageKind {
+    Plain = 0,
+    /// Note: This uses the Encrypted image layout, but omits the Enc. Image Header?
+    PlainSigned = 1,
+    PlainWithCrc = 2,
+    EncryptedSigned = 3,
+    XipPlainSigned = 4,
+    XipPlainWithCrc = 5,
+}
+
+impl ImageKind {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        Some(match value {
+            0 => Self::Plain,
+            1 => Self::PlainSigned,
+            2 => Self::PlainWithCrc,
+            3 => Self::EncryptedSigned,
+            4 => Self::XipPlainSigned,
+            5 => Self::XipPlainWithCrc,
+            _ => return None,
+        })
+    }
+
+    pub fn has_hmac(&self) -> bool {
+        match self {
+            ImageKind::Plain | ImageKind::PlainWithCrc | ImageKind::XipPlainSigned | ImageKind::XipPlainWithCrc => {
+                false
+            }
+            ImageKind::PlainSigned | ImageKind::EncryptedSigned => true,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TrustZone {
+    Enabled = 0,
+    Disabled = 1,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TrustZonePreset {
+    NotIncluded = 0,
+    Included = 1,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ivt_offsets() {
+        let mut data = [0u8; Ivt::LEN];
+        data[0x20..0x24].copy_from_slice(&0x1234u32.to_le_bytes());
+        data[0x24..0x28].copy_from_slice(&0x0004u32.to_le_bytes());
+        data[0x28..0x2C].copy_from_slice(&0x1000u32.to_le_bytes());
+        data[0x34..0x38].copy_from_slice(&0x1017_0000u32.to_le_bytes());
+
+        let ivt = Ivt::parse(&data).unwrap();
+        assert_eq!(
+            ivt,
+            Ivt {
+                image_len: 0x1234,
+                image_type: 0x0004,
+                header_offset: 0x1000,
+                load_addr: 0x1017_0000,
+            }
+        );
+        assert_eq!(ivt.image_kind(), Some(ImageKind::XipPlainSigned));
+
+        let mut written = [0u8; Ivt::LEN];
+        ivt.write(&mut written).unwrap();
+        assert_eq!(written, data);
+    }
+
+    #[test]
+    fn ivt_write_preserves_vector_table() {
+        let mut data = [0xAAu8; Ivt::LEN + 4];
+        let ivt = Ivt {
+            image_len: 1,
+            image_type: 2,
+            header_offset: 3,
+            load_addr: 4,
+        };
+        ivt.write(&mut data).unwrap();
+
+        assert_eq!(Ivt::parse(&data), Ok(ivt));
+        assert_eq!(data[..0x20], [0xAA; 0x20]);
+        assert_eq!(data[0x2C..0x34], [0xAA; 8]);
+        assert_eq!(data[0x38..], [0xAA; 0xC]);
+    }
+
+    #[test]
+    fn ivt_too_small() {
+        let data = [0u8; Ivt::LEN];
+        let ivt = Ivt::parse(&data).unwrap();
+
+        assert_eq!(Ivt::parse(&data[..Ivt::LEN - 1]), Err(BufferTooSmall));
+        assert_eq!(ivt.write(&mut [0u8; Ivt::LEN - 1]), Err(BufferTooSmall));
+    }
+
+    #[test]
+    fn image_type() {
+        assert_eq!(ImageType::new(ImageKind::XipPlainSigned).as_u32(), 0x0004);
+
+        let image_type = ImageType {
+            key_store_included: true,
+            tz_m_image_type: TrustZone::Disabled,
+            tz_m_preset: TrustZonePreset::Included,
+            enable_hw_user_mode_keys: true,
+            image_kind: ImageKind::EncryptedSigned,
+        };
+        assert_eq!(image_type.as_u32(), 0xF003);
+        assert_eq!(ImageType::from_u32(0xF003), Some(image_type));
+        assert_eq!(
+            ImageType::from_u32(0x0004),
+            Some(ImageType::new(ImageKind::XipPlainSigned))
+        );
+        assert_eq!(ImageType::from_u32(0x0006), None);
+        assert!(image_type.image_kind.has_hmac());
+        assert_eq!(ImageKind::from_u8(6), None);
+    }
+}
@@ -0,0 +1,41 @@
+#![no_std]
+//! Layout of the NXP Master Boot Image (MBI) as consumed by the i.MX RT600 ROM, see UM11147.
+//!
+//! Shared between the bootloader and the host tooling such that both agree on the offset of every field.
+//! All fields are stored little endian.
+
+#[cfg(test)]
+extern crate std;
+
+mod binding;
+mod cert_block;
+mod certificate;
+mod ivt;
+mod trailer;
+
+pub use binding::SlotBinding;
+pub use cert_block::CertBlockHeader;
+pub use certificate::{CertificateError, Certificates, RsaPublicKey};
+pub use ivt::{ImageKind, ImageType, Ivt, TrustZone, TrustZonePreset};
+pub use trailer::{ImageRole, SubregionDigest, Tag, Trailer, TrailerError, TrailerWriter};
+
+/// The buffer is too small to contain the structure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct BufferTooSmall;
+
+fn read_u16(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+}
+
+fn write_u16(data: &mut [u8], offset: usize, value: u16) {
+    data[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+fn write_u32(data: &mut [u8], offset: usize, value: u32) {
+    data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
@@ -0,0 +1,413 @@
+use crate::{read_u32, write_u32};
+
+/// Tag of an entry in the metadata [Trailer].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum Tag {
+    /// Human readable version of the image, as UTF-8.
+    Version = 1,
+    /// Product the image is built for, as 32-bit integer.
+    ProductId = 2,
+    /// Hash of the git commit the image is built from, as hexadecimal UTF-8.
+    GitHash = 3,
+    /// Time the image was built at, as 64-bit integer of seconds since the Unix epoch.
+    BuildTime = 4,
+    /// Digest of a blob at a fixed offset in the slot beyond the image, as [SubregionDigest].
+    ///
+    /// May occur multiple times, once for every covered subregion.
+    SubregionDigest = 5,
+    /// Role of the image in the boot chain, as single byte [ImageRole].
+    ///
+    /// Images without this tag are applications.
+    Role = 6,
+    /// Whether the image awaits activation by the application before the bootloader boots it as new target, as single
+    /// byte: [Trailer::STAGED] until activated, [Trailer::ACTIVATED] after.
+    ///
+    /// As activating only clears bits, the application activates the image in place without erasing the slot.
+    Staged = 7,
+    /// SHA-256 digest of the image itself, over the image length from its header, as 32 bytes.
+    ///
+    /// Allows checking the integrity of the image without authenticating its signature, for example on a device in
+    /// development mode. Being part of the trailer, the digest does not prove where the image comes from.
+    ImageDigest = 8,
+}
+
+/// Role of an image in the boot chain, see [Tag::Role].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum ImageRole {
+    /// Image that runs the product, the end of the chain.
+    Application = 0,
+    /// Secondary loader that boots an image of its own slots in turn.
+    Loader = 1,
+}
+
+impl ImageRole {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        Some(match value {
+            0 => Self::Application,
+            1 => Self::Loader,
+            _ => return None,
+        })
+    }
+}
+
+/// SHA-256 digest of a blob placed at a fixed offset in the slot, next to the image itself.
+///
+/// Encoded as the offset from the start of the slot and the length of the blob, both as 32-bit integer, followed by the digest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SubregionDigest {
+    pub offset: u32,
+    pub len: u32,
+    pub sha256: [u8; 32],
+}
+
+impl SubregionDigest {
+    /// Length of the encoded digest.
+    pub const LEN: usize = 40;
+
+    /// Decode from the value of a [Tag::SubregionDigest] entry.
+    pub fn parse(value: &[u8]) -> Option<Self> {
+        if value.len() != Self::LEN {
+            return None;
+        }
+        Some(Self {
+            offset: read_u32(value, 0),
+            len: read_u32(value, 4),
+            sha256: value[8..].try_into().ok()?,
+        })
+    }
+
+    /// Encode as value of a [Tag::SubregionDigest] entry.
+    pub fn to_bytes(&self) -> [u8; Self::LEN] {
+        let mut buf = [0u8; Self::LEN];
+        write_u32(&mut buf, 0, self.offset);
+        write_u32(&mut buf, 4, self.len);
+        buf[8..].copy_from_slice(&self.sha256);
+        buf
+    }
+}
+
+/// Failure to parse or write a metadata [Trailer].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TrailerError {
+    /// The data does not start with [Trailer::MAGIC], typically because the image has no trailer.
+    Missing,
+    /// The entries exceed the data.
+    Truncated,
+    /// A value is longer than the 255 bytes an entry can hold.
+    ValueTooLong,
+}
+
+/// Optional metadata following the signature of a signed image, describing the identity of the firmware.
+///
+/// Starts at the image length from the header, rounded up to a multiple of 4 bytes.
+/// Layout: [Trailer::MAGIC], the length of the entries as 32-bit integer, and the entries.
+/// Each entry consists of its [Tag], the length of its value and the value itself.
+/// Unknown tags are skipped, such that tags can be added later on.
+///
+/// The trailer is not covered by the signature, and should thus only be used for informational purposes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Trailer<'a> {
+    entries: &'a [u8],
+}
+
+impl<'a> Trailer<'a> {
+    /// Magic at the start of the trailer.
+    pub const MAGIC: [u8; 4] = *b"MDTA";
+    /// Length of the magic and the length of the entries.
+    pub const HEADER_LEN: usize = 8;
+    /// Value of [Tag::Staged] of an image that awaits activation.
+    pub const STAGED: u8 = 0xff;
+    /// Value of [Tag::Staged] of an image that has been activated.
+    pub const ACTIVATED: u8 = 0x00;
+
+    /// Offset of the trailer from the start of an image of `image_len` bytes.
+    pub const fn offset(image_len: usize) -> usize {
+        image_len.next_multiple_of(4)
+    }
+
+    /// Length of the entries according to the header at the start of `data`.
+    ///
+    /// Allows reading only the header first, before reading the entries.
+    pub fn entries_len(data: &[u8]) -> Result<usize, TrailerError> {
+        if data.len() < Self::HEADER_LEN {
+            return Err(TrailerError::Truncated);
+        }
+        if data[..4] != Self::MAGIC {
+            return Err(TrailerError::Missing);
+        }
+        Ok(read_u32(data, 4) as usize)
+    }
+
+    /// Parse the trailer from the start of `data`, ignoring any trailing bytes.
+    pub fn parse(data: &'a [u8]) -> Result<Self, TrailerError> {
+        let entries_len = Self::entries_len(data)?;
+        let entries = data[Self::HEADER_LEN..]
+            .get(..entries_len)
+            .ok_or(TrailerError::Truncated)?;
+
+        let trailer = Self { entries };
+        if trailer.iter().any(|entry| entry.is_err()) {
+            return Err(TrailerError::Truncated);
+        }
+        Ok(trailer)
+    }
+
+    /// Total length of the trailer, including its header.
+    pub fn total_len(&self) -> usize {
+        Self::HEADER_LEN + self.entries.len()
+    }
+
+    /// Iterate over all entries as raw tag and value, including those with unknown tags.
+    pub fn iter(&self) -> impl Iterator<Item = Result<(u8, &'a [u8]), TrailerError>> {
+        let mut rest = self.entries;
+        core::iter::from_fn(move || {
+            if rest.is_empty() {
+                return None;
+            }
+            let Some((&[tag, len], tail)) = rest.split_first_chunk::<2>() else {
+                rest = &[];
+                return Some(Err(TrailerError::Truncated));
+            };
+            let Some((value, tail)) = tail.split_at_checked(len as usize) else {
+                rest = &[];
+                return Some(Err(TrailerError::Truncated));
+            };
+            rest = tail;
+            Some(Ok((tag, value)))
+        })
+    }
+
+    /// Value of the first entry with `tag`, if any.
+    pub fn get(&self, tag: Tag) -> Option<&'a [u8]> {
+        self.iter()
+            .filter_map(Result::ok)
+            .find(|(entry_tag, _)| *entry_tag == tag as u8)
+            .map(|(_, value)| value)
+    }
+
+    /// Offset of the value of the first entry with `tag` from the start of the trailer, if any.
+    pub fn value_offset(&self, tag: Tag) -> Option<usize> {
+        let value = self.get(tag)?;
+        Some(Self::HEADER_LEN + (value.as_ptr() as usize - self.entries.as_ptr() as usize))
+    }
+
+    /// See [Tag::Version].
+    pub fn version(&self) -> Option<&'a str> {
+        core::str::from_utf8(self.get(Tag::Version)?).ok()
+    }
+
+    /// See [Tag::ProductId].
+    pub fn product_id(&self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.get(Tag::ProductId)?.try_into().ok()?))
+    }
+
+    /// See [Tag::GitHash].
+    pub fn git_hash(&self) -> Option<&'a str> {
+        core::str::from_utf8(self.get(Tag::GitHash)?).ok()
+    }
+
+    /// See [Tag::BuildTime].
+    pub fn build_time(&self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.get(Tag::BuildTime)?.try_into().ok()?))
+    }
+
+    /// See [Tag::Role], [None] for a missing or unknown role.
+    pub fn role(&self) -> Option<ImageRole> {
+        match self.get(Tag::Role)? {
+            [role] => ImageRole::from_u8(*role),
+            _ => None,
+        }
+    }
+
+    /// See [Tag::Staged], `false` for images that were never staged or have been activated.
+    pub fn staged(&self) -> bool {
+        self.get(Tag::Staged) == Some(&[Self::STAGED])
+    }
+
+    /// See [Tag::ImageDigest], [None] if missing or malformed.
+    pub fn image_digest(&self) -> Option<[u8; 32]> {
+        self.get(Tag::ImageDigest)?.try_into().ok()
+    }
+
+    /// All entries with [Tag::SubregionDigest], skipping malformed ones.
+    pub fn subregion_digests(&self) -> impl Iterator<Item = SubregionDigest> + 'a {
+        self.iter()
+            .filter_map(Result::ok)
+            .filter(|(tag, _)| *tag == Tag::SubregionDigest as u8)
+            .filter_map(|(_, value)| SubregionDigest::parse(value))
+    }
+}
+
+/// Writer of a metadata [Trailer] into a buffer.
+pub struct TrailerWriter<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> TrailerWriter<'a> {
+    /// Start a trailer at the start of `buf`.
+    pub fn new(buf: &'a mut [u8]) -> Result<Self, TrailerError> {
+        if buf.len() < Trailer::HEADER_LEN {
+            return Err(TrailerError::Truncated);
+        }
+        Ok(Self {
+            buf,
+            len: Trailer::HEADER_LEN,
+        })
+    }
+
+    /// Append an entry.
+    pub fn push(&mut self, tag: Tag, value: &[u8]) -> Result<(), TrailerError> {
+        let len = u8::try_from(value.len()).map_err(|_| TrailerError::ValueTooLong)?;
+        let entry = self
+            .buf
+            .get_mut(self.len..self.len + 2 + value.len())
+            .ok_or(TrailerError::Truncated)?;
+        entry[0] = tag as u8;
+        entry[1] = len;
+        entry[2..].copy_from_slice(value);
+        self.len += entry.len();
+        Ok(())
+    }
+
+    /// Write the header, yielding the total length of the trailer.
+    pub fn finish(self) -> usize {
+        self.buf[..4].copy_from_slice(&Trailer::MAGIC);
+        write_u32(self.buf, 4, (self.len - Trailer::HEADER_LEN) as u32);
+        self.len
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trailer_roundtrip() {
+        let mut buf = [0xffu8; 64];
+        let mut writer = TrailerWriter::new(&mut buf).unwrap();
+        writer.push(Tag::Version, b"1.2.3").unwrap();
+        writer.push(Tag::ProductId, &0x1234u32.to_le_bytes()).unwrap();
+        writer.push(Tag::BuildTime, &1_700_000_000u64.to_le_bytes()).unwrap();
+        writer.push(Tag::Role, &[ImageRole::Loader as u8]).unwrap();
+        let len = writer.finish();
+        assert_eq!(len, Trailer::HEADER_LEN + (2 + 5) + (2 + 4) + (2 + 8) + (2 + 1));
+
+        let trailer = Trailer::parse(&buf).unwrap();
+        assert_eq!(trailer.total_len(), len);
+        assert_eq!(trailer.version(), Some("1.2.3"));
+        assert_eq!(trailer.product_id(), Some(0x1234));
+        assert_eq!(trailer.git_hash(), None);
+        assert_eq!(trailer.build_time(), Some(1_700_000_000));
+        assert_eq!(trailer.role(), Some(ImageRole::Loader));
+        assert_eq!(trailer.image_digest(), None);
+    }
+
+    #[test]
+    fn trailer_image_digest() {
+        let mut buf = [0xffu8; 64];
+        let mut writer = TrailerWriter::new(&mut buf).unwrap();
+        writer.push(Tag::ImageDigest, &[0x5a; 32]).unwrap();
+        writer.push(Tag::ImageDigest, &[0xa5; 32]).unwrap_err();
+        writer.finish();
+        assert_eq!(Trailer::parse(&buf).unwrap().image_digest(), Some([0x5a; 32]));
+
+        // A digest of the wrong length is not a digest.
+        let mut buf = [0xffu8; 64];
+        let mut writer = TrailerWriter::new(&mut buf).unwrap();
+        writer.push(Tag::ImageDigest, &[0x5a; 31]).unwrap();
+        writer.finish();
+        assert_eq!(Trailer::parse(&buf).unwrap().image_digest(), None);
+    }
+
+    #[test]
+    fn trailer_staged() {
+        let mut buf = [0xffu8; 32];
+        let mut writer = TrailerWriter::new(&mut buf).unwrap();
+        writer.push(Tag::Version, b"1.2.3").unwrap();
+        writer.push(Tag::Staged, &[Trailer::STAGED]).unwrap();
+        writer.finish();
+
+        let trailer = Trailer::parse(&buf).unwrap();
+        assert!(trailer.staged());
+        let offset = trailer.value_offset(Tag::Staged).unwrap();
+        assert_eq!(offset, Trailer::HEADER_LEN + (2 + 5) + 2);
+        assert_eq!(trailer.value_offset(Tag::ProductId), None);
+
+        // Activating clears the value in place.
+        buf[offset] = Trailer::ACTIVATED;
+        assert!(!Trailer::parse(&buf).unwrap().staged());
+    }
+
+    #[test]
+    fn trailer_unknown_tag() {
+        let mut buf = [0u8; 32];
+        buf[..4].copy_from_slice(&Trailer::MAGIC);
+        buf[4..8].copy_from_slice(&8u32.to_le_bytes());
+        buf[8..16].copy_from_slice(&[0x42, 2, 0xaa, 0xbb, Tag::GitHash as u8, 2, b'a', b'b']);
+
+        let trailer = Trailer::parse(&buf).unwrap();
+        assert_eq!(trailer.iter().count(), 2);
+        assert_eq!(trailer.git_hash(), Some("ab"));
+    }
+
+    #[test]
+    fn trailer_subregion_digests() {
+        let radio = SubregionDigest {
+            offset: 0xc0000,
+            len: 0x1234,
+            sha256: [0xab; 32],
+        };
+        let config = SubregionDigest {
+            offset: 0xe0000,
+            len: 0x100,
+            sha256: [0x12; 32],
+        };
+
+        let mut buf = [0xffu8; 128];
+        let mut writer = TrailerWriter::new(&mut buf).unwrap();
+        writer.push(Tag::SubregionDigest, &radio.to_bytes()).unwrap();
+        writer.push(Tag::Version, b"1.0").unwrap();
+        writer.push(Tag::SubregionDigest, &config.to_bytes()).unwrap();
+        // Malformed digests are skipped.
+        writer.push(Tag::SubregionDigest, &[0; 8]).unwrap();
+        writer.finish();
+
+        let trailer = Trailer::parse(&buf).unwrap();
+        let mut digests = trailer.subregion_digests();
+        assert_eq!(digests.next(), Some(radio));
+        assert_eq!(digests.next(), Some(config));
+        assert_eq!(digests.next(), None);
+        assert_eq!(trailer.version(), Some("1.0"));
+    }
+
+    #[test]
+    fn trailer_malformed() {
+        // Erased flash following an image without trailer.
+        assert_eq!(Trailer::parse(&[0xff; 16]), Err(TrailerError::Missing));
+        assert_eq!(Trailer::parse(&Trailer::MAGIC), Err(TrailerError::Truncated));
+
+        // Entries exceed the data.
+        let mut buf = [0u8; 12];
+        buf[..4].copy_from_slice(&Trailer::MAGIC);
+        buf[4..8].copy_from_slice(&8u32.to_le_bytes());
+        assert_eq!(Trailer::parse(&buf), Err(TrailerError::Truncated));
+
+        // Value exceeds the entries.
+        buf[4..8].copy_from_slice(&4u32.to_le_bytes());
+        buf[8..12].copy_from_slice(&[Tag::Version as u8, 3, b'1', b'.']);
+        assert_eq!(Trailer::parse(&buf), Err(TrailerError::Truncated));
+
+        let mut buf = [0u8; 300];
+        let mut writer = TrailerWriter::new(&mut buf).unwrap();
+        assert_eq!(writer.push(Tag::Version, &[b'1'; 256]), Err(TrailerError::ValueTooLong));
+        let mut writer = TrailerWriter::new(&mut buf[..16]).unwrap();
+        assert_eq!(writer.push(Tag::Version, &[b'1'; 7]), Err(TrailerError::Truncated));
+    }
+}